@@ -0,0 +1,107 @@
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+
+use crate::error::AIInfraError;
+
+// Layout of the ed25519 program's instruction data: a signature count and a
+// padding byte, then one set of offsets per signature into the data
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_LEN: usize = 14;
+// Instruction index the offsets use to point into the ed25519 instruction
+// itself
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+// Message an agent's attestation key signs to vouch for a result:
+// task || result_hash
+pub fn attestation_message(task: &Pubkey, result_hash: &[u8; 32]) -> [u8; 64] {
+    let mut message = [0; 64];
+    message[..32].copy_from_slice(task.as_ref());
+    message[32..].copy_from_slice(result_hash);
+    message
+}
+
+// Whether `data`, an ed25519 program instruction, verifies a signature by
+// `key` over `message`. Only signatures whose key and message live in the
+// instruction's own data count, as those are the bytes read here.
+pub fn signs(data: &[u8], key: &Pubkey, message: &[u8]) -> bool {
+    let count = match data.first() {
+        Some(&count) => count as usize,
+        None => return false,
+    };
+    (0..count).any(|index| {
+        let start = SIGNATURE_OFFSETS_START + index * SIGNATURE_OFFSETS_LEN;
+        let offsets = match data.get(start..start + SIGNATURE_OFFSETS_LEN) {
+            Some(offsets) => offsets,
+            None => return false,
+        };
+        let field = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]);
+        let (signature_index, key_offset, key_index) = (field(2), field(4), field(6));
+        let (message_offset, message_size, message_index) = (field(8), field(10), field(12));
+        if [signature_index, key_index, message_index] != [CURRENT_INSTRUCTION; 3] {
+            return false;
+        }
+
+        let key_offset = key_offset as usize;
+        let message_offset = message_offset as usize;
+        data.get(key_offset..key_offset + 32) == Some(key.as_ref())
+            && data.get(message_offset..message_offset + message_size as usize) == Some(message)
+    })
+}
+
+// Fails unless an instruction before the current one in the transaction is
+// an ed25519 program instruction verifying `key`'s signature over `message`.
+// The runtime has already rejected the transaction if that signature is bad.
+pub fn verify_attestation(
+    instructions_sysvar: &AccountInfo,
+    key: &Pubkey,
+    message: &[u8],
+) -> ProgramResult {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    for index in 0..current {
+        let instruction = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+        if instruction.program_id == ed25519_program::id() && signs(&instruction.data, key, message)
+        {
+            return Ok(());
+        }
+    }
+    Err(AIInfraError::InvalidAttestation.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        ed25519_instruction::new_ed25519_instruction,
+        signature::{Keypair, Signer},
+    };
+
+    use super::*;
+
+    #[test]
+    fn attestation_needs_the_signed_key_and_message() {
+        let signer = Keypair::new();
+        let key = signer.pubkey();
+        let message = attestation_message(&Pubkey::new_unique(), &[7; 32]);
+        let keypair = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+        let data = new_ed25519_instruction(&keypair, &message).data;
+
+        assert!(signs(&data, &key, &message));
+        assert!(!signs(&data, &Pubkey::new_unique(), &message));
+        assert!(!signs(&data, &key, &message[..32]));
+        assert!(!signs(&data, &key, &attestation_message(&key, &[7; 32])));
+
+        // Signatures over bytes in other instructions don't count
+        let mut elsewhere = data.clone();
+        elsewhere[6..8].copy_from_slice(&0u16.to_le_bytes());
+        assert!(!signs(&elsewhere, &key, &message));
+
+        // Nor does data too short to hold what it claims
+        assert!(!signs(&[], &key, &message));
+        assert!(!signs(&data[..16], &key, &message));
+        assert!(!signs(&data[..data.len() - 1], &key, &message));
+    }
+}
@@ -0,0 +1,21 @@
+// Writes the program's IDL, which shank reads from the annotations on
+// AIInfraInstruction and the account structs, for generated clients. Built
+// with the idl feature:
+// cargo run --features idl --bin idl -- [path, idl.json by default]
+use std::{env, fs, path::Path, process};
+
+use sonic_ai_infra::idl::{generate_json, IdlError};
+
+fn run() -> Result<(), IdlError> {
+    let path = env::args().nth(1).unwrap_or_else(|| "idl.json".to_string());
+    fs::write(&path, generate_json(Path::new(env!("CARGO_MANIFEST_DIR")))?)?;
+    println!("Wrote {path}");
+    Ok(())
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {error}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,330 @@
+// Command line tool for operating an agent: registering it, managing its
+// credits and running its tasks. Built with the cli feature:
+// cargo run --features cli --bin sonic-agent -- --help
+use std::{env, error::Error, process, str::FromStr};
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use sonic_ai_infra::{
+    client::{self, ClientError},
+    find_agent_address, find_config_address, find_requester_address, find_task_address,
+    instruction, ComputeRequirements, Config, Requester,
+};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+const DEFAULT_URL: &str = "https://api.testnet.sonic.game";
+const DEFAULT_PROGRAM_ID: &str = "BCp9BaReGXHGPfcYFm7YdtKdBC2x9i3gvskgtiEKXJvk";
+const DEFAULT_KEYPAIR: &str = "~/.config/solana/id.json";
+
+fn pubkey_arg(name: &'static str) -> Arg<'static> {
+    Arg::new(name).value_name("PUBKEY").value_parser(Pubkey::from_str)
+}
+
+fn lamports_arg(name: &'static str) -> Arg<'static> {
+    Arg::new(name).value_name("LAMPORTS").value_parser(value_parser!(u64))
+}
+
+fn nonce_arg() -> Arg<'static> {
+    Arg::new("expected-nonce")
+        .long("expected-nonce")
+        .value_name("N")
+        .help("Fails unless the agent's operation nonce is still N, so a retry applies once")
+        .value_parser(value_parser!(u64))
+}
+
+fn requirement_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .value_name("N")
+        .help(help)
+        .value_parser(value_parser!(u32))
+        .default_value("0")
+}
+
+fn cli() -> Command<'static> {
+    Command::new("sonic-agent")
+        .about("Operates an agent of the Sonic AI infrastructure program")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .short('u')
+                .value_name("URL")
+                .env("SONIC_RPC_URL")
+                .global(true)
+                .default_value(DEFAULT_URL)
+                .help("RPC endpoint to send transactions to"),
+        )
+        .arg(
+            Arg::new("keypair")
+                .long("keypair")
+                .short('k')
+                .value_name("PATH")
+                .env("SONIC_KEYPAIR")
+                .global(true)
+                .default_value(DEFAULT_KEYPAIR)
+                .help("Keypair file of the agent's owner, who signs and pays"),
+        )
+        .arg(
+            pubkey_arg("program-id")
+                .long("program-id")
+                .env("SONIC_PROGRAM_ID")
+                .global(true)
+                .default_value(DEFAULT_PROGRAM_ID)
+                .help("Address of the deployed program"),
+        )
+        .subcommand(Command::new("register").about("Registers the keypair's agent"))
+        .subcommand(
+            Command::new("deposit")
+                .about("Deposits lamports into the agent's credit vault")
+                .arg(lamports_arg("amount").required(true))
+                .arg(nonce_arg()),
+        )
+        .subcommand(
+            Command::new("withdraw")
+                .about("Withdraws lamports from the agent's credit vault")
+                .arg(lamports_arg("amount").required(true))
+                .arg(nonce_arg()),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Activates or deactivates the agent")
+                .arg(Arg::new("state").required(true).value_parser(["on", "off"])),
+        )
+        .subcommand(
+            Command::new("create-task")
+                .about("Creates a task, paid by the keypair, for an agent or as an open listing")
+                .arg(
+                    pubkey_arg("agent")
+                        .long("agent")
+                        .help("Agent to run the task; lists an open task when omitted"),
+                )
+                .arg(requirement_arg("cpu", "CPU units the task needs"))
+                .arg(requirement_arg("mem", "Memory the task needs, in MB"))
+                .arg(requirement_arg("storage", "Storage the task needs, in MB"))
+                .arg(requirement_arg("time", "Longest the task may run, in seconds"))
+                .arg(
+                    lamports_arg("payment")
+                        .long("payment")
+                        .required(true)
+                        .help("Lamports escrowed to pay for the task"),
+                ),
+        )
+        .subcommand(
+            Command::new("start-task")
+                .about("Starts one of the agent's pending tasks")
+                .arg(pubkey_arg("task").required(true)),
+        )
+        .subcommand(
+            Command::new("complete-task")
+                .about("Completes a running task with the hash of its result")
+                .arg(pubkey_arg("task").required(true))
+                .arg(
+                    Arg::new("result-hash")
+                        .required(true)
+                        .value_name("HEX")
+                        .value_parser(parse_hash)
+                        .help("32-byte result hash as 64 hex digits"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Prints a summary of any of the program's accounts")
+                .arg(pubkey_arg("address").required(true)),
+        )
+}
+
+fn parse_hash(value: &str) -> Result<[u8; 32], String> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    if digits.len() != 64 || !digits.is_ascii() {
+        return Err("expected 64 hex digits".to_string());
+    }
+    let mut hash = [0u8; 32];
+    for (byte, pair) in hash.iter_mut().zip(digits.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).unwrap();
+        *byte = u8::from_str_radix(pair, 16).map_err(|error| error.to_string())?;
+    }
+    Ok(hash)
+}
+
+fn read_keypair(path: &str) -> CliResult<Keypair> {
+    let path = match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{home}/{rest}"),
+        _ => path.to_string(),
+    };
+    read_keypair_file(&path).map_err(|error| format!("reading keypair {path}: {error}").into())
+}
+
+// Signs `instructions` with `payer` and prints the confirmed signature
+async fn send(rpc: &RpcClient, payer: &Keypair, instructions: &[Instruction]) -> CliResult<()> {
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    println!("{}", rpc.send_and_confirm_transaction(&transaction).await?);
+    Ok(())
+}
+
+async fn run(matches: ArgMatches) -> CliResult<()> {
+    let url = matches.get_one::<String>("url").unwrap();
+    let program_id = *matches.get_one::<Pubkey>("program-id").unwrap();
+    let rpc = RpcClient::new_with_commitment(url.clone(), CommitmentConfig::confirmed());
+    let (command, args) = matches.subcommand().unwrap();
+
+    if command == "show" {
+        let address = args.get_one::<Pubkey>("address").unwrap();
+        print!("{}", client::get_decoded_account(&rpc, &program_id, address).await?);
+        return Ok(());
+    }
+
+    let signer = read_keypair(matches.get_one::<String>("keypair").unwrap())?;
+    let owner = signer.pubkey();
+    let (agent, _) = find_agent_address(&owner, &program_id);
+    let instruction = match command {
+        "register" => instruction::register_agent(&program_id, &agent, &owner),
+        "deposit" => {
+            let amount = *args.get_one::<u64>("amount").unwrap();
+            let expected_nonce = args.get_one::<u64>("expected-nonce").copied();
+            instruction::deposit_credits(&program_id, &agent, &owner, amount, expected_nonce)
+        }
+        "withdraw" => {
+            let amount = *args.get_one::<u64>("amount").unwrap();
+            let expected_nonce = args.get_one::<u64>("expected-nonce").copied();
+            instruction::withdraw_credits(&program_id, &agent, &owner, amount, expected_nonce)
+        }
+        "status" => {
+            let is_active = args.get_one::<String>("state").unwrap() == "on";
+            instruction::update_agent_status(&program_id, &agent, &owner, is_active)
+        }
+        "create-task" => {
+            let agent = args.get_one::<Pubkey>("agent").copied().unwrap_or_default();
+            // The task's address is numbered by the next value of its
+            // agent's counter, or the config's for open listings
+            let index = if agent == Pubkey::default() {
+                let (config, _) = find_config_address(&program_id);
+                client::get_account::<Config>(&rpc, &program_id, &config)
+                    .await?
+                    .open_tasks_created
+            } else {
+                client::get_agent(&rpc, &program_id, &agent).await?.tasks_created
+            };
+            let (task, _) = find_task_address(&agent, index, &program_id);
+            let requirement = |name| *args.get_one::<u32>(name).unwrap();
+            // Single-step, so complete-task can finish it without
+            // commit-reveal
+            let requirements = ComputeRequirements {
+                cpu_units: requirement("cpu"),
+                memory_mb: requirement("mem"),
+                storage_mb: requirement("storage"),
+                max_time_seconds: requirement("time"),
+                single_step: true,
+                ..ComputeRequirements::default()
+            };
+            let payment = *args.get_one::<u64>("payment").unwrap();
+            println!("Task {task}");
+            instruction::create_task(
+                &program_id,
+                &task,
+                &agent,
+                index,
+                &owner,
+                requirements,
+                payment,
+            )
+        }
+        "start-task" => {
+            let task = args.get_one::<Pubkey>("task").unwrap();
+            let agent = client::get_task(&rpc, &program_id, task).await?.agent;
+            instruction::start_task(&program_id, task, &agent, &owner)
+        }
+        "complete-task" => {
+            let task_key = args.get_one::<Pubkey>("task").unwrap();
+            let result_hash = *args.get_one::<[u8; 32]>("result-hash").unwrap();
+            let task = client::get_task(&rpc, &program_id, task_key).await?;
+            let (config, _) = find_config_address(&program_id);
+            let config = client::get_account::<Config>(&rpc, &program_id, &config).await?;
+            let mut instruction = instruction::complete_task(
+                &program_id,
+                task_key,
+                &task.agent,
+                &owner,
+                &task.requester,
+                &config.treasury,
+                result_hash,
+            );
+            // Settling the payment may need the requester's referrer, the
+            // agent's credit vault and the insurance pool, depending on the
+            // config
+            if config.referral_bps > 0 {
+                let (requester, _) = find_requester_address(&task.requester, &program_id);
+                let referrer =
+                    match client::get_account::<Requester>(&rpc, &program_id, &requester).await {
+                        Ok(requester) => requester.referrer,
+                        Err(ClientError::AccountMissing(_)) => None,
+                        Err(error) => return Err(error.into()),
+                    };
+                instruction = instruction::with_referral(
+                    instruction,
+                    &program_id,
+                    &task.requester,
+                    referrer.as_ref(),
+                );
+            }
+            if config.payment_hold_seconds > 0 {
+                instruction = instruction::with_payment_hold(instruction, &program_id, &task.agent);
+            }
+            if config.insurance_bps > 0 {
+                instruction = instruction::with_insurance_pool(instruction, &program_id);
+            }
+            instruction
+        }
+        _ => unreachable!("clap rejects unknown subcommands"),
+    };
+    send(&rpc, &signer, &[instruction]).await
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = run(cli().get_matches()).await {
+        eprintln!("error: {error}");
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse() {
+        cli().debug_assert();
+        let matches = cli()
+            .try_get_matches_from(["sonic-agent", "create-task", "--cpu", "4", "--payment", "500"])
+            .unwrap();
+        let (_, args) = matches.subcommand().unwrap();
+        assert_eq!(args.get_one::<u32>("cpu"), Some(&4));
+        assert_eq!(args.get_one::<u32>("mem"), Some(&0));
+        assert_eq!(args.get_one::<Pubkey>("agent"), None);
+        assert!(cli().try_get_matches_from(["sonic-agent", "status", "maybe"]).is_err());
+    }
+
+    #[test]
+    fn result_hash_parses_from_hex() {
+        let hex = "00ff".repeat(16);
+        let hash = parse_hash(&hex).unwrap();
+        assert_eq!(hash[..2], [0x00, 0xff]);
+        assert_eq!(parse_hash(&format!("0x{hex}")), Ok(hash));
+        assert!(parse_hash("00ff").is_err());
+        assert!(parse_hash(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,543 @@
+// Off-chain helpers that fetch and decode the program's accounts, built with
+// the client feature. They read through AccountSource, which the nonblocking
+// RPC client implements, so tests can run the same calls against BanksClient.
+use std::future::Future;
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    client_error::ClientError as RpcError,
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_sdk::account::Account;
+use thiserror::Error;
+
+use crate::{
+    inspect::{decode_account, DecodedAccount},
+    pda::{find_metadata_address, find_registry_address, find_task_page_address},
+    state::{
+        AIAgent, AgentMetadata, Bid, ComputeTask, ProgramAccount, Registry, TaskPage, TaskStatus,
+        ACCOUNT_TYPE_OFFSET, CURRENT_VERSION, TASKS_PER_PAGE, TASK_TAGS, VERSION_OFFSET,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Account {0} does not exist")]
+    AccountMissing(Pubkey),
+    #[error("Account {address} is owned by {owner}, not the program")]
+    WrongOwner { address: Pubkey, owner: Pubkey },
+    #[error("Account {address} failed to decode: {error}")]
+    Decode { address: Pubkey, error: ProgramError },
+    // Boxed, as solana-client's error is several hundred bytes
+    #[error(transparent)]
+    Rpc(Box<RpcError>),
+}
+
+impl From<RpcError> for ClientError {
+    fn from(error: RpcError) -> Self {
+        ClientError::Rpc(Box::new(error))
+    }
+}
+
+// Where the helpers read accounts from. None means there is no account at
+// `address`.
+pub trait AccountSource {
+    fn get_account(
+        &self,
+        address: &Pubkey,
+    ) -> impl Future<Output = Result<Option<Account>, ClientError>>;
+}
+
+impl AccountSource for RpcClient {
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>, ClientError> {
+        Ok(self.get_account_with_commitment(address, self.commitment()).await?.value)
+    }
+}
+
+// Decodes `account` as a `T`, checking that the program owns it
+fn decode<T: ProgramAccount>(
+    program_id: &Pubkey,
+    address: &Pubkey,
+    account: &Account,
+) -> Result<T, ClientError> {
+    if account.owner != *program_id {
+        return Err(ClientError::WrongOwner { address: *address, owner: account.owner });
+    }
+    T::unpack(&account.data).map_err(|error| ClientError::Decode { address: *address, error })
+}
+
+// Fetches and decodes the `T` at `address`
+pub async fn get_account<T: ProgramAccount>(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    address: &Pubkey,
+) -> Result<T, ClientError> {
+    match source.get_account(address).await? {
+        Some(account) => decode(program_id, address, &account),
+        None => Err(ClientError::AccountMissing(*address)),
+    }
+}
+
+// Fetches the account at `address`, whichever of the program's types it is
+pub async fn get_decoded_account(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    address: &Pubkey,
+) -> Result<DecodedAccount, ClientError> {
+    let account = source.get_account(address).await?;
+    let account = account.ok_or(ClientError::AccountMissing(*address))?;
+    if account.owner != *program_id {
+        return Err(ClientError::WrongOwner { address: *address, owner: account.owner });
+    }
+    decode_account(&account.data).map_err(|error| ClientError::Decode { address: *address, error })
+}
+
+pub async fn get_agent(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    agent: &Pubkey,
+) -> Result<AIAgent, ClientError> {
+    get_account(source, program_id, agent).await
+}
+
+pub async fn get_task(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    task: &Pubkey,
+) -> Result<ComputeTask, ClientError> {
+    get_account(source, program_id, task).await
+}
+
+// Fetches the metadata `agent` publishes, or None if it never set any
+pub async fn get_agent_metadata(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    agent: &Pubkey,
+) -> Result<Option<AgentMetadata>, ClientError> {
+    let (address, _) = find_metadata_address(agent, program_id);
+    match source.get_account(&address).await? {
+        Some(account) => Ok(Some(decode(program_id, &address, &account)?)),
+        None => Ok(None),
+    }
+}
+
+// Fetches the program's registry of running totals
+pub async fn get_registry(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+) -> Result<Registry, ClientError> {
+    get_account(source, program_id, &find_registry_address(program_id).0).await
+}
+
+// Lists every task created for `agent`, in creation order, by walking its
+// task index from page 0 until a page is missing or not full. The default
+// pubkey lists open listings.
+pub async fn list_agent_tasks(
+    source: &impl AccountSource,
+    program_id: &Pubkey,
+    agent: &Pubkey,
+) -> Result<Vec<Pubkey>, ClientError> {
+    let mut tasks = vec![];
+    for page in 0.. {
+        let (address, _) = find_task_page_address(agent, page, program_id);
+        let task_page: TaskPage = match source.get_account(&address).await? {
+            Some(account) => decode(program_id, &address, &account)?,
+            None => break,
+        };
+        let full = task_page.tasks.len() as u64 == TASKS_PER_PAGE;
+        tasks.extend(task_page.tasks);
+        if !full {
+            break;
+        }
+    }
+    Ok(tasks)
+}
+
+// Fetches every task in `status` with a single getProgramAccounts call
+pub async fn list_tasks_by_status(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    status: TaskStatus,
+) -> Result<Vec<(Pubkey, ComputeTask)>, ClientError> {
+    list_tasks(rpc, program_id, tasks_with_status(status)).await
+}
+
+// Fetches every task tagged `tag`, with a getProgramAccounts call for each
+// slot the tag could sit in. A task carrying it twice is listed once.
+pub async fn list_tasks_by_tag(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    tag: u8,
+) -> Result<Vec<(Pubkey, ComputeTask)>, ClientError> {
+    let mut tasks: Vec<(Pubkey, ComputeTask)> = vec![];
+    for slot in 0..TASK_TAGS {
+        for (address, task) in list_tasks(rpc, program_id, tasks_with_tag(slot, tag)).await? {
+            if !tasks.iter().any(|(listed, _)| *listed == address) {
+                tasks.push((address, task));
+            }
+        }
+    }
+    Ok(tasks)
+}
+
+// Fetches the tasks that pass `filters`
+async fn list_tasks(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+) -> Result<Vec<(Pubkey, ComputeTask)>, ClientError> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        // Tasks are too large for the default base58 encoding
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    rpc.get_program_accounts_with_config(program_id, config)
+        .await?
+        .iter()
+        // Tasks match at any size, see account_filters
+        .filter(|(_, account)| account.data.len() >= ComputeTask::LEN)
+        .map(|(address, account)| Ok((*address, decode(program_id, address, account)?)))
+        .collect()
+}
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, bytes))
+}
+
+// getProgramAccounts filters matching every `T` in the current layout. Accounts
+// still in an older layout don't match until they are migrated. A size filter
+// can't match a type whose accounts grow, so those match at any size, and
+// callers drop the ones shorter than T::LEN.
+pub fn account_filters<T: ProgramAccount>() -> Vec<RpcFilterType> {
+    let mut filters = vec![
+        memcmp(ACCOUNT_TYPE_OFFSET, &[T::ACCOUNT_TYPE as u8]),
+        memcmp(VERSION_OFFSET, &[CURRENT_VERSION]),
+    ];
+    if T::MAX_LEN == T::LEN {
+        filters.insert(0, RpcFilterType::DataSize(T::LEN as u64));
+    }
+    filters
+}
+
+// Filters for the agents `owner` owns
+pub fn agents_owned_by(owner: &Pubkey) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<AIAgent>();
+    filters.push(memcmp(AIAgent::OWNER_OFFSET, owner.as_ref()));
+    filters
+}
+
+// Filters for the agents accepting tasks
+pub fn active_agents() -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<AIAgent>();
+    filters.push(memcmp(AIAgent::IS_ACTIVE_OFFSET, &[1]));
+    filters
+}
+
+// Filters for the tasks in `status`
+pub fn tasks_with_status(status: TaskStatus) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<ComputeTask>();
+    filters.push(memcmp(ComputeTask::STATUS_OFFSET, &[status as u8]));
+    filters
+}
+
+// Filters for the tasks assigned to `agent`
+pub fn tasks_for_agent(agent: &Pubkey) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<ComputeTask>();
+    filters.push(memcmp(ComputeTask::AGENT_OFFSET, agent.as_ref()));
+    filters
+}
+
+// Filters for the tasks `requester` created
+pub fn tasks_requested_by(requester: &Pubkey) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<ComputeTask>();
+    filters.push(memcmp(ComputeTask::REQUESTER_OFFSET, requester.as_ref()));
+    filters
+}
+
+// Filters for the tasks at `priority`. Schedulers pair it with
+// tasks_with_status to find the pending tasks worth claiming first.
+pub fn tasks_with_priority(priority: u8) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<ComputeTask>();
+    filters.push(memcmp(ComputeTask::PRIORITY_OFFSET, &[priority]));
+    filters
+}
+
+// Filters for the tasks carrying `tag` in position `slot` of their tags
+pub fn tasks_with_tag(slot: usize, tag: u8) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<ComputeTask>();
+    filters.push(memcmp(ComputeTask::TAGS_OFFSET + slot, &[tag]));
+    filters
+}
+
+// Filters for the bids placed on `task`
+pub fn bids_for_task(task: &Pubkey) -> Vec<RpcFilterType> {
+    let mut filters = account_filters::<Bid>();
+    filters.push(memcmp(Bid::TASK_OFFSET, task.as_ref()));
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use serde_json::json;
+    use solana_client::{rpc_client::Mocks, rpc_request::RpcRequest};
+    use std::collections::HashMap;
+
+    use crate::{
+        pda::find_task_address,
+        state::{AccountType, FailureReason},
+    };
+
+    // Accounts held in memory, keyed by address
+    struct Accounts(HashMap<Pubkey, Account>);
+
+    impl AccountSource for Accounts {
+        async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>, ClientError> {
+            Ok(self.0.get(address).cloned())
+        }
+    }
+
+    fn account_with(owner: &Pubkey, data: Vec<u8>) -> Account {
+        Account { lamports: 1_000_000, data, owner: *owner, executable: false, rent_epoch: 0 }
+    }
+
+    // Whether an account holding `data` passes every filter
+    fn passes(filters: &[RpcFilterType], data: &[u8]) -> bool {
+        filters.iter().all(|filter| match filter {
+            RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+            RpcFilterType::Memcmp(compare) => compare.bytes_match(data),
+            RpcFilterType::TokenAccountState => false,
+        })
+    }
+
+    fn registry_data(program_id: &Pubkey) -> Vec<u8> {
+        let registry = Registry {
+            account_type: AccountType::Registry,
+            version: CURRENT_VERSION,
+            total_agents: 3,
+            total_tasks: 5,
+            total_credits_deposited: 7000,
+            bump: find_registry_address(program_id).1,
+        };
+        registry.try_to_vec().unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_accounts_over_rpc() {
+        let program_id = Pubkey::new_unique();
+        let mut mocks = Mocks::new();
+        mocks.insert(
+            RpcRequest::GetAccountInfo,
+            json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "lamports": 1_000_000,
+                    "data": [base64::encode(registry_data(&program_id)), "base64"],
+                    "owner": program_id.to_string(),
+                    "executable": false,
+                    "rentEpoch": 0,
+                },
+            }),
+        );
+        let rpc = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+
+        let registry = get_registry(&rpc, &program_id).await.unwrap();
+        assert_eq!(registry.total_agents, 3);
+        assert_eq!(registry.total_tasks, 5);
+        assert_eq!(registry.total_credits_deposited, 7000);
+    }
+
+    #[tokio::test]
+    async fn fetch_errors_tell_their_causes_apart() {
+        let program_id = Pubkey::new_unique();
+        let (registry, _) = find_registry_address(&program_id);
+        let agent = Pubkey::new_unique();
+        let task = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        let accounts = Accounts(HashMap::from([
+            (registry, account_with(&program_id, registry_data(&program_id))),
+            (agent, account_with(&other_owner, vec![])),
+            // The registry's bytes don't decode as a task
+            (task, account_with(&program_id, registry_data(&program_id))),
+        ]));
+
+        assert_eq!(get_registry(&accounts, &program_id).await.unwrap().total_tasks, 5);
+        assert!(matches!(
+            get_agent(&accounts, &program_id, &Pubkey::new_unique()).await,
+            Err(ClientError::AccountMissing(_))
+        ));
+        assert!(matches!(
+            get_agent(&accounts, &program_id, &agent).await,
+            Err(ClientError::WrongOwner { owner, .. }) if owner == other_owner
+        ));
+        assert!(matches!(
+            get_task(&accounts, &program_id, &task).await,
+            Err(ClientError::Decode { address, .. }) if address == task
+        ));
+        assert!(matches!(
+            get_decoded_account(&accounts, &program_id, &task).await,
+            Ok(DecodedAccount::Registry(registry)) if registry.total_tasks == 5
+        ));
+        assert!(matches!(
+            get_decoded_account(&accounts, &program_id, &agent).await,
+            Err(ClientError::WrongOwner { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_tasks_carry_their_reason() {
+        let program_id = Pubkey::new_unique();
+        let task = Pubkey::new_unique();
+        let failed = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            status: TaskStatus::Failed,
+            failure_code: FailureReason::OutOfMemory.code(),
+            failure_reason: Some(FailureReason::OutOfMemory),
+            ..ComputeTask::default()
+        };
+        let mut data = failed.try_to_vec().unwrap();
+        data.resize(ComputeTask::LEN, 0);
+        let accounts = Accounts(HashMap::from([(task, account_with(&program_id, data))]));
+
+        let fetched = get_task(&accounts, &program_id, &task).await.unwrap();
+        assert_eq!(fetched.failure_reason, Some(FailureReason::OutOfMemory));
+    }
+
+    #[tokio::test]
+    async fn metadata_is_optional() {
+        let program_id = Pubkey::new_unique();
+        let agent = Pubkey::new_unique();
+        let (address, bump) = find_metadata_address(&agent, &program_id);
+        let metadata = AgentMetadata {
+            account_type: AccountType::AgentMetadata,
+            version: CURRENT_VERSION,
+            agent,
+            bump,
+            name: "worker".to_string(),
+            uri: "https://example.com/worker.json".to_string(),
+            endpoint: "grpc://worker.example.com:443".to_string(),
+        };
+        let mut data = metadata.try_to_vec().unwrap();
+        data.resize(AgentMetadata::LEN, 0);
+        let accounts = Accounts(HashMap::from([(address, account_with(&program_id, data))]));
+
+        let fetched = get_agent_metadata(&accounts, &program_id, &agent).await.unwrap();
+        assert_eq!(fetched, Some(metadata));
+        let other = Pubkey::new_unique();
+        assert_eq!(get_agent_metadata(&accounts, &program_id, &other).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn lists_tasks_across_pages() {
+        let program_id = Pubkey::new_unique();
+        let agent = Pubkey::new_unique();
+        let tasks: Vec<Pubkey> = (0..40)
+            .map(|index| find_task_address(&agent, index, &program_id).0)
+            .collect();
+
+        let mut pages = HashMap::new();
+        for (page, chunk) in tasks.chunks(TASKS_PER_PAGE as usize).enumerate() {
+            let (address, bump) = find_task_page_address(&agent, page as u64, &program_id);
+            let task_page = TaskPage {
+                account_type: AccountType::TaskPage,
+                version: CURRENT_VERSION,
+                agent,
+                page: page as u64,
+                bump,
+                tasks: chunk.to_vec(),
+            };
+            pages.insert(address, account_with(&program_id, task_page.try_to_vec().unwrap()));
+        }
+        assert_eq!(pages.len(), 2);
+        let accounts = Accounts(pages);
+
+        assert_eq!(list_agent_tasks(&accounts, &program_id, &agent).await.unwrap(), tasks);
+
+        // An agent without tasks has no pages
+        let listed = list_agent_tasks(&accounts, &program_id, &Pubkey::new_unique()).await;
+        assert!(listed.unwrap().is_empty());
+    }
+
+    #[test]
+    fn filters_select_matching_accounts() {
+        let requester = Pubkey::new_unique();
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: Pubkey::new_unique(),
+            status: TaskStatus::InProgress,
+            requester,
+            ..ComputeTask::default()
+        };
+        let mut data = task.try_to_vec().unwrap();
+        data.resize(ComputeTask::LEN, 0);
+
+        assert!(passes(&tasks_with_status(TaskStatus::InProgress), &data));
+        assert!(!passes(&tasks_with_status(TaskStatus::Pending), &data));
+        assert!(passes(&tasks_for_agent(&task.agent), &data));
+        assert!(passes(&tasks_requested_by(&requester), &data));
+        assert!(!passes(&tasks_requested_by(&task.agent), &data));
+        assert!(!passes(&agents_owned_by(&requester), &data));
+        // Tasks that grew to hold result data match too
+        let mut grown = data.clone();
+        grown.resize(ComputeTask::LEN + 5, 0);
+        assert!(passes(&tasks_with_status(TaskStatus::InProgress), &grown));
+
+        // Priority sits at the same offset with or without a payment mint
+        for payment_mint in [None, Some(Pubkey::new_unique())] {
+            let task = ComputeTask {
+                account_type: AccountType::Task,
+                version: CURRENT_VERSION,
+                payment_mint,
+                priority: 200,
+                ..ComputeTask::default()
+            };
+            let mut data = task.try_to_vec().unwrap();
+            data.resize(ComputeTask::LEN, 0);
+            task.write_trailer(&mut data[ComputeTask::PRIORITY_OFFSET..]);
+            assert!(passes(&tasks_with_priority(200), &data));
+            assert!(!passes(&tasks_with_priority(0), &data));
+
+            // and so do its tags
+            let task = ComputeTask { tags: [0, 0, 7, 0, 0, 0, 0, 0], ..task };
+            task.write_trailer(&mut data[ComputeTask::PRIORITY_OFFSET..]);
+            assert!(passes(&tasks_with_tag(2, 7), &data));
+            assert!(!passes(&tasks_with_tag(0, 7), &data));
+            assert!(!passes(&tasks_with_tag(2, 3), &data));
+        }
+
+        let owner = Pubkey::new_unique();
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner,
+            ..AIAgent::default()
+        };
+        let mut data = agent.try_to_vec().unwrap();
+        data.resize(AIAgent::LEN, 0);
+        assert!(passes(&agents_owned_by(&owner), &data));
+        assert!(!passes(&active_agents(), &data));
+        assert!(!passes(&tasks_requested_by(&owner), &data));
+
+        // A bid for the task is found from the task's key
+        let task_key = Pubkey::new_unique();
+        let bid = Bid {
+            account_type: AccountType::Bid,
+            version: CURRENT_VERSION,
+            task: task_key,
+            ..Bid::default()
+        };
+        let data = bid.try_to_vec().unwrap();
+        assert!(passes(&bids_for_task(&task_key), &data));
+        assert!(!passes(&bids_for_task(&Pubkey::new_unique()), &data));
+    }
+}
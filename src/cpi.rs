@@ -0,0 +1,213 @@
+// Cross-program invocation helpers for programs that drive this one. Each
+// builds the instruction with the matching builder and invokes the program
+// at `ai_infra_program`. Pass the caller's PDA seeds as `signer_seeds` when
+// a signing account is one of its PDAs, or `&[]` otherwise.
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+};
+
+use crate::{instruction, state::ComputeRequirements};
+
+pub fn register_agent<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    registry: &AccountInfo<'a>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::register_agent(ai_infra_program.key, agent.key, owner.key),
+        &[
+            agent.clone(),
+            owner.clone(),
+            system_program.clone(),
+            registry.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn update_agent_status<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    is_active: bool,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::update_agent_status(ai_infra_program.key, agent.key, owner.key, is_active),
+        &[agent.clone(), owner.clone(), ai_infra_program.clone()],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_task<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    task: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    index: u64,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    config: &AccountInfo<'a>,
+    registry: &AccountInfo<'a>,
+    task_page: &AccountInfo<'a>,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::create_task(
+            ai_infra_program.key,
+            task.key,
+            agent.key,
+            index,
+            payer.key,
+            requirements,
+            payment_amount,
+        ),
+        &[
+            task.clone(),
+            agent.clone(),
+            payer.clone(),
+            system_program.clone(),
+            vault.clone(),
+            config.clone(),
+            registry.clone(),
+            task_page.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn start_task<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    task: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    signer: &AccountInfo<'a>,
+    config: &AccountInfo<'a>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::start_task(ai_infra_program.key, task.key, agent.key, signer.key),
+        &[
+            task.clone(),
+            agent.clone(),
+            signer.clone(),
+            config.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn complete_task<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    task: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    signer: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    requester: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    config: &AccountInfo<'a>,
+    treasury: &AccountInfo<'a>,
+    result_hash: [u8; 32],
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::complete_task(
+            ai_infra_program.key,
+            task.key,
+            agent.key,
+            signer.key,
+            requester.key,
+            treasury.key,
+            result_hash,
+        ),
+        &[
+            task.clone(),
+            agent.clone(),
+            signer.clone(),
+            vault.clone(),
+            requester.clone(),
+            system_program.clone(),
+            config.clone(),
+            treasury.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_credits<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    credit_vault: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    config: &AccountInfo<'a>,
+    registry: &AccountInfo<'a>,
+    amount: u64,
+    expected_nonce: Option<u64>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::deposit_credits(
+            ai_infra_program.key,
+            agent.key,
+            owner.key,
+            amount,
+            expected_nonce,
+        ),
+        &[
+            agent.clone(),
+            owner.clone(),
+            credit_vault.clone(),
+            system_program.clone(),
+            config.clone(),
+            registry.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_credits<'a>(
+    ai_infra_program: &AccountInfo<'a>,
+    agent: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    credit_vault: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    config: &AccountInfo<'a>,
+    amount: u64,
+    expected_nonce: Option<u64>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::withdraw_credits(
+            ai_infra_program.key,
+            agent.key,
+            owner.key,
+            amount,
+            expected_nonce,
+        ),
+        &[
+            agent.clone(),
+            owner.clone(),
+            credit_vault.clone(),
+            system_program.clone(),
+            config.clone(),
+            ai_infra_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
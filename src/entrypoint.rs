@@ -1,18 +1,26 @@
 use solana_program::{
     account_info::AccountInfo,
-    entrypoint,
     entrypoint::ProgramResult,
+    program_error::PrintProgramError,
     pubkey::Pubkey,
 };
 
-use crate::processor::Processor;
+use crate::{error::AIInfraError, processor::Processor};
 
-entrypoint!(process_instruction);
+// Programs that CPI into this one build with the no-entrypoint feature, so
+// their own entrypoint doesn't clash with this one
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    Processor::process(program_id, accounts, instruction_data)
+    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+        // Logs custom errors by name rather than as a bare code
+        error.print::<AIInfraError>();
+        return Err(error);
+    }
+    Ok(())
 }
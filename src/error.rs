@@ -1,7 +1,16 @@
-use solana_program::program_error::ProgramError;
+// num-derive 0.3 expands FromPrimitive into an impl inside a const block
+#![allow(non_local_definitions)]
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
 use thiserror::Error;
 
-#[derive(Error, Debug, Copy, Clone)]
+// Each variant's code is its position, so new variants go at the end
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 pub enum AIInfraError {
     #[error("Invalid Instruction")]
     InvalidInstruction,
@@ -9,10 +18,293 @@ pub enum AIInfraError {
     NotRentExempt,
     #[error("Insufficient Credits")]
     InsufficientCredits,
+    #[error("Agent Not Active")]
+    AgentNotActive,
+    #[error("Already Initialized")]
+    AlreadyInitialized,
+    #[error("Invalid Account Type")]
+    InvalidAccountType,
+    #[error("Agent Task Mismatch")]
+    AgentTaskMismatch,
+    #[error("Numerical Overflow")]
+    NumericalOverflow,
+    #[error("Task Id Mismatch")]
+    TaskIdMismatch,
+    #[error("Task Still Open")]
+    TaskStillOpen,
+    #[error("Agent Has Open Balance")]
+    AgentHasOpenBalance,
+    #[error("Token Mint Mismatch")]
+    TokenMintMismatch,
+    #[error("Token Owner Mismatch")]
+    TokenOwnerMismatch,
+    #[error("Fee Too High")]
+    FeeTooHigh,
+    #[error("Payment Too Low")]
+    PaymentTooLow,
+    #[error("Program Paused")]
+    ProgramPaused,
+    #[error("Task Already Claimed")]
+    TaskAlreadyClaimed,
+    #[error("Bid Too High")]
+    BidTooHigh,
+    #[error("Task Expired")]
+    TaskExpired,
+    #[error("Task Not Expired")]
+    TaskNotExpired,
+    #[error("Agent Stale")]
+    AgentStale,
+    #[error("Agent Not Stale")]
+    AgentNotStale,
+    #[error("Reputation Too Low")]
+    ReputationTooLow,
+    #[error("Stake Too Low")]
+    StakeTooLow,
+    #[error("Unstake Cooldown Active")]
+    UnstakeCooldownActive,
+    #[error("Dispute Window Open")]
+    DisputeWindowOpen,
+    #[error("Dispute Window Closed")]
+    DisputeWindowClosed,
+    #[error("Commitment Mismatch")]
+    CommitmentMismatch,
+    #[error("Result Not Verified")]
+    ResultNotVerified,
+    #[error("Verification Pending")]
+    VerificationPending,
+    #[error("Requester Not Allowed")]
+    RequesterNotAllowed,
+    #[error("Allowlist Full")]
+    AllowlistFull,
+    #[error("Allowance Exceeded")]
+    AllowanceExceeded,
+    // The task's status doesn't allow the requested transition
+    #[error("Invalid Task Status")]
+    InvalidTaskStatus,
+    // The signer isn't the owner recorded in the account
+    #[error("Owner Mismatch")]
+    OwnerMismatch,
+    // A program account passed in isn't owned by this program
+    #[error("Account Not Owned By Program")]
+    AccountNotOwnedByProgram,
+    #[error("Account Not Initialized")]
+    AccountNotInitialized,
+    // The allowance belongs to another agent or spender
+    #[error("Allowance Mismatch")]
+    AllowanceMismatch,
+    // The account's data isn't the size of the type stored in it
+    #[error("Invalid Account Size")]
+    InvalidAccountSize,
+    // The task state machine doesn't allow the status change
+    #[error("Invalid Status Transition")]
+    InvalidStatusTransition,
+    // The agent already runs as many tasks as its owner allows
+    #[error("Agent At Capacity")]
+    AgentAtCapacity,
+    // The agent declares less of a resource than the task requires
+    #[error("Insufficient Capabilities")]
+    InsufficientCapabilities,
+    // The payment is below the price the config sets for the requirements
+    #[error("Payment Below Minimum")]
+    PaymentBelowMinimum,
+    // The account is in an older layout that needs MigrateAccount, or a
+    // newer one than this program knows
+    #[error("Unsupported Account Version")]
+    UnsupportedAccountVersion,
+    // The completion's result_hash differs from the task's expected result
+    #[error("Result Mismatch")]
+    ResultMismatch,
+    // An agent with an attestation key completed a task without an ed25519
+    // instruction in which that key signs the task and result
+    #[error("Invalid Attestation")]
+    InvalidAttestation,
+    #[error("Batch Item Already Claimed")]
+    BatchItemClaimed,
+    // The proof doesn't link the item's result to the batch's Merkle root
+    #[error("Invalid Merkle Proof")]
+    InvalidMerkleProof,
+    // The recurring task was cranked before its next run was due
+    #[error("Run Not Due")]
+    RunNotDue,
+    // No result has a quorum yet, and replicas are still outstanding
+    #[error("Quorum Not Reached")]
+    QuorumNotReached,
+    // An account the instruction writes to was passed readonly
+    #[error("Account Not Writable")]
+    AccountNotWritable,
+    // A task asked for resources outside the config's bounds
+    #[error("Invalid Requirements")]
+    InvalidRequirements,
+    // An agent would hold more credits than the config caps them at
+    #[error("Credit Cap Exceeded")]
+    CreditCapExceeded,
+    // A partial withdrawal was under the config's minimum
+    #[error("Withdrawal Too Small")]
+    WithdrawalTooSmall,
+    // A credit operation expected another operation nonce than the agent's
+    #[error("Nonce Mismatch")]
+    NonceMismatch,
+    // A program account the instruction writes to was passed more than once
+    #[error("Duplicate Account")]
+    DuplicateAccount,
+    // An agent's metadata field was longer than its limit
+    #[error("Invalid Metadata")]
+    InvalidMetadata,
+    // The agent doesn't serve one of the task's categories
+    #[error("Tag Mismatch")]
+    TagMismatch,
+    // A completion's inline result payload is over MAX_RESULT_DATA_LEN bytes
+    #[error("Result Too Large")]
+    ResultTooLarge,
+    // The requester created the config's limit of tasks this window
+    #[error("Rate Limited")]
+    RateLimited,
+    // A withdrawal would take the agent over its policy's daily limit
+    #[error("Withdrawal Limit Exceeded")]
+    WithdrawalLimitExceeded,
+    // The agent's last withdrawal was within its policy's cooldown
+    #[error("Withdrawal Cooldown Active")]
+    WithdrawalCooldownActive,
+    // The config's grant amount is zero
+    #[error("Grants Disabled")]
+    GrantsDisabled,
+    // The agent already claimed its grant
+    #[error("Grant Already Claimed")]
+    GrantAlreadyClaimed,
+    // The agent registered before the previous epoch
+    #[error("Grant Window Closed")]
+    GrantWindowClosed,
+    // The faucet vault can't cover a grant and keep its rent reserve
+    #[error("Faucet Empty")]
+    FaucetEmpty,
+    // The task's insurance claim window has closed, or never opened
+    #[error("Insurance Claim Window Closed")]
+    InsuranceClaimWindowClosed,
+    // The requester already claimed on the task
+    #[error("Insurance Already Claimed")]
+    InsuranceAlreadyClaimed,
+    // The insurance pool has nothing past its rent reserve to pay out
+    #[error("Insurance Pool Empty")]
+    InsurancePoolEmpty,
+    // Only lamport tasks the requester didn't cancel themselves are insured
+    #[error("Task Not Insured")]
+    TaskNotInsured,
+    // Fewer of the agent's multisig members signed than its threshold
+    #[error("Multisig Threshold Not Met")]
+    MultisigThresholdNotMet,
+    // A multisig's members repeat, number more than it holds, or fall short
+    // of its threshold
+    #[error("Invalid Multisig")]
+    InvalidMultisig,
+    // The token account doesn't hold the agent's NFT
+    #[error("Not NFT Holder")]
+    NotNftHolder,
+    // The mint isn't an NFT: a single token with no decimals
+    #[error("Invalid NFT Mint")]
+    InvalidNftMint,
+    // The agent answers to its NFT's holder, not its owner's key
+    #[error("Agent Bound To NFT")]
+    AgentBoundToNft,
+    // The oracle's price was published longer ago than the config allows
+    #[error("Stale Oracle")]
+    StaleOracle,
+    // The oracle's price isn't positive or isn't trading
+    #[error("Invalid Oracle Price")]
+    InvalidOraclePrice,
+    // The oracle's confidence interval is wider than the config allows
+    #[error("Oracle Confidence Too Wide")]
+    OracleConfidenceTooWide,
+    // A task description is longer than MAX_TASK_DESCRIPTION_LEN
+    #[error("Description Too Long")]
+    DescriptionTooLong,
+    // The agent answers to its multisig's members, not its owner's key alone
+    #[error("Agent Owned By Multisig")]
+    AgentOwnedByMultisig,
 }
 
 impl From<AIInfraError> for ProgramError {
     fn from(e: AIInfraError) -> Self {
         ProgramError::Custom(e as u32)
     }
-}
\ No newline at end of file
+}
+
+impl<T> DecodeError<T> for AIInfraError {
+    fn type_of() -> &'static str {
+        "AIInfraError"
+    }
+}
+
+impl PrintProgramError for AIInfraError {
+    fn print<E>(&self) {
+        msg!("Error: {}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::FromPrimitive;
+
+    #[test]
+    fn error_codes_are_stable() {
+        assert_eq!(AIInfraError::InvalidInstruction as u32, 0);
+        assert_eq!(AIInfraError::AllowanceExceeded as u32, 32);
+        assert_eq!(AIInfraError::InvalidTaskStatus as u32, 33);
+        assert_eq!(AIInfraError::OwnerMismatch as u32, 34);
+        assert_eq!(AIInfraError::AccountNotOwnedByProgram as u32, 35);
+        assert_eq!(AIInfraError::AccountNotInitialized as u32, 36);
+        assert_eq!(AIInfraError::AllowanceMismatch as u32, 37);
+        assert_eq!(AIInfraError::InvalidAccountSize as u32, 38);
+        assert_eq!(AIInfraError::InvalidStatusTransition as u32, 39);
+        assert_eq!(AIInfraError::AgentAtCapacity as u32, 40);
+        assert_eq!(AIInfraError::InsufficientCapabilities as u32, 41);
+        assert_eq!(AIInfraError::PaymentBelowMinimum as u32, 42);
+        assert_eq!(AIInfraError::UnsupportedAccountVersion as u32, 43);
+        assert_eq!(AIInfraError::ResultMismatch as u32, 44);
+        assert_eq!(AIInfraError::InvalidAttestation as u32, 45);
+        assert_eq!(AIInfraError::BatchItemClaimed as u32, 46);
+        assert_eq!(AIInfraError::InvalidMerkleProof as u32, 47);
+        assert_eq!(AIInfraError::RunNotDue as u32, 48);
+        assert_eq!(AIInfraError::QuorumNotReached as u32, 49);
+        assert_eq!(AIInfraError::AccountNotWritable as u32, 50);
+        assert_eq!(AIInfraError::InvalidRequirements as u32, 51);
+        assert_eq!(AIInfraError::CreditCapExceeded as u32, 52);
+        assert_eq!(AIInfraError::WithdrawalTooSmall as u32, 53);
+        assert_eq!(AIInfraError::NonceMismatch as u32, 54);
+        assert_eq!(AIInfraError::DuplicateAccount as u32, 55);
+        assert_eq!(AIInfraError::InvalidMetadata as u32, 56);
+        assert_eq!(AIInfraError::TagMismatch as u32, 57);
+        assert_eq!(AIInfraError::ResultTooLarge as u32, 58);
+        assert_eq!(AIInfraError::RateLimited as u32, 59);
+        assert_eq!(AIInfraError::WithdrawalLimitExceeded as u32, 60);
+        assert_eq!(AIInfraError::WithdrawalCooldownActive as u32, 61);
+        assert_eq!(AIInfraError::GrantsDisabled as u32, 62);
+        assert_eq!(AIInfraError::GrantAlreadyClaimed as u32, 63);
+        assert_eq!(AIInfraError::GrantWindowClosed as u32, 64);
+        assert_eq!(AIInfraError::FaucetEmpty as u32, 65);
+        assert_eq!(AIInfraError::InsuranceClaimWindowClosed as u32, 66);
+        assert_eq!(AIInfraError::InsuranceAlreadyClaimed as u32, 67);
+        assert_eq!(AIInfraError::InsurancePoolEmpty as u32, 68);
+        assert_eq!(AIInfraError::TaskNotInsured as u32, 69);
+        assert_eq!(AIInfraError::MultisigThresholdNotMet as u32, 70);
+        assert_eq!(AIInfraError::InvalidMultisig as u32, 71);
+        assert_eq!(AIInfraError::NotNftHolder as u32, 72);
+        assert_eq!(AIInfraError::InvalidNftMint as u32, 73);
+        assert_eq!(AIInfraError::AgentBoundToNft as u32, 74);
+        assert_eq!(AIInfraError::StaleOracle as u32, 75);
+        assert_eq!(AIInfraError::InvalidOraclePrice as u32, 76);
+        assert_eq!(AIInfraError::OracleConfidenceTooWide as u32, 77);
+        assert_eq!(AIInfraError::DescriptionTooLong as u32, 78);
+        assert_eq!(AIInfraError::AgentOwnedByMultisig as u32, 79);
+
+        // Codes decode back to the variant that raised them
+        let error = ProgramError::from(AIInfraError::OwnerMismatch);
+        assert_eq!(error, ProgramError::Custom(34));
+        assert_eq!(AIInfraError::from_u32(34), Some(AIInfraError::OwnerMismatch));
+        assert_eq!(AIInfraError::from_u32(80), None);
+        assert_eq!(
+            <AIInfraError as DecodeError<AIInfraError>>::type_of(),
+            "AIInfraError"
+        );
+    }
+}
@@ -0,0 +1,487 @@
+// Structured events for indexers. Every state transition logs one through
+// sol_log_data, so clients can follow the program from transaction logs
+// without re-fetching accounts.
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    clock::Clock,
+    entrypoint::ProgramResult,
+    log::sol_log_data,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::state::{AgentCapabilities, FailureReason};
+
+// Prefix the runtime puts on every line logged through sol_log_data
+pub const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+// New variants go at the end so logged discriminants stay stable
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub enum Event {
+    AgentRegistered {
+        agent: Pubkey,
+        owner: Pubkey,
+    },
+    AgentDeregistered {
+        agent: Pubkey,
+        owner: Pubkey,
+    },
+    AgentStatusUpdated {
+        agent: Pubkey,
+        is_active: bool,
+    },
+    AgentOwnershipTransferred {
+        agent: Pubkey,
+        previous_owner: Pubkey,
+        new_owner: Pubkey,
+    },
+    // None when the operator was revoked
+    OperatorSet {
+        agent: Pubkey,
+        operator: Option<Pubkey>,
+    },
+    AllowedRequesterAdded {
+        agent: Pubkey,
+        requester: Pubkey,
+    },
+    AllowedRequesterRemoved {
+        agent: Pubkey,
+        requester: Pubkey,
+    },
+    HeartbeatRecorded {
+        agent: Pubkey,
+    },
+    StaleAgentDeactivated {
+        agent: Pubkey,
+        caller: Pubkey,
+    },
+    // The agent is the default pubkey for an open task
+    TaskCreated {
+        task: Pubkey,
+        agent: Pubkey,
+        requester: Pubkey,
+        payment_amount: u64,
+        payment_mint: Option<Pubkey>,
+    },
+    TaskStarted {
+        task: Pubkey,
+        agent: Pubkey,
+    },
+    TaskClaimed {
+        task: Pubkey,
+        agent: Pubkey,
+    },
+    ResultCommitted {
+        task: Pubkey,
+        agent: Pubkey,
+    },
+    TaskCompleted {
+        task: Pubkey,
+        agent: Pubkey,
+        result_hash: [u8; 32],
+        payment_pending: bool,
+    },
+    // The escrow was released to the agent's owner, less `fee` to the
+    // treasury, `forfeit` back to the requester and any ReferralPaid share
+    TaskPaid {
+        task: Pubkey,
+        agent: Pubkey,
+        amount: u64,
+        fee: u64,
+        forfeit: u64,
+    },
+    TaskDisputed {
+        task: Pubkey,
+        requester: Pubkey,
+    },
+    DisputeResolved {
+        task: Pubkey,
+        uphold_result: bool,
+    },
+    ResultVerified {
+        task: Pubkey,
+        verifier: Pubkey,
+    },
+    // Covers failures, expiries, lost disputes and unverified refunds, told
+    // apart by `reason`. The whole escrow went back to the requester, or
+    // `refund` is zero where the vault holds it for an insurance claim.
+    TaskFailed {
+        task: Pubkey,
+        agent: Pubkey,
+        reason: FailureReason,
+        refund: u64,
+    },
+    TaskCancelled {
+        task: Pubkey,
+        refund: u64,
+    },
+    TaskClosed {
+        task: Pubkey,
+    },
+    BidSubmitted {
+        bid: Pubkey,
+        task: Pubkey,
+        agent: Pubkey,
+        amount: u64,
+    },
+    BidAccepted {
+        task: Pubkey,
+        agent: Pubkey,
+        amount: u64,
+    },
+    BidClosed {
+        bid: Pubkey,
+    },
+    // Credit operations carry the operation nonce each agent took for them
+    CreditsDeposited {
+        agent: Pubkey,
+        amount: u64,
+        nonce: u64,
+    },
+    CreditsWithdrawn {
+        agent: Pubkey,
+        amount: u64,
+        nonce: u64,
+    },
+    CreditsTransferred {
+        source: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+        source_nonce: u64,
+        destination_nonce: u64,
+    },
+    CreditsApproved {
+        agent: Pubkey,
+        spender: Pubkey,
+        amount: u64,
+    },
+    AllowanceRevoked {
+        agent: Pubkey,
+        spender: Pubkey,
+    },
+    // Emitted for tasks created with an allowance, after TaskCreated
+    AllowanceSpent {
+        agent: Pubkey,
+        spender: Pubkey,
+        amount: u64,
+        remaining: u64,
+    },
+    CollateralStaked {
+        agent: Pubkey,
+        amount: u64,
+    },
+    UnstakeRequested {
+        agent: Pubkey,
+        amount: u64,
+    },
+    CollateralUnstaked {
+        agent: Pubkey,
+        amount: u64,
+    },
+    StakeSlashed {
+        agent: Pubkey,
+        amount: u64,
+    },
+    RequesterCreditsDeposited {
+        requester: Pubkey,
+        amount: u64,
+    },
+    RequesterCreditsWithdrawn {
+        requester: Pubkey,
+        amount: u64,
+    },
+    VerifierRegistered {
+        verifier: Pubkey,
+    },
+    ConfigInitialized {
+        admin: Pubkey,
+    },
+    ConfigUpdated {
+        admin: Pubkey,
+    },
+    PausedSet {
+        paused: bool,
+    },
+    AgentLimitsSet {
+        agent: Pubkey,
+        max_concurrent_tasks: u32,
+    },
+    CapabilitiesSet {
+        agent: Pubkey,
+        capabilities: AgentCapabilities,
+    },
+    AccountMigrated {
+        account: Pubkey,
+        from_version: u8,
+    },
+    RegistryInitialized {
+        registry: Pubkey,
+    },
+    ExpectedResultSet {
+        task: Pubkey,
+        expected_result_hash: [u8; 32],
+    },
+    AttestationKeySet {
+        agent: Pubkey,
+        attestation_key: Option<Pubkey>,
+    },
+    BatchTaskCreated {
+        batch: Pubkey,
+        agent: Pubkey,
+        requester: Pubkey,
+        task_count: u32,
+        payment_per_task: u64,
+    },
+    BatchResultSubmitted {
+        batch: Pubkey,
+        merkle_root: [u8; 32],
+    },
+    BatchItemClaimed {
+        batch: Pubkey,
+        index: u32,
+        result_hash: [u8; 32],
+        amount: u64,
+        fee: u64,
+    },
+    ReferrerSet {
+        requester: Pubkey,
+        referrer: Pubkey,
+    },
+    // `amount` of a task's payment went to its requester's referrer
+    ReferralPaid {
+        task: Pubkey,
+        referrer: Pubkey,
+        amount: u64,
+    },
+    // `tipper` tipped the agent's owner `amount` for a completed task, adding
+    // `reputation_gain` to the agent's score
+    TipPaid {
+        task: Pubkey,
+        agent: Pubkey,
+        tipper: Pubkey,
+        amount: u64,
+        reputation_gain: u32,
+    },
+    // The agent's share of a task's payment went into its pending payments
+    // until `release_at`
+    PaymentHeld {
+        task: Pubkey,
+        agent: Pubkey,
+        amount: u64,
+        release_at: i64,
+    },
+    PaymentsReleased {
+        agent: Pubkey,
+        amount: u64,
+    },
+    TemplateCreated {
+        template: Pubkey,
+        requester: Pubkey,
+        default_payment: u64,
+    },
+    TemplateUpdated {
+        template: Pubkey,
+        default_payment: u64,
+    },
+    TemplateClosed {
+        template: Pubkey,
+    },
+    RecurringTaskCreated {
+        recurring: Pubkey,
+        requester: Pubkey,
+        agent: Pubkey,
+        payment_per_run: u64,
+        interval_seconds: i64,
+    },
+    RecurringTaskCranked {
+        recurring: Pubkey,
+        task: Pubkey,
+        cranker: Pubkey,
+        reward: u64,
+    },
+    RecurringTaskCancelled {
+        recurring: Pubkey,
+        refunded: u64,
+    },
+    TaskSplit {
+        task: Pubkey,
+        shard_count: u8,
+    },
+    ShardReissued {
+        task: Pubkey,
+        failed_shard: Pubkey,
+        shard: Pubkey,
+        shard_index: u8,
+    },
+    ResultsAggregated {
+        task: Pubkey,
+        result_hash: [u8; 32],
+    },
+    RedundantTaskCreated {
+        task: Pubkey,
+        requester: Pubkey,
+        replicas: u8,
+        quorum: u8,
+        payment_per_replica: u64,
+    },
+    ReplicaResultSubmitted {
+        task: Pubkey,
+        agent: Pubkey,
+        result_hash: [u8; 32],
+    },
+    RedundantTaskFinalized {
+        task: Pubkey,
+        agreed_hash: Option<[u8; 32]>,
+        agents_paid: u8,
+    },
+    AgentMetadataSet {
+        agent: Pubkey,
+    },
+    // surcharge is the lamports raising the priority added to the escrow
+    TaskPriorityUpdated {
+        task: Pubkey,
+        priority: u8,
+        surcharge: u64,
+    },
+    AcceptedTagsSet {
+        agent: Pubkey,
+        accepted_tags: [u8; 32],
+    },
+    // effective_at is the unix timestamp the policy applies from, later than
+    // now when it loosens the current one
+    WithdrawalPolicySet {
+        agent: Pubkey,
+        max_per_day: u64,
+        cooldown_seconds: i64,
+        effective_at: i64,
+    },
+    GrantClaimed {
+        agent: Pubkey,
+        amount: u64,
+    },
+    FaucetFunded {
+        amount: u64,
+    },
+    GrantAmountSet {
+        amount: u64,
+    },
+    InsurancePoolInitialized {
+        insurance_pool: Pubkey,
+    },
+    // The haircut settling a completed task paid into the insurance pool
+    InsuranceCollected {
+        task: Pubkey,
+        amount: u64,
+    },
+    InsuranceClaimPaid {
+        task: Pubkey,
+        requester: Pubkey,
+        amount: u64,
+    },
+    InsurancePoolSwept {
+        amount: u64,
+    },
+    // No signers hands the agent back to its owner's key
+    MultisigOwnerSet {
+        agent: Pubkey,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    },
+    AgentBoundToNft {
+        agent: Pubkey,
+        mint: Pubkey,
+    },
+    // `owner` is the holder, who now owns the agent by key
+    AgentUnboundFromNft {
+        agent: Pubkey,
+        owner: Pubkey,
+    },
+    // Emitted after TaskCreated for a task priced in USD, with the oracle
+    // price, `price * 10^expo` dollars per SOL, its payment was converted at
+    TaskPricedInUsd {
+        task: Pubkey,
+        usd_cents: u64,
+        payment_amount: u64,
+        price: i64,
+        expo: i32,
+    },
+    PreferNativeSet {
+        agent: Pubkey,
+        prefer_native: bool,
+    },
+    // A batch closed, its last item claimed or its unclaimed items'
+    // payments, `refunded`, returned to the requester
+    BatchTaskClosed {
+        batch: Pubkey,
+        refunded: u64,
+    },
+}
+
+// An event as logged, stamped with the cluster time it happened at
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EventRecord {
+    pub timestamp: i64,
+    pub event: Event,
+}
+
+impl EventRecord {
+    // The log line the runtime writes for this record
+    #[cfg(not(target_os = "solana"))]
+    pub fn to_log_line(&self) -> String {
+        format!("{}{}", PROGRAM_DATA_PREFIX, base64::encode(self.try_to_vec().unwrap()))
+    }
+}
+
+// Logs `event` with the current cluster time
+pub fn emit(event: Event) -> ProgramResult {
+    let record = EventRecord {
+        timestamp: Clock::get()?.unix_timestamp,
+        event,
+    };
+    sol_log_data(&[&record.try_to_vec()?]);
+    Ok(())
+}
+
+// Decodes one line of a transaction's logs. Returns None for lines that
+// aren't program data or don't hold an event.
+#[cfg(not(target_os = "solana"))]
+pub fn parse_event(log_line: &str) -> Option<EventRecord> {
+    let payload = log_line.strip_prefix(PROGRAM_DATA_PREFIX)?;
+    let data = base64::decode(payload).ok()?;
+    EventRecord::try_from_slice(&data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_line_round_trips() {
+        let events = [
+            Event::TaskCreated {
+                task: Pubkey::new_unique(),
+                agent: Pubkey::new_unique(),
+                requester: Pubkey::new_unique(),
+                payment_amount: 1000,
+                payment_mint: Some(Pubkey::new_unique()),
+            },
+            Event::TaskFailed {
+                task: Pubkey::new_unique(),
+                agent: Pubkey::new_unique(),
+                reason: FailureReason::Other(513),
+                refund: 1000,
+            },
+        ];
+        for event in events {
+            let record = EventRecord { timestamp: 1_700_000_000, event };
+            assert_eq!(parse_event(&record.to_log_line()), Some(record));
+        }
+    }
+
+    #[test]
+    fn parse_event_skips_other_lines() {
+        assert_eq!(parse_event("Program log: Task started successfully"), None);
+        assert_eq!(parse_event("Program data: not base64!"), None);
+        assert_eq!(parse_event("Program data: AAEC"), None);
+    }
+}
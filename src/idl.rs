@@ -0,0 +1,54 @@
+// Builds the program's IDL from its shank annotations, built with the idl
+// feature. shank reads source files rather than the compiled crate, so the
+// instructions, accounts and errors are read from the modules defining them
+// and merged. The rest of the crate, like the client, isn't part of the
+// program's interface. FailureReason, whose encoding isn't a plain Borsh
+// enum, is left for clients to define: its code byte, then a u16 for Other.
+use std::path::Path;
+
+use shank_idl::{idl::Idl, idl_metadata::IdlMetadata, parse_file, ParseIdlConfig};
+
+// Source files, relative to the crate root, that make up the interface
+const SOURCES: [&str; 3] = ["src/instruction.rs", "src/state.rs", "src/error.rs"];
+
+pub type IdlError = Box<dyn std::error::Error>;
+
+// Reads the IDL from the crate at `root`
+pub fn generate(root: &Path) -> Result<Idl, IdlError> {
+    let config = ParseIdlConfig {
+        program_name: "sonic_ai_infra".to_string(),
+        program_version: env!("CARGO_PKG_VERSION").to_string(),
+        ..ParseIdlConfig::optional_program_address()
+    };
+    let mut idl = Idl {
+        version: config.program_version.clone(),
+        name: config.program_name.clone(),
+        constants: vec![],
+        instructions: vec![],
+        state: None,
+        accounts: vec![],
+        types: vec![],
+        events: None,
+        errors: None,
+        metadata: IdlMetadata { origin: "shank".to_string(), address: None },
+    };
+    for source in SOURCES {
+        let module = parse_file(root.join(source), &config)?.ok_or("no IDL found")?;
+        idl.instructions.extend(module.instructions);
+        idl.accounts.extend(module.accounts);
+        for ty in module.types {
+            if !idl.types.iter().any(|known| known.name == ty.name) {
+                idl.types.push(ty);
+            }
+        }
+        if module.errors.is_some() {
+            idl.errors = module.errors;
+        }
+    }
+    Ok(idl)
+}
+
+// generate, as the JSON written to idl.json
+pub fn generate_json(root: &Path) -> Result<String, IdlError> {
+    Ok(generate(root)?.try_into_json()?)
+}
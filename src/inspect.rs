@@ -0,0 +1,376 @@
+// Human-readable views of the program's accounts, for debugging and the CLI.
+// decode_account reads any account the program owns by its type tag, and the
+// agent and task summaries line their values up under a column of labels.
+use std::fmt;
+
+use borsh::BorshDeserialize;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    error::AIInfraError,
+    state::{
+        AIAgent, AccountType, AgentAllowlist, AgentMetadata, Allowance, BatchTask, Bid,
+        ComputeRequirements, ComputeTask, Config, InsurancePool, MultisigOwner, ProgramAccount,
+        RecurringTask, RedundantTask, Registry, Requester, TaskPage, TaskStatus, TaskTemplate,
+        Verifier,
+    },
+};
+
+// Width of the label column in the account summaries
+const LABEL_WIDTH: usize = 14;
+
+// `key` as its first and last 4 base58 characters
+pub fn short_key(key: &Pubkey) -> String {
+    let key = key.to_string();
+    format!("{}..{}", &key[..4], &key[key.len() - 4..])
+}
+
+// `amount` with a comma between each group of three digits
+pub fn thousands(amount: u64) -> String {
+    let digits = amount.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn field(f: &mut fmt::Formatter, label: &str, value: impl fmt::Display) -> fmt::Result {
+    writeln!(f, "  {label:<LABEL_WIDTH$}{value}")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::InProgress => "in progress",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+            TaskStatus::Disputed => "disputed",
+            TaskStatus::Split => "split",
+        })
+    }
+}
+
+// The requirements that are set, on one line, or "none"
+impl fmt::Display for ComputeRequirements {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        let mut add = |value: u64, unit: &str| {
+            if value > 0 {
+                parts.push(format!("{} {unit}", thousands(value)));
+            }
+        };
+        add(self.cpu_units.into(), "CPU");
+        add(self.gpu_units.into(), "GPU");
+        add(self.memory_mb.into(), "MB memory");
+        add(self.storage_mb.into(), "MB storage");
+        add(self.network_mbps.into(), "Mbps");
+        add(self.max_time_seconds.into(), "s");
+        add(self.min_reputation.into(), "reputation");
+        add(self.min_stake, "staked");
+        if self.single_step {
+            parts.push("single step".to_string());
+        }
+        if parts.is_empty() {
+            return f.write_str("none");
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl fmt::Display for AIAgent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Agent")?;
+        field(f, "owner", short_key(&self.owner))?;
+        if let Some(operator) = &self.operator {
+            field(f, "operator", short_key(operator))?;
+        }
+        field(f, "status", if self.is_active { "active" } else { "inactive" })?;
+        field(f, "credits", thousands(self.compute_credits))?;
+        field(f, "staked", thousands(self.staked_lamports))?;
+        field(f, "reputation", self.reputation_score)?;
+        let limit = match self.max_concurrent_tasks {
+            0 => String::new(),
+            limit => format!(" of {limit}"),
+        };
+        field(f, "active tasks", format!("{}{limit}", self.active_tasks))?;
+        field(
+            f,
+            "finished",
+            format!(
+                "{} completed, {} failed, {} cancelled",
+                self.tasks_completed, self.tasks_failed, self.tasks_cancelled
+            ),
+        )?;
+        field(f, "heartbeat", self.last_heartbeat)
+    }
+}
+
+impl fmt::Display for ComputeTask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Task {}", self.index)?;
+        field(f, "status", self.status)?;
+        let agent = if self.open_listing && self.agent == Pubkey::default() {
+            "open listing".to_string()
+        } else {
+            short_key(&self.agent)
+        };
+        field(f, "agent", agent)?;
+        field(f, "requester", short_key(&self.requester))?;
+        let mint = match &self.payment_mint {
+            Some(mint) => format!(" of {}", short_key(mint)),
+            None => String::new(),
+        };
+        field(f, "payment", format!("{}{mint}", thousands(self.payment_amount)))?;
+        field(f, "priority", self.priority)?;
+        field(f, "requirements", &self.requirements)?;
+        if self.result_hash != [0; 32] {
+            field(f, "result", hex(&self.result_hash))?;
+        }
+        if let Some(reason) = self.failure_reason {
+            field(f, "failure", format!("{reason:?}"))?;
+        }
+        field(f, "created", self.created_at)
+    }
+}
+
+// Any account the program owns, read by its type tag
+#[derive(Debug)]
+pub enum DecodedAccount {
+    Agent(AIAgent),
+    Task(ComputeTask),
+    Config(Config),
+    Bid(Bid),
+    Requester(Requester),
+    Verifier(Verifier),
+    Allowlist(AgentAllowlist),
+    Allowance(Allowance),
+    Registry(Registry),
+    TaskPage(TaskPage),
+    BatchTask(BatchTask),
+    TaskTemplate(TaskTemplate),
+    RecurringTask(RecurringTask),
+    RedundantTask(RedundantTask),
+    AgentMetadata(AgentMetadata),
+    InsurancePool(InsurancePool),
+    MultisigOwner(MultisigOwner),
+}
+
+// Reads `data` as whichever account its first byte tags it as, in the
+// current layout
+pub fn decode_account(data: &[u8]) -> Result<DecodedAccount, ProgramError> {
+    let tag = data.first().ok_or(AIInfraError::AccountNotInitialized)?;
+    let account_type = AccountType::try_from_slice(&[*tag])
+        .map_err(|_| AIInfraError::InvalidAccountType)?;
+    Ok(match account_type {
+        AccountType::Uninitialized => return Err(AIInfraError::AccountNotInitialized.into()),
+        AccountType::Agent => DecodedAccount::Agent(AIAgent::unpack(data)?),
+        AccountType::Task => DecodedAccount::Task(ComputeTask::unpack(data)?),
+        AccountType::Config => DecodedAccount::Config(Config::unpack(data)?),
+        AccountType::Bid => DecodedAccount::Bid(Bid::unpack(data)?),
+        AccountType::Requester => DecodedAccount::Requester(Requester::unpack(data)?),
+        AccountType::Verifier => DecodedAccount::Verifier(Verifier::unpack(data)?),
+        AccountType::Allowlist => DecodedAccount::Allowlist(AgentAllowlist::unpack(data)?),
+        AccountType::Allowance => DecodedAccount::Allowance(Allowance::unpack(data)?),
+        AccountType::Registry => DecodedAccount::Registry(Registry::unpack(data)?),
+        AccountType::TaskPage => DecodedAccount::TaskPage(TaskPage::unpack(data)?),
+        AccountType::BatchTask => DecodedAccount::BatchTask(BatchTask::unpack(data)?),
+        AccountType::TaskTemplate => DecodedAccount::TaskTemplate(TaskTemplate::unpack(data)?),
+        AccountType::RecurringTask => DecodedAccount::RecurringTask(RecurringTask::unpack(data)?),
+        AccountType::RedundantTask => DecodedAccount::RedundantTask(RedundantTask::unpack(data)?),
+        AccountType::AgentMetadata => DecodedAccount::AgentMetadata(AgentMetadata::unpack(data)?),
+        AccountType::InsurancePool => DecodedAccount::InsurancePool(InsurancePool::unpack(data)?),
+        AccountType::MultisigOwner => DecodedAccount::MultisigOwner(MultisigOwner::unpack(data)?),
+    })
+}
+
+// Agents and tasks as their summaries, the other accounts as their fields
+impl fmt::Display for DecodedAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+            DecodedAccount::Agent(agent) => agent.fmt(f),
+            DecodedAccount::Task(task) => task.fmt(f),
+            DecodedAccount::Config(config) => writeln!(f, "{config:#?}"),
+            DecodedAccount::Bid(bid) => writeln!(f, "{bid:#?}"),
+            DecodedAccount::Requester(requester) => writeln!(f, "{requester:#?}"),
+            DecodedAccount::Verifier(verifier) => writeln!(f, "{verifier:#?}"),
+            DecodedAccount::Allowlist(allowlist) => writeln!(f, "{allowlist:#?}"),
+            DecodedAccount::Allowance(allowance) => writeln!(f, "{allowance:#?}"),
+            DecodedAccount::Registry(registry) => writeln!(f, "{registry:#?}"),
+            DecodedAccount::TaskPage(page) => writeln!(f, "{page:#?}"),
+            DecodedAccount::BatchTask(batch) => writeln!(f, "{batch:#?}"),
+            DecodedAccount::TaskTemplate(template) => writeln!(f, "{template:#?}"),
+            DecodedAccount::RecurringTask(recurring) => writeln!(f, "{recurring:#?}"),
+            DecodedAccount::RedundantTask(redundant) => writeln!(f, "{redundant:#?}"),
+            DecodedAccount::AgentMetadata(metadata) => writeln!(f, "{metadata:#?}"),
+            DecodedAccount::InsurancePool(pool) => writeln!(f, "{pool:#?}"),
+            DecodedAccount::MultisigOwner(multisig) => writeln!(f, "{multisig:#?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{FailureReason, CURRENT_VERSION};
+
+    fn account_data<T: ProgramAccount>(value: &T) -> Vec<u8> {
+        let mut data = value.try_to_vec().unwrap();
+        data.resize(T::LEN, 0);
+        value.write_trailer(&mut data[T::LEN - T::TRAILER_LEN..]);
+        data
+    }
+
+    fn agent() -> AIAgent {
+        AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: Pubkey::new_from_array([1; 32]),
+            compute_credits: 1_234_567,
+            reputation_score: 250,
+            tasks_completed: 12,
+            tasks_failed: 1,
+            is_active: true,
+            staked_lamports: 5_000_000_000,
+            active_tasks: 2,
+            max_concurrent_tasks: 4,
+            last_heartbeat: 1_700_000_000,
+            ..AIAgent::default()
+        }
+    }
+
+    fn task() -> ComputeTask {
+        ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: Pubkey::new_from_array([2; 32]),
+            requester: Pubkey::new_from_array([3; 32]),
+            index: 7,
+            status: TaskStatus::Failed,
+            payment_amount: 500_000,
+            priority: 3,
+            requirements: ComputeRequirements {
+                cpu_units: 4,
+                memory_mb: 2_048,
+                max_time_seconds: 60,
+                single_step: true,
+                ..ComputeRequirements::default()
+            },
+            result_hash: [0xab; 32],
+            failure_reason: Some(FailureReason::OutOfTime),
+            created_at: 1_700_000_100,
+            ..ComputeTask::default()
+        }
+    }
+
+    #[test]
+    fn numbers_and_keys_shorten() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(123_456_789), "123,456,789");
+        assert_eq!(thousands(u64::MAX), "18,446,744,073,709,551,615");
+        assert_eq!(short_key(&Pubkey::new_from_array([1; 32])), "4vJ9..kLKi");
+    }
+
+    #[test]
+    fn agents_print_as_summaries() {
+        assert_eq!(
+            agent().to_string(),
+            "Agent\n\
+             \x20 owner         4vJ9..kLKi\n\
+             \x20 status        active\n\
+             \x20 credits       1,234,567\n\
+             \x20 staked        5,000,000,000\n\
+             \x20 reputation    250\n\
+             \x20 active tasks  2 of 4\n\
+             \x20 finished      12 completed, 1 failed, 0 cancelled\n\
+             \x20 heartbeat     1700000000\n"
+        );
+    }
+
+    #[test]
+    fn tasks_print_as_summaries() {
+        assert_eq!(
+            task().to_string(),
+            format!(
+                "Task 7\n\
+                 \x20 status        failed\n\
+                 \x20 agent         8qbH..VfeR\n\
+                 \x20 requester     CktR..Ezy8\n\
+                 \x20 payment       500,000\n\
+                 \x20 priority      3\n\
+                 \x20 requirements  4 CPU, 2,048 MB memory, 60 s, single step\n\
+                 \x20 result        {}\n\
+                 \x20 failure       OutOfTime\n\
+                 \x20 created       1700000100\n",
+                "ab".repeat(32),
+            )
+        );
+        let open = ComputeTask { agent: Pubkey::default(), open_listing: true, ..task() };
+        assert!(open.to_string().contains("  agent         open listing\n"));
+    }
+
+    #[test]
+    fn statuses_and_requirements_print_as_text() {
+        assert_eq!(TaskStatus::InProgress.to_string(), "in progress");
+        assert_eq!(TaskStatus::Disputed.to_string(), "disputed");
+        assert_eq!(ComputeRequirements::default().to_string(), "none");
+        let requirements = ComputeRequirements {
+            gpu_units: 1,
+            storage_mb: 10,
+            network_mbps: 100,
+            min_reputation: 50,
+            min_stake: 1_000_000,
+            ..ComputeRequirements::default()
+        };
+        assert_eq!(
+            requirements.to_string(),
+            "1 GPU, 10 MB storage, 100 Mbps, 50 reputation, 1,000,000 staked"
+        );
+    }
+
+    #[test]
+    fn accounts_decode_by_their_tag() {
+        let decoded = decode_account(&account_data(&agent())).unwrap();
+        assert!(matches!(&decoded, DecodedAccount::Agent(decoded) if *decoded == agent()));
+        assert_eq!(decoded.to_string(), agent().to_string());
+
+        let decoded = decode_account(&account_data(&task())).unwrap();
+        assert!(matches!(&decoded, DecodedAccount::Task(decoded) if *decoded == task()));
+
+        let registry = Registry {
+            account_type: AccountType::Registry,
+            version: CURRENT_VERSION,
+            total_agents: 3,
+            ..Registry::default()
+        };
+        let decoded = decode_account(&account_data(&registry)).unwrap();
+        assert!(matches!(decoded, DecodedAccount::Registry(Registry { total_agents: 3, .. })));
+        assert!(decoded.to_string().contains("total_agents: 3"));
+    }
+
+    #[test]
+    fn unknown_accounts_fail_to_decode() {
+        let not_initialized = Err(AIInfraError::AccountNotInitialized.into());
+        assert_eq!(decode_account(&[]).map(|_| ()), not_initialized);
+        assert_eq!(decode_account(&[0; 8]).map(|_| ()), not_initialized);
+        assert_eq!(
+            decode_account(&[200, CURRENT_VERSION]).map(|_| ()),
+            Err(AIInfraError::InvalidAccountType.into())
+        );
+        // A tag alone doesn't make an account of the type
+        let mut data = account_data(&agent());
+        data.truncate(10);
+        assert!(decode_account(&data).is_err());
+    }
+}
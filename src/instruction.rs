@@ -1,12 +1,3210 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use shank::ShankInstruction;
+use solana_program::{
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    system_program,
+    sysvar,
+};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+use crate::{
+    pda::{
+        find_agent_address, find_config_address, find_credit_vault_address, find_faucet_address,
+        find_insurance_pool_address, find_metadata_address, find_multisig_address,
+        find_registry_address, find_requester_address, find_task_address,
+        find_task_page_address, find_vault_address,
+    },
+    state::{
+        AIAgent, AgentCapabilities, ComputeRequirements, ComputeTask, ConfigParams,
+        FailureReason, ProgramAccount, TaskPage, WithdrawalPolicy, TASKS_PER_PAGE, TASK_TAGS,
+        tag_set,
+    },
+};
+
+// A task BatchCompleteTasks completes, and the result it completes it with.
+// Encoded as the (task_id, result_hash) pair it once was.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskResult {
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub task_id: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+    pub result_hash: [u8; 32],
+}
+
+// Instructions the program accepts. Borsh encodes each variant as its
+// position in this list, and clients build instructions from those indices,
+// so variants are only ever appended: never reorder, remove or insert one.
+// The tests below pin every variant's discriminant.
+//
+// Each variant's #[account] attributes list the accounts the processor reads,
+// in order, for the IDL. Accounts whose number varies, like a token task's
+// token accounts, follow those and are described with the variant.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, ShankInstruction)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AIInfraInstruction {
+    // Agent Management
+    #[account(0, writable, name = "agent", desc = "The agent PDA derived from the owner")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner, who pays its rent")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    #[account(3, writable, name = "registry", desc = "The program registry")]
     RegisterAgent,
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    UpdateAgentStatus { is_active: bool },
+    
+    // Task Management. A priority above the config's threshold raises the
+    // price the payment must meet. Only agents serving every tag can claim
+    // the task. The requester and allowlist accounts may come in either order.
+    // A task priced in USD takes the config's oracle last, after them, and a
+    // described task the memo program its description is written to after
+    // that. Only the description's hash is stored on the task.
+    #[account(0, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        1, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(2, writable, signer, name = "payer", desc = "The requester, who pays the escrow")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "config", desc = "The program config, writable for an open task")]
+    #[account(6, writable, name = "registry", desc = "The program registry")]
+    #[account(7, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(
+        8, writable, optional, name = "requester",
+        desc = "The payer's requester account, while the config rate limits them",
+    )]
+    #[account(9, optional, name = "allowlist", desc = "The agent's allowlist, once it has entries")]
+    #[account(
+        10, optional, name = "oracle",
+        desc = "The config's price oracle, for a task priced in USD",
+    )]
+    #[account(
+        11, optional, name = "memo_program",
+        desc = "The SPL Memo program, for a described task",
+    )]
     CreateTask {
         requirements: ComputeRequirements,
+        // Zero when payment_usd_cents prices the task instead, at the
+        // oracle's price when the task is created
+        payment_amount: u64,
+        priority: u8,
+        tags: [u8; 8],
+        payment_usd_cents: Option<u64>,
+        description: Option<String>,
+    },
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, name = "config", desc = "The program config")]
+    StartTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    // result_data, when given, is stored on the task, and must hash to
+    // result_hash. A token task's token accounts, the referral, payment
+    // hold and insurance pool accounts the config calls for, and the
+    // instructions sysvar for an attested agent follow the accounts below.
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(4, writable, name = "requester", desc = "The task's requester")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    #[account(7, writable, name = "treasury", desc = "The config's treasury")]
+    CompleteTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        result_hash: [u8; 32],
+        result_data: Option<Vec<u8>>,
+    },
+    
+    // Payment Management. With expected_nonce set, a credit operation fails
+    // unless the agent's operation_nonce still has that value.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "credit_vault", desc = "The agent's credit vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    #[account(5, writable, name = "registry", desc = "The program registry")]
+    DepositCredits { amount: u64, expected_nonce: Option<u64> },
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "credit_vault", desc = "The agent's credit vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    WithdrawCredits { amount: u64, expected_nonce: Option<u64> },
+
+    // Later additions are appended so existing variant indices stay stable
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "signer", desc = "The requester or the agent's owner")]
+    #[account(3, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(4, writable, name = "requester", desc = "The task's requester, who is refunded")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    CancelTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(4, writable, name = "requester", desc = "The task's requester, who is refunded")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    #[account(7, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    #[account(8, writable, name = "treasury", desc = "The config's treasury")]
+    FailTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        reason: FailureReason,
+    },
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, name = "agent", desc = "The task's agent")]
+    #[account(
+        2, signer, name = "signer",
+        desc = "The task's requester, or the agent's owner of a completed task",
+    )]
+    #[account(3, writable, name = "destination", desc = "The wallet taking the task's rent")]
+    #[account(
+        4, writable, optional, name = "vault",
+        desc = "The task's vault, while it holds a failed task's refund",
+    )]
+    #[account(5, optional, name = "system_program", desc = "The system program")]
+    CloseTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "credit_vault", desc = "The agent's credit vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    DeregisterAgent,
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, signer, name = "new_owner", desc = "The agent's new owner")]
+    TransferAgentOwnership {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        new_owner: Pubkey,
+    },
+    #[account(0, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        1, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(2, writable, signer, name = "payer", desc = "The requester, who pays the escrow")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "vault", desc = "The task's vault, which owns vault_token")]
+    #[account(5, writable, name = "payer_token", desc = "The payer's token account")]
+    #[account(6, writable, name = "vault_token", desc = "The vault's associated token account")]
+    #[account(7, name = "mint", desc = "The payment's mint")]
+    #[account(8, name = "token_program", desc = "The token program, SPL Token or Token-2022")]
+    #[account(9, name = "associated_token_program", desc = "The associated token account program")]
+    #[account(10, name = "config", desc = "The program config, writable for an open task")]
+    #[account(11, writable, name = "registry", desc = "The program registry")]
+    #[account(12, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(
+        13, writable, optional, name = "requester",
+        desc = "The payer's requester account, while the config rate limits them",
+    )]
+    #[account(
+        14, optional, name = "allowlist",
+        desc = "The agent's allowlist, once it has entries",
+    )]
+    CreateTaskWithToken {
+        requirements: ComputeRequirements,
+        amount: u64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        mint: Pubkey,
+    },
+    #[account(0, writable, name = "config", desc = "The config PDA")]
+    #[account(1, writable, signer, name = "payer", desc = "The wallet paying the config's rent")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    InitializeConfig {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        admin: Pubkey,
+        params: ConfigParams,
+    },
+    // Replaces every setting, including the admin itself
+    #[account(0, writable, name = "config", desc = "The program config")]
+    #[account(1, signer, name = "admin", desc = "The config's admin")]
+    UpdateConfig {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        admin: Pubkey,
+        params: ConfigParams,
+    },
+    #[account(0, writable, name = "config", desc = "The program config")]
+    #[account(1, signer, name = "admin", desc = "The config's admin")]
+    PauseProgram,
+    #[account(0, writable, name = "config", desc = "The program config")]
+    #[account(1, signer, name = "admin", desc = "The config's admin")]
+    UnpauseProgram,
+    #[account(0, writable, name = "task", desc = "The open task")]
+    #[account(1, writable, name = "agent", desc = "The claiming agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, name = "config", desc = "The program config")]
+    ClaimTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "bid", desc = "The agent's bid PDA for the task")]
+    #[account(1, name = "task", desc = "The open task")]
+    #[account(2, name = "agent", desc = "The bidding agent")]
+    #[account(
+        3, writable, signer, name = "owner",
+        desc = "The agent's owner, who pays the bid's rent",
+    )]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    SubmitBid {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        amount: u64,
+    },
+    #[account(0, writable, name = "task", desc = "The open task")]
+    #[account(1, name = "bid", desc = "The accepted bid")]
+    #[account(2, writable, name = "agent", desc = "The bid's agent")]
+    #[account(3, writable, signer, name = "requester", desc = "The task's requester")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    AcceptBid {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "bid", desc = "The bid")]
+    #[account(1, name = "agent", desc = "The bid's agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner")]
+    #[account(3, writable, name = "destination", desc = "The wallet taking the bid's rent")]
+    CloseBid,
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(3, writable, name = "requester", desc = "The task's requester, who is refunded")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    #[account(6, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    #[account(7, writable, name = "treasury", desc = "The config's treasury")]
+    ExpireTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner or operator")]
+    Heartbeat,
+    #[account(0, writable, name = "agent", desc = "The stale agent")]
+    #[account(1, writable, name = "config", desc = "The program config, which pays the bounty")]
+    #[account(2, writable, name = "caller", desc = "The wallet taking the bounty")]
+    DeactivateStaleAgent,
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    StakeCollateral { amount: u64 },
+    // Requests an unstake of `amount`, or collects a pending request of the
+    // same amount once the config's cooldown has passed
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    UnstakeCollateral { amount: u64 },
+    // The first deposit creates the wallet's requester account
+    #[account(0, writable, name = "requester", desc = "The wallet's requester account")]
+    #[account(1, writable, signer, name = "owner", desc = "The requester's wallet")]
+    #[account(2, writable, name = "credit_vault", desc = "The requester's credit vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    DepositRequesterCredits { amount: u64 },
+    #[account(0, writable, name = "requester", desc = "The wallet's requester account")]
+    #[account(1, writable, signer, name = "owner", desc = "The requester's wallet")]
+    #[account(2, writable, name = "credit_vault", desc = "The requester's credit vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    WithdrawRequesterCredits { amount: u64 },
+    // Like CreateTask, but escrows the payment out of the requester's credits
+    #[account(0, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        1, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(
+        2, writable, signer, name = "payer",
+        desc = "The requester, who pays the task's rent",
+    )]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "config", desc = "The program config, writable for an open task")]
+    #[account(6, writable, name = "registry", desc = "The program registry")]
+    #[account(7, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(8, writable, name = "requester", desc = "The payer's requester account")]
+    #[account(9, writable, name = "credit_vault", desc = "The requester's credit vault")]
+    #[account(
+        10, optional, name = "allowlist",
+        desc = "The agent's allowlist, once it has entries",
+    )]
+    CreateTaskWithCredits {
+        requirements: ComputeRequirements,
+        payment_amount: u64,
     },
-    DepositCredits {
+    // Takes CompleteTask's accounts, and those that follow them
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, signer, name = "signer", desc = "The agent's owner")]
+    #[account(3, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(4, writable, name = "requester", desc = "The task's requester")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    #[account(7, writable, name = "treasury", desc = "The config's treasury")]
+    ClaimPayment {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "task", desc = "The completed task")]
+    #[account(1, signer, name = "requester", desc = "The task's requester")]
+    #[account(2, name = "config", desc = "The program config")]
+    DisputeTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    #[account(0, writable, name = "task", desc = "The disputed task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "arbitrator", desc = "The config's arbitrator")]
+    #[account(3, writable, name = "owner", desc = "The agent's owner")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, writable, name = "requester", desc = "The task's requester")]
+    #[account(6, name = "system_program", desc = "The system program")]
+    #[account(7, name = "config", desc = "The program config")]
+    #[account(8, writable, name = "treasury", desc = "The config's treasury")]
+    #[account(9, writable, name = "stake_vault", desc = "The agent's stake vault")]
+    ResolveDispute {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        uphold_result: bool,
+    },
+    // Commit-reveal replacement for CompleteTask: the agent first commits to
+    // sha256(result_hash || salt), then reveals both to finish the task
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, name = "config", desc = "The program config")]
+    CommitResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        commitment: [u8; 32],
+    },
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(4, writable, name = "requester", desc = "The task's requester")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    #[account(7, writable, name = "treasury", desc = "The config's treasury")]
+    RevealResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        result_hash: [u8; 32],
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        salt: [u8; 32],
+    },
+    #[account(0, writable, name = "verifier", desc = "The verifier PDA")]
+    #[account(1, writable, signer, name = "admin", desc = "The config's admin, who pays the rent")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    #[account(3, name = "config", desc = "The program config")]
+    RegisterVerifier {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        verifier: Pubkey,
+    },
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, signer, name = "verifier_signer", desc = "The verifier's wallet")]
+    #[account(3, name = "verifier", desc = "The verifier's account")]
+    #[account(4, writable, name = "owner", desc = "The agent's owner")]
+    #[account(5, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(6, writable, name = "requester", desc = "The task's requester")]
+    #[account(7, name = "system_program", desc = "The system program")]
+    #[account(8, name = "config", desc = "The program config")]
+    #[account(9, writable, name = "treasury", desc = "The config's treasury")]
+    VerifyResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    // Refunds a task whose result went unverified past the config's timeout
+    #[account(0, writable, name = "task", desc = "The task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(3, writable, name = "requester", desc = "The task's requester, who is refunded")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    RefundUnverifiedTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+    },
+    // Single-step completion of up to MAX_BATCH_COMPLETIONS tasks of one
+    // agent. Each task's account, vault and requester follow the accounts
+    // below, in results order.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    #[account(3, name = "config", desc = "The program config")]
+    #[account(4, writable, name = "treasury", desc = "The config's treasury")]
+    BatchCompleteTasks { results: Vec<TaskResult> },
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetOperator {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        operator: Pubkey,
+    },
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    RevokeOperator,
+    // Once an agent's allowlist is non-empty, CreateTask for it must pass
+    // the allowlist account among its optional trailing accounts, and the
+    // requester must be on it
+    #[account(0, writable, name = "allowlist", desc = "The agent's allowlist")]
+    #[account(1, writable, name = "agent", desc = "The agent")]
+    #[account(2, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    AddAllowedRequester {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        requester: Pubkey,
+    },
+    #[account(0, writable, name = "allowlist", desc = "The agent's allowlist")]
+    #[account(1, writable, name = "agent", desc = "The agent")]
+    #[account(2, signer, name = "owner", desc = "The agent's owner")]
+    RemoveAllowedRequester {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        requester: Pubkey,
+    },
+    // Moves credits from one of the signer's agents to any other agent.
+    // expected_nonce is checked against the source agent's.
+    #[account(0, writable, name = "source", desc = "The agent paying the credits")]
+    #[account(1, writable, name = "destination", desc = "The agent receiving them")]
+    #[account(2, writable, signer, name = "owner", desc = "The source agent's owner")]
+    #[account(3, writable, name = "source_vault", desc = "The source agent's credit vault")]
+    #[account(4, writable, name = "destination_vault", desc = "The destination's credit vault")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    #[account(6, name = "config", desc = "The program config")]
+    TransferCredits { amount: u64, expected_nonce: Option<u64> },
+    // Lets `spender` fund up to `amount` of tasks out of the agent's credits,
+    // replacing any earlier allowance
+    #[account(0, writable, name = "allowance", desc = "The allowance PDA")]
+    #[account(1, name = "agent", desc = "The agent")]
+    #[account(2, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    ApproveCredits {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        spender: Pubkey,
         amount: u64,
     },
-}
\ No newline at end of file
+    #[account(0, writable, name = "allowance", desc = "The allowance")]
+    #[account(1, name = "agent", desc = "The agent")]
+    #[account(2, writable, signer, name = "owner", desc = "The agent's owner, who takes its rent")]
+    RevokeAllowance,
+    // Like CreateTask, but a spender escrows the payment out of another
+    // agent's credits under its allowance
+    #[account(0, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        1, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(2, writable, signer, name = "payer", desc = "The spender, who pays the task's rent")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "config", desc = "The program config, writable for an open task")]
+    #[account(6, writable, name = "registry", desc = "The program registry")]
+    #[account(7, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(8, writable, name = "funding", desc = "The agent whose credits pay")]
+    #[account(9, writable, name = "credit_vault", desc = "The funding agent's credit vault")]
+    #[account(10, writable, name = "allowance", desc = "The spender's allowance")]
+    #[account(
+        11, optional, name = "allowlist",
+        desc = "The agent's allowlist, once it has entries",
+    )]
+    CreateTaskWithAllowance {
+        requirements: ComputeRequirements,
+        payment_amount: u64,
+    },
+    // Caps how many unfinished tasks the agent takes on at once; zero lifts
+    // the cap
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetAgentLimits { max_concurrent_tasks: u32 },
+    // Replaces the resources the agent declares, which StartTask, ClaimTask
+    // and SubmitBid check against the task's requirements
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetCapabilities { capabilities: AgentCapabilities },
+    // Rewrites a program account from an older layout version in the
+    // current one. The program rejects accounts in any other version.
+    #[account(0, writable, name = "account", desc = "The program account")]
+    #[account(1, writable, signer, name = "payer", desc = "The wallet paying any added rent")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    MigrateAccount,
+    // Creates the registry of program-wide counters. Anyone may pay for it,
+    // once.
+    #[account(0, writable, name = "registry", desc = "The registry PDA")]
+    #[account(1, writable, signer, name = "payer", desc = "The wallet paying its rent")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    InitializeRegistry,
+    // Signed by the requester while the task is pending, usually alongside
+    // CreateTask. Once set, CompleteTask only accepts this result_hash.
+    #[account(0, writable, name = "task", desc = "The pending task")]
+    #[account(1, signer, name = "requester", desc = "The task's requester")]
+    SetExpectedResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        expected_result_hash: [u8; 32],
+    },
+    // Sets or clears the key whose ed25519 attestation the agent's
+    // completions need. Completions by an agent with a key must pass the
+    // instructions sysvar among their accounts.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetAttestationKey {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+        attestation_key: Option<Pubkey>,
+    },
+    // Escrows `task_count` payments of `payment_per_task` for a batch of
+    // small tasks, numbered by the agent's batch counter
+    #[account(0, writable, name = "batch", desc = "The agent's next batch PDA")]
+    #[account(1, writable, name = "agent", desc = "The agent")]
+    #[account(2, writable, signer, name = "requester", desc = "The requester, who pays the escrow")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "vault", desc = "The batch's escrow vault")]
+    #[account(5, name = "config", desc = "The program config")]
+    CreateBatchTask {
+        task_count: u32,
+        payment_per_task: u64,
+    },
+    // Signed by the agent's owner or operator, once, with the root of the
+    // Merkle tree over every item's result (see merkle.rs)
+    #[account(0, writable, name = "batch", desc = "The batch")]
+    #[account(1, name = "agent", desc = "The batch's agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    SubmitBatchResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        merkle_root: [u8; 32],
+    },
+    // Pays the agent's owner for item `index` once `proof` shows
+    // `result_hash` is its result under the submitted root. Claiming the
+    // last item closes the batch.
+    #[account(0, writable, name = "batch", desc = "The batch")]
+    #[account(1, name = "agent", desc = "The batch's agent")]
+    #[account(2, writable, signer, name = "owner", desc = "The agent's owner, who is paid")]
+    #[account(3, writable, name = "vault", desc = "The batch's escrow vault")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    #[account(6, writable, name = "treasury", desc = "The config's treasury")]
+    #[account(7, writable, name = "requester", desc = "The batch's requester, repaid its rent")]
+    ClaimBatchItem {
+        index: u32,
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        result_hash: [u8; 32],
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash_vec"))]
+        proof: Vec<[u8; 32]>,
+    },
+    // Names the wallet that takes the config's referral share of the signing
+    // requester's completed tasks, creating their requester account if
+    // needed. A referrer can only be set once.
+    #[account(0, writable, name = "requester", desc = "The owner's requester account")]
+    #[account(1, writable, signer, name = "owner", desc = "The requester's wallet")]
+    #[account(2, name = "system_program", desc = "The system program")]
+    SetReferrer {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        referrer: Pubkey,
+    },
+    // Tips a completed task's agent owner `amount` lamports from the signer,
+    // adding a little reputation up to a per-task cap
+    #[account(0, writable, name = "task", desc = "The completed task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, signer, name = "tipper", desc = "The wallet paying the tip")]
+    #[account(3, writable, name = "owner", desc = "The agent's owner, who is paid")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    TipAgent { amount: u64 },
+    // Credits the agent with the pending payments whose hold has passed.
+    // Anyone may send it.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    ReleasePayments,
+    // Stores requirements and a default payment the signing requester can
+    // create tasks from, numbered by their template counter
+    #[account(0, writable, name = "template", desc = "The requester's next template PDA")]
+    #[account(1, writable, name = "requester", desc = "The wallet's requester account")]
+    #[account(2, writable, signer, name = "owner", desc = "The requester's wallet")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    CreateTemplate {
+        requirements: ComputeRequirements,
+        default_payment: u64,
+    },
+    // Replaces a template's requirements and default payment. Tasks already
+    // created from it keep theirs.
+    #[account(0, writable, name = "template", desc = "The template")]
+    #[account(1, signer, name = "owner", desc = "The template's requester")]
+    UpdateTemplate {
+        requirements: ComputeRequirements,
+        default_payment: u64,
+    },
+    // Closes a template, returning its rent to its requester
+    #[account(0, writable, name = "template", desc = "The template")]
+    #[account(
+        1, writable, signer, name = "owner",
+        desc = "The template's requester, who takes its rent",
+    )]
+    CloseTemplate,
+    // CreateTask with the template's requirements, paying `payment_amount`
+    // or the template's default payment. Only the template's requester may
+    // use it.
+    #[account(0, name = "template", desc = "The template")]
+    #[account(1, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        2, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(
+        3, writable, signer, name = "payer",
+        desc = "The template's requester, who pays the escrow",
+    )]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(6, name = "config", desc = "The program config, writable for an open task")]
+    #[account(7, writable, name = "registry", desc = "The program registry")]
+    #[account(8, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(
+        9, writable, optional, name = "requester",
+        desc = "The payer's requester account, while the config rate limits them",
+    )]
+    #[account(
+        10, optional, name = "allowlist",
+        desc = "The agent's allowlist, once it has entries",
+    )]
+    CreateTaskFromTemplate {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        template: Pubkey,
+        payment_amount: Option<u64>,
+    },
+    // Creates a recurring task, numbered by the signing requester's
+    // recurring task counter, funded with `funding` lamports from them. Its
+    // first run is due at once.
+    #[account(0, writable, name = "recurring", desc = "The requester's next recurring task PDA")]
+    #[account(1, writable, name = "requester", desc = "The wallet's requester account")]
+    #[account(2, writable, signer, name = "owner", desc = "The requester's wallet, which funds it")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    CreateRecurringTask {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        agent: Pubkey,
+        requirements: ComputeRequirements,
+        payment_per_run: u64,
+        interval_seconds: i64,
+        funding: u64,
+    },
+    // Creates a recurring task's due run as a task. Anyone may send it: the
+    // funding reimburses the signer's rent outlay and pays them the config's
+    // crank_reward.
+    #[account(0, writable, name = "recurring", desc = "The recurring task")]
+    #[account(1, writable, name = "task", desc = "The task, the agent's next task PDA")]
+    #[account(
+        2, writable, name = "agent",
+        desc = "The agent, or the default pubkey to list an open task",
+    )]
+    #[account(3, writable, signer, name = "payer", desc = "The cranker, who fronts the run's rent")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(6, name = "config", desc = "The program config, writable for an open task")]
+    #[account(7, writable, name = "registry", desc = "The program registry")]
+    #[account(8, writable, name = "task_page", desc = "The agent's page of its task index")]
+    #[account(9, optional, name = "allowlist", desc = "The agent's allowlist, once it has entries")]
+    CrankRecurringTask,
+    // Cancels a recurring task, returning its funding and rent to its
+    // requester. Runs already created are unaffected.
+    #[account(0, writable, name = "recurring", desc = "The recurring task")]
+    #[account(1, writable, signer, name = "owner", desc = "The recurring task's requester")]
+    CancelRecurringTask,
+    // Divides a pending lamport task into open-listed shards, one per share
+    // of basis points summing to 10_000, each escrowing that share of the
+    // payment. Signed by the requester. Each shard's task, vault and task
+    // page follow the accounts below.
+    #[account(0, writable, name = "task", desc = "The pending task")]
+    #[account(1, writable, name = "agent", desc = "The task's agent")]
+    #[account(2, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(3, writable, signer, name = "requester", desc = "The task's requester")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, writable, name = "config", desc = "The program config")]
+    #[account(6, writable, name = "registry", desc = "The program registry")]
+    SplitTask { shares: Vec<u16> },
+    // Completes a split task once every shard has completed, with the hash
+    // of the shards' result hashes in shard order. Anyone may send it. The
+    // shards follow the task.
+    #[account(0, writable, name = "task", desc = "The split task")]
+    AggregateResults,
+    // Replaces a failed or cancelled shard with a fresh open-listed one,
+    // escrowing its payment again from the requester
+    #[account(0, name = "task", desc = "The split task")]
+    #[account(1, writable, name = "failed_shard", desc = "The failed or cancelled shard")]
+    #[account(2, writable, signer, name = "requester", desc = "The task's requester")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "config", desc = "The program config")]
+    #[account(5, writable, name = "registry", desc = "The program registry")]
+    #[account(6, writable, name = "shard", desc = "The next open task PDA")]
+    #[account(7, writable, name = "shard_vault", desc = "The shard's escrow vault")]
+    #[account(8, writable, name = "shard_page", desc = "The open listings' task index page")]
+    ReissueShard,
+    // Escrows `payment` for each of `replicas` distinct agents to run the
+    // same job, paid if `quorum` of them, a majority, agree on the result.
+    // Numbered by the signing requester's redundant task counter.
+    #[account(0, writable, name = "task", desc = "The requester's next redundant task PDA")]
+    #[account(1, writable, name = "requester", desc = "The wallet's requester account")]
+    #[account(2, writable, signer, name = "owner", desc = "The requester's wallet, which pays")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "config", desc = "The program config")]
+    CreateRedundantTask {
+        requirements: ComputeRequirements,
+        payment: u64,
+        replicas: u8,
+        quorum: u8,
+    },
+    // Records a qualified agent's result, signed by its owner or operator,
+    // until the task expires
+    #[account(0, writable, name = "task", desc = "The redundant task")]
+    #[account(1, name = "agent", desc = "The submitting agent")]
+    #[account(2, signer, name = "signer", desc = "The agent's owner or operator")]
+    #[account(3, name = "config", desc = "The program config")]
+    SubmitRedundantResult {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        result_hash: [u8; 32],
+    },
+    // Settles a redundant task once a quorum agrees, or every replica has
+    // submitted or REDUNDANT_TASK_EXPIRY_SECONDS have passed without one.
+    // Agreeing agents are paid; dissenting agents take the reputation
+    // penalty. Anyone may send it. Each submitting agent and its owner
+    // follow the accounts below, then the holder's token account for an
+    // agent bound to an NFT.
+    #[account(0, writable, name = "task", desc = "The redundant task")]
+    #[account(1, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(2, writable, name = "requester", desc = "The task's requester")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    #[account(4, name = "config", desc = "The program config")]
+    #[account(5, writable, name = "treasury", desc = "The config's treasury")]
+    FinalizeRedundantTask,
+    // Sets the metadata schedulers reach the agent by, creating its
+    // metadata account on first use. Signed by the agent's owner, who pays
+    // the rent.
+    #[account(0, writable, name = "metadata", desc = "The agent's metadata PDA")]
+    #[account(1, name = "agent", desc = "The agent")]
+    #[account(2, writable, signer, name = "owner", desc = "The agent's owner, who pays the rent")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    SetAgentMetadata {
+        name: String,
+        uri: String,
+        endpoint: String,
+    },
+    // Changes a pending task's priority. Signed by its requester, who pays
+    // into the escrow whatever raising it adds to the price.
+    #[account(0, writable, name = "task", desc = "The pending task")]
+    #[account(1, writable, signer, name = "requester", desc = "The task's requester")]
+    #[account(2, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(3, name = "config", desc = "The program config")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    UpdateTaskPriority {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+        task_id: Pubkey,
+        priority: u8,
+    },
+    // Replaces the task categories the agent serves, one bit per tag code
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetAcceptedTags {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+        accepted_tags: [u8; 32],
+    },
+    // Limits withdrawals and transfers of the agent's credits to max_per_day
+    // a day, cooldown_seconds apart. Zero turns a limit off. Looser limits
+    // take effect WITHDRAWAL_POLICY_DELAY_SECONDS later.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetWithdrawalPolicy { max_per_day: u64, cooldown_seconds: i64 },
+    // Credits a newly registered agent with the config's grant amount, out of
+    // the faucet vault. Each agent can claim once, in the epoch it registered
+    // or the next.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner")]
+    #[account(2, writable, name = "credit_vault", desc = "The agent's credit vault PDA")]
+    #[account(3, writable, name = "faucet", desc = "The faucet vault PDA")]
+    #[account(4, name = "system_program", desc = "The system program")]
+    #[account(5, name = "config", desc = "The program config")]
+    ClaimGrant,
+    // Moves lamports from the admin into the faucet vault grants are paid from
+    #[account(0, name = "config", desc = "The program config")]
+    #[account(1, writable, signer, name = "admin", desc = "The config's admin")]
+    #[account(2, writable, name = "faucet", desc = "The faucet vault PDA")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    FundFaucet { amount: u64 },
+    // Sets the credits each grant gives. Zero turns grants off.
+    #[account(0, writable, name = "config", desc = "The program config")]
+    #[account(1, signer, name = "admin", desc = "The config's admin")]
+    SetGrantAmount { amount: u64 },
+    // Creates the insurance pool completions pay the config's haircut into
+    #[account(0, name = "config", desc = "The program config")]
+    #[account(1, writable, signer, name = "admin", desc = "The config's admin, who pays the rent")]
+    #[account(2, writable, name = "insurance_pool", desc = "The insurance pool PDA")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    InitializeInsurancePool,
+    // Pays the requester of a failed lamport task up to its payment amount
+    // out of the insurance pool, once, within the config's claim window. The
+    // refund the task's vault held goes to the pool up to the payout, and the
+    // rest to the requester.
+    #[account(0, writable, name = "task", desc = "The failed task")]
+    #[account(1, writable, signer, name = "requester", desc = "The task's requester")]
+    #[account(2, writable, name = "insurance_pool", desc = "The insurance pool PDA")]
+    #[account(3, name = "config", desc = "The program config")]
+    #[account(4, writable, name = "vault", desc = "The task's escrow vault")]
+    #[account(5, name = "system_program", desc = "The system program")]
+    FileInsuranceClaim,
+    // Moves `amount` lamports the pool holds past its rent reserve to the
+    // treasury
+    #[account(0, name = "config", desc = "The program config")]
+    #[account(1, signer, name = "admin", desc = "The config's admin")]
+    #[account(2, writable, name = "insurance_pool", desc = "The insurance pool PDA")]
+    #[account(3, writable, name = "treasury", desc = "The config's treasury")]
+    SweepInsurancePool { amount: u64 },
+    // Has `threshold` of `signers` sign for the agent's owner in
+    // WithdrawCredits, SetOperator, RevokeOperator and
+    // TransferAgentOwnership, creating its multisig account on first use. No
+    // signers hands those back to the owner's key. Signed by the owner, who
+    // pays the rent, or while the multisig signs for them, by its members
+    // following the accounts below.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, writable, signer, name = "owner", desc = "The agent's owner, who pays the rent")]
+    #[account(2, writable, name = "multisig", desc = "The agent's multisig PDA")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    SetMultisigOwner {
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    },
+    // Hands control of the agent to whoever holds the NFT of `mint`, in place
+    // of the owner's key or multisig. Every instruction the owner signs or is
+    // paid through then takes the holder as the owner, with their token
+    // account holding the NFT as its last account. Signed by the owner, or
+    // the holder of an NFT the agent is already bound to, whose token
+    // account follows the accounts below.
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner or NFT holder")]
+    #[account(2, name = "mint", desc = "The NFT's mint")]
+    BindAgentToNft,
+    // Returns the agent to ownership by key, making the NFT's holder its
+    // owner
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "holder", desc = "The NFT's holder")]
+    #[account(2, name = "holder_token", desc = "The holder's token account holding the NFT")]
+    UnbindAgentFromNft,
+    // Has payments in wrapped SOL reach the owner as lamports, unwrapped on
+    // completion, rather than in their wrapped SOL token account
+    #[account(0, writable, name = "agent", desc = "The agent")]
+    #[account(1, signer, name = "owner", desc = "The agent's owner")]
+    SetPreferNative { prefer_native: bool },
+    // Returns the payments of a batch's unclaimed items to its requester,
+    // once BATCH_REFUND_DELAY_SECONDS have passed since its creation, and
+    // closes it
+    #[account(0, writable, name = "batch", desc = "The batch")]
+    #[account(1, writable, signer, name = "requester", desc = "The batch's requester")]
+    #[account(2, writable, name = "vault", desc = "The batch's escrow vault")]
+    #[account(3, name = "system_program", desc = "The system program")]
+    RefundBatchTask,
+}
+
+// Compact wire format
+//
+// Data whose first byte has COMPACT_FLAG set is in the compact encoding.
+// Anything else is read as Borsh, the legacy layout, whose first byte is the
+// variant's index and so always below COMPACT_FLAG.
+//
+// A compact instruction is one byte, COMPACT_FLAG | the variant's index,
+// then the variant's fields in declaration order with nothing in between:
+// - integers little-endian at their full width, and bools as 0 or 1
+// - pubkeys and hashes as their 32 bytes
+// - no task_id: the processor takes it from the key of the account it
+//   reads the task from, which is the first one except for SubmitBid's
+//   second
+// - ComputeRequirements as a u16 bitmask of its non-default fields, bit 0
+//   for the first in declaration order, then only those fields. single_step
+//   is its bit alone.
+// - an Option as a flags byte with bit 0 set when it is Some, then the value
+// - a Vec as a u8 length, then its items. The program caps every list well
+//   below 256 items. Strings and byte payloads take a u16 length instead.
+// - AgentCapabilities and ConfigParams as Borsh, which packs them already
+//
+// CompleteTask thus takes 34 bytes instead of 66, and CreateTask with the
+// default requirements 22 instead of 57.
+pub const COMPACT_FLAG: u8 = 0x80;
+
+// ComputeRequirements fields in bitmask order
+const CPU_UNITS: u16 = 1 << 0;
+const MEMORY_MB: u16 = 1 << 1;
+const STORAGE_MB: u16 = 1 << 2;
+const MAX_TIME_SECONDS: u16 = 1 << 3;
+const MIN_REPUTATION: u16 = 1 << 4;
+const MIN_STAKE: u16 = 1 << 5;
+const SINGLE_STEP: u16 = 1 << 6;
+const GPU_UNITS: u16 = 1 << 7;
+const NETWORK_MBPS: u16 = 1 << 8;
+
+impl AIInfraInstruction {
+    // The compact encoding of the instruction
+    pub fn pack(&self) -> Vec<u8> {
+        use AIInfraInstruction::*;
+        let mut data = Writer(Vec::new());
+        let w = &mut data;
+        match self {
+            RegisterAgent => w.tag(0),
+            UpdateAgentStatus { is_active } => w.tag(1).bool(*is_active),
+            CreateTask {
+                requirements,
+                payment_amount,
+                priority,
+                tags,
+                payment_usd_cents,
+                description,
+            } => {
+                w.tag(2)
+                    .requirements(requirements)
+                    .u64(*payment_amount)
+                    .u8(*priority)
+                    .bytes(tags)
+                    .u64_option(*payment_usd_cents)
+                    .flags(description.is_some());
+                if let Some(description) = description {
+                    w.string(description);
+                }
+                w
+            }
+            StartTask { .. } => w.tag(3),
+            CompleteTask { result_hash, result_data, .. } => {
+                w.tag(4).bytes(result_hash).flags(result_data.is_some());
+                if let Some(result_data) = result_data {
+                    w.data(result_data);
+                }
+                w
+            }
+            DepositCredits { amount, expected_nonce } => {
+                w.tag(5).u64(*amount).u64_option(*expected_nonce)
+            }
+            WithdrawCredits { amount, expected_nonce } => {
+                w.tag(6).u64(*amount).u64_option(*expected_nonce)
+            }
+            CancelTask { .. } => w.tag(7),
+            FailTask { reason, .. } => w.tag(8).borsh(reason),
+            CloseTask { .. } => w.tag(9),
+            DeregisterAgent => w.tag(10),
+            TransferAgentOwnership { new_owner } => w.tag(11).bytes(new_owner.as_ref()),
+            CreateTaskWithToken { requirements, amount, mint } => {
+                w.tag(12).requirements(requirements).u64(*amount).bytes(mint.as_ref())
+            }
+            InitializeConfig { admin, params } => w.tag(13).bytes(admin.as_ref()).borsh(params),
+            UpdateConfig { admin, params } => w.tag(14).bytes(admin.as_ref()).borsh(params),
+            PauseProgram => w.tag(15),
+            UnpauseProgram => w.tag(16),
+            ClaimTask { .. } => w.tag(17),
+            SubmitBid { amount, .. } => w.tag(18).u64(*amount),
+            AcceptBid { .. } => w.tag(19),
+            CloseBid => w.tag(20),
+            ExpireTask { .. } => w.tag(21),
+            Heartbeat => w.tag(22),
+            DeactivateStaleAgent => w.tag(23),
+            StakeCollateral { amount } => w.tag(24).u64(*amount),
+            UnstakeCollateral { amount } => w.tag(25).u64(*amount),
+            DepositRequesterCredits { amount } => w.tag(26).u64(*amount),
+            WithdrawRequesterCredits { amount } => w.tag(27).u64(*amount),
+            CreateTaskWithCredits { requirements, payment_amount } => {
+                w.tag(28).requirements(requirements).u64(*payment_amount)
+            }
+            ClaimPayment { .. } => w.tag(29),
+            DisputeTask { .. } => w.tag(30),
+            ResolveDispute { uphold_result, .. } => w.tag(31).bool(*uphold_result),
+            CommitResult { commitment, .. } => w.tag(32).bytes(commitment),
+            RevealResult { result_hash, salt, .. } => w.tag(33).bytes(result_hash).bytes(salt),
+            RegisterVerifier { verifier } => w.tag(34).bytes(verifier.as_ref()),
+            VerifyResult { .. } => w.tag(35),
+            RefundUnverifiedTask { .. } => w.tag(36),
+            BatchCompleteTasks { results } => {
+                w.tag(37).len(results.len());
+                for TaskResult { task_id, result_hash } in results {
+                    w.bytes(task_id.as_ref()).bytes(result_hash);
+                }
+                w
+            }
+            SetOperator { operator } => w.tag(38).bytes(operator.as_ref()),
+            RevokeOperator => w.tag(39),
+            AddAllowedRequester { requester } => w.tag(40).bytes(requester.as_ref()),
+            RemoveAllowedRequester { requester } => w.tag(41).bytes(requester.as_ref()),
+            TransferCredits { amount, expected_nonce } => {
+                w.tag(42).u64(*amount).u64_option(*expected_nonce)
+            }
+            ApproveCredits { spender, amount } => w.tag(43).bytes(spender.as_ref()).u64(*amount),
+            RevokeAllowance => w.tag(44),
+            CreateTaskWithAllowance { requirements, payment_amount } => {
+                w.tag(45).requirements(requirements).u64(*payment_amount)
+            }
+            SetAgentLimits { max_concurrent_tasks } => w.tag(46).u32(*max_concurrent_tasks),
+            SetCapabilities { capabilities } => w.tag(47).borsh(capabilities),
+            MigrateAccount => w.tag(48),
+            InitializeRegistry => w.tag(49),
+            SetExpectedResult { expected_result_hash, .. } => {
+                w.tag(50).bytes(expected_result_hash)
+            }
+            SetAttestationKey { attestation_key } => {
+                w.tag(51).flags(attestation_key.is_some());
+                if let Some(attestation_key) = attestation_key {
+                    w.bytes(attestation_key.as_ref());
+                }
+                w
+            }
+            CreateBatchTask { task_count, payment_per_task } => {
+                w.tag(52).u32(*task_count).u64(*payment_per_task)
+            }
+            SubmitBatchResult { merkle_root } => w.tag(53).bytes(merkle_root),
+            ClaimBatchItem { index, result_hash, proof } => {
+                w.tag(54).u32(*index).bytes(result_hash).len(proof.len());
+                for node in proof {
+                    w.bytes(node);
+                }
+                w
+            }
+            SetReferrer { referrer } => w.tag(55).bytes(referrer.as_ref()),
+            TipAgent { amount } => w.tag(56).u64(*amount),
+            ReleasePayments => w.tag(57),
+            CreateTemplate { requirements, default_payment } => {
+                w.tag(58).requirements(requirements).u64(*default_payment)
+            }
+            UpdateTemplate { requirements, default_payment } => {
+                w.tag(59).requirements(requirements).u64(*default_payment)
+            }
+            CloseTemplate => w.tag(60),
+            CreateTaskFromTemplate { template, payment_amount } => {
+                w.tag(61).bytes(template.as_ref()).flags(payment_amount.is_some());
+                if let Some(payment_amount) = payment_amount {
+                    w.u64(*payment_amount);
+                }
+                w
+            }
+            CreateRecurringTask {
+                agent,
+                requirements,
+                payment_per_run,
+                interval_seconds,
+                funding,
+            } => w
+                .tag(62)
+                .bytes(agent.as_ref())
+                .requirements(requirements)
+                .u64(*payment_per_run)
+                .i64(*interval_seconds)
+                .u64(*funding),
+            CrankRecurringTask => w.tag(63),
+            CancelRecurringTask => w.tag(64),
+            SplitTask { shares } => {
+                w.tag(65).len(shares.len());
+                for share in shares {
+                    w.u16(*share);
+                }
+                w
+            }
+            AggregateResults => w.tag(66),
+            ReissueShard => w.tag(67),
+            CreateRedundantTask { requirements, payment, replicas, quorum } => {
+                w.tag(68).requirements(requirements).u64(*payment).u8(*replicas).u8(*quorum)
+            }
+            SubmitRedundantResult { result_hash } => w.tag(69).bytes(result_hash),
+            FinalizeRedundantTask => w.tag(70),
+            SetAgentMetadata { name, uri, endpoint } => {
+                w.tag(71).string(name).string(uri).string(endpoint)
+            }
+            UpdateTaskPriority { priority, .. } => w.tag(72).u8(*priority),
+            SetAcceptedTags { accepted_tags } => w.tag(73).bytes(accepted_tags),
+            SetWithdrawalPolicy { max_per_day, cooldown_seconds } => {
+                w.tag(74).u64(*max_per_day).i64(*cooldown_seconds)
+            }
+            ClaimGrant => w.tag(75),
+            FundFaucet { amount } => w.tag(76).u64(*amount),
+            SetGrantAmount { amount } => w.tag(77).u64(*amount),
+            InitializeInsurancePool => w.tag(78),
+            FileInsuranceClaim => w.tag(79),
+            SweepInsurancePool { amount } => w.tag(80).u64(*amount),
+            SetMultisigOwner { signers, threshold } => {
+                w.tag(81).len(signers.len());
+                for signer in signers {
+                    w.bytes(signer.as_ref());
+                }
+                w.u8(*threshold)
+            }
+            BindAgentToNft => w.tag(82),
+            UnbindAgentFromNft => w.tag(83),
+            SetPreferNative { prefer_native } => w.tag(84).bool(*prefer_native),
+            RefundBatchTask => w.tag(85),
+        };
+        data.0
+    }
+
+    // Decodes instruction data in either encoding. `account_key` gives the
+    // key of the instruction's account at an index, which compact data takes
+    // its task_id from.
+    pub fn unpack(
+        data: &[u8],
+        account_key: impl Fn(usize) -> Option<Pubkey>,
+    ) -> Result<Self, ProgramError> {
+        use AIInfraInstruction::*;
+        let tag = match data.first() {
+            Some(&tag) if tag & COMPACT_FLAG != 0 => tag & !COMPACT_FLAG,
+            _ => return Self::unpack_borsh(data),
+        };
+        let task_id = |index| account_key(index).ok_or(ProgramError::NotEnoughAccountKeys);
+        let r = &mut Reader(&data[1..]);
+        let instruction = match tag {
+            0 => RegisterAgent,
+            1 => UpdateAgentStatus { is_active: r.bool()? },
+            2 => CreateTask {
+                requirements: r.requirements()?,
+                payment_amount: r.u64()?,
+                priority: r.u8()?,
+                tags: r.bytes()?,
+                // Data from before USD pricing ends after the tags, and from
+                // before descriptions after the USD price
+                payment_usd_cents: if r.0.is_empty() { None } else { r.option(Reader::u64)? },
+                description: if r.0.is_empty() { None } else { r.option(Reader::string)? },
+            },
+            3 => StartTask { task_id: task_id(0)? },
+            4 => CompleteTask {
+                task_id: task_id(0)?,
+                result_hash: r.bytes()?,
+                result_data: r.option(Reader::data)?,
+            },
+            5 => DepositCredits { amount: r.u64()?, expected_nonce: r.option(Reader::u64)? },
+            6 => WithdrawCredits { amount: r.u64()?, expected_nonce: r.option(Reader::u64)? },
+            7 => CancelTask { task_id: task_id(0)? },
+            8 => FailTask { task_id: task_id(0)?, reason: r.borsh()? },
+            9 => CloseTask { task_id: task_id(0)? },
+            10 => DeregisterAgent,
+            11 => TransferAgentOwnership { new_owner: r.pubkey()? },
+            12 => CreateTaskWithToken {
+                requirements: r.requirements()?,
+                amount: r.u64()?,
+                mint: r.pubkey()?,
+            },
+            13 => InitializeConfig { admin: r.pubkey()?, params: r.borsh()? },
+            14 => UpdateConfig { admin: r.pubkey()?, params: r.borsh()? },
+            15 => PauseProgram,
+            16 => UnpauseProgram,
+            17 => ClaimTask { task_id: task_id(0)? },
+            18 => SubmitBid { task_id: task_id(1)?, amount: r.u64()? },
+            19 => AcceptBid { task_id: task_id(0)? },
+            20 => CloseBid,
+            21 => ExpireTask { task_id: task_id(0)? },
+            22 => Heartbeat,
+            23 => DeactivateStaleAgent,
+            24 => StakeCollateral { amount: r.u64()? },
+            25 => UnstakeCollateral { amount: r.u64()? },
+            26 => DepositRequesterCredits { amount: r.u64()? },
+            27 => WithdrawRequesterCredits { amount: r.u64()? },
+            28 => CreateTaskWithCredits {
+                requirements: r.requirements()?,
+                payment_amount: r.u64()?,
+            },
+            29 => ClaimPayment { task_id: task_id(0)? },
+            30 => DisputeTask { task_id: task_id(0)? },
+            31 => ResolveDispute { task_id: task_id(0)?, uphold_result: r.bool()? },
+            32 => CommitResult { task_id: task_id(0)?, commitment: r.bytes()? },
+            33 => RevealResult {
+                task_id: task_id(0)?,
+                result_hash: r.bytes()?,
+                salt: r.bytes()?,
+            },
+            34 => RegisterVerifier { verifier: r.pubkey()? },
+            35 => VerifyResult { task_id: task_id(0)? },
+            36 => RefundUnverifiedTask { task_id: task_id(0)? },
+            37 => BatchCompleteTasks {
+                results: r.list(|r| {
+                    Ok(TaskResult { task_id: r.pubkey()?, result_hash: r.bytes()? })
+                })?,
+            },
+            38 => SetOperator { operator: r.pubkey()? },
+            39 => RevokeOperator,
+            40 => AddAllowedRequester { requester: r.pubkey()? },
+            41 => RemoveAllowedRequester { requester: r.pubkey()? },
+            42 => TransferCredits { amount: r.u64()?, expected_nonce: r.option(Reader::u64)? },
+            43 => ApproveCredits { spender: r.pubkey()?, amount: r.u64()? },
+            44 => RevokeAllowance,
+            45 => CreateTaskWithAllowance {
+                requirements: r.requirements()?,
+                payment_amount: r.u64()?,
+            },
+            46 => SetAgentLimits { max_concurrent_tasks: r.u32()? },
+            47 => SetCapabilities { capabilities: r.borsh()? },
+            48 => MigrateAccount,
+            49 => InitializeRegistry,
+            50 => SetExpectedResult { task_id: task_id(0)?, expected_result_hash: r.bytes()? },
+            51 => SetAttestationKey { attestation_key: r.option(Reader::pubkey)? },
+            52 => CreateBatchTask { task_count: r.u32()?, payment_per_task: r.u64()? },
+            53 => SubmitBatchResult { merkle_root: r.bytes()? },
+            54 => ClaimBatchItem {
+                index: r.u32()?,
+                result_hash: r.bytes()?,
+                proof: r.list(Reader::bytes)?,
+            },
+            55 => SetReferrer { referrer: r.pubkey()? },
+            56 => TipAgent { amount: r.u64()? },
+            57 => ReleasePayments,
+            58 => CreateTemplate { requirements: r.requirements()?, default_payment: r.u64()? },
+            59 => UpdateTemplate { requirements: r.requirements()?, default_payment: r.u64()? },
+            60 => CloseTemplate,
+            61 => CreateTaskFromTemplate {
+                template: r.pubkey()?,
+                payment_amount: r.option(Reader::u64)?,
+            },
+            62 => CreateRecurringTask {
+                agent: r.pubkey()?,
+                requirements: r.requirements()?,
+                payment_per_run: r.u64()?,
+                interval_seconds: r.i64()?,
+                funding: r.u64()?,
+            },
+            63 => CrankRecurringTask,
+            64 => CancelRecurringTask,
+            65 => SplitTask { shares: r.list(Reader::u16)? },
+            66 => AggregateResults,
+            67 => ReissueShard,
+            68 => CreateRedundantTask {
+                requirements: r.requirements()?,
+                payment: r.u64()?,
+                replicas: r.u8()?,
+                quorum: r.u8()?,
+            },
+            69 => SubmitRedundantResult { result_hash: r.bytes()? },
+            70 => FinalizeRedundantTask,
+            71 => SetAgentMetadata { name: r.string()?, uri: r.string()?, endpoint: r.string()? },
+            72 => UpdateTaskPriority { task_id: task_id(0)?, priority: r.u8()? },
+            73 => SetAcceptedTags { accepted_tags: r.bytes()? },
+            74 => SetWithdrawalPolicy { max_per_day: r.u64()?, cooldown_seconds: r.i64()? },
+            75 => ClaimGrant,
+            76 => FundFaucet { amount: r.u64()? },
+            77 => SetGrantAmount { amount: r.u64()? },
+            78 => InitializeInsurancePool,
+            79 => FileInsuranceClaim,
+            80 => SweepInsurancePool { amount: r.u64()? },
+            81 => SetMultisigOwner { signers: r.list(Reader::pubkey)?, threshold: r.u8()? },
+            82 => BindAgentToNft,
+            83 => UnbindAgentFromNft,
+            84 => SetPreferNative { prefer_native: r.bool()? },
+            85 => RefundBatchTask,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        if !r.0.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(instruction)
+    }
+
+    // Reads the Borsh layout. The credit operations took no expected_nonce
+    // at first, and their data from then ends after the amount, which reads
+    // as expecting none. Likewise CreateTask took no priority, tags, USD
+    // price or description at first, and its data from then reads as the
+    // lowest priority, untagged, priced in lamports and undescribed.
+    // CompleteTask data from before inline results reads as carrying none.
+    fn unpack_borsh(data: &[u8]) -> Result<Self, ProgramError> {
+        let instruction = match data {
+            [4, fields @ ..] if fields.len() == 64 => Self::try_from_slice(&[data, &[0]].concat()),
+            [5 | 6 | 42, amount @ ..] if amount.len() == 8 => {
+                Self::try_from_slice(&[data, &[0]].concat())
+            }
+            [2, fields @ ..] if fields.len() == ComputeRequirements::LEN + 8 => {
+                Self::try_from_slice(&[data, &[0; 1 + TASK_TAGS + 2]].concat())
+            }
+            [2, fields @ ..] if fields.len() == ComputeRequirements::LEN + 9 => {
+                Self::try_from_slice(&[data, &[0; TASK_TAGS + 2]].concat())
+            }
+            [2, fields @ ..] if fields.len() == ComputeRequirements::LEN + 9 + TASK_TAGS => {
+                Self::try_from_slice(&[data, &[0; 2]].concat())
+            }
+            // Data from before descriptions ends after a USD price, which a
+            // described task's data can be as long as
+            [2, ..] => Self::try_from_slice(data)
+                .or_else(|_| Self::try_from_slice(&[data, &[0]].concat())),
+            _ => Self::try_from_slice(data),
+        };
+        Ok(instruction?)
+    }
+}
+
+// Appends fields in the compact encoding
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn tag(&mut self, index: u8) -> &mut Self {
+        self.u8(COMPACT_FLAG | index)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes(&[value])
+    }
+
+    fn bool(&mut self, value: bool) -> &mut Self {
+        self.u8(value as u8)
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn i64(&mut self, value: i64) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn flags(&mut self, is_some: bool) -> &mut Self {
+        self.bool(is_some)
+    }
+
+    fn u64_option(&mut self, value: Option<u64>) -> &mut Self {
+        self.flags(value.is_some());
+        if let Some(value) = value {
+            self.u64(value);
+        }
+        self
+    }
+
+    fn len(&mut self, len: usize) -> &mut Self {
+        self.u8(u8::try_from(len).expect("compact lists hold at most 255 items"))
+    }
+
+    fn string(&mut self, value: &str) -> &mut Self {
+        self.data(value.as_bytes())
+    }
+
+    fn data(&mut self, value: &[u8]) -> &mut Self {
+        let len = u16::try_from(value.len()).expect("compact payloads hold at most 65535 bytes");
+        self.u16(len).bytes(value)
+    }
+
+    fn borsh(&mut self, value: &impl BorshSerialize) -> &mut Self {
+        value.serialize(&mut self.0).unwrap();
+        self
+    }
+
+    fn requirements(&mut self, requirements: &ComputeRequirements) -> &mut Self {
+        let mut mask = 0;
+        let mut fields = Writer(Vec::new());
+        let leading = [
+            (CPU_UNITS, requirements.cpu_units),
+            (MEMORY_MB, requirements.memory_mb),
+            (STORAGE_MB, requirements.storage_mb),
+            (MAX_TIME_SECONDS, requirements.max_time_seconds),
+            (MIN_REPUTATION, requirements.min_reputation),
+        ];
+        for (bit, value) in leading {
+            if value != 0 {
+                mask |= bit;
+                fields.u32(value);
+            }
+        }
+        if requirements.min_stake != 0 {
+            mask |= MIN_STAKE;
+            fields.u64(requirements.min_stake);
+        }
+        if requirements.single_step {
+            mask |= SINGLE_STEP;
+        }
+        let trailing = [
+            (GPU_UNITS, requirements.gpu_units),
+            (NETWORK_MBPS, requirements.network_mbps),
+        ];
+        for (bit, value) in trailing {
+            if value != 0 {
+                mask |= bit;
+                fields.u32(value);
+            }
+        }
+        self.u16(mask).bytes(&fields.0)
+    }
+}
+
+// Reads fields in the compact encoding off the front of the data
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], ProgramError> {
+        if self.0.len() < N {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, ProgramError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, ProgramError> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProgramError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProgramError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn i64(&mut self) -> Result<i64, ProgramError> {
+        Ok(i64::from_le_bytes(self.bytes()?))
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::new_from_array(self.bytes()?))
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ProgramError>,
+    ) -> Result<Option<T>, ProgramError> {
+        match self.bool()? {
+            true => Ok(Some(read(self)?)),
+            false => Ok(None),
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, ProgramError>,
+    ) -> Result<Vec<T>, ProgramError> {
+        let len = self.u8()?;
+        (0..len).map(|_| read(self)).collect()
+    }
+
+    // Strings that aren't UTF-8 don't decode
+    fn string(&mut self) -> Result<String, ProgramError> {
+        String::from_utf8(self.data()?).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    fn data(&mut self) -> Result<Vec<u8>, ProgramError> {
+        let len = self.u16()? as usize;
+        if self.0.len() < len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head.to_vec())
+    }
+
+    fn borsh<T: BorshDeserialize>(&mut self) -> Result<T, ProgramError> {
+        Ok(T::deserialize(&mut self.0)?)
+    }
+
+    fn requirements(&mut self) -> Result<ComputeRequirements, ProgramError> {
+        let mask = self.u16()?;
+        if mask >= NETWORK_MBPS << 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let u32_field = |r: &mut Self, bit| if mask & bit != 0 { r.u32() } else { Ok(0) };
+        Ok(ComputeRequirements {
+            cpu_units: u32_field(self, CPU_UNITS)?,
+            memory_mb: u32_field(self, MEMORY_MB)?,
+            storage_mb: u32_field(self, STORAGE_MB)?,
+            max_time_seconds: u32_field(self, MAX_TIME_SECONDS)?,
+            min_reputation: u32_field(self, MIN_REPUTATION)?,
+            min_stake: if mask & MIN_STAKE != 0 { self.u64()? } else { 0 },
+            single_step: mask & SINGLE_STEP != 0,
+            gpu_units: u32_field(self, GPU_UNITS)?,
+            network_mbps: u32_field(self, NETWORK_MBPS)?,
+        })
+    }
+}
+
+// Builders for off-chain clients. Each lists its accounts in the order the
+// processor reads them, and encodes the instruction compactly.
+
+fn new_instruction(
+    program_id: &Pubkey,
+    instruction: &AIInfraInstruction,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), accounts)
+}
+
+// RegisterAgent for `agent`, the PDA derived from `owner`, who pays its rent
+pub fn register_agent(program_id: &Pubkey, agent: &Pubkey, owner: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::RegisterAgent,
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_registry_address(program_id).0, false),
+        ],
+    )
+}
+
+// RegisterAgent for `owner`'s agent, preceded by `payer` funding the agent's
+// rent. The program allocates the pre-funded account in place, so the owner
+// only signs and needn't hold any lamports.
+pub fn build_register_agent_tx(
+    program_id: &Pubkey,
+    rent: &Rent,
+    payer: &Pubkey,
+    owner: &Pubkey,
+) -> Vec<Instruction> {
+    let (agent, _) = find_agent_address(owner, program_id);
+    vec![
+        system_instruction::transfer(payer, &agent, rent.minimum_balance(AIAgent::LEN)),
+        register_agent(program_id, &agent, owner),
+    ]
+}
+
+pub fn update_agent_status(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    is_active: bool,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::UpdateAgentStatus { is_active },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn set_agent_limits(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    max_concurrent_tasks: u32,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetAgentLimits { max_concurrent_tasks },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn set_capabilities(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    capabilities: AgentCapabilities,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetCapabilities { capabilities },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+// SetAcceptedTags for an agent serving the categories in `tags`, or every
+// category when `tags` is empty
+pub fn set_accepted_tags(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    tags: &[u8],
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetAcceptedTags { accepted_tags: tag_set(tags) },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn set_withdrawal_policy(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    policy: WithdrawalPolicy,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetWithdrawalPolicy {
+            max_per_day: policy.max_per_day,
+            cooldown_seconds: policy.cooldown_seconds,
+        },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn set_attestation_key(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    attestation_key: Option<Pubkey>,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetAttestationKey { attestation_key },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+// MigrateAccount for any program account, with `payer` covering the rent of
+// the larger layout
+pub fn migrate_account(program_id: &Pubkey, account: &Pubkey, payer: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::MigrateAccount,
+        vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// InitializeRegistry with `payer` covering the registry's rent
+pub fn initialize_registry(program_id: &Pubkey, payer: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::InitializeRegistry,
+        vec![
+            AccountMeta::new(find_registry_address(program_id).0, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// CreateTask for `task`, the agent's task PDA numbered `index` by its next
+// counter value, with `payer` escrowing the payment. The default pubkey as
+// `agent` lists an open task numbered by the config instead.
+pub fn create_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    payer: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+) -> Instruction {
+    create_task_with_priority(
+        program_id,
+        task,
+        agent,
+        index,
+        payer,
+        requirements,
+        payment_amount,
+        0,
+    )
+}
+
+// create_task for an untagged task at `priority`
+#[allow(clippy::too_many_arguments)]
+pub fn create_task_with_priority(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    payer: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+    priority: u8,
+) -> Instruction {
+    create_tagged_task(
+        program_id,
+        task,
+        agent,
+        index,
+        payer,
+        requirements,
+        payment_amount,
+        priority,
+        [0; TASK_TAGS],
+        None,
+    )
+}
+
+// create_task for a task at `priority` tagged with `tags`. A `description`
+// is written to a memo, for which the memo program follows the accounts
+// create_task lists.
+#[allow(clippy::too_many_arguments)]
+pub fn create_tagged_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    payer: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+    priority: u8,
+    tags: [u8; TASK_TAGS],
+    description: Option<&str>,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let config = if *agent == Pubkey::default() {
+        AccountMeta::new(config, false)
+    } else {
+        AccountMeta::new_readonly(config, false)
+    };
+    let mut instruction = new_instruction(
+        program_id,
+        &AIInfraInstruction::CreateTask {
+            requirements,
+            payment_amount,
+            priority,
+            tags,
+            payment_usd_cents: None,
+            description: description.map(str::to_string),
+        },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_vault_address(task, program_id).0, false),
+            config,
+            AccountMeta::new(find_registry_address(program_id).0, false),
+            AccountMeta::new(
+                find_task_page_address(agent, index / TASKS_PER_PAGE, program_id).0,
+                false,
+            ),
+            AccountMeta::new(find_requester_address(payer, program_id).0, false),
+        ],
+    );
+    if description.is_some() {
+        instruction.accounts.push(AccountMeta::new_readonly(spl_memo::id(), false));
+    }
+    instruction
+}
+
+// create_task for a task priced at `payment_usd_cents`, escrowing however
+// many lamports `oracle`, the config's price oracle, says that is
+#[allow(clippy::too_many_arguments)]
+pub fn create_task_in_usd(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    payer: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_usd_cents: u64,
+    oracle: &Pubkey,
+) -> Instruction {
+    let mut instruction =
+        create_task(program_id, task, agent, index, payer, requirements.clone(), 0);
+    instruction.data = AIInfraInstruction::CreateTask {
+        requirements,
+        payment_amount: 0,
+        priority: 0,
+        tags: [0; TASK_TAGS],
+        payment_usd_cents: Some(payment_usd_cents),
+        description: None,
+    }
+    .pack();
+    instruction.accounts.push(AccountMeta::new_readonly(*oracle, false));
+    instruction
+}
+
+// CreateTask for the task numbered `index`, preceded by `payer` funding its
+// rent and, when it opens a new page of the agent's task index, the page's.
+// The requester then only pays the escrow.
+#[allow(clippy::too_many_arguments)]
+pub fn build_create_task_tx(
+    program_id: &Pubkey,
+    rent: &Rent,
+    payer: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    requester: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+) -> Vec<Instruction> {
+    let (task, _) = find_task_address(agent, index, program_id);
+    let mut instructions = vec![system_instruction::transfer(
+        payer,
+        &task,
+        rent.minimum_balance(ComputeTask::LEN),
+    )];
+    let (page, offset) = (index / TASKS_PER_PAGE, index % TASKS_PER_PAGE);
+    if offset == 0 {
+        let (page, _) = find_task_page_address(agent, page, program_id);
+        instructions.push(system_instruction::transfer(
+            payer,
+            &page,
+            rent.minimum_balance(TaskPage::LEN),
+        ));
+    }
+    instructions.push(create_task(
+        program_id,
+        &task,
+        agent,
+        index,
+        requester,
+        requirements,
+        payment_amount,
+    ));
+    instructions
+}
+
+// SetExpectedResult signed by the task's requester
+pub fn set_expected_result(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    requester: &Pubkey,
+    expected_result_hash: [u8; 32],
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetExpectedResult { task_id: *task, expected_result_hash },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new_readonly(*requester, true),
+        ],
+    )
+}
+
+// StartTask signed by the agent's owner or operator
+pub fn start_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::StartTask { task_id: *task },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new_readonly(*agent, false),
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// CompleteTask for a lamport task, signed by the agent's owner or operator.
+// An owner's completion pays them out of the escrow, less the protocol fee
+// sent to `treasury`, unless the config holds payments. Token tasks append
+// their token accounts to these.
+pub fn complete_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+    requester: &Pubkey,
+    treasury: &Pubkey,
+    result_hash: [u8; 32],
+) -> Instruction {
+    complete_task_instruction(
+        program_id,
+        task,
+        agent,
+        signer,
+        requester,
+        treasury,
+        result_hash,
+        None,
+    )
+}
+
+// complete_task storing `result_data` on the task, whose hash is the result.
+// The signer pays the rent on the bytes it adds to the task account.
+pub fn complete_task_with_data(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+    requester: &Pubkey,
+    treasury: &Pubkey,
+    result_data: Vec<u8>,
+) -> Instruction {
+    let result_hash = hash(&result_data).to_bytes();
+    complete_task_instruction(
+        program_id,
+        task,
+        agent,
+        signer,
+        requester,
+        treasury,
+        result_hash,
+        Some(result_data),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn complete_task_instruction(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+    requester: &Pubkey,
+    treasury: &Pubkey,
+    result_hash: [u8; 32],
+    result_data: Option<Vec<u8>>,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CompleteTask { task_id: *task, result_hash, result_data },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*signer, true),
+            AccountMeta::new(find_vault_address(task, program_id).0, false),
+            AccountMeta::new(*requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(*treasury, false),
+        ],
+    )
+}
+
+// Appends the instructions sysvar to a completion, which an agent with an
+// attestation key needs to find its ed25519 attestation
+pub fn with_attestation(mut instruction: Instruction) -> Instruction {
+    instruction.accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+    instruction
+}
+
+// Appends the accounts that settling a task's payment needs while the config
+// sets a referral share: the requester's account and, when it names a
+// referrer, the referrer's wallet, or token account for token tasks. They
+// follow any token accounts and go before with_attestation's sysvar.
+pub fn with_referral(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    requester: &Pubkey,
+    referrer: Option<&Pubkey>,
+) -> Instruction {
+    let (requester_account, _) = find_requester_address(requester, program_id);
+    instruction.accounts.push(AccountMeta::new_readonly(requester_account, false));
+    if let Some(referrer) = referrer {
+        instruction.accounts.push(AccountMeta::new(*referrer, false));
+    }
+    instruction
+}
+
+// Appends the agent's credit vault, where settling a lamport payment sends
+// the agent's share while the config sets a hold period. It follows any
+// referral accounts and goes before with_attestation's sysvar.
+pub fn with_payment_hold(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    agent: &Pubkey,
+) -> Instruction {
+    let (credit_vault, _) = find_credit_vault_address(agent, program_id);
+    instruction.accounts.push(AccountMeta::new(credit_vault, false));
+    instruction
+}
+
+// Appends the agent's multisig and the `members` of it signing for the
+// owner, who then needn't sign, to one of the instructions a multisig can
+// sign. Each lists the owner as its first signer.
+pub fn with_multisig(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    members: &[Pubkey],
+) -> Instruction {
+    let (multisig, _) = find_multisig_address(agent, program_id);
+    instruction.accounts.push(AccountMeta::new_readonly(multisig, false));
+    with_multisig_members(instruction, members)
+}
+
+// Appends the `members` of the agent's multisig signing for the owner, who
+// then needn't sign, to a SetMultisigOwner, which already lists the multisig
+pub fn with_multisig_members(mut instruction: Instruction, members: &[Pubkey]) -> Instruction {
+    if let Some(owner) = instruction.accounts.iter_mut().find(|account| account.is_signer) {
+        owner.is_signer = false;
+    }
+    let members = members.iter().map(|member| AccountMeta::new_readonly(*member, true));
+    instruction.accounts.extend(members);
+    instruction
+}
+
+// Appends the token account through which `owner`, the holder of the NFT
+// the agent is bound to, acts for it or is paid. It goes last, after any
+// other accounts appended.
+pub fn with_nft_holder(mut instruction: Instruction, holder_token: &Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new_readonly(*holder_token, false));
+    instruction
+}
+
+// Appends the vault of the failed `task` and the system program to a
+// CloseTask the requester signs, which returns the refund the vault held for
+// an insurance claim to them
+pub fn with_held_refund(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    task: &Pubkey,
+) -> Instruction {
+    instruction.accounts[2].is_writable = true;
+    let (vault, _) = find_vault_address(task, program_id);
+    instruction.accounts.push(AccountMeta::new(vault, false));
+    instruction.accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    instruction
+}
+
+// Appends the insurance pool, which settling a lamport payment pays the
+// config's insurance haircut into. It follows with_payment_hold's vault.
+pub fn with_insurance_pool(mut instruction: Instruction, program_id: &Pubkey) -> Instruction {
+    let (insurance_pool, _) = find_insurance_pool_address(program_id);
+    instruction.accounts.push(AccountMeta::new(insurance_pool, false));
+    instruction
+}
+
+// TipAgent paying `amount` lamports from `tipper` to `owner`, the owner of
+// the agent that completed `task`
+pub fn tip_agent(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    tipper: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::TipAgent { amount },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*tipper, true),
+            AccountMeta::new(*owner, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// CreateBatchTask for `batch`, the agent's batch PDA numbered `index` by its
+// next counter value, with `requester` escrowing every item's payment
+pub fn create_batch_task(
+    program_id: &Pubkey,
+    batch: &Pubkey,
+    agent: &Pubkey,
+    requester: &Pubkey,
+    task_count: u32,
+    payment_per_task: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CreateBatchTask { task_count, payment_per_task },
+        vec![
+            AccountMeta::new(*batch, false),
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_vault_address(batch, program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// SubmitBatchResult signed by the agent's owner or operator
+pub fn submit_batch_result(
+    program_id: &Pubkey,
+    batch: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+    merkle_root: [u8; 32],
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SubmitBatchResult { merkle_root },
+        vec![
+            AccountMeta::new(*batch, false),
+            AccountMeta::new_readonly(*agent, false),
+            AccountMeta::new_readonly(*signer, true),
+        ],
+    )
+}
+
+// ClaimBatchItem signed by the agent's owner, who is paid less the protocol
+// fee sent to `treasury`
+#[allow(clippy::too_many_arguments)]
+pub fn claim_batch_item(
+    program_id: &Pubkey,
+    batch: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    treasury: &Pubkey,
+    requester: &Pubkey,
+    index: u32,
+    result_hash: [u8; 32],
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::ClaimBatchItem { index, result_hash, proof },
+        vec![
+            AccountMeta::new(*batch, false),
+            AccountMeta::new_readonly(*agent, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(find_vault_address(batch, program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(*treasury, false),
+            AccountMeta::new(*requester, false),
+        ],
+    )
+}
+
+// RefundBatchTask signed by the batch's requester
+pub fn refund_batch_task(program_id: &Pubkey, batch: &Pubkey, requester: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::RefundBatchTask,
+        vec![
+            AccountMeta::new(*batch, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new(find_vault_address(batch, program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// Accounts shared by DepositCredits and WithdrawCredits
+fn credit_accounts(program_id: &Pubkey, agent: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*agent, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(find_credit_vault_address(agent, program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ]
+}
+
+// DepositCredits moving `amount` lamports from the owner into the agent's
+// credit vault
+pub fn deposit_credits(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    expected_nonce: Option<u64>,
+) -> Instruction {
+    let mut accounts = credit_accounts(program_id, agent, owner);
+    accounts.push(AccountMeta::new(find_registry_address(program_id).0, false));
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::DepositCredits { amount, expected_nonce },
+        accounts,
+    )
+}
+
+// WithdrawCredits paying `amount` lamports from the credit vault to the owner
+pub fn withdraw_credits(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    expected_nonce: Option<u64>,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::WithdrawCredits { amount, expected_nonce },
+        credit_accounts(program_id, agent, owner),
+    )
+}
+
+// ClaimGrant crediting the agent owned by `owner` from the faucet vault
+pub fn claim_grant(program_id: &Pubkey, agent: &Pubkey, owner: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::ClaimGrant,
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(find_credit_vault_address(agent, program_id).0, false),
+            AccountMeta::new(find_faucet_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// FundFaucet moving `amount` lamports from the admin into the faucet vault
+pub fn fund_faucet(program_id: &Pubkey, admin: &Pubkey, amount: u64) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::FundFaucet { amount },
+        vec![
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(find_faucet_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+pub fn set_grant_amount(program_id: &Pubkey, admin: &Pubkey, amount: u64) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetGrantAmount { amount },
+        vec![
+            AccountMeta::new(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*admin, true),
+        ],
+    )
+}
+
+pub fn initialize_insurance_pool(program_id: &Pubkey, admin: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::InitializeInsurancePool,
+        vec![
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(find_insurance_pool_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// FileInsuranceClaim for the failed `task`, paid to its `requester`
+pub fn file_insurance_claim(program_id: &Pubkey, task: &Pubkey, requester: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::FileInsuranceClaim,
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new(find_insurance_pool_address(program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(find_vault_address(task, program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// SetMultisigOwner putting the agent owned by `owner` under `threshold` of
+// `signers`
+pub fn set_multisig_owner(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    signers: &[Pubkey],
+    threshold: u8,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetMultisigOwner { signers: signers.to_vec(), threshold },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(find_multisig_address(agent, program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// BindAgentToNft handing the agent owned by `owner` to the holder of `mint`
+pub fn bind_agent_to_nft(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::BindAgentToNft,
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+    )
+}
+
+// UnbindAgentFromNft making `holder`, whose `holder_token` holds the NFT, the
+// agent's owner
+pub fn unbind_agent_from_nft(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    holder: &Pubkey,
+    holder_token: &Pubkey,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::UnbindAgentFromNft,
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*holder, true),
+            AccountMeta::new_readonly(*holder_token, false),
+        ],
+    )
+}
+
+pub fn set_prefer_native(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    prefer_native: bool,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetPreferNative { prefer_native },
+        vec![
+            AccountMeta::new(*agent, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+// SweepInsurancePool moving `amount` lamports from the pool to `treasury`
+pub fn sweep_insurance_pool(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    treasury: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SweepInsurancePool { amount },
+        vec![
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_insurance_pool_address(program_id).0, false),
+            AccountMeta::new(*treasury, false),
+        ],
+    )
+}
+
+// ReleasePayments crediting `agent` with its matured pending payments
+pub fn release_payments(program_id: &Pubkey, agent: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::ReleasePayments,
+        vec![AccountMeta::new(*agent, false)],
+    )
+}
+
+// SetReferrer signed by `owner`, who pays for their requester account if it
+// doesn't exist yet
+pub fn set_referrer(program_id: &Pubkey, owner: &Pubkey, referrer: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetReferrer { referrer: *referrer },
+        vec![
+            AccountMeta::new(find_requester_address(owner, program_id).0, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// CreateTemplate for `template`, the requester's template numbered `index`
+// by their next counter value. `requester` pays for their requester account
+// if it doesn't exist yet.
+pub fn create_template(
+    program_id: &Pubkey,
+    template: &Pubkey,
+    requester: &Pubkey,
+    requirements: ComputeRequirements,
+    default_payment: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CreateTemplate { requirements, default_payment },
+        vec![
+            AccountMeta::new(*template, false),
+            AccountMeta::new(find_requester_address(requester, program_id).0, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// UpdateTemplate signed by the template's requester
+pub fn update_template(
+    program_id: &Pubkey,
+    template: &Pubkey,
+    requester: &Pubkey,
+    requirements: ComputeRequirements,
+    default_payment: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::UpdateTemplate { requirements, default_payment },
+        vec![AccountMeta::new(*template, false), AccountMeta::new_readonly(*requester, true)],
+    )
+}
+
+// CloseTemplate signed by the template's requester, who takes its rent
+pub fn close_template(program_id: &Pubkey, template: &Pubkey, requester: &Pubkey) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CloseTemplate,
+        vec![AccountMeta::new(*template, false), AccountMeta::new(*requester, true)],
+    )
+}
+
+// CreateTaskFromTemplate: the template, then CreateTask's accounts for `task`
+pub fn create_task_from_template(
+    program_id: &Pubkey,
+    template: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    payer: &Pubkey,
+    payment_amount: Option<u64>,
+) -> Instruction {
+    let mut instruction = create_task(
+        program_id,
+        task,
+        agent,
+        index,
+        payer,
+        ComputeRequirements::default(),
+        0,
+    );
+    instruction.data =
+        AIInfraInstruction::CreateTaskFromTemplate { template: *template, payment_amount }.pack();
+    instruction.accounts.insert(0, AccountMeta::new_readonly(*template, false));
+    instruction
+}
+
+// CreateRecurringTask for `recurring`, the requester's recurring task
+// numbered by their next counter value
+#[allow(clippy::too_many_arguments)]
+pub fn create_recurring_task(
+    program_id: &Pubkey,
+    recurring: &Pubkey,
+    requester: &Pubkey,
+    agent: &Pubkey,
+    requirements: ComputeRequirements,
+    payment_per_run: u64,
+    interval_seconds: i64,
+    funding: u64,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CreateRecurringTask {
+            agent: *agent,
+            requirements,
+            payment_per_run,
+            interval_seconds,
+            funding,
+        },
+        vec![
+            AccountMeta::new(*recurring, false),
+            AccountMeta::new(find_requester_address(requester, program_id).0, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// CrankRecurringTask: the recurring task, then CreateTask's accounts for
+// `task`, the run's address, with `cranker` fronting its rent
+pub fn crank_recurring_task(
+    program_id: &Pubkey,
+    recurring: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    index: u64,
+    cranker: &Pubkey,
+) -> Instruction {
+    let mut instruction = create_task(
+        program_id,
+        task,
+        agent,
+        index,
+        cranker,
+        ComputeRequirements::default(),
+        0,
+    );
+    instruction.data = AIInfraInstruction::CrankRecurringTask.pack();
+    // Runs aren't rate limited, so the cranker's requester account is left off
+    instruction.accounts.pop();
+    instruction.accounts.insert(0, AccountMeta::new(*recurring, false));
+    instruction
+}
+
+// CancelRecurringTask signed by the recurring task's requester
+pub fn cancel_recurring_task(
+    program_id: &Pubkey,
+    recurring: &Pubkey,
+    requester: &Pubkey,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CancelRecurringTask,
+        vec![AccountMeta::new(*recurring, false), AccountMeta::new(*requester, true)],
+    )
+}
+
+// Accounts of a shard created as the open task numbered `index`: the task,
+// its vault and its page of the open listings' task index
+fn shard_accounts(program_id: &Pubkey, index: u64) -> [AccountMeta; 3] {
+    let (shard, _) = find_task_address(&Pubkey::default(), index, program_id);
+    let (page, _) =
+        find_task_page_address(&Pubkey::default(), index / TASKS_PER_PAGE, program_id);
+    [
+        AccountMeta::new(shard, false),
+        AccountMeta::new(find_vault_address(&shard, program_id).0, false),
+        AccountMeta::new(page, false),
+    ]
+}
+
+// SplitTask dividing `task` into shards numbered from `first_shard_index`,
+// the config's next open task counter value
+pub fn split_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    requester: &Pubkey,
+    first_shard_index: u64,
+    shares: Vec<u16>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*task, false),
+        AccountMeta::new(*agent, false),
+        AccountMeta::new(find_vault_address(task, program_id).0, false),
+        AccountMeta::new(*requester, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new(find_registry_address(program_id).0, false),
+    ];
+    for index in first_shard_index..first_shard_index + shares.len() as u64 {
+        accounts.extend(shard_accounts(program_id, index));
+    }
+    new_instruction(program_id, &AIInfraInstruction::SplitTask { shares }, accounts)
+}
+
+// AggregateResults for `task` over its completed `shards`, in shard order
+pub fn aggregate_results(program_id: &Pubkey, task: &Pubkey, shards: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*task, false)];
+    accounts.extend(shards.iter().map(|shard| AccountMeta::new_readonly(*shard, false)));
+    new_instruction(program_id, &AIInfraInstruction::AggregateResults, accounts)
+}
+
+// ReissueShard replacing `failed_shard` of `task` with the open task
+// numbered `index`, the config's next open task counter value
+pub fn reissue_shard(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    failed_shard: &Pubkey,
+    requester: &Pubkey,
+    index: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*task, false),
+        AccountMeta::new(*failed_shard, false),
+        AccountMeta::new(*requester, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new(find_registry_address(program_id).0, false),
+    ];
+    accounts.extend(shard_accounts(program_id, index));
+    new_instruction(program_id, &AIInfraInstruction::ReissueShard, accounts)
+}
+
+// CreateRedundantTask for `task`, the requester's redundant task numbered
+// by their next counter value
+pub fn create_redundant_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    requester: &Pubkey,
+    requirements: ComputeRequirements,
+    payment: u64,
+    replicas: u8,
+    quorum: u8,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::CreateRedundantTask { requirements, payment, replicas, quorum },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(find_requester_address(requester, program_id).0, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_vault_address(task, program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// SubmitRedundantResult for `agent`, signed by its owner or operator
+pub fn submit_redundant_result(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    agent: &Pubkey,
+    signer: &Pubkey,
+    result_hash: [u8; 32],
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SubmitRedundantResult { result_hash },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new_readonly(*agent, false),
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+// FinalizeRedundantTask, with each submitting agent and its owner in the
+// order they submitted. An agent bound to an NFT is paid to the holder's
+// wallet, whose token account the caller inserts right after it.
+pub fn finalize_redundant_task(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    requester: &Pubkey,
+    treasury: &Pubkey,
+    submitters: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*task, false),
+        AccountMeta::new(find_vault_address(task, program_id).0, false),
+        AccountMeta::new(*requester, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*treasury, false),
+    ];
+    for (agent, owner) in submitters {
+        accounts.push(AccountMeta::new(*agent, false));
+        accounts.push(AccountMeta::new(*owner, false));
+    }
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::FinalizeRedundantTask,
+        accounts,
+    )
+}
+
+// SetAgentMetadata for the agent `owner` owns
+pub fn set_agent_metadata(
+    program_id: &Pubkey,
+    agent: &Pubkey,
+    owner: &Pubkey,
+    name: &str,
+    uri: &str,
+    endpoint: &str,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::SetAgentMetadata {
+            name: name.to_string(),
+            uri: uri.to_string(),
+            endpoint: endpoint.to_string(),
+        },
+        vec![
+            AccountMeta::new(find_metadata_address(agent, program_id).0, false),
+            AccountMeta::new_readonly(*agent, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// UpdateTaskPriority signed by the task's requester, who funds any surcharge
+pub fn update_task_priority(
+    program_id: &Pubkey,
+    task: &Pubkey,
+    requester: &Pubkey,
+    priority: u8,
+) -> Instruction {
+    new_instruction(
+        program_id,
+        &AIInfraInstruction::UpdateTaskPriority { task_id: *task, priority },
+        vec![
+            AccountMeta::new(*task, false),
+            AccountMeta::new(*requester, true),
+            AccountMeta::new(find_vault_address(task, program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TAG_SET_LEN;
+
+    // One of every variant, in declaration order, so each one's discriminant
+    // is its index. Task ids are `key`.
+    fn one_of_each(key: Pubkey) -> Vec<AIInfraInstruction> {
+        let requirements = || ComputeRequirements {
+            cpu_units: 1,
+            memory_mb: 2,
+            storage_mb: 3,
+            max_time_seconds: 4,
+            min_reputation: 5,
+            min_stake: 6,
+            single_step: true,
+            gpu_units: 7,
+            network_mbps: 8,
+        };
+        let params = || ConfigParams {
+            fee_bps: 100,
+            treasury: key,
+            arbitrator: key,
+            require_verification: true,
+            ..ConfigParams::default()
+        };
+
+        vec![
+            AIInfraInstruction::RegisterAgent,
+            AIInfraInstruction::UpdateAgentStatus { is_active: true },
+            AIInfraInstruction::CreateTask {
+                requirements: requirements(),
+                payment_amount: 7,
+                priority: 200,
+                tags: [1, 2, 3, 0, 0, 0, 0, 0],
+                payment_usd_cents: Some(1_500),
+                description: Some("Summarize the filings".to_string()),
+            },
+            AIInfraInstruction::StartTask { task_id: key },
+            AIInfraInstruction::CompleteTask {
+                task_id: key,
+                result_hash: [1; 32],
+                result_data: Some(vec![4, 5, 6]),
+            },
+            AIInfraInstruction::DepositCredits { amount: 8, expected_nonce: None },
+            AIInfraInstruction::WithdrawCredits { amount: 9, expected_nonce: Some(9) },
+            AIInfraInstruction::CancelTask { task_id: key },
+            AIInfraInstruction::FailTask { task_id: key, reason: FailureReason::Other(3) },
+            AIInfraInstruction::CloseTask { task_id: key },
+            AIInfraInstruction::DeregisterAgent,
+            AIInfraInstruction::TransferAgentOwnership { new_owner: key },
+            AIInfraInstruction::CreateTaskWithToken {
+                requirements: requirements(),
+                amount: 10,
+                mint: key,
+            },
+            AIInfraInstruction::InitializeConfig { admin: key, params: params() },
+            AIInfraInstruction::UpdateConfig { admin: key, params: params() },
+            AIInfraInstruction::PauseProgram,
+            AIInfraInstruction::UnpauseProgram,
+            AIInfraInstruction::ClaimTask { task_id: key },
+            AIInfraInstruction::SubmitBid { task_id: key, amount: 11 },
+            AIInfraInstruction::AcceptBid { task_id: key },
+            AIInfraInstruction::CloseBid,
+            AIInfraInstruction::ExpireTask { task_id: key },
+            AIInfraInstruction::Heartbeat,
+            AIInfraInstruction::DeactivateStaleAgent,
+            AIInfraInstruction::StakeCollateral { amount: 12 },
+            AIInfraInstruction::UnstakeCollateral { amount: 13 },
+            AIInfraInstruction::DepositRequesterCredits { amount: 14 },
+            AIInfraInstruction::WithdrawRequesterCredits { amount: 15 },
+            AIInfraInstruction::CreateTaskWithCredits {
+                requirements: requirements(),
+                payment_amount: 16,
+            },
+            AIInfraInstruction::ClaimPayment { task_id: key },
+            AIInfraInstruction::DisputeTask { task_id: key },
+            AIInfraInstruction::ResolveDispute { task_id: key, uphold_result: true },
+            AIInfraInstruction::CommitResult { task_id: key, commitment: [2; 32] },
+            AIInfraInstruction::RevealResult {
+                task_id: key,
+                result_hash: [3; 32],
+                salt: [4; 32],
+            },
+            AIInfraInstruction::RegisterVerifier { verifier: key },
+            AIInfraInstruction::VerifyResult { task_id: key },
+            AIInfraInstruction::RefundUnverifiedTask { task_id: key },
+            AIInfraInstruction::BatchCompleteTasks {
+                results: vec![TaskResult { task_id: key, result_hash: [5; 32] }; 2],
+            },
+            AIInfraInstruction::SetOperator { operator: key },
+            AIInfraInstruction::RevokeOperator,
+            AIInfraInstruction::AddAllowedRequester { requester: key },
+            AIInfraInstruction::RemoveAllowedRequester { requester: key },
+            AIInfraInstruction::TransferCredits { amount: 17, expected_nonce: Some(17) },
+            AIInfraInstruction::ApproveCredits { spender: key, amount: 18 },
+            AIInfraInstruction::RevokeAllowance,
+            AIInfraInstruction::CreateTaskWithAllowance {
+                requirements: requirements(),
+                payment_amount: 19,
+            },
+            AIInfraInstruction::SetAgentLimits { max_concurrent_tasks: 20 },
+            AIInfraInstruction::SetCapabilities {
+                capabilities: AgentCapabilities { gpu_units: 21, ..AgentCapabilities::default() },
+            },
+            AIInfraInstruction::MigrateAccount,
+            AIInfraInstruction::InitializeRegistry,
+            AIInfraInstruction::SetExpectedResult {
+                task_id: key,
+                expected_result_hash: [22; 32],
+            },
+            AIInfraInstruction::SetAttestationKey { attestation_key: Some(key) },
+            AIInfraInstruction::CreateBatchTask { task_count: 23, payment_per_task: 24 },
+            AIInfraInstruction::SubmitBatchResult { merkle_root: [25; 32] },
+            AIInfraInstruction::ClaimBatchItem {
+                index: 26,
+                result_hash: [27; 32],
+                proof: vec![[28; 32], [29; 32]],
+            },
+            AIInfraInstruction::SetReferrer { referrer: key },
+            AIInfraInstruction::TipAgent { amount: 30 },
+            AIInfraInstruction::ReleasePayments,
+            AIInfraInstruction::CreateTemplate {
+                requirements: requirements(),
+                default_payment: 31,
+            },
+            AIInfraInstruction::UpdateTemplate {
+                requirements: requirements(),
+                default_payment: 32,
+            },
+            AIInfraInstruction::CloseTemplate,
+            AIInfraInstruction::CreateTaskFromTemplate { template: key, payment_amount: Some(33) },
+            AIInfraInstruction::CreateRecurringTask {
+                agent: key,
+                requirements: requirements(),
+                payment_per_run: 34,
+                interval_seconds: 35,
+                funding: 36,
+            },
+            AIInfraInstruction::CrankRecurringTask,
+            AIInfraInstruction::CancelRecurringTask,
+            AIInfraInstruction::SplitTask { shares: vec![5_000, 3_000, 2_000] },
+            AIInfraInstruction::AggregateResults,
+            AIInfraInstruction::ReissueShard,
+            AIInfraInstruction::CreateRedundantTask {
+                requirements: requirements(),
+                payment: 37,
+                replicas: 3,
+                quorum: 2,
+            },
+            AIInfraInstruction::SubmitRedundantResult { result_hash: [38; 32] },
+            AIInfraInstruction::FinalizeRedundantTask,
+            AIInfraInstruction::SetAgentMetadata {
+                name: "agent".to_string(),
+                uri: "https://example.com/agent.json".to_string(),
+                endpoint: "grpc://agent.example.com:443".to_string(),
+            },
+            AIInfraInstruction::UpdateTaskPriority { task_id: key, priority: 73 },
+            AIInfraInstruction::SetAcceptedTags { accepted_tags: [74; TAG_SET_LEN] },
+            AIInfraInstruction::SetWithdrawalPolicy { max_per_day: 75, cooldown_seconds: -75 },
+            AIInfraInstruction::ClaimGrant,
+            AIInfraInstruction::FundFaucet { amount: 77 },
+            AIInfraInstruction::SetGrantAmount { amount: 78 },
+            AIInfraInstruction::InitializeInsurancePool,
+            AIInfraInstruction::FileInsuranceClaim,
+            AIInfraInstruction::SweepInsurancePool { amount: 81 },
+            AIInfraInstruction::SetMultisigOwner { signers: vec![key, key], threshold: 82 },
+            AIInfraInstruction::BindAgentToNft,
+            AIInfraInstruction::UnbindAgentFromNft,
+            AIInfraInstruction::SetPreferNative { prefer_native: true },
+            AIInfraInstruction::RefundBatchTask,
+        ]
+    }
+
+    #[test]
+    fn every_variant_round_trips_with_a_stable_discriminant() {
+        let instructions = one_of_each(Pubkey::new_unique());
+        assert_eq!(instructions.len(), 86);
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            let data = instruction.try_to_vec().unwrap();
+            assert_eq!(data[0] as usize, index, "{:?}", instruction);
+            assert_eq!(&AIInfraInstruction::try_from_slice(&data).unwrap(), instruction);
+        }
+
+        // Nothing decodes past the last variant
+        assert!(AIInfraInstruction::try_from_slice(&[86]).is_err());
+    }
+
+    #[test]
+    fn every_variant_round_trips_compactly() {
+        let key = Pubkey::new_unique();
+        for (index, instruction) in one_of_each(key).iter().enumerate() {
+            let data = instruction.pack();
+            assert_eq!(data[0], COMPACT_FLAG | index as u8, "{:?}", instruction);
+            let unpacked = AIInfraInstruction::unpack(&data, |_| Some(key)).unwrap();
+            assert_eq!(&unpacked, instruction);
+
+            // Cut short or padded, the data no longer decodes
+            if data.len() > 1 {
+                let short = &data[..data.len() - 1];
+                let result = AIInfraInstruction::unpack(short, |_| Some(key));
+                assert!(result.is_err(), "{:?}", instruction);
+            }
+            let long = [&data[..], &[0]].concat();
+            let result = AIInfraInstruction::unpack(&long, |_| Some(key));
+            assert!(result.is_err(), "{:?}", instruction);
+
+            // Borsh data still decodes the same
+            let legacy = instruction.try_to_vec().unwrap();
+            assert_eq!(&AIInfraInstruction::unpack(&legacy, |_| None).unwrap(), instruction);
+        }
+        assert_eq!(
+            AIInfraInstruction::unpack(&[COMPACT_FLAG | 74], |_| Some(key)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn every_variant_round_trips_through_json() {
+        let key = Pubkey::new_unique();
+        for instruction in one_of_each(key) {
+            let json = serde_json::to_string(&instruction).unwrap();
+            let read: AIInfraInstruction = serde_json::from_str(&json).unwrap();
+            assert_eq!(read, instruction, "{json}");
+        }
+        // Keys are base58 and hashes hex, under snake_case field names
+        let instruction = AIInfraInstruction::CommitResult { task_id: key, commitment: [0xab; 32] };
+        assert_eq!(
+            serde_json::to_value(&instruction).unwrap(),
+            serde_json::json!({
+                "CommitResult": { "task_id": key.to_string(), "commitment": "ab".repeat(32) },
+            }),
+        );
+    }
+
+    #[test]
+    fn compact_requirements_send_only_set_fields() {
+        // Every combination of set and default fields
+        for mask in 0..1u16 << 9 {
+            let value = |bit: u16| if mask & (1 << bit) != 0 { bit as u32 + 1 } else { 0 };
+            let requirements = ComputeRequirements {
+                cpu_units: value(0),
+                memory_mb: value(1),
+                storage_mb: value(2),
+                max_time_seconds: value(3),
+                min_reputation: value(4),
+                min_stake: value(5) as u64 * u32::MAX as u64,
+                single_step: value(6) != 0,
+                gpu_units: value(7),
+                network_mbps: value(8),
+            };
+            let instruction = AIInfraInstruction::CreateTask {
+                requirements,
+                payment_amount: 1,
+                priority: 0,
+                tags: [0; TASK_TAGS],
+                payment_usd_cents: None,
+                description: None,
+            };
+            let data = instruction.pack();
+            assert_eq!(data[1..3], mask.to_le_bytes());
+            let widths = [4, 4, 4, 4, 4, 8, 0, 4, 4];
+            let fields: usize =
+                (0..9).filter(|bit| mask & (1 << bit) != 0).map(|bit| widths[bit]).sum();
+            assert_eq!(data.len(), 1 + 2 + fields + 8 + 1 + TASK_TAGS + 1 + 1);
+            assert_eq!(AIInfraInstruction::unpack(&data, |_| None).unwrap(), instruction);
+        }
+
+        // Bits past the last field are rejected
+        let data = [COMPACT_FLAG | 2, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(AIInfraInstruction::unpack(&data, |_| None).is_err());
+    }
+
+    #[test]
+    fn compact_encoding_is_smaller() {
+        let task = Pubkey::new_unique();
+        let complete = AIInfraInstruction::CompleteTask {
+            task_id: task,
+            result_hash: [1; 32],
+            result_data: None,
+        };
+        assert_eq!((complete.pack().len(), complete.try_to_vec().unwrap().len()), (34, 66));
+        let create = AIInfraInstruction::CreateTask {
+            requirements: ComputeRequirements::default(),
+            payment_amount: 1,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            payment_usd_cents: None,
+            description: None,
+        };
+        assert_eq!((create.pack().len(), create.try_to_vec().unwrap().len()), (22, 57));
+    }
+
+    #[test]
+    fn compact_task_ids_come_from_the_task_account() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts = |index: usize| [first, second].get(index).copied();
+        let start = AIInfraInstruction::StartTask { task_id: Pubkey::new_unique() }.pack();
+        assert_eq!(
+            AIInfraInstruction::unpack(&start, accounts),
+            Ok(AIInfraInstruction::StartTask { task_id: first })
+        );
+
+        // SubmitBid passes its bid account ahead of the task
+        let bid = AIInfraInstruction::SubmitBid { task_id: first, amount: 5 }.pack();
+        assert_eq!(
+            AIInfraInstruction::unpack(&bid, accounts),
+            Ok(AIInfraInstruction::SubmitBid { task_id: second, amount: 5 })
+        );
+        assert_eq!(
+            AIInfraInstruction::unpack(&start, |_| None),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // Optional fields decode either way
+        for attestation_key in [None, Some(first)] {
+            let instruction = AIInfraInstruction::SetAttestationKey { attestation_key };
+            let data = instruction.pack();
+            assert_eq!(data.len(), 2 + attestation_key.map_or(0, |_| 32));
+            assert_eq!(AIInfraInstruction::unpack(&data, |_| None), Ok(instruction));
+        }
+        let instruction =
+            AIInfraInstruction::CreateTaskFromTemplate { template: first, payment_amount: None };
+        assert_eq!(AIInfraInstruction::unpack(&instruction.pack(), |_| None), Ok(instruction));
+    }
+
+    #[test]
+    fn credit_operations_decode_without_a_nonce() {
+        // The Borsh layout from before expected_nonce: the tag and the amount
+        for (tag, instruction) in [
+            (5, AIInfraInstruction::DepositCredits { amount: 7, expected_nonce: None }),
+            (6, AIInfraInstruction::WithdrawCredits { amount: 7, expected_nonce: None }),
+            (42, AIInfraInstruction::TransferCredits { amount: 7, expected_nonce: None }),
+        ] {
+            let data = [&[tag][..], &7u64.to_le_bytes()].concat();
+            assert_eq!(AIInfraInstruction::unpack(&data, |_| None), Ok(instruction));
+        }
+
+        // Other instructions this short still fail
+        let data = [&[24][..], &7u64.to_le_bytes()[..7]].concat();
+        assert!(AIInfraInstruction::unpack(&data, |_| None).is_err());
+        let data = [&[5][..], &7u64.to_le_bytes()[..7]].concat();
+        assert!(AIInfraInstruction::unpack(&data, |_| None).is_err());
+    }
+
+    #[test]
+    fn create_task_decodes_without_a_priority_or_tags() {
+        let instruction = |payment_usd_cents| AIInfraInstruction::CreateTask {
+            requirements: ComputeRequirements { cpu_units: 3, ..Default::default() },
+            payment_amount: 7,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            payment_usd_cents,
+            description: None,
+        };
+        let unpack = |data: &[u8]| AIInfraInstruction::unpack(data, |_| None);
+        // From before descriptions, compact or not
+        for payment_usd_cents in [Some(150), None] {
+            let mut data = instruction(payment_usd_cents).pack();
+            data.pop();
+            assert_eq!(unpack(&data), Ok(instruction(payment_usd_cents)));
+            let mut data = instruction(payment_usd_cents).try_to_vec().unwrap();
+            data.pop();
+            assert_eq!(unpack(&data), Ok(instruction(payment_usd_cents)));
+        }
+        // A description as long as a USD price still reads as one
+        let described = AIInfraInstruction::CreateTask {
+            requirements: ComputeRequirements { cpu_units: 3, ..Default::default() },
+            payment_amount: 7,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            payment_usd_cents: None,
+            description: Some("abc".to_string()),
+        };
+        let data = described.try_to_vec().unwrap();
+        assert_eq!(data.len(), instruction(Some(150)).try_to_vec().unwrap().len() - 1);
+        assert_eq!(unpack(&data), Ok(described));
+
+        // From before USD pricing, compact or not
+        let mut data = instruction(None).pack();
+        data.truncate(data.len() - 2);
+        assert_eq!(unpack(&data), Ok(instruction(None)));
+        let mut data = instruction(None).try_to_vec().unwrap();
+        data.truncate(data.len() - 2);
+        assert_eq!(unpack(&data), Ok(instruction(None)));
+        // then from before tags, and from before priorities
+        data.truncate(data.len() - TASK_TAGS);
+        assert_eq!(unpack(&data), Ok(instruction(None)));
+        data.pop();
+        assert_eq!(unpack(&data), Ok(instruction(None)));
+        data.pop();
+        assert!(unpack(&data).is_err());
+    }
+
+    #[test]
+    fn complete_task_decodes_without_result_data() {
+        let task_id = Pubkey::new_unique();
+        let complete = AIInfraInstruction::CompleteTask {
+            task_id,
+            result_hash: [1; 32],
+            result_data: None,
+        };
+        let mut data = complete.try_to_vec().unwrap();
+        data.pop();
+        assert_eq!(AIInfraInstruction::unpack(&data, |_| None), Ok(complete));
+
+        // A compact payload shorter than its length is rejected
+        let mut data = AIInfraInstruction::CompleteTask {
+            task_id,
+            result_hash: [1; 32],
+            result_data: Some(vec![7; 4]),
+        }
+        .pack();
+        data.pop();
+        assert!(AIInfraInstruction::unpack(&data, |_| None).is_err());
+    }
+
+    #[test]
+    fn metadata_must_be_utf8() {
+        let instruction = AIInfraInstruction::SetAgentMetadata {
+            name: "ok".to_string(),
+            uri: String::new(),
+            endpoint: String::new(),
+        };
+        let mut data = instruction.pack();
+        let mut legacy = instruction.try_to_vec().unwrap();
+        assert_eq!(AIInfraInstruction::unpack(&data, |_| None), Ok(instruction));
+
+        // The name's first byte, after the tag and its length
+        data[3] = 0xff;
+        assert_eq!(
+            AIInfraInstruction::unpack(&data, |_| None),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        legacy[5] = 0xff;
+        assert!(AIInfraInstruction::unpack(&legacy, |_| None).is_err());
+    }
+}
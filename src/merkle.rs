@@ -0,0 +1,130 @@
+use solana_program::hash::hashv;
+
+// Leaf committing to `result_hash` as the result of batch item `index`. Its
+// 36-byte preimage can't pass for the 64 bytes of a pair of nodes.
+pub fn leaf(index: u32, result_hash: &[u8; 32]) -> [u8; 32] {
+    hashv(&[&index.to_le_bytes(), result_hash]).to_bytes()
+}
+
+// Parent of two nodes. They are hashed in sorted order, so a proof needn't
+// say which side each sibling is on.
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[low, high]).to_bytes()
+}
+
+// Whether `proof`, the siblings on the path from `leaf` up, leads to `root`
+pub fn verify(root: &[u8; 32], leaf: &[u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(*leaf, |node, sibling| hash_pair(&node, sibling)) == *root
+}
+
+// Off-chain builders for the trees the program verifies against. Each level
+// pairs up adjacent nodes, and an unpaired last node moves up unchanged.
+#[cfg(not(target_os = "solana"))]
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => hash_pair(a, b),
+            [a] => *a,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// Root of the tree over `leaves`, or zeroes when there are none
+#[cfg(not(target_os = "solana"))]
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.first().copied().unwrap_or_default()
+}
+
+// Proof for the leaf at `index`, as verify takes it
+#[cfg(not(target_os = "solana"))]
+pub fn proof(leaves: &[[u8; 32]], mut index: usize) -> Vec<[u8; 32]> {
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    proof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkle_proofs_match_a_reference_tree() {
+        // Reference: hash every level in full, sorting each pair and carrying
+        // an unpaired last node up, and read proofs off the stored levels
+        fn reference_levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+            let mut levels = vec![leaves.to_vec()];
+            while levels.last().unwrap().len() > 1 {
+                let below = levels.last().unwrap();
+                let mut level = Vec::new();
+                let mut i = 0;
+                while i < below.len() {
+                    if i + 1 < below.len() {
+                        let (a, b) = (below[i], below[i + 1]);
+                        let (low, high) = if a < b { (a, b) } else { (b, a) };
+                        level.push(hashv(&[&low, &high]).to_bytes());
+                    } else {
+                        level.push(below[i]);
+                    }
+                    i += 2;
+                }
+                levels.push(level);
+            }
+            levels
+        }
+
+        for count in [1, 2, 3, 5, 8, 13, 64, 100] {
+            let leaves: Vec<[u8; 32]> = (0..count).map(|i| leaf(i, &[i as u8; 32])).collect();
+            let levels = reference_levels(&leaves);
+            let expected_root = levels.last().unwrap()[0];
+            assert_eq!(root(&leaves), expected_root, "{count} leaves");
+
+            for index in 0..count as usize {
+                let mut expected_proof = Vec::new();
+                let mut position = index;
+                for level in &levels[..levels.len() - 1] {
+                    let sibling = if position % 2 == 0 { position + 1 } else { position - 1 };
+                    if sibling < level.len() {
+                        expected_proof.push(level[sibling]);
+                    }
+                    position /= 2;
+                }
+                let proof = proof(&leaves, index);
+                assert_eq!(proof, expected_proof, "leaf {index} of {count}");
+                assert!(verify(&expected_root, &leaves[index], &proof));
+
+                // The proof is for this leaf alone
+                let other = leaf(index as u32, &[0xee; 32]);
+                assert!(!verify(&expected_root, &other, &proof));
+            }
+        }
+
+        // Two leaves hash to their sorted concatenation, in either order
+        let (a, b) = (leaf(0, &[1; 32]), leaf(1, &[2; 32]));
+        assert_eq!(hash_pair(&a, &b), hash_pair(&b, &a));
+        assert_eq!(root(&[a, b]), hashv(&[a.min(b).as_ref(), a.max(b).as_ref()]).to_bytes());
+        assert_eq!(leaf(7, &[3; 32]), hashv(&[&7u32.to_le_bytes(), &[3; 32]]).to_bytes());
+
+        // A leaf for another index doesn't verify, nor does a truncated proof
+        let leaves: Vec<[u8; 32]> = (0..8).map(|i| leaf(i, &[9; 32])).collect();
+        let tree_root = root(&leaves);
+        let proof = proof(&leaves, 5);
+        assert!(verify(&tree_root, &leaves[5], &proof));
+        assert!(!verify(&tree_root, &leaf(4, &[9; 32]), &proof));
+        assert!(!verify(&tree_root, &leaves[5], &proof[..2]));
+        assert!(!verify(&tree_root, &leaves[5], &[]));
+    }
+}
@@ -0,0 +1,195 @@
+// Reads SOL/USD prices out of a Pyth price account (layout version 2), or
+// any oracle publishing the same format, for pricing tasks in dollars. Only
+// the aggregate price and the fields validating it are read.
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
+
+use crate::error::AIInfraError;
+
+const MAGIC: u32 = 0xa1b2_c3d4;
+const VERSION: u32 = 2;
+const PRICE_ACCOUNT: u32 = 3;
+// Aggregate status of a price being published normally
+const TRADING: u32 = 1;
+
+// Offsets of the fields read, and the length of the account through the
+// aggregate price
+const EXPO_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const PRICE_OFFSET: usize = 208;
+const CONF_OFFSET: usize = 216;
+const STATUS_OFFSET: usize = 224;
+pub const PRICE_ACCOUNT_LEN: usize = 240;
+
+// Lamports in one SOL, as a power of ten, less the two digits of the cents
+const LAMPORTS_PER_CENT_EXPO: i32 = 9 - 2;
+
+// An aggregate price of `price * 10^expo` dollars per SOL, give or take
+// `conf` in the same units, as of unix timestamp `publish_time`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    // Reads the aggregate price out of a price account's data
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < PRICE_ACCOUNT_LEN
+            || read_u32(data, 0) != MAGIC
+            || read_u32(data, 4) != VERSION
+            || read_u32(data, 8) != PRICE_ACCOUNT
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if read_u32(data, STATUS_OFFSET) != TRADING {
+            return Err(AIInfraError::InvalidOraclePrice.into());
+        }
+        Ok(Self {
+            price: read_u64(data, PRICE_OFFSET) as i64,
+            conf: read_u64(data, CONF_OFFSET),
+            expo: read_u32(data, EXPO_OFFSET) as i32,
+            publish_time: read_u64(data, TIMESTAMP_OFFSET) as i64,
+        })
+    }
+
+    // Price account data holding just this price, as tests and local
+    // validators mock an oracle with
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = vec![0; PRICE_ACCOUNT_LEN];
+        for (offset, bytes) in [
+            (0, &MAGIC.to_le_bytes()[..]),
+            (4, &VERSION.to_le_bytes()),
+            (8, &PRICE_ACCOUNT.to_le_bytes()),
+            (EXPO_OFFSET, &self.expo.to_le_bytes()),
+            (TIMESTAMP_OFFSET, &self.publish_time.to_le_bytes()),
+            (PRICE_OFFSET, &self.price.to_le_bytes()),
+            (CONF_OFFSET, &self.conf.to_le_bytes()),
+            (STATUS_OFFSET, &TRADING.to_le_bytes()),
+        ] {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        data
+    }
+
+    // Checks the price is positive, published no more than `max_age_seconds`
+    // before `now`, and certain to within `max_confidence_bps` of itself. A
+    // zero confidence bound accepts any interval.
+    pub fn validate(
+        &self,
+        now: i64,
+        max_age_seconds: i64,
+        max_confidence_bps: u16,
+    ) -> ProgramResult {
+        if self.price <= 0 {
+            return Err(AIInfraError::InvalidOraclePrice.into());
+        }
+        if now.saturating_sub(self.publish_time) > max_age_seconds {
+            return Err(AIInfraError::StaleOracle.into());
+        }
+        if max_confidence_bps != 0
+            && self.conf as u128 * 10_000 > self.price as u128 * max_confidence_bps as u128
+        {
+            return Err(AIInfraError::OracleConfidenceTooWide.into());
+        }
+        Ok(())
+    }
+
+    // Lamports `usd_cents` buys at this price, rounded down like the fee
+    pub fn lamports_for_usd_cents(&self, usd_cents: u64) -> Result<u64, ProgramError> {
+        if self.price <= 0 {
+            return Err(AIInfraError::InvalidOraclePrice.into());
+        }
+        // lamports = cents * 10^7 / (price * 10^expo)
+        let scale = |expo: i32| 10u128.checked_pow(expo.unsigned_abs());
+        let shift = LAMPORTS_PER_CENT_EXPO as i64 - self.expo as i64;
+        let shift = i32::try_from(shift).map_err(|_| AIInfraError::NumericalOverflow)?;
+        let (numerator, denominator) = if shift >= 0 {
+            (scale(shift).and_then(|scale| (usd_cents as u128).checked_mul(scale)), Some(1))
+        } else {
+            (Some(usd_cents as u128), scale(shift))
+        };
+        let lamports = numerator
+            .zip(denominator.and_then(|scale| (self.price as u128).checked_mul(scale)))
+            .map(|(numerator, denominator)| numerator / denominator)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        u64::try_from(lamports).map_err(|_| AIInfraError::NumericalOverflow.into())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // $150.00 per SOL, as Pyth publishes SOL/USD
+    fn sol_at_150() -> OraclePrice {
+        OraclePrice { price: 15_000_000_000, conf: 1_500_000, expo: -8, publish_time: 1_000 }
+    }
+
+    #[test]
+    fn usd_cents_convert_at_the_price() {
+        let price = sol_at_150();
+        assert_eq!(price.lamports_for_usd_cents(15_000).unwrap(), 1_000_000_000);
+        assert_eq!(price.lamports_for_usd_cents(1).unwrap(), 66_666);
+        assert_eq!(price.lamports_for_usd_cents(0).unwrap(), 0);
+
+        // A positive exponent divides the other way
+        let price = OraclePrice { price: 15, expo: 1, ..price };
+        assert_eq!(price.lamports_for_usd_cents(15_000).unwrap(), 1_000_000_000);
+
+        // Too many lamports for a u64
+        let price = OraclePrice { price: 1, expo: -8, ..price };
+        assert_eq!(
+            price.lamports_for_usd_cents(u64::MAX),
+            Err(AIInfraError::NumericalOverflow.into())
+        );
+        let price = OraclePrice { price: 0, ..price };
+        assert_eq!(price.lamports_for_usd_cents(1), Err(AIInfraError::InvalidOraclePrice.into()));
+    }
+
+    #[test]
+    fn prices_must_be_fresh_positive_and_tight() {
+        let price = sol_at_150();
+        assert_eq!(price.validate(1_060, 60, 100), Ok(()));
+        assert_eq!(price.validate(1_061, 60, 100), Err(AIInfraError::StaleOracle.into()));
+
+        // A 0.01% interval passes a 1 bps bound but not a tighter price
+        assert_eq!(price.validate(1_000, 60, 1), Ok(()));
+        let wide = OraclePrice { conf: 1_500_001, ..price };
+        assert_eq!(wide.validate(1_000, 60, 1), Err(AIInfraError::OracleConfidenceTooWide.into()));
+        assert_eq!(wide.validate(1_000, 60, 0), Ok(()));
+
+        for price in [0, -1] {
+            assert_eq!(
+                OraclePrice { price, ..sol_at_150() }.validate(1_000, 60, 0),
+                Err(AIInfraError::InvalidOraclePrice.into())
+            );
+        }
+    }
+
+    #[test]
+    fn price_accounts_round_trip() {
+        let price = sol_at_150();
+        let mut data = price.pack();
+        assert_eq!(OraclePrice::unpack(&data), Ok(price));
+
+        // A halted price is no price
+        data[STATUS_OFFSET] = 0;
+        assert_eq!(OraclePrice::unpack(&data), Err(AIInfraError::InvalidOraclePrice.into()));
+        data[0] = 0;
+        assert_eq!(OraclePrice::unpack(&data), Err(ProgramError::InvalidAccountData));
+        assert_eq!(
+            OraclePrice::unpack(&price.pack()[..PRICE_ACCOUNT_LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+pub const AGENT_SEED: &[u8] = b"agent";
+
+// Agent accounts live at a PDA derived from the owner's wallet
+pub fn find_agent_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AGENT_SEED, owner.as_ref()], program_id)
+}
+
+// Re-derives an agent address from its stored bump, skipping the bump search
+pub fn create_agent_address(
+    owner: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[AGENT_SEED, owner.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const TASK_SEED: &[u8] = b"task";
+
+// Task accounts are derived from their agent and the agent's task counter
+pub fn find_task_address(agent: &Pubkey, index: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TASK_SEED, agent.as_ref(), &index.to_le_bytes()], program_id)
+}
+
+pub fn create_task_address(
+    agent: &Pubkey,
+    index: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[TASK_SEED, agent.as_ref(), &index.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const TASK_PAGE_SEED: &[u8] = b"task_page";
+
+// Pages of an agent's task index are derived from the agent and page number
+pub fn find_task_page_address(agent: &Pubkey, page: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TASK_PAGE_SEED, agent.as_ref(), &page.to_le_bytes()], program_id)
+}
+
+pub fn create_task_page_address(
+    agent: &Pubkey,
+    page: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[TASK_PAGE_SEED, agent.as_ref(), &page.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const BATCH_TASK_SEED: &[u8] = b"batch_task";
+
+// Batch tasks are derived from their agent and the agent's batch counter
+pub fn find_batch_task_address(agent: &Pubkey, index: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[BATCH_TASK_SEED, agent.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn create_batch_task_address(
+    agent: &Pubkey,
+    index: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[BATCH_TASK_SEED, agent.as_ref(), &index.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const TASK_TEMPLATE_SEED: &[u8] = b"task_template";
+
+// Task templates are derived from their requester and the requester's
+// template counter
+pub fn find_task_template_address(
+    requester: &Pubkey,
+    index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TASK_TEMPLATE_SEED, requester.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn create_task_template_address(
+    requester: &Pubkey,
+    index: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[TASK_TEMPLATE_SEED, requester.as_ref(), &index.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const RECURRING_TASK_SEED: &[u8] = b"recurring_task";
+
+// Recurring tasks are derived from their requester and the requester's
+// recurring task counter
+pub fn find_recurring_task_address(
+    requester: &Pubkey,
+    index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RECURRING_TASK_SEED, requester.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn create_recurring_task_address(
+    requester: &Pubkey,
+    index: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[RECURRING_TASK_SEED, requester.as_ref(), &index.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const REDUNDANT_TASK_SEED: &[u8] = b"redundant_task";
+
+// Redundant tasks are derived from their requester and the requester's
+// redundant task counter
+pub fn find_redundant_task_address(
+    requester: &Pubkey,
+    index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REDUNDANT_TASK_SEED, requester.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn create_redundant_task_address(
+    requester: &Pubkey,
+    index: u64,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[REDUNDANT_TASK_SEED, requester.as_ref(), &index.to_le_bytes(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const VAULT_SEED: &[u8] = b"vault";
+
+// Each task escrows its payment in a system-owned vault derived from the task
+pub fn find_vault_address(task: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, task.as_ref()], program_id)
+}
+
+pub fn create_vault_address(
+    task: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[VAULT_SEED, task.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const CREDIT_VAULT_SEED: &[u8] = b"credits";
+
+// Deposited credits are backed by lamports held in a per-agent vault
+pub fn find_credit_vault_address(agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CREDIT_VAULT_SEED, agent.as_ref()], program_id)
+}
+
+pub const STAKE_VAULT_SEED: &[u8] = b"stake";
+
+// Staked collateral sits in its own per-agent vault, apart from credits
+pub fn find_stake_vault_address(agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STAKE_VAULT_SEED, agent.as_ref()], program_id)
+}
+
+pub const FAUCET_SEED: &[u8] = b"faucet";
+
+// Lamports backing the credits ClaimGrant gives new agents sit in a single
+// faucet vault the admin funds
+pub fn find_faucet_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FAUCET_SEED], program_id)
+}
+
+pub const CONFIG_SEED: &[u8] = b"config";
+
+// The program keeps a single global config account
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+pub fn create_config_address(bump: u8, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[CONFIG_SEED, &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const REGISTRY_SEED: &[u8] = b"registry";
+
+// Program-wide counters live in a single registry account
+pub fn find_registry_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED], program_id)
+}
+
+pub fn create_registry_address(bump: u8, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[REGISTRY_SEED, &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const INSURANCE_POOL_SEED: &[u8] = b"insurance_pool";
+
+// The insurance pool is a single program-owned PDA holding its own lamports
+pub fn find_insurance_pool_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[INSURANCE_POOL_SEED], program_id)
+}
+
+pub fn create_insurance_pool_address(
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[INSURANCE_POOL_SEED, &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const BID_SEED: &[u8] = b"bid";
+
+// Each agent can hold one bid per open task
+pub fn find_bid_address(task: &Pubkey, agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BID_SEED, task.as_ref(), agent.as_ref()], program_id)
+}
+
+pub fn create_bid_address(
+    task: &Pubkey,
+    agent: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[BID_SEED, task.as_ref(), agent.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const REQUESTER_SEED: &[u8] = b"requester";
+
+// Requesters keep a prepaid credit balance in a PDA derived from their wallet
+pub fn find_requester_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REQUESTER_SEED, owner.as_ref()], program_id)
+}
+
+pub fn create_requester_address(
+    owner: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[REQUESTER_SEED, owner.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const VERIFIER_SEED: &[u8] = b"verifier";
+
+// Verifiers are registered in a PDA derived from their signing key
+pub fn find_verifier_address(authority: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VERIFIER_SEED, authority.as_ref()], program_id)
+}
+
+pub fn create_verifier_address(
+    authority: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[VERIFIER_SEED, authority.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const ALLOWLIST_SEED: &[u8] = b"allowlist";
+
+// An agent's allowlist of requesters is derived from the agent PDA
+pub fn find_allowlist_address(agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ALLOWLIST_SEED, agent.as_ref()], program_id)
+}
+
+pub fn create_allowlist_address(
+    agent: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[ALLOWLIST_SEED, agent.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const MULTISIG_SEED: &[u8] = b"multisig";
+
+// The key set signing for an agent's owner is derived from the agent PDA
+pub fn find_multisig_address(agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MULTISIG_SEED, agent.as_ref()], program_id)
+}
+
+pub fn create_multisig_address(
+    agent: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[MULTISIG_SEED, agent.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const ALLOWANCE_SEED: &[u8] = b"allowance";
+
+// A spender's allowance against an agent's credits is derived from both keys
+pub fn find_allowance_address(
+    agent: &Pubkey,
+    spender: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ALLOWANCE_SEED, agent.as_ref(), spender.as_ref()], program_id)
+}
+
+pub fn create_allowance_address(
+    agent: &Pubkey,
+    spender: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[ALLOWANCE_SEED, agent.as_ref(), spender.as_ref(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+pub const METADATA_SEED: &[u8] = b"metadata";
+
+// An agent's metadata is derived from the agent PDA
+pub fn find_metadata_address(agent: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[METADATA_SEED, agent.as_ref()], program_id)
+}
+
+pub fn create_metadata_address(
+    agent: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[METADATA_SEED, agent.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)
+}
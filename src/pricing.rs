@@ -0,0 +1,140 @@
+use crate::state::{ComputeRequirements, Config};
+
+// Lowest lamport payment the config's unit prices allow for a task with
+// `requirements`. CPU is priced per unit-second of the task's time limit,
+// memory and storage per MB. Saturates at u64::MAX.
+pub fn compute_task_price(requirements: &ComputeRequirements, config: &Config) -> u64 {
+    let cpu_unit_seconds = requirements.cpu_units as u128 * requirements.max_time_seconds as u128;
+    let price = (cpu_unit_seconds * config.price_per_cpu_unit_second as u128)
+        .saturating_add(requirements.memory_mb as u128 * config.price_per_memory_mb as u128)
+        .saturating_add(requirements.storage_mb as u128 * config.price_per_storage_mb as u128);
+    u64::try_from(price).unwrap_or(u64::MAX)
+}
+
+// Price multiplier, in basis points, for a task at `priority`. Each level
+// above the config's threshold adds priority_multiplier_bps to the 1x base.
+pub fn priority_multiplier_bps(priority: u8, config: &Config) -> u64 {
+    let levels = priority.saturating_sub(config.priority_threshold) as u64;
+    10_000 + levels * config.priority_multiplier_bps as u64
+}
+
+// compute_task_price scaled by the multiplier for `priority`. Saturates at
+// u64::MAX.
+pub fn priority_task_price(
+    requirements: &ComputeRequirements,
+    config: &Config,
+    priority: u8,
+) -> u64 {
+    let price = compute_task_price(requirements, config) as u128
+        * priority_multiplier_bps(priority, config) as u128
+        / 10_000;
+    u64::try_from(price).unwrap_or(u64::MAX)
+}
+
+// Lamports raising a task from priority `from` to `to` costs: how much the
+// price for its requirements rises. Nothing when `to` is no higher.
+pub fn priority_surcharge(
+    requirements: &ComputeRequirements,
+    config: &Config,
+    from: u8,
+    to: u8,
+) -> u64 {
+    priority_task_price(requirements, config, to)
+        .saturating_sub(priority_task_price(requirements, config, from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_above_the_threshold_cost_more() {
+        let config = Config {
+            price_per_cpu_unit_second: 10,
+            priority_threshold: 100,
+            priority_multiplier_bps: 500,
+            ..Config::default()
+        };
+        let requirements =
+            ComputeRequirements { cpu_units: 2, max_time_seconds: 50, ..Default::default() };
+        assert_eq!(compute_task_price(&requirements, &config), 1_000);
+
+        // Up to the threshold the base price applies
+        assert_eq!(priority_multiplier_bps(0, &config), 10_000);
+        assert_eq!(priority_task_price(&requirements, &config, 100), 1_000);
+        // Then each level adds 5%
+        assert_eq!(priority_multiplier_bps(101, &config), 10_500);
+        assert_eq!(priority_task_price(&requirements, &config, 101), 1_050);
+        assert_eq!(priority_task_price(&requirements, &config, 110), 1_500);
+        assert_eq!(priority_multiplier_bps(255, &config), 10_000 + 155 * 500);
+
+        // Raising charges only the increment, and lowering nothing
+        assert_eq!(priority_surcharge(&requirements, &config, 0, 100), 0);
+        assert_eq!(priority_surcharge(&requirements, &config, 50, 110), 500);
+        assert_eq!(priority_surcharge(&requirements, &config, 105, 110), 250);
+        assert_eq!(priority_surcharge(&requirements, &config, 110, 105), 0);
+
+        // The price saturates rather than wrapping
+        let huge = ComputeRequirements {
+            cpu_units: u32::MAX,
+            max_time_seconds: u32::MAX,
+            ..Default::default()
+        };
+        let config = Config { price_per_cpu_unit_second: u64::MAX, ..config };
+        assert_eq!(priority_task_price(&huge, &config, 255), u64::MAX);
+    }
+
+    #[test]
+    fn task_price_covers_every_resource() {
+        let config = Config {
+            price_per_cpu_unit_second: 3,
+            price_per_memory_mb: 5,
+            price_per_storage_mb: 7,
+            ..Config::default()
+        };
+        let requirements = ComputeRequirements {
+            cpu_units: 10,
+            memory_mb: 100,
+            storage_mb: 1000,
+            max_time_seconds: 60,
+            ..ComputeRequirements::default()
+        };
+        assert_eq!(compute_task_price(&requirements, &config), 10 * 60 * 3 + 100 * 5 + 1000 * 7);
+
+        // Unpriced configs and empty requirements cost nothing
+        assert_eq!(compute_task_price(&requirements, &Config::default()), 0);
+        assert_eq!(compute_task_price(&ComputeRequirements::default(), &config), 0);
+    }
+
+    #[test]
+    fn task_price_saturates_at_the_extremes() {
+        let maximal = ComputeRequirements {
+            cpu_units: u32::MAX,
+            memory_mb: u32::MAX,
+            storage_mb: u32::MAX,
+            max_time_seconds: u32::MAX,
+            ..ComputeRequirements::default()
+        };
+        let priciest = Config {
+            price_per_cpu_unit_second: u64::MAX,
+            price_per_memory_mb: u64::MAX,
+            price_per_storage_mb: u64::MAX,
+            ..Config::default()
+        };
+        assert_eq!(compute_task_price(&maximal, &priciest), u64::MAX);
+
+        // The largest CPU term alone still fits in u64 at a one lamport price
+        let cpu_only = Config { price_per_cpu_unit_second: 1, ..Config::default() };
+        let cpu_task = ComputeRequirements { memory_mb: 0, storage_mb: 0, ..maximal };
+        assert_eq!(compute_task_price(&cpu_task, &cpu_only), u32::MAX as u64 * u32::MAX as u64);
+
+        // Memory and storage terms at their maximum stay exact
+        let memory_only = Config { price_per_memory_mb: u32::MAX as u64, ..Config::default() };
+        assert_eq!(
+            compute_task_price(&maximal, &memory_only),
+            u32::MAX as u64 * u32::MAX as u64
+        );
+        let storage_only = Config { price_per_storage_mb: u64::MAX, ..Config::default() };
+        assert_eq!(compute_task_price(&maximal, &storage_only), u64::MAX);
+    }
+}
@@ -1,16 +1,53 @@
+use borsh::BorshDeserialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    hash::{hash, hashv},
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction,
-    program::invoke,
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction, system_program,
+    sysvar::{self, rent::Rent, Sysvar},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+};
+use spl_token_2022::extension::{
+    transfer_fee::TransferFeeAmount, BaseStateWithExtensions, StateWithExtensions,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
 
-use crate::state::{AIAgent, ComputeTask, AIInfraInstruction, TaskStatus};
+use crate::{
+    attestation::{attestation_message, verify_attestation},
+    error::AIInfraError,
+    events::{emit, Event},
+    merkle,
+    oracle::OraclePrice,
+    pda::{
+        self, find_agent_address, find_allowance_address, find_allowlist_address,
+        find_batch_task_address, find_bid_address, find_config_address, find_credit_vault_address,
+        find_faucet_address, find_insurance_pool_address, find_metadata_address,
+        find_multisig_address, find_recurring_task_address,
+        find_redundant_task_address, find_registry_address, find_requester_address,
+        find_stake_vault_address,
+        find_task_address, find_task_page_address, find_task_template_address,
+        find_vault_address, find_verifier_address,
+    },
+    instruction::{AIInfraInstruction, TaskResult},
+    pricing::{priority_surcharge, priority_task_price},
+    reputation,
+    state::{
+        account_version, load_account, migrate_data, save_account, split_payment, AIAgent,
+        AccountType, AgentAllowlist, AgentCapabilities, AgentMetadata, Allowance, BatchTask, Bid,
+        ComputeRequirements, ComputeTask, Config, ConfigParams, FailureReason, InsurancePool,
+        MultisigOwner, ProgramAccount, RecurringTask, RedundantTask, Registry, Requester, TaskPage,
+        TaskStatus, TaskTemplate, TokenProgram, Verifier, WithdrawalPolicy, CURRENT_VERSION,
+        MAX_ALLOWED_REQUESTERS, MAX_BATCH_COMPLETIONS, MAX_BATCH_ITEMS, MAX_REPLICAS,
+        MAX_RESULT_DATA_LEN, MAX_TASK_DESCRIPTION_LEN, TAG_SET_LEN, TASKS_PER_PAGE, TASK_TAGS,
+    },
+    zero_copy,
+};
 
 pub struct Processor;
 
@@ -20,33 +57,7360 @@ impl Processor {
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
-        let instruction = AIInfraInstruction::try_from_slice(instruction_data)?;
-        
+        let instruction = AIInfraInstruction::unpack(instruction_data, |index| {
+            accounts.get(index).map(|account| *account.key)
+        })?;
+        let roles = account_roles(&instruction, accounts);
+        check_account_roles(accounts, &roles)?;
+        check_distinct_accounts(program_id, accounts, &roles)?;
+
         match instruction {
             AIInfraInstruction::RegisterAgent => {
                 Self::process_register_agent(program_id, accounts)
             }
-            AIInfraInstruction::CreateTask { requirements, payment_amount } => {
-                Self::process_create_task(program_id, accounts, requirements, payment_amount)
+            AIInfraInstruction::CreateTask {
+                requirements,
+                payment_amount,
+                priority,
+                tags,
+                payment_usd_cents,
+                description,
+            } => Self::process_create_task(
+                program_id,
+                accounts,
+                requirements,
+                payment_amount,
+                priority,
+                tags,
+                payment_usd_cents,
+                description,
+            ),
+            AIInfraInstruction::StartTask { task_id } => {
+                Self::process_start_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::CompleteTask { task_id, result_hash, result_data } => {
+                Self::process_complete_task(program_id, accounts, task_id, result_hash, result_data)
+            }
+            AIInfraInstruction::DepositCredits { amount, expected_nonce } => {
+                Self::process_deposit_credits(program_id, accounts, amount, expected_nonce)
+            }
+            AIInfraInstruction::WithdrawCredits { amount, expected_nonce } => {
+                Self::process_withdraw_credits(program_id, accounts, amount, expected_nonce)
+            }
+            AIInfraInstruction::UpdateAgentStatus { is_active } => {
+                Self::process_update_status(program_id, accounts, is_active)
+            }
+            AIInfraInstruction::CancelTask { task_id } => {
+                Self::process_cancel_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::FailTask { task_id, reason } => {
+                Self::process_fail_task(program_id, accounts, task_id, reason)
+            }
+            AIInfraInstruction::CloseTask { task_id } => {
+                Self::process_close_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::DeregisterAgent => {
+                Self::process_deregister_agent(program_id, accounts)
+            }
+            AIInfraInstruction::TransferAgentOwnership { new_owner } => {
+                Self::process_transfer_ownership(program_id, accounts, new_owner)
+            }
+            AIInfraInstruction::CreateTaskWithToken { requirements, amount, mint } => {
+                Self::process_create_task_with_token(
+                    program_id,
+                    accounts,
+                    requirements,
+                    amount,
+                    mint,
+                )
+            }
+            AIInfraInstruction::InitializeConfig { admin, params } => {
+                Self::process_initialize_config(program_id, accounts, admin, params)
+            }
+            AIInfraInstruction::UpdateConfig { admin, params } => {
+                Self::process_update_config(program_id, accounts, admin, params)
+            }
+            AIInfraInstruction::PauseProgram => {
+                Self::process_set_paused(program_id, accounts, true)
+            }
+            AIInfraInstruction::UnpauseProgram => {
+                Self::process_set_paused(program_id, accounts, false)
+            }
+            AIInfraInstruction::ClaimTask { task_id } => {
+                Self::process_claim_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::SubmitBid { task_id, amount } => {
+                Self::process_submit_bid(program_id, accounts, task_id, amount)
+            }
+            AIInfraInstruction::AcceptBid { task_id } => {
+                Self::process_accept_bid(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::CloseBid => Self::process_close_bid(program_id, accounts),
+            AIInfraInstruction::ExpireTask { task_id } => {
+                Self::process_expire_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::Heartbeat => Self::process_heartbeat(program_id, accounts),
+            AIInfraInstruction::DeactivateStaleAgent => {
+                Self::process_deactivate_stale_agent(program_id, accounts)
+            }
+            AIInfraInstruction::StakeCollateral { amount } => {
+                Self::process_stake_collateral(program_id, accounts, amount)
+            }
+            AIInfraInstruction::UnstakeCollateral { amount } => {
+                Self::process_unstake_collateral(program_id, accounts, amount)
+            }
+            AIInfraInstruction::DepositRequesterCredits { amount } => {
+                Self::process_deposit_requester_credits(program_id, accounts, amount)
+            }
+            AIInfraInstruction::WithdrawRequesterCredits { amount } => {
+                Self::process_withdraw_requester_credits(program_id, accounts, amount)
+            }
+            AIInfraInstruction::CreateTaskWithCredits { requirements, payment_amount } => {
+                Self::process_create_task_with_credits(
+                    program_id,
+                    accounts,
+                    requirements,
+                    payment_amount,
+                )
+            }
+            AIInfraInstruction::ClaimPayment { task_id } => {
+                Self::process_claim_payment(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::DisputeTask { task_id } => {
+                Self::process_dispute_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::ResolveDispute { task_id, uphold_result } => {
+                Self::process_resolve_dispute(program_id, accounts, task_id, uphold_result)
+            }
+            AIInfraInstruction::CommitResult { task_id, commitment } => {
+                Self::process_commit_result(program_id, accounts, task_id, commitment)
+            }
+            AIInfraInstruction::RevealResult { task_id, result_hash, salt } => {
+                Self::process_reveal_result(program_id, accounts, task_id, result_hash, salt)
+            }
+            AIInfraInstruction::RegisterVerifier { verifier } => {
+                Self::process_register_verifier(program_id, accounts, verifier)
+            }
+            AIInfraInstruction::VerifyResult { task_id } => {
+                Self::process_verify_result(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::RefundUnverifiedTask { task_id } => {
+                Self::process_refund_unverified_task(program_id, accounts, task_id)
+            }
+            AIInfraInstruction::BatchCompleteTasks { results } => {
+                Self::process_batch_complete_tasks(program_id, accounts, results)
+            }
+            AIInfraInstruction::SetOperator { operator } => {
+                Self::process_set_operator(program_id, accounts, Some(operator))
+            }
+            AIInfraInstruction::RevokeOperator => {
+                Self::process_set_operator(program_id, accounts, None)
+            }
+            AIInfraInstruction::AddAllowedRequester { requester } => {
+                Self::process_add_allowed_requester(program_id, accounts, requester)
+            }
+            AIInfraInstruction::RemoveAllowedRequester { requester } => {
+                Self::process_remove_allowed_requester(program_id, accounts, requester)
+            }
+            AIInfraInstruction::TransferCredits { amount, expected_nonce } => {
+                Self::process_transfer_credits(program_id, accounts, amount, expected_nonce)
+            }
+            AIInfraInstruction::ApproveCredits { spender, amount } => {
+                Self::process_approve_credits(program_id, accounts, spender, amount)
+            }
+            AIInfraInstruction::RevokeAllowance => {
+                Self::process_revoke_allowance(program_id, accounts)
+            }
+            AIInfraInstruction::CreateTaskWithAllowance { requirements, payment_amount } => {
+                Self::process_create_task_with_allowance(
+                    program_id,
+                    accounts,
+                    requirements,
+                    payment_amount,
+                )
+            }
+            AIInfraInstruction::SetAgentLimits { max_concurrent_tasks } => {
+                Self::process_set_agent_limits(program_id, accounts, max_concurrent_tasks)
+            }
+            AIInfraInstruction::SetCapabilities { capabilities } => {
+                Self::process_set_capabilities(program_id, accounts, capabilities)
+            }
+            AIInfraInstruction::MigrateAccount => {
+                Self::process_migrate_account(program_id, accounts)
+            }
+            AIInfraInstruction::InitializeRegistry => {
+                Self::process_initialize_registry(program_id, accounts)
+            }
+            AIInfraInstruction::SetExpectedResult { task_id, expected_result_hash } => {
+                Self::process_set_expected_result(
+                    program_id,
+                    accounts,
+                    task_id,
+                    expected_result_hash,
+                )
+            }
+            AIInfraInstruction::SetAttestationKey { attestation_key } => {
+                Self::process_set_attestation_key(program_id, accounts, attestation_key)
+            }
+            AIInfraInstruction::CreateBatchTask { task_count, payment_per_task } => {
+                Self::process_create_batch_task(program_id, accounts, task_count, payment_per_task)
+            }
+            AIInfraInstruction::SubmitBatchResult { merkle_root } => {
+                Self::process_submit_batch_result(program_id, accounts, merkle_root)
+            }
+            AIInfraInstruction::ClaimBatchItem { index, result_hash, proof } => {
+                Self::process_claim_batch_item(program_id, accounts, index, result_hash, proof)
+            }
+            AIInfraInstruction::SetReferrer { referrer } => {
+                Self::process_set_referrer(program_id, accounts, referrer)
+            }
+            AIInfraInstruction::TipAgent { amount } => {
+                Self::process_tip_agent(program_id, accounts, amount)
+            }
+            AIInfraInstruction::ReleasePayments => {
+                Self::process_release_payments(program_id, accounts)
+            }
+            AIInfraInstruction::CreateTemplate { requirements, default_payment } => {
+                Self::process_create_template(program_id, accounts, requirements, default_payment)
+            }
+            AIInfraInstruction::UpdateTemplate { requirements, default_payment } => {
+                Self::process_update_template(program_id, accounts, requirements, default_payment)
+            }
+            AIInfraInstruction::CloseTemplate => {
+                Self::process_close_template(program_id, accounts)
+            }
+            AIInfraInstruction::CreateTaskFromTemplate { template, payment_amount } => {
+                Self::process_create_task_from_template(
+                    program_id,
+                    accounts,
+                    template,
+                    payment_amount,
+                )
+            }
+            AIInfraInstruction::CreateRecurringTask {
+                agent,
+                requirements,
+                payment_per_run,
+                interval_seconds,
+                funding,
+            } => Self::process_create_recurring_task(
+                program_id,
+                accounts,
+                agent,
+                requirements,
+                payment_per_run,
+                interval_seconds,
+                funding,
+            ),
+            AIInfraInstruction::CrankRecurringTask => {
+                Self::process_crank_recurring_task(program_id, accounts)
+            }
+            AIInfraInstruction::CancelRecurringTask => {
+                Self::process_cancel_recurring_task(program_id, accounts)
+            }
+            AIInfraInstruction::SplitTask { shares } => {
+                Self::process_split_task(program_id, accounts, shares)
+            }
+            AIInfraInstruction::AggregateResults => {
+                Self::process_aggregate_results(program_id, accounts)
+            }
+            AIInfraInstruction::ReissueShard => {
+                Self::process_reissue_shard(program_id, accounts)
+            }
+            AIInfraInstruction::CreateRedundantTask { requirements, payment, replicas, quorum } => {
+                Self::process_create_redundant_task(
+                    program_id,
+                    accounts,
+                    requirements,
+                    payment,
+                    replicas,
+                    quorum,
+                )
+            }
+            AIInfraInstruction::SubmitRedundantResult { result_hash } => {
+                Self::process_submit_redundant_result(program_id, accounts, result_hash)
+            }
+            AIInfraInstruction::FinalizeRedundantTask => {
+                Self::process_finalize_redundant_task(program_id, accounts)
+            }
+            AIInfraInstruction::SetAgentMetadata { name, uri, endpoint } => {
+                Self::process_set_agent_metadata(program_id, accounts, name, uri, endpoint)
+            }
+            AIInfraInstruction::UpdateTaskPriority { task_id, priority } => {
+                Self::process_update_task_priority(program_id, accounts, task_id, priority)
+            }
+            AIInfraInstruction::SetAcceptedTags { accepted_tags } => {
+                Self::process_set_accepted_tags(program_id, accounts, accepted_tags)
+            }
+            AIInfraInstruction::SetWithdrawalPolicy { max_per_day, cooldown_seconds } => {
+                let policy = WithdrawalPolicy { max_per_day, cooldown_seconds };
+                Self::process_set_withdrawal_policy(program_id, accounts, policy)
+            }
+            AIInfraInstruction::ClaimGrant => Self::process_claim_grant(program_id, accounts),
+            AIInfraInstruction::FundFaucet { amount } => {
+                Self::process_fund_faucet(program_id, accounts, amount)
+            }
+            AIInfraInstruction::SetGrantAmount { amount } => {
+                Self::process_set_grant_amount(program_id, accounts, amount)
+            }
+            AIInfraInstruction::InitializeInsurancePool => {
+                Self::process_initialize_insurance_pool(program_id, accounts)
+            }
+            AIInfraInstruction::FileInsuranceClaim => {
+                Self::process_file_insurance_claim(program_id, accounts)
+            }
+            AIInfraInstruction::SweepInsurancePool { amount } => {
+                Self::process_sweep_insurance_pool(program_id, accounts, amount)
+            }
+            AIInfraInstruction::SetMultisigOwner { signers, threshold } => {
+                Self::process_set_multisig_owner(program_id, accounts, signers, threshold)
+            }
+            AIInfraInstruction::BindAgentToNft => {
+                Self::process_bind_agent_to_nft(program_id, accounts)
+            }
+            AIInfraInstruction::UnbindAgentFromNft => {
+                Self::process_unbind_agent_from_nft(program_id, accounts)
+            }
+            AIInfraInstruction::SetPreferNative { prefer_native } => {
+                Self::process_set_prefer_native(program_id, accounts, prefer_native)
+            }
+            AIInfraInstruction::RefundBatchTask => {
+                Self::process_refund_batch_task(program_id, accounts)
             }
-            // ... Add other instruction handlers
         }
     }
 
+    // Implementation of register_agent
     fn process_register_agent(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-        // Your existing register_agent implementation
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let mut registry_account = next_account_info(accounts_iter)?;
+        // Clients built before the rent sysvar was dropped still pass it here
+        if passes_rent_sysvar(accounts) {
+            registry_account = next_account_info(accounts_iter)?;
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (agent_address, bump) = find_agent_address(owner_account.key, program_id);
+        if agent_address != *agent_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Registering an existing agent would wipe its credits and reputation
+        if agent_account.owner == program_id && !agent_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let mut registry = Registry::load(program_id, registry_account)?;
+        registry.total_agents = registry.total_agents.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        let clock = Clock::get()?;
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: *owner_account.key,
+            compute_credits: 0,
+            reputation_score: 100, // Initial reputation
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: *owner_account.key,
+            last_heartbeat: clock.unix_timestamp,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: clock.epoch,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+
+        let rent = &Rent::get()?;
+
+        // Create the agent PDA
+        create_pda_account(
+            owner_account,
+            agent_account,
+            system_program,
+            program_id,
+            rent,
+            AIAgent::LEN,
+            &[pda::AGENT_SEED, owner_account.key.as_ref(), &[bump]],
+        )?;
+        assert_rent_exempt(rent, agent_account, AIAgent::LEN)?;
+
+        save_account(agent_account, &agent)?;
+        save_account(registry_account, &registry)?;
+
+        emit(Event::AgentRegistered { agent: *agent_account.key, owner: *owner_account.key })?;
+        msg!("AI Agent registered successfully");
+        Ok(())
+    }
+
+    // Implementation of create_task. A task priced in USD escrows however many
+    // lamports the config's oracle says its price is, and takes the oracle
+    // last, after any optional accounts. A description goes to a memo, after
+    // which the memo program comes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_create_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        payment_amount: u64,
+        priority: u8,
+        tags: [u8; TASK_TAGS],
+        payment_usd_cents: Option<u64>,
+        description: Option<String>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let task_page_account = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        let tail = accounts_iter.as_slice();
+        let (memo_program, tail) = match &description {
+            Some(description) if description.len() > MAX_TASK_DESCRIPTION_LEN => {
+                return Err(AIInfraError::DescriptionTooLong.into())
+            }
+            Some(_) => {
+                let (memo_program, tail) =
+                    tail.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+                if *memo_program.key != spl_memo::id() {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                (Some(memo_program), tail)
+            }
+            None => (None, tail),
+        };
+        let (payment_amount, oracle_price, tail) = match payment_usd_cents {
+            Some(_) if payment_amount != 0 => return Err(ProgramError::InvalidArgument),
+            Some(usd_cents) => {
+                let (oracle_account, tail) =
+                    tail.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let price = load_oracle_price(&config, oracle_account)?;
+                (price.lamports_for_usd_cents(usd_cents)?, Some(price), tail)
+            }
+            None => (payment_amount, None, tail),
+        };
+        check_payment(&config, &requirements, payment_amount, priority)?;
+
+        let (requester_account, allowlist_account) = task_creation_tail(tail);
+        count_requested_task(
+            program_id,
+            &config,
+            requester_account,
+            payer_account,
+            system_program,
+        )?;
+
+        init_task(
+            program_id,
+            task_account,
+            agent_account,
+            payer_account,
+            payer_account.key,
+            system_program,
+            vault_account,
+            config_account,
+            registry_account,
+            task_page_account,
+            &mut config,
+            requirements,
+            payment_amount,
+            None,
+            priority,
+            tags,
+            allowlist_account,
+        )?;
+        if payment_usd_cents.is_some() || description.is_some() {
+            let mut task = load_account::<ComputeTask>(task_account)?;
+            task.payment_usd_cents = payment_usd_cents.unwrap_or(0);
+            if let Some(description) = &description {
+                task.description_hash = hash(description.as_bytes()).to_bytes();
+            }
+            save_account(task_account, &task)?;
+        }
+        if let (Some(usd_cents), Some(price)) = (payment_usd_cents, oracle_price) {
+            emit(Event::TaskPricedInUsd {
+                task: *task_account.key,
+                usd_cents,
+                payment_amount,
+                price: price.price,
+                expo: price.expo,
+            })?;
+        }
+        // The description only lands in the transaction's record
+        if let (Some(description), Some(memo_program)) = (&description, memo_program) {
+            let memo = spl_memo::build_memo(description.as_bytes(), &[]);
+            invoke(&memo, std::slice::from_ref(memo_program))?;
+        }
+
+        // Escrow the payment, plus enough to keep the vault rent exempt until
+        // it is drained
+        let escrow = payment_amount.checked_add(Rent::get()?.minimum_balance(0))
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        invoke(
+            &system_instruction::transfer(payer_account.key, vault_account.key, escrow),
+            &[payer_account.clone(), vault_account.clone(), system_program.clone()],
+        )?;
+
+        msg!("Compute task created successfully");
+        Ok(())
+    }
+
+    // Like CreateTask, but the payment comes out of the payer's requester credits.
+    // The payer still funds the task's rent and the vault's rent reserve.
+    pub fn process_create_task_with_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        payment_amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let task_page_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        check_payment(&config, &requirements, payment_amount, 0)?;
+
+        let (mut requester, credit_vault_bump) =
+            load_requester(program_id, requester_account, payer_account, credit_vault)?;
+        requester.credits = requester.credits.checked_sub(payment_amount)
+            .ok_or(AIInfraError::InsufficientCredits)?;
+        config.count_task(
+            payer_account.key,
+            &mut requester.last_task_slot,
+            &mut requester.tasks_in_window,
+            Clock::get()?.slot,
+        )?;
+
+        init_task(
+            program_id,
+            task_account,
+            agent_account,
+            payer_account,
+            payer_account.key,
+            system_program,
+            vault_account,
+            config_account,
+            registry_account,
+            task_page_account,
+            &mut config,
+            requirements,
+            payment_amount,
+            None,
+            0,
+            [0; TASK_TAGS],
+            accounts_iter.next(),
+        )?;
+
+        save_account(requester_account, &requester)?;
+        deposit_to_vault(payer_account, vault_account, system_program, 0)?;
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            requester_account.key,
+            credit_vault_bump,
+            credit_vault,
+            vault_account,
+            system_program,
+            payment_amount,
+        )?;
+
+        msg!("Compute task created successfully");
         Ok(())
     }
 
-    fn process_create_task(
+    // Like CreateTask, but the payment comes out of the funding agent's credits,
+    // drawn against the allowance its owner approved for the payer. The payer
+    // funds the rents and is the task's requester.
+    pub fn process_create_task_with_allowance(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         requirements: ComputeRequirements,
         payment_amount: u64,
     ) -> ProgramResult {
-        // Your existing create_task implementation
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let task_page_account = next_account_info(accounts_iter)?;
+        let funding_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let allowance_account = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        check_payment(&config, &requirements, payment_amount, 0)?;
+
+        // init_task rewrites the assigned agent, so it can't also be the funder
+        if funding_account.key == agent_account.key {
+            return Err(AIInfraError::DuplicateAccount.into());
+        }
+
+        if funding_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut funding = load_account::<AIAgent>(funding_account)?;
+        funding.assert_address(program_id, funding_account.key)?;
+
+        let (credit_vault_address, credit_vault_bump) =
+            find_credit_vault_address(funding_account.key, program_id);
+        if credit_vault_address != *credit_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let mut allowance =
+            Allowance::load(program_id, funding_account.key, payer_account.key, allowance_account)?;
+        allowance.amount = allowance.amount.checked_sub(payment_amount)
+            .ok_or(AIInfraError::AllowanceExceeded)?;
+
+        if funding.compute_credits < payment_amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        funding.compute_credits = funding.compute_credits.checked_sub(payment_amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        config.count_task(
+            payer_account.key,
+            &mut funding.last_task_slot,
+            &mut funding.tasks_in_window,
+            Clock::get()?.slot,
+        )?;
+
+        init_task(
+            program_id,
+            task_account,
+            agent_account,
+            payer_account,
+            payer_account.key,
+            system_program,
+            vault_account,
+            config_account,
+            registry_account,
+            task_page_account,
+            &mut config,
+            requirements,
+            payment_amount,
+            None,
+            0,
+            [0; TASK_TAGS],
+            accounts_iter.next(),
+        )?;
+
+        save_account(allowance_account, &allowance)?;
+        save_account(funding_account, &funding)?;
+        emit(Event::AllowanceSpent {
+            agent: *funding_account.key,
+            spender: *payer_account.key,
+            amount: payment_amount,
+            remaining: allowance.amount,
+        })?;
+        deposit_to_vault(payer_account, vault_account, system_program, 0)?;
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            funding_account.key,
+            credit_vault_bump,
+            credit_vault,
+            vault_account,
+            system_program,
+            payment_amount,
+        )?;
+
+        msg!("Compute task created successfully");
+        Ok(())
+    }
+
+    // Like CreateTask, but the payment is escrowed in SPL or Token-2022
+    // tokens held by the vault's associated token account
+    pub fn process_create_task_with_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        amount: u64,
+        mint: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let payer_token = next_account_info(accounts_iter)?;
+        let vault_token = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        let associated_token_program = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let task_page_account = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if *mint_account.key != mint {
+            return Err(AIInfraError::TokenMintMismatch.into());
+        }
+
+        let token = TokenProgram::from_id(token_program.key)?;
+        if *associated_token_program.key != spl_associated_token_account::id()
+            || mint_account.owner != token_program.key
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let decimals = unpack_mint(mint_account)?.decimals;
+
+        assert_token_account(payer_token, &mint, payer_account.key)?;
+        let vault_token_address = get_associated_token_address_with_program_id(
+            vault_account.key,
+            &mint,
+            token_program.key,
+        );
+        if vault_token_address != *vault_token.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let (requester_account, allowlist_account) = task_creation_tail(accounts_iter.as_slice());
+        count_requested_task(
+            program_id,
+            &config,
+            requester_account,
+            payer_account,
+            system_program,
+        )?;
+
+        // Open the vault's token account and escrow the payment in it. A
+        // Token-2022 mint may withhold a transfer fee from it, so the task
+        // is paid what the new account received rather than `amount`.
+        invoke(
+            &create_associated_token_account(
+                payer_account.key,
+                vault_account.key,
+                &mint,
+                token_program.key,
+            ),
+            &[
+                payer_account.clone(),
+                vault_token.clone(),
+                vault_account.clone(),
+                mint_account.clone(),
+                system_program.clone(),
+                token_program.clone(),
+                associated_token_program.clone(),
+            ],
+        )?;
+        invoke(
+            &spl_token_2022::instruction::transfer_checked(
+                token_program.key,
+                payer_token.key,
+                mint_account.key,
+                vault_token.key,
+                payer_account.key,
+                &[],
+                amount,
+                decimals,
+            )?,
+            &[
+                payer_token.clone(),
+                mint_account.clone(),
+                vault_token.clone(),
+                payer_account.clone(),
+                token_program.clone(),
+            ],
+        )?;
+        let received = unpack_token_account(vault_token)?.amount;
+
+        init_task(
+            program_id,
+            task_account,
+            agent_account,
+            payer_account,
+            payer_account.key,
+            system_program,
+            vault_account,
+            config_account,
+            registry_account,
+            task_page_account,
+            &mut config,
+            requirements,
+            received,
+            Some((mint, token)),
+            0,
+            [0; TASK_TAGS],
+            allowlist_account,
+        )?;
+
+        msg!("Compute task created successfully");
+        Ok(())
+    }
+
+    pub fn process_start_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Both accounts are read and updated in place, see zero_copy.rs
+        let mut task_data = task_account.try_borrow_mut_data()?;
+        let mut task = zero_copy::task_mut(&mut task_data)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status()? != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.header.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent_data = agent_account.try_borrow_data()?;
+        let agent = zero_copy::agent(&agent_data)?;
+        agent.assert_address(program_id, agent_account.key)?;
+
+        // Only the assigned agent's owner or operator may pick the task up
+        if !is_view_operator(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if agent.header.is_active == 0 {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+        task.assert_qualified(&agent)?;
+
+        task.start(Clock::get()?.unix_timestamp)?;
+
+        emit(Event::TaskStarted { task: *task_account.key, agent: *agent_account.key })?;
+        msg!("Task started successfully");
+        Ok(())
+    }
+
+    // Finishes a task that opted out of commit-reveal in a single step
+    pub fn process_complete_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        result_hash: [u8; 32],
+        result_data: Option<Vec<u8>>,
+    ) -> ProgramResult {
+        // An inline payload must be the result its hash stands for
+        if let Some(data) = &result_data {
+            if data.len() > MAX_RESULT_DATA_LEN {
+                return Err(AIInfraError::ResultTooLarge.into());
+            }
+            if hash(data).to_bytes() != result_hash {
+                return Err(AIInfraError::ResultMismatch.into());
+            }
+        }
+
+        finish_task(program_id, accounts, task_id, result_hash, None, result_data)
+    }
+
+    // Records the agent's commitment to a result without revealing it
+    pub fn process_commit_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        commitment: [u8; 32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::InProgress || task.commitment.is_some() {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_operator(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        task.commitment = Some(commitment);
+        save_account(task_account, &task)?;
+
+        emit(Event::ResultCommitted { task: *task_account.key, agent: *agent_account.key })?;
+        msg!("Task result committed");
+        Ok(())
+    }
+
+    // Finishes a task by revealing the result and salt behind its commitment
+    pub fn process_reveal_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        result_hash: [u8; 32],
+        salt: [u8; 32],
+    ) -> ProgramResult {
+        finish_task(program_id, accounts, task_id, result_hash, Some(salt), None)
+    }
+
+    // Completes every task in `results`, all run by the agent passed first. The
+    // shared accounts are followed by each task's account, vault and requester
+    // in order, plus its token accounts when it is paid in tokens. Any failing
+    // entry fails the whole batch.
+    pub fn process_batch_complete_tasks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        results: Vec<TaskResult>,
+    ) -> ProgramResult {
+        if results.is_empty() || results.len() > MAX_BATCH_COMPLETIONS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        let config = load_completion_config(program_id, config_account, treasury_account)?;
+        for TaskResult { task_id, result_hash } in results {
+            let task_account = next_account_info(accounts_iter)?;
+            let vault_account = next_account_info(accounts_iter)?;
+            let requester_account = next_account_info(accounts_iter)?;
+            complete_task(
+                program_id,
+                accounts_iter,
+                task_account,
+                agent_account,
+                owner_account,
+                vault_account,
+                requester_account,
+                system_program,
+                treasury_account,
+                find_instructions_sysvar(accounts),
+                accounts.last(),
+                &config,
+                task_id,
+                result_hash,
+                None,
+            )?;
+        }
+
+        msg!("Task batch completed successfully");
         Ok(())
     }
-    
-    // ... Add other process functions
-}
\ No newline at end of file
+
+    // Pays the agent for a task after its dispute window has passed
+    pub fn process_claim_payment(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Completed || !task.payment_pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        if config.treasury != *treasury_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if config.require_verification && !task.verified {
+            return Err(AIInfraError::ResultNotVerified.into());
+        }
+        if !config.dispute_window_closed(&task, Clock::get()?.unix_timestamp)? {
+            return Err(AIInfraError::DisputeWindowOpen.into());
+        }
+
+        settle_completed_task(
+            program_id,
+            accounts_iter,
+            task_account,
+            agent_account,
+            task,
+            agent,
+            &config,
+            owner_account,
+            vault_account,
+            requester_account,
+            system_program,
+            treasury_account,
+        )?;
+
+        msg!("Task payment claimed successfully");
+        Ok(())
+    }
+
+    // Lets the requester challenge a completed task's result while its dispute
+    // window is open, holding the payment until the arbitrator rules
+    pub fn process_dispute_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if task.status != TaskStatus::Completed || !task.payment_pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.dispute_window_closed(&task, Clock::get()?.unix_timestamp)? {
+            return Err(AIInfraError::DisputeWindowClosed.into());
+        }
+
+        task.transition(TaskStatus::Disputed)?;
+        save_account(task_account, &task)?;
+
+        emit(Event::TaskDisputed { task: *task_account.key, requester: *requester_account.key })?;
+        msg!("Task disputed");
+        Ok(())
+    }
+
+    // Settles a disputed task. Upholding the result pays the agent as if the
+    // window had passed; otherwise the task fails, the requester is refunded and
+    // the agent is penalized and slashed.
+    pub fn process_resolve_dispute(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        uphold_result: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let arbitrator_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !arbitrator_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.arbitrator != *arbitrator_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if config.treasury != *treasury_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Disputed {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        emit(Event::DisputeResolved { task: *task_account.key, uphold_result })?;
+
+        if uphold_result {
+            task.transition(TaskStatus::Completed)?;
+
+            // An upheld result that still needs a verifier's sign-off waits for it
+            if config.require_verification && !task.verified {
+                save_account(task_account, &task)?;
+                msg!("Dispute resolved for the agent, verification pending");
+                return Ok(());
+            }
+
+            settle_completed_task(
+                program_id,
+                accounts_iter,
+                task_account,
+                agent_account,
+                task,
+                agent,
+                &config,
+                owner_account,
+                vault_account,
+                requester_account,
+                system_program,
+                treasury_account,
+            )?;
+
+            msg!("Dispute resolved for the agent");
+            return Ok(());
+        }
+
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        task.payment_pending = false;
+        slash_stake(
+            program_id,
+            agent_account.key,
+            &mut agent,
+            &config,
+            stake_vault,
+            treasury_account,
+            system_program,
+        )?;
+        fail_and_refund(
+            task_account,
+            agent_account,
+            task,
+            agent,
+            FailureReason::DisputeLost,
+            config.reputation_penalty,
+            &config,
+            vault_account,
+            requester_account,
+            system_program,
+            token_escrow,
+        )?;
+
+        msg!("Dispute resolved for the requester");
+        Ok(())
+    }
+
+    // Registers `verifier` as a key that may sign off on task results. Only the
+    // config's admin may do so; the admin also pays for the verifier account.
+    pub fn process_register_verifier(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        verifier: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let verifier_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (verifier_address, bump) = find_verifier_address(&verifier, program_id);
+        if verifier_address != *verifier_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if verifier_account.owner == program_id && !verifier_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let rent = &Rent::get()?;
+        create_pda_account(
+            admin_account,
+            verifier_account,
+            system_program,
+            program_id,
+            rent,
+            Verifier::LEN,
+            &[pda::VERIFIER_SEED, verifier.as_ref(), &[bump]],
+        )?;
+        assert_rent_exempt(rent, verifier_account, Verifier::LEN)?;
+
+        let verifier = Verifier {
+            account_type: AccountType::Verifier,
+            version: CURRENT_VERSION,
+            authority: verifier,
+            bump,
+        };
+        save_account(verifier_account, &verifier)?;
+
+        emit(Event::VerifierRegistered { verifier: verifier.authority })?;
+        msg!("Verifier registered successfully");
+        Ok(())
+    }
+
+    // Records a registered verifier's sign-off on a completed task's result and
+    // pays the agent, unless a dispute window is still open. In that case the
+    // payment waits for ClaimPayment as usual.
+    pub fn process_verify_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let verifier_signer = next_account_info(accounts_iter)?;
+        let verifier_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id
+            || agent_account.owner != program_id
+            || verifier_account.owner != program_id
+        {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !verifier_signer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let verifier = load_account::<Verifier>(verifier_account)?;
+        verifier.assert_address(program_id, verifier_account.key)?;
+        if verifier.authority != *verifier_signer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Completed || !task.payment_pending || task.verified {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        if config.treasury != *treasury_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        task.verified = true;
+        emit(Event::ResultVerified { task: *task_account.key, verifier: *verifier_signer.key })?;
+        if !config.dispute_window_closed(&task, Clock::get()?.unix_timestamp)? {
+            save_account(task_account, &task)?;
+            msg!("Task result verified, payment pending");
+            return Ok(());
+        }
+
+        settle_completed_task(
+            program_id,
+            accounts_iter,
+            task_account,
+            agent_account,
+            task,
+            agent,
+            &config,
+            owner_account,
+            vault_account,
+            requester_account,
+            system_program,
+            treasury_account,
+        )?;
+
+        msg!("Task result verified successfully");
+        Ok(())
+    }
+
+    // Fails a completed task that no verifier signed off on within the config's
+    // timeout and refunds the requester. The agent isn't penalized, since the
+    // missing sign-off need not be its fault. Anyone may call it.
+    pub fn process_refund_unverified_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Completed
+            || !task.payment_pending
+            || task.verified
+            || !config.require_verification
+        {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if !config.verification_timed_out(&task, Clock::get()?.unix_timestamp)? {
+            return Err(AIInfraError::VerificationPending.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        task.payment_pending = false;
+        fail_and_refund(
+            task_account,
+            agent_account,
+            task,
+            agent,
+            FailureReason::Unverified,
+            0,
+            &config,
+            vault_account,
+            requester_account,
+            system_program,
+            token_escrow,
+        )?;
+
+        msg!("Unverified task refunded");
+        Ok(())
+    }
+
+    // Assigns an open task to the signing owner's agent and starts it. Only one
+    // claim can succeed, since the first one records its agent.
+    pub fn process_claim_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.agent != Pubkey::default() {
+            return Err(AIInfraError::TaskAlreadyClaimed.into());
+        }
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_operator(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+        task.assert_qualified(&agent)?;
+        task.assert_tags_accepted(&agent)?;
+
+        agent.take_task()?;
+        agent.open_tasks = agent.open_tasks.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        task.agent = *agent_account.key;
+        task.start(Clock::get()?.unix_timestamp)?;
+        save_account(task_account, &task)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::TaskClaimed { task: *task_account.key, agent: *agent_account.key })?;
+        msg!("Task claimed successfully");
+        Ok(())
+    }
+
+    // Offers to run an open task for `amount`, which can't exceed what the
+    // requester escrowed
+    pub fn process_submit_bid(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let bid_account = next_account_info(accounts_iter)?;
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if task.agent != Pubkey::default() {
+            return Err(AIInfraError::TaskAlreadyClaimed.into());
+        }
+        if amount > task.payment_amount {
+            return Err(AIInfraError::BidTooHigh.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+        task.assert_qualified(&agent)?;
+
+        let (bid_address, bump) = find_bid_address(task_account.key, agent_account.key, program_id);
+        if bid_address != *bid_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if bid_account.owner == program_id && !bid_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let bid = Bid {
+            account_type: AccountType::Bid,
+            version: CURRENT_VERSION,
+            task: *task_account.key,
+            agent: *agent_account.key,
+            amount,
+            bump,
+        };
+        let rent = &Rent::get()?;
+
+        create_pda_account(
+            owner_account,
+            bid_account,
+            system_program,
+            program_id,
+            rent,
+            Bid::LEN,
+            &[pda::BID_SEED, task_account.key.as_ref(), agent_account.key.as_ref(), &[bump]],
+        )?;
+        assert_rent_exempt(rent, bid_account, Bid::LEN)?;
+
+        save_account(bid_account, &bid)?;
+
+        emit(Event::BidSubmitted {
+            bid: *bid_account.key,
+            task: *task_account.key,
+            agent: *agent_account.key,
+            amount,
+        })?;
+        msg!("Bid submitted successfully");
+        Ok(())
+    }
+
+    // Assigns an open task to the bidding agent at the bid price and refunds the
+    // rest of the escrow to the requester, who must sign. The task stays Pending
+    // until the agent starts it.
+    pub fn process_accept_bid(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let bid_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if task_account.owner != program_id
+            || bid_account.owner != program_id
+            || agent_account.owner != program_id
+        {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if task.agent != Pubkey::default() {
+            return Err(AIInfraError::TaskAlreadyClaimed.into());
+        }
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+
+        let bid = load_account::<Bid>(bid_account)?;
+        bid.assert_address(program_id, bid_account.key)?;
+        if bid.task != *task_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if bid.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+
+        // Token escrows refund the difference to the requester's token account
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        let refund = task.payment_amount.checked_sub(bid.amount)
+            .ok_or(AIInfraError::BidTooHigh)?;
+        agent.take_task()?;
+        agent.open_tasks = agent.open_tasks.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        task.agent = *agent_account.key;
+        task.payment_amount = bid.amount;
+        save_account(task_account, &task)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::BidAccepted {
+            task: *task_account.key,
+            agent: *agent_account.key,
+            amount: bid.amount,
+        })?;
+
+        match token_escrow {
+            Some((vault_token, requester_token, token)) => transfer_from_token_vault(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                vault_token,
+                requester_token,
+                token,
+                refund,
+            )?,
+            None => transfer_from_vault(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                requester_account,
+                system_program,
+                refund,
+            )?,
+        }
+
+        msg!("Bid accepted successfully");
+        Ok(())
+    }
+
+    // Withdraws a bid, or clears one out after the auction, returning its rent
+    // to the destination account. The bidding agent's owner signs.
+    pub fn process_close_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let bid_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let destination_account = next_account_info(accounts_iter)?;
+
+        if bid_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if destination_account.key == bid_account.key {
+            return Err(AIInfraError::DuplicateAccount.into());
+        }
+
+        let bid = load_account::<Bid>(bid_account)?;
+        bid.assert_address(program_id, bid_account.key)?;
+        if bid.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        bid_account.data.borrow_mut().fill(0);
+
+        let bid_lamports = bid_account.lamports();
+        **destination_account.lamports.borrow_mut() = destination_account.lamports()
+            .checked_add(bid_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **bid_account.lamports.borrow_mut() = 0;
+
+        emit(Event::BidClosed { bid: *bid_account.key })?;
+        msg!("Bid closed successfully");
+        Ok(())
+    }
+
+    // Cancels a task nobody has started yet and refunds its escrow to the
+    // requester. Either the requester or the assigned agent's owner, who can
+    // decline the task, authorizes it.
+    pub fn process_cancel_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        let agent = if owner_account.is_signer && task.requester == *owner_account.key {
+            load_task_agent(program_id, &task, agent_account)?
+        } else {
+            authorize_task_signer(program_id, &task, agent_account, owner_account, accounts.last())?
+        };
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        task.transition(TaskStatus::Cancelled)?;
+        save_account(task_account, &task)?;
+        emit(Event::TaskCancelled { task: *task_account.key, refund: task.payment_amount })?;
+
+        if let Some(mut agent) = agent {
+            agent.release_task();
+            agent.tasks_cancelled = agent.tasks_cancelled.checked_add(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            agent.open_tasks = agent.open_tasks.checked_sub(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            save_account(agent_account, &agent)?;
+        }
+
+        // Refund the escrow to the requester
+        if let Some((vault_token, requester_token, token)) = token_escrow {
+            release_token_escrow(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                vault_token,
+                requester_token,
+                requester_account,
+                token,
+            )?;
+        }
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            requester_account,
+            system_program,
+            vault_account.lamports(),
+        )?;
+
+        msg!("Task cancelled successfully");
+        Ok(())
+    }
+
+    // Marks a running task as failed. The escrow goes back to the requester, and
+    // the agent takes a reputation hit and has part of its stake slashed.
+    pub fn process_fail_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        reason: FailureReason,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::InProgress {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_operator(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        let config = Config::load(program_id, config_account)?;
+        slash_stake(
+            program_id,
+            agent_account.key,
+            &mut agent,
+            &config,
+            stake_vault,
+            treasury_account,
+            system_program,
+        )?;
+        fail_and_refund(
+            task_account,
+            agent_account,
+            task,
+            agent,
+            reason,
+            config.reputation_penalty,
+            &config,
+            vault_account,
+            requester_account,
+            system_program,
+            token_escrow,
+        )?;
+
+        msg!("Task failed");
+        Ok(())
+    }
+
+    // Times out a running task past its deadline. Anyone may call it; the task
+    // fails as if its agent had given up on it.
+    pub fn process_expire_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::InProgress {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        if !task.is_past_deadline(Clock::get()?.unix_timestamp) {
+            return Err(AIInfraError::TaskNotExpired.into());
+        }
+
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let token_escrow = match task.payment_mint {
+            Some(mint) => Some(next_token_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+                &task.requester,
+            )?),
+            None => None,
+        };
+
+        let config = Config::load(program_id, config_account)?;
+        slash_stake(
+            program_id,
+            agent_account.key,
+            &mut agent,
+            &config,
+            stake_vault,
+            treasury_account,
+            system_program,
+        )?;
+        fail_and_refund(
+            task_account,
+            agent_account,
+            task,
+            agent,
+            FailureReason::Expired,
+            config.reputation_penalty,
+            &config,
+            vault_account,
+            requester_account,
+            system_program,
+            token_escrow,
+        )?;
+
+        msg!("Task expired");
+        Ok(())
+    }
+
+    // Closes a finished task and sends its rent to the destination account,
+    // and a failed task's held refund to the requester. The task's requester
+    // may sign for every terminal status, the agent owner only for a
+    // completed task.
+    pub fn process_close_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let destination_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if destination_account.key == task_account.key {
+            return Err(AIInfraError::DuplicateAccount.into());
+        }
+
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        match task.status {
+            TaskStatus::Completed if !task.payment_pending => {}
+            TaskStatus::Failed | TaskStatus::Cancelled => {}
+            _ => return Err(AIInfraError::TaskStillOpen.into()),
+        }
+
+        if !(owner_account.is_signer && *owner_account.key == task.requester) {
+            if task.status != TaskStatus::Completed {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            authorize_task_signer(
+                program_id,
+                &task,
+                agent_account,
+                owner_account,
+                accounts.last(),
+            )?;
+        }
+
+        // A refund still held for an insurance claim goes back to the
+        // requester, the only one who can close a failed task
+        if task.held_refund != 0 {
+            let vault_account = next_account_info(accounts_iter)?;
+            let system_program = next_system_program(accounts_iter)?;
+            task.assert_vault(program_id, task_account.key, vault_account.key)?;
+            transfer_from_vault(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                owner_account,
+                system_program,
+                vault_account.lamports(),
+            )?;
+        }
+
+        // Wipe the data before draining so the account can't be revived with
+        // stale state later in the same transaction
+        task_account.data.borrow_mut().fill(0);
+
+        let task_lamports = task_account.lamports();
+        **destination_account.lamports.borrow_mut() = destination_account.lamports()
+            .checked_add(task_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **task_account.lamports.borrow_mut() = 0;
+
+        emit(Event::TaskClosed { task: *task_account.key })?;
+        msg!("Task closed successfully");
+        Ok(())
+    }
+
+    // Closes an agent with nothing left in it and returns its rent, and the
+    // credit vault's reserve, to the owner
+    pub fn process_deregister_agent(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if agent.owner != *owner_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        agent.assert_not_nft_bound()?;
+        agent.assert_not_multisig_owned()?;
+
+        // Credits, held payments, stake and tasks still in flight would be
+        // lost with the account
+        if agent.compute_credits > 0
+            || agent.pending_payments > 0
+            || agent.staked_lamports > 0
+            || agent.open_tasks > 0
+        {
+            return Err(AIInfraError::AgentHasOpenBalance.into());
+        }
+
+        let (credit_vault_address, credit_vault_bump) =
+            find_credit_vault_address(agent_account.key, program_id);
+        if credit_vault_address != *credit_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let (stake_vault_address, stake_vault_bump) =
+            find_stake_vault_address(agent_account.key, program_id);
+        if stake_vault_address != *stake_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // With no credits or stake left the vaults only hold their rent reserves
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            agent_account.key,
+            credit_vault_bump,
+            credit_vault,
+            owner_account,
+            system_program,
+            credit_vault.lamports(),
+        )?;
+        transfer_from_account_vault(
+            pda::STAKE_VAULT_SEED,
+            agent_account.key,
+            stake_vault_bump,
+            stake_vault,
+            owner_account,
+            system_program,
+            stake_vault.lamports(),
+        )?;
+
+        agent_account.data.borrow_mut().fill(0);
+
+        let agent_lamports = agent_account.lamports();
+        **owner_account.lamports.borrow_mut() = owner_account.lamports()
+            .checked_add(agent_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **agent_account.lamports.borrow_mut() = 0;
+
+        emit(Event::AgentDeregistered { agent: *agent_account.key, owner: *owner_account.key })?;
+        msg!("AI Agent deregistered successfully");
+        Ok(())
+    }
+
+    // Hands the agent to a new owner. Both wallets sign so ownership can't be
+    // pushed onto an address that never agreed to it.
+    pub fn process_transfer_ownership(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_owner: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let new_owner_account = next_account_info(accounts_iter)?;
+
+        if !new_owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if *new_owner_account.key != new_owner {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        // The new owner picks their own operator and signs with their own key
+        agent.owner = new_owner;
+        agent.operator = None;
+        agent.multisig_owner = false;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AgentOwnershipTransferred {
+            agent: *agent_account.key,
+            previous_owner: *owner_account.key,
+            new_owner,
+        })?;
+
+        msg!("Agent ownership transferred successfully");
+        Ok(())
+    }
+
+    // Delegates the agent's task lifecycle to `operator`, or revokes the
+    // delegation when None. Only the owner may do either.
+    pub fn process_set_operator(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        operator: Option<Pubkey>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.operator = operator;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::OperatorSet { agent: *agent_account.key, operator })?;
+
+        if operator.is_some() {
+            msg!("Agent operator set");
+        } else {
+            msg!("Agent operator revoked");
+        }
+        Ok(())
+    }
+
+    // Caps the agent's unfinished tasks at `max_concurrent_tasks`, or lifts
+    // the cap when zero. Tasks already above a lowered cap keep running.
+    pub fn process_set_agent_limits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_concurrent_tasks: u32,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.max_concurrent_tasks = max_concurrent_tasks;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AgentLimitsSet { agent: *agent_account.key, max_concurrent_tasks })?;
+
+        msg!("Agent limits set");
+        Ok(())
+    }
+
+    // Sets whether the agent's wrapped SOL payments are unwrapped for its
+    // owner on completion
+    pub fn process_set_prefer_native(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        prefer_native: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.prefer_native = prefer_native;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::PreferNativeSet { agent: *agent_account.key, prefer_native })?;
+
+        msg!("Prefer native set");
+        Ok(())
+    }
+
+    // Replaces the resources the agent declares. Tasks it already runs aren't
+    // rechecked.
+    pub fn process_set_capabilities(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        capabilities: AgentCapabilities,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.capabilities = capabilities;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::CapabilitiesSet { agent: *agent_account.key, capabilities })?;
+
+        msg!("Agent capabilities set");
+        Ok(())
+    }
+
+    pub fn process_set_accepted_tags(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        accepted_tags: [u8; TAG_SET_LEN],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.accepted_tags = accepted_tags;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AcceptedTagsSet { agent: *agent_account.key, accepted_tags })?;
+
+        msg!("Agent accepted tags set");
+        Ok(())
+    }
+
+    pub fn process_set_withdrawal_policy(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        policy: WithdrawalPolicy,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        if policy.cooldown_seconds < 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        let effective_at = agent.set_withdrawal_policy(policy, Clock::get()?.unix_timestamp);
+        save_account(agent_account, &agent)?;
+
+        emit(Event::WithdrawalPolicySet {
+            agent: *agent_account.key,
+            max_per_day: policy.max_per_day,
+            cooldown_seconds: policy.cooldown_seconds,
+            effective_at,
+        })?;
+
+        msg!("Agent withdrawal policy set");
+        Ok(())
+    }
+
+    pub fn process_set_attestation_key(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        attestation_key: Option<Pubkey>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        agent.attestation_key = attestation_key;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AttestationKeySet { agent: *agent_account.key, attestation_key })?;
+
+        msg!("Agent attestation key set");
+        Ok(())
+    }
+
+    // Creates a batch of `task_count` small tasks for an active agent and
+    // escrows every item's payment up front
+    pub fn process_create_batch_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_count: u32,
+        payment_per_task: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let batch_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if task_count == 0 || task_count > MAX_BATCH_ITEMS {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let total_payment = payment_per_task.checked_mul(task_count as u64)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+
+        let index = agent.batches_created;
+        let (batch_address, bump) = find_batch_task_address(agent_account.key, index, program_id);
+        if batch_address != *batch_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (vault_address, vault_bump) = find_vault_address(batch_account.key, program_id);
+        if vault_address != *vault_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = &Rent::get()?;
+        create_pda_account(
+            requester_account,
+            batch_account,
+            system_program,
+            program_id,
+            rent,
+            BatchTask::LEN,
+            &[pda::BATCH_TASK_SEED, agent_account.key.as_ref(), &index.to_le_bytes(), &[bump]],
+        )?;
+        assert_rent_exempt(rent, batch_account, BatchTask::LEN)?;
+
+        let batch = BatchTask {
+            account_type: AccountType::BatchTask,
+            version: CURRENT_VERSION,
+            agent: *agent_account.key,
+            requester: *requester_account.key,
+            index,
+            bump,
+            vault_bump,
+            task_count,
+            payment_per_task,
+            merkle_root: None,
+            items_claimed: 0,
+            claimed: vec![0; BatchTask::BITMAP_LEN],
+            created_at: Clock::get()?.unix_timestamp,
+        };
+        save_account(batch_account, &batch)?;
+
+        agent.batches_created = agent.batches_created.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(agent_account, &agent)?;
+
+        // Escrow every item's payment, plus the vault's rent reserve
+        let escrow = total_payment.checked_add(rent.minimum_balance(0))
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        invoke(
+            &system_instruction::transfer(requester_account.key, vault_account.key, escrow),
+            &[requester_account.clone(), vault_account.clone(), system_program.clone()],
+        )?;
+
+        emit(Event::BatchTaskCreated {
+            batch: *batch_account.key,
+            agent: *agent_account.key,
+            requester: *requester_account.key,
+            task_count,
+            payment_per_task,
+        })?;
+        msg!("Batch task created successfully");
+        Ok(())
+    }
+
+    // Records the Merkle root over a batch's results, which items are then
+    // claimed against. The root can't be replaced once submitted.
+    pub fn process_submit_batch_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        merkle_root: [u8; 32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let batch_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let signer_account = next_account_info(accounts_iter)?;
+
+        if !signer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut batch = BatchTask::load(program_id, batch_account)?;
+        if batch.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_operator(&agent, signer_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if batch.merkle_root.is_some() {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        batch.merkle_root = Some(merkle_root);
+        save_account(batch_account, &batch)?;
+
+        emit(Event::BatchResultSubmitted { batch: *batch_account.key, merkle_root })?;
+        msg!("Batch result submitted");
+        Ok(())
+    }
+
+    // Pays the agent's owner for one batch item, less the protocol fee, once
+    // its result is proven against the batch's root. Each item pays once, and
+    // the last closes the batch.
+    pub fn process_claim_batch_item(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        index: u32,
+        result_hash: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let batch_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+
+        let config = load_completion_config(program_id, config_account, treasury_account)?;
+
+        let mut batch = BatchTask::load(program_id, batch_account)?;
+        if batch.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+        load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        batch.assert_vault(program_id, batch_account.key, vault_account.key)?;
+        if batch.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let merkle_root = batch.merkle_root.ok_or(AIInfraError::InvalidTaskStatus)?;
+        if index >= batch.task_count {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if batch.is_claimed(index) {
+            return Err(AIInfraError::BatchItemClaimed.into());
+        }
+        if !merkle::verify(&merkle_root, &merkle::leaf(index, &result_hash), &proof) {
+            return Err(AIInfraError::InvalidMerkleProof.into());
+        }
+
+        batch.mark_claimed(index);
+        batch.items_claimed = batch.items_claimed.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(batch_account, &batch)?;
+
+        let fee = config.fee_for(batch.payment_per_task)?;
+        let amount = batch.payment_per_task - fee;
+        emit(Event::BatchItemClaimed {
+            batch: *batch_account.key,
+            index,
+            result_hash,
+            amount,
+            fee,
+        })?;
+
+        transfer_from_vault(
+            batch_account.key,
+            batch.vault_bump,
+            vault_account,
+            treasury_account,
+            system_program,
+            fee,
+        )?;
+        transfer_from_vault(
+            batch_account.key,
+            batch.vault_bump,
+            vault_account,
+            owner_account,
+            system_program,
+            amount,
+        )?;
+
+        if batch.is_fully_claimed() {
+            close_batch(&batch, batch_account, vault_account, requester_account, system_program)?;
+            emit(Event::BatchTaskClosed { batch: *batch_account.key, refunded: 0 })?;
+        }
+
+        msg!("Batch item claimed");
+        Ok(())
+    }
+
+    // Returns the payments of a batch's unclaimed items to its requester once
+    // its refund delay has passed, whether or not the agent ever submitted a
+    // root, and closes it
+    pub fn process_refund_batch_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let batch_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let batch = BatchTask::load(program_id, batch_account)?;
+        if batch.requester != *requester_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        batch.assert_vault(program_id, batch_account.key, vault_account.key)?;
+        if !batch.is_refundable(Clock::get()?.unix_timestamp) {
+            return Err(AIInfraError::TaskNotExpired.into());
+        }
+
+        let refunded = batch.unclaimed_payment()?;
+        close_batch(&batch, batch_account, vault_account, requester_account, system_program)?;
+
+        emit(Event::BatchTaskClosed { batch: *batch_account.key, refunded })?;
+        msg!("Batch task refunded");
+        Ok(())
+    }
+
+    // Records the referrer that takes the config's referral share of the
+    // signing requester's completed tasks. It can't be changed afterwards.
+    pub fn process_set_referrer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        referrer: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // A requester can't refer themselves into a rebate
+        if referrer == *owner_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut requester =
+            load_or_create_requester(program_id, requester_account, owner_account, system_program)?;
+        if requester.referrer.is_some() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+        requester.referrer = Some(referrer);
+        save_account(requester_account, &requester)?;
+
+        emit(Event::ReferrerSet { requester: *owner_account.key, referrer })?;
+        msg!("Referrer set");
+        Ok(())
+    }
+
+    // Stores a template numbered by the requester's template counter,
+    // creating their requester account if needed
+    pub fn process_create_template(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        default_payment: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let template_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut requester =
+            load_or_create_requester(program_id, requester_account, owner_account, system_program)?;
+
+        let index = requester.templates_created;
+        let (template_address, bump) =
+            find_task_template_address(owner_account.key, index, program_id);
+        if template_address != *template_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = &Rent::get()?;
+        create_pda_account(
+            owner_account,
+            template_account,
+            system_program,
+            program_id,
+            rent,
+            TaskTemplate::LEN,
+            &[
+                pda::TASK_TEMPLATE_SEED,
+                owner_account.key.as_ref(),
+                &index.to_le_bytes(),
+                &[bump],
+            ],
+        )?;
+        assert_rent_exempt(rent, template_account, TaskTemplate::LEN)?;
+
+        let template = TaskTemplate {
+            account_type: AccountType::TaskTemplate,
+            version: CURRENT_VERSION,
+            requester: *owner_account.key,
+            index,
+            bump,
+            requirements,
+            default_payment,
+        };
+        save_account(template_account, &template)?;
+
+        requester.templates_created = requester.templates_created.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(requester_account, &requester)?;
+
+        emit(Event::TemplateCreated {
+            template: *template_account.key,
+            requester: *owner_account.key,
+            default_payment,
+        })?;
+        msg!("Task template created");
+        Ok(())
+    }
+
+    // Replaces the requirements and default payment of one of the signer's
+    // templates
+    pub fn process_update_template(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        default_payment: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let template_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut template = load_owned_template(program_id, template_account, owner_account)?;
+        template.requirements = requirements;
+        template.default_payment = default_payment;
+        save_account(template_account, &template)?;
+
+        emit(Event::TemplateUpdated { template: *template_account.key, default_payment })?;
+        msg!("Task template updated");
+        Ok(())
+    }
+
+    // Closes one of the signer's templates and returns its rent to them
+    pub fn process_close_template(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let template_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        load_owned_template(program_id, template_account, owner_account)?;
+
+        template_account.data.borrow_mut().fill(0);
+
+        let template_lamports = template_account.lamports();
+        **owner_account.lamports.borrow_mut() = owner_account.lamports()
+            .checked_add(template_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **template_account.lamports.borrow_mut() = 0;
+
+        emit(Event::TemplateClosed { template: *template_account.key })?;
+        msg!("Task template closed");
+        Ok(())
+    }
+
+    // CreateTask with a template's requirements. The template comes first and
+    // CreateTask's accounts follow it, with the template's requester paying.
+    pub fn process_create_task_from_template(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        template_key: Pubkey,
+        payment_amount: Option<u64>,
+    ) -> ProgramResult {
+        let (template_account, task_accounts) =
+            accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *template_account.key != template_key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let template = TaskTemplate::load(program_id, template_account)?;
+        // CreateTask's payer, who must be the template's requester
+        let payer_account = task_accounts.get(2).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if template.requester != *payer_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+
+        Self::process_create_task(
+            program_id,
+            task_accounts,
+            template.requirements,
+            payment_amount.unwrap_or(template.default_payment),
+            0,
+            [0; TASK_TAGS],
+            None,
+            None,
+        )
+    }
+
+    // Creates a recurring task numbered by the requester's recurring task
+    // counter and moves `funding` lamports into it
+    pub fn process_create_recurring_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        agent: Pubkey,
+        requirements: ComputeRequirements,
+        payment_per_run: u64,
+        interval_seconds: i64,
+        funding: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let recurring_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if interval_seconds <= 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut requester =
+            load_or_create_requester(program_id, requester_account, owner_account, system_program)?;
+
+        let index = requester.recurring_tasks_created;
+        let (recurring_address, bump) =
+            find_recurring_task_address(owner_account.key, index, program_id);
+        if recurring_address != *recurring_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = &Rent::get()?;
+        create_pda_account(
+            owner_account,
+            recurring_account,
+            system_program,
+            program_id,
+            rent,
+            RecurringTask::LEN,
+            &[
+                pda::RECURRING_TASK_SEED,
+                owner_account.key.as_ref(),
+                &index.to_le_bytes(),
+                &[bump],
+            ],
+        )?;
+        assert_rent_exempt(rent, recurring_account, RecurringTask::LEN)?;
+
+        if funding > 0 {
+            invoke(
+                &system_instruction::transfer(owner_account.key, recurring_account.key, funding),
+                &[owner_account.clone(), recurring_account.clone(), system_program.clone()],
+            )?;
+        }
+
+        let recurring = RecurringTask {
+            account_type: AccountType::RecurringTask,
+            version: CURRENT_VERSION,
+            requester: *owner_account.key,
+            agent,
+            index,
+            bump,
+            requirements,
+            payment_per_run,
+            interval_seconds,
+            next_run_at: Clock::get()?.unix_timestamp,
+            funding_balance: funding,
+            runs: 0,
+        };
+        save_account(recurring_account, &recurring)?;
+
+        requester.recurring_tasks_created = requester.recurring_tasks_created.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(requester_account, &requester)?;
+
+        emit(Event::RecurringTaskCreated {
+            recurring: *recurring_account.key,
+            requester: *owner_account.key,
+            agent,
+            payment_per_run,
+            interval_seconds,
+        })?;
+        msg!("Recurring task created");
+        Ok(())
+    }
+
+    // Creates a recurring task's due run as a task requested by its
+    // requester. The recurring task comes first and CreateTask's accounts
+    // follow it, with the signing cranker as the payer. The funding escrows
+    // the payment, refunds the rent the cranker fronted and pays them the
+    // config's crank_reward.
+    pub fn process_crank_recurring_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let recurring_account = next_account_info(accounts_iter)?;
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let cranker_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let task_page_account = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        let mut recurring = RecurringTask::load(program_id, recurring_account)?;
+        if recurring.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+        check_payment(&config, &recurring.requirements, recurring.payment_per_run, 0)?;
+        recurring.advance(Clock::get()?.unix_timestamp)?;
+
+        let cranker_before = cranker_account.lamports();
+        init_task(
+            program_id,
+            task_account,
+            agent_account,
+            cranker_account,
+            &recurring.requester,
+            system_program,
+            vault_account,
+            config_account,
+            registry_account,
+            task_page_account,
+            &mut config,
+            recurring.requirements.clone(),
+            recurring.payment_per_run,
+            None,
+            0,
+            [0; TASK_TAGS],
+            accounts_iter.next(),
+        )?;
+        let fronted = cranker_before.checked_sub(cranker_account.lamports())
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        // The escrow, plus the vault's rent reserve, and what the cranker is
+        // owed
+        let escrow = recurring.payment_per_run.checked_add(Rent::get()?.minimum_balance(0))
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        let cranker_owed = fronted.checked_add(config.crank_reward)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        let cost = escrow.checked_add(cranker_owed).ok_or(AIInfraError::NumericalOverflow)?;
+        recurring.funding_balance = recurring.funding_balance.checked_sub(cost)
+            .ok_or(ProgramError::InsufficientFunds)?;
+
+        **recurring_account.lamports.borrow_mut() = recurring_account.lamports()
+            .checked_sub(cost)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **vault_account.lamports.borrow_mut() = vault_account.lamports()
+            .checked_add(escrow)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **cranker_account.lamports.borrow_mut() = cranker_account.lamports()
+            .checked_add(cranker_owed)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(recurring_account, &recurring)?;
+
+        emit(Event::RecurringTaskCranked {
+            recurring: *recurring_account.key,
+            task: *task_account.key,
+            cranker: *cranker_account.key,
+            reward: config.crank_reward,
+        })?;
+        msg!("Recurring task cranked");
+        Ok(())
+    }
+
+    // Closes one of the signer's recurring tasks, returning its remaining
+    // funding and its rent to them
+    pub fn process_cancel_recurring_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let recurring_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let recurring = RecurringTask::load(program_id, recurring_account)?;
+        if recurring.requester != *owner_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+
+        recurring_account.data.borrow_mut().fill(0);
+
+        let recurring_lamports = recurring_account.lamports();
+        **owner_account.lamports.borrow_mut() = owner_account.lamports()
+            .checked_add(recurring_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **recurring_account.lamports.borrow_mut() = 0;
+
+        emit(Event::RecurringTaskCancelled {
+            recurring: *recurring_account.key,
+            refunded: recurring.funding_balance,
+        })?;
+        msg!("Recurring task cancelled");
+        Ok(())
+    }
+
+    // Divides a pending task into open-listed shards that each escrow their
+    // share of its payment, moved over from its vault. Its agent, if it has
+    // one, gets back the slot the task held.
+    pub fn process_split_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        shares: Vec<u16>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        // Shards escrow lamports only
+        if task.payment_mint.is_some() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+
+        let payments = split_payment(task.payment_amount, &shares)?;
+        let agent = load_task_agent(program_id, &task, agent_account)?;
+
+        task.transition(TaskStatus::Split)?;
+        task.shard_count = payments.len() as u8;
+        save_account(task_account, &task)?;
+
+        if let Some(mut agent) = agent {
+            agent.release_task();
+            agent.open_tasks = agent.open_tasks.checked_sub(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            save_account(agent_account, &agent)?;
+        }
+
+        for (shard_index, payment) in payments.into_iter().enumerate() {
+            let shard_account = next_account_info(accounts_iter)?;
+            let shard_vault = next_account_info(accounts_iter)?;
+            let shard_page = next_account_info(accounts_iter)?;
+            init_shard(
+                program_id,
+                task_account.key,
+                &task,
+                shard_index as u8,
+                payment,
+                shard_account,
+                shard_vault,
+                shard_page,
+                requester_account,
+                system_program,
+                config_account,
+                registry_account,
+                &mut config,
+            )?;
+            deposit_to_vault(requester_account, shard_vault, system_program, 0)?;
+            transfer_from_vault(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                shard_vault,
+                system_program,
+                payment,
+            )?;
+        }
+
+        emit(Event::TaskSplit { task: *task_account.key, shard_count: task.shard_count })?;
+        msg!("Task split into shards");
+        Ok(())
+    }
+
+    // Completes a split task whose shards have all completed. The shards
+    // follow the task in shard order, and the task's result is the hash of
+    // their result hashes in that order.
+    pub fn process_aggregate_results(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let (task_account, shard_accounts) =
+            accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.status != TaskStatus::Split {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if shard_accounts.len() != task.shard_count as usize {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let mut result_hashes = Vec::with_capacity(shard_accounts.len());
+        for (shard_index, shard_account) in shard_accounts.iter().enumerate() {
+            if shard_account.owner != program_id {
+                return Err(AIInfraError::AccountNotOwnedByProgram.into());
+            }
+            let shard = load_account::<ComputeTask>(shard_account)?;
+            shard.assert_address(program_id, shard_account.key)?;
+            if shard.parent != Some(*task_account.key) || shard.shard_index as usize != shard_index
+            {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if shard.status != TaskStatus::Completed {
+                return Err(AIInfraError::InvalidTaskStatus.into());
+            }
+            result_hashes.push(shard.result_hash);
+        }
+        let hashes: Vec<&[u8]> = result_hashes.iter().map(|hash| hash.as_ref()).collect();
+        let result_hash = hashv(&hashes).to_bytes();
+
+        task.transition(TaskStatus::Completed)?;
+        task.result_hash = result_hash;
+        task.completed_at = Clock::get()?.unix_timestamp;
+        save_account(task_account, &task)?;
+
+        emit(Event::ResultsAggregated { task: *task_account.key, result_hash })?;
+        msg!("Shard results aggregated");
+        Ok(())
+    }
+
+    // Replaces a shard that failed or was cancelled, whose escrow went back to
+    // the requester, with a fresh open-listed shard the requester funds again
+    pub fn process_reissue_shard(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let failed_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+        let shard_account = next_account_info(accounts_iter)?;
+        let shard_vault = next_account_info(accounts_iter)?;
+        let shard_page = next_account_info(accounts_iter)?;
+
+        let mut config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if task_account.owner != program_id || failed_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        if task.status != TaskStatus::Split {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        let mut failed = load_account::<ComputeTask>(failed_account)?;
+        failed.assert_address(program_id, failed_account.key)?;
+        if failed.parent != Some(*task_account.key) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        // Each shard is replaced at most once, so only one replacement can
+        // complete
+        if !failed.status.is_final() || failed.reissued {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        failed.reissued = true;
+        save_account(failed_account, &failed)?;
+
+        init_shard(
+            program_id,
+            task_account.key,
+            &task,
+            failed.shard_index,
+            failed.payment_amount,
+            shard_account,
+            shard_vault,
+            shard_page,
+            requester_account,
+            system_program,
+            config_account,
+            registry_account,
+            &mut config,
+        )?;
+        deposit_to_vault(requester_account, shard_vault, system_program, failed.payment_amount)?;
+
+        emit(Event::ShardReissued {
+            task: *task_account.key,
+            failed_shard: *failed_account.key,
+            shard: *shard_account.key,
+            shard_index: failed.shard_index,
+        })?;
+        msg!("Shard reissued");
+        Ok(())
+    }
+
+    // Creates a redundant task numbered by the requester's redundant task
+    // counter and escrows a payment for every replica
+    pub fn process_create_redundant_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requirements: ComputeRequirements,
+        payment: u64,
+        replicas: u8,
+        quorum: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        requirements.validate(&config.requirement_bounds)?;
+        check_payment(&config, &requirements, payment, 0)?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // A quorum must be a majority, so two results can't both have one
+        if replicas as usize > MAX_REPLICAS || quorum == 0 || quorum > replicas
+            || quorum as u16 * 2 <= replicas as u16
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let total_payment = payment.checked_mul(replicas as u64)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        let mut requester =
+            load_or_create_requester(program_id, requester_account, owner_account, system_program)?;
+        config.count_task(
+            owner_account.key,
+            &mut requester.last_task_slot,
+            &mut requester.tasks_in_window,
+            Clock::get()?.slot,
+        )?;
+
+        let index = requester.redundant_tasks_created;
+        let (task_address, bump) =
+            find_redundant_task_address(owner_account.key, index, program_id);
+        if task_address != *task_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (vault_address, vault_bump) = find_vault_address(task_account.key, program_id);
+        if vault_address != *vault_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = &Rent::get()?;
+        create_pda_account(
+            owner_account,
+            task_account,
+            system_program,
+            program_id,
+            rent,
+            RedundantTask::LEN,
+            &[
+                pda::REDUNDANT_TASK_SEED,
+                owner_account.key.as_ref(),
+                &index.to_le_bytes(),
+                &[bump],
+            ],
+        )?;
+        assert_rent_exempt(rent, task_account, RedundantTask::LEN)?;
+
+        let task = RedundantTask {
+            account_type: AccountType::RedundantTask,
+            version: CURRENT_VERSION,
+            requester: *owner_account.key,
+            index,
+            bump,
+            vault_bump,
+            requirements,
+            payment_per_replica: payment,
+            replicas,
+            quorum,
+            submission_count: 0,
+            submissions: Default::default(),
+            finalized: false,
+            agreed_hash: None,
+            created_at: Clock::get()?.unix_timestamp,
+        };
+        save_account(task_account, &task)?;
+
+        requester.redundant_tasks_created = requester.redundant_tasks_created.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(requester_account, &requester)?;
+
+        deposit_to_vault(owner_account, vault_account, system_program, total_payment)?;
+
+        emit(Event::RedundantTaskCreated {
+            task: *task_account.key,
+            requester: *owner_account.key,
+            replicas,
+            quorum,
+            payment_per_replica: payment,
+        })?;
+        msg!("Redundant task created");
+        Ok(())
+    }
+
+    // Records the result of an active agent that meets the task's
+    // requirements, taking one of its replicas
+    pub fn process_submit_redundant_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        result_hash: [u8; 32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let signer_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if !signer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_operator(&agent, signer_account.key, accounts.last()) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+
+        let mut task = RedundantTask::load(program_id, task_account)?;
+        if task.is_expired(Clock::get()?.unix_timestamp) {
+            return Err(AIInfraError::TaskExpired.into());
+        }
+        task.requirements.assert_met_by(&agent)?;
+        task.submit(*agent_account.key, result_hash)?;
+        save_account(task_account, &task)?;
+
+        emit(Event::ReplicaResultSubmitted {
+            task: *task_account.key,
+            agent: *agent_account.key,
+            result_hash,
+        })?;
+        msg!("Replica result submitted");
+        Ok(())
+    }
+
+    // Settles a redundant task. Each agent whose result matches the
+    // quorum's is paid its replica's payment, less the protocol fee, and
+    // credited a completion; each whose result differs takes the failure
+    // penalty. What isn't paid out, including replicas never taken, goes
+    // back to the requester. Without any quorum nobody can be told apart, so
+    // the whole escrow is refunded, once every replica has submitted or the
+    // task has expired waiting for them.
+    pub fn process_finalize_redundant_task(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        let config = load_completion_config(program_id, config_account, treasury_account)?;
+
+        let mut task = RedundantTask::load(program_id, task_account)?;
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if task.finalized {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+
+        let agreed_hash = task.quorum_hash();
+        if agreed_hash.is_none()
+            && task.submission_count < task.replicas
+            && !task.is_expired(Clock::get()?.unix_timestamp)
+        {
+            return Err(AIInfraError::QuorumNotReached.into());
+        }
+
+        let mut agents_paid: u8 = 0;
+        for submission in task.submitted() {
+            let agent_account = next_account_info(accounts_iter)?;
+            let owner_account = next_account_info(accounts_iter)?;
+            if submission.agent != *agent_account.key {
+                return Err(AIInfraError::AgentTaskMismatch.into());
+            }
+            if agent_account.owner != program_id {
+                return Err(AIInfraError::AccountNotOwnedByProgram.into());
+            }
+            let mut agent = load_account::<AIAgent>(agent_account)?;
+            agent.assert_address(program_id, agent_account.key)?;
+            // The holder of an NFT-bound agent follows with their token account
+            let holder_token = match agent.owner_nft_mint {
+                Some(_) => Some(next_account_info(accounts_iter)?),
+                None => None,
+            };
+            if !is_agent_owner(&agent, owner_account.key, holder_token) {
+                return Err(AIInfraError::OwnerMismatch.into());
+            }
+
+            match agreed_hash {
+                Some(hash) if hash == submission.result_hash => {
+                    agent.tasks_completed = agent.tasks_completed.checked_add(1)
+                        .ok_or(AIInfraError::NumericalOverflow)?;
+                    agent.reputation_score = reputation::after_completion(
+                        agent.reputation_score,
+                        task.payment_per_replica,
+                        config.reputation_bonus,
+                    );
+                    let fee = config.fee_for(task.payment_per_replica)?;
+                    transfer_from_vault(
+                        task_account.key,
+                        task.vault_bump,
+                        vault_account,
+                        treasury_account,
+                        system_program,
+                        fee,
+                    )?;
+                    transfer_from_vault(
+                        task_account.key,
+                        task.vault_bump,
+                        vault_account,
+                        owner_account,
+                        system_program,
+                        task.payment_per_replica - fee,
+                    )?;
+                    agents_paid += 1;
+                }
+                Some(_) => {
+                    agent.tasks_failed = agent
+                        .tasks_failed
+                        .checked_add(1)
+                        .ok_or(AIInfraError::NumericalOverflow)?;
+                    agent.reputation_score = reputation::after_failure(
+                        agent.reputation_score,
+                        config.reputation_penalty,
+                    );
+                }
+                None => {}
+            }
+            save_account(agent_account, &agent)?;
+        }
+
+        task.finalized = true;
+        task.agreed_hash = agreed_hash;
+        save_account(task_account, &task)?;
+
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            requester_account,
+            system_program,
+            vault_account.lamports(),
+        )?;
+
+        emit(Event::RedundantTaskFinalized {
+            task: *task_account.key,
+            agreed_hash,
+            agents_paid,
+        })?;
+        msg!("Redundant task finalized");
+        Ok(())
+    }
+
+    // Sets the agent's metadata, creating its account on first use
+    pub fn process_set_agent_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: String,
+        uri: String,
+        endpoint: String,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let metadata_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        AgentMetadata::validate(&name, &uri, &endpoint)?;
+
+        let bump = if metadata_account.data_is_empty() {
+            let (metadata_address, bump) = find_metadata_address(agent_account.key, program_id);
+            if metadata_address != *metadata_account.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            let rent = &Rent::get()?;
+            create_pda_account(
+                owner_account,
+                metadata_account,
+                system_program,
+                program_id,
+                rent,
+                AgentMetadata::LEN,
+                &[pda::METADATA_SEED, agent_account.key.as_ref(), &[bump]],
+            )?;
+            assert_rent_exempt(rent, metadata_account, AgentMetadata::LEN)?;
+            bump
+        } else {
+            AgentMetadata::load(program_id, agent_account.key, metadata_account)?.bump
+        };
+
+        let metadata = AgentMetadata {
+            account_type: AccountType::AgentMetadata,
+            version: CURRENT_VERSION,
+            agent: *agent_account.key,
+            bump,
+            name,
+            uri,
+            endpoint,
+        };
+        save_account(metadata_account, &metadata)?;
+
+        emit(Event::AgentMetadataSet { agent: *agent_account.key })?;
+        msg!("Agent metadata set");
+        Ok(())
+    }
+
+    // Moves a tip from the signer to the owner of the agent that completed a
+    // task. Each tip adds a little reputation, but only up to a fixed cap per
+    // task, so tipping can't buy an agent a score.
+    pub fn process_tip_agent(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let tipper_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if !tipper_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if amount == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if task_account.owner != program_id || agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        // A split task's shards were run by their own agents
+        if task.status != TaskStatus::Completed || task.shard_count > 0 {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if task.agent != *agent_account.key {
+            return Err(AIInfraError::AgentTaskMismatch.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (reputation_score, reputation_gain) =
+            reputation::after_tip(agent.reputation_score, task.tip_reputation);
+        agent.reputation_score = reputation_score;
+        agent.tips_received = agent.tips_received.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        task.tip_reputation = task.tip_reputation.saturating_add(reputation_gain);
+        save_account(agent_account, &agent)?;
+        save_account(task_account, &task)?;
+
+        invoke(
+            &system_instruction::transfer(tipper_account.key, owner_account.key, amount),
+            &[tipper_account.clone(), owner_account.clone(), system_program.clone()],
+        )?;
+
+        emit(Event::TipPaid {
+            task: *task_account.key,
+            agent: *agent_account.key,
+            tipper: *tipper_account.key,
+            amount,
+            reputation_gain,
+        })?;
+        msg!("Agent tipped");
+        Ok(())
+    }
+
+    // Credits an agent with the held payments whose release time has passed.
+    // The lamports already sit in its credit vault, so only the books move.
+    pub fn process_release_payments(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        let amount = agent.release_payments(Clock::get()?.unix_timestamp)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::PaymentsReleased { agent: *agent_account.key, amount })?;
+        msg!("Payments released");
+        Ok(())
+    }
+
+    // Rewrites an account from an older layout version in the current one,
+    // growing it as needed. Anyone may pay the extra rent; accounts already
+    // current are left as they are.
+    pub fn process_migrate_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let account_type = match account.data.borrow().first() {
+            Some(&tag) => AccountType::try_from_slice(&[tag])
+                .map_err(|_| AIInfraError::InvalidAccountType)?,
+            None => AccountType::Uninitialized,
+        };
+        let from_version = match account_type {
+            AccountType::Uninitialized => {
+                return Err(AIInfraError::AccountNotInitialized.into());
+            }
+            AccountType::Agent => migrate_account::<AIAgent>(account, payer, system_program)?,
+            AccountType::Task => migrate_account::<ComputeTask>(account, payer, system_program)?,
+            AccountType::Config => migrate_account::<Config>(account, payer, system_program)?,
+            AccountType::Bid => migrate_account::<Bid>(account, payer, system_program)?,
+            AccountType::Requester => {
+                migrate_account::<Requester>(account, payer, system_program)?
+            }
+            AccountType::Verifier => migrate_account::<Verifier>(account, payer, system_program)?,
+            AccountType::Allowlist => {
+                migrate_account::<AgentAllowlist>(account, payer, system_program)?
+            }
+            AccountType::Allowance => {
+                migrate_account::<Allowance>(account, payer, system_program)?
+            }
+            AccountType::Registry => migrate_account::<Registry>(account, payer, system_program)?,
+            AccountType::TaskPage => migrate_account::<TaskPage>(account, payer, system_program)?,
+            AccountType::BatchTask => {
+                migrate_account::<BatchTask>(account, payer, system_program)?
+            }
+            AccountType::TaskTemplate => {
+                migrate_account::<TaskTemplate>(account, payer, system_program)?
+            }
+            AccountType::RecurringTask => {
+                migrate_account::<RecurringTask>(account, payer, system_program)?
+            }
+            AccountType::RedundantTask => {
+                migrate_account::<RedundantTask>(account, payer, system_program)?
+            }
+            AccountType::AgentMetadata => {
+                migrate_account::<AgentMetadata>(account, payer, system_program)?
+            }
+            AccountType::InsurancePool => {
+                migrate_account::<InsurancePool>(account, payer, system_program)?
+            }
+            AccountType::MultisigOwner => {
+                migrate_account::<MultisigOwner>(account, payer, system_program)?
+            }
+        };
+
+        if let Some(from_version) = from_version {
+            emit(Event::AccountMigrated { account: *account.key, from_version })?;
+            msg!("Account migrated");
+        }
+        Ok(())
+    }
+
+    // Adds `requester` to the agent's allowlist, creating the allowlist on first
+    // use at the owner's expense
+    pub fn process_add_allowed_requester(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requester: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let allowlist_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        let mut allowlist = if allowlist_account.data_is_empty() {
+            let (allowlist_address, bump) = find_allowlist_address(agent_account.key, program_id);
+            if allowlist_address != *allowlist_account.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            let rent = &Rent::get()?;
+            create_pda_account(
+                owner_account,
+                allowlist_account,
+                system_program,
+                program_id,
+                rent,
+                AgentAllowlist::LEN,
+                &[pda::ALLOWLIST_SEED, agent_account.key.as_ref(), &[bump]],
+            )?;
+            assert_rent_exempt(rent, allowlist_account, AgentAllowlist::LEN)?;
+
+            AgentAllowlist {
+                account_type: AccountType::Allowlist,
+                version: CURRENT_VERSION,
+                agent: *agent_account.key,
+                bump,
+                requesters: vec![],
+            }
+        } else {
+            AgentAllowlist::load(program_id, agent_account.key, allowlist_account)?
+        };
+
+        if allowlist.requesters.contains(&requester) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if allowlist.requesters.len() >= MAX_ALLOWED_REQUESTERS {
+            return Err(AIInfraError::AllowlistFull.into());
+        }
+
+        allowlist.requesters.push(requester);
+        agent.allowed_requesters = allowlist.requesters.len() as u16;
+        save_account(allowlist_account, &allowlist)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AllowedRequesterAdded { agent: *agent_account.key, requester })?;
+        msg!("Allowed requester added");
+        Ok(())
+    }
+
+    // Removes `requester` from the agent's allowlist. Emptying the list opens
+    // the agent to every requester again.
+    pub fn process_remove_allowed_requester(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        requester: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let allowlist_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        let mut allowlist = AgentAllowlist::load(program_id, agent_account.key, allowlist_account)?;
+
+        let position = allowlist.requesters.iter().position(|key| *key == requester)
+            .ok_or(ProgramError::InvalidArgument)?;
+        allowlist.requesters.remove(position);
+        agent.allowed_requesters = allowlist.requesters.len() as u16;
+        save_account(allowlist_account, &allowlist)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AllowedRequesterRemoved { agent: *agent_account.key, requester })?;
+        msg!("Allowed requester removed");
+        Ok(())
+    }
+
+    pub fn process_deposit_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_nonce: Option<u64>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let registry_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+
+        let (credit_vault_address, _) = find_credit_vault_address(agent_account.key, program_id);
+        if credit_vault_address != *credit_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let mut registry = Registry::load(program_id, registry_account)?;
+        registry.total_credits_deposited = registry.total_credits_deposited.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        let nonce = agent.take_operation_nonce(expected_nonce)?;
+        agent.compute_credits = agent.compute_credits.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        config.assert_within_credit_cap(agent.compute_credits)?;
+
+        // Credits are backed 1:1 by lamports
+        deposit_to_vault(owner_account, credit_vault, system_program, amount)?;
+
+        save_account(agent_account, &agent)?;
+        save_account(registry_account, &registry)?;
+
+        emit(Event::CreditsDeposited { agent: *agent_account.key, amount, nonce })?;
+        msg!("Credits deposited successfully");
+        Ok(())
+    }
+
+    pub fn process_withdraw_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_nonce: Option<u64>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        let (credit_vault_address, credit_vault_bump) =
+            find_credit_vault_address(agent_account.key, program_id);
+        if credit_vault_address != *credit_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if agent.compute_credits < amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        config.assert_withdrawable(amount, agent.compute_credits)?;
+        agent.record_withdrawal(amount, Clock::get()?.unix_timestamp)?;
+
+        let nonce = agent.take_operation_nonce(expected_nonce)?;
+        agent.compute_credits = agent.compute_credits.checked_sub(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(agent_account, &agent)?;
+
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            agent_account.key,
+            credit_vault_bump,
+            credit_vault,
+            owner_account,
+            system_program,
+            amount,
+        )?;
+
+        emit(Event::CreditsWithdrawn { agent: *agent_account.key, amount, nonce })?;
+        msg!("Credits withdrawn successfully");
+        Ok(())
+    }
+
+    // Pays a new agent the config's grant, moving the lamports backing it
+    // from the faucet vault into the agent's credit vault
+    pub fn process_claim_grant(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let faucet = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+        let amount = config.grant_amount;
+        if amount == 0 {
+            return Err(AIInfraError::GrantsDisabled.into());
+        }
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+        let (credit_vault_address, _) = find_credit_vault_address(agent_account.key, program_id);
+        let (faucet_address, faucet_bump) = find_faucet_address(program_id);
+        if credit_vault_address != *credit_vault.key || faucet_address != *faucet.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        agent.claim_grant(Clock::get()?.epoch)?;
+        let reserve = Rent::get()?.minimum_balance(0);
+        if faucet.lamports() < amount.saturating_add(reserve) {
+            return Err(AIInfraError::FaucetEmpty.into());
+        }
+        agent.compute_credits = agent.compute_credits.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        config.assert_within_credit_cap(agent.compute_credits)?;
+        save_account(agent_account, &agent)?;
+
+        deposit_to_vault(owner_account, credit_vault, system_program, 0)?;
+        invoke_signed(
+            &system_instruction::transfer(faucet.key, credit_vault.key, amount),
+            &[faucet.clone(), credit_vault.clone(), system_program.clone()],
+            &[&[pda::FAUCET_SEED, &[faucet_bump]]],
+        )?;
+
+        emit(Event::GrantClaimed { agent: *agent_account.key, amount })?;
+        msg!("Grant claimed");
+        Ok(())
+    }
+
+    // Moves credits, and the lamports backing them, between two agents' credit
+    // vaults. The destination may belong to a different owner; the source
+    // owner covers the destination vault's rent reserve if it has none yet.
+    pub fn process_transfer_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_nonce: Option<u64>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let source_account = next_account_info(accounts_iter)?;
+        let destination_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let source_vault = next_account_info(accounts_iter)?;
+        let destination_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if source_account.key == destination_account.key {
+            return Err(AIInfraError::DuplicateAccount.into());
+        }
+
+        let mut source =
+            load_agent_as_owner(program_id, source_account, owner_account, accounts_iter)?;
+
+        if destination_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut destination = load_account::<AIAgent>(destination_account)?;
+        destination.assert_address(program_id, destination_account.key)?;
+
+        let (source_vault_address, source_vault_bump) =
+            find_credit_vault_address(source_account.key, program_id);
+        let (destination_vault_address, _) =
+            find_credit_vault_address(destination_account.key, program_id);
+        if source_vault_address != *source_vault.key
+            || destination_vault_address != *destination_vault.key
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if source.compute_credits < amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        // Another agent's credits are as good as withdrawn, so transfers count
+        // against the source's withdrawal policy too
+        source.record_withdrawal(amount, Clock::get()?.unix_timestamp)?;
+
+        let source_nonce = source.take_operation_nonce(expected_nonce)?;
+        let destination_nonce = destination.take_operation_nonce(None)?;
+        source.compute_credits = source.compute_credits.checked_sub(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        destination.compute_credits = destination.compute_credits.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        config.assert_within_credit_cap(destination.compute_credits)?;
+        save_account(source_account, &source)?;
+        save_account(destination_account, &destination)?;
+
+        deposit_to_vault(owner_account, destination_vault, system_program, 0)?;
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            source_account.key,
+            source_vault_bump,
+            source_vault,
+            destination_vault,
+            system_program,
+            amount,
+        )?;
+
+        emit(Event::CreditsTransferred {
+            source: *source_account.key,
+            destination: *destination_account.key,
+            amount,
+            source_nonce,
+            destination_nonce,
+        })?;
+        msg!("Credits transferred successfully");
+        Ok(())
+    }
+
+    // Sets the credits `spender` may draw from the agent, creating the
+    // allowance on first use at the owner's expense
+    pub fn process_approve_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        spender: Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let allowance_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        let mut allowance = if allowance_account.data_is_empty() {
+            let (allowance_address, bump) =
+                find_allowance_address(agent_account.key, &spender, program_id);
+            if allowance_address != *allowance_account.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            let rent = &Rent::get()?;
+            create_pda_account(
+                owner_account,
+                allowance_account,
+                system_program,
+                program_id,
+                rent,
+                Allowance::LEN,
+                &[pda::ALLOWANCE_SEED, agent_account.key.as_ref(), spender.as_ref(), &[bump]],
+            )?;
+            assert_rent_exempt(rent, allowance_account, Allowance::LEN)?;
+
+            Allowance {
+                account_type: AccountType::Allowance,
+                version: CURRENT_VERSION,
+                agent: *agent_account.key,
+                spender,
+                amount: 0,
+                bump,
+            }
+        } else {
+            Allowance::load(program_id, agent_account.key, &spender, allowance_account)?
+        };
+
+        allowance.amount = amount;
+        save_account(allowance_account, &allowance)?;
+
+        emit(Event::CreditsApproved { agent: *agent_account.key, spender, amount })?;
+        msg!("Credits approved");
+        Ok(())
+    }
+
+    // Closes a spender's allowance, returning its rent to the owner
+    pub fn process_revoke_allowance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let allowance_account = next_account_info(accounts_iter)?;
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        if allowance_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let allowance = load_account::<Allowance>(allowance_account)?;
+        if allowance.agent != *agent_account.key {
+            return Err(AIInfraError::AllowanceMismatch.into());
+        }
+        let expected = pda::create_allowance_address(
+            agent_account.key,
+            &allowance.spender,
+            allowance.bump,
+            program_id,
+        )?;
+        if expected != *allowance_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        allowance_account.data.borrow_mut().fill(0);
+
+        let allowance_lamports = allowance_account.lamports();
+        **owner_account.lamports.borrow_mut() = owner_account.lamports()
+            .checked_add(allowance_lamports)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        **allowance_account.lamports.borrow_mut() = 0;
+
+        emit(Event::AllowanceRevoked { agent: *agent_account.key, spender: allowance.spender })?;
+        msg!("Allowance revoked");
+        Ok(())
+    }
+
+    // Locks lamports from the owner into the agent's stake vault as collateral
+    pub fn process_stake_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !is_agent_owner(&agent, owner_account.key, accounts.last()) {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+
+        let (stake_vault_address, _) = find_stake_vault_address(agent_account.key, program_id);
+        if stake_vault_address != *stake_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        agent.staked_lamports = agent.staked_lamports.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+
+        deposit_to_vault(owner_account, stake_vault, system_program, amount)?;
+
+        save_account(agent_account, &agent)?;
+
+        emit(Event::CollateralStaked { agent: *agent_account.key, amount })?;
+        msg!("Collateral staked successfully");
+        Ok(())
+    }
+
+    // Withdraws stake in two steps so an agent can't pull its collateral out
+    // from under a task that is about to fail. The first call records the
+    // request; calling again with the same amount after the cooldown pays it out.
+    // Stake slashed in the meantime shrinks the payout.
+    pub fn process_unstake_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let stake_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if agent.owner != *owner_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        agent.assert_not_nft_bound()?;
+
+        let (stake_vault_address, stake_vault_bump) =
+            find_stake_vault_address(agent_account.key, program_id);
+        if stake_vault_address != *stake_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        if agent.unstake_amount == 0 {
+            if amount == 0 {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if agent.staked_lamports < amount {
+                return Err(ProgramError::InsufficientFunds);
+            }
+            agent.unstake_amount = amount;
+            agent.unstake_requested_at = now;
+            save_account(agent_account, &agent)?;
+
+            emit(Event::UnstakeRequested { agent: *agent_account.key, amount })?;
+            msg!("Unstake requested");
+            return Ok(());
+        }
+
+        if amount != agent.unstake_amount {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let unlocks_at = agent.unstake_requested_at.checked_add(config.unstake_cooldown_seconds)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        if now < unlocks_at {
+            return Err(AIInfraError::UnstakeCooldownActive.into());
+        }
+
+        let payout = amount.min(agent.staked_lamports);
+        agent.staked_lamports -= payout;
+        agent.unstake_amount = 0;
+        agent.unstake_requested_at = 0;
+        save_account(agent_account, &agent)?;
+
+        transfer_from_account_vault(
+            pda::STAKE_VAULT_SEED,
+            agent_account.key,
+            stake_vault_bump,
+            stake_vault,
+            owner_account,
+            system_program,
+            payout,
+        )?;
+
+        emit(Event::CollateralUnstaked { agent: *agent_account.key, amount: payout })?;
+        msg!("Collateral unstaked successfully");
+        Ok(())
+    }
+
+    // Adds lamports to the signing wallet's requester credits, creating its
+    // requester account on first use
+    pub fn process_deposit_requester_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut requester =
+            load_or_create_requester(program_id, requester_account, owner_account, system_program)?;
+
+        let (credit_vault_address, _) =
+            find_credit_vault_address(requester_account.key, program_id);
+        if credit_vault_address != *credit_vault.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        requester.credits = requester.credits.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        deposit_to_vault(owner_account, credit_vault, system_program, amount)?;
+        save_account(requester_account, &requester)?;
+
+        emit(Event::RequesterCreditsDeposited { requester: *requester_account.key, amount })?;
+        msg!("Requester credits deposited successfully");
+        Ok(())
+    }
+
+    pub fn process_withdraw_requester_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let requester_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let credit_vault = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+
+        Config::load(program_id, config_account)?.assert_not_paused()?;
+
+        let (mut requester, credit_vault_bump) =
+            load_requester(program_id, requester_account, owner_account, credit_vault)?;
+        if requester.credits < amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        requester.credits -= amount;
+        save_account(requester_account, &requester)?;
+
+        transfer_from_account_vault(
+            pda::CREDIT_VAULT_SEED,
+            requester_account.key,
+            credit_vault_bump,
+            credit_vault,
+            owner_account,
+            system_program,
+            amount,
+        )?;
+
+        emit(Event::RequesterCreditsWithdrawn { requester: *requester_account.key, amount })?;
+        msg!("Requester credits withdrawn successfully");
+        Ok(())
+    }
+
+    pub fn process_update_status(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        is_active: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        update_field(program_id, agent_account, owner_account, accounts.last(), true, |agent| {
+            agent.is_active = is_active as u8;
+        })?;
+
+        emit(Event::AgentStatusUpdated { agent: *agent_account.key, is_active })?;
+        msg!("Agent status updated successfully");
+        Ok(())
+    }
+
+    // Records that the agent's owner is still around
+    pub fn process_heartbeat(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        update_field(program_id, agent_account, owner_account, accounts.last(), false, |agent| {
+            agent.last_heartbeat.set(now);
+        })?;
+
+        emit(Event::HeartbeatRecorded { agent: *agent_account.key })?;
+        msg!("Agent heartbeat recorded");
+        Ok(())
+    }
+
+    // Deactivates an agent whose owner has stopped sending heartbeats. Anyone may
+    // call it, and the caller collects the config's stale bounty for doing so
+    // while the config holds enough above its rent reserve to pay it.
+    pub fn process_deactivate_stale_agent(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let caller_account = next_account_info(accounts_iter)?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let config = Config::load(program_id, config_account)?;
+
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if !agent.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+        if !config.is_stale(&agent, Clock::get()?.unix_timestamp) {
+            return Err(AIInfraError::AgentNotStale.into());
+        }
+
+        agent.is_active = false;
+        save_account(agent_account, &agent)?;
+
+        let reserve = Rent::get()?.minimum_balance(Config::LEN);
+        let available = config_account.lamports().saturating_sub(reserve);
+        if config.stale_bounty > 0 && config.stale_bounty <= available {
+            **caller_account.lamports.borrow_mut() = caller_account.lamports()
+                .checked_add(config.stale_bounty)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            **config_account.lamports.borrow_mut() =
+                config_account.lamports() - config.stale_bounty;
+        }
+
+        emit(Event::StaleAgentDeactivated {
+            agent: *agent_account.key,
+            caller: *caller_account.key,
+        })?;
+        msg!("Stale agent deactivated");
+        Ok(())
+    }
+
+    // Creates the global config account, paid for by the signer. It can only be
+    // created once; afterwards only `admin` can change it.
+    pub fn process_initialize_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        admin: Pubkey,
+        params: ConfigParams,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !payer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (config_address, bump) = find_config_address(program_id);
+        if config_address != *config_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if config_account.owner == program_id && !config_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let mut config = Config {
+            account_type: AccountType::Config,
+            version: CURRENT_VERSION,
+            admin,
+            bump,
+            ..Config::default()
+        };
+        config.apply(params)?;
+        let rent = &Rent::get()?;
+
+        create_pda_account(
+            payer_account,
+            config_account,
+            system_program,
+            program_id,
+            rent,
+            Config::LEN,
+            &[pda::CONFIG_SEED, &[bump]],
+        )?;
+        assert_rent_exempt(rent, config_account, Config::LEN)?;
+
+        save_account(config_account, &config)?;
+
+        emit(Event::ConfigInitialized { admin })?;
+        msg!("Config initialized successfully");
+        Ok(())
+    }
+
+    pub fn process_initialize_registry(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let registry_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !payer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (registry_address, bump) = find_registry_address(program_id);
+        if registry_address != *registry_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if registry_account.owner == program_id && !registry_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let registry = Registry {
+            account_type: AccountType::Registry,
+            version: CURRENT_VERSION,
+            bump,
+            ..Registry::default()
+        };
+        let rent = &Rent::get()?;
+
+        create_pda_account(
+            payer_account,
+            registry_account,
+            system_program,
+            program_id,
+            rent,
+            Registry::LEN,
+            &[pda::REGISTRY_SEED, &[bump]],
+        )?;
+        assert_rent_exempt(rent, registry_account, Registry::LEN)?;
+
+        save_account(registry_account, &registry)?;
+
+        emit(Event::RegistryInitialized { registry: *registry_account.key })?;
+        msg!("Registry initialized successfully");
+        Ok(())
+    }
+
+    // Records the result the requester expects of a pending task. It can
+    // only be set once, so an agent that starts the task knows what it must
+    // produce.
+    pub fn process_set_expected_result(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        expected_result_hash: [u8; 32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if task.expected_result_hash.is_some() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        task.expected_result_hash = Some(expected_result_hash);
+        save_account(task_account, &task)?;
+
+        emit(Event::ExpectedResultSet { task: *task_account.key, expected_result_hash })?;
+        msg!("Expected result set");
+        Ok(())
+    }
+
+    // Changes a pending task's priority. Raising it escrows the surcharge
+    // the new priority adds to the price of the task's requirements, which
+    // the agent earns with the rest of the payment. Lowering it refunds
+    // nothing.
+    pub fn process_update_task_priority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        task_id: Pubkey,
+        priority: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if *task_account.key != task_id {
+            return Err(AIInfraError::TaskIdMismatch.into());
+        }
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        task.assert_address(program_id, task_account.key)?;
+        if task.requester != *requester_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        if task.status != TaskStatus::Pending {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+
+        let surcharge = priority_surcharge(&task.requirements, &config, task.priority, priority);
+        if surcharge > 0 {
+            // The surcharge is priced in lamports
+            if task.payment_mint.is_some() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            task.payment_amount = task.payment_amount.checked_add(surcharge)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            invoke(
+                &system_instruction::transfer(requester_account.key, vault_account.key, surcharge),
+                &[requester_account.clone(), vault_account.clone(), system_program.clone()],
+            )?;
+        }
+        task.priority = priority;
+        save_account(task_account, &task)?;
+
+        emit(Event::TaskPriorityUpdated { task: *task_account.key, priority, surcharge })?;
+        msg!("Task priority updated");
+        Ok(())
+    }
+
+    // Replaces the config's settings. Only the current admin may do so, and
+    // passing a different `admin` hands control to that key.
+    pub fn process_update_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        admin: Pubkey,
+        params: ConfigParams,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        config.apply(params)?;
+        config.admin = admin;
+        save_account(config_account, &config)?;
+
+        emit(Event::ConfigUpdated { admin })?;
+        msg!("Config updated successfully");
+        Ok(())
+    }
+
+    // Pauses or resumes the program. Only the config's admin may do so.
+    pub fn process_set_paused(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        paused: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        config.paused = paused;
+        save_account(config_account, &config)?;
+
+        emit(Event::PausedSet { paused })?;
+
+        if paused {
+            msg!("Program paused");
+        } else {
+            msg!("Program unpaused");
+        }
+        Ok(())
+    }
+
+    // Tops up the faucet vault grants are paid from. Only the config's admin
+    // may do so.
+    pub fn process_fund_faucet(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+        let faucet = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if find_faucet_address(program_id).0 != *faucet.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        deposit_to_vault(admin_account, faucet, system_program, amount)?;
+
+        emit(Event::FaucetFunded { amount })?;
+        msg!("Faucet funded");
+        Ok(())
+    }
+
+    // Sets the credits each grant gives. Only the config's admin may do so.
+    pub fn process_set_grant_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        config.grant_amount = amount;
+        save_account(config_account, &config)?;
+
+        emit(Event::GrantAmountSet { amount })?;
+        msg!("Grant amount set");
+        Ok(())
+    }
+
+    // Creates the insurance pool. Only the config's admin may do so, paying
+    // its rent.
+    pub fn process_initialize_insurance_pool(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+        let pool_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (pool_address, bump) = find_insurance_pool_address(program_id);
+        if pool_address != *pool_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if pool_account.owner == program_id && !pool_account.data_is_empty() {
+            return Err(AIInfraError::AlreadyInitialized.into());
+        }
+
+        let pool = InsurancePool {
+            account_type: AccountType::InsurancePool,
+            version: CURRENT_VERSION,
+            bump,
+            ..InsurancePool::default()
+        };
+        let rent = &Rent::get()?;
+
+        create_pda_account(
+            admin_account,
+            pool_account,
+            system_program,
+            program_id,
+            rent,
+            InsurancePool::LEN,
+            &[pda::INSURANCE_POOL_SEED, &[bump]],
+        )?;
+        assert_rent_exempt(rent, pool_account, InsurancePool::LEN)?;
+
+        save_account(pool_account, &pool)?;
+
+        emit(Event::InsurancePoolInitialized { insurance_pool: *pool_account.key })?;
+        msg!("Insurance pool initialized");
+        Ok(())
+    }
+
+    // Pays the requester of a failed lamport task out of the insurance pool,
+    // settling the refund its vault held back. The claim is marked on the
+    // task before any lamports move.
+    pub fn process_file_insurance_claim(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let task_account = next_account_info(accounts_iter)?;
+        let requester_account = next_account_info(accounts_iter)?;
+        let pool_account = next_account_info(accounts_iter)?;
+        let config_account = next_account_info(accounts_iter)?;
+        let vault_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if !requester_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        config.assert_not_paused()?;
+
+        if task_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        if task.requester != *requester_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if task.status != TaskStatus::Failed {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        task.assert_vault(program_id, task_account.key, vault_account.key)?;
+        // Token tasks pay nothing into the pool, and a requester can't
+        // insure against their own cancellation
+        if task.payment_mint.is_some()
+            || task.failure_reason == Some(FailureReason::RequesterCancelledAfterStart)
+        {
+            return Err(AIInfraError::TaskNotInsured.into());
+        }
+
+        let mut pool = InsurancePool::load(program_id, pool_account)?;
+        let available = pool_account.lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(InsurancePool::LEN));
+        let payout = config.insurance_payout(&task, Clock::get()?.unix_timestamp, available)?;
+
+        // The claim settles the held refund in its place: the escrow pays the
+        // pool back as much of the payout as it covers, and the rest of the
+        // vault goes to the requester
+        let reimbursed = payout.min(task.held_refund);
+        task.insurance_claimed = true;
+        task.held_refund = 0;
+        save_account(task_account, &task)?;
+        pool.total_collected = pool.total_collected.checked_add(reimbursed)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        pool.total_paid = pool.total_paid.checked_add(payout)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(pool_account, &pool)?;
+
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            pool_account,
+            system_program,
+            reimbursed,
+        )?;
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            requester_account,
+            system_program,
+            vault_account.lamports(),
+        )?;
+
+        // The pool is program-owned, so its lamports move without a CPI. They
+        // move after the vault's, as a CPI can't follow a direct change.
+        **pool_account.try_borrow_mut_lamports()? -= payout;
+        **requester_account.try_borrow_mut_lamports()? += payout;
+
+        emit(Event::InsuranceClaimPaid {
+            task: *task_account.key,
+            requester: *requester_account.key,
+            amount: payout,
+        })?;
+        msg!("Insurance claim paid");
+        Ok(())
+    }
+
+    // Moves lamports the insurance pool holds past its rent reserve to the
+    // treasury. Only the config's admin may do so.
+    pub fn process_sweep_insurance_pool(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin_account = next_account_info(accounts_iter)?;
+        let pool_account = next_account_info(accounts_iter)?;
+        let treasury_account = next_account_info(accounts_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config = Config::load(program_id, config_account)?;
+        if config.admin != *admin_account.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if config.treasury != *treasury_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut pool = InsurancePool::load(program_id, pool_account)?;
+        let available = pool_account.lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(InsurancePool::LEN));
+        if amount > available {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        pool.total_swept = pool.total_swept.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        save_account(pool_account, &pool)?;
+
+        **pool_account.try_borrow_mut_lamports()? -= amount;
+        **treasury_account.try_borrow_mut_lamports()? += amount;
+
+        emit(Event::InsurancePoolSwept { amount })?;
+        msg!("Insurance pool swept");
+        Ok(())
+    }
+
+    // Puts the agent's owner-gated instructions a multisig can sign under
+    // `threshold` of `signers`, or with no signers, back under the owner's
+    // key. While the multisig already signs for the owner, its members must
+    // approve the change.
+    pub fn process_set_multisig_owner(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let multisig_account = next_account_info(accounts_iter)?;
+        let system_program = next_system_program(accounts_iter)?;
+
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let mut agent = load_account::<AIAgent>(agent_account)?;
+        agent.assert_address(program_id, agent_account.key)?;
+        if agent.owner != *owner_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        agent.assert_not_nft_bound()?;
+        if !signers.is_empty() {
+            MultisigOwner::validate(&signers, threshold)?;
+        }
+
+        let mut multisig = if multisig_account.data_is_empty() {
+            if !owner_account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let (multisig_address, bump) = find_multisig_address(agent_account.key, program_id);
+            if multisig_address != *multisig_account.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            let rent = &Rent::get()?;
+            create_pda_account(
+                owner_account,
+                multisig_account,
+                system_program,
+                program_id,
+                rent,
+                MultisigOwner::LEN,
+                &[pda::MULTISIG_SEED, agent_account.key.as_ref(), &[bump]],
+            )?;
+            assert_rent_exempt(rent, multisig_account, MultisigOwner::LEN)?;
+
+            MultisigOwner {
+                account_type: AccountType::MultisigOwner,
+                version: CURRENT_VERSION,
+                agent: *agent_account.key,
+                bump,
+                ..MultisigOwner::default()
+            }
+        } else {
+            let multisig = MultisigOwner::load(program_id, agent_account.key, multisig_account)?;
+            if agent.multisig_owner {
+                assert_multisig_approved(&multisig, accounts_iter)?;
+            } else if !owner_account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            multisig
+        };
+
+        agent.multisig_owner = !signers.is_empty();
+        multisig.threshold = threshold;
+        multisig.signers = signers;
+        save_account(multisig_account, &multisig)?;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::MultisigOwnerSet {
+            agent: *agent_account.key,
+            signers: multisig.signers,
+            threshold,
+        })?;
+        msg!("Multisig owner set");
+        Ok(())
+    }
+
+    // Hands control of the agent to the holder of the NFT `mint_account`
+    // issues, in place of the owner's key or multisig
+    pub fn process_bind_agent_to_nft(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let owner_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, owner_account, accounts_iter)?;
+
+        let mint = unpack_mint(mint_account)?;
+        if mint.supply != 1 || mint.decimals != 0 {
+            return Err(AIInfraError::InvalidNftMint.into());
+        }
+
+        agent.owner_nft_mint = Some(*mint_account.key);
+        agent.multisig_owner = false;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AgentBoundToNft { agent: *agent_account.key, mint: *mint_account.key })?;
+        msg!("Agent bound to NFT");
+        Ok(())
+    }
+
+    // Returns an agent bound to an NFT to ownership by key, the holder's
+    pub fn process_unbind_agent_from_nft(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let agent_account = next_account_info(accounts_iter)?;
+        let holder_account = next_account_info(accounts_iter)?;
+
+        let mut agent =
+            load_agent_as_owner(program_id, agent_account, holder_account, accounts_iter)?;
+        if agent.owner_nft_mint.is_none() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        agent.owner_nft_mint = None;
+        save_account(agent_account, &agent)?;
+
+        emit(Event::AgentUnboundFromNft {
+            agent: *agent_account.key,
+            owner: *holder_account.key,
+        })?;
+        msg!("Agent unbound from NFT");
+        Ok(())
+    }
+}
+
+// Creates a program-owned PDA. An address that already holds lamports (for
+// example topped up by the client in the same transaction) can't go through
+// create_account, so it is allocated and assigned in place instead.
+fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    rent: &Rent,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if new_account.lamports() == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                new_account.key,
+                rent.minimum_balance(space),
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    } else {
+        invoke_signed(
+            &system_instruction::allocate(new_account.key, space as u64),
+            &[new_account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account.key, program_id),
+            &[new_account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
+}
+
+// Rewrites `account`, holding a `T`, in the current layout and version.
+// `payer` tops up the rent when the account grows. Returns the version it
+// was migrated from, or None when it was already current.
+fn migrate_account<'a, T: ProgramAccount>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> Result<Option<u8>, ProgramError> {
+    let (from_version, migrated) = {
+        let data = account.data.borrow();
+        match migrate_data::<T>(&data)? {
+            Some(migrated) => (account_version::<T>(&data), migrated),
+            None => return Ok(None),
+        }
+    };
+
+    grow_account(account, payer, system_program, T::LEN)?;
+    account.data.borrow_mut().copy_from_slice(&migrated);
+    Ok(Some(from_version))
+}
+
+// Resizes `account` to `len` bytes, `payer` topping up its rent to cover them
+fn grow_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    len: usize,
+) -> ProgramResult {
+    let shortfall = Rent::get()?.minimum_balance(len).saturating_sub(account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, shortfall),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(len, false)
+}
+
+// Fails unless the account can hold `data_len` bytes without paying rent
+fn assert_rent_exempt(rent: &Rent, account: &AccountInfo, data_len: usize) -> ProgramResult {
+    if !rent.is_exempt(account.lamports(), data_len) {
+        return Err(AIInfraError::NotRentExempt.into());
+    }
+    Ok(())
+}
+
+// Pays out of a task vault. The vault is a system account, so lamports leave
+// it through a system transfer signed with the vault seeds.
+fn transfer_from_vault<'a>(
+    task_key: &Pubkey,
+    vault_bump: u8,
+    vault: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(vault.key, destination.key, amount),
+        &[vault.clone(), destination.clone(), system_program.clone()],
+        &[&[pda::VAULT_SEED, task_key.as_ref(), &[vault_bump]]],
+    )
+}
+
+// Empties a batch's vault and returns it and the batch's rent to its
+// requester. The batch's data is wiped so it can't be revived with stale
+// state later in the same transaction.
+fn close_batch<'a>(
+    batch: &BatchTask,
+    batch_account: &AccountInfo<'a>,
+    vault_account: &AccountInfo<'a>,
+    requester_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    transfer_from_vault(
+        batch_account.key,
+        batch.vault_bump,
+        vault_account,
+        requester_account,
+        system_program,
+        vault_account.lamports(),
+    )?;
+
+    batch_account.data.borrow_mut().fill(0);
+    let batch_lamports = batch_account.lamports();
+    **requester_account.lamports.borrow_mut() = requester_account.lamports()
+        .checked_add(batch_lamports)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    **batch_account.lamports.borrow_mut() = 0;
+    Ok(())
+}
+
+// Reads a token account of either token program, past any Token-2022
+// extensions
+fn unpack_token_account(
+    account: &AccountInfo,
+) -> Result<spl_token_2022::state::Account, ProgramError> {
+    TokenProgram::from_id(account.owner)?;
+    let data = account.data.borrow();
+    Ok(StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?.base)
+}
+
+// Reads a mint of either token program, past any Token-2022 extensions
+fn unpack_mint(account: &AccountInfo) -> Result<spl_token_2022::state::Mint, ProgramError> {
+    TokenProgram::from_id(account.owner)?;
+    let data = account.data.borrow();
+    Ok(StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?.base)
+}
+
+// Checks that a token account holds `mint` and belongs to `owner`
+fn assert_token_account(account: &AccountInfo, mint: &Pubkey, owner: &Pubkey) -> ProgramResult {
+    let token_account = unpack_token_account(account)?;
+    if token_account.mint != *mint {
+        return Err(AIInfraError::TokenMintMismatch.into());
+    }
+    if token_account.owner != *owner {
+        return Err(AIInfraError::TokenOwnerMismatch.into());
+    }
+    Ok(())
+}
+
+// Checks that a token account belonging to `holder` holds the single token
+// of the NFT `mint`
+fn assert_nft_holder(account: &AccountInfo, mint: &Pubkey, holder: &Pubkey) -> ProgramResult {
+    assert_token_account(account, mint, holder)?;
+    if unpack_token_account(account)?.amount != 1 {
+        return Err(AIInfraError::NotNftHolder.into());
+    }
+    Ok(())
+}
+
+// Whether `owner` is the agent's owner: the key it stores, or for an agent
+// bound to an NFT, whoever holds the NFT through `holder_token`. The key an
+// NFT-bound agent stores goes stale once the NFT changes hands, so every
+// check of the owner, and every payout to it, goes through here. Holders
+// pass their token account last, after the instruction's other accounts.
+fn is_agent_owner(agent: &AIAgent, owner: &Pubkey, holder_token: Option<&AccountInfo>) -> bool {
+    owns_agent(&agent.owner, agent.owner_nft_mint, owner, holder_token)
+}
+
+// Whether `key` may run the agent's tasks: its owner, as is_agent_owner
+// checks, or its operator
+fn is_agent_operator(agent: &AIAgent, key: &Pubkey, holder_token: Option<&AccountInfo>) -> bool {
+    agent.is_operated_by(key) || is_agent_owner(agent, key, holder_token)
+}
+
+// is_agent_owner and is_agent_operator for an agent viewed in place
+fn is_view_owner(
+    agent: &zero_copy::AgentRef,
+    owner: &Pubkey,
+    holder_token: Option<&AccountInfo>,
+) -> bool {
+    owns_agent(&agent.header.owner, agent.owner_nft_mint, owner, holder_token)
+}
+
+fn is_view_operator(
+    agent: &zero_copy::AgentRef,
+    key: &Pubkey,
+    holder_token: Option<&AccountInfo>,
+) -> bool {
+    agent.is_operated_by(key) || is_view_owner(agent, key, holder_token)
+}
+
+fn owns_agent(
+    stored_owner: &Pubkey,
+    owner_nft_mint: Option<Pubkey>,
+    owner: &Pubkey,
+    holder_token: Option<&AccountInfo>,
+) -> bool {
+    match owner_nft_mint {
+        Some(mint) => holder_token
+            .is_some_and(|holder_token| assert_nft_holder(holder_token, &mint, owner).is_ok()),
+        None => *stored_owner == *owner,
+    }
+}
+
+// Reads the system program account that transfers and account creations
+// invoke, rejecting any other program passed in its place
+fn next_system_program<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    accounts_iter: &mut I,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let system_program = next_account_info(accounts_iter)?;
+    if *system_program.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(system_program)
+}
+
+// Reads the credit vault of the agent at `agent_account`, which takes the
+// agent's share of a lamport payment while the config sets a hold period.
+// The vault must already hold its rent reserve, which DepositCredits covers.
+fn next_hold_vault<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    accounts_iter: &mut I,
+    agent_account: &AccountInfo<'b>,
+    task: &ComputeTask,
+    config: &Config,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if config.payment_hold_seconds == 0 || task.payment_mint.is_some() {
+        return Ok(None);
+    }
+
+    let credit_vault = next_account_info(accounts_iter)?;
+    let (credit_vault_address, _) = find_credit_vault_address(agent_account.key, program_id);
+    if credit_vault_address != *credit_vault.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if credit_vault.lamports() < Rent::get()?.minimum_balance(0) {
+        return Err(AIInfraError::NotRentExempt.into());
+    }
+    Ok(Some(credit_vault))
+}
+
+// Reads the insurance pool, which takes the config's haircut of a lamport
+// payment while the config sets one
+fn next_insurance_pool<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    accounts_iter: &mut I,
+    task: &ComputeTask,
+    config: &Config,
+) -> Result<Option<(&'a AccountInfo<'b>, InsurancePool)>, ProgramError> {
+    if config.insurance_bps == 0 || task.payment_mint.is_some() {
+        return Ok(None);
+    }
+
+    let pool_account = next_account_info(accounts_iter)?;
+    let pool = InsurancePool::load(program_id, pool_account)?;
+    Ok(Some((pool_account, pool)))
+}
+
+// Reads the referral accounts settling `task` needs while the config sets a
+// referral share: its requester's account, which may not exist, then the
+// referrer it names, whose wallet takes lamport payments and whose token
+// account takes token ones. Returns the referrer's account, if any.
+fn next_referrer_account<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    accounts_iter: &mut I,
+    task: &ComputeTask,
+    config: &Config,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if config.referral_bps == 0 {
+        return Ok(None);
+    }
+
+    let requester_account = next_account_info(accounts_iter)?;
+    let (requester_address, _) = find_requester_address(&task.requester, program_id);
+    if requester_address != *requester_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if requester_account.owner != program_id || requester_account.data_is_empty() {
+        return Ok(None);
+    }
+    let referrer = match load_account::<Requester>(requester_account)?.referrer {
+        Some(referrer) => referrer,
+        None => return Ok(None),
+    };
+
+    let referrer_account = next_account_info(accounts_iter)?;
+    match task.payment_mint {
+        Some(mint) => assert_token_account(referrer_account, &mint, &referrer)?,
+        None if *referrer_account.key != referrer => return Err(ProgramError::InvalidArgument),
+        None => {}
+    }
+    Ok(Some(referrer_account))
+}
+
+// The token program and mint a token task's escrow moves through, and the
+// mint's decimals, which transfer_checked confirms
+#[derive(Clone, Copy)]
+struct EscrowToken<'a, 'b> {
+    program: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    decimals: u8,
+}
+
+// Reads the token accounts a token-paid task settles through: the vault's
+// associated token account, the account receiving the escrow, the task's
+// token program and its mint, writable so the escrow's withheld transfer
+// fees can be harvested to it
+fn next_token_escrow_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    accounts_iter: &mut I,
+    vault: &Pubkey,
+    mint: &Pubkey,
+    token_program: TokenProgram,
+    destination_owner: &Pubkey,
+) -> Result<(&'a AccountInfo<'b>, &'a AccountInfo<'b>, EscrowToken<'a, 'b>), ProgramError> {
+    let escrow = next_escrow_accounts(accounts_iter, vault, mint, token_program)?;
+    assert_token_account(escrow.1, mint, destination_owner)?;
+    Ok(escrow)
+}
+
+// next_token_escrow_accounts, leaving the caller to check the account
+// receiving the escrow
+fn next_escrow_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    accounts_iter: &mut I,
+    vault: &Pubkey,
+    mint: &Pubkey,
+    token_program: TokenProgram,
+) -> Result<(&'a AccountInfo<'b>, &'a AccountInfo<'b>, EscrowToken<'a, 'b>), ProgramError> {
+    let vault_token = next_account_info(accounts_iter)?;
+    let destination_token = next_account_info(accounts_iter)?;
+    let program = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+
+    if *program.key != token_program.id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if *mint_account.key != *mint {
+        return Err(AIInfraError::TokenMintMismatch.into());
+    }
+    if get_associated_token_address_with_program_id(vault, mint, program.key) != *vault_token.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let decimals = unpack_mint(mint_account)?.decimals;
+    Ok((vault_token, destination_token, EscrowToken { program, mint: mint_account, decimals }))
+}
+
+// Pays out of a task's token escrow, signed with the vault seeds
+fn transfer_from_token_vault<'a>(
+    task_key: &Pubkey,
+    vault_bump: u8,
+    vault: &AccountInfo<'a>,
+    vault_token: &AccountInfo<'a>,
+    destination_token: &AccountInfo<'a>,
+    token: EscrowToken<'_, 'a>,
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &spl_token_2022::instruction::transfer_checked(
+            token.program.key,
+            vault_token.key,
+            token.mint.key,
+            destination_token.key,
+            vault.key,
+            &[],
+            amount,
+            token.decimals,
+        )?,
+        &[
+            vault_token.clone(),
+            token.mint.clone(),
+            destination_token.clone(),
+            vault.clone(),
+            token.program.clone(),
+        ],
+        &[&[pda::VAULT_SEED, task_key.as_ref(), &[vault_bump]]],
+    )
+}
+
+// Empties a task's token escrow into `destination_token` and closes the
+// escrow account, returning its rent to `rent_destination`
+#[allow(clippy::too_many_arguments)]
+fn release_token_escrow<'a>(
+    task_key: &Pubkey,
+    vault_bump: u8,
+    vault: &AccountInfo<'a>,
+    vault_token: &AccountInfo<'a>,
+    destination_token: &AccountInfo<'a>,
+    rent_destination: &AccountInfo<'a>,
+    token: EscrowToken<'_, 'a>,
+) -> ProgramResult {
+    let amount = unpack_token_account(vault_token)?.amount;
+    transfer_from_token_vault(
+        task_key,
+        vault_bump,
+        vault,
+        vault_token,
+        destination_token,
+        token,
+        amount,
+    )?;
+
+    // Transfer fees the escrow withheld on the way in keep it from closing
+    // until they are harvested to the mint, which anyone may do
+    let withheld = {
+        let data = vault_token.data.borrow();
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+        state.get_extension::<TransferFeeAmount>()
+            .map_or(0, |fees| u64::from(fees.withheld_amount))
+    };
+    if withheld > 0 {
+        invoke(
+            &spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+                token.program.key,
+                token.mint.key,
+                &[vault_token.key],
+            )?,
+            &[token.mint.clone(), vault_token.clone(), token.program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &spl_token_2022::instruction::close_account(
+            token.program.key,
+            vault_token.key,
+            rent_destination.key,
+            vault.key,
+            &[],
+        )?,
+        &[vault_token.clone(), rent_destination.clone(), vault.clone(), token.program.clone()],
+        &[&[pda::VAULT_SEED, task_key.as_ref(), &[vault_bump]]],
+    )
+}
+
+// Empties a task's wrapped SOL escrow into `destination` as lamports by
+// closing the escrow account to the vault, which then pays the escrowed
+// amount on to `destination` and the account's rent to `rent_destination`
+#[allow(clippy::too_many_arguments)]
+fn unwrap_token_escrow<'a>(
+    task_key: &Pubkey,
+    vault_bump: u8,
+    vault: &AccountInfo<'a>,
+    vault_token: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    rent_destination: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    token: EscrowToken<'_, 'a>,
+) -> ProgramResult {
+    let amount = unpack_token_account(vault_token)?.amount;
+    let rent = vault_token.lamports().checked_sub(amount)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    invoke_signed(
+        &spl_token_2022::instruction::close_account(
+            token.program.key,
+            vault_token.key,
+            vault.key,
+            vault.key,
+            &[],
+        )?,
+        &[vault_token.clone(), vault.clone(), token.program.clone()],
+        &[&[pda::VAULT_SEED, task_key.as_ref(), &[vault_bump]]],
+    )?;
+    transfer_from_vault(task_key, vault_bump, vault, destination, system_program, amount)?;
+    transfer_from_vault(task_key, vault_bump, vault, rent_destination, system_program, rent)
+}
+
+// Pays out of a vault derived from `seed` and a program account's address,
+// such as an agent's credits or stake
+fn transfer_from_account_vault<'a>(
+    seed: &[u8],
+    account_key: &Pubkey,
+    vault_bump: u8,
+    vault: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(vault.key, destination.key, amount),
+        &[vault.clone(), destination.clone(), system_program.clone()],
+        &[&[seed, account_key.as_ref(), &[vault_bump]]],
+    )
+}
+
+// Moves `amount` lamports from `payer` into a vault. The first deposit also
+// tops the vault up to its rent reserve, which stays put until the vault is
+// drained for good.
+fn deposit_to_vault<'a>(
+    payer: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let shortfall = Rent::get()?.minimum_balance(0).saturating_sub(vault.lamports());
+    let lamports = amount.checked_add(shortfall)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    invoke(
+        &system_instruction::transfer(payer.key, vault.key, lamports),
+        &[payer.clone(), vault.clone(), system_program.clone()],
+    )
+}
+
+// Checks that `signer` may act on a task before it runs: normally the
+// assigned agent's owner, or the requester while an open task is still
+// unclaimed. Returns the agent when the task has one.
+fn authorize_task_signer(
+    program_id: &Pubkey,
+    task: &ComputeTask,
+    agent_account: &AccountInfo,
+    signer: &AccountInfo,
+    holder_token: Option<&AccountInfo>,
+) -> Result<Option<AIAgent>, ProgramError> {
+    if !signer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if task.open_listing && task.agent == Pubkey::default() {
+        if task.requester != *signer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        return Ok(None);
+    }
+
+    let agent = load_task_agent(program_id, task, agent_account)?;
+    if let Some(agent) = &agent {
+        if !is_agent_owner(agent, signer.key, holder_token) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+    }
+    Ok(agent)
+}
+
+// Loads the agent assigned to `task`, or None while an open task is unclaimed
+fn load_task_agent(
+    program_id: &Pubkey,
+    task: &ComputeTask,
+    agent_account: &AccountInfo,
+) -> Result<Option<AIAgent>, ProgramError> {
+    if task.open_listing && task.agent == Pubkey::default() {
+        return Ok(None);
+    }
+
+    if agent_account.owner != program_id {
+        return Err(AIInfraError::AccountNotOwnedByProgram.into());
+    }
+
+    if task.agent != *agent_account.key {
+        return Err(AIInfraError::AgentTaskMismatch.into());
+    }
+
+    let agent = load_account::<AIAgent>(agent_account)?;
+    agent.assert_address(program_id, agent_account.key)?;
+    Ok(Some(agent))
+}
+
+// Fails a running task: the agent takes the reputation penalty and the whole
+// escrow goes back to the requester, unless the config holds it in the vault
+// for an insurance claim to settle
+#[allow(clippy::too_many_arguments)]
+fn fail_and_refund<'a>(
+    task_account: &AccountInfo<'a>,
+    agent_account: &AccountInfo<'a>,
+    mut task: ComputeTask,
+    mut agent: AIAgent,
+    reason: FailureReason,
+    penalty: u32,
+    config: &Config,
+    vault_account: &AccountInfo<'a>,
+    requester_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    token_escrow: Option<(&AccountInfo<'a>, &AccountInfo<'a>, EscrowToken<'_, 'a>)>,
+) -> ProgramResult {
+    agent.reputation_score = reputation::after_failure(agent.reputation_score, penalty);
+    agent.tasks_failed = agent.tasks_failed.checked_add(1)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    agent.open_tasks = agent.open_tasks.checked_sub(1)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    // Completed and disputed tasks gave their slot back on completion
+    if matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress) {
+        agent.release_task();
+    }
+
+    // While a claim can still be filed, the escrow stays in the vault so the
+    // requester can't take both the refund and the claim's payout
+    if config.holds_refund(&task, reason) {
+        task.held_refund =
+            vault_account.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    }
+
+    task.transition(TaskStatus::Failed)?;
+    task.failure_code = reason.code();
+    task.failure_reason = Some(reason);
+    task.failed_at = Clock::get()?.unix_timestamp;
+    save_account(task_account, &task)?;
+    save_account(agent_account, &agent)?;
+
+    emit(Event::TaskFailed {
+        task: *task_account.key,
+        agent: *agent_account.key,
+        reason,
+        refund: if task.held_refund != 0 { 0 } else { task.payment_amount },
+    })?;
+    if task.held_refund != 0 {
+        return Ok(());
+    }
+
+    if let Some((vault_token, requester_token, token)) = token_escrow {
+        release_token_escrow(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            vault_token,
+            requester_token,
+            requester_account,
+            token,
+        )?;
+    }
+    transfer_from_vault(
+        task_account.key,
+        task.vault_bump,
+        vault_account,
+        requester_account,
+        system_program,
+        vault_account.lamports(),
+    )
+}
+
+// Sends the config's slash share of the agent's stake to the treasury. The
+// caller saves the agent.
+fn slash_stake<'a>(
+    program_id: &Pubkey,
+    agent_key: &Pubkey,
+    agent: &mut AIAgent,
+    config: &Config,
+    stake_vault: &AccountInfo<'a>,
+    treasury_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let (stake_vault_address, stake_vault_bump) = find_stake_vault_address(agent_key, program_id);
+    if stake_vault_address != *stake_vault.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if config.treasury != *treasury_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let slash = config.slash_for(agent.staked_lamports)?;
+    agent.staked_lamports -= slash;
+    if slash > 0 {
+        emit(Event::StakeSlashed { agent: *agent_key, amount: slash })?;
+    }
+    transfer_from_account_vault(
+        pda::STAKE_VAULT_SEED,
+        agent_key,
+        stake_vault_bump,
+        stake_vault,
+        treasury_account,
+        system_program,
+        slash,
+    )
+}
+
+// Pays out a completed task: the agent collects its stats and the escrow,
+// less the protocol fee, any referral share and any forfeit for finishing
+// late. `accounts_iter` yields the token accounts a token payment needs,
+// then the referral accounts while the config sets a referral share.
+#[allow(clippy::too_many_arguments)]
+fn settle_completed_task<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    accounts_iter: &mut I,
+    task_account: &AccountInfo<'b>,
+    agent_account: &AccountInfo<'b>,
+    mut task: ComputeTask,
+    mut agent: AIAgent,
+    config: &Config,
+    owner_account: &AccountInfo<'b>,
+    vault_account: &AccountInfo<'b>,
+    requester_account: &AccountInfo<'b>,
+    system_program: &AccountInfo<'b>,
+    treasury_account: &AccountInfo<'b>,
+) -> ProgramResult {
+    // Update agent stats. Payments held after completion count the time up
+    // to the completion rather than the payout.
+    agent.tasks_completed = agent.tasks_completed.checked_add(1)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    agent.open_tasks = agent.open_tasks.checked_sub(1)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    let completed_at = match task.completed_at {
+        0 => Clock::get()?.unix_timestamp,
+        completed_at => completed_at,
+    };
+    let completion_seconds = completed_at.saturating_sub(task.started_at).max(0) as u64;
+    agent.total_completion_seconds =
+        agent.total_completion_seconds.saturating_add(completion_seconds);
+
+    let forfeit = if task.is_past_deadline(task.completed_at) {
+        config.late_forfeit_for(task.payment_amount)?
+    } else {
+        0
+    };
+    let earned = task.payment_amount - forfeit;
+    let fee = config.fee_for(earned)?;
+    agent.reputation_score =
+        reputation::after_completion(agent.reputation_score, earned, config.reputation_bonus);
+
+    // Token payments go to the agent owner's token account instead, or to
+    // the owner's wallet itself when the agent prefers its wrapped SOL
+    // unwrapped. The fee and any forfeit need the treasury's and requester's
+    // token accounts, in that order, when they are non-zero.
+    let unwrap = agent.prefer_native
+        && task.payment_mint == Some(task.token_program.native_mint());
+    let token_escrow = match task.payment_mint {
+        Some(mint) => {
+            let (vault_token, owner_token, token) = next_escrow_accounts(
+                accounts_iter,
+                vault_account.key,
+                &mint,
+                task.token_program,
+            )?;
+            if !unwrap {
+                assert_token_account(owner_token, &mint, owner_account.key)?;
+            } else if *owner_token.key != *owner_account.key {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let treasury_token = if fee > 0 {
+                let treasury_token = next_account_info(accounts_iter)?;
+                assert_token_account(treasury_token, &mint, &config.treasury)?;
+                Some(treasury_token)
+            } else {
+                None
+            };
+            let requester_token = if forfeit > 0 {
+                let requester_token = next_account_info(accounts_iter)?;
+                assert_token_account(requester_token, &mint, &task.requester)?;
+                Some(requester_token)
+            } else {
+                None
+            };
+            Some((vault_token, owner_token, token, treasury_token, requester_token))
+        }
+        None => None,
+    };
+
+    // The requester's referrer, if any, takes its share of the earned
+    // payment. The agent keeps whatever the rounded-down fee and share leave.
+    let referral = match next_referrer_account(program_id, accounts_iter, &task, config)? {
+        Some(referrer_account) => Some((referrer_account, config.referral_for(earned)?)),
+        None => None,
+    };
+    let referral_amount = referral.map_or(0, |(_, amount)| amount);
+
+    // While the config sets an insurance haircut, a lamport payment gives up
+    // that share of the earned payment to the insurance pool
+    let hold_vault = next_hold_vault(program_id, accounts_iter, agent_account, &task, config)?;
+    let insurance = match next_insurance_pool(program_id, accounts_iter, &task, config)? {
+        Some((pool_account, pool)) => Some((pool_account, pool, config.insurance_for(earned)?)),
+        None => None,
+    };
+    let insurance_amount = insurance.as_ref().map_or(0, |(_, _, amount)| *amount);
+    let amount = earned.checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(referral_amount))
+        .and_then(|amount| amount.checked_sub(insurance_amount))
+        .ok_or(AIInfraError::NumericalOverflow)?;
+
+    // While the config sets a hold period, the agent's share of a lamport
+    // payment waits in its credit vault instead of going to the owner
+    let held = match hold_vault {
+        Some(credit_vault) => {
+            let release_at = Clock::get()?.unix_timestamp
+                .checked_add(config.payment_hold_seconds)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            agent.hold_payment(amount, release_at)?;
+            Some((credit_vault, release_at))
+        }
+        None => None,
+    };
+
+    let reserve = match token_escrow {
+        Some(_) => 0,
+        None => vault_account.lamports().checked_sub(task.payment_amount)
+            .ok_or(AIInfraError::NumericalOverflow)?,
+    };
+
+    task.payment_pending = false;
+    save_account(task_account, &task)?;
+    save_account(agent_account, &agent)?;
+    let insurance = match insurance {
+        Some((pool_account, mut pool, amount)) => {
+            pool.total_collected = pool.total_collected.checked_add(amount)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            save_account(pool_account, &pool)?;
+            Some((pool_account, amount))
+        }
+        None => None,
+    };
+
+    emit(Event::TaskPaid {
+        task: *task_account.key,
+        agent: *agent_account.key,
+        amount,
+        fee,
+        forfeit,
+    })?;
+    if let Some((referrer_account, referral_amount)) = referral {
+        emit(Event::ReferralPaid {
+            task: *task_account.key,
+            referrer: *referrer_account.key,
+            amount: referral_amount,
+        })?;
+    }
+    if let Some((_, release_at)) = held {
+        emit(Event::PaymentHeld {
+            task: *task_account.key,
+            agent: *agent_account.key,
+            amount,
+            release_at,
+        })?;
+    }
+    if let Some((_, insurance_amount)) = insurance {
+        emit(Event::InsuranceCollected { task: *task_account.key, amount: insurance_amount })?;
+    }
+
+    if let Some((vault_token, owner_token, token, treasury_token, requester_token)) = token_escrow
+    {
+        let referrer_token = referral
+            .filter(|&(_, amount)| amount > 0)
+            .map(|(referrer_token, _)| referrer_token);
+        for (destination, amount) in [
+            (treasury_token, fee),
+            (requester_token, forfeit),
+            (referrer_token, referral_amount),
+        ] {
+            if let Some(destination) = destination {
+                transfer_from_token_vault(
+                    task_account.key,
+                    task.vault_bump,
+                    vault_account,
+                    vault_token,
+                    destination,
+                    token,
+                    amount,
+                )?;
+            }
+        }
+        if unwrap {
+            unwrap_token_escrow(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                vault_token,
+                owner_token,
+                requester_account,
+                system_program,
+                token,
+            )?;
+        } else {
+            release_token_escrow(
+                task_account.key,
+                task.vault_bump,
+                vault_account,
+                vault_token,
+                owner_token,
+                requester_account,
+                token,
+            )?;
+        }
+        return Ok(());
+    }
+
+    // Release the fee to the treasury, any referral share to the referrer,
+    // any haircut to the insurance pool, the rest of the earned payment to
+    // the agent owner or its hold, and any forfeit with the rent reserve
+    // back to the requester
+    transfer_from_vault(
+        task_account.key,
+        task.vault_bump,
+        vault_account,
+        treasury_account,
+        system_program,
+        fee,
+    )?;
+    if let Some((referrer_account, referral_amount)) = referral {
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            referrer_account,
+            system_program,
+            referral_amount,
+        )?;
+    }
+    if let Some((pool_account, insurance_amount)) = insurance {
+        transfer_from_vault(
+            task_account.key,
+            task.vault_bump,
+            vault_account,
+            pool_account,
+            system_program,
+            insurance_amount,
+        )?;
+    }
+    transfer_from_vault(
+        task_account.key,
+        task.vault_bump,
+        vault_account,
+        held.map_or(owner_account, |(credit_vault, _)| credit_vault),
+        system_program,
+        amount,
+    )?;
+    transfer_from_vault(
+        task_account.key,
+        task.vault_bump,
+        vault_account,
+        requester_account,
+        system_program,
+        reserve + forfeit,
+    )
+}
+
+// Creates the next task PDA, escrowing through the vault at `vault_account`,
+// lists it in its agent's task index, and advances the counter it was
+// numbered from and the registry's task count. `payer_account` pays the
+// rents and `requester` is recorded as the task's requester. The caller
+// moves the payment itself.
+//
+// Passing the default pubkey as the agent lists an open task that any agent
+// can claim later. Open tasks are numbered by the config's counter, since
+// there is no agent to number them yet.
+#[allow(clippy::too_many_arguments)]
+fn init_task<'a>(
+    program_id: &Pubkey,
+    task_account: &AccountInfo<'a>,
+    agent_account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    requester: &Pubkey,
+    system_program: &AccountInfo<'a>,
+    vault_account: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    registry_account: &AccountInfo<'a>,
+    task_page_account: &AccountInfo<'a>,
+    config: &mut Config,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+    payment_mint: Option<(Pubkey, TokenProgram)>,
+    priority: u8,
+    tags: [u8; TASK_TAGS],
+    allowlist_account: Option<&AccountInfo<'a>>,
+) -> ProgramResult {
+    if !payer_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    requirements.validate(&config.requirement_bounds)?;
+
+    let mut registry = Registry::load(program_id, registry_account)?;
+
+    let open_listing = *agent_account.key == Pubkey::default();
+    let mut agent = None;
+    let index = if open_listing {
+        config.open_tasks_created
+    } else {
+        if agent_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+
+        let assigned = load_account::<AIAgent>(agent_account)?;
+        assigned.assert_address(program_id, agent_account.key)?;
+        if !assigned.is_active {
+            return Err(AIInfraError::AgentNotActive.into());
+        }
+        if config.stale_after_seconds != 0
+            && config.is_stale(&assigned, Clock::get()?.unix_timestamp)
+        {
+            return Err(AIInfraError::AgentStale.into());
+        }
+        if assigned.staked_lamports < requirements.min_stake {
+            return Err(AIInfraError::StakeTooLow.into());
+        }
+
+        // An agent with an allowlist only takes tasks from requesters on it
+        if assigned.allowed_requesters > 0 {
+            let allowlist_account = allowlist_account.ok_or(AIInfraError::RequesterNotAllowed)?;
+            let allowlist = AgentAllowlist::load(program_id, agent_account.key, allowlist_account)?;
+            if !allowlist.requesters.contains(requester) {
+                return Err(AIInfraError::RequesterNotAllowed.into());
+            }
+        }
+        let index = assigned.tasks_created;
+        agent = Some(assigned);
+        index
+    };
+
+    let (task_address, bump) = find_task_address(agent_account.key, index, program_id);
+    if task_address != *task_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let (vault_address, vault_bump) = find_vault_address(task_account.key, program_id);
+    if vault_address != *vault_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: *agent_account.key,
+        requirements,
+        status: TaskStatus::Pending,
+        result_hash: [0; 32],
+        payment_amount,
+        index,
+        bump,
+        failure_code: 0,
+        requester: *requester,
+        vault_bump,
+        payment_mint: payment_mint.map(|(mint, _)| mint),
+        open_listing,
+        started_at: 0,
+        deadline: 0,
+        completed_at: 0,
+        payment_pending: false,
+        commitment: None,
+        verified: false,
+        created_at: Clock::get()?.unix_timestamp,
+        expected_result_hash: None,
+        tip_reputation: 0,
+        parent: None,
+        shard_index: 0,
+        shard_count: 0,
+        reissued: false,
+        failure_reason: None,
+        priority,
+        tags,
+        failed_at: 0,
+        insurance_claimed: false,
+        token_program: payment_mint.map_or(TokenProgram::Spl, |(_, program)| program),
+        payment_usd_cents: 0,
+        description_hash: [0; 32],
+        held_refund: 0,
+        result_data: Vec::new(),
+    };
+    let rent = &Rent::get()?;
+
+    // Create the task PDA
+    create_pda_account(
+        payer_account,
+        task_account,
+        system_program,
+        program_id,
+        rent,
+        ComputeTask::LEN,
+        &[pda::TASK_SEED, agent_account.key.as_ref(), &index.to_le_bytes(), &[bump]],
+    )?;
+    assert_rent_exempt(rent, task_account, ComputeTask::LEN)?;
+
+    save_account(task_account, &task)?;
+
+    // List the task in the agent's index, opening a new page for every
+    // TASKS_PER_PAGE tasks
+    let page = index / TASKS_PER_PAGE;
+    let mut task_page = if index % TASKS_PER_PAGE == 0 {
+        let (page_address, page_bump) =
+            find_task_page_address(agent_account.key, page, program_id);
+        if page_address != *task_page_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        create_pda_account(
+            payer_account,
+            task_page_account,
+            system_program,
+            program_id,
+            rent,
+            TaskPage::LEN,
+            &[pda::TASK_PAGE_SEED, agent_account.key.as_ref(), &page.to_le_bytes(), &[page_bump]],
+        )?;
+        assert_rent_exempt(rent, task_page_account, TaskPage::LEN)?;
+        TaskPage {
+            account_type: AccountType::TaskPage,
+            version: CURRENT_VERSION,
+            agent: *agent_account.key,
+            page,
+            bump: page_bump,
+            tasks: Vec::new(),
+        }
+    } else {
+        TaskPage::load(program_id, agent_account.key, page, task_page_account)?
+    };
+    task_page.tasks.push(*task_account.key);
+    save_account(task_page_account, &task_page)?;
+
+    emit(Event::TaskCreated {
+        task: *task_account.key,
+        agent: *agent_account.key,
+        requester: *requester,
+        payment_amount,
+        payment_mint: task.payment_mint,
+    })?;
+
+    // Advance the task counter
+    match agent {
+        Some(mut agent) => {
+            agent.tasks_created = agent.tasks_created.checked_add(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            agent.take_task()?;
+            agent.open_tasks = agent.open_tasks.checked_add(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            save_account(agent_account, &agent)?;
+        }
+        None => {
+            config.open_tasks_created = config.open_tasks_created.checked_add(1)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            save_account(config_account, config)?;
+        }
+    }
+
+    registry.total_tasks = registry.total_tasks.checked_add(1)
+        .ok_or(AIInfraError::NumericalOverflow)?;
+    save_account(registry_account, &registry)
+}
+
+// Creates shard `shard_index` of `task` as an open task paying `payment`,
+// requested by the task's requester and paid for by `payer_account`. The
+// caller funds the shard's vault.
+#[allow(clippy::too_many_arguments)]
+fn init_shard<'a>(
+    program_id: &Pubkey,
+    task_key: &Pubkey,
+    task: &ComputeTask,
+    shard_index: u8,
+    payment: u64,
+    shard_account: &AccountInfo<'a>,
+    shard_vault: &AccountInfo<'a>,
+    shard_page: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    registry_account: &AccountInfo<'a>,
+    config: &mut Config,
+) -> ProgramResult {
+    // Open listings' agent is the default pubkey, which is the system
+    // program's address
+    init_task(
+        program_id,
+        shard_account,
+        system_program,
+        payer_account,
+        &task.requester,
+        system_program,
+        shard_vault,
+        config_account,
+        registry_account,
+        shard_page,
+        config,
+        task.requirements.clone(),
+        payment,
+        None,
+        task.priority,
+        task.tags,
+        None,
+    )?;
+    let mut shard = load_account::<ComputeTask>(shard_account)?;
+    shard.parent = Some(*task_key);
+    shard.shard_index = shard_index;
+    save_account(shard_account, &shard)
+}
+
+// Shared by CompleteTask and RevealResult, which take the same accounts.
+// `salt` is None for a single-step completion, and `result_data` the inline
+// payload CompleteTask may store with the result.
+fn finish_task(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    task_id: Pubkey,
+    result_hash: [u8; 32],
+    salt: Option<[u8; 32]>,
+    result_data: Option<Vec<u8>>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let task_account = next_account_info(accounts_iter)?;
+    let agent_account = next_account_info(accounts_iter)?;
+    let owner_account = next_account_info(accounts_iter)?;
+    let vault_account = next_account_info(accounts_iter)?;
+    let requester_account = next_account_info(accounts_iter)?;
+    let system_program = next_system_program(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury_account = next_account_info(accounts_iter)?;
+
+    let config = load_completion_config(program_id, config_account, treasury_account)?;
+    complete_task(
+        program_id,
+        accounts_iter,
+        task_account,
+        agent_account,
+        owner_account,
+        vault_account,
+        requester_account,
+        system_program,
+        treasury_account,
+        find_instructions_sysvar(accounts),
+        accounts.last(),
+        &config,
+        task_id,
+        result_hash,
+        salt,
+    )?;
+
+    // The signer pays the rent on the bytes the payload adds
+    if let Some(data) = result_data {
+        let mut task = load_account::<ComputeTask>(task_account)?;
+        grow_account(task_account, owner_account, system_program, ComputeTask::LEN + data.len())?;
+        task.result_data = data;
+        save_account(task_account, &task)?;
+    }
+    Ok(())
+}
+
+// The instructions sysvar, if it is anywhere among `accounts`
+fn find_instructions_sysvar<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+) -> Option<&'a AccountInfo<'b>> {
+    accounts.iter().find(|account| sysvar::instructions::check_id(account.key))
+}
+
+// Loads the config for a completion, which must not be paused and must
+// name `treasury_account` as its treasury
+fn load_completion_config(
+    program_id: &Pubkey,
+    config_account: &AccountInfo,
+    treasury_account: &AccountInfo,
+) -> Result<Config, ProgramError> {
+    let config = Config::load(program_id, config_account)?;
+    config.assert_not_paused()?;
+    if config.treasury != *treasury_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(config)
+}
+
+// Validates and completes a single task, paying the agent unless the config
+// holds the payment. `accounts_iter` yields the token accounts a token
+// payment needs.
+#[allow(clippy::too_many_arguments)]
+fn complete_task<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    accounts_iter: &mut I,
+    task_account: &AccountInfo<'b>,
+    agent_account: &AccountInfo<'b>,
+    owner_account: &AccountInfo<'b>,
+    vault_account: &AccountInfo<'b>,
+    requester_account: &AccountInfo<'b>,
+    system_program: &AccountInfo<'b>,
+    treasury_account: &AccountInfo<'b>,
+    instructions_sysvar: Option<&AccountInfo<'b>>,
+    holder_token: Option<&AccountInfo<'b>>,
+    config: &Config,
+    task_id: Pubkey,
+    result_hash: [u8; 32],
+    salt: Option<[u8; 32]>,
+) -> ProgramResult {
+    if *task_account.key != task_id {
+        return Err(AIInfraError::TaskIdMismatch.into());
+    }
+
+    if task_account.owner != program_id || agent_account.owner != program_id {
+        return Err(AIInfraError::AccountNotOwnedByProgram.into());
+    }
+
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // The task and agent are read in place, see zero_copy.rs, and updated
+    // in place when the payment is held
+    let mut task_data = task_account.try_borrow_mut_data()?;
+    let mut task = zero_copy::task_mut(&mut task_data)?;
+    task.assert_address(program_id, task_account.key)?;
+    if task.status()? != TaskStatus::InProgress {
+        return Err(AIInfraError::InvalidTaskStatus.into());
+    }
+
+    // Tasks that didn't opt out must be finished by revealing a commitment
+    match salt {
+        None if task.header.requirements.single_step == 0 => {
+            return Err(AIInfraError::InvalidInstruction.into());
+        }
+        None => {}
+        Some(salt) => {
+            let commitment = task.commitment.ok_or(AIInfraError::InvalidTaskStatus)?;
+            if hashv(&[&result_hash, &salt]).to_bytes() != commitment {
+                return Err(AIInfraError::CommitmentMismatch.into());
+            }
+        }
+    }
+
+    // A deterministic job only settles on the result its requester expects.
+    // The task stays in progress for the agent to retry.
+    if matches!(task.expected_result_hash, Some(expected) if expected != result_hash) {
+        return Err(AIInfraError::ResultMismatch.into());
+    }
+
+    // Only the task's own agent may complete it and collect the stats
+    if task.header.agent != *agent_account.key {
+        return Err(AIInfraError::AgentTaskMismatch.into());
+    }
+
+    let mut agent_data = agent_account.try_borrow_mut_data()?;
+    let mut agent = zero_copy::agent_mut(&mut agent_data)?;
+    agent.view().assert_address(program_id, agent_account.key)?;
+    let is_owner = is_view_owner(&agent.view(), owner_account.key, holder_token);
+    if !is_owner && !agent.view().is_operated_by(owner_account.key) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // An agent with an attestation key only completes tasks whose result its
+    // worker signed in an earlier ed25519 instruction
+    if let Some(attestation_key) = agent.attestation_key {
+        let instructions_sysvar = instructions_sysvar.ok_or(AIInfraError::InvalidAttestation)?;
+        let message = attestation_message(task_account.key, &result_hash);
+        verify_attestation(instructions_sysvar, &attestation_key, &message)?;
+    }
+
+    task.assert_vault(program_id, task_account.key, vault_account.key)?;
+    if task.header.requester != *requester_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Completions are timestamped when there is a deadline to check them
+    // against or a dispute window or verification timeout to run from
+    let completed_at = if task.times.deadline.get() != 0 || config.holds_payment() {
+        Clock::get()?.unix_timestamp
+    } else {
+        task.times.completed_at.get()
+    };
+
+    // A late completion forfeits part of the payment back to the requester,
+    // or is rejected outright when the config sets no decay
+    if task.is_past_deadline(completed_at) && config.late_decay_bps == 0 {
+        return Err(AIInfraError::TaskExpired.into());
+    }
+
+    // The payment waits for ClaimPayment inside a dispute window, or for
+    // VerifyResult when the config requires verification. Payouts only go to
+    // the owner's wallet, so an operator's completion also leaves it for the
+    // owner to claim.
+    let payment_pending = config.holds_payment() || !is_owner;
+    emit(Event::TaskCompleted {
+        task: *task_account.key,
+        agent: *agent_account.key,
+        result_hash,
+        payment_pending,
+    })?;
+    if payment_pending {
+        task.transition(TaskStatus::Completed)?;
+        task.header.result_hash = result_hash;
+        task.times.completed_at.set(completed_at);
+        task.times.payment_pending = 1;
+        agent.release_task();
+        msg!("Task completed, payment pending");
+        return Ok(());
+    }
+
+    // Paying out goes through the Borsh structs, as ClaimPayment does, and
+    // writes nothing until the payment has been worked out
+    drop(task_data);
+    drop(agent_data);
+    let mut task = load_account::<ComputeTask>(task_account)?;
+    let mut agent = load_account::<AIAgent>(agent_account)?;
+    task.transition(TaskStatus::Completed)?;
+    task.result_hash = result_hash;
+    task.completed_at = completed_at;
+    agent.release_task();
+
+    settle_completed_task(
+        program_id,
+        accounts_iter,
+        task_account,
+        agent_account,
+        task,
+        agent,
+        config,
+        owner_account,
+        vault_account,
+        requester_account,
+        system_program,
+        treasury_account,
+    )?;
+
+    msg!("Task completed successfully");
+    Ok(())
+}
+
+// Fails unless a lamport payment meets both the config's flat minimum and
+// the price it sets for `requirements` at `priority`
+fn check_payment(
+    config: &Config,
+    requirements: &ComputeRequirements,
+    payment_amount: u64,
+    priority: u8,
+) -> ProgramResult {
+    if payment_amount < config.min_payment {
+        return Err(AIInfraError::PaymentTooLow.into());
+    }
+    if payment_amount < priority_task_price(requirements, config, priority) {
+        return Err(AIInfraError::PaymentBelowMinimum.into());
+    }
+    Ok(())
+}
+
+// The current price of the config's oracle, which `oracle_account` must be,
+// once it passes the config's staleness and confidence bounds
+fn load_oracle_price(
+    config: &Config,
+    oracle_account: &AccountInfo,
+) -> Result<OraclePrice, ProgramError> {
+    if config.oracle == Pubkey::default() || *oracle_account.key != config.oracle {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let price = OraclePrice::unpack(&oracle_account.data.borrow())?;
+    price.validate(
+        Clock::get()?.unix_timestamp,
+        config.max_oracle_age_seconds,
+        config.max_oracle_confidence_bps,
+    )?;
+    Ok(price)
+}
+
+// Accounts an instruction may write to and those that must sign it, by
+// index. Checked before dispatch, so a client that passes one with the wrong
+// flags learns which, instead of the handler failing deep inside.
+struct AccountRoles {
+    writable: Vec<usize>,
+    // Where a variable list of written accounts starts, for instructions
+    // that take one
+    writable_from: Option<usize>,
+    signers: &'static [usize],
+}
+
+impl AccountRoles {
+    fn new(writable: &[usize], signers: &'static [usize]) -> Self {
+        Self { writable: writable.to_vec(), writable_from: None, signers }
+    }
+
+    // Adds the accounts init_task takes, starting at `first`: the task, the
+    // agent, the paying signer, the vault, the registry and the task page,
+    // and the config when it numbers an open listing
+    fn with_task_creation(mut self, accounts: &[AccountInfo], first: usize) -> Self {
+        self.writable.extend([0, 1, 2, 4, 6, 7].map(|offset| first + offset));
+        if lists_open_task(accounts, first + 1) {
+            self.writable.push(first + 5);
+        }
+        self
+    }
+
+    fn with_writable_from(self, index: usize) -> Self {
+        Self { writable_from: Some(index), ..self }
+    }
+}
+
+// Whether the agent at `agent_index` is the default pubkey, listing the task
+// being created as open
+fn lists_open_task(accounts: &[AccountInfo], agent_index: usize) -> bool {
+    accounts.get(agent_index).is_some_and(|agent| *agent.key == Pubkey::default())
+}
+
+// Whether accounts follow the `fixed` ones of an instruction a multisig can
+// sign for the agent's owner: the multisig's, or an NFT holder's token
+// account
+fn multisig_signs(accounts: &[AccountInfo], fixed: usize) -> bool {
+    accounts.len() > fixed
+}
+
+// Whether a CloseTask lists a failed task's vault and the system program, to
+// return the refund it held to the requester
+fn releases_held_refund(accounts: &[AccountInfo]) -> bool {
+    accounts.get(5).is_some_and(|account| *account.key == system_program::id())
+}
+
+// Whether a RegisterAgent still lists the rent sysvar after the system program
+fn passes_rent_sysvar(accounts: &[AccountInfo]) -> bool {
+    accounts.get(3).is_some_and(|account| *account.key == sysvar::rent::id())
+}
+
+fn account_roles(instruction: &AIInfraInstruction, accounts: &[AccountInfo]) -> AccountRoles {
+    use AIInfraInstruction::*;
+    match instruction {
+        RegisterAgent => {
+            AccountRoles::new(&[0, 1, 3 + usize::from(passes_rent_sysvar(accounts))], &[1])
+        }
+        UpdateAgentStatus { .. }
+        | Heartbeat
+        | SetGrantAmount { .. }
+        | UpdateConfig { .. }
+        | PauseProgram
+        | UnpauseProgram
+        | DisputeTask { .. }
+        | SetExpectedResult { .. }
+        | UpdateTemplate { .. } => AccountRoles::new(&[0], &[1]),
+        CreateTask { .. } => AccountRoles::new(&[], &[2]).with_task_creation(accounts, 0),
+        StartTask { .. } | CommitResult { .. } | SubmitRedundantResult { .. } => {
+            AccountRoles::new(&[0], &[2])
+        }
+        CompleteTask { .. } | RevealResult { .. } | ClaimPayment { .. } => {
+            AccountRoles::new(&[0, 1, 2, 3, 4, 7], &[2])
+        }
+        DepositCredits { .. } => AccountRoles::new(&[0, 1, 2, 5], &[1]),
+        ClaimGrant => AccountRoles::new(&[0, 1, 2, 3], &[1]),
+        FundFaucet { .. } | InitializeInsurancePool => AccountRoles::new(&[1, 2], &[1]),
+        FileInsuranceClaim => AccountRoles::new(&[0, 1, 2, 4], &[1]),
+        SweepInsurancePool { .. } => AccountRoles::new(&[2, 3], &[1]),
+        // The owner needn't sign a withdrawal the agent's multisig signs for
+        WithdrawCredits { .. } if multisig_signs(accounts, 5) => {
+            AccountRoles::new(&[0, 1, 2], &[])
+        }
+        WithdrawCredits { .. }
+        | StakeCollateral { .. }
+        | UnstakeCollateral { .. }
+        | DepositRequesterCredits { .. }
+        | WithdrawRequesterCredits { .. } => AccountRoles::new(&[0, 1, 2], &[1]),
+        CancelTask { .. } => AccountRoles::new(&[0, 1, 3, 4], &[2]),
+        FailTask { .. } => AccountRoles::new(&[0, 1, 3, 4, 7, 8], &[2]),
+        CloseTask { .. } if releases_held_refund(accounts) => {
+            AccountRoles::new(&[0, 2, 3, 4], &[2])
+        }
+        CloseTask { .. } => AccountRoles::new(&[0, 3], &[2]),
+        DeregisterAgent => AccountRoles::new(&[0, 1, 2, 4], &[1]),
+        // The owner needn't sign an instruction its agent's multisig signs
+        // for, whose account and members follow the rest. An NFT holder
+        // does, which the handler checks. The owner still signs those it
+        // pays rent for.
+        SetOperator { .. }
+        | RevokeOperator
+        | SetAgentLimits { .. }
+        | SetPreferNative { .. }
+        | SetCapabilities { .. }
+        | SetAcceptedTags { .. }
+        | SetWithdrawalPolicy { .. }
+        | SetAttestationKey { .. }
+            if multisig_signs(accounts, 2) =>
+        {
+            AccountRoles::new(&[0], &[])
+        }
+        SetOperator { .. }
+        | RevokeOperator
+        | SetAgentLimits { .. }
+        | SetPreferNative { .. }
+        | SetCapabilities { .. }
+        | SetAcceptedTags { .. }
+        | SetWithdrawalPolicy { .. }
+        | SetAttestationKey { .. } => AccountRoles::new(&[0], &[1]),
+        TransferAgentOwnership { .. } if multisig_signs(accounts, 3) => {
+            AccountRoles::new(&[0], &[2])
+        }
+        TransferAgentOwnership { .. } => AccountRoles::new(&[0], &[1, 2]),
+        SetMultisigOwner { .. } if multisig_signs(accounts, 4) => {
+            AccountRoles::new(&[0, 2], &[])
+        }
+        SetMultisigOwner { .. } => AccountRoles::new(&[0, 1, 2], &[1]),
+        BindAgentToNft if multisig_signs(accounts, 3) => AccountRoles::new(&[0], &[]),
+        BindAgentToNft | UnbindAgentFromNft => AccountRoles::new(&[0], &[1]),
+        // Token tasks take their token accounts between the vault and config
+        CreateTaskWithToken { .. } => {
+            let mut roles = AccountRoles::new(&[0, 1, 2, 5, 6, 11, 12], &[2]);
+            if lists_open_task(accounts, 1) {
+                roles.writable.push(10);
+            }
+            roles
+        }
+        InitializeConfig { .. }
+        | MigrateAccount
+        | InitializeRegistry
+        | RegisterVerifier { .. }
+        | SetReferrer { .. }
+        | CloseTemplate
+        | CancelRecurringTask => AccountRoles::new(&[0, 1], &[1]),
+        ClaimTask { .. } => AccountRoles::new(&[0, 1], &[2]),
+        SubmitBid { .. } => AccountRoles::new(&[0, 3], &[3]),
+        AcceptBid { .. } => AccountRoles::new(&[0, 2, 3, 4], &[3]),
+        CloseBid => AccountRoles::new(&[0, 3], &[2]),
+        ExpireTask { .. } => AccountRoles::new(&[0, 1, 2, 3, 6, 7], &[]),
+        DeactivateStaleAgent => AccountRoles::new(&[0, 1, 2], &[]),
+        CreateTaskWithCredits { .. } => {
+            AccountRoles::new(&[8, 9], &[2]).with_task_creation(accounts, 0)
+        }
+        ResolveDispute { .. } => AccountRoles::new(&[0, 1, 3, 4, 5, 8, 9], &[2]),
+        VerifyResult { .. } => AccountRoles::new(&[0, 1, 4, 5, 6, 9], &[2]),
+        RefundUnverifiedTask { .. } => AccountRoles::new(&[0, 1, 2, 3], &[]),
+        // Each task's accounts may be followed by readonly token accounts
+        BatchCompleteTasks { .. } => AccountRoles::new(&[0, 1, 4], &[1]),
+        AddAllowedRequester { .. } | CreateTemplate { .. } | CreateRecurringTask { .. } => {
+            AccountRoles::new(&[0, 1, 2], &[2])
+        }
+        RemoveAllowedRequester { .. } if multisig_signs(accounts, 3) => {
+            AccountRoles::new(&[0, 1], &[])
+        }
+        RemoveAllowedRequester { .. } => AccountRoles::new(&[0, 1], &[2]),
+        TransferCredits { .. } => AccountRoles::new(&[0, 1, 2, 3, 4], &[2]),
+        RevokeAllowance if multisig_signs(accounts, 3) => AccountRoles::new(&[0, 2], &[]),
+        ApproveCredits { .. } | RevokeAllowance => AccountRoles::new(&[0, 2], &[2]),
+        CreateTaskWithAllowance { .. } => {
+            AccountRoles::new(&[8, 9, 10], &[2]).with_task_creation(accounts, 0)
+        }
+        CreateBatchTask { .. } | CreateRedundantTask { .. } => {
+            AccountRoles::new(&[0, 1, 2, 4], &[2])
+        }
+        SubmitBatchResult { .. } => AccountRoles::new(&[0], &[2]),
+        ClaimBatchItem { .. } if multisig_signs(accounts, 8) => {
+            AccountRoles::new(&[0, 2, 3, 6, 7], &[])
+        }
+        ClaimBatchItem { .. } => AccountRoles::new(&[0, 2, 3, 6, 7], &[2]),
+        RefundBatchTask => AccountRoles::new(&[0, 1, 2], &[1]),
+        TipAgent { .. } => AccountRoles::new(&[0, 1, 2, 3], &[2]),
+        ReleasePayments | AggregateResults => AccountRoles::new(&[0], &[]),
+        CreateTaskFromTemplate { .. } => {
+            AccountRoles::new(&[], &[3]).with_task_creation(accounts, 1)
+        }
+        CrankRecurringTask => AccountRoles::new(&[0], &[3]).with_task_creation(accounts, 1),
+        SplitTask { .. } => AccountRoles::new(&[0, 2, 3, 5, 6], &[3]).with_writable_from(7),
+        ReissueShard => AccountRoles::new(&[1, 2, 4, 5, 6, 7, 8], &[2]),
+        // Each submitting agent and its owner, who are paid
+        FinalizeRedundantTask => AccountRoles::new(&[0, 1, 2, 5], &[]).with_writable_from(6),
+        SetAgentMetadata { .. } => AccountRoles::new(&[0, 2], &[2]),
+        UpdateTaskPriority { .. } => AccountRoles::new(&[0, 1, 2], &[1]),
+    }
+}
+
+// Fails at the first account not passed as `roles` needs. The default pubkey
+// stands in for an open listing's absent agent and is never written, so it
+// may be readonly. Missing accounts are left to the handler to report.
+fn check_account_roles(accounts: &[AccountInfo], roles: &AccountRoles) -> ProgramResult {
+    for (index, account) in accounts.iter().enumerate() {
+        let writable = roles.writable.contains(&index)
+            || roles.writable_from.is_some_and(|from| index >= from);
+        if writable && !account.is_writable && *account.key != Pubkey::default() {
+            msg!("Account {} must be writable", index);
+            return Err(AIInfraError::AccountNotWritable.into());
+        }
+        if roles.signers.contains(&index) && !account.is_signer {
+            msg!("Account {} must sign", index);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+    }
+    Ok(())
+}
+
+// Fails when a program account the instruction writes to is passed again
+// elsewhere, so no handler reads and writes one account under two names or
+// holds aliasing borrows of its data. Wallets may repeat, as when an owner
+// also pays, and so may accounts within a variable list, like the task page
+// several shards share.
+fn check_distinct_accounts(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    roles: &AccountRoles,
+) -> ProgramResult {
+    for &index in &roles.writable {
+        let Some(account) = accounts.get(index).filter(|account| account.owner == program_id)
+        else {
+            continue;
+        };
+        let repeat = accounts.iter().enumerate().position(|(other, other_account)| {
+            other != index && other_account.key == account.key
+        });
+        if let Some(repeat) = repeat {
+            msg!("Account {} repeats account {}", repeat, index);
+            return Err(AIInfraError::DuplicateAccount.into());
+        }
+    }
+    Ok(())
+}
+
+// Loads the agent at `agent_account`, which `owner_account` must own. The
+// owner signs, unless the agent's multisig signs for them: its account then
+// comes next, followed by at least its threshold of members as signers. For
+// an agent bound to an NFT, `owner_account` is instead the signing holder,
+// whose token account comes next, and who takes over as the owner.
+fn load_agent_as_owner<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    agent_account: &AccountInfo<'b>,
+    owner_account: &AccountInfo<'b>,
+    accounts_iter: &mut I,
+) -> Result<AIAgent, ProgramError> {
+    if agent_account.owner != program_id {
+        return Err(AIInfraError::AccountNotOwnedByProgram.into());
+    }
+
+    let mut agent = load_account::<AIAgent>(agent_account)?;
+    agent.assert_address(program_id, agent_account.key)?;
+    if let Some(mint) = agent.owner_nft_mint {
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let holder_token = next_account_info(accounts_iter)?;
+        assert_nft_holder(holder_token, &mint, owner_account.key)?;
+        agent.owner = *owner_account.key;
+        return Ok(agent);
+    }
+    if agent.owner != *owner_account.key {
+        return Err(AIInfraError::OwnerMismatch.into());
+    }
+
+    if !agent.multisig_owner {
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        return Ok(agent);
+    }
+    let multisig_account = next_account_info(accounts_iter)?;
+    let multisig = MultisigOwner::load(program_id, agent_account.key, multisig_account)?;
+    assert_multisig_approved(&multisig, accounts_iter)?;
+    Ok(agent)
+}
+
+// Checks at least the multisig's threshold of members sign among the rest of
+// the accounts
+fn assert_multisig_approved<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    multisig: &MultisigOwner,
+    accounts_iter: &mut I,
+) -> ProgramResult {
+    let signed: Vec<Pubkey> = accounts_iter
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
+    multisig.assert_approved(&signed)
+}
+
+// Writes fields of the agent's header in place once `signer_account` proves
+// it may, which spares handlers that change a single field a Borsh round
+// trip of the whole agent. With `owner_only`, an operator may not. The
+// holder of an NFT-bound agent passes their token account as `holder_token`.
+fn update_field(
+    program_id: &Pubkey,
+    agent_account: &AccountInfo,
+    signer_account: &AccountInfo,
+    holder_token: Option<&AccountInfo>,
+    owner_only: bool,
+    update: impl FnOnce(&mut zero_copy::AgentHeader),
+) -> ProgramResult {
+    if agent_account.owner != program_id {
+        return Err(AIInfraError::AccountNotOwnedByProgram.into());
+    }
+
+    if !signer_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut agent_data = agent_account.try_borrow_mut_data()?;
+    let agent = zero_copy::agent_mut(&mut agent_data)?;
+    agent.view().assert_address(program_id, agent_account.key)?;
+    let is_owner = is_view_owner(&agent.view(), signer_account.key, holder_token);
+    if owner_only && !is_owner {
+        return Err(AIInfraError::OwnerMismatch.into());
+    }
+    // A multisig's members can't sign here, so its owner's key can't either
+    if owner_only && agent.view().multisig_owner {
+        return Err(AIInfraError::AgentOwnedByMultisig.into());
+    }
+    if !is_owner && !agent.view().is_operated_by(signer_account.key) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    update(agent.header);
+    Ok(())
+}
+
+// Loads the requester account of the signing `owner_account`, creating it at
+// the owner's expense the first time
+fn load_or_create_requester<'a>(
+    program_id: &Pubkey,
+    requester_account: &AccountInfo<'a>,
+    owner_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> Result<Requester, ProgramError> {
+    if !requester_account.data_is_empty() {
+        if requester_account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let requester = load_account::<Requester>(requester_account)?;
+        requester.assert_address(program_id, requester_account.key)?;
+        if requester.owner != *owner_account.key {
+            return Err(AIInfraError::OwnerMismatch.into());
+        }
+        return Ok(requester);
+    }
+
+    let (requester_address, bump) = find_requester_address(owner_account.key, program_id);
+    if requester_address != *requester_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let rent = &Rent::get()?;
+    create_pda_account(
+        owner_account,
+        requester_account,
+        system_program,
+        program_id,
+        rent,
+        Requester::LEN,
+        &[pda::REQUESTER_SEED, owner_account.key.as_ref(), &[bump]],
+    )?;
+    assert_rent_exempt(rent, requester_account, Requester::LEN)?;
+
+    Ok(Requester {
+        account_type: AccountType::Requester,
+        version: CURRENT_VERSION,
+        owner: *owner_account.key,
+        credits: 0,
+        bump,
+        referrer: None,
+        templates_created: 0,
+        recurring_tasks_created: 0,
+        redundant_tasks_created: 0,
+        last_task_slot: 0,
+        tasks_in_window: 0,
+    })
+}
+
+// Splits the optional accounts that may follow task creation's own into the
+// payer's requester account and the assigned agent's allowlist. They can come
+// in either order, the allowlist being the one tagged as such.
+fn task_creation_tail<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+) -> (Option<&'a AccountInfo<'b>>, Option<&'a AccountInfo<'b>>) {
+    let is_allowlist = |account: &&AccountInfo| {
+        account.data.borrow().first() == Some(&(AccountType::Allowlist as u8))
+    };
+    let tail = &accounts[..accounts.len().min(2)];
+    (tail.iter().find(|account| !is_allowlist(account)), tail.iter().find(is_allowlist))
+}
+
+// Counts a task the signing `payer_account` requests against the config's
+// rate limit, in the window their requester account tracks. The account is
+// only needed while the limit applies to them, and is created at their
+// expense the first time.
+fn count_requested_task<'a>(
+    program_id: &Pubkey,
+    config: &Config,
+    requester_account: Option<&AccountInfo<'a>>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if !config.rate_limits(payer_account.key) {
+        return Ok(());
+    }
+    let requester_account = requester_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let mut requester =
+        load_or_create_requester(program_id, requester_account, payer_account, system_program)?;
+    config.count_task(
+        payer_account.key,
+        &mut requester.last_task_slot,
+        &mut requester.tasks_in_window,
+        Clock::get()?.slot,
+    )?;
+    save_account(requester_account, &requester)
+}
+
+// Loads the template at `template_account`, which must belong to the signing
+// `owner_account`
+fn load_owned_template(
+    program_id: &Pubkey,
+    template_account: &AccountInfo,
+    owner_account: &AccountInfo,
+) -> Result<TaskTemplate, ProgramError> {
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let template = TaskTemplate::load(program_id, template_account)?;
+    if template.requester != *owner_account.key {
+        return Err(AIInfraError::OwnerMismatch.into());
+    }
+    Ok(template)
+}
+
+// Loads the requester account at `requester_account`, which must belong to
+// the signing `owner_account`, and checks its credit vault. Returns the
+// requester with the vault's bump.
+fn load_requester(
+    program_id: &Pubkey,
+    requester_account: &AccountInfo,
+    owner_account: &AccountInfo,
+    credit_vault: &AccountInfo,
+) -> Result<(Requester, u8), ProgramError> {
+    if requester_account.owner != program_id {
+        return Err(AIInfraError::AccountNotOwnedByProgram.into());
+    }
+
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let requester = load_account::<Requester>(requester_account)?;
+    requester.assert_address(program_id, requester_account.key)?;
+    if requester.owner != *owner_account.key {
+        return Err(AIInfraError::OwnerMismatch.into());
+    }
+
+    let (credit_vault_address, credit_vault_bump) =
+        find_credit_vault_address(requester_account.key, program_id);
+    if credit_vault_address != *credit_vault.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok((requester, credit_vault_bump))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        account_infos, assert_data_eq, read_account, set_sysvar_stubs, AccountFixture,
+    };
+
+    fn agent_with(owner: Pubkey, program_id: &Pubkey) -> (Pubkey, AIAgent) {
+        let (address, bump) = find_agent_address(&owner, program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner,
+            registrar: owner,
+            reputation_score: 100,
+            is_active: true,
+            bump,
+            ..AIAgent::default()
+        };
+        (address, agent)
+    }
+
+    fn config_account(program_id: &Pubkey, treasury: Pubkey) -> AccountFixture {
+        let (config_key, config_bump) = find_config_address(program_id);
+        let config = Config {
+            account_type: AccountType::Config,
+            version: CURRENT_VERSION,
+            treasury,
+            bump: config_bump,
+            ..Config::default()
+        };
+        AccountFixture::program(config_key, program_id, &config)
+    }
+
+    // The accounts DepositCredits takes, in order, for an agent holding
+    // `credits`. WithdrawCredits and UpdateAgentStatus read a prefix of them.
+    fn credit_accounts(program_id: &Pubkey, credits: u64) -> Vec<AccountFixture> {
+        let owner = Pubkey::new_unique();
+        let (agent_key, mut agent) = agent_with(owner, program_id);
+        agent.compute_credits = credits;
+        let (credit_vault_key, _) = find_credit_vault_address(&agent_key, program_id);
+        let (registry_key, registry_bump) = find_registry_address(program_id);
+        let registry = Registry {
+            account_type: AccountType::Registry,
+            version: CURRENT_VERSION,
+            bump: registry_bump,
+            ..Registry::default()
+        };
+        vec![
+            AccountFixture::program(agent_key, program_id, &agent).writable(true),
+            AccountFixture::new(owner).signer(true).writable(true),
+            AccountFixture::new(credit_vault_key).writable(true),
+            AccountFixture::system_program(),
+            config_account(program_id, Pubkey::default()),
+            AccountFixture::program(registry_key, program_id, &registry).writable(true),
+        ]
+    }
+
+    #[test]
+    fn deposit_rejects_credit_overflow() {
+        set_sysvar_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = credit_accounts(&program_id, u64::MAX - 1);
+        let accounts = account_infos(&mut accounts);
+
+        assert_eq!(
+            Processor::process_deposit_credits(&program_id, &accounts, 2, None),
+            Err(AIInfraError::NumericalOverflow.into())
+        );
+        let stored = read_account::<AIAgent>(&accounts[0]);
+        assert_eq!(stored.compute_credits, u64::MAX - 1);
+
+        // The full balance can be withdrawn back down to zero
+        Processor::process_withdraw_credits(&program_id, &accounts, u64::MAX - 1, None).unwrap();
+        let stored = read_account::<AIAgent>(&accounts[0]);
+        assert_eq!(stored.compute_credits, 0);
+    }
+
+    #[test]
+    fn credit_and_status_updates_are_saved() {
+        set_sysvar_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = credit_accounts(&program_id, 10);
+        let accounts = account_infos(&mut accounts);
+
+        Processor::process_deposit_credits(&program_id, &accounts, 5, None).unwrap();
+        assert_eq!(read_account::<AIAgent>(&accounts[0]).compute_credits, 15);
+        assert_eq!(read_account::<Registry>(&accounts[5]).total_credits_deposited, 5);
+
+        Processor::process_withdraw_credits(&program_id, &accounts, 15, None).unwrap();
+        assert_eq!(read_account::<AIAgent>(&accounts[0]).compute_credits, 0);
+        assert_eq!(
+            Processor::process_withdraw_credits(&program_id, &accounts, 1, None),
+            Err(ProgramError::InsufficientFunds)
+        );
+
+        Processor::process_update_status(&program_id, &accounts, false).unwrap();
+        assert!(!read_account::<AIAgent>(&accounts[0]).is_active);
+        Processor::process_update_status(&program_id, &accounts, true).unwrap();
+        assert!(read_account::<AIAgent>(&accounts[0]).is_active);
+    }
+
+    #[test]
+    fn credit_cap_and_withdrawal_minimum_hold() {
+        set_sysvar_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = credit_accounts(&program_id, 90);
+        let config = Config {
+            max_agent_credits: 100,
+            min_withdraw_amount: 20,
+            ..read_account(&accounts[4].info())
+        };
+        accounts[4] = AccountFixture::program(accounts[4].key, &program_id, &config);
+        let accounts = account_infos(&mut accounts);
+
+        // Up to the cap exactly, and not a credit more
+        assert_eq!(
+            Processor::process_deposit_credits(&program_id, &accounts, 11, None),
+            Err(AIInfraError::CreditCapExceeded.into())
+        );
+        Processor::process_deposit_credits(&program_id, &accounts, 10, None).unwrap();
+        assert_eq!(read_account::<AIAgent>(&accounts[0]).compute_credits, 100);
+        assert_eq!(
+            Processor::process_deposit_credits(&program_id, &accounts, 1, None),
+            Err(AIInfraError::CreditCapExceeded.into())
+        );
+
+        assert_eq!(
+            Processor::process_withdraw_credits(&program_id, &accounts, 19, None),
+            Err(AIInfraError::WithdrawalTooSmall.into())
+        );
+        Processor::process_withdraw_credits(&program_id, &accounts, 90, None).unwrap();
+        assert_eq!(read_account::<AIAgent>(&accounts[0]).compute_credits, 10);
+
+        // Emptying the balance is allowed however little is left
+        Processor::process_withdraw_credits(&program_id, &accounts, 10, None).unwrap();
+        assert_eq!(read_account::<AIAgent>(&accounts[0]).compute_credits, 0);
+    }
+
+    #[test]
+    fn credit_and_status_updates_check_the_agent() {
+        set_sysvar_stubs();
+        type Handler = fn(&Pubkey, &[AccountInfo]) -> ProgramResult;
+        let handlers: [(&str, Handler); 3] = [
+            ("deposit", |program_id, accounts| {
+                Processor::process_deposit_credits(program_id, accounts, 5, None)
+            }),
+            ("withdraw", |program_id, accounts| {
+                Processor::process_withdraw_credits(program_id, accounts, 5, None)
+            }),
+            ("status", |program_id, accounts| {
+                Processor::process_update_status(program_id, accounts, false)
+            }),
+        ];
+        type Corruption = fn(&mut [AccountFixture]);
+        let cases: [(Corruption, ProgramError); 5] = [
+            (
+                |accounts| accounts[0].owner = Pubkey::new_unique(),
+                AIInfraError::AccountNotOwnedByProgram.into(),
+            ),
+            (|accounts| accounts[1].is_signer = false, ProgramError::MissingRequiredSignature),
+            (
+                |accounts| accounts[1].key = Pubkey::new_unique(),
+                AIInfraError::OwnerMismatch.into(),
+            ),
+            (
+                |accounts| accounts[0].data.truncate(AIAgent::LEN - 1),
+                AIInfraError::InvalidAccountSize.into(),
+            ),
+            (|accounts| accounts[0].data.clear(), AIInfraError::AccountNotInitialized.into()),
+        ];
+
+        for (name, handler) in handlers {
+            for (index, (corrupt, error)) in cases.iter().enumerate() {
+                let program_id = Pubkey::new_unique();
+                let mut accounts = credit_accounts(&program_id, 10);
+                corrupt(&mut accounts);
+                let agent_data = accounts[0].data.clone();
+                let accounts = account_infos(&mut accounts);
+
+                let result = handler(&program_id, &accounts);
+                assert_eq!(result.as_ref(), Err(error), "{} case {}", name, index);
+                assert_data_eq(&accounts[0], &agent_data);
+            }
+        }
+    }
+
+    #[test]
+    fn complete_task_rejects_tasks_completed_overflow() {
+        set_sysvar_stubs();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (agent_key, mut agent) = agent_with(owner, &program_id);
+        agent.tasks_completed = u32::MAX;
+        agent.tasks_created = 1;
+        agent.open_tasks = 1;
+
+        let (task_key, task_bump) = find_task_address(&agent_key, 0, &program_id);
+        let (vault_key, vault_bump) = find_vault_address(&task_key, &program_id);
+        let requester = Pubkey::new_unique();
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_key,
+            requirements: ComputeRequirements { single_step: true, ..Default::default() },
+            status: TaskStatus::InProgress,
+            bump: task_bump,
+            failure_code: 0,
+            requester,
+            vault_bump,
+            ..ComputeTask::default()
+        };
+        let treasury = Pubkey::new_unique();
+        let mut accounts = [
+            AccountFixture::program(task_key, &program_id, &task).writable(true),
+            AccountFixture::program(agent_key, &program_id, &agent).writable(true),
+            AccountFixture::new(owner).signer(true).writable(true),
+            AccountFixture::new(vault_key).writable(true),
+            AccountFixture::new(requester).writable(true),
+            AccountFixture::system_program(),
+            config_account(&program_id, treasury),
+            AccountFixture::new(treasury).writable(true),
+        ];
+        let accounts = account_infos(&mut accounts);
+
+        assert_eq!(
+            Processor::process_complete_task(&program_id, &accounts, task_key, [1; 32], None),
+            Err(AIInfraError::NumericalOverflow.into())
+        );
+
+        // Nothing was written before the overflow was detected
+        let stored = ComputeTask::unpack(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(stored.status, TaskStatus::InProgress);
+    }
+}
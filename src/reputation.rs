@@ -0,0 +1,72 @@
+// Highest reputation an agent can reach
+pub const MAX_REPUTATION: u32 = 10_000;
+
+// Score after completing a task paid `payment_amount`. The gain is `bonus`
+// for every significant bit of the payment, so it grows with the log of the
+// amount and a free task earns nothing.
+pub fn after_completion(score: u32, payment_amount: u64, bonus: u32) -> u32 {
+    let bits = u64::BITS - payment_amount.leading_zeros();
+    score.saturating_add(bonus.saturating_mul(bits)).min(MAX_REPUTATION)
+}
+
+// Reputation a single tip adds, and the most tips can add for any one task,
+// so reputation can't be bought by tipping a task over and over
+pub const TIP_REPUTATION_BONUS: u32 = 1;
+pub const MAX_TIP_REPUTATION_PER_TASK: u32 = 3;
+
+// Score after a tip on a task whose tips have already added `awarded`, and
+// the reputation this tip adds
+pub fn after_tip(score: u32, awarded: u32) -> (u32, u32) {
+    let gain = TIP_REPUTATION_BONUS.min(MAX_TIP_REPUTATION_PER_TASK.saturating_sub(awarded));
+    let new_score = score.saturating_add(gain).min(MAX_REPUTATION);
+    (new_score, new_score.saturating_sub(score))
+}
+
+// Score after failing a task, never dropping below zero
+pub fn after_failure(score: u32, penalty: u32) -> u32 {
+    score.saturating_sub(penalty).min(MAX_REPUTATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reputation_stays_within_bounds() {
+        // The gain scales with the number of significant bits in the payment
+        assert_eq!(after_completion(100, 0, 5), 100);
+        assert_eq!(after_completion(100, 1, 5), 105);
+        assert_eq!(after_completion(100, 1_000, 5), 150);
+        assert_eq!(after_completion(100, u64::MAX, 5), 420);
+
+        // Enormous payments and bonuses saturate at the cap
+        assert_eq!(after_completion(0, u64::MAX, u32::MAX), MAX_REPUTATION);
+        assert_eq!(after_completion(MAX_REPUTATION, 1, 1), MAX_REPUTATION);
+        assert_eq!(after_completion(u32::MAX, 0, 0), MAX_REPUTATION);
+
+        assert_eq!(after_failure(100, 10), 90);
+        assert_eq!(after_failure(5, 10), 0);
+        assert_eq!(after_failure(0, u32::MAX), 0);
+        assert_eq!(after_failure(MAX_REPUTATION, 0), MAX_REPUTATION);
+    }
+
+    #[test]
+    fn tip_reputation_is_capped_per_task() {
+        // Tips add a point each until the task's cap, then nothing
+        let mut score = 100;
+        let mut awarded = 0;
+        for _ in 0..MAX_TIP_REPUTATION_PER_TASK + 5 {
+            let (new_score, gain) = after_tip(score, awarded);
+            assert_eq!(new_score, score + gain);
+            score = new_score;
+            awarded += gain;
+        }
+        assert_eq!(score, 100 + MAX_TIP_REPUTATION_PER_TASK);
+        assert_eq!(awarded, MAX_TIP_REPUTATION_PER_TASK);
+        assert_eq!(after_tip(100, u32::MAX), (100, 0));
+
+        // The score itself stays within bounds, and only what it rose counts
+        assert_eq!(after_tip(MAX_REPUTATION, 0), (MAX_REPUTATION, 0));
+        assert_eq!(after_tip(u32::MAX, 0), (MAX_REPUTATION, 0));
+    }
+}
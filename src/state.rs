@@ -1,60 +1,3661 @@
+// Account layouts. Every account the program owns is defined here once,
+// with its Borsh-serialized size and the checks that load it.
+use std::io;
+
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use shank::ShankAccount;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{error::AIInfraError, pda};
+
+// Upper bound on the protocol fee, and on the referral share, in basis points
+pub const MAX_FEE_BPS: u16 = 1000;
+
+// Most tasks BatchCompleteTasks accepts, to stay within compute limits
+pub const MAX_BATCH_COMPLETIONS: usize = 16;
+
+// Most requesters an agent's allowlist can hold
+pub const MAX_ALLOWED_REQUESTERS: usize = 32;
+// Most requesters the config can exempt from its task rate limit
+pub const MAX_RATE_LIMIT_EXEMPT: usize = 8;
+// Most members an agent's multisig can hold
+pub const MAX_MULTISIG_SIGNERS: usize = 5;
+
+// Task keys each page of an agent's task index holds
+pub const TASKS_PER_PAGE: u64 = 32;
+
+// Most held payments an agent tracks separately. Later ones fold into the
+// newest entry.
+pub const MAX_PENDING_PAYMENTS: usize = 8;
+
+// Length of the days an agent's withdrawal limit counts over, and how long a
+// looser withdrawal policy waits before it takes effect
+pub const WITHDRAWAL_DAY_SECONDS: i64 = 86_400;
+pub const WITHDRAWAL_POLICY_DELAY_SECONDS: i64 = 86_400;
+
+// Most items a batch task can hold, one bit each in its claimed bitmap
+pub const MAX_BATCH_ITEMS: u32 = 4096;
+// How long after creating a batch its requester waits before they can take
+// back the payments of items still unclaimed
+pub const BATCH_REFUND_DELAY_SECONDS: i64 = 7 * 86_400;
+// Most shards SplitTask divides a task into, bounded by the accounts a
+// transaction can carry
+pub const MAX_TASK_SHARDS: usize = 8;
+// Most agents a redundant task can be run by
+pub const MAX_REPLICAS: usize = 5;
+// How long a redundant task waits for a quorum before it stops taking
+// results and can be settled without one
+pub const REDUNDANT_TASK_EXPIRY_SECONDS: i64 = 7 * 86_400;
+// Category codes a task carries
+pub const TASK_TAGS: usize = 8;
+// Bytes in a set of tag codes, one bit for each of the 256
+pub const TAG_SET_LEN: usize = 32;
+// Longest name, URI and service endpoint an agent's metadata holds, in bytes
+pub const MAX_METADATA_NAME_LEN: usize = 32;
+pub const MAX_METADATA_URI_LEN: usize = 200;
+pub const MAX_METADATA_ENDPOINT_LEN: usize = 200;
+
+// The IDL generator only reads literal array lengths, so the account structs
+// and instructions spell out these sizes
+const _: () = assert!(
+    MAX_PENDING_PAYMENTS == 8 && MAX_REPLICAS == 5 && TASK_TAGS == 8 && TAG_SET_LEN == 32
+);
+// Longest result payload a task stores inline, in bytes
+pub const MAX_RESULT_DATA_LEN: usize = 512;
+// Longest description CreateTask writes to a memo, in bytes
+pub const MAX_TASK_DESCRIPTION_LEN: usize = 512;
+
+// Largest resources a task can ask for. Configs can lower these ceilings,
+// never raise them.
+pub const MAX_REQUIREMENTS: ResourceLimits = ResourceLimits {
+    cpu_units: 4_096,
+    memory_mb: 4_194_304,
+    storage_mb: 104_857_600,
+    max_time_seconds: 31_536_000,
+    gpu_units: 1_024,
+    network_mbps: 400_000,
+};
+
+// Layout version stored after the type tag of every account. Version 1 is
+// the layouts from before accounts carried a version. Layouts only ever
+// append fields, and each change bumps this.
+pub const CURRENT_VERSION: u8 = 2;
+
+// Account type tag, stored as the first byte of every program account
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccountType {
+    #[default]
+    Uninitialized,
+    Agent,
+    Task,
+    Config,
+    Bid,
+    Requester,
+    Verifier,
+    Allowlist,
+    Allowance,
+    Registry,
+    TaskPage,
+    BatchTask,
+    TaskTemplate,
+    RecurringTask,
+    RedundantTask,
+    AgentMetadata,
+    InsurancePool,
+    MultisigOwner,
+}
+
+// Fails unless `data` is tagged as `expected`. Zeroed data means the account
+// was never initialized.
+fn check_account_type(data: &[u8], expected: AccountType) -> ProgramResult {
+    match data.first() {
+        Some(&tag) if tag == expected as u8 => Ok(()),
+        None => Err(AIInfraError::AccountNotInitialized.into()),
+        Some(&tag) if tag == AccountType::Uninitialized as u8 => {
+            Err(AIInfraError::AccountNotInitialized.into())
+        }
+        Some(_) => Err(AIInfraError::InvalidAccountType.into()),
+    }
+}
+
+// Byte offsets every account shares, for getProgramAccounts memcmp filters
+pub const ACCOUNT_TYPE_OFFSET: usize = 0;
+pub const VERSION_OFFSET: usize = 1;
+
+// Implemented by every account the program owns. Accounts are allocated at
+// exactly LEN bytes, the size of their largest layout, so an account without
+// its optional fields set leaves zero padding at the end.
+pub trait ProgramAccount: BorshSerialize + BorshDeserialize {
+    const LEN: usize;
+    // Size of the version 1 layout, which had no version byte. Later
+    // versions only grow, so no versioned account is this short.
+    const V1_LEN: usize;
+    const ACCOUNT_TYPE: AccountType;
+    // Bytes at the very end of the account that the Borsh layout skips, so
+    // they sit at a fixed offset however long the variable fields run. The
+    // trailer only grows at its end, which migrates an older one in place.
+    const TRAILER_LEN: usize = 0;
+    // Size the account may grow to. Types that grow keep the extra bytes
+    // past LEN, after the trailer, and the trailer hooks see them too.
+    const MAX_LEN: usize = Self::LEN;
+
+    // Deserializes the account, rejecting data tagged as any other type or
+    // written in another version's layout
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, Self::ACCOUNT_TYPE)?;
+        check_version::<Self>(data)?;
+        decode(data)
+    }
+
+    // Fills the fields kept in the trailer from `trailer`
+    fn read_trailer(&mut self, _trailer: &[u8]) {}
+
+    // Writes the fields kept in the trailer into `trailer`
+    fn write_trailer(&self, _trailer: &mut [u8]) {}
+
+    // Bytes this value needs past LEN
+    fn grown_len(&self) -> usize {
+        0
+    }
+}
+
+// Set of the tag codes in `tags`, as AIAgent::accepted_tags holds them.
+// Zero, which marks an unset tag, is left out.
+pub fn tag_set(tags: &[u8]) -> [u8; TAG_SET_LEN] {
+    let mut set = [0; TAG_SET_LEN];
+    for &tag in tags.iter().filter(|&&tag| tag != 0) {
+        set[tag as usize / 8] |= 1 << (tag % 8);
+    }
+    set
+}
+
+// Deserializes a `T` from `data`, reading its trailer too when `data` is
+// at least full length
+fn decode<T: ProgramAccount>(data: &[u8]) -> Result<T, ProgramError> {
+    let mut value = T::deserialize(&mut &data[..])?;
+    if data.len() >= T::LEN {
+        value.read_trailer(&data[T::LEN - T::TRAILER_LEN..]);
+    }
+    Ok(value)
+}
+
+// Layout version of the `T` in `data`, which must be tagged as one
+pub fn account_version<T: ProgramAccount>(data: &[u8]) -> u8 {
+    if data.len() <= T::V1_LEN {
+        1
+    } else {
+        data[1]
+    }
+}
+
+fn check_version<T: ProgramAccount>(data: &[u8]) -> ProgramResult {
+    if account_version::<T>(data) != CURRENT_VERSION {
+        return Err(AIInfraError::UnsupportedAccountVersion.into());
+    }
+    Ok(())
+}
+
+// Rewrites `data`, a `T` at any version up to the current one, in the
+// current layout. Fields the old layout lacked start out zeroed, which
+// decodes as their defaults. Returns None when `data` is already current.
+pub fn migrate_data<T: ProgramAccount>(data: &[u8]) -> Result<Option<Vec<u8>>, ProgramError> {
+    check_account_type(data, T::ACCOUNT_TYPE)?;
+    let fields = match account_version::<T>(data) {
+        1 => &data[1..],
+        CURRENT_VERSION if data.len() >= T::LEN => return Ok(None),
+        version if version > CURRENT_VERSION => {
+            return Err(AIInfraError::UnsupportedAccountVersion.into())
+        }
+        _ => &data[2..],
+    };
+    if fields.len() > T::LEN - 2 {
+        return Err(AIInfraError::InvalidAccountSize.into());
+    }
+
+    let mut migrated = vec![0; T::LEN];
+    migrated[0] = T::ACCOUNT_TYPE as u8;
+    migrated[1] = CURRENT_VERSION;
+    migrated[2..2 + fields.len()].copy_from_slice(fields);
+    T::deserialize(&mut &migrated[..])?;
+    Ok(Some(migrated))
+}
+
+// Reads a `T` from `account`, whose data must be T::LEN bytes, or up to
+// T::MAX_LEN for a type that grows, in the current version. An account of
+// another type fails as such, and one of another version as such, before its
+// size is checked.
+pub fn load_account<T: ProgramAccount>(account: &AccountInfo) -> Result<T, ProgramError> {
+    let data = account.data.borrow();
+    check_layout::<T>(&data)?;
+    decode(&data)
+}
+
+// Fails unless `data` holds a `T` that load_account would read
+pub(crate) fn check_layout<T: ProgramAccount>(data: &[u8]) -> ProgramResult {
+    check_account_type(data, T::ACCOUNT_TYPE)?;
+    check_version::<T>(data)?;
+    if data.len() < T::LEN || data.len() > T::MAX_LEN {
+        return Err(AIInfraError::InvalidAccountSize.into());
+    }
+    Ok(())
+}
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+// Writes `value` over `account`'s data, which must be T::LEN bytes plus
+// whatever `value` grew by. Whatever the layout doesn't use is zeroed.
+// `value` must be stamped with the current version.
+pub fn save_account<T: ProgramAccount>(account: &AccountInfo, value: &T) -> ProgramResult {
+    let mut data = account.try_borrow_mut_data()?;
+    let bytes = value.try_to_vec()?;
+    if data.len() != T::LEN + value.grown_len() || bytes.len() > T::LEN - T::TRAILER_LEN {
+        return Err(AIInfraError::InvalidAccountSize.into());
+    }
+    if bytes.get(1) != Some(&CURRENT_VERSION) {
+        return Err(AIInfraError::UnsupportedAccountVersion.into());
+    }
+    data[..bytes.len()].copy_from_slice(&bytes);
+    data[bytes.len()..].fill(0);
+    value.write_trailer(&mut data[T::LEN - T::TRAILER_LEN..]);
+    Ok(())
+}
+
+// AI Agent Account Structure
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AIAgent {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
     pub owner: Pubkey,
-    pub credits: u64,
     pub compute_credits: u64,
     pub reputation_score: u32,
     pub tasks_completed: u32,
     pub is_active: bool,
+    pub bump: u8,
+    pub tasks_created: u64,
+    pub open_tasks: u32,
+    // Wallet the agent PDA was derived from; unchanged by ownership transfers
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub registrar: Pubkey,
+    // Unix timestamp of the owner's last Heartbeat, or of registration
+    pub last_heartbeat: i64,
+    // Lamports held as collateral in the agent's stake vault
+    pub staked_lamports: u64,
+    // Stake the owner asked to withdraw, and when. Both are zero while no
+    // unstake is pending.
+    pub unstake_amount: u64,
+    pub unstake_requested_at: i64,
+    // Hot key the owner lets run the agent's tasks, but not move its funds
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub operator: Option<Pubkey>,
+    // Number of requesters on the agent's allowlist. Zero lets anyone create
+    // tasks for the agent.
+    pub allowed_requesters: u16,
+    // Tasks assigned to the agent that it hasn't finished yet, and the most
+    // the owner lets it take on at once. A zero cap means no limit.
+    pub active_tasks: u32,
+    pub max_concurrent_tasks: u32,
+    pub capabilities: AgentCapabilities,
+    // Outcomes besides tasks_completed, and the seconds completed tasks took
+    // from start to completion in total
+    pub tasks_failed: u32,
+    pub tasks_cancelled: u32,
+    pub total_completion_seconds: u64,
+    // Key of the agent's off-chain worker. When set, completions need an
+    // ed25519 signature by it over the task and result, see attestation.rs.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub attestation_key: Option<Pubkey>,
+    // Number of batch tasks created for the agent, which numbers their
+    // addresses
+    pub batches_created: u64,
+    // Lamports tipped to the agent's owner for completed tasks
+    pub tips_received: u64,
+    // Task payments sitting in the credit vault through the config's hold
+    // period, not yet part of compute_credits. pending_releases holds them by
+    // release time as a ring of pending_count entries from pending_head.
+    pub pending_payments: u64,
+    pub pending_releases: [PendingPayment; 8],
+    pub pending_head: u8,
+    pub pending_count: u8,
+    // Credit operations applied so far. Each DepositCredits, WithdrawCredits
+    // and TransferCredits takes the current value, which tells identical ones
+    // apart and lets clients retry without applying one twice.
+    pub operation_nonce: u64,
+    // Task categories the agent serves, one bit per tag code. All clear
+    // serves every category.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+    pub accepted_tags: [u8; 32],
+    // Slot of the last task funded through an allowance on the agent, and
+    // the number of them created in the config's rate limit window it fell in
+    pub last_task_slot: u64,
+    pub tasks_in_window: u32,
+    // Limits on moving credits out of the agent, and a looser policy the
+    // owner set that takes effect at pending_policy_at, zero while none is
+    pub withdrawal_policy: WithdrawalPolicy,
+    pub pending_withdrawal_policy: WithdrawalPolicy,
+    pub pending_policy_at: i64,
+    // Credits moved out during the day starting at withdrawal_day, and when
+    // the last of them were
+    pub withdrawn_today: u64,
+    pub withdrawal_day: i64,
+    pub last_withdrawal_at: i64,
+    // Epoch the agent registered in, and whether it has claimed the
+    // promotional grant new agents get
+    pub registered_at_epoch: u64,
+    pub grant_claimed: bool,
+    // Whether the agent's multisig signs for its owner in the instructions
+    // it can sign
+    pub multisig_owner: bool,
+    // Mint of the NFT whose holder controls the agent in place of its
+    // owner's key, if bound to one. The holder takes over as owner, where
+    // payments go, whenever they act for the agent.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub owner_nft_mint: Option<Pubkey>,
+    // Whether payments in wrapped SOL reach the owner unwrapped, as lamports
+    pub prefer_native: bool,
+}
+
+// Part of an agent's pending payments and when ReleasePayments may move it
+// into its credits
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PendingPayment {
+    pub amount: u64,
+    pub release_at: i64,
+}
+
+impl PendingPayment {
+    // Borsh-serialized size, embedded in AIAgent
+    pub const LEN: usize = 8 + 8;
+}
+
+// Limits an agent's owner puts on withdrawing its credits, so a stolen owner
+// key can't drain it at once. Zero leaves either limit off.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithdrawalPolicy {
+    pub max_per_day: u64,
+    pub cooldown_seconds: i64,
+}
+
+impl WithdrawalPolicy {
+    // Borsh-serialized size, embedded in AIAgent
+    pub const LEN: usize = 8 + 8;
+
+    // Whether the policy lets through anything `current` wouldn't
+    pub fn loosens(&self, current: &WithdrawalPolicy) -> bool {
+        let raises_limit = current.max_per_day != 0
+            && (self.max_per_day == 0 || self.max_per_day > current.max_per_day);
+        raises_limit || self.cooldown_seconds < current.cooldown_seconds
+    }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+// Compute Task Structure
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComputeTask {
-    pub owner: Pubkey,
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
     pub requirements: ComputeRequirements,
     pub status: TaskStatus,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
     pub result_hash: [u8; 32],
     pub payment_amount: u64,
+    pub index: u64,
+    pub bump: u8,
+    // failure_reason's code, kept where indexers read it at a fixed offset.
+    // Tasks that failed before reasons were typed hold the raw FailTask
+    // reason, or 255, 254 and 253 for expired, disputed and unverified.
+    pub failure_code: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub requester: Pubkey,
+    pub vault_bump: u8,
+    // None for tasks paid in lamports
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub payment_mint: Option<Pubkey>,
+    // Listed without an agent for any agent to claim. The address stays
+    // derived from the default pubkey and the config's counter after a claim.
+    pub open_listing: bool,
+    // Unix timestamps set when the task starts. A zero deadline means the
+    // task has no time limit.
+    pub started_at: i64,
+    pub deadline: i64,
+    // When the agent completed the task, if a deadline or dispute window
+    // needed the time
+    pub completed_at: i64,
+    // Set while a completed task's payment waits out the dispute window
+    pub payment_pending: bool,
+    // sha256(result_hash || salt) from CommitResult, until the reveal
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_hash"))]
+    pub commitment: Option<[u8; 32]>,
+    // Set once a registered verifier signs off on the result
+    pub verified: bool,
+    // Unix timestamp of the CreateTask that escrowed the payment
+    pub created_at: i64,
+    // Result the requester expects of a deterministic job. When set, only a
+    // completion with this exact result_hash settles the task.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_hash"))]
+    pub expected_result_hash: Option<[u8; 32]>,
+    // Reputation tips on the completed task have added to its agent, held
+    // under reputation::MAX_TIP_REPUTATION_PER_TASK
+    pub tip_reputation: u32,
+    // Task this one is a shard of, if SplitTask created it
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub parent: Option<Pubkey>,
+    // Position of a shard among its parent's, which orders their results
+    pub shard_index: u8,
+    // Number of shards a split task was divided into
+    pub shard_count: u8,
+    // Set on a failed or cancelled shard once ReissueShard replaces it
+    pub reissued: bool,
+    // Why the task failed, once it has
+    pub failure_reason: Option<FailureReason>,
+    // How urgently the requester wants the task run. Kept in the trailer,
+    // at the end of the account, for schedulers to filter on.
+    #[borsh_skip]
+    #[skip]
+    pub priority: u8,
+    // Application-defined category codes, zero where unset. Kept in the
+    // trailer after priority.
+    #[borsh_skip]
+    #[skip]
+    pub tags: [u8; 8],
+    // Unix timestamp the task failed at, which opens the requester's
+    // insurance claim window, and whether the requester has claimed. Kept in
+    // the trailer after tags.
+    #[borsh_skip]
+    #[skip]
+    pub failed_at: i64,
+    #[borsh_skip]
+    #[skip]
+    pub insurance_claimed: bool,
+    // Token program holding a token task's escrow. Kept in the trailer after
+    // insurance_claimed.
+    #[borsh_skip]
+    #[skip]
+    pub token_program: TokenProgram,
+    // US cents the requester priced the task in, converted to
+    // payment_amount at the oracle's price, or zero for a task priced in
+    // lamports or tokens. Kept in the trailer after token_program.
+    #[borsh_skip]
+    #[skip]
+    pub payment_usd_cents: u64,
+    // SHA-256 of the description the requester wrote to a memo when creating
+    // the task, or zero for none. Kept in the trailer after
+    // payment_usd_cents.
+    #[borsh_skip]
+    #[skip]
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+    pub description_hash: [u8; 32],
+    // Lamports of a failed task's escrow its vault holds back from the
+    // requester while an insurance claim on it can still be filed, zero once
+    // the claim or closing the task releases them. Kept in the trailer after
+    // description_hash.
+    #[borsh_skip]
+    #[skip]
+    pub held_refund: u64,
+    // Result payload the agent stored inline on completion, at most
+    // MAX_RESULT_DATA_LEN bytes. The account grows past LEN to hold it.
+    #[borsh_skip]
+    #[skip]
+    pub result_data: Vec<u8>,
+}
+
+// Global program configuration
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Config {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub admin: Pubkey,
+    // Protocol fee taken from each completed task's payment, in basis points
+    pub fee_bps: u16,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub treasury: Pubkey,
+    // Smallest lamport payment CreateTask accepts
+    pub min_payment: u64,
+    // Set by the admin to halt task and credit activity in an emergency
+    pub paused: bool,
+    // Number of open tasks listed so far, which numbers their addresses
+    pub open_tasks_created: u64,
+    // Share of the payment, in basis points, an agent forfeits by completing
+    // after the deadline. Zero rejects late completions outright.
+    pub late_decay_bps: u16,
+    // Seconds without a heartbeat after which an agent counts as stale. Zero
+    // disables staleness checks.
+    pub stale_after_seconds: i64,
+    // Lamports paid, out of the config's own balance, to whoever deactivates
+    // a stale agent
+    pub stale_bounty: u64,
+    // Reputation gained per significant bit of a completed task's payment
+    pub reputation_bonus: u32,
+    // Reputation lost each time one of an agent's tasks fails
+    pub reputation_penalty: u32,
+    // Share of an agent's stake, in basis points, sent to the treasury each
+    // time one of its tasks fails
+    pub slash_bps: u16,
+    // Seconds an agent must wait between requesting an unstake and
+    // collecting it
+    pub unstake_cooldown_seconds: i64,
+    // Seconds after completion during which the requester can dispute a
+    // result. Zero pays agents as soon as they complete.
+    pub dispute_window_seconds: i64,
+    // Key that rules on disputed tasks
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub arbitrator: Pubkey,
+    // Holds completed tasks' payments until a registered verifier signs off
+    pub require_verification: bool,
+    // Seconds after completion before an unverified task can be refunded.
+    // Zero leaves it waiting on the verifier indefinitely.
+    pub verification_timeout_seconds: i64,
+    // Unit prices, in lamports, that set the floor CreateTask holds lamport
+    // payments to. See pricing::compute_task_price.
+    pub price_per_cpu_unit_second: u64,
+    pub price_per_memory_mb: u64,
+    pub price_per_storage_mb: u64,
+    pub bump: u8,
+    // Share of each completed task's earned payment, in basis points, paid
+    // to the referrer registered for its requester
+    pub referral_bps: u16,
+    // Seconds a completed task's lamport payment sits in its agent's pending
+    // payments before ReleasePayments can credit it. Zero pays the owner's
+    // wallet directly.
+    pub payment_hold_seconds: i64,
+    // Lamports a recurring task's funding pays whoever cranks one of its runs
+    pub crank_reward: u64,
+    // Range each resource a task asks for must fall in
+    pub requirement_bounds: RequirementBounds,
+    // Most credits any one agent can hold. Zero leaves balances uncapped.
+    pub max_agent_credits: u64,
+    // Smallest credit withdrawal allowed, except one that empties the balance
+    pub min_withdraw_amount: u64,
+    // Highest task priority charged no more than the base price
+    pub priority_threshold: u8,
+    // Basis points each priority level above the threshold adds to the
+    // price multiplier. See pricing::priority_task_price.
+    pub priority_multiplier_bps: u16,
+    // Most tasks one requester may create in each window of window_slots
+    // slots, counted from slot zero. Zero leaves task creation unlimited.
+    pub max_tasks_per_window: u32,
+    pub window_slots: u64,
+    // Requesters the rate limit never applies to
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+    pub rate_limit_exempt: Vec<Pubkey>,
+    // Credits ClaimGrant gives each new agent out of the faucet vault. Zero
+    // turns grants off.
+    pub grant_amount: u64,
+    // Share of each completed lamport task's earned payment, in basis
+    // points, paid into the insurance pool
+    pub insurance_bps: u16,
+    // Most one FileInsuranceClaim pays out, and how long after its task
+    // failed the requester has to file it. Zero places no cap beyond the
+    // task's payment, and a zero window takes no claims.
+    pub max_insurance_claim: u64,
+    pub insurance_claim_window_seconds: i64,
+    // Price account CreateTask converts USD-priced payments to lamports
+    // with, its oldest acceptable price, and the widest confidence interval
+    // it takes, in basis points of the price. The default key prices nothing
+    // in USD, and a zero interval bound accepts any.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub oracle: Pubkey,
+    pub max_oracle_age_seconds: i64,
+    pub max_oracle_confidence_bps: u16,
+}
+
+// Admin-tunable settings, carried by InitializeConfig and UpdateConfig
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfigParams {
+    pub fee_bps: u16,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub treasury: Pubkey,
+    pub min_payment: u64,
+    pub late_decay_bps: u16,
+    pub stale_after_seconds: i64,
+    pub stale_bounty: u64,
+    pub reputation_bonus: u32,
+    pub reputation_penalty: u32,
+    pub slash_bps: u16,
+    pub unstake_cooldown_seconds: i64,
+    pub dispute_window_seconds: i64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub arbitrator: Pubkey,
+    pub require_verification: bool,
+    pub verification_timeout_seconds: i64,
+    pub price_per_cpu_unit_second: u64,
+    pub price_per_memory_mb: u64,
+    pub price_per_storage_mb: u64,
+    pub referral_bps: u16,
+    pub payment_hold_seconds: i64,
+    pub crank_reward: u64,
+    pub requirement_bounds: RequirementBounds,
+    pub max_agent_credits: u64,
+    pub min_withdraw_amount: u64,
+    pub priority_threshold: u8,
+    pub priority_multiplier_bps: u16,
+    pub max_tasks_per_window: u32,
+    pub window_slots: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+    pub rate_limit_exempt: Vec<Pubkey>,
+    pub insurance_bps: u16,
+    pub max_insurance_claim: u64,
+    pub insurance_claim_window_seconds: i64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub oracle: Pubkey,
+    pub max_oracle_age_seconds: i64,
+    pub max_oracle_confidence_bps: u16,
+}
+
+// An agent's offer to run an open task for `amount`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bid {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub task: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+// Prepaid lamport balance a wallet can fund tasks from. The lamports sit in
+// a credit vault derived from this account, as they do for agents.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Requester {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub owner: Pubkey,
+    pub credits: u64,
+    pub bump: u8,
+    // Wallet that takes the config's referral share of this requester's
+    // completed tasks. Set once and never changed.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_pubkey"))]
+    pub referrer: Option<Pubkey>,
+    // Number of task templates the requester has created, which numbers
+    // their addresses
+    pub templates_created: u64,
+    // Number of recurring tasks the requester has created, which numbers
+    // their addresses
+    pub recurring_tasks_created: u64,
+    // Number of redundant tasks the requester has created, which numbers
+    // their addresses
+    pub redundant_tasks_created: u64,
+    // Slot of the requester's last rate-limited task, and the number of
+    // them created in the config's window it fell in
+    pub last_task_slot: u64,
+    pub tasks_in_window: u32,
+}
+
+impl ProgramAccount for AIAgent {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // owner
+        8 +  // compute_credits
+        4 +  // reputation_score
+        4 +  // tasks_completed
+        1 +  // is_active
+        1 +  // bump
+        8 +  // tasks_created
+        4 +  // open_tasks
+        32 + // registrar
+        8 +  // last_heartbeat
+        8 +  // staked_lamports
+        8 +  // unstake_amount
+        8 +  // unstake_requested_at
+        1 + 32 + // operator
+        2 +  // allowed_requesters
+        4 +  // active_tasks
+        4 +  // max_concurrent_tasks
+        AgentCapabilities::LEN +
+        4 +  // tasks_failed
+        4 +  // tasks_cancelled
+        8 +  // total_completion_seconds
+        1 + 32 + // attestation_key
+        8 +  // batches_created
+        8 +  // tips_received
+        8 +  // pending_payments
+        PendingPayment::LEN * MAX_PENDING_PAYMENTS +
+        1 +  // pending_head
+        1 +  // pending_count
+        8 +  // operation_nonce
+        TAG_SET_LEN + // accepted_tags
+        8 +  // last_task_slot
+        4 +  // tasks_in_window
+        WithdrawalPolicy::LEN * 2 + // withdrawal_policy, pending_withdrawal_policy
+        8 +  // pending_policy_at
+        8 +  // withdrawn_today
+        8 +  // withdrawal_day
+        8 +  // last_withdrawal_at
+        8 +  // registered_at_epoch
+        1 +  // grant_claimed
+        1 +  // multisig_owner
+        1 + 32 + // owner_nft_mint
+        1;   // prefer_native
+    const V1_LEN: usize = 206;
+    const ACCOUNT_TYPE: AccountType = AccountType::Agent;
+}
+
+impl AIAgent {
+    // Offsets of fields indexers filter on. Later fields follow the optional
+    // operator, so their offsets vary.
+    pub const OWNER_OFFSET: usize = VERSION_OFFSET + 1;
+    pub const IS_ACTIVE_OFFSET: usize = Self::OWNER_OFFSET +
+        32 + // owner
+        8 +  // compute_credits
+        4 +  // reputation_score
+        4;   // tasks_completed
+    pub const REGISTRAR_OFFSET: usize = Self::IS_ACTIVE_OFFSET +
+        1 +  // is_active
+        1 +  // bump
+        8 +  // tasks_created
+        4;   // open_tasks
+
+    // Whether `key` may run the agent's tasks: its owner or its operator. The
+    // key an agent bound to an NFT stores goes stale once the NFT changes
+    // hands, so it doesn't count; the processor checks the holder instead.
+    pub fn is_operated_by(&self, key: &Pubkey) -> bool {
+        (self.owner_nft_mint.is_none() && self.owner == *key) || self.operator == Some(*key)
+    }
+
+    // Share of the agent's completed and failed tasks that it completed, in
+    // basis points. None until it has finished one either way.
+    pub fn success_rate_bps(&self) -> Option<u16> {
+        let finished = self.tasks_completed as u64 + self.tasks_failed as u64;
+        if finished == 0 {
+            return None;
+        }
+        Some((self.tasks_completed as u64 * 10_000 / finished) as u16)
+    }
+
+    // Whether the agent serves tasks tagged `tag`
+    pub fn accepts_tag(&self, tag: u8) -> bool {
+        self.accepted_tags == [0; TAG_SET_LEN]
+            || self.accepted_tags[tag as usize / 8] & (1 << (tag % 8)) != 0
+    }
+
+    // Numbers a credit operation with the current nonce, which must be
+    // `expected` when the client set one. Returns the nonce taken.
+    pub fn take_operation_nonce(&mut self, expected: Option<u64>) -> Result<u64, ProgramError> {
+        let nonce = self.operation_nonce;
+        if expected.is_some_and(|expected| expected != nonce) {
+            return Err(AIInfraError::NonceMismatch.into());
+        }
+        self.operation_nonce = nonce.checked_add(1).ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(nonce)
+    }
+
+    // Sets the agent's withdrawal policy at `now`. A stricter one applies at
+    // once and drops any pending change; a looser one waits out the delay.
+    // Returns when the policy takes effect.
+    pub fn set_withdrawal_policy(&mut self, policy: WithdrawalPolicy, now: i64) -> i64 {
+        self.apply_due_withdrawal_policy(now);
+        if policy.loosens(&self.withdrawal_policy) {
+            self.pending_withdrawal_policy = policy;
+            self.pending_policy_at = now.saturating_add(WITHDRAWAL_POLICY_DELAY_SECONDS);
+            return self.pending_policy_at;
+        }
+        self.withdrawal_policy = policy;
+        self.pending_withdrawal_policy = WithdrawalPolicy::default();
+        self.pending_policy_at = 0;
+        now
+    }
+
+    // Puts a pending withdrawal policy into effect once its delay has passed
+    pub fn apply_due_withdrawal_policy(&mut self, now: i64) {
+        if self.pending_policy_at != 0 && now >= self.pending_policy_at {
+            self.withdrawal_policy = self.pending_withdrawal_policy;
+            self.pending_withdrawal_policy = WithdrawalPolicy::default();
+            self.pending_policy_at = 0;
+        }
+    }
+
+    // Counts `amount` moved out of the agent's credits at `now` against its
+    // withdrawal policy
+    pub fn record_withdrawal(&mut self, amount: u64, now: i64) -> ProgramResult {
+        self.apply_due_withdrawal_policy(now);
+        let policy = self.withdrawal_policy;
+        if policy.cooldown_seconds != 0
+            && self.last_withdrawal_at != 0
+            && now < self.last_withdrawal_at.saturating_add(policy.cooldown_seconds)
+        {
+            return Err(AIInfraError::WithdrawalCooldownActive.into());
+        }
+        let day = now - now.rem_euclid(WITHDRAWAL_DAY_SECONDS);
+        if day != self.withdrawal_day {
+            self.withdrawal_day = day;
+            self.withdrawn_today = 0;
+        }
+        let withdrawn = self.withdrawn_today.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        if policy.max_per_day != 0 && withdrawn > policy.max_per_day {
+            return Err(AIInfraError::WithdrawalLimitExceeded.into());
+        }
+        self.withdrawn_today = withdrawn;
+        self.last_withdrawal_at = now;
+        Ok(())
+    }
+
+    // Fails for an agent bound to an NFT, whose holder acts for it rather
+    // than the owner's key
+    pub fn assert_not_nft_bound(&self) -> ProgramResult {
+        if self.owner_nft_mint.is_some() {
+            return Err(AIInfraError::AgentBoundToNft.into());
+        }
+        Ok(())
+    }
+
+    // Fails for an agent owned by a multisig, whose members must approve
+    // what the owner's key would otherwise do alone
+    pub fn assert_not_multisig_owned(&self) -> ProgramResult {
+        if self.multisig_owner {
+            return Err(AIInfraError::AgentOwnedByMultisig.into());
+        }
+        Ok(())
+    }
+
+    // Marks the agent's grant claimed in `epoch`. Only agents registered in
+    // that epoch or the one before can claim it, and only once.
+    pub fn claim_grant(&mut self, epoch: u64) -> ProgramResult {
+        if self.grant_claimed {
+            return Err(AIInfraError::GrantAlreadyClaimed.into());
+        }
+        if epoch.saturating_sub(self.registered_at_epoch) > 1 {
+            return Err(AIInfraError::GrantWindowClosed.into());
+        }
+        self.grant_claimed = true;
+        Ok(())
+    }
+
+    // Counts a newly assigned task against the agent's concurrency cap
+    pub fn take_task(&mut self) -> ProgramResult {
+        if self.max_concurrent_tasks != 0 && self.active_tasks >= self.max_concurrent_tasks {
+            return Err(AIInfraError::AgentAtCapacity.into());
+        }
+        self.active_tasks = self.active_tasks.checked_add(1)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(())
+    }
+
+    // Frees the slot of a task the agent completed, failed or gave up
+    pub fn release_task(&mut self) {
+        self.active_tasks = self.active_tasks.saturating_sub(1);
+    }
+
+    // Holds `amount` until `release_at`. With every entry taken it joins the
+    // newest one instead, which then waits for the later of the two times.
+    pub fn hold_payment(&mut self, amount: u64, release_at: i64) -> ProgramResult {
+        self.pending_payments = self.pending_payments.checked_add(amount)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        let count = self.pending_count as usize;
+        if count == MAX_PENDING_PAYMENTS {
+            let newest = &mut self.pending_releases[self.pending_slot(count - 1)];
+            newest.amount = newest.amount.checked_add(amount)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            newest.release_at = newest.release_at.max(release_at);
+            return Ok(());
+        }
+        let slot = self.pending_slot(count);
+        self.pending_releases[slot] = PendingPayment { amount, release_at };
+        self.pending_count += 1;
+        Ok(())
+    }
+
+    // Moves the held payments released by `now` into compute_credits, oldest
+    // first, stopping at the first that isn't. Returns the amount moved.
+    pub fn release_payments(&mut self, now: i64) -> Result<u64, ProgramError> {
+        let mut released: u64 = 0;
+        while self.pending_count > 0 {
+            let slot = self.pending_slot(0);
+            let entry = self.pending_releases[slot];
+            if entry.release_at > now {
+                break;
+            }
+            released = released.checked_add(entry.amount)
+                .ok_or(AIInfraError::NumericalOverflow)?;
+            self.pending_releases[slot] = PendingPayment::default();
+            self.pending_head = ((slot + 1) % MAX_PENDING_PAYMENTS) as u8;
+            self.pending_count -= 1;
+        }
+        self.pending_payments = self.pending_payments.checked_sub(released)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        self.compute_credits = self.compute_credits.checked_add(released)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(released)
+    }
+
+    // Index into pending_releases of the entry `offset` places after the head
+    fn pending_slot(&self, offset: usize) -> usize {
+        (self.pending_head as usize + offset) % MAX_PENDING_PAYMENTS
+    }
+
+    // Checks that the account holding this agent is the registrar's PDA
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        if pda::create_agent_address(&self.registrar, self.bump, program_id)? != *address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+impl ProgramAccount for ComputeTask {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        ComputeRequirements::LEN +
+        1 +  // status
+        32 + // result_hash
+        8 +  // payment_amount
+        8 +  // index
+        1 +  // bump
+        1 +  // failure_code
+        32 + // requester
+        1 +  // vault_bump
+        1 + 32 + // payment_mint
+        1 +  // open_listing
+        8 +  // started_at
+        8 +  // deadline
+        8 +  // completed_at
+        1 +  // payment_pending
+        1 + 32 + // commitment
+        1 +  // verified
+        8 +  // created_at
+        1 + 32 + // expected_result_hash
+        4 +  // tip_reputation
+        1 + 32 + // parent
+        1 +  // shard_index
+        1 +  // shard_count
+        1 +  // reissued
+        1 + FailureReason::LEN + // failure_reason
+        Self::TRAILER_LEN;
+    const V1_LEN: usize = 255;
+    const ACCOUNT_TYPE: AccountType = AccountType::Task;
+    const TRAILER_LEN: usize = 1 + // priority
+        TASK_TAGS + // tags
+        8 +  // failed_at
+        1 +  // insurance_claimed
+        1 +  // token_program
+        8 +  // payment_usd_cents
+        32 + // description_hash
+        8;   // held_refund
+    const MAX_LEN: usize = Self::LEN + MAX_RESULT_DATA_LEN;
+
+    fn read_trailer(&mut self, trailer: &[u8]) {
+        let (tags, rest) = trailer[1..].split_at(TASK_TAGS);
+        self.priority = trailer[0];
+        self.tags.copy_from_slice(tags);
+        self.failed_at = i64::from_le_bytes(rest[..8].try_into().unwrap());
+        self.insurance_claimed = rest[8] != 0;
+        self.token_program = TokenProgram::from_code(rest[9]);
+        self.payment_usd_cents = u64::from_le_bytes(rest[10..18].try_into().unwrap());
+        self.description_hash.copy_from_slice(&rest[18..50]);
+        self.held_refund = u64::from_le_bytes(rest[50..58].try_into().unwrap());
+        self.result_data = trailer[Self::TRAILER_LEN..].to_vec();
+    }
+
+    fn write_trailer(&self, trailer: &mut [u8]) {
+        let (priority, rest) = trailer.split_at_mut(1);
+        let (tags, rest) = rest.split_at_mut(TASK_TAGS);
+        priority[0] = self.priority;
+        tags.copy_from_slice(&self.tags);
+        rest[..8].copy_from_slice(&self.failed_at.to_le_bytes());
+        rest[8] = self.insurance_claimed as u8;
+        rest[9] = self.token_program as u8;
+        rest[10..18].copy_from_slice(&self.payment_usd_cents.to_le_bytes());
+        rest[18..50].copy_from_slice(&self.description_hash);
+        rest[50..58].copy_from_slice(&self.held_refund.to_le_bytes());
+        rest[58..].copy_from_slice(&self.result_data);
+    }
+
+    fn grown_len(&self) -> usize {
+        self.result_data.len()
+    }
+}
+
+impl ComputeTask {
+    // Offsets of fields indexers filter on. Later fields follow the optional
+    // payment mint, so their offsets vary, except for the trailer's.
+    pub const AGENT_OFFSET: usize = VERSION_OFFSET + 1;
+    pub const STATUS_OFFSET: usize = Self::AGENT_OFFSET +
+        32 + // agent
+        ComputeRequirements::LEN;
+    pub const REQUESTER_OFFSET: usize = Self::STATUS_OFFSET +
+        1 +  // status
+        32 + // result_hash
+        8 +  // payment_amount
+        8 +  // index
+        1 +  // bump
+        1;   // failure_code
+    pub const PRIORITY_OFFSET: usize = Self::LEN - Self::TRAILER_LEN;
+    pub const TAGS_OFFSET: usize = Self::PRIORITY_OFFSET + 1;
+
+    // Checks that the account holding this task is the PDA for its agent and index
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        let seed_agent = if self.open_listing { Pubkey::default() } else { self.agent };
+        if pda::create_task_address(&seed_agent, self.index, self.bump, program_id)? != *address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    // Moves the task to `next`, if its current status allows it
+    pub fn transition(&mut self, next: TaskStatus) -> ProgramResult {
+        if !self.status.can_transition_to(next) {
+            return Err(AIInfraError::InvalidStatusTransition.into());
+        }
+        self.status = next;
+        Ok(())
+    }
+
+    // Moves the task to InProgress and starts its clock. Tasks without a
+    // max_time_seconds never expire.
+    pub fn start(&mut self, now: i64) -> ProgramResult {
+        self.transition(TaskStatus::InProgress)?;
+        self.started_at = now;
+        self.deadline = match self.requirements.max_time_seconds {
+            0 => 0,
+            seconds => now.checked_add(seconds as i64)
+                .ok_or(AIInfraError::NumericalOverflow)?,
+        };
+        Ok(())
+    }
+
+    // Fails unless `agent` has the reputation, stake and resources the
+    // requester asked for
+    pub fn assert_qualified(&self, agent: &AIAgent) -> ProgramResult {
+        self.requirements.assert_met_by(agent)
+    }
+
+    // Fails unless `agent` serves every category the task is tagged with
+    pub fn assert_tags_accepted(&self, agent: &AIAgent) -> ProgramResult {
+        if self.tags.iter().any(|&tag| tag != 0 && !agent.accepts_tag(tag)) {
+            return Err(AIInfraError::TagMismatch.into());
+        }
+        Ok(())
+    }
+
+    // Whether a running task has overrun its deadline at `now`
+    pub fn is_past_deadline(&self, now: i64) -> bool {
+        self.deadline != 0 && now > self.deadline
+    }
+
+    // Checks that `vault` is the escrow PDA of the task stored at `address`
+    pub fn assert_vault(
+        &self,
+        program_id: &Pubkey,
+        address: &Pubkey,
+        vault: &Pubkey,
+    ) -> ProgramResult {
+        if pda::create_vault_address(address, self.vault_bump, program_id)? != *vault {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+impl ProgramAccount for Config {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // admin
+        2 +  // fee_bps
+        32 + // treasury
+        8 +  // min_payment
+        1 +  // paused
+        8 +  // open_tasks_created
+        2 +  // late_decay_bps
+        8 +  // stale_after_seconds
+        8 +  // stale_bounty
+        4 +  // reputation_bonus
+        4 +  // reputation_penalty
+        2 +  // slash_bps
+        8 +  // unstake_cooldown_seconds
+        8 +  // dispute_window_seconds
+        32 + // arbitrator
+        1 +  // require_verification
+        8 +  // verification_timeout_seconds
+        8 +  // price_per_cpu_unit_second
+        8 +  // price_per_memory_mb
+        8 +  // price_per_storage_mb
+        1 +  // bump
+        2 +  // referral_bps
+        8 +  // payment_hold_seconds
+        8 +  // crank_reward
+        RequirementBounds::LEN +
+        8 +  // max_agent_credits
+        8 +  // min_withdraw_amount
+        1 +  // priority_threshold
+        2 +  // priority_multiplier_bps
+        4 +  // max_tasks_per_window
+        8 +  // window_slots
+        4 + 32 * MAX_RATE_LIMIT_EXEMPT + // rate_limit_exempt
+        8 +  // grant_amount
+        2 +  // insurance_bps
+        8 +  // max_insurance_claim
+        8 +  // insurance_claim_window_seconds
+        32 + // oracle
+        8 +  // max_oracle_age_seconds
+        2;   // max_oracle_confidence_bps
+    const V1_LEN: usize = 194;
+    const ACCOUNT_TYPE: AccountType = AccountType::Config;
+}
+
+impl Config {
+    // Reads the config from `account`, which must be the program's config PDA
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let config = load_account::<Self>(account)?;
+        if pda::create_config_address(config.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(config)
+    }
+
+    // Validates and stores the admin-tunable settings
+    pub fn apply(&mut self, params: ConfigParams) -> ProgramResult {
+        if params.fee_bps > MAX_FEE_BPS
+            || params.referral_bps > MAX_FEE_BPS
+            || params.insurance_bps > MAX_FEE_BPS
+        {
+            return Err(AIInfraError::FeeTooHigh.into());
+        }
+        if params.late_decay_bps > 10_000
+            || params.slash_bps > 10_000
+            || params.stale_after_seconds < 0
+            || params.unstake_cooldown_seconds < 0
+            || params.dispute_window_seconds < 0
+            || params.verification_timeout_seconds < 0
+            || params.payment_hold_seconds < 0
+            || !params.requirement_bounds.is_valid()
+            || (params.max_tasks_per_window != 0 && params.window_slots == 0)
+            || params.rate_limit_exempt.len() > MAX_RATE_LIMIT_EXEMPT
+            || params.insurance_claim_window_seconds < 0
+            || params.max_oracle_age_seconds < 0
+            || params.max_oracle_confidence_bps > 10_000
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.fee_bps = params.fee_bps;
+        self.treasury = params.treasury;
+        self.min_payment = params.min_payment;
+        self.late_decay_bps = params.late_decay_bps;
+        self.stale_after_seconds = params.stale_after_seconds;
+        self.stale_bounty = params.stale_bounty;
+        self.reputation_bonus = params.reputation_bonus;
+        self.reputation_penalty = params.reputation_penalty;
+        self.slash_bps = params.slash_bps;
+        self.unstake_cooldown_seconds = params.unstake_cooldown_seconds;
+        self.dispute_window_seconds = params.dispute_window_seconds;
+        self.arbitrator = params.arbitrator;
+        self.require_verification = params.require_verification;
+        self.verification_timeout_seconds = params.verification_timeout_seconds;
+        self.price_per_cpu_unit_second = params.price_per_cpu_unit_second;
+        self.price_per_memory_mb = params.price_per_memory_mb;
+        self.price_per_storage_mb = params.price_per_storage_mb;
+        self.referral_bps = params.referral_bps;
+        self.payment_hold_seconds = params.payment_hold_seconds;
+        self.crank_reward = params.crank_reward;
+        self.requirement_bounds = params.requirement_bounds;
+        self.max_agent_credits = params.max_agent_credits;
+        self.min_withdraw_amount = params.min_withdraw_amount;
+        self.priority_threshold = params.priority_threshold;
+        self.priority_multiplier_bps = params.priority_multiplier_bps;
+        self.max_tasks_per_window = params.max_tasks_per_window;
+        self.window_slots = params.window_slots;
+        self.rate_limit_exempt = params.rate_limit_exempt;
+        self.insurance_bps = params.insurance_bps;
+        self.max_insurance_claim = params.max_insurance_claim;
+        self.insurance_claim_window_seconds = params.insurance_claim_window_seconds;
+        self.oracle = params.oracle;
+        self.max_oracle_age_seconds = params.max_oracle_age_seconds;
+        self.max_oracle_confidence_bps = params.max_oracle_confidence_bps;
+        Ok(())
+    }
+
+    // Whether the rate limit applies to tasks `requester` creates
+    pub fn rate_limits(&self, requester: &Pubkey) -> bool {
+        self.max_tasks_per_window != 0 && !self.rate_limit_exempt.contains(requester)
+    }
+
+    // Counts a task `requester` creates at `slot` against the rate limit,
+    // in the window that `last_task_slot` and `tasks_in_window`, held by the
+    // account funding it, track. The count starts over with the first task
+    // in each new window.
+    pub fn count_task(
+        &self,
+        requester: &Pubkey,
+        last_task_slot: &mut u64,
+        tasks_in_window: &mut u32,
+        slot: u64,
+    ) -> ProgramResult {
+        if !self.rate_limits(requester) {
+            return Ok(());
+        }
+        if slot / self.window_slots != *last_task_slot / self.window_slots {
+            *tasks_in_window = 0;
+        }
+        if *tasks_in_window >= self.max_tasks_per_window {
+            return Err(AIInfraError::RateLimited.into());
+        }
+        *tasks_in_window += 1;
+        *last_task_slot = slot;
+        Ok(())
+    }
+
+    // Fails if an agent would end up holding `credits`, over the cap
+    pub fn assert_within_credit_cap(&self, credits: u64) -> ProgramResult {
+        if self.max_agent_credits != 0 && credits > self.max_agent_credits {
+            return Err(AIInfraError::CreditCapExceeded.into());
+        }
+        Ok(())
+    }
+
+    // Fails for a withdrawal of `amount` under the minimum that leaves part
+    // of `balance` behind
+    pub fn assert_withdrawable(&self, amount: u64, balance: u64) -> ProgramResult {
+        if amount < self.min_withdraw_amount && amount != balance {
+            return Err(AIInfraError::WithdrawalTooSmall.into());
+        }
+        Ok(())
+    }
+
+    // Fails while the admin has the program paused
+    pub fn assert_not_paused(&self) -> ProgramResult {
+        if self.paused {
+            return Err(AIInfraError::ProgramPaused.into());
+        }
+        Ok(())
+    }
+
+    // Protocol fee owed on `amount`. Rounds down, so the agent keeps any
+    // fraction of a unit.
+    pub fn fee_for(&self, amount: u64) -> Result<u64, ProgramError> {
+        bps_of(amount, self.fee_bps)
+    }
+
+    // Referrer's share of `amount`, rounded down like the fee, so the agent
+    // keeps any fraction of a unit
+    pub fn referral_for(&self, amount: u64) -> Result<u64, ProgramError> {
+        bps_of(amount, self.referral_bps)
+    }
+
+    // Insurance pool's share of `amount`, rounded down like the fee
+    pub fn insurance_for(&self, amount: u64) -> Result<u64, ProgramError> {
+        bps_of(amount, self.insurance_bps)
+    }
+
+    // Whether failing `task` for `reason` holds its refund back in the vault
+    // while the config opens a claim window, for an insurance claim to settle
+    // instead. Token tasks and a requester's own cancellation aren't insured.
+    pub fn holds_refund(&self, task: &ComputeTask, reason: FailureReason) -> bool {
+        self.insurance_claim_window_seconds != 0
+            && task.payment_mint.is_none()
+            && reason != FailureReason::RequesterCancelledAfterStart
+    }
+
+    // What a claim on `task`, failed and filed at `now`, pays out of a pool
+    // with `available` lamports to spare: the task's payment, capped by the
+    // config's claim maximum and what the pool holds. Only a task whose
+    // refund is still held can claim, as the claim settles that refund.
+    pub fn insurance_payout(
+        &self,
+        task: &ComputeTask,
+        now: i64,
+        available: u64,
+    ) -> Result<u64, ProgramError> {
+        if task.insurance_claimed {
+            return Err(AIInfraError::InsuranceAlreadyClaimed.into());
+        }
+        if self.insurance_claim_window_seconds == 0
+            || task.failed_at == 0
+            || now > task.failed_at.saturating_add(self.insurance_claim_window_seconds)
+        {
+            return Err(AIInfraError::InsuranceClaimWindowClosed.into());
+        }
+        if task.held_refund == 0 {
+            return Err(AIInfraError::TaskNotInsured.into());
+        }
+        let mut payout = task.payment_amount.min(available);
+        if self.max_insurance_claim != 0 {
+            payout = payout.min(self.max_insurance_claim);
+        }
+        if payout == 0 {
+            return Err(AIInfraError::InsurancePoolEmpty.into());
+        }
+        Ok(payout)
+    }
+
+    // Whether `agent` has gone longer than the stale threshold without a
+    // heartbeat at `now`
+    pub fn is_stale(&self, agent: &AIAgent, now: i64) -> bool {
+        self.stale_after_seconds != 0
+            && now.saturating_sub(agent.last_heartbeat) > self.stale_after_seconds
+    }
+
+    // Whether completed tasks' payments wait for a dispute window or a
+    // verifier instead of being paid on completion
+    pub fn holds_payment(&self) -> bool {
+        self.dispute_window_seconds != 0 || self.require_verification
+    }
+
+    // Whether the requester's chance to dispute `task` has passed at `now`.
+    // Without a window there is never a chance.
+    pub fn dispute_window_closed(
+        &self,
+        task: &ComputeTask,
+        now: i64,
+    ) -> Result<bool, ProgramError> {
+        if self.dispute_window_seconds == 0 {
+            return Ok(true);
+        }
+        let closes_at = task.completed_at.checked_add(self.dispute_window_seconds)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(now > closes_at)
+    }
+
+    // Whether `task` has waited out the verification timeout at `now`
+    pub fn verification_timed_out(
+        &self,
+        task: &ComputeTask,
+        now: i64,
+    ) -> Result<bool, ProgramError> {
+        if self.verification_timeout_seconds == 0 {
+            return Ok(false);
+        }
+        let times_out_at = task.completed_at.checked_add(self.verification_timeout_seconds)
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(now > times_out_at)
+    }
+
+    // Part of a late task's payment the agent forfeits, rounded down
+    pub fn late_forfeit_for(&self, amount: u64) -> Result<u64, ProgramError> {
+        bps_of(amount, self.late_decay_bps)
+    }
+
+    // Part of `stake` slashed when a task fails, rounded down
+    pub fn slash_for(&self, stake: u64) -> Result<u64, ProgramError> {
+        bps_of(stake, self.slash_bps)
+    }
+}
+
+// Divides a payment of `amount` into shards of `shares` basis points each.
+// The shares must be nonzero and sum to 10_000, and the last shard takes
+// the rounding remainder so the shards add up to `amount` exactly.
+pub fn split_payment(amount: u64, shares: &[u16]) -> Result<Vec<u64>, ProgramError> {
+    if !(2..=MAX_TASK_SHARDS).contains(&shares.len())
+        || shares.contains(&0)
+        || shares.iter().map(|&share| share as u32).sum::<u32>() != 10_000
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut payments = shares[..shares.len() - 1]
+        .iter()
+        .map(|&share| bps_of(amount, share))
+        .collect::<Result<Vec<_>, _>>()?;
+    let assigned: u64 = payments.iter().sum();
+    payments.push(amount - assigned);
+    Ok(payments)
+}
+
+// `bps` basis points of `amount`, rounded down
+fn bps_of(amount: u64, bps: u16) -> Result<u64, ProgramError> {
+    let share = amount as u128 * bps as u128 / 10_000;
+    u64::try_from(share).map_err(|_| AIInfraError::NumericalOverflow.into())
+}
+
+impl ProgramAccount for Bid {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // task
+        32 + // agent
+        8 +  // amount
+        1;   // bump
+    const V1_LEN: usize = 74;
+    const ACCOUNT_TYPE: AccountType = AccountType::Bid;
+}
+
+impl Bid {
+    // Offsets of fields indexers filter on
+    pub const TASK_OFFSET: usize = VERSION_OFFSET + 1;
+    pub const AGENT_OFFSET: usize = Self::TASK_OFFSET + 32;
+
+    // Checks that the account holding this bid is the PDA for its task and agent
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        if pda::create_bid_address(&self.task, &self.agent, self.bump, program_id)? != *address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+impl ProgramAccount for Requester {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // owner
+        8 +  // credits
+        1 +  // bump
+        1 + 32 + // referrer
+        8 +  // templates_created
+        8 +  // recurring_tasks_created
+        8 +  // redundant_tasks_created
+        8 +  // last_task_slot
+        4;   // tasks_in_window
+    const V1_LEN: usize = 42;
+    const ACCOUNT_TYPE: AccountType = AccountType::Requester;
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+impl Requester {
+    // Checks that the account holding this requester is its owner's PDA
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        if pda::create_requester_address(&self.owner, self.bump, program_id)? != *address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+// Requesters an agent accepts tasks from, kept in a PDA derived from the
+// agent
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgentAllowlist {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub bump: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+    pub requesters: Vec<Pubkey>,
+}
+
+// Keys that sign for an agent's owner once the agent's multisig_owner is
+// set, any `threshold` of them together, kept in a PDA derived from the agent
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultisigOwner {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub bump: u8,
+    pub threshold: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+    pub signers: Vec<Pubkey>,
+}
+
+// Credits a spender may still draw from an agent to fund tasks, kept in a
+// PDA derived from the agent and the spender
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Allowance {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub spender: Pubkey,
+    // Remaining credits the spender may draw
+    pub amount: u64,
+    pub bump: u8,
+}
+
+// A third party the admin registered to sign off on task results
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Verifier {
+    pub account_type: AccountType,
+    pub version: u8,
+    // Key that signs VerifyResult
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub authority: Pubkey,
+    pub bump: u8,
+}
+
+// Program-wide running totals, kept in a single PDA
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Registry {
+    pub account_type: AccountType,
+    pub version: u8,
+    // Agents ever registered; deregistering doesn't count down
+    pub total_agents: u64,
+    pub total_tasks: u64,
+    // Lamports ever deposited as credits; withdrawals aren't subtracted
+    pub total_credits_deposited: u64,
+    pub bump: u8,
+}
+
+// One page of the index listing an agent's tasks in creation order. Task
+// `index` is entry `index % TASKS_PER_PAGE` of page `index / TASKS_PER_PAGE`,
+// and a page only exists once its first task does. Open listings are indexed
+// under the default pubkey.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskPage {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub page: u64,
+    pub bump: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey_vec"))]
+    pub tasks: Vec<Pubkey>,
+}
+
+// Many small tasks for one agent, paid per item and settled together. The
+// agent submits a Merkle root over every item's result, then claims each
+// item's payment by proving its result against the root.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatchTask {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub requester: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub task_count: u32,
+    pub payment_per_task: u64,
+    // Root of the Merkle tree over the items' results, once submitted
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_hash"))]
+    pub merkle_root: Option<[u8; 32]>,
+    pub items_claimed: u32,
+    // Bit `i % 8` of byte `i / 8` is set once item `i` has been claimed
+    pub claimed: Vec<u8>,
+    pub created_at: i64,
+}
+
+// Requirements and a payment a requester stores once and creates any number
+// of identical tasks from with CreateTaskFromTemplate
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskTemplate {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub requester: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub requirements: ComputeRequirements,
+    // Payment tasks escrow unless their creation overrides it
+    pub default_payment: u64,
+}
+
+// Standing order CrankRecurringTask turns into a task every interval_seconds
+// for as long as its funding lasts. The funding is held as lamports in this
+// account on top of its rent.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecurringTask {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub requester: Pubkey,
+    // Agent each run is assigned to, or the default pubkey to list each run
+    // as an open task
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub requirements: ComputeRequirements,
+    pub payment_per_run: u64,
+    pub interval_seconds: i64,
+    // Earliest time the next run can be cranked
+    pub next_run_at: i64,
+    // Lamports left to pay for runs
+    pub funding_balance: u64,
+    // Number of runs cranked so far
+    pub runs: u64,
+}
+
+// One agent's result for a redundant task
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplicaResult {
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::hash"))]
+    pub result_hash: [u8; 32],
+}
+
+// The same job run by up to `replicas` distinct agents, each paid
+// payment_per_replica if at least `quorum` of them agree on the result.
+// Payments are escrowed in a vault derived from this account.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RedundantTask {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub requester: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub requirements: ComputeRequirements,
+    pub payment_per_replica: u64,
+    pub replicas: u8,
+    pub quorum: u8,
+    // The first submission_count entries hold the results submitted so far
+    pub submission_count: u8,
+    pub submissions: [ReplicaResult; 5],
+    pub finalized: bool,
+    // Result the quorum agreed on, if it did, once finalized
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_hash"))]
+    pub agreed_hash: Option<[u8; 32]>,
+    pub created_at: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComputeRequirements {
-    pub cpu_cores: u32,
+    pub cpu_units: u32,
     pub memory_mb: u32,
     pub storage_mb: u32,
     pub max_time_seconds: u32,
+    // Lowest reputation_score an agent needs to run the task; zero admits any
+    pub min_reputation: u32,
+    // Lowest staked_lamports an agent needs to be assigned or run the task
+    pub min_stake: u64,
+    // Opts out of commit-reveal, letting the agent finish with CompleteTask
+    // alone instead of CommitResult and RevealResult
+    pub single_step: bool,
+    pub gpu_units: u32,
+    pub network_mbps: u32,
+}
+
+// Amount of each resource a task can ask for, as one side of a range
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceLimits {
+    pub cpu_units: u32,
+    pub memory_mb: u32,
+    pub storage_mb: u32,
+    pub max_time_seconds: u32,
+    pub gpu_units: u32,
+    pub network_mbps: u32,
+}
+
+// Floors and ceilings the config holds task requirements to, inclusive. A
+// zero ceiling leaves the resource capped by MAX_REQUIREMENTS alone.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequirementBounds {
+    pub min: ResourceLimits,
+    pub max: ResourceLimits,
+}
+
+// Resources an agent's owner declares it has. Agents that never declared
+// any, which is all of them zeroed, are taken to meet every requirement.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgentCapabilities {
+    pub cpu_units: u32,
+    pub memory_mb: u32,
+    pub storage_mb: u32,
+    pub gpu_units: u32,
+    pub network_mbps: u32,
+}
+
+impl ProgramAccount for AgentAllowlist {
+    // Size of the account with a full list
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        1 +  // bump
+        4 + 32 * MAX_ALLOWED_REQUESTERS; // requesters
+    const V1_LEN: usize = 1062;
+    const ACCOUNT_TYPE: AccountType = AccountType::Allowlist;
+}
+
+impl AgentAllowlist {
+    // Reads the allowlist of the agent at `agent_key` from `account`
+    pub fn load(
+        program_id: &Pubkey,
+        agent_key: &Pubkey,
+        account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let allowlist = load_account::<Self>(account)?;
+        if pda::create_allowlist_address(agent_key, allowlist.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(allowlist)
+    }
+}
+
+impl ProgramAccount for MultisigOwner {
+    // Size of the account with a full key set
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        1 +  // bump
+        1 +  // threshold
+        4 + 32 * MAX_MULTISIG_SIGNERS; // signers
+    // Multisigs were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::MultisigOwner;
+}
+
+impl MultisigOwner {
+    // Reads the multisig of the agent at `agent_key` from `account`
+    pub fn load(
+        program_id: &Pubkey,
+        agent_key: &Pubkey,
+        account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let multisig = load_account::<Self>(account)?;
+        if pda::create_multisig_address(agent_key, multisig.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(multisig)
+    }
+
+    // Checks `signers` are distinct, fit the account, and number at least
+    // `threshold`, which must be at least one
+    pub fn validate(signers: &[Pubkey], threshold: u8) -> ProgramResult {
+        let distinct = signers.iter().enumerate()
+            .all(|(index, signer)| !signers[..index].contains(signer));
+        if !distinct
+            || signers.len() > MAX_MULTISIG_SIGNERS
+            || threshold == 0
+            || usize::from(threshold) > signers.len()
+        {
+            return Err(AIInfraError::InvalidMultisig.into());
+        }
+        Ok(())
+    }
+
+    // Fails unless at least the threshold of members are among `signed`.
+    // Members are distinct, so a key signing twice still counts once.
+    pub fn assert_approved(&self, signed: &[Pubkey]) -> ProgramResult {
+        let approvals = self.signers.iter().filter(|member| signed.contains(member)).count();
+        if approvals < usize::from(self.threshold) {
+            return Err(AIInfraError::MultisigThresholdNotMet.into());
+        }
+        Ok(())
+    }
+}
+
+impl ProgramAccount for Verifier {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // authority
+        1;   // bump
+    const V1_LEN: usize = 34;
+    const ACCOUNT_TYPE: AccountType = AccountType::Verifier;
+}
+
+impl Verifier {
+    // Checks that the account holding this verifier is its authority's PDA
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        if pda::create_verifier_address(&self.authority, self.bump, program_id)? != *address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+impl ProgramAccount for Allowance {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        32 + // spender
+        8 +  // amount
+        1;   // bump
+    const V1_LEN: usize = 74;
+    const ACCOUNT_TYPE: AccountType = AccountType::Allowance;
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+impl Allowance {
+    // Reads the allowance `spender` holds against the agent at `agent_key`
+    pub fn load(
+        program_id: &Pubkey,
+        agent_key: &Pubkey,
+        spender: &Pubkey,
+        account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let allowance = load_account::<Self>(account)?;
+        if allowance.agent != *agent_key || allowance.spender != *spender {
+            return Err(AIInfraError::AllowanceMismatch.into());
+        }
+        if pda::create_allowance_address(agent_key, spender, allowance.bump, program_id)?
+            != *account.key
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(allowance)
+    }
+}
+
+impl ProgramAccount for Registry {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        8 +  // total_agents
+        8 +  // total_tasks
+        8 +  // total_credits_deposited
+        1;   // bump
+    // The registry was introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::Registry;
+}
+
+impl Registry {
+    // Reads the registry from `account`, which must be the program's registry PDA
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let registry = load_account::<Self>(account)?;
+        if pda::create_registry_address(registry.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(registry)
+    }
+}
+
+// Lamports set aside from completed tasks' payments for requesters whose
+// tasks fail. The pool holds them itself, past its rent reserve.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InsurancePool {
+    pub account_type: AccountType,
+    pub version: u8,
+    pub bump: u8,
+    // Lamports ever paid in by completions and the held refunds of claimed
+    // tasks, out to claims, and swept to the treasury
+    pub total_collected: u64,
+    pub total_paid: u64,
+    pub total_swept: u64,
+}
+
+impl ProgramAccount for InsurancePool {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        1 +  // bump
+        8 +  // total_collected
+        8 +  // total_paid
+        8;   // total_swept
+    // The pool was introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::InsurancePool;
+}
+
+impl InsurancePool {
+    // Reads the pool from `account`, which must be the program's pool PDA
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let pool = load_account::<Self>(account)?;
+        if pda::create_insurance_pool_address(pool.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(pool)
+    }
+}
+
+impl ProgramAccount for TaskPage {
+    // Size of the account with a full page
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        8 +  // page
+        1 +  // bump
+        4 + 32 * TASKS_PER_PAGE as usize; // tasks
+    // Task pages were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::TaskPage;
+}
+
+impl TaskPage {
+    // Reads page `page` of the index of the agent at `agent_key`
+    pub fn load(
+        program_id: &Pubkey,
+        agent_key: &Pubkey,
+        page: u64,
+        account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let task_page = load_account::<Self>(account)?;
+        if pda::create_task_page_address(agent_key, page, task_page.bump, program_id)?
+            != *account.key
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(task_page)
+    }
+}
+
+impl ProgramAccount for BatchTask {
+    // The claimed bitmap always has room for MAX_BATCH_ITEMS
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        32 + // requester
+        8 +  // index
+        1 +  // bump
+        1 +  // vault_bump
+        4 +  // task_count
+        8 +  // payment_per_task
+        1 + 32 + // merkle_root
+        4 +  // items_claimed
+        4 + BatchTask::BITMAP_LEN + // claimed
+        8;   // created_at
+    // Batch tasks were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::BatchTask;
+}
+
+impl BatchTask {
+    pub const BITMAP_LEN: usize = MAX_BATCH_ITEMS as usize / 8;
+
+    // Reads the batch task at `account`, which must be the PDA for its agent
+    // and index
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let batch = load_account::<Self>(account)?;
+        if pda::create_batch_task_address(&batch.agent, batch.index, batch.bump, program_id)?
+            != *account.key
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(batch)
+    }
+
+    // Checks that `vault` is the escrow PDA of the batch stored at `address`
+    pub fn assert_vault(
+        &self,
+        program_id: &Pubkey,
+        address: &Pubkey,
+        vault: &Pubkey,
+    ) -> ProgramResult {
+        if pda::create_vault_address(address, self.vault_bump, program_id)? != *vault {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    pub fn is_claimed(&self, item: u32) -> bool {
+        self.claimed[item as usize / 8] & (1 << (item % 8)) != 0
+    }
+
+    pub fn mark_claimed(&mut self, item: u32) {
+        self.claimed[item as usize / 8] |= 1 << (item % 8);
+    }
+
+    // Whether every item has been claimed, leaving nothing in escrow
+    pub fn is_fully_claimed(&self) -> bool {
+        self.items_claimed == self.task_count
+    }
+
+    // Whether the requester can take back the unclaimed items' payments at
+    // `now`
+    pub fn is_refundable(&self, now: i64) -> bool {
+        now >= self.created_at.saturating_add(BATCH_REFUND_DELAY_SECONDS)
+    }
+
+    // Payments still escrowed for the items not yet claimed
+    pub fn unclaimed_payment(&self) -> Result<u64, ProgramError> {
+        let unclaimed = self.task_count.saturating_sub(self.items_claimed) as u64;
+        Ok(unclaimed.checked_mul(self.payment_per_task).ok_or(AIInfraError::NumericalOverflow)?)
+    }
+}
+
+impl ProgramAccount for TaskTemplate {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // requester
+        8 +  // index
+        1 +  // bump
+        ComputeRequirements::LEN +
+        8;   // default_payment
+    // Templates were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::TaskTemplate;
+}
+
+impl TaskTemplate {
+    // Reads the template at `account`, which must be the PDA for its
+    // requester and index
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let template = load_account::<Self>(account)?;
+        let address = pda::create_task_template_address(
+            &template.requester,
+            template.index,
+            template.bump,
+            program_id,
+        )?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(template)
+    }
+}
+
+impl ProgramAccount for RecurringTask {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // requester
+        32 + // agent
+        8 +  // index
+        1 +  // bump
+        ComputeRequirements::LEN +
+        8 +  // payment_per_run
+        8 +  // interval_seconds
+        8 +  // next_run_at
+        8 +  // funding_balance
+        8;   // runs
+    // Recurring tasks were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::RecurringTask;
+}
+
+impl RecurringTask {
+    // Reads the recurring task at `account`, which must be the PDA for its
+    // requester and index
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let recurring = load_account::<Self>(account)?;
+        let address = pda::create_recurring_task_address(
+            &recurring.requester,
+            recurring.index,
+            recurring.bump,
+            program_id,
+        )?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(recurring)
+    }
+
+    // Records a run at `now` and schedules the next one interval_seconds
+    // after the run that was due. Runs missed entirely are skipped rather
+    // than cranked back to back.
+    pub fn advance(&mut self, now: i64) -> ProgramResult {
+        if now < self.next_run_at {
+            return Err(AIInfraError::RunNotDue.into());
+        }
+        let missed = (now - self.next_run_at) / self.interval_seconds;
+        self.next_run_at = missed.checked_add(1)
+            .and_then(|intervals| intervals.checked_mul(self.interval_seconds))
+            .and_then(|delay| self.next_run_at.checked_add(delay))
+            .ok_or(AIInfraError::NumericalOverflow)?;
+        self.runs = self.runs.checked_add(1).ok_or(AIInfraError::NumericalOverflow)?;
+        Ok(())
+    }
+}
+
+impl ReplicaResult {
+    pub const LEN: usize = 32 + 32;
+}
+
+impl ProgramAccount for RedundantTask {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // requester
+        8 +  // index
+        1 +  // bump
+        1 +  // vault_bump
+        ComputeRequirements::LEN +
+        8 +  // payment_per_replica
+        1 +  // replicas
+        1 +  // quorum
+        1 +  // submission_count
+        ReplicaResult::LEN * MAX_REPLICAS +
+        1 +  // finalized
+        1 + 32 + // agreed_hash
+        8;   // created_at
+    // Redundant tasks were introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::RedundantTask;
+}
+
+impl RedundantTask {
+    // Reads the redundant task at `account`, which must be the PDA for its
+    // requester and index
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let task = load_account::<Self>(account)?;
+        let address =
+            pda::create_redundant_task_address(&task.requester, task.index, task.bump, program_id)?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(task)
+    }
+
+    // Checks that `vault` is the escrow PDA of the task stored at `address`
+    pub fn assert_vault(
+        &self,
+        program_id: &Pubkey,
+        address: &Pubkey,
+        vault: &Pubkey,
+    ) -> ProgramResult {
+        if pda::create_vault_address(address, self.vault_bump, program_id)? != *vault {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    // The results submitted so far
+    pub fn submitted(&self) -> &[ReplicaResult] {
+        &self.submissions[..self.submission_count as usize]
+    }
+
+    // Records `agent`'s result. Each agent submits once, and only while
+    // replicas are outstanding.
+    pub fn submit(&mut self, agent: Pubkey, result_hash: [u8; 32]) -> ProgramResult {
+        if self.finalized || self.submission_count >= self.replicas {
+            return Err(AIInfraError::InvalidTaskStatus.into());
+        }
+        if self.submitted().iter().any(|submission| submission.agent == agent) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.submissions[self.submission_count as usize] = ReplicaResult { agent, result_hash };
+        self.submission_count += 1;
+        Ok(())
+    }
+
+    // Whether REDUNDANT_TASK_EXPIRY_SECONDS have passed since the task was
+    // created
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.created_at.saturating_add(REDUNDANT_TASK_EXPIRY_SECONDS)
+    }
+
+    // The result at least `quorum` submissions share, if any. A quorum is
+    // a majority of the replicas, so at most one result can have one.
+    pub fn quorum_hash(&self) -> Option<[u8; 32]> {
+        let submitted = self.submitted();
+        submitted.iter().map(|submission| submission.result_hash).find(|hash| {
+            submitted.iter().filter(|submission| submission.result_hash == *hash).count()
+                >= self.quorum as usize
+        })
+    }
+}
+
+// How off-chain schedulers reach an agent, kept in an optional PDA derived
+// from the agent so AIAgent doesn't grow
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq, ShankAccount)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgentMetadata {
+    pub account_type: AccountType,
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::pubkey"))]
+    pub agent: Pubkey,
+    pub bump: u8,
+    pub name: String,
+    // Where a longer description of the agent is published
+    pub uri: String,
+    // Where the agent takes work
+    pub endpoint: String,
+}
+
+impl ProgramAccount for AgentMetadata {
+    const LEN: usize = 1 + // account_type
+        1 +  // version
+        32 + // agent
+        1 +  // bump
+        4 + MAX_METADATA_NAME_LEN +
+        4 + MAX_METADATA_URI_LEN +
+        4 + MAX_METADATA_ENDPOINT_LEN;
+    // Metadata was introduced after accounts were versioned
+    const V1_LEN: usize = 0;
+    const ACCOUNT_TYPE: AccountType = AccountType::AgentMetadata;
+}
+
+impl AgentMetadata {
+    // Reads the metadata of the agent at `agent_key` from `account`
+    pub fn load(
+        program_id: &Pubkey,
+        agent_key: &Pubkey,
+        account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(AIInfraError::AccountNotOwnedByProgram.into());
+        }
+        let metadata = load_account::<Self>(account)?;
+        if pda::create_metadata_address(agent_key, metadata.bump, program_id)? != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(metadata)
+    }
+
+    // Fails unless each field fits its limit. Strings are UTF-8 once
+    // decoded, so only their lengths are left to check.
+    pub fn validate(name: &str, uri: &str, endpoint: &str) -> ProgramResult {
+        if name.len() > MAX_METADATA_NAME_LEN
+            || uri.len() > MAX_METADATA_URI_LEN
+            || endpoint.len() > MAX_METADATA_ENDPOINT_LEN
+        {
+            return Err(AIInfraError::InvalidMetadata.into());
+        }
+        Ok(())
+    }
+}
+
+impl ComputeRequirements {
+    // Borsh-serialized size, embedded in ComputeTask
+    pub const LEN: usize = 4 + 4 + 4 + 4 + 4 + 8 + 1 + 4 + 4;
+
+    // Fails unless `agent` has the reputation, stake and resources these
+    // requirements ask for
+    pub fn assert_met_by(&self, agent: &AIAgent) -> ProgramResult {
+        self.assert_met(agent.reputation_score, agent.staked_lamports, &agent.capabilities)
+    }
+
+    // Fails unless every resource asked for is within `bounds`
+    pub fn validate(&self, bounds: &RequirementBounds) -> ProgramResult {
+        let requested = ResourceLimits::from(self).values();
+        let in_bounds = requested.into_iter().zip(bounds.ranges())
+            .all(|(value, (min, max))| (min..=max).contains(&value));
+        if !in_bounds {
+            return Err(AIInfraError::InvalidRequirements.into());
+        }
+        Ok(())
+    }
+
+    // assert_met_by for the fields of an agent read in place, see zero_copy.rs
+    pub fn assert_met(
+        &self,
+        reputation_score: u32,
+        staked_lamports: u64,
+        capabilities: &AgentCapabilities,
+    ) -> ProgramResult {
+        if reputation_score < self.min_reputation {
+            return Err(AIInfraError::ReputationTooLow.into());
+        }
+        if staked_lamports < self.min_stake {
+            return Err(AIInfraError::StakeTooLow.into());
+        }
+        if !capabilities.satisfy(self) {
+            return Err(AIInfraError::InsufficientCapabilities.into());
+        }
+        Ok(())
+    }
+}
+
+impl ResourceLimits {
+    // Borsh-serialized size, embedded in RequirementBounds
+    pub const LEN: usize = 4 * 6;
+
+    // Each resource, in declaration order
+    fn values(&self) -> [u32; 6] {
+        [
+            self.cpu_units,
+            self.memory_mb,
+            self.storage_mb,
+            self.max_time_seconds,
+            self.gpu_units,
+            self.network_mbps,
+        ]
+    }
+}
+
+impl From<&ComputeRequirements> for ResourceLimits {
+    fn from(requirements: &ComputeRequirements) -> Self {
+        Self {
+            cpu_units: requirements.cpu_units,
+            memory_mb: requirements.memory_mb,
+            storage_mb: requirements.storage_mb,
+            max_time_seconds: requirements.max_time_seconds,
+            gpu_units: requirements.gpu_units,
+            network_mbps: requirements.network_mbps,
+        }
+    }
+}
+
+impl RequirementBounds {
+    // Borsh-serialized size, embedded in Config
+    pub const LEN: usize = ResourceLimits::LEN * 2;
+
+    // Each resource's floor and effective ceiling, in declaration order
+    fn ranges(&self) -> impl Iterator<Item = (u32, u32)> {
+        let ceilings = self.max.values().into_iter().zip(MAX_REQUIREMENTS.values());
+        self.min.values().into_iter().zip(ceilings).map(|(min, (max, hard_max))| {
+            (min, if max == 0 { hard_max } else { max })
+        })
+    }
+
+    // Whether every ceiling is within MAX_REQUIREMENTS and every floor is
+    // within its ceiling, so some requirements pass
+    pub fn is_valid(&self) -> bool {
+        let within_hard_max = self.max.values().iter().zip(MAX_REQUIREMENTS.values())
+            .all(|(&max, hard_max)| max <= hard_max);
+        within_hard_max && self.ranges().all(|(min, max)| min <= max)
+    }
+}
+
+impl AgentCapabilities {
+    // Borsh-serialized size, embedded in AIAgent
+    pub const LEN: usize = 4 * 5;
+
+    // Whether the agent has at least what `requirements` asks for. Zeroed
+    // requirements ask for nothing.
+    pub fn satisfy(&self, requirements: &ComputeRequirements) -> bool {
+        if *self == Self::default() {
+            return true;
+        }
+        [
+            (self.cpu_units, requirements.cpu_units),
+            (self.memory_mb, requirements.memory_mb),
+            (self.storage_mb, requirements.storage_mb),
+            (self.gpu_units, requirements.gpu_units),
+            (self.network_mbps, requirements.network_mbps),
+        ]
+        .iter()
+        .all(|&(declared, required)| declared >= required)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TaskStatus {
+    #[default]
     Pending,
     InProgress,
     Completed,
     Failed,
+    Cancelled,
+    // Completed, but the requester has challenged the result
+    Disputed,
+    // Divided by SplitTask into shards, and waiting on their results
+    Split,
+}
+
+impl TaskStatus {
+    // The task lifecycle. A completed task can still be disputed, or fail
+    // for want of verification, until it is paid; failed and cancelled
+    // tasks are final. A split task completes when its shards' results are
+    // aggregated.
+    pub fn can_transition_to(self, next: TaskStatus) -> bool {
+        use TaskStatus::*;
+        matches!(
+            (self, next),
+            (Pending, InProgress)
+                | (Pending, Cancelled)
+                | (InProgress, Completed)
+                | (InProgress, Failed)
+                | (Completed, Disputed)
+                | (Completed, Failed)
+                | (Disputed, Completed)
+                | (Disputed, Failed)
+                | (Pending, Split)
+                | (Split, Completed)
+        )
+    }
+
+    // Failed and cancelled tasks never change status again
+    pub fn is_final(self) -> bool {
+        matches!(self, TaskStatus::Failed | TaskStatus::Cancelled)
+    }
+}
+
+// Token program a token task's escrow is held under. Tasks escrowed before
+// Token-2022 was supported read as the original program.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenProgram {
+    #[default]
+    Spl,
+    Token2022,
+}
+
+impl TokenProgram {
+    // The token program at `program_id`, which must be one of the two
+    pub fn from_id(program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if *program_id == spl_token::id() {
+            Ok(TokenProgram::Spl)
+        } else if *program_id == spl_token_2022::id() {
+            Ok(TokenProgram::Token2022)
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+
+    fn from_code(code: u8) -> Self {
+        if code == TokenProgram::Token2022 as u8 {
+            TokenProgram::Token2022
+        } else {
+            TokenProgram::Spl
+        }
+    }
+
+    pub fn id(self) -> Pubkey {
+        match self {
+            TokenProgram::Spl => spl_token::id(),
+            TokenProgram::Token2022 => spl_token_2022::id(),
+        }
+    }
+
+    // The program's wrapped SOL mint, whose token accounts hold lamports
+    pub fn native_mint(self) -> Pubkey {
+        match self {
+            TokenProgram::Spl => spl_token::native_mint::id(),
+            TokenProgram::Token2022 => spl_token_2022::native_mint::id(),
+        }
+    }
+}
+
+// Why a task failed. Each variant keeps its code, written first in its
+// Borsh encoding, so later variants can be added anywhere in the list and
+// penalties told apart by reason.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FailureReason {
+    OutOfTime = 0,
+    OutOfMemory = 1,
+    AgentError = 2,
+    RequesterCancelledAfterStart = 3,
+    // The arbitrator ruled a dispute for the requester
+    DisputeLost = 4,
+    // ExpireTask timed the task out
+    Expired = 5,
+    // The result went unverified for too long
+    Unverified = 6,
+    // A cause without a variant, in the agent's own numbering
+    Other(u16) = 255,
+}
+
+impl FailureReason {
+    // Borsh-serialized size of the largest variant
+    pub const LEN: usize = 1 + 2;
+
+    pub fn code(self) -> u8 {
+        use FailureReason::*;
+        match self {
+            OutOfTime => 0,
+            OutOfMemory => 1,
+            AgentError => 2,
+            RequesterCancelledAfterStart => 3,
+            DisputeLost => 4,
+            Expired => 5,
+            Unverified => 6,
+            Other(_) => 255,
+        }
+    }
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        TaskStatus::Pending
-    }
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum AIInfraInstruction {
-    RegisterAgent,
-    UpdateAgentStatus { is_active: bool },
-    CreateTask {
-        requirements: ComputeRequirements,
-        payment_amount: u64,
-    },
-    StartTask { task_id: Pubkey },
-    CompleteTask {
-        task_id: Pubkey,
-        result_hash: [u8; 32],
-    },
-    DepositCredits { amount: u64 },
-    WithdrawCredits { amount: u64 },
-}
\ No newline at end of file
+impl BorshSerialize for FailureReason {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.code().serialize(writer)?;
+        if let FailureReason::Other(code) = self {
+            code.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for FailureReason {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        use FailureReason::*;
+        Ok(match u8::deserialize(buf)? {
+            0 => OutOfTime,
+            1 => OutOfMemory,
+            2 => AgentError,
+            3 => RequesterCancelledAfterStart,
+            4 => DisputeLost,
+            5 => Expired,
+            6 => Unverified,
+            255 => Other(u16::deserialize(buf)?),
+            code => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown failure reason {code}"),
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_rounds_in_favor_of_agent() {
+        let config = Config { fee_bps: MAX_FEE_BPS, ..Config::default() };
+        assert_eq!(config.fee_for(1_000).unwrap(), 100);
+        assert_eq!(config.fee_for(9).unwrap(), 0);
+        assert_eq!(config.fee_for(19).unwrap(), 1);
+        assert_eq!(config.fee_for(u64::MAX).unwrap(), u64::MAX / 10);
+
+        let free = Config::default();
+        assert_eq!(free.fee_for(u64::MAX).unwrap(), 0);
+    }
+
+    #[test]
+    fn deadlines_start_with_the_task() {
+        let mut task = ComputeTask::default();
+        task.requirements.max_time_seconds = 60;
+        task.start(1_000).unwrap();
+        assert_eq!(task.status, TaskStatus::InProgress);
+        assert_eq!(task.deadline, 1_060);
+        assert!(!task.is_past_deadline(1_060));
+        assert!(task.is_past_deadline(1_061));
+
+        // A running task can't be started again
+        assert_eq!(
+            task.start(2_000).unwrap_err(),
+            AIInfraError::InvalidStatusTransition.into()
+        );
+
+        // No time limit means no deadline
+        let mut task = ComputeTask::default();
+        task.start(1_000).unwrap();
+        assert_eq!(task.deadline, 0);
+        assert!(!task.is_past_deadline(i64::MAX));
+
+        let config = Config { late_decay_bps: 10_000, ..Config::default() };
+        assert_eq!(config.late_forfeit_for(u64::MAX).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn success_rate_counts_completions_against_failures() {
+        let mut agent = AIAgent::default();
+        assert_eq!(agent.success_rate_bps(), None);
+
+        // Cancellations don't count either way
+        agent.tasks_cancelled = 5;
+        assert_eq!(agent.success_rate_bps(), None);
+
+        agent.tasks_completed = 99;
+        agent.tasks_failed = 1;
+        assert_eq!(agent.success_rate_bps(), Some(9_900));
+
+        agent.tasks_completed = 1;
+        agent.tasks_failed = 2;
+        assert_eq!(agent.success_rate_bps(), Some(3_333));
+
+        agent.tasks_completed = u32::MAX;
+        agent.tasks_failed = 0;
+        assert_eq!(agent.success_rate_bps(), Some(10_000));
+        agent.tasks_failed = u32::MAX;
+        assert_eq!(agent.success_rate_bps(), Some(5_000));
+    }
+
+    #[test]
+    fn capabilities_cover_requirements() {
+        let gpu_task = ComputeRequirements { cpu_units: 4, gpu_units: 1, ..Default::default() };
+        let cpu_only = AgentCapabilities { cpu_units: 16, memory_mb: 4096, ..Default::default() };
+        let gpu_node = AgentCapabilities { gpu_units: 2, ..cpu_only };
+
+        assert!(!cpu_only.satisfy(&gpu_task));
+        assert!(gpu_node.satisfy(&gpu_task));
+        assert!(!AgentCapabilities { cpu_units: 2, ..gpu_node }.satisfy(&gpu_task));
+
+        // Undeclared agents and unconstrained tasks match anything
+        assert!(AgentCapabilities::default().satisfy(&gpu_task));
+        assert!(cpu_only.satisfy(&ComputeRequirements::default()));
+    }
+
+    #[test]
+    fn requirements_are_held_to_their_bounds() {
+        let bounds = RequirementBounds {
+            min: ResourceLimits { cpu_units: 1, max_time_seconds: 10, ..Default::default() },
+            max: ResourceLimits { cpu_units: 64, memory_mb: 8_192, ..Default::default() },
+        };
+        let requirements =
+            ComputeRequirements { cpu_units: 1, max_time_seconds: 10, ..Default::default() };
+        let rejected = |requirements: ComputeRequirements| {
+            requirements.validate(&bounds) == Err(AIInfraError::InvalidRequirements.into())
+        };
+
+        // Values exactly at either bound pass
+        assert_eq!(requirements.validate(&bounds), Ok(()));
+        let at_ceilings = ComputeRequirements {
+            cpu_units: 64,
+            memory_mb: 8_192,
+            max_time_seconds: MAX_REQUIREMENTS.max_time_seconds,
+            gpu_units: MAX_REQUIREMENTS.gpu_units,
+            ..requirements.clone()
+        };
+        assert_eq!(at_ceilings.validate(&bounds), Ok(()));
+
+        assert!(rejected(ComputeRequirements { cpu_units: 0, ..requirements.clone() }));
+        assert!(rejected(ComputeRequirements { max_time_seconds: 9, ..requirements.clone() }));
+        assert!(rejected(ComputeRequirements { cpu_units: 65, ..requirements.clone() }));
+        assert!(rejected(ComputeRequirements { memory_mb: 8_193, ..requirements.clone() }));
+        // Resources the config leaves uncapped still stop at the hard ceiling
+        assert!(rejected(ComputeRequirements {
+            storage_mb: MAX_REQUIREMENTS.storage_mb + 1,
+            ..requirements.clone()
+        }));
+        assert!(rejected(ComputeRequirements { network_mbps: u32::MAX, ..requirements }));
+
+        // Unset bounds admit zeroed requirements but not absurd ones
+        let unset = RequirementBounds::default();
+        assert_eq!(ComputeRequirements::default().validate(&unset), Ok(()));
+        let huge = ComputeRequirements { memory_mb: u32::MAX, ..Default::default() };
+        assert_eq!(huge.validate(&unset), Err(AIInfraError::InvalidRequirements.into()));
+    }
+
+    #[test]
+    fn requirement_bounds_must_admit_something() {
+        assert!(RequirementBounds::default().is_valid());
+        assert!(RequirementBounds { min: MAX_REQUIREMENTS, max: MAX_REQUIREMENTS }.is_valid());
+
+        let floor = ResourceLimits { gpu_units: 2, ..Default::default() };
+        assert!(RequirementBounds { min: floor, max: ResourceLimits::default() }.is_valid());
+        let ceiling = ResourceLimits { gpu_units: 1, ..Default::default() };
+        assert!(!RequirementBounds { min: floor, max: ceiling }.is_valid());
+
+        // Configs can't raise the compiled-in ceilings
+        let raised = ResourceLimits { cpu_units: MAX_REQUIREMENTS.cpu_units + 1, ..ceiling };
+        assert!(!RequirementBounds { min: ResourceLimits::default(), max: raised }.is_valid());
+    }
+
+    #[test]
+    fn split_payments_add_up() {
+        assert_eq!(split_payment(1_000, &[5_000, 3_000, 2_000]).unwrap(), vec![500, 300, 200]);
+        // The last shard takes what rounding leaves over
+        assert_eq!(split_payment(100, &[3_333, 3_333, 3_334]).unwrap(), vec![33, 33, 34]);
+        assert_eq!(split_payment(u64::MAX, &[1, 9_999]).unwrap().iter().sum::<u64>(), u64::MAX);
+
+        for shares in [
+            &[10_000][..],
+            &[5_000, 4_000],
+            &[5_000, 5_001],
+            &[10_000, 0],
+            &[1_250; MAX_TASK_SHARDS + 1],
+        ] {
+            assert_eq!(split_payment(1_000, shares), Err(ProgramError::InvalidArgument));
+        }
+    }
+
+    #[test]
+    fn redundant_results_need_a_quorum() {
+        let mut task = RedundantTask { replicas: 3, quorum: 2, ..RedundantTask::default() };
+        let agents = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        task.submit(agents[0], [1; 32]).unwrap();
+        assert_eq!(task.quorum_hash(), None);
+
+        // Each agent submits once
+        assert_eq!(task.submit(agents[0], [1; 32]), Err(ProgramError::InvalidArgument));
+        task.submit(agents[1], [2; 32]).unwrap();
+        assert_eq!(task.quorum_hash(), None);
+        task.submit(agents[2], [2; 32]).unwrap();
+        assert_eq!(task.quorum_hash(), Some([2; 32]));
+
+        // No replicas are left to submit
+        assert_eq!(
+            task.submit(Pubkey::new_unique(), [2; 32]),
+            Err(ProgramError::Custom(AIInfraError::InvalidTaskStatus as u32))
+        );
+
+        // Tasks expire a fixed time after their creation
+        task.created_at = 1_000;
+        assert!(!task.is_expired(1_000 + REDUNDANT_TASK_EXPIRY_SECONDS - 1));
+        assert!(task.is_expired(1_000 + REDUNDANT_TASK_EXPIRY_SECONDS));
+    }
+
+    #[test]
+    fn batches_refund_unclaimed_items_after_the_delay() {
+        let mut batch = BatchTask {
+            task_count: 3,
+            payment_per_task: 100,
+            claimed: vec![0; BatchTask::BITMAP_LEN],
+            created_at: 1_000,
+            ..BatchTask::default()
+        };
+        assert!(!batch.is_refundable(1_000 + BATCH_REFUND_DELAY_SECONDS - 1));
+        assert!(batch.is_refundable(1_000 + BATCH_REFUND_DELAY_SECONDS));
+        assert_eq!(batch.unclaimed_payment(), Ok(300));
+
+        for item in 0..3 {
+            assert!(!batch.is_fully_claimed());
+            batch.mark_claimed(item);
+            batch.items_claimed += 1;
+        }
+        assert!(batch.is_fully_claimed());
+        assert_eq!(batch.unclaimed_payment(), Ok(0));
+    }
+
+    #[test]
+    fn metadata_fields_are_held_to_their_limits() {
+        let name = "n".repeat(MAX_METADATA_NAME_LEN);
+        let uri = "u".repeat(MAX_METADATA_URI_LEN);
+        let endpoint = "e".repeat(MAX_METADATA_ENDPOINT_LEN);
+        AgentMetadata::validate(&name, &uri, &endpoint).unwrap();
+        AgentMetadata::validate("", "", "").unwrap();
+
+        let invalid = Err(ProgramError::Custom(AIInfraError::InvalidMetadata as u32));
+        assert_eq!(AgentMetadata::validate(&(name.clone() + "n"), &uri, &endpoint), invalid);
+        assert_eq!(AgentMetadata::validate(&name, &(uri.clone() + "u"), &endpoint), invalid);
+        assert_eq!(AgentMetadata::validate(&name, &uri, &(endpoint + "e")), invalid);
+
+        // Limits count bytes, not characters
+        let name = "é".repeat(MAX_METADATA_NAME_LEN / 2 + 1);
+        assert_eq!(AgentMetadata::validate(&name, "", ""), invalid);
+    }
+
+    #[test]
+    fn recurring_runs_follow_the_schedule() {
+        let mut recurring =
+            RecurringTask { interval_seconds: 60, next_run_at: 1_000, ..RecurringTask::default() };
+        assert_eq!(
+            recurring.advance(999),
+            Err(ProgramError::Custom(AIInfraError::RunNotDue as u32))
+        );
+
+        // A late crank doesn't delay the schedule
+        recurring.advance(1_010).unwrap();
+        assert_eq!((recurring.next_run_at, recurring.runs), (1_060, 1));
+        assert!(recurring.advance(1_059).is_err());
+
+        // Runs missed entirely are skipped
+        recurring.advance(1_250).unwrap();
+        assert_eq!((recurring.next_run_at, recurring.runs), (1_300, 2));
+    }
+
+    #[test]
+    fn task_windows_start_over_on_their_boundaries() {
+        let exempt = Pubkey::new_unique();
+        let requester = Pubkey::new_unique();
+        let mut config = Config::default();
+        config.apply(ConfigParams {
+            max_tasks_per_window: 2,
+            window_slots: 100,
+            rate_limit_exempt: vec![exempt],
+            ..ConfigParams::default()
+        }).unwrap();
+        let (mut last_task_slot, mut tasks_in_window) = (0, 0);
+        let mut count = |key, slot| {
+            config.count_task(key, &mut last_task_slot, &mut tasks_in_window, slot)
+        };
+
+        count(&requester, 110).unwrap();
+        count(&requester, 199).unwrap();
+        assert_eq!(count(&requester, 199), Err(AIInfraError::RateLimited.into()));
+        count(&exempt, 199).unwrap();
+        // A window's count holds however far into it the next task comes
+        count(&requester, 200).unwrap();
+        count(&requester, 250).unwrap();
+        assert!(count(&requester, 299).is_err());
+        count(&requester, 1_000).unwrap();
+        assert_eq!((last_task_slot, tasks_in_window), (1_000, 1));
+
+        // A limit needs a window, and the exempt list has room for so many
+        for params in [
+            ConfigParams { max_tasks_per_window: 1, ..ConfigParams::default() },
+            ConfigParams {
+                rate_limit_exempt: vec![exempt; MAX_RATE_LIMIT_EXEMPT + 1],
+                ..ConfigParams::default()
+            },
+        ] {
+            assert_eq!(Config::default().apply(params), Err(ProgramError::InvalidArgument));
+        }
+        // and no limit counts nothing
+        let unlimited = Config::default();
+        unlimited.count_task(&requester, &mut last_task_slot, &mut tasks_in_window, 0).unwrap();
+        assert_eq!((last_task_slot, tasks_in_window), (1_000, 1));
+    }
+
+    #[test]
+    fn held_payments_release_in_order() {
+        let mut agent = AIAgent::default();
+        agent.hold_payment(100, 10).unwrap();
+        agent.hold_payment(200, 20).unwrap();
+        assert_eq!(agent.pending_payments, 300);
+
+        // Nothing is released early, and each entry once it is due
+        assert_eq!(agent.release_payments(9).unwrap(), 0);
+        assert_eq!(agent.release_payments(15).unwrap(), 100);
+        assert_eq!((agent.pending_payments, agent.compute_credits), (200, 100));
+        assert_eq!(agent.release_payments(15).unwrap(), 0);
+
+        // The ring wraps around as entries come and go
+        for i in 0..MAX_PENDING_PAYMENTS as i64 - 1 {
+            agent.hold_payment(1, 30 + i).unwrap();
+        }
+        assert_eq!(agent.pending_count as usize, MAX_PENDING_PAYMENTS);
+        assert_eq!(agent.release_payments(20).unwrap(), 200);
+        agent.hold_payment(1, 40).unwrap();
+        assert_eq!(agent.pending_count as usize, MAX_PENDING_PAYMENTS);
+
+        // Once full, a payment joins the newest entry and waits as long
+        agent.hold_payment(50, 35).unwrap();
+        assert_eq!(agent.pending_count as usize, MAX_PENDING_PAYMENTS);
+        assert_eq!(agent.release_payments(39).unwrap(), MAX_PENDING_PAYMENTS as u64 - 1);
+        assert_eq!(agent.pending_payments, 51);
+        assert_eq!(agent.release_payments(40).unwrap(), 51);
+        assert_eq!((agent.pending_payments, agent.pending_count), (0, 0));
+        assert_eq!(agent.compute_credits, 100 + 200 + MAX_PENDING_PAYMENTS as u64 + 50);
+    }
+
+    #[test]
+    fn withdrawal_policy_limits_each_day_and_delays_loosening() {
+        let day = WITHDRAWAL_DAY_SECONDS;
+        let mut agent = AIAgent::default();
+        // Without a policy anything goes
+        agent.record_withdrawal(u64::MAX, day).unwrap();
+        agent.record_withdrawal(0, day).unwrap();
+
+        let policy = WithdrawalPolicy { max_per_day: 100, cooldown_seconds: 60 };
+        assert_eq!(agent.set_withdrawal_policy(policy, day + 10), day + 10);
+        assert_eq!(agent.withdrawal_policy, policy);
+        assert_eq!(
+            agent.record_withdrawal(1, day + 59),
+            Err(AIInfraError::WithdrawalCooldownActive.into())
+        );
+        let mut agent = AIAgent { withdrawal_policy: policy, ..AIAgent::default() };
+        agent.record_withdrawal(60, day).unwrap();
+        agent.record_withdrawal(40, day + 60).unwrap();
+        assert_eq!(
+            agent.record_withdrawal(1, day + 120),
+            Err(AIInfraError::WithdrawalLimitExceeded.into())
+        );
+        // The limit starts over with the next day, however soon that comes
+        agent.record_withdrawal(100, 2 * day).unwrap();
+        assert_eq!((agent.withdrawn_today, agent.withdrawal_day), (100, 2 * day));
+
+        // Tightening applies at once, loosening only after the delay
+        let now = 2 * day + 500;
+        let tighter = WithdrawalPolicy { max_per_day: 50, cooldown_seconds: 120 };
+        assert_eq!(agent.set_withdrawal_policy(tighter, now), now);
+        for looser in [
+            WithdrawalPolicy { max_per_day: 0, cooldown_seconds: 120 },
+            WithdrawalPolicy { max_per_day: 51, cooldown_seconds: 120 },
+            WithdrawalPolicy { max_per_day: 50, cooldown_seconds: 119 },
+        ] {
+            assert!(looser.loosens(&tighter));
+            let effective_at = agent.set_withdrawal_policy(looser, now);
+            assert_eq!(effective_at, now + WITHDRAWAL_POLICY_DELAY_SECONDS);
+            assert_eq!(agent.withdrawal_policy, tighter);
+            assert_eq!(agent.pending_withdrawal_policy, looser);
+        }
+        agent.apply_due_withdrawal_policy(now + WITHDRAWAL_POLICY_DELAY_SECONDS - 1);
+        assert_eq!(agent.withdrawal_policy, tighter);
+        agent.apply_due_withdrawal_policy(now + WITHDRAWAL_POLICY_DELAY_SECONDS);
+        assert_eq!(agent.withdrawal_policy.cooldown_seconds, 119);
+        assert_eq!(agent.pending_policy_at, 0);
+
+        // and one no looser drops a looser one still waiting
+        let current = agent.withdrawal_policy;
+        agent.set_withdrawal_policy(WithdrawalPolicy::default(), now);
+        assert_eq!(agent.set_withdrawal_policy(current, now), now);
+        assert_eq!(agent.withdrawal_policy, current);
+        assert_eq!((agent.pending_withdrawal_policy, agent.pending_policy_at), Default::default());
+    }
+
+    #[test]
+    fn grants_go_to_new_agents_once() {
+        let mut agent = AIAgent { registered_at_epoch: 7, ..AIAgent::default() };
+        assert_eq!(agent.claim_grant(9), Err(AIInfraError::GrantWindowClosed.into()));
+        agent.claim_grant(8).unwrap();
+        assert!(agent.grant_claimed);
+        assert_eq!(agent.claim_grant(8), Err(AIInfraError::GrantAlreadyClaimed.into()));
+
+        let mut agent = AIAgent { registered_at_epoch: 7, ..AIAgent::default() };
+        agent.claim_grant(7).unwrap();
+    }
+
+    #[test]
+    fn multisig_needs_its_threshold_of_distinct_members() {
+        let [first, second, third, outsider] = [(); 4].map(|_| Pubkey::new_unique());
+        let multisig = MultisigOwner {
+            threshold: 2,
+            signers: vec![first, second, third],
+            ..MultisigOwner::default()
+        };
+        multisig.assert_approved(&[first, third]).unwrap();
+        multisig.assert_approved(&[third, outsider, second]).unwrap();
+        for signed in [&[first][..], &[first, outsider], &[first, first], &[]] {
+            assert_eq!(
+                multisig.assert_approved(signed),
+                Err(AIInfraError::MultisigThresholdNotMet.into())
+            );
+        }
+
+        MultisigOwner::validate(&multisig.signers, 3).unwrap();
+        let too_many = [(); MAX_MULTISIG_SIGNERS + 1].map(|_| Pubkey::new_unique());
+        for (signers, threshold) in [
+            (&[first, second][..], 3),
+            (&[first, second], 0),
+            (&[first, first], 1),
+            (&too_many, 1),
+        ] {
+            assert_eq!(
+                MultisigOwner::validate(signers, threshold),
+                Err(AIInfraError::InvalidMultisig.into())
+            );
+        }
+    }
+
+    #[test]
+    fn insurance_pays_failed_tasks_once_within_the_window() {
+        let mut config = Config {
+            max_insurance_claim: 300,
+            insurance_claim_window_seconds: 100,
+            ..Config::default()
+        };
+        let mut task = ComputeTask {
+            payment_amount: 500,
+            failed_at: 1_000,
+            held_refund: 500,
+            ..ComputeTask::default()
+        };
+        assert_eq!(config.insurance_payout(&task, 1_100, 1_000), Ok(300));
+        assert_eq!(config.insurance_payout(&task, 1_100, 200), Ok(200));
+        assert_eq!(
+            config.insurance_payout(&task, 1_100, 0),
+            Err(AIInfraError::InsurancePoolEmpty.into())
+        );
+        assert_eq!(
+            config.insurance_payout(&task, 1_101, 1_000),
+            Err(AIInfraError::InsuranceClaimWindowClosed.into())
+        );
+
+        config.max_insurance_claim = 0;
+        assert_eq!(config.insurance_payout(&task, 1_000, 1_000), Ok(500));
+
+        // The claim pays up to the payment, but only while the refund is held
+        task.held_refund = 250;
+        assert_eq!(config.insurance_payout(&task, 1_000, 1_000), Ok(500));
+        task.held_refund = 0;
+        assert_eq!(
+            config.insurance_payout(&task, 1_000, 1_000),
+            Err(AIInfraError::TaskNotInsured.into())
+        );
+        task.held_refund = 500;
+
+        task.insurance_claimed = true;
+        assert_eq!(
+            config.insurance_payout(&task, 1_000, 1_000),
+            Err(AIInfraError::InsuranceAlreadyClaimed.into())
+        );
+        task.insurance_claimed = false;
+        task.failed_at = 0;
+        assert_eq!(
+            config.insurance_payout(&task, 0, 1_000),
+            Err(AIInfraError::InsuranceClaimWindowClosed.into())
+        );
+
+        assert!(config.holds_refund(&task, FailureReason::AgentError));
+        assert!(!config.holds_refund(&task, FailureReason::RequesterCancelledAfterStart));
+        task.payment_mint = Some(Pubkey::new_unique());
+        assert!(!config.holds_refund(&task, FailureReason::AgentError));
+        task.payment_mint = None;
+        config.insurance_claim_window_seconds = 0;
+        assert!(!config.holds_refund(&task, FailureReason::AgentError));
+    }
+
+    #[test]
+    fn task_status_transitions() {
+        use TaskStatus::*;
+        let statuses = [Pending, InProgress, Completed, Failed, Cancelled, Disputed, Split];
+        let allowed = |from: TaskStatus| -> &[TaskStatus] {
+            match from {
+                Pending => &[InProgress, Cancelled, Split],
+                InProgress => &[Completed, Failed],
+                Completed => &[Disputed, Failed],
+                Disputed => &[Completed, Failed],
+                Split => &[Completed],
+                Failed | Cancelled => &[],
+            }
+        };
+
+        for from in statuses {
+            for next in statuses {
+                let expected = allowed(from).contains(&next);
+                assert_eq!(from.can_transition_to(next), expected, "{:?} -> {:?}", from, next);
+
+                let mut task = ComputeTask { status: from, ..ComputeTask::default() };
+                if expected {
+                    task.transition(next).unwrap();
+                    assert_eq!(task.status, next);
+                } else {
+                    assert_eq!(
+                        task.transition(next).unwrap_err(),
+                        AIInfraError::InvalidStatusTransition.into()
+                    );
+                    assert_eq!(task.status, from);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn account_len_matches_borsh_size() {
+        let operated_agent = AIAgent {
+            operator: Some(Pubkey::default()),
+            attestation_key: Some(Pubkey::default()),
+            owner_nft_mint: Some(Pubkey::default()),
+            ..AIAgent::default()
+        };
+        assert_eq!(operated_agent.try_to_vec().unwrap().len(), AIAgent::LEN);
+        let exempting = Config {
+            rate_limit_exempt: vec![Pubkey::default(); MAX_RATE_LIMIT_EXEMPT],
+            ..Config::default()
+        };
+        assert_eq!(exempting.try_to_vec().unwrap().len(), Config::LEN);
+        assert_eq!(Bid::default().try_to_vec().unwrap().len(), Bid::LEN);
+        let referred = Requester { referrer: Some(Pubkey::default()), ..Requester::default() };
+        assert_eq!(referred.try_to_vec().unwrap().len(), Requester::LEN);
+        let full_batch = BatchTask {
+            merkle_root: Some([0; 32]),
+            claimed: vec![0; BatchTask::BITMAP_LEN],
+            ..BatchTask::default()
+        };
+        assert_eq!(full_batch.try_to_vec().unwrap().len(), BatchTask::LEN);
+        assert_eq!(TaskTemplate::default().try_to_vec().unwrap().len(), TaskTemplate::LEN);
+        assert_eq!(RecurringTask::default().try_to_vec().unwrap().len(), RecurringTask::LEN);
+        let agreed = RedundantTask { agreed_hash: Some([0; 32]), ..RedundantTask::default() };
+        assert_eq!(agreed.try_to_vec().unwrap().len(), RedundantTask::LEN);
+        assert_eq!(Verifier::default().try_to_vec().unwrap().len(), Verifier::LEN);
+        assert_eq!(Allowance::default().try_to_vec().unwrap().len(), Allowance::LEN);
+        assert_eq!(Registry::default().try_to_vec().unwrap().len(), Registry::LEN);
+        assert_eq!(InsurancePool::default().try_to_vec().unwrap().len(), InsurancePool::LEN);
+        let full_page = TaskPage {
+            tasks: vec![Pubkey::default(); TASKS_PER_PAGE as usize],
+            ..TaskPage::default()
+        };
+        assert_eq!(full_page.try_to_vec().unwrap().len(), TaskPage::LEN);
+        let full_allowlist = AgentAllowlist {
+            requesters: vec![Pubkey::default(); MAX_ALLOWED_REQUESTERS],
+            ..AgentAllowlist::default()
+        };
+        assert_eq!(full_allowlist.try_to_vec().unwrap().len(), AgentAllowlist::LEN);
+        let full_multisig = MultisigOwner {
+            signers: vec![Pubkey::default(); MAX_MULTISIG_SIGNERS],
+            ..MultisigOwner::default()
+        };
+        assert_eq!(full_multisig.try_to_vec().unwrap().len(), MultisigOwner::LEN);
+        let full_metadata = AgentMetadata {
+            name: "n".repeat(MAX_METADATA_NAME_LEN),
+            uri: "u".repeat(MAX_METADATA_URI_LEN),
+            endpoint: "e".repeat(MAX_METADATA_ENDPOINT_LEN),
+            ..AgentMetadata::default()
+        };
+        assert_eq!(full_metadata.try_to_vec().unwrap().len(), AgentMetadata::LEN);
+        let token_task = ComputeTask {
+            payment_mint: Some(Pubkey::default()),
+            commitment: Some([0; 32]),
+            expected_result_hash: Some([0; 32]),
+            parent: Some(Pubkey::default()),
+            failure_reason: Some(FailureReason::Other(0)),
+            ..ComputeTask::default()
+        };
+        assert_eq!(
+            token_task.try_to_vec().unwrap().len(),
+            ComputeTask::LEN - ComputeTask::TRAILER_LEN
+        );
+        assert_eq!(
+            ComputeRequirements::default().try_to_vec().unwrap().len(),
+            ComputeRequirements::LEN
+        );
+    }
+
+    #[test]
+    fn unpack_tells_uninitialized_from_mistyped_accounts() {
+        let uninitialized = |result: Result<AIAgent, ProgramError>| {
+            result.unwrap_err() == AIInfraError::AccountNotInitialized.into()
+        };
+        assert!(uninitialized(AIAgent::unpack(&[])));
+        assert!(uninitialized(AIAgent::unpack(&[0; AIAgent::LEN])));
+
+        let task = ComputeTask { account_type: AccountType::Task, ..ComputeTask::default() };
+        assert_eq!(
+            AIAgent::unpack(&task.try_to_vec().unwrap()).unwrap_err(),
+            AIInfraError::InvalidAccountType.into()
+        );
+    }
+
+    #[test]
+    fn account_helpers_check_the_buffer_size() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let bid = Bid {
+            account_type: AccountType::Bid,
+            version: CURRENT_VERSION,
+            amount: 7,
+            ..Bid::default()
+        };
+        let serialized = bid.try_to_vec().unwrap();
+
+        for len in [Bid::LEN - 1, Bid::LEN, Bid::LEN + 1] {
+            let mut data = serialized.clone();
+            data.resize(len, 0);
+            let mut lamports = 0;
+            let account = AccountInfo::new(
+                &key, false, true, &mut lamports, &mut data, &owner, false, 0,
+            );
+
+            if len == Bid::LEN {
+                assert_eq!(load_account::<Bid>(&account).unwrap().amount, 7);
+                let raised = Bid { amount: 8, ..bid };
+                save_account(&account, &raised).unwrap();
+                assert_eq!(load_account::<Bid>(&account).unwrap().amount, 8);
+            } else {
+                // A short account reads as the unversioned layout
+                let load_error = if len < Bid::LEN {
+                    AIInfraError::UnsupportedAccountVersion
+                } else {
+                    AIInfraError::InvalidAccountSize
+                };
+                assert_eq!(load_account::<Bid>(&account).unwrap_err(), load_error.into());
+                assert_eq!(
+                    save_account(&account, &bid).unwrap_err(),
+                    AIInfraError::InvalidAccountSize.into()
+                );
+            }
+        }
+
+        // Writing a shorter layout zeroes what the longer one left behind
+        let mut data = vec![0xff; AIAgent::LEN];
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key, false, true, &mut lamports, &mut data, &owner, false, 0,
+        );
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            ..AIAgent::default()
+        };
+        save_account(&account, &agent).unwrap();
+        assert_eq!(account.data.borrow()[AIAgent::LEN - 33..], [0; 33]);
+        assert_eq!(load_account::<AIAgent>(&account).unwrap().operator, None);
+    }
+
+    #[test]
+    fn accounts_carry_their_layout_version() {
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            reputation_score: 700,
+            max_concurrent_tasks: 3,
+            ..AIAgent::default()
+        };
+        let mut current = agent.try_to_vec().unwrap();
+        current.resize(AIAgent::LEN, 0);
+        assert_eq!(account_version::<AIAgent>(&current), CURRENT_VERSION);
+        assert_eq!(AIAgent::unpack(&current).unwrap().reputation_score, 700);
+        assert_eq!(migrate_data::<AIAgent>(&current).unwrap(), None);
+
+        // Version 1 had no version byte and ended before the outcome counters
+        let mut v1 = current.clone();
+        v1.remove(1);
+        v1.truncate(AIAgent::V1_LEN - 16);
+        assert_eq!(account_version::<AIAgent>(&v1), 1);
+        let unsupported = AIInfraError::UnsupportedAccountVersion.into();
+        assert_eq!(AIAgent::unpack(&v1).unwrap_err(), unsupported);
+        assert_eq!(migrate_data::<AIAgent>(&v1).unwrap(), Some(current.clone()));
+
+        // Layouts newer than the program's are refused, not reinterpreted
+        let mut newer = current.clone();
+        newer[1] = CURRENT_VERSION + 1;
+        assert_eq!(AIAgent::unpack(&newer).unwrap_err(), unsupported);
+        assert_eq!(migrate_data::<AIAgent>(&newer).unwrap_err(), unsupported);
+        newer.push(0);
+        assert_eq!(migrate_data::<AIAgent>(&newer).unwrap_err(), unsupported);
+
+        // Values that were never stamped can't be saved
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; AIAgent::LEN];
+        let account = AccountInfo::new(
+            &key, false, true, &mut lamports, &mut data, &key, false, 0,
+        );
+        let unstamped = AIAgent { version: 0, ..agent };
+        assert_eq!(save_account(&account, &unstamped).unwrap_err(), unsupported);
+    }
+
+    // Checks that `field` sits at exactly `offset` in `data`
+    fn assert_at(data: &[u8], offset: usize, field: &[u8]) {
+        assert_eq!(&data[offset..offset + field.len()], field, "offset {}", offset);
+    }
+
+    #[test]
+    fn filter_offsets_match_layout() {
+        let owner = Pubkey::new_unique();
+        let registrar = Pubkey::new_unique();
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner,
+            is_active: true,
+            registrar,
+            ..AIAgent::default()
+        };
+        let data = agent.try_to_vec().unwrap();
+        assert_at(&data, ACCOUNT_TYPE_OFFSET, &[AccountType::Agent as u8]);
+        assert_at(&data, VERSION_OFFSET, &[CURRENT_VERSION]);
+        assert_at(&data, AIAgent::OWNER_OFFSET, owner.as_ref());
+        assert_at(&data, AIAgent::IS_ACTIVE_OFFSET, &[1]);
+        assert_at(&data, AIAgent::REGISTRAR_OFFSET, registrar.as_ref());
+
+        let agent_key = Pubkey::new_unique();
+        let requester = Pubkey::new_unique();
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_key,
+            status: TaskStatus::Cancelled,
+            requester,
+            ..ComputeTask::default()
+        };
+        let data = task.try_to_vec().unwrap();
+        assert_at(&data, ACCOUNT_TYPE_OFFSET, &[AccountType::Task as u8]);
+        assert_at(&data, ComputeTask::AGENT_OFFSET, agent_key.as_ref());
+        assert_at(&data, ComputeTask::STATUS_OFFSET, &[TaskStatus::Cancelled as u8]);
+        assert_at(&data, ComputeTask::REQUESTER_OFFSET, requester.as_ref());
+
+        let task_key = Pubkey::new_unique();
+        let bid = Bid {
+            account_type: AccountType::Bid,
+            version: CURRENT_VERSION,
+            task: task_key,
+            agent: agent_key,
+            ..Bid::default()
+        };
+        let data = bid.try_to_vec().unwrap();
+        assert_at(&data, ACCOUNT_TYPE_OFFSET, &[AccountType::Bid as u8]);
+        assert_at(&data, Bid::TASK_OFFSET, task_key.as_ref());
+        assert_at(&data, Bid::AGENT_OFFSET, agent_key.as_ref());
+    }
+
+    #[test]
+    fn agent_layout_is_stable() {
+        let owner = Pubkey::new_unique();
+        let registrar = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let attestation_key = Pubkey::new_unique();
+        let owner_nft_mint = Pubkey::new_unique();
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner,
+            compute_credits: 0x0102_0304_0506_0708,
+            reputation_score: 0x1112_1314,
+            tasks_completed: 0x2122_2324,
+            is_active: true,
+            bump: 0xfe,
+            tasks_created: 0x3132_3334_3536_3738,
+            open_tasks: 0x4142_4344,
+            registrar,
+            last_heartbeat: -2,
+            staked_lamports: 0x5152_5354_5556_5758,
+            unstake_amount: 0x6162_6364_6566_6768,
+            unstake_requested_at: 0x7172_7374_7576_7778,
+            operator: Some(operator),
+            allowed_requesters: 0x8182,
+            active_tasks: 0x9192_9394,
+            max_concurrent_tasks: 0xa1a2_a3a4,
+            capabilities: AgentCapabilities {
+                cpu_units: 0xb1b2_b3b4,
+                memory_mb: 0xc1c2_c3c4,
+                storage_mb: 0xd1d2_d3d4,
+                gpu_units: 0xe1e2_e3e4,
+                network_mbps: 0xf1f2_f3f4,
+            },
+            tasks_failed: 0x0a0b_0c0d,
+            tasks_cancelled: 0x1a1b_1c1d,
+            total_completion_seconds: 0x2a2b_2c2d_2e2f_3a3b,
+            attestation_key: Some(attestation_key),
+            batches_created: 0x3a3b_3c3d_3e3f_4a4b,
+            tips_received: 0x4c4d_4e4f_5a5b_5c5d,
+            pending_payments: 0x5e5f_6a6b_6c6d_6e6f,
+            pending_releases: [PendingPayment {
+                amount: 0x7a7b_7c7d_7e7f_8a8b,
+                release_at: -5,
+            }; MAX_PENDING_PAYMENTS],
+            pending_head: 0xfb,
+            pending_count: 0xfa,
+            operation_nonce: 0x9a9b_9c9d_9e9f_aaab,
+            accepted_tags: [0xf9; TAG_SET_LEN],
+            last_task_slot: 0xbabb_bcbd_bebf_cacb,
+            tasks_in_window: 0xcccd_cecf,
+            withdrawal_policy: WithdrawalPolicy {
+                max_per_day: 0xd0d1_d2d3_d4d5_d6d7,
+                cooldown_seconds: -7,
+            },
+            pending_withdrawal_policy: WithdrawalPolicy {
+                max_per_day: 0xd8d9_dadb_dcdd_dedf,
+                cooldown_seconds: -8,
+            },
+            pending_policy_at: -9,
+            withdrawn_today: 0xe0e1_e2e3_e4e5_e6e7,
+            withdrawal_day: -10,
+            last_withdrawal_at: -11,
+            registered_at_epoch: 0xe8e9_eaeb_eced_eeef,
+            grant_claimed: true,
+            multisig_owner: true,
+            owner_nft_mint: Some(owner_nft_mint),
+            prefer_native: true,
+        };
+        let data = agent.try_to_vec().unwrap();
+        assert_eq!(data.len(), 554);
+        assert_at(&data, 0, &[AccountType::Agent as u8]);
+        assert_at(&data, 1, &[CURRENT_VERSION]);
+        assert_at(&data, 2, owner.as_ref());
+        assert_at(&data, 34, &agent.compute_credits.to_le_bytes());
+        assert_at(&data, 42, &agent.reputation_score.to_le_bytes());
+        assert_at(&data, 46, &agent.tasks_completed.to_le_bytes());
+        assert_at(&data, 50, &[1, 0xfe]);
+        assert_at(&data, 52, &agent.tasks_created.to_le_bytes());
+        assert_at(&data, 60, &agent.open_tasks.to_le_bytes());
+        assert_at(&data, 64, registrar.as_ref());
+        assert_at(&data, 96, &agent.last_heartbeat.to_le_bytes());
+        assert_at(&data, 104, &agent.staked_lamports.to_le_bytes());
+        assert_at(&data, 112, &agent.unstake_amount.to_le_bytes());
+        assert_at(&data, 120, &agent.unstake_requested_at.to_le_bytes());
+        assert_at(&data, 128, &[1]);
+        assert_at(&data, 129, operator.as_ref());
+        assert_at(&data, 161, &agent.allowed_requesters.to_le_bytes());
+        assert_at(&data, 163, &agent.active_tasks.to_le_bytes());
+        assert_at(&data, 167, &agent.max_concurrent_tasks.to_le_bytes());
+        assert_at(&data, 171, &agent.capabilities.cpu_units.to_le_bytes());
+        assert_at(&data, 175, &agent.capabilities.memory_mb.to_le_bytes());
+        assert_at(&data, 179, &agent.capabilities.storage_mb.to_le_bytes());
+        assert_at(&data, 183, &agent.capabilities.gpu_units.to_le_bytes());
+        assert_at(&data, 187, &agent.capabilities.network_mbps.to_le_bytes());
+        assert_at(&data, 191, &agent.tasks_failed.to_le_bytes());
+        assert_at(&data, 195, &agent.tasks_cancelled.to_le_bytes());
+        assert_at(&data, 199, &agent.total_completion_seconds.to_le_bytes());
+        assert_at(&data, 207, &[1]);
+        assert_at(&data, 208, attestation_key.as_ref());
+        assert_at(&data, 240, &agent.batches_created.to_le_bytes());
+        assert_at(&data, 248, &agent.tips_received.to_le_bytes());
+        assert_at(&data, 256, &agent.pending_payments.to_le_bytes());
+        for slot in 0..MAX_PENDING_PAYMENTS {
+            let entry = &agent.pending_releases[slot];
+            assert_at(&data, 264 + 16 * slot, &entry.amount.to_le_bytes());
+            assert_at(&data, 272 + 16 * slot, &entry.release_at.to_le_bytes());
+        }
+        assert_at(&data, 392, &[0xfb, 0xfa]);
+        assert_at(&data, 394, &agent.operation_nonce.to_le_bytes());
+        assert_at(&data, 402, &[0xf9; TAG_SET_LEN]);
+        assert_at(&data, 434, &agent.last_task_slot.to_le_bytes());
+        assert_at(&data, 442, &agent.tasks_in_window.to_le_bytes());
+        assert_at(&data, 446, &agent.withdrawal_policy.max_per_day.to_le_bytes());
+        assert_at(&data, 454, &agent.withdrawal_policy.cooldown_seconds.to_le_bytes());
+        assert_at(&data, 462, &agent.pending_withdrawal_policy.max_per_day.to_le_bytes());
+        assert_at(&data, 470, &agent.pending_withdrawal_policy.cooldown_seconds.to_le_bytes());
+        assert_at(&data, 478, &agent.pending_policy_at.to_le_bytes());
+        assert_at(&data, 486, &agent.withdrawn_today.to_le_bytes());
+        assert_at(&data, 494, &agent.withdrawal_day.to_le_bytes());
+        assert_at(&data, 502, &agent.last_withdrawal_at.to_le_bytes());
+        assert_at(&data, 510, &agent.registered_at_epoch.to_le_bytes());
+        assert_at(&data, 518, &[1, 1]);
+        assert_at(&data, 520, &[1]);
+        assert_at(&data, 521, owner_nft_mint.as_ref());
+        assert_at(&data, 553, &[1]);
+    }
+
+    #[test]
+    fn task_layout_is_stable() {
+        let agent = Pubkey::new_unique();
+        let requester = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let parent = Pubkey::new_unique();
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent,
+            requirements: ComputeRequirements {
+                cpu_units: 0x0102_0304,
+                memory_mb: 0x1112_1314,
+                storage_mb: 0x2122_2324,
+                max_time_seconds: 0x3132_3334,
+                min_reputation: 0x4142_4344,
+                min_stake: 0x5152_5354_5556_5758,
+                single_step: true,
+                gpu_units: 0x6162_6364,
+                network_mbps: 0x7172_7374,
+            },
+            status: TaskStatus::Disputed,
+            result_hash: [0xaa; 32],
+            payment_amount: 0x6162_6364_6566_6768,
+            index: 0x7172_7374_7576_7778,
+            bump: 0xfe,
+            failure_code: 0xfd,
+            requester,
+            vault_bump: 0xfc,
+            payment_mint: Some(mint),
+            open_listing: true,
+            started_at: -1,
+            deadline: -2,
+            completed_at: -3,
+            payment_pending: true,
+            commitment: Some([0xbb; 32]),
+            verified: true,
+            created_at: -4,
+            expected_result_hash: Some([0xcc; 32]),
+            tip_reputation: 0x8182_8384,
+            parent: Some(parent),
+            shard_index: 0xf1,
+            shard_count: 0xf2,
+            reissued: true,
+            failure_reason: Some(FailureReason::Other(0xf3f4)),
+            // In the trailer, not the Borsh layout
+            priority: 0xf5,
+            tags: [0xf6; TASK_TAGS],
+            failed_at: -5,
+            insurance_claimed: true,
+            token_program: TokenProgram::Token2022,
+            payment_usd_cents: 0xf8f9,
+            description_hash: [0xfa; 32],
+            held_refund: 0xfb,
+            result_data: vec![0xf7; 3],
+        };
+        let data = task.try_to_vec().unwrap();
+        assert_eq!(data.len(), 333);
+        assert_at(&data, 0, &[AccountType::Task as u8]);
+        assert_at(&data, 1, &[CURRENT_VERSION]);
+        assert_at(&data, 2, agent.as_ref());
+        assert_at(&data, 34, &task.requirements.cpu_units.to_le_bytes());
+        assert_at(&data, 38, &task.requirements.memory_mb.to_le_bytes());
+        assert_at(&data, 42, &task.requirements.storage_mb.to_le_bytes());
+        assert_at(&data, 46, &task.requirements.max_time_seconds.to_le_bytes());
+        assert_at(&data, 50, &task.requirements.min_reputation.to_le_bytes());
+        assert_at(&data, 54, &task.requirements.min_stake.to_le_bytes());
+        assert_at(&data, 62, &[1]);
+        assert_at(&data, 63, &task.requirements.gpu_units.to_le_bytes());
+        assert_at(&data, 67, &task.requirements.network_mbps.to_le_bytes());
+        assert_at(&data, 71, &[TaskStatus::Disputed as u8]);
+        assert_at(&data, 72, &task.result_hash);
+        assert_at(&data, 104, &task.payment_amount.to_le_bytes());
+        assert_at(&data, 112, &task.index.to_le_bytes());
+        assert_at(&data, 120, &[0xfe, 0xfd]);
+        assert_at(&data, 122, requester.as_ref());
+        assert_at(&data, 154, &[0xfc, 1]);
+        assert_at(&data, 156, mint.as_ref());
+        assert_at(&data, 188, &[1]);
+        assert_at(&data, 189, &task.started_at.to_le_bytes());
+        assert_at(&data, 197, &task.deadline.to_le_bytes());
+        assert_at(&data, 205, &task.completed_at.to_le_bytes());
+        assert_at(&data, 213, &[1, 1]);
+        assert_at(&data, 215, &[0xbb; 32]);
+        assert_at(&data, 247, &[1]);
+        assert_at(&data, 248, &task.created_at.to_le_bytes());
+        assert_at(&data, 256, &[1]);
+        assert_at(&data, 257, &[0xcc; 32]);
+        assert_at(&data, 289, &task.tip_reputation.to_le_bytes());
+        assert_at(&data, 293, &[1]);
+        assert_at(&data, 294, parent.as_ref());
+        assert_at(&data, 326, &[0xf1, 0xf2, 1]);
+        assert_at(&data, 329, &[1, 255, 0xf4, 0xf3]);
+        assert_eq!(ComputeTask::PRIORITY_OFFSET, 333);
+        assert_eq!(ComputeTask::TAGS_OFFSET, 334);
+    }
+
+    #[test]
+    fn task_trailer_holds_priority_and_tags() {
+        let key = Pubkey::new_unique();
+        for payment_mint in [None, Some(Pubkey::new_unique())] {
+            let task = ComputeTask {
+                account_type: AccountType::Task,
+                version: CURRENT_VERSION,
+                payment_mint,
+                priority: 9,
+                tags: [3, 7, 0, 0, 0, 0, 0, 0],
+                failed_at: 1_700_000_000,
+                insurance_claimed: true,
+                token_program: TokenProgram::Token2022,
+                payment_usd_cents: 1_500,
+                description_hash: [5; 32],
+                held_refund: 400,
+                ..ComputeTask::default()
+            };
+            let mut lamports = 0;
+            let mut data = vec![0xff; ComputeTask::LEN];
+            let account = AccountInfo::new(
+                &key, false, true, &mut lamports, &mut data, &key, false, 0,
+            );
+            save_account(&account, &task).unwrap();
+            assert_eq!(account.data.borrow()[ComputeTask::PRIORITY_OFFSET], 9);
+            assert_at(&account.data.borrow(), ComputeTask::TAGS_OFFSET, &task.tags);
+            assert_eq!(load_account::<ComputeTask>(&account).unwrap(), task);
+            assert_eq!(ComputeTask::unpack(&account.data.borrow()).unwrap(), task);
+        }
+
+        // Tasks from before priorities migrate to the lowest, untagged
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            payment_amount: 5,
+            ..ComputeTask::default()
+        };
+        let mut old = task.try_to_vec().unwrap();
+        old.resize(ComputeTask::LEN - ComputeTask::TRAILER_LEN, 0);
+        let migrated = migrate_data::<ComputeTask>(&old).unwrap().unwrap();
+        assert_eq!(migrated.len(), ComputeTask::LEN);
+        assert_eq!(ComputeTask::unpack(&migrated).unwrap(), task);
+
+        // and those from before tags keep their priority
+        old.push(9);
+        let migrated = migrate_data::<ComputeTask>(&old).unwrap().unwrap();
+        assert_eq!(ComputeTask::unpack(&migrated).unwrap(), ComputeTask { priority: 9, ..task });
+
+        // and those from before insurance claims their tags
+        old.extend_from_slice(&[3; TASK_TAGS]);
+        let migrated = migrate_data::<ComputeTask>(&old).unwrap().unwrap();
+        let tagged = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            payment_amount: 5,
+            priority: 9,
+            tags: [3; TASK_TAGS],
+            ..ComputeTask::default()
+        };
+        assert_eq!(ComputeTask::unpack(&migrated).unwrap(), tagged);
+    }
+
+    #[test]
+    fn tasks_grow_to_hold_result_data() {
+        let key = Pubkey::new_unique();
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            priority: 4,
+            tags: [2; TASK_TAGS],
+            result_data: vec![0xab; 5],
+            ..ComputeTask::default()
+        };
+        let mut lamports = 0;
+        let mut data = vec![0; ComputeTask::LEN + 5];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &key, false, 0);
+        save_account(&account, &task).unwrap();
+        // The payload follows the trailer, which keeps its offsets
+        assert_eq!(account.data.borrow()[ComputeTask::PRIORITY_OFFSET], 4);
+        assert_at(&account.data.borrow(), ComputeTask::LEN, &[0xab; 5]);
+        assert_eq!(load_account::<ComputeTask>(&account).unwrap(), task);
+        assert_eq!(migrate_data::<ComputeTask>(&account.data.borrow()), Ok(None));
+
+        // The account must fit the payload exactly, and no more than the cap
+        let empty = ComputeTask { result_data: Vec::new(), ..task };
+        assert_eq!(save_account(&account, &empty), Err(AIInfraError::InvalidAccountSize.into()));
+        let mut lamports = 0;
+        let mut data = vec![0; ComputeTask::MAX_LEN + 1];
+        data[..2].copy_from_slice(&[AccountType::Task as u8, CURRENT_VERSION]);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &key, false, 0);
+        assert_eq!(
+            load_account::<ComputeTask>(&account),
+            Err(AIInfraError::InvalidAccountSize.into())
+        );
+    }
+
+    #[test]
+    fn agents_accept_the_tags_in_their_set() {
+        let mut agent = AIAgent::default();
+        let task = ComputeTask { tags: [7, 0, 0, 0, 0, 0, 0, 0], ..ComputeTask::default() };
+        // An agent that declared nothing serves every category
+        assert!((0..=255).all(|tag| agent.accepts_tag(tag)));
+        assert_eq!(task.assert_tags_accepted(&agent), Ok(()));
+
+        agent.accepted_tags = tag_set(&[3, 255, 0]);
+        assert_eq!(agent.accepted_tags[0], 1 << 3);
+        assert_eq!(agent.accepted_tags[31], 1 << 7);
+        let accepted: Vec<u8> = (0..=255).filter(|&tag| agent.accepts_tag(tag)).collect();
+        assert_eq!(accepted, [3, 255]);
+        assert_eq!(task.assert_tags_accepted(&agent), Err(AIInfraError::TagMismatch.into()));
+
+        // Untagged slots need nothing, but every tag set must be served
+        let task = ComputeTask { tags: [0, 3, 0, 0, 0, 0, 0, 255], ..task };
+        assert_eq!(task.assert_tags_accepted(&agent), Ok(()));
+        let task = ComputeTask { tags: [3, 7, 0, 0, 0, 0, 0, 0], ..task };
+        assert!(task.assert_tags_accepted(&agent).is_err());
+    }
+
+    #[test]
+    fn failure_reasons_round_trip_with_their_codes() {
+        use FailureReason::*;
+        let reasons = [
+            (OutOfTime, 0),
+            (OutOfMemory, 1),
+            (AgentError, 2),
+            (RequesterCancelledAfterStart, 3),
+            (DisputeLost, 4),
+            (Expired, 5),
+            (Unverified, 6),
+            (Other(0), 255),
+            (Other(u16::MAX), 255),
+        ];
+        for (reason, code) in reasons {
+            assert_eq!(reason.code(), code);
+            let data = reason.try_to_vec().unwrap();
+            assert_eq!(data[0], code);
+            assert!(data.len() <= FailureReason::LEN);
+            assert_eq!(FailureReason::try_from_slice(&data).unwrap(), reason);
+        }
+        assert_eq!(Other(0x0102).try_to_vec().unwrap(), [255, 0x02, 0x01]);
+
+        // Codes without a variant don't decode, nor does Other without its own
+        assert!(FailureReason::try_from_slice(&[7]).is_err());
+        assert!(FailureReason::try_from_slice(&[255, 1]).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+// In-memory accounts for running the processor outside the runtime, in unit
+// and property tests. Each fixture owns its buffers and lends them out as the
+// AccountInfos the processor reads, so tests can hand it accounts the runtime
+// would never build, like truncated or foreign-owned ones.
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::SUCCESS, program_stubs, pubkey::Pubkey,
+    rent::Rent, system_program,
+};
+
+use crate::state::ProgramAccount;
+
+#[derive(Clone, Debug, Default)]
+pub struct AccountFixture {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+}
+
+impl AccountFixture {
+    // An empty account owned by the system program, like a wallet
+    pub fn new(key: Pubkey) -> Self {
+        Self { key, owner: system_program::id(), ..Self::default() }
+    }
+
+    // `value` serialized into an account of its full size, owned by
+    // `program_id`
+    pub fn program<T: ProgramAccount>(key: Pubkey, program_id: &Pubkey, value: &T) -> Self {
+        let mut data = value.try_to_vec().unwrap();
+        data.resize(T::LEN, 0);
+        Self::new(key).owner(*program_id).data(data)
+    }
+
+    pub fn system_program() -> Self {
+        Self::new(system_program::id()).executable(true)
+    }
+
+    pub fn owner(self, owner: Pubkey) -> Self {
+        Self { owner, ..self }
+    }
+
+    pub fn data(self, data: Vec<u8>) -> Self {
+        Self { data, ..self }
+    }
+
+    pub fn lamports(self, lamports: u64) -> Self {
+        Self { lamports, ..self }
+    }
+
+    pub fn signer(self, is_signer: bool) -> Self {
+        Self { is_signer, ..self }
+    }
+
+    pub fn writable(self, is_writable: bool) -> Self {
+        Self { is_writable, ..self }
+    }
+
+    pub fn executable(self, executable: bool) -> Self {
+        Self { executable, ..self }
+    }
+
+    pub fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            self.executable,
+            0,
+        )
+    }
+}
+
+// Lends out `accounts`, in order, as a processor's account list
+pub fn account_infos(accounts: &mut [AccountFixture]) -> Vec<AccountInfo<'_>> {
+    accounts.iter_mut().map(AccountFixture::info).collect()
+}
+
+// Reads back a program account the processor wrote
+pub fn read_account<T: ProgramAccount>(account: &AccountInfo) -> T {
+    T::unpack(&account.data.borrow()).unwrap()
+}
+
+// Fails unless `account` still holds exactly `expected`, as when a processor
+// returns an error before writing anything
+pub fn assert_data_eq(account: &AccountInfo, expected: &[u8]) {
+    assert!(*account.data.borrow() == expected, "{} was written", account.key);
+}
+
+// Serves a default clock and rent to processors run outside the runtime,
+// which read them to timestamp events and size vault reserves. Other sysvars
+// stay unavailable.
+struct SysvarStubs;
+
+impl program_stubs::SyscallStubs for SysvarStubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+pub fn set_sysvar_stubs() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(SysvarStubs));
+    });
+}
@@ -0,0 +1,621 @@
+// Zero-copy views of the accounts StartTask and CompleteTask update, so the
+// hot path flips a status byte and bumps a counter in place instead of
+// round-tripping whole accounts through Borsh. Agent status updates and
+// heartbeats write their single field through the same views. The Pod structs mirror the
+// Borsh layout byte for byte: integers are stored as little-endian byte
+// arrays, so nothing is aligned or padded. An optional field moves whatever
+// follows it, so each account is viewed as the fixed runs between its
+// options. The Borsh structs in state.rs stay the representation clients
+// and every other instruction use.
+use std::mem::size_of;
+
+use borsh::BorshDeserialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    error::AIInfraError,
+    pda,
+    state::{
+        check_layout, AIAgent, AgentCapabilities, ComputeRequirements, ComputeTask, PendingPayment,
+        TaskStatus, WithdrawalPolicy, MAX_PENDING_PAYMENTS, TAG_SET_LEN,
+    },
+};
+
+macro_rules! pod_int {
+    ($name:ident, $int:ty) => {
+        // Little-endian $int with an alignment of one
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+        pub struct $name([u8; size_of::<$int>()]);
+
+        impl $name {
+            pub fn get(self) -> $int {
+                <$int>::from_le_bytes(self.0)
+            }
+
+            pub fn set(&mut self, value: $int) {
+                self.0 = value.to_le_bytes();
+            }
+        }
+
+        impl From<$int> for $name {
+            fn from(value: $int) -> Self {
+                Self(value.to_le_bytes())
+            }
+        }
+    };
+}
+
+pod_int!(PodU16, u16);
+pod_int!(PodU32, u32);
+pod_int!(PodU64, u64);
+pod_int!(PodI64, i64);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct PodRequirements {
+    pub cpu_units: PodU32,
+    pub memory_mb: PodU32,
+    pub storage_mb: PodU32,
+    pub max_time_seconds: PodU32,
+    pub min_reputation: PodU32,
+    pub min_stake: PodU64,
+    pub single_step: u8,
+    pub gpu_units: PodU32,
+    pub network_mbps: PodU32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct PodCapabilities {
+    pub cpu_units: PodU32,
+    pub memory_mb: PodU32,
+    pub storage_mb: PodU32,
+    pub gpu_units: PodU32,
+    pub network_mbps: PodU32,
+}
+
+// ComputeTask's fields up to its payment mint
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct TaskHeader {
+    pub account_type: u8,
+    pub version: u8,
+    pub agent: Pubkey,
+    pub requirements: PodRequirements,
+    pub status: u8,
+    pub result_hash: [u8; 32],
+    pub payment_amount: PodU64,
+    pub index: PodU64,
+    pub bump: u8,
+    pub failure_code: u8,
+    pub requester: Pubkey,
+    pub vault_bump: u8,
+}
+
+// ComputeTask's fields between its payment mint and commitment
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct TaskTimes {
+    pub open_listing: u8,
+    pub started_at: PodI64,
+    pub deadline: PodI64,
+    pub completed_at: PodI64,
+    pub payment_pending: u8,
+}
+
+// AIAgent's fields up to its operator
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct AgentHeader {
+    pub account_type: u8,
+    pub version: u8,
+    pub owner: Pubkey,
+    pub compute_credits: PodU64,
+    pub reputation_score: PodU32,
+    pub tasks_completed: PodU32,
+    pub is_active: u8,
+    pub bump: u8,
+    pub tasks_created: PodU64,
+    pub open_tasks: PodU32,
+    pub registrar: Pubkey,
+    pub last_heartbeat: PodI64,
+    pub staked_lamports: PodU64,
+    pub unstake_amount: PodU64,
+    pub unstake_requested_at: PodI64,
+}
+
+// AIAgent's fields between its operator and attestation key
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct AgentCounters {
+    pub allowed_requesters: PodU16,
+    pub active_tasks: PodU32,
+    pub max_concurrent_tasks: PodU32,
+    pub capabilities: PodCapabilities,
+    pub tasks_failed: PodU32,
+    pub tasks_cancelled: PodU32,
+    pub total_completion_seconds: PodU64,
+}
+
+impl From<&ComputeRequirements> for PodRequirements {
+    fn from(requirements: &ComputeRequirements) -> Self {
+        Self {
+            cpu_units: requirements.cpu_units.into(),
+            memory_mb: requirements.memory_mb.into(),
+            storage_mb: requirements.storage_mb.into(),
+            max_time_seconds: requirements.max_time_seconds.into(),
+            min_reputation: requirements.min_reputation.into(),
+            min_stake: requirements.min_stake.into(),
+            single_step: requirements.single_step as u8,
+            gpu_units: requirements.gpu_units.into(),
+            network_mbps: requirements.network_mbps.into(),
+        }
+    }
+}
+
+impl From<&PodRequirements> for ComputeRequirements {
+    fn from(requirements: &PodRequirements) -> Self {
+        Self {
+            cpu_units: requirements.cpu_units.get(),
+            memory_mb: requirements.memory_mb.get(),
+            storage_mb: requirements.storage_mb.get(),
+            max_time_seconds: requirements.max_time_seconds.get(),
+            min_reputation: requirements.min_reputation.get(),
+            min_stake: requirements.min_stake.get(),
+            single_step: requirements.single_step != 0,
+            gpu_units: requirements.gpu_units.get(),
+            network_mbps: requirements.network_mbps.get(),
+        }
+    }
+}
+
+impl From<&AgentCapabilities> for PodCapabilities {
+    fn from(capabilities: &AgentCapabilities) -> Self {
+        Self {
+            cpu_units: capabilities.cpu_units.into(),
+            memory_mb: capabilities.memory_mb.into(),
+            storage_mb: capabilities.storage_mb.into(),
+            gpu_units: capabilities.gpu_units.into(),
+            network_mbps: capabilities.network_mbps.into(),
+        }
+    }
+}
+
+impl From<&PodCapabilities> for AgentCapabilities {
+    fn from(capabilities: &PodCapabilities) -> Self {
+        Self {
+            cpu_units: capabilities.cpu_units.get(),
+            memory_mb: capabilities.memory_mb.get(),
+            storage_mb: capabilities.storage_mb.get(),
+            gpu_units: capabilities.gpu_units.get(),
+            network_mbps: capabilities.network_mbps.get(),
+        }
+    }
+}
+
+impl From<&ComputeTask> for TaskHeader {
+    fn from(task: &ComputeTask) -> Self {
+        Self {
+            account_type: task.account_type as u8,
+            version: task.version,
+            agent: task.agent,
+            requirements: (&task.requirements).into(),
+            status: task.status as u8,
+            result_hash: task.result_hash,
+            payment_amount: task.payment_amount.into(),
+            index: task.index.into(),
+            bump: task.bump,
+            failure_code: task.failure_code,
+            requester: task.requester,
+            vault_bump: task.vault_bump,
+        }
+    }
+}
+
+impl From<&ComputeTask> for TaskTimes {
+    fn from(task: &ComputeTask) -> Self {
+        Self {
+            open_listing: task.open_listing as u8,
+            started_at: task.started_at.into(),
+            deadline: task.deadline.into(),
+            completed_at: task.completed_at.into(),
+            payment_pending: task.payment_pending as u8,
+        }
+    }
+}
+
+impl From<&AIAgent> for AgentHeader {
+    fn from(agent: &AIAgent) -> Self {
+        Self {
+            account_type: agent.account_type as u8,
+            version: agent.version,
+            owner: agent.owner,
+            compute_credits: agent.compute_credits.into(),
+            reputation_score: agent.reputation_score.into(),
+            tasks_completed: agent.tasks_completed.into(),
+            is_active: agent.is_active as u8,
+            bump: agent.bump,
+            tasks_created: agent.tasks_created.into(),
+            open_tasks: agent.open_tasks.into(),
+            registrar: agent.registrar,
+            last_heartbeat: agent.last_heartbeat.into(),
+            staked_lamports: agent.staked_lamports.into(),
+            unstake_amount: agent.unstake_amount.into(),
+            unstake_requested_at: agent.unstake_requested_at.into(),
+        }
+    }
+}
+
+impl From<&AIAgent> for AgentCounters {
+    fn from(agent: &AIAgent) -> Self {
+        Self {
+            allowed_requesters: agent.allowed_requesters.into(),
+            active_tasks: agent.active_tasks.into(),
+            max_concurrent_tasks: agent.max_concurrent_tasks.into(),
+            capabilities: (&agent.capabilities).into(),
+            tasks_failed: agent.tasks_failed.into(),
+            tasks_cancelled: agent.tasks_cancelled.into(),
+            total_completion_seconds: agent.total_completion_seconds.into(),
+        }
+    }
+}
+
+// Splits a `T` off the front of `data`
+fn take<'a, T: Pod>(data: &mut &'a mut [u8]) -> Result<&'a mut T, ProgramError> {
+    if data.len() < size_of::<T>() {
+        return Err(AIInfraError::InvalidAccountSize.into());
+    }
+    let (head, rest) = std::mem::take(data).split_at_mut(size_of::<T>());
+    *data = rest;
+    Ok(bytemuck::from_bytes_mut(head))
+}
+
+// Reads a Borsh Option<T> off the front of `data`
+fn take_option<T: Pod>(data: &mut &mut [u8]) -> Result<Option<T>, ProgramError> {
+    match *take::<u8>(data)? {
+        0 => Ok(None),
+        1 => Ok(Some(*take::<T>(data)?)),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// take for data that can't be written
+fn take_ref<'a, T: Pod>(data: &mut &'a [u8]) -> Result<&'a T, ProgramError> {
+    if data.len() < size_of::<T>() {
+        return Err(AIInfraError::InvalidAccountSize.into());
+    }
+    let (head, rest) = data.split_at(size_of::<T>());
+    *data = rest;
+    Ok(bytemuck::from_bytes(head))
+}
+
+fn take_option_ref<T: Pod>(data: &mut &[u8]) -> Result<Option<T>, ProgramError> {
+    match *take_ref::<u8>(data)? {
+        0 => Ok(None),
+        1 => Ok(Some(*take_ref::<T>(data)?)),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// A task account's data, viewed in place
+pub struct TaskMut<'a> {
+    pub header: &'a mut TaskHeader,
+    pub payment_mint: Option<Pubkey>,
+    pub times: &'a mut TaskTimes,
+    pub commitment: Option<[u8; 32]>,
+    pub expected_result_hash: Option<[u8; 32]>,
+}
+
+// Views `data` as a task, failing where load_account would
+pub fn task_mut(mut data: &mut [u8]) -> Result<TaskMut<'_>, ProgramError> {
+    check_layout::<ComputeTask>(data)?;
+    let header = take::<TaskHeader>(&mut data)?;
+    let payment_mint = take_option::<Pubkey>(&mut data)?;
+    let times = take::<TaskTimes>(&mut data)?;
+    let commitment = take_option::<[u8; 32]>(&mut data)?;
+    // verified and created_at
+    take::<[u8; 9]>(&mut data)?;
+    let expected_result_hash = take_option::<[u8; 32]>(&mut data)?;
+    Ok(TaskMut { header, payment_mint, times, commitment, expected_result_hash })
+}
+
+impl TaskMut<'_> {
+    pub fn status(&self) -> Result<TaskStatus, ProgramError> {
+        Ok(TaskStatus::try_from_slice(&[self.header.status])?)
+    }
+
+    // As ComputeTask::assert_address
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        let seed_agent = match self.times.open_listing {
+            0 => self.header.agent,
+            _ => Pubkey::default(),
+        };
+        let index = self.header.index.get();
+        if pda::create_task_address(&seed_agent, index, self.header.bump, program_id)? != *address
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    // As ComputeTask::assert_vault
+    pub fn assert_vault(
+        &self,
+        program_id: &Pubkey,
+        address: &Pubkey,
+        vault: &Pubkey,
+    ) -> ProgramResult {
+        if pda::create_vault_address(address, self.header.vault_bump, program_id)? != *vault {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    // As ComputeTask::transition
+    pub fn transition(&mut self, next: TaskStatus) -> ProgramResult {
+        if !self.status()?.can_transition_to(next) {
+            return Err(AIInfraError::InvalidStatusTransition.into());
+        }
+        self.header.status = next as u8;
+        Ok(())
+    }
+
+    // As ComputeTask::start
+    pub fn start(&mut self, now: i64) -> ProgramResult {
+        self.transition(TaskStatus::InProgress)?;
+        self.times.started_at.set(now);
+        let deadline = match self.header.requirements.max_time_seconds.get() {
+            0 => 0,
+            seconds => now.checked_add(seconds as i64)
+                .ok_or(AIInfraError::NumericalOverflow)?,
+        };
+        self.times.deadline.set(deadline);
+        Ok(())
+    }
+
+    // As ComputeTask::assert_qualified
+    pub fn assert_qualified(&self, agent: &AgentRef) -> ProgramResult {
+        ComputeRequirements::from(&self.header.requirements).assert_met(
+            agent.header.reputation_score.get(),
+            agent.header.staked_lamports.get(),
+            &(&agent.counters.capabilities).into(),
+        )
+    }
+
+    // As ComputeTask::is_past_deadline
+    pub fn is_past_deadline(&self, now: i64) -> bool {
+        let deadline = self.times.deadline.get();
+        deadline != 0 && now > deadline
+    }
+}
+
+// An agent account's data, viewed in place
+pub struct AgentMut<'a> {
+    pub header: &'a mut AgentHeader,
+    pub operator: Option<Pubkey>,
+    pub counters: &'a mut AgentCounters,
+    pub attestation_key: Option<Pubkey>,
+    pub multisig_owner: bool,
+    pub owner_nft_mint: Option<Pubkey>,
+}
+
+// A read-only AgentMut, for agents passed without write access
+pub struct AgentRef<'a> {
+    pub header: &'a AgentHeader,
+    pub operator: Option<Pubkey>,
+    pub counters: &'a AgentCounters,
+    pub attestation_key: Option<Pubkey>,
+    pub multisig_owner: bool,
+    pub owner_nft_mint: Option<Pubkey>,
+}
+
+// AIAgent's fixed fields between attestation_key and multisig_owner, which
+// the views skip
+const AGENT_FIELDS_BEFORE_MULTISIG: usize = 8 + // batches_created
+    8 +  // tips_received
+    8 +  // pending_payments
+    PendingPayment::LEN * MAX_PENDING_PAYMENTS +
+    1 +  // pending_head
+    1 +  // pending_count
+    8 +  // operation_nonce
+    TAG_SET_LEN + // accepted_tags
+    8 +  // last_task_slot
+    4 +  // tasks_in_window
+    WithdrawalPolicy::LEN * 2 + // withdrawal_policy, pending_withdrawal_policy
+    8 +  // pending_policy_at
+    8 +  // withdrawn_today
+    8 +  // withdrawal_day
+    8 +  // last_withdrawal_at
+    8 +  // registered_at_epoch
+    1;   // grant_claimed
+
+// Views `data` as an agent, failing where load_account would
+pub fn agent_mut(mut data: &mut [u8]) -> Result<AgentMut<'_>, ProgramError> {
+    check_layout::<AIAgent>(data)?;
+    let header = take::<AgentHeader>(&mut data)?;
+    let operator = take_option::<Pubkey>(&mut data)?;
+    let counters = take::<AgentCounters>(&mut data)?;
+    let attestation_key = take_option::<Pubkey>(&mut data)?;
+    let (multisig_owner, owner_nft_mint) = ownership(data)?;
+    Ok(AgentMut { header, operator, counters, attestation_key, multisig_owner, owner_nft_mint })
+}
+
+// agent_mut over data that can't be written
+pub fn agent(mut data: &[u8]) -> Result<AgentRef<'_>, ProgramError> {
+    check_layout::<AIAgent>(data)?;
+    let header = take_ref::<AgentHeader>(&mut data)?;
+    let operator = take_option_ref::<Pubkey>(&mut data)?;
+    let counters = take_ref::<AgentCounters>(&mut data)?;
+    let attestation_key = take_option_ref::<Pubkey>(&mut data)?;
+    let (multisig_owner, owner_nft_mint) = ownership(data)?;
+    Ok(AgentRef { header, operator, counters, attestation_key, multisig_owner, owner_nft_mint })
+}
+
+// Reads whether a multisig owns an agent, and the NFT it's bound to, off the
+// data following its attestation key
+fn ownership(data: &[u8]) -> Result<(bool, Option<Pubkey>), ProgramError> {
+    let mut data = data.get(AGENT_FIELDS_BEFORE_MULTISIG..)
+        .ok_or(AIInfraError::InvalidAccountSize)?;
+    let multisig_owner = *take_ref::<u8>(&mut data)? != 0;
+    Ok((multisig_owner, take_option_ref::<Pubkey>(&mut data)?))
+}
+
+impl AgentMut<'_> {
+    pub fn view(&self) -> AgentRef<'_> {
+        AgentRef {
+            header: self.header,
+            operator: self.operator,
+            counters: self.counters,
+            attestation_key: self.attestation_key,
+            multisig_owner: self.multisig_owner,
+            owner_nft_mint: self.owner_nft_mint,
+        }
+    }
+
+    // As AIAgent::release_task
+    pub fn release_task(&mut self) {
+        let active_tasks = self.counters.active_tasks.get();
+        self.counters.active_tasks.set(active_tasks.saturating_sub(1));
+    }
+}
+
+impl AgentRef<'_> {
+    // As AIAgent::is_operated_by
+    pub fn is_operated_by(&self, key: &Pubkey) -> bool {
+        (self.owner_nft_mint.is_none() && self.header.owner == *key) || self.operator == Some(*key)
+    }
+
+    // As AIAgent::assert_address
+    pub fn assert_address(&self, program_id: &Pubkey, address: &Pubkey) -> ProgramResult {
+        if pda::create_agent_address(&self.header.registrar, self.header.bump, program_id)?
+            != *address
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AccountType, ProgramAccount, CURRENT_VERSION};
+
+    // `value` serialized and padded to its account's size
+    fn account_data<T: ProgramAccount>(value: &T) -> Vec<u8> {
+        let mut data = value.try_to_vec().unwrap();
+        data.resize(T::LEN, 0);
+        data
+    }
+
+    fn task(payment_mint: Option<Pubkey>, commitment: Option<[u8; 32]>) -> ComputeTask {
+        ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: Pubkey::new_unique(),
+            requirements: ComputeRequirements {
+                max_time_seconds: 60,
+                min_stake: 5,
+                single_step: true,
+                network_mbps: 7,
+                ..ComputeRequirements::default()
+            },
+            status: TaskStatus::Pending,
+            payment_amount: 1_000,
+            index: 3,
+            requester: Pubkey::new_unique(),
+            payment_mint,
+            started_at: -1,
+            completed_at: 9,
+            commitment,
+            expected_result_hash: Some([4; 32]),
+            ..ComputeTask::default()
+        }
+    }
+
+    #[test]
+    fn headers_match_the_borsh_layout() {
+        assert_eq!(size_of::<TaskHeader>(), ComputeTask::REQUESTER_OFFSET + 32 + 1);
+        assert_eq!(size_of::<AgentHeader>(), AIAgent::REGISTRAR_OFFSET + 32 + 8 * 4);
+        assert_eq!(size_of::<PodRequirements>(), ComputeRequirements::LEN);
+        assert_eq!(size_of::<PodCapabilities>(), AgentCapabilities::LEN);
+    }
+
+    #[test]
+    fn task_views_follow_its_options() {
+        let options = [(None, None), (Some(Pubkey::new_unique()), Some([2; 32]))];
+        for (payment_mint, commitment) in options {
+            let mut stored = task(payment_mint, commitment);
+            let mut data = account_data(&stored);
+            let mut view = task_mut(&mut data).unwrap();
+            assert_eq!(*view.header, TaskHeader::from(&stored));
+            assert_eq!(*view.times, TaskTimes::from(&stored));
+            assert_eq!(view.payment_mint, payment_mint);
+            assert_eq!(view.commitment, commitment);
+            assert_eq!(view.expected_result_hash, Some([4; 32]));
+            assert_eq!(ComputeRequirements::from(&view.header.requirements), stored.requirements);
+
+            // Writes through the view are what Borsh reads back
+            view.start(100).unwrap();
+            view.times.payment_pending = 1;
+            stored.start(100).unwrap();
+            stored.payment_pending = true;
+            assert_eq!(data, account_data(&stored));
+        }
+
+        let mut data = account_data(&task(None, None));
+        data[ComputeTask::STATUS_OFFSET] = TaskStatus::Completed as u8;
+        let mut view = task_mut(&mut data).unwrap();
+        assert_eq!(
+            view.start(100).unwrap_err(),
+            AIInfraError::InvalidStatusTransition.into()
+        );
+
+        let mut data = account_data(&task(None, None));
+        data.pop();
+        assert_eq!(task_mut(&mut data).err(), Some(AIInfraError::InvalidAccountSize.into()));
+    }
+
+    #[test]
+    fn agent_views_follow_its_options() {
+        let keys = [
+            (None, None, None),
+            (Some(Pubkey::new_unique()), Some(Pubkey::new_unique()), Some(Pubkey::new_unique())),
+        ];
+        for (operator, attestation_key, owner_nft_mint) in keys {
+            let mut stored = AIAgent {
+                account_type: AccountType::Agent,
+                version: CURRENT_VERSION,
+                owner: Pubkey::new_unique(),
+                reputation_score: 80,
+                operator,
+                active_tasks: 2,
+                capabilities: AgentCapabilities { gpu_units: 1, ..AgentCapabilities::default() },
+                total_completion_seconds: 30,
+                attestation_key,
+                pending_payments: 6,
+                grant_claimed: true,
+                multisig_owner: operator.is_some(),
+                owner_nft_mint,
+                ..AIAgent::default()
+            };
+            let mut data = account_data(&stored);
+            let view = agent(&data).unwrap();
+            assert_eq!(*view.header, AgentHeader::from(&stored));
+            assert_eq!(*view.counters, AgentCounters::from(&stored));
+            assert_eq!((view.operator, view.attestation_key), (operator, attestation_key));
+            assert_eq!(view.multisig_owner, operator.is_some());
+            assert_eq!(view.owner_nft_mint, owner_nft_mint);
+            // The key an NFT-bound agent stores no longer speaks for it
+            assert_eq!(view.is_operated_by(&stored.owner), owner_nft_mint.is_none());
+            assert!(!view.is_operated_by(&Pubkey::default()));
+
+            let mut view = agent_mut(&mut data).unwrap();
+            view.release_task();
+            stored.release_task();
+            assert_eq!(data, account_data(&stored));
+        }
+    }
+}
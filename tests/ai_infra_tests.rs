@@ -1,267 +1,13479 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::AccountInfo,
-    clock::Epoch,
+    clock::{Clock, Epoch},
     pubkey::Pubkey,
     rent::Rent,
-    system_program,
-    hash::Hash,
-    sysvar,
     system_instruction,
-    msg,
+    system_program,
+    hash::{hash, hashv, Hash},
+    native_token::LAMPORTS_PER_SOL,
 };
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
+    ed25519_instruction::new_ed25519_instruction,
     signature::{Keypair, Signer},
-    transaction::Transaction,
-    instruction::{AccountMeta, Instruction},
+    transaction::{Transaction, TransactionError},
+    instruction::{AccountMeta, Instruction, InstructionError},
 };
 use solana_banks_client::{BanksClient, BanksClientError};
+use solana_program::{program_option::COption, program_pack::Pack};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account,
+};
+use spl_token_2022::extension::{
+    transfer_fee::{instruction::initialize_transfer_fee_config, TransferFeeConfig},
+    BaseStateWithExtensions, StateWithExtensions,
+};
 use sonic_ai_infra::{
+    AccountType,
+    AgentAllowlist,
     AIAgent,
+    AgentCapabilities,
+    AgentMetadata,
+    Allowance,
     AIInfraInstruction,
+    BatchTask,
+    Bid,
     ComputeRequirements,
     ComputeTask,
+    Config,
+    ConfigParams,
+    FailureReason,
+    InsurancePool,
+    MultisigOwner,
+    ProgramAccount,
+    RecurringTask,
+    RedundantTask,
+    Registry,
+    Requester,
+    RequirementBounds,
+    ResourceLimits,
+    TaskPage,
     TaskStatus,
+    TaskTemplate,
+    TokenProgram,
+    Verifier,
+    WithdrawalPolicy,
+    AIInfraError,
+    BATCH_REFUND_DELAY_SECONDS,
+    MAX_ALLOWED_REQUESTERS,
+    MAX_BATCH_COMPLETIONS,
+    MAX_FEE_BPS,
+    MAX_METADATA_NAME_LEN,
+    MAX_METADATA_URI_LEN,
+    MAX_RESULT_DATA_LEN,
+    MAX_TASK_DESCRIPTION_LEN,
+    REDUNDANT_TASK_EXPIRY_SECONDS,
+    TASKS_PER_PAGE,
+    TAG_SET_LEN,
+    TASK_TAGS,
+    WITHDRAWAL_POLICY_DELAY_SECONDS,
+    CURRENT_VERSION,
+    compute_task_price,
+    priority_surcharge,
+    priority_task_price,
+    find_agent_address,
+    find_allowance_address,
+    find_allowlist_address,
+    find_batch_task_address,
+    find_bid_address,
+    find_config_address,
+    find_credit_vault_address,
+    find_faucet_address,
+    find_insurance_pool_address,
+    find_metadata_address,
+    find_multisig_address,
+    find_recurring_task_address,
+    find_redundant_task_address,
+    find_registry_address,
+    find_requester_address,
+    find_stake_vault_address,
+    find_task_address,
+    find_task_page_address,
+    find_task_template_address,
+    find_vault_address,
+    find_verifier_address,
+    instruction::{self, TaskResult},
     process_instruction,
+    attestation::attestation_message,
+    merkle,
+    oracle::OraclePrice,
+    reputation::{MAX_REPUTATION, MAX_TIP_REPUTATION_PER_TASK, TIP_REPUTATION_BONUS},
 };
 
-// Helper function with corrected types
-async fn create_test_agent(
-    banks_client: &mut BanksClient,
-    payer: &Keypair,
-    recent_blockhash: Hash,
-    program_id: Pubkey,
-    agent_keypair: &Keypair,
-    owner_keypair: &Keypair,
-) -> Result<(), BanksClientError> {
-    let instruction_data = AIInfraInstruction::RegisterAgent.try_to_vec().unwrap();
-    
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(
-            program_id,
-            &instruction_data,
-            vec![
-                AccountMeta::new(agent_keypair.pubkey(), false),
-                AccountMeta::new(owner_keypair.pubkey(), true),
-                AccountMeta::new_readonly(system_program::id(), false),
-                AccountMeta::new_readonly(sysvar::rent::id(), false),
-            ],
-        )],
-        Some(&payer.pubkey()),
+// RegisterAgent for the owner's agent PDA, paid for by the owner
+fn register_agent_instruction(program_id: Pubkey, owner: Pubkey) -> Instruction {
+    let (agent_address, _) = find_agent_address(&owner, &program_id);
+    instruction::register_agent(&program_id, &agent_address, &owner)
+}
+
+// Registers the owner's agent PDA through the program
+async fn create_test_agent(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: Hash,
+    program_id: Pubkey,
+    owner_keypair: &Keypair,
+) -> Result<(), BanksClientError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[register_agent_instruction(program_id, owner_keypair.pubkey())],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[payer, owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
+
+// DeregisterAgent returning the agent's and its vaults' rent to its owner
+fn deregister_agent_instruction(program_id: Pubkey, owner: Pubkey) -> Instruction {
+    let (agent_address, _) = find_agent_address(&owner, &program_id);
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::DeregisterAgent,
+        vec![
+            AccountMeta::new(agent_address, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new(find_credit_vault_address(&agent_address, &program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_stake_vault_address(&agent_address, &program_id).0, false),
+        ],
+    )
+}
+
+// Accounts shared by DepositRequesterCredits and WithdrawRequesterCredits
+fn requester_credit_accounts(program_id: Pubkey, owner: Pubkey) -> Vec<AccountMeta> {
+    let (requester, _) = find_requester_address(&owner, &program_id);
+    vec![
+        AccountMeta::new(requester, false),
+        AccountMeta::new(owner, true),
+        AccountMeta::new(find_credit_vault_address(&requester, &program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+    ]
+}
+
+fn deposit_requester_credits_instruction(
+    program_id: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::DepositRequesterCredits { amount },
+        requester_credit_accounts(program_id, owner),
+    )
+}
+
+fn withdraw_requester_credits_instruction(
+    program_id: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::WithdrawRequesterCredits { amount },
+        requester_credit_accounts(program_id, owner),
+    )
+}
+
+// Accounts shared by StakeCollateral and UnstakeCollateral
+fn stake_accounts(program_id: Pubkey, agent: Pubkey, owner: Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(agent, false),
+        AccountMeta::new(owner, true),
+        AccountMeta::new(find_stake_vault_address(&agent, &program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+    ]
+}
+
+fn stake_collateral_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::StakeCollateral { amount },
+        stake_accounts(program_id, agent, owner),
+    )
+}
+
+fn unstake_collateral_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::UnstakeCollateral { amount },
+        stake_accounts(program_id, agent, owner),
+    )
+}
+
+// Heartbeat signed by the agent's owner
+fn heartbeat_instruction(program_id: Pubkey, agent: Pubkey, owner: Pubkey) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::Heartbeat,
+        vec![
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(owner, true),
+        ],
+    )
+}
+
+fn set_operator_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    operator: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::SetOperator { operator },
+        vec![AccountMeta::new(agent, false), AccountMeta::new_readonly(owner, true)],
+    )
+}
+
+fn revoke_operator_instruction(program_id: Pubkey, agent: Pubkey, owner: Pubkey) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::RevokeOperator,
+        vec![AccountMeta::new(agent, false), AccountMeta::new_readonly(owner, true)],
+    )
+}
+
+fn allowlist_accounts(program_id: Pubkey, agent: Pubkey, owner: Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(find_allowlist_address(&agent, &program_id).0, false),
+        AccountMeta::new(agent, false),
+        AccountMeta::new(owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+fn add_allowed_requester_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::AddAllowedRequester { requester },
+        allowlist_accounts(program_id, agent, owner),
+    )
+}
+
+fn remove_allowed_requester_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::RemoveAllowedRequester { requester },
+        allowlist_accounts(program_id, agent, owner),
+    )
+}
+
+// DeactivateStaleAgent paying the bounty to `caller`, who needn't sign
+fn deactivate_stale_agent_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    caller: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::DeactivateStaleAgent,
+        vec![
+            AccountMeta::new(agent, false),
+            AccountMeta::new(find_config_address(&program_id).0, false),
+            AccountMeta::new(caller, false),
+        ],
+    )
+}
+
+fn transfer_credits_instruction(
+    program_id: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::TransferCredits { amount, expected_nonce: None },
+        vec![
+            AccountMeta::new(source, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new(find_credit_vault_address(&source, &program_id).0, false),
+            AccountMeta::new(find_credit_vault_address(&destination, &program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+fn approve_credits_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    spender: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::ApproveCredits { spender, amount },
+        vec![
+            AccountMeta::new(find_allowance_address(&agent, &spender, &program_id).0, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn revoke_allowance_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    spender: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::RevokeAllowance,
+        vec![
+            AccountMeta::new(find_allowance_address(&agent, &spender, &program_id).0, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new(owner, true),
+        ],
+    )
+}
+
+// CreateTaskWithAllowance for `agent`, funded from `funding`'s credits by
+// the spending `payer`
+#[allow(clippy::too_many_arguments)]
+fn create_task_with_allowance_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    index: u64,
+    payer: Pubkey,
+    funding: Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CreateTaskWithAllowance {
+            requirements,
+            payment_amount,
+        },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(find_registry_address(&program_id).0, false),
+            AccountMeta::new(
+                find_task_page_address(&agent, index / TASKS_PER_PAGE, &program_id).0,
+                false,
+            ),
+            AccountMeta::new(funding, false),
+            AccountMeta::new(find_credit_vault_address(&funding, &program_id).0, false),
+            AccountMeta::new(find_allowance_address(&funding, &payer, &program_id).0, false),
+        ],
+    )
+}
+
+// A credit vault backing `credits` already deposited to an agent
+fn credit_vault_account_with(credits: u64) -> Account {
+    Account::new(
+        credits + Rent::default().minimum_balance(0),
+        0,
+        &system_program::id(),
+    )
+}
+
+// A funded system wallet, so it stays rent exempt when receiving payouts
+fn wallet_account() -> Account {
+    Account::new(LAMPORTS_PER_SOL, 0, &system_program::id())
+}
+
+// A task vault already holding `payment_amount` in escrow
+fn vault_account_with(payment_amount: u64) -> Account {
+    Account::new(
+        payment_amount + Rent::default().minimum_balance(0),
+        0,
+        &system_program::id(),
+    )
+}
+
+// An initialized mock mint with no authority
+fn mint_account() -> Account {
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: u64::MAX,
+        decimals: 6,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
+
+// A token account for `mint` owned by `owner`, holding `amount`
+fn token_account_with(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint,
+        owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+// CreateTaskWithToken escrowing `amount` of `mint`, a mint of `token_program`,
+// from the requester's token account
+#[allow(clippy::too_many_arguments)]
+fn create_task_with_token_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    index: u64,
+    requester: Pubkey,
+    requester_token: Pubkey,
+    mint: Pubkey,
+    token_program: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (vault, _) = find_vault_address(&task, &program_id);
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CreateTaskWithToken {
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            amount,
+            mint,
+        },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(requester, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(vault, false),
+            AccountMeta::new(requester_token, false),
+            AccountMeta::new(
+                get_associated_token_address_with_program_id(&vault, &mint, &token_program),
+                false,
+            ),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(find_registry_address(&program_id).0, false),
+            AccountMeta::new(
+                find_task_page_address(&agent, index / TASKS_PER_PAGE, &program_id).0,
+                false,
+            ),
+        ],
+    )
+}
+
+// Appends the token accounts a token-paid task settles through
+fn with_token_escrow(
+    mut instruction: Instruction,
+    task: Pubkey,
+    mint: Pubkey,
+    token_program: Pubkey,
+    destination_token: Pubkey,
+) -> Instruction {
+    let (vault, _) = find_vault_address(&task, &instruction.program_id);
+    instruction.accounts.extend([
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(&vault, &mint, &token_program),
+            false,
+        ),
+        AccountMeta::new(destination_token, false),
+        AccountMeta::new_readonly(token_program, false),
+        AccountMeta::new(mint, false),
+    ]);
+    instruction
+}
+
+// Builds a program account pre-populated with a serialized agent
+fn agent_account_with(agent: &AIAgent, owner: Pubkey) -> Account {
+    let mut data = agent.try_to_vec().unwrap();
+    data.resize(AIAgent::LEN, 0);
+
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
+
+// Builds a program account pre-populated with a serialized task
+fn task_account_with(task: &ComputeTask, owner: Pubkey) -> Account {
+    let mut data = task.try_to_vec().unwrap();
+    data.resize(ComputeTask::LEN, 0);
+    task.write_trailer(&mut data[ComputeTask::PRIORITY_OFFSET..]);
+
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
+
+// Default requirements for a task the agent finishes with CompleteTask
+fn single_step_requirements() -> ComputeRequirements {
+    ComputeRequirements { single_step: true, ..ComputeRequirements::default() }
+}
+
+// ClaimTask signed by the claiming agent's owner
+// CreateTaskWithCredits funded from `payer`'s requester credits
+fn create_task_with_credits_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    index: u64,
+    payer: Pubkey,
+    requirements: ComputeRequirements,
+    payment_amount: u64,
+) -> Instruction {
+    let (requester, _) = find_requester_address(&payer, &program_id);
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CreateTaskWithCredits {
+            requirements,
+            payment_amount,
+        },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(find_registry_address(&program_id).0, false),
+            AccountMeta::new(
+                find_task_page_address(&agent, index / TASKS_PER_PAGE, &program_id).0,
+                false,
+            ),
+            AccountMeta::new(requester, false),
+            AccountMeta::new(find_credit_vault_address(&requester, &program_id).0, false),
+        ],
+    )
+}
+
+fn claim_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::ClaimTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// SubmitBid from the agent's owner, who pays for the bid account
+fn submit_bid_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::SubmitBid { task_id: task, amount },
+        vec![
+            AccountMeta::new(find_bid_address(&task, &agent, &program_id).0, false),
+            AccountMeta::new_readonly(task, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// AcceptBid signed by the task's requester
+fn accept_bid_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    requester: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::AcceptBid { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new_readonly(find_bid_address(&task, &agent, &program_id).0, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(requester, true),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// CloseBid signed by the bidding agent's owner, who gets the rent back
+fn close_bid_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CloseBid,
+        vec![
+            AccountMeta::new(find_bid_address(&task, &agent, &program_id).0, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(owner, false),
+        ],
+    )
+}
+
+// CancelTask signed by the agent's owner, refunding the escrow to `requester`
+fn cancel_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CancelTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// FailTask signed by the agent's owner, refunding the escrow to `requester`
+fn fail_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+    reason: FailureReason,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::FailTask { task_id: task, reason },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(find_stake_vault_address(&agent, &program_id).0, false),
+            AccountMeta::new(treasury, false),
+        ],
+    )
+}
+
+// ExpireTask, which needs no signer beyond the fee payer
+fn expire_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::ExpireTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(find_stake_vault_address(&agent, &program_id).0, false),
+            AccountMeta::new(treasury, false),
+        ],
+    )
+}
+
+fn register_verifier_instruction(
+    program_id: Pubkey,
+    admin: Pubkey,
+    verifier: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::RegisterVerifier { verifier },
+        vec![
+            AccountMeta::new(find_verifier_address(&verifier, &program_id).0, false),
+            AccountMeta::new(admin, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// VerifyResult signed by `verifier`, passing its own verifier account
+fn verify_result_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    verifier: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::VerifyResult { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(verifier, true),
+            AccountMeta::new_readonly(find_verifier_address(&verifier, &program_id).0, false),
+            AccountMeta::new(owner, false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(treasury, false),
+        ],
+    )
+}
+
+fn refund_unverified_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    requester: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::RefundUnverifiedTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// CloseTask signed by `owner`, the agent's owner or the requester, refunding
+// rent to `destination`
+fn close_task_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    destination: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CloseTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(destination, false),
+        ],
+    )
+}
+
+// BatchCompleteTasks for tasks of one agent that share a requester
+fn batch_complete_tasks_instruction(
+    program_id: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+    results: Vec<(Pubkey, [u8; 32])>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(agent, false),
+        AccountMeta::new(owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        AccountMeta::new(treasury, false),
+    ];
+    for (task, _) in &results {
+        accounts.push(AccountMeta::new(*task, false));
+        accounts.push(AccountMeta::new(find_vault_address(task, &program_id).0, false));
+        accounts.push(AccountMeta::new(requester, false));
+    }
+    let results = results
+        .into_iter()
+        .map(|(task_id, result_hash)| TaskResult { task_id, result_hash })
+        .collect();
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::BatchCompleteTasks { results },
+        accounts,
+    )
+}
+
+// An ed25519 program instruction in which `signer` signs `message`
+fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    let keypair = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+    new_ed25519_instruction(&keypair, message)
+}
+
+fn commit_result_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    commitment: [u8; 32],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CommitResult { task_id: task, commitment },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new_readonly(agent, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+// RevealResult takes the same accounts as CompleteTask
+#[allow(clippy::too_many_arguments)]
+fn reveal_result_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+    result_hash: [u8; 32],
+    salt: [u8; 32],
+) -> Instruction {
+    let mut reveal = instruction::complete_task(
+        &program_id,
+        &task,
+        &agent,
+        &owner,
+        &requester,
+        &treasury,
+        result_hash,
+    );
+    reveal.data = AIInfraInstruction::RevealResult { task_id: task, result_hash, salt }
+        .try_to_vec()
+        .unwrap();
+    reveal
+}
+
+fn claim_payment_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::ClaimPayment { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(treasury, false),
+        ],
+    )
+}
+
+fn dispute_task_instruction(program_id: Pubkey, task: Pubkey, requester: Pubkey) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::DisputeTask { task_id: task },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new_readonly(requester, true),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+        ],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resolve_dispute_instruction(
+    program_id: Pubkey,
+    task: Pubkey,
+    agent: Pubkey,
+    arbitrator: Pubkey,
+    owner: Pubkey,
+    requester: Pubkey,
+    treasury: Pubkey,
+    uphold_result: bool,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::ResolveDispute { task_id: task, uphold_result },
+        vec![
+            AccountMeta::new(task, false),
+            AccountMeta::new(agent, false),
+            AccountMeta::new_readonly(arbitrator, true),
+            AccountMeta::new(owner, false),
+            AccountMeta::new(find_vault_address(&task, &program_id).0, false),
+            AccountMeta::new(requester, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(find_stake_vault_address(&agent, &program_id).0, false),
+        ],
+    )
+}
+
+// InitializeConfig paid for by `payer`
+fn initialize_config_instruction(
+    program_id: Pubkey,
+    payer: Pubkey,
+    admin: Pubkey,
+    params: ConfigParams,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::InitializeConfig { admin, params },
+        vec![
+            AccountMeta::new(find_config_address(&program_id).0, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+// UpdateConfig signed by `signer`, which must be the current admin
+fn update_config_instruction(
+    program_id: Pubkey,
+    signer: Pubkey,
+    admin: Pubkey,
+    params: ConfigParams,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::UpdateConfig { admin, params },
+        vec![
+            AccountMeta::new(find_config_address(&program_id).0, false),
+            AccountMeta::new_readonly(signer, true),
+        ],
+    )
+}
+
+// Config parameters with the given fee settings and every other tunable
+// left at its default
+fn fee_params(fee_bps: u16, treasury: Pubkey, min_payment: u64) -> ConfigParams {
+    ConfigParams { fee_bps, treasury, min_payment, ..ConfigParams::default() }
+}
+
+// PauseProgram or UnpauseProgram signed by `admin`
+fn set_paused_instruction(program_id: Pubkey, admin: Pubkey, paused: bool) -> Instruction {
+    let instruction = if paused {
+        AIInfraInstruction::PauseProgram
+    } else {
+        AIInfraInstruction::UnpauseProgram
+    };
+    Instruction::new_with_borsh(
+        program_id,
+        &instruction,
+        vec![
+            AccountMeta::new(find_config_address(&program_id).0, false),
+            AccountMeta::new_readonly(admin, true),
+        ],
+    )
+}
+
+// Reputation the seeded config takes from an agent for each failed task
+const REPUTATION_PENALTY: u32 = 10;
+
+// Seeds the global config PDA with the given fee and treasury, no minimum
+// payment and no reputation bonus
+fn add_config(program_test: &mut ProgramTest, program_id: Pubkey, fee_bps: u16, treasury: Pubkey) {
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        fee_bps,
+        treasury,
+        reputation_penalty: REPUTATION_PENALTY,
+        ..Config::default()
+    };
+    add_config_account(program_test, program_id, config);
+}
+
+// `config` laid out in a full-length config account
+fn config_data(config: &Config) -> Vec<u8> {
+    let mut data = config.try_to_vec().unwrap();
+    data.resize(Config::LEN, 0);
+    data
+}
+
+// Seeds the global config PDA with `config`, filling in its type and bump
+fn add_config_account(program_test: &mut ProgramTest, program_id: Pubkey, config: Config) {
+    let (address, bump) = find_config_address(&program_id);
+    let config = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        bump,
+        ..config
+    };
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(Config::LEN),
+            data: config_data(&config),
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+}
+
+// Seeds the registry PDA with all of its counters at zero
+fn add_registry(program_test: &mut ProgramTest, program_id: Pubkey) {
+    let (address, bump) = find_registry_address(&program_id);
+    let registry = Registry {
+        account_type: AccountType::Registry,
+        version: CURRENT_VERSION,
+        bump,
+        ..Registry::default()
+    };
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(Registry::LEN),
+            data: registry.try_to_vec().unwrap(),
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+}
+
+// Seeds the first page of `agent`'s task index, listing `tasks` as if the
+// program had created them
+fn add_task_page(
+    program_test: &mut ProgramTest,
+    program_id: Pubkey,
+    agent: Pubkey,
+    tasks: &[Pubkey],
+) {
+    let (address, bump) = find_task_page_address(&agent, 0, &program_id);
+    let task_page = TaskPage {
+        account_type: AccountType::TaskPage,
+        version: CURRENT_VERSION,
+        agent,
+        page: 0,
+        bump,
+        tasks: tasks.to_vec(),
+    };
+    let mut data = task_page.try_to_vec().unwrap();
+    data.resize(TaskPage::LEN, 0);
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(TaskPage::LEN),
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+}
+
+#[tokio::test]
+async fn test_agent_registration() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The program allocates the agent PDA itself
+    create_test_agent(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        program_id,
+        &payer,
+    )
+    .await
+    .unwrap();
+
+    // Only verify the account exists at the derived address
+    let (agent_address, _) = find_agent_address(&payer.pubkey(), &program_id);
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+}
+
+#[tokio::test]
+async fn test_task_creation_and_execution() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+
+    // Setup initial agent account with credits
+    let initial_credits = 1000;
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: initial_credits,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    // A second, unrelated agent
+    let other_owner = Keypair::new();
+    let (other_agent_address, other_bump) =
+        find_agent_address(&other_owner.pubkey(), &program_id);
+    let other_agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: other_owner.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump: other_bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: other_owner.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(
+        other_agent_address,
+        agent_account_with(&other_agent, program_id),
+    );
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    // Fee-free config, so completion pays out exactly the task payment
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let vault_reserve = Rent::default().minimum_balance(0);
+
+    // Create task
+    let requirements = ComputeRequirements {
+        cpu_units: 100,
+        memory_mb: 512,
+        storage_mb: 1024,
+        max_time_seconds: 3600,
+        min_reputation: 0,
+        min_stake: 0,
+        single_step: true,
+        gpu_units: 0,
+        network_mbps: 0,
+    };
+
+    let payment_amount = 500;
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &requester.pubkey(),
+            requirements,
+            payment_amount,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Verify task creation
+    let task_account = banks_client
+        .get_account(task_address)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task_account.owner, program_id);
+    assert_eq!(task.agent, agent_address);
+    assert_eq!(task.payment_amount, payment_amount);
+    assert_eq!(task.status, TaskStatus::Pending);
+    assert_eq!(task.index, 0);
+    assert_eq!(task.requester, requester.pubkey());
+
+    // The payment is escrowed in the vault rather than taken from credits
+    assert_eq!(
+        banks_client.get_balance(vault_address).await.unwrap(),
+        payment_amount + vault_reserve
+    );
+
+    let agent_account = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let updated_agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(updated_agent.compute_credits, initial_credits);
+    assert_eq!(updated_agent.tasks_created, 1);
+
+    // Another agent can't start the task
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &task_address,
+            &other_agent_address,
+            &other_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &other_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentTaskMismatch as u32),
+    );
+
+    // Start task
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::InProgress);
+
+    // Another agent can't complete it and collect the stats
+    let result_hash = [7u8; 32];
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &other_agent_address,
+            &other_owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &other_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentTaskMismatch as u32),
+    );
+
+    // Nor can someone else sign for the assigned agent
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &other_owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &other_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // Complete task
+    let owner_balance = banks_client.get_balance(owner_keypair.pubkey()).await.unwrap();
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The payment goes to the agent owner and the reserve back to the requester
+    assert!(banks_client.get_account(vault_address).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + vault_reserve
+    );
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.result_hash, result_hash);
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let updated_agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(updated_agent.tasks_completed, 1);
+
+    let other_account = banks_client
+        .get_account(other_agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let other_agent = AIAgent::unpack(&other_account.data).unwrap();
+    assert_eq!(other_agent.tasks_completed, 0);
+}
+
+#[tokio::test]
+async fn test_credit_management() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    // Start from a freshly registered agent with no credits
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (credit_vault, _) = find_credit_vault_address(&agent_address, &program_id);
+    let vault_reserve = Rent::default().minimum_balance(0);
+    let owner_balance = banks_client.get_balance(owner_keypair.pubkey()).await.unwrap();
+
+    // Every deposit and withdrawal moves real lamports, so the vault always
+    // holds exactly the credits on paper plus its rent reserve
+    let owner = owner_keypair.pubkey();
+    let steps = [
+        instruction::deposit_credits(&program_id, &agent_address, &owner, 1000, None),
+        instruction::withdraw_credits(&program_id, &agent_address, &owner, 300, None),
+        instruction::deposit_credits(&program_id, &agent_address, &owner, 50, None),
+        instruction::withdraw_credits(&program_id, &agent_address, &owner, 750, None),
+    ];
+    let expected_credits = [1000, 700, 750, 0];
+
+    for (instruction, expected) in steps.into_iter().zip(expected_credits) {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let agent_account = banks_client
+            .get_account(agent_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let agent = AIAgent::unpack(&agent_account.data).unwrap();
+        assert_eq!(agent.compute_credits, expected);
+        assert_eq!(
+            banks_client.get_balance(credit_vault).await.unwrap(),
+            expected + vault_reserve
+        );
+        assert_eq!(
+            banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+            owner_balance - vault_reserve - expected
+        );
+    }
+
+    // Withdrawing more than was deposited fails
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            1,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InsufficientFunds,
+    );
+}
+fn assert_instruction_error(result: Result<(), BanksClientError>, expected: InstructionError) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, expected)
+    );
+}
+
+#[tokio::test]
+async fn test_credit_operations_take_nonces() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let load_agent = |account: Option<Account>| AIAgent::unpack(&account.unwrap().data).unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::deposit_credits(&program_id, &agent_address, &owner.pubkey(), 1000, None)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let agent = load_agent(banks_client.get_account(agent_address).await.unwrap());
+    assert_eq!(agent.operation_nonce, 1);
+
+    // Two clients withdraw at once, both having read nonce 1. Whichever
+    // lands first takes it and the other is turned away.
+    let withdraw = |amount| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::withdraw_credits(
+                &program_id,
+                &agent_address,
+                &owner.pubkey(),
+                amount,
+                Some(1),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        transaction
+    };
+    let (first, second) = (withdraw(100), withdraw(200));
+    banks_client.process_transaction(first).await.unwrap();
+    assert_instruction_error(
+        banks_client.process_transaction(second).await,
+        InstructionError::Custom(AIInfraError::NonceMismatch as u32),
+    );
+    let agent = load_agent(banks_client.get_account(agent_address).await.unwrap());
+    assert_eq!(agent.compute_credits, 900);
+    assert_eq!(agent.operation_nonce, 2);
+
+    // Retrying the one that went through can't apply it twice
+    let blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut transaction = withdraw(100);
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::NonceMismatch as u32),
+    );
+    let agent = load_agent(banks_client.get_account(agent_address).await.unwrap());
+    assert_eq!(agent.compute_credits, 900);
+}
+
+#[tokio::test]
+async fn test_create_task_rejects_forged_agent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let attacker = Keypair::new();
+    let (forged_agent, bump) = find_agent_address(&attacker.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&forged_agent, 0, &program_id);
+
+    // Borsh-compatible agent data with an absurd balance, owned by someone else
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: attacker.pubkey(),
+        compute_credits: u64::MAX,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: attacker.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(
+        forged_agent,
+        agent_account_with(&agent, Pubkey::new_unique()),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &forged_agent,
+            0,
+            &payer.pubkey(),
+            ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            1_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+    );
+
+    // No task account was created
+    assert!(banks_client.get_account(task_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_owner_instructions_reject_forged_agent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let attacker = Keypair::new();
+    let (forged_agent, bump) = find_agent_address(&attacker.pubkey(), &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: attacker.pubkey(),
+        compute_credits: u64::MAX,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: attacker.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(
+        forged_agent,
+        agent_account_with(&agent, attacker.pubkey()),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let attacker_key = attacker.pubkey();
+    let forged_instructions = [
+        instruction::deposit_credits(&program_id, &forged_agent, &attacker_key, 1, None),
+        instruction::withdraw_credits(&program_id, &forged_agent, &attacker_key, 1, None),
+        instruction::update_agent_status(&program_id, &forged_agent, &attacker_key, false),
+    ];
+
+    for forged in forged_instructions {
+        let mut transaction = Transaction::new_with_payer(&[forged], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &attacker], recent_blockhash);
+
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_register_agent_accepts_legacy_rent_sysvar() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The layout clients sent while the program still read rent from its sysvar
+    let mut register = register_agent_instruction(program_id, owner.pubkey());
+    assert_eq!(register.accounts.len(), 4);
+    let rent_sysvar = AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false);
+    register.accounts.insert(3, rent_sysvar);
+    let mut transaction = Transaction::new_with_payer(&[register], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = banks_client.get_account(agent).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent.data).unwrap().owner, owner.pubkey());
+    let registry = banks_client.get_account(find_registry_address(&program_id).0).await;
+    assert_eq!(Registry::unpack(&registry.unwrap().unwrap().data).unwrap().total_agents, 1);
+}
+
+#[tokio::test]
+async fn test_substituted_system_accounts_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+
+    // A made-up program where the system program goes
+    let mut register = register_agent_instruction(program_id, owner.pubkey());
+    register.accounts[2].pubkey = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(&[register], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::IncorrectProgramId,
+    );
+
+    assert!(banks_client.get_account(agent).await.unwrap().is_none());
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    // Deposits move lamports through the system program too
+    let mut deposit = instruction::deposit_credits(&program_id, &agent, &owner.pubkey(), 10, None);
+    deposit.accounts[3].pubkey = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(&[deposit], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::IncorrectProgramId,
+    );
+}
+
+#[tokio::test]
+async fn test_misflagged_accounts_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+
+    // The agent passed readonly, as a client might by mistake
+    let mut update = instruction::update_agent_status(&program_id, &agent, &owner.pubkey(), false);
+    update.accounts[0].is_writable = false;
+    let mut transaction = Transaction::new_with_payer(&[update], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotWritable as u32),
+    );
+
+    // The credit vault, further down the list
+    let mut deposit = instruction::deposit_credits(&program_id, &agent, &owner.pubkey(), 10, None);
+    deposit.accounts[2].is_writable = false;
+    let mut transaction = Transaction::new_with_payer(&[deposit], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotWritable as u32),
+    );
+
+    // The owner not signing
+    let mut update = instruction::update_agent_status(&program_id, &agent, &owner.pubkey(), false);
+    update.accounts[1].is_signer = false;
+    let mut transaction = Transaction::new_with_payer(&[update], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let stored = banks_client.get_account(agent).await.unwrap().unwrap();
+    let stored = AIAgent::unpack(&stored.data).unwrap();
+    assert!(stored.is_active);
+    assert_eq!(stored.compute_credits, 0);
+}
+
+#[tokio::test]
+async fn test_duplicate_accounts_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+
+    // The agent passed again as the registry, which deposits also write
+    let mut deposit = instruction::deposit_credits(&program_id, &agent, &owner.pubkey(), 10, None);
+    deposit.accounts[5].pubkey = agent;
+    let mut transaction = Transaction::new_with_payer(&[deposit], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DuplicateAccount as u32),
+    );
+
+    // The agent named as the address of its own new task
+    let create = instruction::create_task(
+        &program_id,
+        &agent,
+        &agent,
+        0,
+        &payer.pubkey(),
+        single_step_requirements(),
+        100,
+    );
+    let mut transaction = Transaction::new_with_payer(&[create], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DuplicateAccount as u32),
+    );
+
+    let stored = banks_client.get_account(agent).await.unwrap().unwrap();
+    let stored = AIAgent::unpack(&stored.data).unwrap();
+    assert_eq!(stored.compute_credits, 0);
+    assert_eq!(stored.tasks_created, 0);
+}
+
+#[tokio::test]
+async fn test_inactive_agent_rejects_tasks() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let (pending_task_address, pending_task_bump) =
+        find_task_address(&agent_address, 0, &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 1, &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    // A task queued before the agent went into maintenance
+    let pending_task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_address,
+        requirements: ComputeRequirements {
+            cpu_units: 100,
+            memory_mb: 512,
+            storage_mb: 1024,
+            max_time_seconds: 3600,
+            min_reputation: 0,
+            min_stake: 0,
+            single_step: true,
+            gpu_units: 0,
+            network_mbps: 0,
+        },
+        status: TaskStatus::Pending,
+        result_hash: [0; 32],
+        payment_amount: 100,
+        index: 0,
+        bump: pending_task_bump,
+        failure_code: 0,
+        requester,
+        vault_bump: find_vault_address(&pending_task_address, &program_id).1,
+        payment_mint: None,
+        open_listing: false,
+        started_at: 0,
+        deadline: 0,
+        completed_at: 0,
+        payment_pending: false,
+        commitment: None,
+        verified: false,
+        created_at: 0,
+        expected_result_hash: None,
+        tip_reputation: 0,
+        parent: None,
+        shard_index: 0,
+        shard_count: 0,
+        reissued: false,
+        failure_reason: None,
+        priority: 0,
+        tags: [0; TASK_TAGS],
+        failed_at: 0,
+        insurance_claimed: false,
+        token_program: TokenProgram::Spl,
+        payment_usd_cents: 0,
+        description_hash: [0; 32],
+        held_refund: 0,
+        result_data: Vec::new(),
+    };
+    program_test.add_account(
+        pending_task_address,
+        task_account_with(&pending_task, program_id),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Deactivate the agent
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::update_agent_status(
+            &program_id,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            false,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // CreateTask against the inactive agent
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            1,
+            &payer.pubkey(),
+            ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            500,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentNotActive as u32),
+    );
+
+    // StartTask on a task queued earlier
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &pending_task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentNotActive as u32),
+    );
+
+    // No credits were locked
+    let agent_account = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.compute_credits, 1000);
+}
+
+#[tokio::test]
+async fn test_double_registration_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let (funded_agent, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 150,
+        tasks_completed: 3,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(funded_agent, agent_account_with(&agent, program_id));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Register a fresh agent, then try to register it again
+    create_test_agent(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        program_id,
+        &payer,
+    )
+    .await
+    .unwrap();
+    let (agent_address, _) = find_agent_address(&payer.pubkey(), &program_id);
+    let registered = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    assert_instruction_error(
+        create_test_agent(
+            &mut banks_client,
+            &payer,
+            blockhash,
+            program_id,
+            &payer,
+        )
+        .await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    let account = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data, registered.data);
+
+    // Re-registering an agent that holds credits must not reset it
+    assert_instruction_error(
+        create_test_agent(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            program_id,
+            &owner_keypair,
+        )
+        .await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    let account = banks_client
+        .get_account(funded_agent)
+        .await
+        .unwrap()
+        .unwrap();
+    let stored = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!(stored.compute_credits, 1000);
+    assert_eq!(stored.reputation_score, 150);
+    assert_eq!(stored.tasks_completed, 3);
+}
+
+#[tokio::test]
+async fn test_account_type_confusion_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let (task_address, task_bump) = find_task_address(&agent_address, 0, &program_id);
+    let (new_task_address, _) = find_task_address(&agent_address, 1, &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_address,
+        requirements: ComputeRequirements {
+            cpu_units: 100,
+            memory_mb: 512,
+            storage_mb: 1024,
+            max_time_seconds: 3600,
+            min_reputation: 0,
+            min_stake: 0,
+            single_step: true,
+            gpu_units: 0,
+            network_mbps: 0,
+        },
+        status: TaskStatus::Pending,
+        result_hash: [0; 32],
+        payment_amount: 100,
+        index: 0,
+        bump: task_bump,
+        failure_code: 0,
+        requester,
+        vault_bump: find_vault_address(&task_address, &program_id).1,
+        payment_mint: None,
+        open_listing: false,
+        started_at: 0,
+        deadline: 0,
+        completed_at: 0,
+        payment_pending: false,
+        commitment: None,
+        verified: false,
+        created_at: 0,
+        expected_result_hash: None,
+        tip_reputation: 0,
+        parent: None,
+        shard_index: 0,
+        shard_count: 0,
+        reissued: false,
+        failure_reason: None,
+        priority: 0,
+        tags: [0; TASK_TAGS],
+        failed_at: 0,
+        insurance_claimed: false,
+        token_program: TokenProgram::Spl,
+        payment_usd_cents: 0,
+        description_hash: [0; 32],
+        held_refund: 0,
+        result_data: Vec::new(),
+    };
+    program_test.add_account(task_address, task_account_with(&task, program_id));
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // An agent account passed as its own task is a duplicate
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &agent_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DuplicateAccount as u32),
+    );
+
+    // A task account passed where an agent is expected
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &new_task_address,
+            &task_address,
+            1,
+            &payer.pubkey(),
+            ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            100,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidAccountType as u32),
+    );
+
+    // An existing task can't be overwritten by CreateTask
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &payer.pubkey(),
+            ComputeRequirements {
+                cpu_units: 1,
+                memory_mb: 1,
+                storage_mb: 1,
+                max_time_seconds: 1,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            100,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidSeeds,
+    );
+}
+
+#[tokio::test]
+async fn test_agent_outside_pda_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let (_, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    // A valid agent stored at an arbitrary address instead of the owner's PDA
+    let stray_agent = Keypair::new();
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(stray_agent.pubkey(), agent_account_with(&agent, program_id));
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &stray_agent.pubkey(),
+            &owner_keypair.pubkey(),
+            1000,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidSeeds,
+    );
+}
+
+#[tokio::test]
+async fn test_task_addresses_follow_agent_counter() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    for index in 0..2u64 {
+        let (task_address, _) = find_task_address(&agent_address, index, &program_id);
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                index,
+                &payer.pubkey(),
+                ComputeRequirements {
+                    cpu_units: 100,
+                    memory_mb: 512,
+                    storage_mb: 1024,
+                    max_time_seconds: 3600,
+                    min_reputation: 0,
+                    min_stake: 0,
+                    single_step: true,
+                    gpu_units: 0,
+                    network_mbps: 0,
+                },
+                100 + index,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // Task N is found purely from the agent address and the index
+    for index in 0..2u64 {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let task_account = banks_client
+            .get_account(task_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let task = ComputeTask::unpack(&task_account.data).unwrap();
+        assert_eq!(task.index, index);
+        assert_eq!(task.bump, task_bump);
+        assert_eq!(task.payment_amount, 100 + index);
+    }
+
+    let agent_account = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.tasks_created, 2);
+    assert_eq!(agent.open_tasks, 2);
+    assert_eq!(agent.compute_credits, 1000);
+}
+
+#[tokio::test]
+async fn test_start_task_requires_agent_owner() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let (task_address, task_bump) = find_task_address(&agent_address, 0, &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_address,
+        requirements: ComputeRequirements {
+            cpu_units: 100,
+            memory_mb: 512,
+            storage_mb: 1024,
+            max_time_seconds: 3600,
+            min_reputation: 0,
+            min_stake: 0,
+            single_step: true,
+            gpu_units: 0,
+            network_mbps: 0,
+        },
+        status: TaskStatus::Pending,
+        result_hash: [0; 32],
+        payment_amount: 100,
+        index: 0,
+        bump: task_bump,
+        failure_code: 0,
+        requester,
+        vault_bump: find_vault_address(&task_address, &program_id).1,
+        payment_mint: None,
+        open_listing: false,
+        started_at: 0,
+        deadline: 0,
+        completed_at: 0,
+        payment_pending: false,
+        commitment: None,
+        verified: false,
+        created_at: 0,
+        expected_result_hash: None,
+        tip_reputation: 0,
+        parent: None,
+        shard_index: 0,
+        shard_count: 0,
+        reissued: false,
+        failure_reason: None,
+        priority: 0,
+        tags: [0; TASK_TAGS],
+        failed_at: 0,
+        insurance_claimed: false,
+        token_program: TokenProgram::Spl,
+        payment_usd_cents: 0,
+        description_hash: [0; 32],
+        held_refund: 0,
+        result_data: Vec::new(),
+    };
+    program_test.add_account(task_address, task_account_with(&task, program_id));
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // A random wallet signing as the owner
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &intruder.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // The real owner passed without signing
+    let mut unsigned_start = instruction::start_task(
+        &program_id,
+        &task_address,
+        &agent_address,
+        &owner_keypair.pubkey(),
+    );
+    unsigned_start.accounts[2].is_signer = false;
+    let mut transaction = Transaction::new_with_payer(&[unsigned_start], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let stored = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(stored.status, TaskStatus::Pending);
+
+    // The owner can start it
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let stored = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(stored.status, TaskStatus::InProgress);
+}
+
+#[tokio::test]
+async fn test_register_rejects_underfunded_agent_account() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&payer.pubkey(), &program_id);
+
+    // Pre-fund the agent PDA with far less than the rent-exempt minimum
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(&payer.pubkey(), &agent_address, 1),
+            register_agent_instruction(program_id, payer.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_eq!(
+        banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(AIInfraError::NotRentExempt as u32),
+        )
+    );
+
+    // Topping the account up to the minimum lets registration go through
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &agent_address,
+                rent.minimum_balance(AIAgent::LEN),
+            ),
+            register_agent_instruction(program_id, payer.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(rent.is_exempt(account.lamports, account.data.len()));
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!(agent.owner, payer.pubkey());
+}
+
+#[tokio::test]
+async fn test_accounts_created_by_cpi_or_pre_funded() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    add_registry(&mut program_test, program_id);
+
+    // The first owner never holds lamports; the second pays its own rent
+    let unfunded_owner = Keypair::new();
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+    let (prefunded_agent, _) = find_agent_address(&unfunded_owner.pubkey(), &program_id);
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+
+    let mut transaction = Transaction::new_with_payer(
+        &instruction::build_register_agent_tx(
+            &program_id,
+            &rent,
+            &payer.pubkey(),
+            &unfunded_owner.pubkey(),
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &unfunded_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[register_agent_instruction(program_id, owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(banks_client.get_balance(unfunded_owner.pubkey()).await.unwrap(), 0);
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        LAMPORTS_PER_SOL - rent.minimum_balance(AIAgent::LEN)
+    );
+    for (address, owner) in [(prefunded_agent, &unfunded_owner), (agent, &owner)] {
+        let account = banks_client.get_account(address).await.unwrap().unwrap();
+        assert_eq!(account.lamports, rent.minimum_balance(AIAgent::LEN));
+        assert_eq!(AIAgent::unpack(&account.data).unwrap().owner, owner.pubkey());
+    }
+
+    let payment = 1_000;
+    let escrow = payment + rent.minimum_balance(0);
+
+    // Task 0 opens the agent's first index page, which is funded alongside it
+    let mut transaction = Transaction::new_with_payer(
+        &instruction::build_create_task_tx(
+            &program_id,
+            &rent,
+            &payer.pubkey(),
+            &agent,
+            0,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            payment,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        LAMPORTS_PER_SOL - escrow
+    );
+
+    let (task, _) = find_task_address(&agent, 1, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task,
+            &agent,
+            1,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            payment,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        LAMPORTS_PER_SOL - 2 * escrow - rent.minimum_balance(ComputeTask::LEN)
+    );
+
+    let (page, _) = find_task_page_address(&agent, 0, &program_id);
+    let page = banks_client.get_account(page).await.unwrap().unwrap();
+    assert_eq!(page.lamports, rent.minimum_balance(TaskPage::LEN));
+    let page = TaskPage::unpack(&page.data).unwrap();
+    assert_eq!(page.tasks, vec![find_task_address(&agent, 0, &program_id).0, task]);
+}
+
+#[tokio::test]
+async fn test_task_id_must_match_task_account() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 2,
+        open_tasks: 2,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let mut task_addresses = vec![];
+    for index in 0..2u64 {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            status: TaskStatus::Pending,
+            result_hash: [0; 32],
+            payment_amount: 100,
+            index,
+            bump: task_bump,
+            failure_code: 0,
+            requester,
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            payment_mint: None,
+            open_listing: false,
+            started_at: 0,
+            deadline: 0,
+            completed_at: 0,
+            payment_pending: false,
+            commitment: None,
+            verified: false,
+            created_at: 0,
+            expected_result_hash: None,
+            tip_reputation: 0,
+            parent: None,
+            shard_index: 0,
+            shard_count: 0,
+            reissued: false,
+            failure_reason: None,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            failed_at: 0,
+            insurance_claimed: false,
+            token_program: TokenProgram::Spl,
+            payment_usd_cents: 0,
+            description_hash: [0; 32],
+            held_refund: 0,
+            result_data: Vec::new(),
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        task_addresses.push(task_address);
+    }
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The instruction names task 1 but the account in position 0 is task 0
+    let mut start = instruction::start_task(
+        &program_id,
+        &task_addresses[0],
+        &agent_address,
+        &owner_keypair.pubkey(),
+    );
+    start.data = AIInfraInstruction::StartTask {
+        task_id: task_addresses[1],
+    }
+    .try_to_vec()
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(&[start], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskIdMismatch as u32),
+    );
+
+    let mut complete = instruction::complete_task(
+        &program_id,
+        &task_addresses[0],
+        &agent_address,
+        &owner_keypair.pubkey(),
+        &requester,
+        &treasury,
+        [1; 32],
+    );
+    complete.data = AIInfraInstruction::CompleteTask {
+        task_id: task_addresses[1],
+        result_hash: [1; 32],
+        result_data: None,
+    }
+    .try_to_vec()
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(&[complete], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskIdMismatch as u32),
+    );
+
+    // Neither task moved
+    for task_address in task_addresses {
+        let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&task_account.data).unwrap();
+        assert_eq!(task.status, TaskStatus::Pending);
+    }
+}
+
+#[tokio::test]
+async fn test_cancel_task_refunds_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let requester = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    // Tasks 0 and 1 already started / finished, task 2 gets created below
+    let initial_credits = 1000;
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: initial_credits,
+        reputation_score: 100,
+        tasks_completed: 1,
+        is_active: true,
+        bump,
+        tasks_created: 2,
+        open_tasks: 1,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    program_test.add_account(requester.pubkey(), wallet_account());
+
+    let mut seeded_tasks = vec![];
+    for (index, status) in [TaskStatus::InProgress, TaskStatus::Completed].into_iter().enumerate() {
+        let (task_address, task_bump) =
+            find_task_address(&agent_address, index as u64, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            status,
+            result_hash: [0; 32],
+            payment_amount: 100,
+            index: index as u64,
+            bump: task_bump,
+            failure_code: 0,
+            requester: requester.pubkey(),
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            payment_mint: None,
+            open_listing: false,
+            started_at: 0,
+            deadline: 0,
+            completed_at: 0,
+            payment_pending: false,
+            commitment: None,
+            verified: false,
+            created_at: 0,
+            expected_result_hash: None,
+            tip_reputation: 0,
+            parent: None,
+            shard_index: 0,
+            shard_count: 0,
+            reissued: false,
+            failure_reason: None,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            failed_at: 0,
+            insurance_claimed: false,
+            token_program: TokenProgram::Spl,
+            payment_usd_cents: 0,
+            description_hash: [0; 32],
+            held_refund: 0,
+            result_data: Vec::new(),
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        seeded_tasks.push(task_address);
+    }
+    add_task_page(&mut program_test, program_id, agent_address, &seeded_tasks);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let task_rent = Rent::default().minimum_balance(ComputeTask::LEN);
+
+    // Escrow 400 lamports in a new task
+    let (task_address, _) = find_task_address(&agent_address, 2, &program_id);
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            2,
+            &requester.pubkey(),
+            ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            400,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance - task_rent - 400 - Rent::default().minimum_balance(0)
+    );
+
+    // Only the requester or the agent owner may cancel
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            intruder.pubkey(),
+            requester.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner_keypair.pubkey(),
+            requester.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Cancelled);
+
+    // The requester gets the whole escrow back and the agent is untouched.
+    // Only the task account's own rent stays out until it is closed.
+    assert!(banks_client.get_account(vault_address).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance - task_rent
+    );
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.compute_credits, initial_credits);
+    assert_eq!(agent.open_tasks, 1);
+
+    // Cancelling twice, or cancelling started / finished tasks, is rejected
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    for task_address in [task_address, seeded_tasks[0], seeded_tasks[1]] {
+        let mut transaction = Transaction::new_with_payer(
+            &[cancel_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                requester.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner_keypair], blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+        );
+    }
+
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance - task_rent
+    );
+
+    // The requester may cancel an assigned task too, and the task records
+    // when it was created
+    let (task_address, _) = find_task_address(&agent_address, 3, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            3,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            400,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let now = banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.requester, requester.pubkey());
+    assert_eq!(task.created_at, now);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            requester.pubkey(),
+            requester.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Cancelled);
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance - 2 * task_rent
+    );
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.open_tasks, 1);
+    assert_eq!(agent.tasks_cancelled, 2);
+    assert_eq!(agent.tasks_failed, 0);
+}
+
+#[tokio::test]
+async fn test_fail_task_refunds_and_penalizes() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    // Low reputation so the second failure saturates at zero
+    let initial_credits = 1000;
+    let initial_reputation = REPUTATION_PENALTY + 5;
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: initial_credits,
+        reputation_score: initial_reputation,
+        tasks_completed: 1,
+        is_active: true,
+        bump,
+        tasks_created: 4,
+        open_tasks: 3,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let statuses = [
+        TaskStatus::Pending,
+        TaskStatus::InProgress,
+        TaskStatus::InProgress,
+        TaskStatus::Completed,
+    ];
+    let mut tasks = vec![];
+    for (index, status) in statuses.into_iter().enumerate() {
+        let (task_address, task_bump) =
+            find_task_address(&agent_address, index as u64, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            status,
+            result_hash: [0; 32],
+            payment_amount: 100,
+            index: index as u64,
+            bump: task_bump,
+            failure_code: 0,
+            requester,
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            payment_mint: None,
+            open_listing: false,
+            started_at: 0,
+            deadline: 0,
+            completed_at: 0,
+            payment_pending: false,
+            commitment: None,
+            verified: false,
+            created_at: 0,
+            expected_result_hash: None,
+            tip_reputation: 0,
+            parent: None,
+            shard_index: 0,
+            shard_count: 0,
+            reissued: false,
+            failure_reason: None,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            failed_at: 0,
+            insurance_claimed: false,
+            token_program: TokenProgram::Spl,
+            payment_usd_cents: 0,
+            description_hash: [0; 32],
+            held_refund: 0,
+            result_data: Vec::new(),
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        if task.status != TaskStatus::Completed {
+            let (vault_address, _) = find_vault_address(&task_address, &program_id);
+            program_test.add_account(vault_address, vault_account_with(task.payment_amount));
+        }
+        tasks.push(task_address);
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let escrow = 100 + Rent::default().minimum_balance(0);
+
+    // Pending and Completed tasks can't fail
+    for task_address in [tasks[0], tasks[3]] {
+        let mut transaction = Transaction::new_with_payer(
+            &[fail_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                requester,
+                treasury,
+                FailureReason::AgentError,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+        );
+    }
+
+    // Someone other than the agent owner can't fail the task
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            tasks[1],
+            agent_address,
+            intruder.pubkey(),
+            requester,
+            treasury,
+            FailureReason::AgentError,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            tasks[1],
+            agent_address,
+            owner_keypair.pubkey(),
+            requester,
+            treasury,
+            FailureReason::Other(7),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(tasks[1]).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert_eq!(task.failure_reason, Some(FailureReason::Other(7)));
+    assert_eq!(task.failure_code, 255);
+
+    // The escrow goes back to the requester, credits are untouched
+    let (vault_address, _) = find_vault_address(&tasks[1], &program_id);
+    assert!(banks_client.get_account(vault_address).await.unwrap().is_none());
+    assert_eq!(banks_client.get_balance(requester).await.unwrap(), escrow);
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.compute_credits, initial_credits);
+    assert_eq!(agent.reputation_score, initial_reputation - REPUTATION_PENALTY);
+    assert_eq!(agent.tasks_completed, 1);
+
+    // A failed task can't fail again
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            tasks[1],
+            agent_address,
+            owner_keypair.pubkey(),
+            requester,
+            treasury,
+            FailureReason::Other(7),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // The second failure takes reputation down to zero, not below
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            tasks[2],
+            agent_address,
+            owner_keypair.pubkey(),
+            requester,
+            treasury,
+            FailureReason::OutOfTime,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.compute_credits, initial_credits);
+    assert_eq!(agent.reputation_score, 0);
+    assert_eq!(banks_client.get_balance(requester).await.unwrap(), 2 * escrow);
+}
+
+#[tokio::test]
+async fn test_close_task_reclaims_rent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let requester = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 1,
+        is_active: true,
+        bump,
+        tasks_created: 5,
+        open_tasks: 2,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let statuses = [
+        TaskStatus::Pending,
+        TaskStatus::InProgress,
+        TaskStatus::Completed,
+        TaskStatus::Failed,
+        TaskStatus::Cancelled,
+    ];
+    let mut tasks = vec![];
+    for (index, status) in statuses.into_iter().enumerate() {
+        let (task_address, task_bump) =
+            find_task_address(&agent_address, index as u64, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 3600,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            status,
+            result_hash: [0; 32],
+            payment_amount: 100,
+            index: index as u64,
+            bump: task_bump,
+            failure_code: 0,
+            requester: requester.pubkey(),
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            payment_mint: None,
+            open_listing: false,
+            started_at: 0,
+            deadline: 0,
+            completed_at: 0,
+            payment_pending: false,
+            commitment: None,
+            verified: false,
+            created_at: 0,
+            expected_result_hash: None,
+            tip_reputation: 0,
+            parent: None,
+            shard_index: 0,
+            shard_count: 0,
+            reissued: false,
+            failure_reason: None,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            failed_at: 0,
+            insurance_claimed: false,
+            token_program: TokenProgram::Spl,
+            payment_usd_cents: 0,
+            description_hash: [0; 32],
+            held_refund: 0,
+            result_data: Vec::new(),
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        tasks.push(task_address);
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let destination = Pubkey::new_unique();
+
+    // Open tasks can't be closed
+    for task_address in [tasks[0], tasks[1]] {
+        let mut transaction = Transaction::new_with_payer(
+            &[close_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                destination,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::TaskStillOpen as u32),
+        );
+    }
+
+    // Only the agent owner may close
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[close_task_instruction(
+            program_id,
+            tasks[2],
+            agent_address,
+            intruder.pubkey(),
+            intruder.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // The agent owner can only close a completed task
+    for task_address in [tasks[3], tasks[4]] {
+        let mut transaction = Transaction::new_with_payer(
+            &[close_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                destination,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::MissingRequiredSignature,
+        );
+    }
+
+    let mut expected_balance = 0;
+    for (task_address, signer) in [
+        (tasks[2], &owner_keypair),
+        (tasks[3], &requester),
+        (tasks[4], &requester),
+    ] {
+        let task_lamports = banks_client
+            .get_account(task_address)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        expected_balance += task_lamports;
+
+        let mut transaction = Transaction::new_with_payer(
+            &[close_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                signer.pubkey(),
+                destination,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, signer], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert!(banks_client.get_account(task_address).await.unwrap().is_none());
+        assert_eq!(banks_client.get_balance(destination).await.unwrap(), expected_balance);
+    }
+
+    // Open tasks are left untouched
+    for task_address in [tasks[0], tasks[1]] {
+        let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+        ComputeTask::unpack(&task_account.data).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_deregister_agent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 50,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    let (credit_vault, _) = find_credit_vault_address(&agent_address, &program_id);
+    program_test.add_account(credit_vault, credit_vault_account_with(50));
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Open a task and withdraw the credits, so only the task blocks deregistration
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &payer.pubkey(),
+                ComputeRequirements {
+                    cpu_units: 100,
+                    memory_mb: 512,
+                    storage_mb: 1024,
+                    max_time_seconds: 3600,
+                    min_reputation: 0,
+                    min_stake: 0,
+                    single_step: true,
+                    gpu_units: 0,
+                    network_mbps: 0,
+                },
+                50,
+            ),
+            instruction::withdraw_credits(
+                &program_id,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                50,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deregister_agent_instruction(program_id, owner_keypair.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentHasOpenBalance as u32),
+    );
+
+    // Cancel the task and deposit again, so only the credits block it
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            cancel_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                payer.pubkey(),
+            ),
+            instruction::deposit_credits(
+                &program_id,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                50,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[deregister_agent_instruction(program_id, owner_keypair.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentHasOpenBalance as u32),
+    );
+
+    // Withdraw the credits, then the agent can be closed
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            50,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_lamports = banks_client
+        .get_account(agent_address)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+    let owner_balance = banks_client.get_balance(owner_keypair.pubkey()).await.unwrap();
+    let vault_reserve = banks_client.get_balance(credit_vault).await.unwrap();
+    assert_eq!(vault_reserve, Rent::default().minimum_balance(0));
+
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[deregister_agent_instruction(program_id, owner_keypair.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Both the agent's rent and the credit vault's reserve go back to the owner
+    assert!(banks_client.get_account(agent_address).await.unwrap().is_none());
+    assert!(banks_client.get_account(credit_vault).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+        owner_balance + agent_lamports + vault_reserve
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_agent_ownership() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let new_owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 1000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    let (credit_vault, _) = find_credit_vault_address(&agent_address, &program_id);
+    program_test.add_account(credit_vault, credit_vault_account_with(1000));
+    program_test.add_account(new_owner_keypair.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The new owner has to consent by signing
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_borsh(
+            program_id,
+            &AIInfraInstruction::TransferAgentOwnership {
+                new_owner: new_owner_keypair.pubkey(),
+            },
+            vec![
+                AccountMeta::new(agent_address, false),
+                AccountMeta::new_readonly(owner_keypair.pubkey(), true),
+                AccountMeta::new_readonly(new_owner_keypair.pubkey(), false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_borsh(
+            program_id,
+            &AIInfraInstruction::TransferAgentOwnership {
+                new_owner: new_owner_keypair.pubkey(),
+            },
+            vec![
+                AccountMeta::new(agent_address, false),
+                AccountMeta::new_readonly(owner_keypair.pubkey(), true),
+                AccountMeta::new_readonly(new_owner_keypair.pubkey(), true),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair, &new_owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.owner, new_owner_keypair.pubkey());
+    assert_eq!(agent.registrar, owner_keypair.pubkey());
+
+    // The old owner has lost control of the credits
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            100,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let new_owner_balance = banks_client.get_balance(new_owner_keypair.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &new_owner_keypair.pubkey(),
+            100,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &new_owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.compute_credits, 900);
+    assert_eq!(
+        banks_client.get_balance(new_owner_keypair.pubkey()).await.unwrap(),
+        new_owner_balance + 100
+    );
+}
+
+#[tokio::test]
+async fn test_token_payments() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    // Run the token programs natively alongside this one
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+    program_test.add_program(
+        "spl_associated_token_account",
+        spl_associated_token_account::id(),
+        processor!(spl_associated_token_account::processor::process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+
+    // A mock USDC mint, plus an unrelated mint for the mismatch case
+    let mint = Pubkey::new_unique();
+    let other_mint = Pubkey::new_unique();
+    program_test.add_account(mint, mint_account());
+    program_test.add_account(other_mint, mint_account());
+
+    let requester_token = get_associated_token_address(&requester.pubkey(), &mint);
+    let requester_other_token = get_associated_token_address(&requester.pubkey(), &other_mint);
+    let owner_token = get_associated_token_address(&owner_keypair.pubkey(), &mint);
+    program_test.add_account(requester_token, token_account_with(mint, requester.pubkey(), 1000));
+    program_test.add_account(
+        requester_other_token,
+        token_account_with(other_mint, requester.pubkey(), 1000),
+    );
+    program_test.add_account(owner_token, token_account_with(mint, owner_keypair.pubkey(), 0));
+    // Fee-free config, so completion pays out exactly the task payment
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Paying from an account of another mint is rejected
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task_with_token_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            0,
+            requester.pubkey(),
+            requester_other_token,
+            mint,
+            spl_token::id(),
+            400,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TokenMintMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task_with_token_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            0,
+            requester.pubkey(),
+            requester_token,
+            mint,
+            spl_token::id(),
+            400,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let vault_token = get_associated_token_address(&vault_address, &mint);
+    assert_eq!(token_balance(&mut banks_client, requester_token).await, 600);
+    assert_eq!(token_balance(&mut banks_client, vault_token).await, 400);
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.payment_mint, Some(mint));
+    assert_eq!(task.payment_amount, 400);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The payout has to land in a token account the agent owner controls
+    let mut transaction = Transaction::new_with_payer(
+        &[with_token_escrow(
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            task_address,
+            mint,
+            spl_token::id(),
+            requester_token,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TokenOwnerMismatch as u32),
+    );
+
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let vault_token_rent = banks_client.get_balance(vault_token).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[with_token_escrow(
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            task_address,
+            mint,
+            spl_token::id(),
+            owner_token,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Tokens go to the agent owner, the escrow account's rent to the requester
+    assert_eq!(token_balance(&mut banks_client, owner_token).await, 400);
+    assert!(banks_client.get_account(vault_token).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + vault_token_rent
+    );
+
+    // A cancelled token task refunds the requester's tokens
+    let (task_address, _) = find_task_address(&agent_address, 1, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task_with_token_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            1,
+            requester.pubkey(),
+            requester_token,
+            mint,
+            spl_token::id(),
+            100,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(token_balance(&mut banks_client, requester_token).await, 500);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[with_token_escrow(
+            cancel_task_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                owner_keypair.pubkey(),
+                requester.pubkey(),
+            ),
+            task_address,
+            mint,
+            spl_token::id(),
+            requester_token,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let vault_token = get_associated_token_address(&vault_address, &mint);
+    assert_eq!(token_balance(&mut banks_client, requester_token).await, 600);
+    assert!(banks_client.get_account(vault_token).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_token_2022_payments() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    program_test.add_program(
+        "spl_token_2022",
+        spl_token_2022::id(),
+        processor!(spl_token_2022::processor::Processor::process),
+    );
+    program_test.add_program(
+        "spl_associated_token_account",
+        spl_associated_token_account::id(),
+        processor!(spl_associated_token_account::processor::process_instruction),
+    );
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let token_program = spl_token_2022::id();
+    let send = |instructions: &[Instruction], signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), recent_blockhash);
+        transaction
+    };
+
+    // A Token-2022 mint withholding a 1% fee on every transfer
+    let mint = Keypair::new();
+    let mint_len = spl_token_2022::extension::ExtensionType::get_account_len::<
+        spl_token_2022::state::Mint,
+    >(&[spl_token_2022::extension::ExtensionType::TransferFeeConfig]);
+    let requester_token = get_associated_token_address_with_program_id(
+        &requester.pubkey(),
+        &mint.pubkey(),
+        &token_program,
+    );
+    let owner_token = get_associated_token_address_with_program_id(
+        &owner.pubkey(),
+        &mint.pubkey(),
+        &token_program,
+    );
+    banks_client
+        .process_transaction(send(
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &mint.pubkey(),
+                    Rent::default().minimum_balance(mint_len),
+                    mint_len as u64,
+                    &token_program,
+                ),
+                initialize_transfer_fee_config(
+                    &token_program,
+                    &mint.pubkey(),
+                    None,
+                    None,
+                    100,
+                    u64::MAX,
+                )
+                .unwrap(),
+                spl_token_2022::instruction::initialize_mint(
+                    &token_program,
+                    &mint.pubkey(),
+                    &payer.pubkey(),
+                    None,
+                    6,
+                )
+                .unwrap(),
+                create_associated_token_account(
+                    &payer.pubkey(),
+                    &requester.pubkey(),
+                    &mint.pubkey(),
+                    &token_program,
+                ),
+                create_associated_token_account(
+                    &payer.pubkey(),
+                    &owner.pubkey(),
+                    &mint.pubkey(),
+                    &token_program,
+                ),
+                spl_token_2022::instruction::mint_to(
+                    &token_program,
+                    &mint.pubkey(),
+                    &requester_token,
+                    &payer.pubkey(),
+                    &[],
+                    10_000,
+                )
+                .unwrap(),
+                instruction::register_agent(&program_id, &agent_address, &owner.pubkey()),
+            ],
+            &[&mint, &owner],
+        ))
+        .await
+        .unwrap();
+
+    // The escrow holds, and the task pays, what's left after the fee
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    banks_client
+        .process_transaction(send(
+            &[create_task_with_token_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                0,
+                requester.pubkey(),
+                requester_token,
+                mint.pubkey(),
+                token_program,
+                1_000,
+            )],
+            &[&requester],
+        ))
+        .await
+        .unwrap();
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let vault_token = get_associated_token_address_with_program_id(
+        &vault_address,
+        &mint.pubkey(),
+        &token_program,
+    );
+    assert_eq!(token_balance(&mut banks_client, requester_token).await, 9_000);
+    assert_eq!(token_balance(&mut banks_client, vault_token).await, 990);
+    let account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!((task.payment_amount, task.token_program), (990, TokenProgram::Token2022));
+
+    banks_client
+        .process_transaction(send(
+            &[instruction::start_task(&program_id, &task_address, &agent_address, &owner.pubkey())],
+            &[&owner],
+        ))
+        .await
+        .unwrap();
+
+    // Settling through the original token program is rejected
+    let complete = |token_program| {
+        with_token_escrow(
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            task_address,
+            mint.pubkey(),
+            token_program,
+            owner_token,
+        )
+    };
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[complete(spl_token::id())], &[&owner])).await,
+        InstructionError::IncorrectProgramId,
+    );
+
+    // The payout pays the fee again on the way out, and the escrow's withheld
+    // fee is harvested to the mint so the escrow can close
+    banks_client.process_transaction(send(&[complete(token_program)], &[&owner])).await.unwrap();
+    assert_eq!(token_balance(&mut banks_client, owner_token).await, 980);
+    assert!(banks_client.get_account(vault_token).await.unwrap().is_none());
+    let account = banks_client.get_account(mint.pubkey()).await.unwrap().unwrap();
+    let mint_state =
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap();
+    let fees = mint_state.get_extension::<TransferFeeConfig>().unwrap();
+    assert_eq!(u64::from(fees.withheld_amount), 10);
+}
+
+#[tokio::test]
+async fn test_protocol_fee() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+    program_test.add_program(
+        "spl_associated_token_account",
+        spl_associated_token_account::id(),
+        processor!(spl_associated_token_account::processor::process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+
+    let admin = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(admin.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(treasury, wallet_account());
+
+    let mint = Pubkey::new_unique();
+    let requester_token = get_associated_token_address(&requester.pubkey(), &mint);
+    let owner_token = get_associated_token_address(&owner_keypair.pubkey(), &mint);
+    let treasury_token = get_associated_token_address(&treasury, &mint);
+    program_test.add_account(mint, mint_account());
+    program_test.add_account(requester_token, token_account_with(mint, requester.pubkey(), 1000));
+    program_test.add_account(owner_token, token_account_with(mint, owner_keypair.pubkey(), 0));
+    program_test.add_account(treasury_token, token_account_with(mint, treasury, 0));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The fee is capped
+    let mut transaction = Transaction::new_with_payer(
+        &[initialize_config_instruction(
+            program_id,
+            payer.pubkey(),
+            admin.pubkey(),
+            fee_params(MAX_FEE_BPS + 1, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::FeeTooHigh as u32),
+    );
+
+    // 2.5%
+    let mut transaction = Transaction::new_with_payer(
+        &[initialize_config_instruction(
+            program_id,
+            payer.pubkey(),
+            admin.pubkey(),
+            fee_params(250, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (config_address, _) = find_config_address(&program_id);
+    let config_account = banks_client.get_account(config_address).await.unwrap().unwrap();
+    let config = Config::unpack(&config_account.data).unwrap();
+    assert_eq!(config.admin, admin.pubkey());
+    assert_eq!(config.fee_bps, 250);
+    assert_eq!(config.treasury, treasury);
+
+    // The config can only be initialized once
+    let mut transaction = Transaction::new_with_payer(
+        &[initialize_config_instruction(
+            program_id,
+            payer.pubkey(),
+            admin.pubkey(),
+            fee_params(0, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    // Only the admin may change it
+    let mut transaction = Transaction::new_with_payer(
+        &[update_config_instruction(
+            program_id,
+            owner_keypair.pubkey(),
+            owner_keypair.pubkey(),
+            fee_params(0, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // A lamport task of 1001 owes 25.025 in fees, rounded down to 25
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let payment_amount = 1001;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &requester.pubkey(),
+                single_step_requirements(),
+                payment_amount,
+            ),
+            instruction::start_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The fee can't be diverted away from the configured treasury
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            &requester.pubkey(),
+            &requester.pubkey(),
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let owner_balance = banks_client.get_balance(owner_keypair.pubkey()).await.unwrap();
+    let treasury_balance = banks_client.get_balance(treasury).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(banks_client.get_balance(treasury).await.unwrap(), treasury_balance + 25);
+    assert_eq!(
+        banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+        owner_balance + payment_amount - 25
+    );
+
+    // Token payments pay the fee into the treasury's token account
+    let (task_address, _) = find_task_address(&agent_address, 1, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_task_with_token_instruction(
+                program_id,
+                task_address,
+                agent_address,
+                1,
+                requester.pubkey(),
+                requester_token,
+                mint,
+                spl_token::id(),
+                400,
+            ),
+            instruction::start_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut complete = with_token_escrow(
+        instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner_keypair.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [2; 32],
+        ),
+        task_address,
+        mint,
+        spl_token::id(),
+        owner_token,
+    );
+    complete.accounts.push(AccountMeta::new(treasury_token, false));
+    let mut transaction = Transaction::new_with_payer(&[complete], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(token_balance(&mut banks_client, treasury_token).await, 10);
+    assert_eq!(token_balance(&mut banks_client, owner_token).await, 390);
+
+    // The admin can turn the fee off again
+    let mut transaction = Transaction::new_with_payer(
+        &[update_config_instruction(
+            program_id,
+            admin.pubkey(),
+            admin.pubkey(),
+            fee_params(0, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let config_account = banks_client.get_account(config_address).await.unwrap().unwrap();
+    let config = Config::unpack(&config_account.data).unwrap();
+    assert_eq!(config.fee_bps, 0);
+}
+
+#[tokio::test]
+async fn test_config_admin_rotation() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    // A config-shaped account outside the config PDA
+    let (config_address, config_bump) = find_config_address(&program_id);
+    let forged_config = Pubkey::new_unique();
+    let forged = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        bump: config_bump,
+        ..Config::default()
+    };
+    program_test.add_account(
+        forged_config,
+        Account {
+            lamports: Rent::default().minimum_balance(Config::LEN),
+            data: config_data(&forged),
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let first_admin = Keypair::new();
+    let second_admin = Keypair::new();
+    let treasury = Pubkey::new_unique();
+
+    // The payer sets up the config on behalf of another admin
+    let mut transaction = Transaction::new_with_payer(
+        &[initialize_config_instruction(
+            program_id,
+            payer.pubkey(),
+            first_admin.pubkey(),
+            fee_params(0, treasury, 100),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Payments below the minimum are rejected
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &payer.pubkey(),
+            single_step_requirements(),
+            99,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::PaymentTooLow as u32),
+    );
+
+    // Handlers only read the config from its PDA
+    let mut create = instruction::create_task(
+        &program_id,
+        &task_address,
+        &agent_address,
+        0,
+        &payer.pubkey(),
+        single_step_requirements(),
+        100,
+    );
+    create.accounts[5] = AccountMeta::new_readonly(forged_config, false);
+    let mut transaction = Transaction::new_with_payer(&[create], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidSeeds,
+    );
+
+    // The admin hands the config over to a new key
+    let mut transaction = Transaction::new_with_payer(
+        &[update_config_instruction(
+            program_id,
+            first_admin.pubkey(),
+            second_admin.pubkey(),
+            fee_params(0, treasury, 100),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &first_admin], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // after which the previous admin is locked out
+    let mut transaction = Transaction::new_with_payer(
+        &[update_config_instruction(
+            program_id,
+            first_admin.pubkey(),
+            first_admin.pubkey(),
+            fee_params(0, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &first_admin], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[update_config_instruction(
+            program_id,
+            second_admin.pubkey(),
+            second_admin.pubkey(),
+            fee_params(0, treasury, 0),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &second_admin], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let config_account = banks_client.get_account(config_address).await.unwrap().unwrap();
+    let config = Config::unpack(&config_account.data).unwrap();
+    assert_eq!(config.admin, second_admin.pubkey());
+    assert_eq!(config.min_payment, 0);
+
+    // With the minimum lifted smaller payments go through
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &payer.pubkey(),
+            single_step_requirements(),
+            50,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_program_pause() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+
+    let admin = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(requester.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (pending_task, _) = find_task_address(&agent_address, 0, &program_id);
+    let (running_task, _) = find_task_address(&agent_address, 1, &program_id);
+    let (blocked_task, _) = find_task_address(&agent_address, 2, &program_id);
+
+    // Queue one task and start another, and fund the agent, before pausing
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            initialize_config_instruction(
+                program_id,
+                payer.pubkey(),
+                admin.pubkey(),
+                fee_params(0, treasury, 0),
+            ),
+            instruction::create_task(
+                &program_id,
+                &pending_task,
+                &agent_address,
+                0,
+                &requester.pubkey(),
+                single_step_requirements(),
+                100,
+            ),
+            instruction::create_task(
+                &program_id,
+                &running_task,
+                &agent_address,
+                1,
+                &requester.pubkey(),
+                single_step_requirements(),
+                100,
+            ),
+            instruction::start_task(
+                &program_id,
+                &running_task,
+                &agent_address,
+                &owner_keypair.pubkey(),
+            ),
+            instruction::deposit_credits(
+                &program_id,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                500,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Only the admin can pause
+    let mut transaction = Transaction::new_with_payer(
+        &[set_paused_instruction(program_id, owner_keypair.pubkey(), true)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_paused_instruction(program_id, admin.pubkey(), true)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (config_address, _) = find_config_address(&program_id);
+    let config_account = banks_client.get_account(config_address).await.unwrap().unwrap();
+    assert!(Config::unpack(&config_account.data).unwrap().paused);
+
+    // Task and credit activity is blocked
+    let blocked = [
+        (
+            instruction::create_task(
+                &program_id,
+                &blocked_task,
+                &agent_address,
+                2,
+                &requester.pubkey(),
+                single_step_requirements(),
+                100,
+            ),
+            &requester,
+        ),
+        (
+            instruction::start_task(
+                &program_id,
+                &pending_task,
+                &agent_address,
+                &owner_keypair.pubkey(),
+            ),
+            &owner_keypair,
+        ),
+        (
+            instruction::complete_task(
+                &program_id,
+                &running_task,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            &owner_keypair,
+        ),
+        (
+            instruction::deposit_credits(
+                &program_id,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                100,
+                None,
+            ),
+            &owner_keypair,
+        ),
+        (
+            instruction::withdraw_credits(
+                &program_id,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                100,
+                None,
+            ),
+            &owner_keypair,
+        ),
+    ];
+    for (instruction, signer) in blocked {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, signer], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::ProgramPaused as u32),
+        );
+    }
+
+    // Refunds still go through
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_task_instruction(
+            program_id,
+            pending_task,
+            agent_address,
+            owner_keypair.pubkey(),
+            requester.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(pending_task).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Cancelled);
+
+    // Once unpaused the running task can be completed
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_paused_instruction(program_id, admin.pubkey(), false),
+            instruction::complete_task(
+                &program_id,
+                &running_task,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(running_task).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+}
+
+#[tokio::test]
+async fn test_task_tags() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let owners = [Keypair::new(), Keypair::new()];
+    for owner in &owners {
+        program_test.add_account(owner.pubkey(), wallet_account());
+    }
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // One agent serves only category 3, the other 3 and 7
+    let mut agents = vec![];
+    for (owner, tags) in owners.into_iter().zip([&[3][..], &[3, 7]]) {
+        create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+            .await
+            .unwrap();
+        let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::set_accepted_tags(&program_id, &agent_address, &owner.pubkey(), tags)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+        let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+        let agent = AIAgent::unpack(&agent_account.data).unwrap();
+        assert!(agent.accepts_tag(3) && !agent.accepts_tag(4));
+        agents.push((agent_address, owner));
+    }
+
+    // An open listing tagged 7
+    let tags = [7, 0, 0, 0, 0, 0, 0, 0];
+    let (task_address, _) = find_task_address(&Pubkey::default(), 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_tagged_task(
+            &program_id,
+            &task_address,
+            &Pubkey::default(),
+            0,
+            &requester.pubkey(),
+            single_step_requirements(),
+            300,
+            0,
+            tags,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let tags_end = ComputeTask::TAGS_OFFSET + TASK_TAGS;
+    assert_eq!(task_account.data[ComputeTask::TAGS_OFFSET..tags_end], tags);
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().tags, tags);
+
+    // The agent accepting only 3 can't claim it
+    let (agent_3, owner_3) = &agents[0];
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, task_address, *agent_3, owner_3.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner_3], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TagMismatch as u32),
+    );
+
+    // but the one also accepting 7 can
+    let (agent_7, owner_7) = &agents[1];
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, task_address, *agent_7, owner_7.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner_7], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!((task.agent, task.status), (*agent_7, TaskStatus::InProgress));
+    assert_eq!(task.tags, tags);
+}
+
+#[tokio::test]
+async fn test_inline_result_data() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let requester = Keypair::new();
+    let owner = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(owner.pubkey(), wallet_account());
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &requester.pubkey(),
+                single_step_requirements(),
+                300,
+            ),
+            instruction::start_task(&program_id, &task_address, &agent_address, &owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let complete = |result_data: Vec<u8>| {
+        instruction::complete_task_with_data(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_data,
+        )
+    };
+
+    // A payload over the cap is rejected
+    let mut transaction = Transaction::new_with_payer(
+        &[complete(vec![1; MAX_RESULT_DATA_LEN + 1])],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::ResultTooLarge as u32),
+    );
+
+    // as is one that isn't the result its hash stands for
+    let result_data = b"inference output".to_vec();
+    let mut mismatched = complete(result_data.clone());
+    mismatched.data = AIInfraInstruction::CompleteTask {
+        task_id: task_address,
+        result_hash: [9; 32],
+        result_data: Some(result_data.clone()),
+    }
+    .pack();
+    let mut transaction = Transaction::new_with_payer(&[mismatched], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::ResultMismatch as u32),
+    );
+
+    // A matching payload is stored, the owner paying the rent it adds
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[complete(result_data.clone())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let grown_len = ComputeTask::LEN + result_data.len();
+    assert_eq!(task_account.data.len(), grown_len);
+    assert_eq!(task_account.lamports, Rent::default().minimum_balance(grown_len));
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.result_hash, hash(&result_data).to_bytes());
+    assert_eq!(task.result_data, result_data);
+    let rent_delta = Rent::default().minimum_balance(grown_len)
+        - Rent::default().minimum_balance(ComputeTask::LEN);
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + 300 - rent_delta
+    );
+}
+
+#[tokio::test]
+async fn test_open_task_claims() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let mut agents = vec![];
+    for _ in 0..2 {
+        let owner = Keypair::new();
+        let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: owner.pubkey(),
+            compute_credits: 0,
+            reputation_score: 100,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: owner.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        program_test.add_account(owner.pubkey(), wallet_account());
+        agents.push((agent_address, owner));
+    }
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (winner, winner_owner) = &agents[0];
+    let (loser, loser_owner) = &agents[1];
+
+    // Listing without an agent numbers the task from the config
+    let (task_address, _) = find_task_address(&Pubkey::default(), 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &Pubkey::default(),
+            0,
+            &requester.pubkey(),
+            single_step_requirements(),
+            300,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert!(task.open_listing);
+    assert_eq!(task.agent, Pubkey::default());
+    assert_eq!(task.status, TaskStatus::Pending);
+
+    let (config_address, _) = find_config_address(&program_id);
+    let config_account = banks_client.get_account(config_address).await.unwrap().unwrap();
+    assert_eq!(Config::unpack(&config_account.data).unwrap().open_tasks_created, 1);
+
+    // The first claim wins and starts the task
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, task_address, *winner, winner_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, winner_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.agent, *winner);
+    assert_eq!(task.status, TaskStatus::InProgress);
+
+    let agent_account = banks_client.get_account(*winner).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().open_tasks, 1);
+
+    // and a later claim loses
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, task_address, *loser, loser_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, loser_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskAlreadyClaimed as u32),
+    );
+
+    // The claimed task settles like any other
+    let owner_balance = banks_client.get_balance(winner_owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            winner,
+            &winner_owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, winner_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(winner_owner.pubkey()).await.unwrap(),
+        owner_balance + 300
+    );
+
+    let agent_account = banks_client.get_account(*winner).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.tasks_completed, 1);
+    assert_eq!(agent.open_tasks, 0);
+
+    // The requester can withdraw a listing nobody claimed and reclaim its rent
+    let (task_address, _) = find_task_address(&Pubkey::default(), 1, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &Pubkey::default(),
+            1,
+            &requester.pubkey(),
+            single_step_requirements(),
+            300,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let task_rent = banks_client.get_balance(task_address).await.unwrap();
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let escrow = banks_client.get_balance(vault_address).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            cancel_task_instruction(
+                program_id,
+                task_address,
+                Pubkey::default(),
+                requester.pubkey(),
+                requester.pubkey(),
+            ),
+            close_task_instruction(
+                program_id,
+                task_address,
+                Pubkey::default(),
+                requester.pubkey(),
+                requester.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert!(banks_client.get_account(task_address).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + escrow + task_rent
+    );
+}
+
+#[tokio::test]
+async fn test_bid_auction() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let mut bidders = vec![];
+    for _ in 0..3 {
+        let owner = Keypair::new();
+        let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: owner.pubkey(),
+            compute_credits: 0,
+            reputation_score: 100,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: owner.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        program_test.add_account(owner.pubkey(), wallet_account());
+        bidders.push((agent_address, owner));
+    }
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The requester lists a task with up to 300 in escrow
+    let (task_address, _) = find_task_address(&Pubkey::default(), 0, &program_id);
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &Pubkey::default(),
+            0,
+            &requester.pubkey(),
+            single_step_requirements(),
+            300,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Bids can't exceed the escrow
+    let (agent, owner) = &bidders[2];
+    let mut transaction = Transaction::new_with_payer(
+        &[submit_bid_instruction(program_id, task_address, *agent, owner.pubkey(), 301)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::BidTooHigh as u32),
+    );
+
+    for ((agent, owner), amount) in bidders.iter().zip([250, 200, 280]) {
+        let mut transaction = Transaction::new_with_payer(
+            &[submit_bid_instruction(program_id, task_address, *agent, owner.pubkey(), amount)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    let (winner, winner_owner) = &bidders[1];
+    let (bid_address, _) = find_bid_address(&task_address, winner, &program_id);
+    let bid_account = banks_client.get_account(bid_address).await.unwrap().unwrap();
+    let bid = Bid::unpack(&bid_account.data).unwrap();
+    assert_eq!(bid.amount, 200);
+    assert_eq!(bid.agent, *winner);
+
+    // Only the requester can accept
+    let mut transaction = Transaction::new_with_payer(
+        &[accept_bid_instruction(program_id, task_address, *winner, winner_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, winner_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // Accepting the lowest bid assigns the task and refunds the difference
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let vault_balance = banks_client.get_balance(vault_address).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[accept_bid_instruction(program_id, task_address, *winner, requester.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + 100
+    );
+    assert_eq!(banks_client.get_balance(vault_address).await.unwrap(), vault_balance - 100);
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.agent, *winner);
+    assert_eq!(task.payment_amount, 200);
+    assert_eq!(task.status, TaskStatus::Pending);
+
+    // The auction is over for everyone else
+    let (late_agent, late_owner) = &bidders[0];
+    let mut transaction = Transaction::new_with_payer(
+        &[accept_bid_instruction(program_id, task_address, *late_agent, requester.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskAlreadyClaimed as u32),
+    );
+
+    // The winner runs the task for the bid price
+    let owner_balance = banks_client.get_balance(winner_owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::start_task(&program_id, &task_address, winner, &winner_owner.pubkey()),
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                winner,
+                &winner_owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, winner_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(winner_owner.pubkey()).await.unwrap(),
+        owner_balance + 200
+    );
+    assert!(banks_client.get_account(vault_address).await.unwrap().is_none());
+
+    // New bids on a finished task are rejected
+    let mut transaction = Transaction::new_with_payer(
+        &[close_bid_instruction(program_id, task_address, *late_agent, late_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, late_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[submit_bid_instruction(program_id, task_address, *late_agent, late_owner.pubkey(), 100)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, late_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // Every bidder gets their bid rent back
+    for (agent, owner) in bidders.iter().skip(1) {
+        let (bid_address, _) = find_bid_address(&task_address, agent, &program_id);
+        let bid_rent = banks_client.get_balance(bid_address).await.unwrap();
+        let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        let mut transaction = Transaction::new_with_payer(
+            &[close_bid_instruction(program_id, task_address, *agent, owner.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert!(banks_client.get_account(bid_address).await.unwrap().is_none());
+        assert_eq!(
+            banks_client.get_balance(owner.pubkey()).await.unwrap(),
+            owner_balance + bid_rent
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_task_deadlines() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let admin = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    let initial_reputation = 100;
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: initial_reputation,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 2,
+        open_tasks: 2,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+
+    let config = Config {
+        admin: admin.pubkey(),
+        treasury,
+        reputation_penalty: REPUTATION_PENALTY,
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    // Both tasks have to finish within a few seconds of starting
+    let payment_amount = 1000;
+    let mut tasks = vec![];
+    for index in 0..2 {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements {
+                cpu_units: 100,
+                memory_mb: 512,
+                storage_mb: 1024,
+                max_time_seconds: 5,
+                min_reputation: 0,
+                min_stake: 0,
+                single_step: true,
+                gpu_units: 0,
+                network_mbps: 0,
+            },
+            status: TaskStatus::Pending,
+            result_hash: [0; 32],
+            payment_amount,
+            index,
+            bump: task_bump,
+            failure_code: 0,
+            requester,
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            payment_mint: None,
+            open_listing: false,
+            started_at: 0,
+            deadline: 0,
+            completed_at: 0,
+            payment_pending: false,
+            commitment: None,
+            verified: false,
+            created_at: 0,
+            expected_result_hash: None,
+            tip_reputation: 0,
+            parent: None,
+            shard_index: 0,
+            shard_count: 0,
+            reissued: false,
+            failure_reason: None,
+            priority: 0,
+            tags: [0; TASK_TAGS],
+            failed_at: 0,
+            insurance_claimed: false,
+            token_program: TokenProgram::Spl,
+            payment_usd_cents: 0,
+            description_hash: [0; 32],
+            held_refund: 0,
+            result_data: Vec::new(),
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        let (vault_address, _) = find_vault_address(&task_address, &program_id);
+        program_test.add_account(vault_address, vault_account_with(payment_amount));
+        tasks.push(task_address);
+    }
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+
+    let owner = owner_keypair.pubkey();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::start_task(&program_id, &tasks[0], &agent_address, &owner),
+            instruction::start_task(&program_id, &tasks[1], &agent_address, &owner),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // Starting the task records when it is due
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert!(task.started_at > 0);
+    assert_eq!(task.deadline, task.started_at + 5);
+
+    // Nobody can expire a task before its deadline
+    let mut transaction = Transaction::new_with_payer(
+        &[expire_task_instruction(program_id, tasks[1], agent_address, requester, treasury)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskNotExpired as u32),
+    );
+
+    context.warp_to_slot(100_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    // Without a late decay a task past its deadline can't be completed
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &tasks[0],
+            &agent_address,
+            &owner_keypair.pubkey(),
+            &requester,
+            &treasury,
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskExpired as u32),
+    );
+
+    // With a decay configured the late agent forfeits part of the payment
+    let owner_balance = context
+        .banks_client
+        .get_balance(owner_keypair.pubkey())
+        .await
+        .unwrap();
+    let params = ConfigParams {
+        treasury,
+        late_decay_bps: 2500,
+        reputation_penalty: REPUTATION_PENALTY,
+        ..ConfigParams::default()
+    };
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            update_config_instruction(program_id, admin.pubkey(), admin.pubkey(), params),
+            instruction::complete_task(
+                &program_id,
+                &tasks[0],
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester,
+                &treasury,
+                [2; 32],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin, &owner_keypair], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let reserve = Rent::default().minimum_balance(0);
+    let forfeit = payment_amount / 4;
+    assert_eq!(
+        context.banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+        owner_balance + payment_amount - forfeit
+    );
+    assert_eq!(
+        context.banks_client.get_balance(requester).await.unwrap(),
+        reserve + forfeit
+    );
+
+    // Anyone can expire the overdue task, refunding the requester in full
+    let mut transaction = Transaction::new_with_payer(
+        &[expire_task_instruction(program_id, tasks[1], agent_address, requester, treasury)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = context.banks_client.get_account(tasks[1]).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert_eq!(task.failure_reason, Some(FailureReason::Expired));
+    assert_eq!(
+        context.banks_client.get_balance(requester).await.unwrap(),
+        2 * reserve + forfeit + payment_amount
+    );
+
+    // One task each way, timed from start to the late completion
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    let completed = ComputeTask::unpack(&task_account.data).unwrap();
+    assert!(completed.completed_at > completed.deadline);
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.open_tasks, 0);
+    assert_eq!(agent.tasks_completed, 1);
+    assert_eq!(agent.tasks_failed, 1);
+    assert_eq!(agent.success_rate_bps(), Some(5_000));
+    assert_eq!(
+        agent.total_completion_seconds,
+        (completed.completed_at - completed.started_at) as u64
+    );
+    assert_eq!(agent.reputation_score, initial_reputation - REPUTATION_PENALTY);
+}
+
+#[tokio::test]
+async fn test_stale_agents() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let caller = Pubkey::new_unique();
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    program_test.add_account(caller, wallet_account());
+    let (agent_address, _) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+
+    // The config holds just enough above its rent reserve for one bounty
+    let bounty = 5000;
+    let config_rent = Rent::default().minimum_balance(Config::LEN);
+    let (config_address, config_bump) = find_config_address(&program_id);
+    let config = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        stale_after_seconds: 30,
+        stale_bounty: bounty,
+        bump: config_bump,
+        ..Config::default()
+    };
+    program_test.add_account(
+        config_address,
+        Account {
+            lamports: config_rent + bounty,
+            data: config_data(&config),
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+
+    // Registration counts as the first heartbeat
+    let mut transaction = Transaction::new_with_payer(
+        &[register_agent_instruction(program_id, owner_keypair.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert!(agent.last_heartbeat > 0);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deactivate_stale_agent_instruction(program_id, agent_address, caller)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentNotStale as u32),
+    );
+
+    context.warp_to_slot(100_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    // Requesters can't assign work to an agent that stopped checking in
+    let requirements = || ComputeRequirements {
+        cpu_units: 100,
+        memory_mb: 512,
+        storage_mb: 1024,
+        max_time_seconds: 3600,
+        min_reputation: 0,
+        min_stake: 0,
+        single_step: true,
+        gpu_units: 0,
+        network_mbps: 0,
+    };
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &payer.pubkey(),
+            requirements(),
+            1000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentStale as u32),
+    );
+
+    // Only the owner can vouch for the agent
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[heartbeat_instruction(program_id, agent_address, intruder.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // A heartbeat makes the agent assignable again
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            heartbeat_instruction(program_id, agent_address, owner_keypair.pubkey()),
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &payer.pubkey(),
+                requirements(),
+                1000,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let heartbeat = AIAgent::unpack(&agent_account.data).unwrap().last_heartbeat;
+    assert!(heartbeat > agent.last_heartbeat);
+
+    // Once it goes quiet again anyone can deactivate it and collect the bounty
+    context.warp_to_slot(200_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[deactivate_stale_agent_instruction(program_id, agent_address, caller)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert!(!AIAgent::unpack(&agent_account.data).unwrap().is_active);
+    assert_eq!(
+        context.banks_client.get_balance(caller).await.unwrap(),
+        LAMPORTS_PER_SOL + bounty
+    );
+    assert_eq!(
+        context.banks_client.get_balance(config_address).await.unwrap(),
+        config_rent
+    );
+
+    // An inactive agent can't be deactivated twice for another bounty
+    let other_caller = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[deactivate_stale_agent_instruction(program_id, agent_address, other_caller)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentNotActive as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_reputation_updates() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let treasury = Pubkey::new_unique();
+    let requester = Pubkey::new_unique();
+    let config = Config {
+        treasury,
+        reputation_bonus: 3,
+        reputation_penalty: 25,
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    // A newcomer with two running tasks and a veteran close to the cap with one
+    let newcomer = Keypair::new();
+    let veteran = Keypair::new();
+    let payment_amount = 1000;
+    let mut tasks = vec![];
+    for (owner, reputation_score, task_count) in
+        [(&newcomer, 100, 2), (&veteran, MAX_REPUTATION - 5, 1)]
+    {
+        let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: owner.pubkey(),
+            compute_credits: 0,
+            reputation_score,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: task_count,
+            open_tasks: task_count as u32,
+            registrar: owner.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        program_test.add_account(owner.pubkey(), wallet_account());
+
+        for index in 0..task_count {
+            let (task_address, task_bump) =
+                find_task_address(&agent_address, index, &program_id);
+            let task = ComputeTask {
+                account_type: AccountType::Task,
+                version: CURRENT_VERSION,
+                agent: agent_address,
+                requirements: ComputeRequirements {
+                    cpu_units: 100,
+                    memory_mb: 512,
+                    storage_mb: 1024,
+                    max_time_seconds: 0,
+                    min_reputation: 0,
+                    min_stake: 0,
+                    single_step: true,
+                    gpu_units: 0,
+                    network_mbps: 0,
+                },
+                status: TaskStatus::InProgress,
+                result_hash: [0; 32],
+                payment_amount,
+                index,
+                bump: task_bump,
+                failure_code: 0,
+                requester,
+                vault_bump: find_vault_address(&task_address, &program_id).1,
+                payment_mint: None,
+                open_listing: false,
+                started_at: 0,
+                deadline: 0,
+                completed_at: 0,
+                payment_pending: false,
+                commitment: None,
+                verified: false,
+                created_at: 0,
+                expected_result_hash: None,
+                tip_reputation: 0,
+                parent: None,
+                shard_index: 0,
+                shard_count: 0,
+                reissued: false,
+                failure_reason: None,
+                priority: 0,
+                tags: [0; TASK_TAGS],
+                failed_at: 0,
+                insurance_claimed: false,
+                token_program: TokenProgram::Spl,
+                payment_usd_cents: 0,
+                description_hash: [0; 32],
+                held_refund: 0,
+                result_data: Vec::new(),
+            };
+            program_test.add_account(task_address, task_account_with(&task, program_id));
+            let (vault_address, _) = find_vault_address(&task_address, &program_id);
+            program_test.add_account(vault_address, vault_account_with(payment_amount));
+            tasks.push((owner, agent_address, task_address));
+        }
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let reputation_of = |agent_account: Account| {
+        AIAgent::unpack(&agent_account.data).unwrap().reputation_score
+    };
+
+    // A 1000 lamport payment has ten significant bits, each worth the bonus
+    let (owner, agent_address, task_address) = tasks[0];
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner.pubkey(),
+            &requester,
+            &treasury,
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(reputation_of(agent_account), 130);
+
+    let (owner, agent_address, task_address) = tasks[1];
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            requester,
+            treasury,
+            FailureReason::AgentError,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(reputation_of(agent_account), 105);
+
+    // The veteran's gain stops at the cap
+    let (owner, agent_address, task_address) = tasks[2];
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner.pubkey(),
+            &requester,
+            &treasury,
+            [2; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(reputation_of(agent_account), MAX_REPUTATION);
+}
+
+#[tokio::test]
+async fn test_minimum_reputation() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let mut agents = vec![];
+    for reputation_score in [50, 500] {
+        let owner = Keypair::new();
+        let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: owner.pubkey(),
+            compute_credits: 0,
+            reputation_score,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: owner.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        program_test.add_account(owner.pubkey(), wallet_account());
+        agents.push((agent_address, owner));
+    }
+    let requester = Keypair::new();
+    program_test.add_account(requester.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (novice, novice_owner) = &agents[0];
+    let (expert, expert_owner) = &agents[1];
+    let requirements = || ComputeRequirements {
+        min_reputation: 200,
+        ..ComputeRequirements::default()
+    };
+
+    let (open_task, _) = find_task_address(&Pubkey::default(), 0, &program_id);
+    let (assigned_task, _) = find_task_address(novice, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &open_task,
+                &Pubkey::default(),
+                0,
+                &requester.pubkey(),
+                requirements(),
+                300,
+            ),
+            instruction::create_task(
+                &program_id,
+                &assigned_task,
+                novice,
+                0,
+                &requester.pubkey(),
+                requirements(),
+                300,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The novice can neither bid on nor claim the restricted listing
+    for instruction in [
+        submit_bid_instruction(program_id, open_task, *novice, novice_owner.pubkey(), 200),
+        claim_task_instruction(program_id, open_task, *novice, novice_owner.pubkey()),
+    ] {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, novice_owner], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::ReputationTooLow as u32),
+        );
+    }
+
+    // nor start a restricted task assigned to it directly
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(&program_id, &assigned_task, novice, &novice_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, novice_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::ReputationTooLow as u32),
+    );
+
+    // The expert clears the bar and takes the listing
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, open_task, *expert, expert_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, expert_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(open_task).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.agent, *expert);
+    assert_eq!(task.status, TaskStatus::InProgress);
+}
+
+#[tokio::test]
+async fn test_stake_collateral() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(treasury, wallet_account());
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    // Each failure slashes a fifth of the stake
+    let config = Config {
+        treasury,
+        slash_bps: 2000,
+        unstake_cooldown_seconds: 5,
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let owner = &owner_keypair;
+    let (stake_vault, _) = find_stake_vault_address(&agent_address, &program_id);
+    let reserve = Rent::default().minimum_balance(0);
+
+    let agent_state = |agent_account: Account| AIAgent::unpack(&agent_account.data).unwrap();
+
+    // The first stake also pays the vault's rent reserve
+    let owner_balance = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[stake_collateral_instruction(program_id, agent_address, owner.pubkey(), 10_000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance - 10_000 - reserve
+    );
+    assert_eq!(context.banks_client.get_balance(stake_vault).await.unwrap(), reserve + 10_000);
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(agent_state(agent_account).staked_lamports, 10_000);
+
+    // Requesters can demand more stake than the agent has put up
+    let requirements = |min_stake| ComputeRequirements {
+        min_stake,
+        ..ComputeRequirements::default()
+    };
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &requester.pubkey(),
+            requirements(10_001),
+            300,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::StakeTooLow as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &requester.pubkey(),
+                requirements(10_000),
+                300,
+            ),
+            instruction::start_task(&program_id, &task_address, &agent_address, &owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // The slash can only go to the configured treasury
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            Pubkey::new_unique(),
+            FailureReason::AgentError,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let treasury_balance = context.banks_client.get_balance(treasury).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[fail_task_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            FailureReason::AgentError,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(treasury).await.unwrap(),
+        treasury_balance + 2000
+    );
+    assert_eq!(context.banks_client.get_balance(stake_vault).await.unwrap(), reserve + 8000);
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(agent_state(agent_account).staked_lamports, 8000);
+
+    // Requesting an unstake moves nothing yet
+    let mut transaction = Transaction::new_with_payer(
+        &[unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 5000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = agent_state(agent_account);
+    assert_eq!(agent.staked_lamports, 8000);
+    assert_eq!(agent.unstake_amount, 5000);
+    assert!(agent.unstake_requested_at > 0);
+    assert_eq!(context.banks_client.get_balance(stake_vault).await.unwrap(), reserve + 8000);
+
+    // Collecting early, or a different amount, is refused
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 5000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::UnstakeCooldownActive as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 4000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Staked agents can't deregister
+    let mut transaction = Transaction::new_with_payer(
+        &[deregister_agent_instruction(program_id, owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentHasOpenBalance as u32),
+    );
+
+    // After the cooldown the request pays out
+    context.warp_to_slot(100_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+    let owner_balance = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 5000),
+            unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 3000),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + 5000
+    );
+    assert_eq!(context.banks_client.get_balance(stake_vault).await.unwrap(), reserve + 3000);
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = agent_state(agent_account);
+    assert_eq!(agent.staked_lamports, 3000);
+    assert_eq!(agent.unstake_amount, 3000);
+
+    // Once the rest is out, deregistering returns the vault's reserve too
+    context.warp_to_slot(200_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+    let agent_rent = context.banks_client.get_balance(agent_address).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            unstake_collateral_instruction(program_id, agent_address, owner.pubkey(), 3000),
+            deregister_agent_instruction(program_id, owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + 5000 + 3000 + reserve + agent_rent
+    );
+    assert!(context.banks_client.get_account(stake_vault).await.unwrap().is_none());
+    assert!(context.banks_client.get_account(agent_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_requester_credits() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner_keypair = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (requester_address, _) = find_requester_address(&requester.pubkey(), &program_id);
+    let (credit_vault, _) = find_credit_vault_address(&requester_address, &program_id);
+    let reserve = Rent::default().minimum_balance(0);
+
+    // The first deposit opens the requester account
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            deposit_requester_credits_instruction(program_id, requester.pubkey(), 5000),
+            withdraw_requester_credits_instruction(program_id, requester.pubkey(), 1000),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let requester_account = banks_client.get_account(requester_address).await.unwrap().unwrap();
+    let stored = Requester::unpack(&requester_account.data).unwrap();
+    assert_eq!(stored.owner, requester.pubkey());
+    assert_eq!(stored.credits, 4000);
+    assert_eq!(banks_client.get_balance(credit_vault).await.unwrap(), reserve + 4000);
+
+    // Nobody else can spend the requester's credits
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction {
+            accounts: vec![
+                AccountMeta::new(requester_address, false),
+                AccountMeta::new(intruder.pubkey(), true),
+                AccountMeta::new(credit_vault, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+            ],
+            ..withdraw_requester_credits_instruction(program_id, intruder.pubkey(), 1000)
+        }],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    // A task can't cost more than the credits on hand
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task_with_credits_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            0,
+            requester.pubkey(),
+            single_step_requirements(),
+            4001,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InsufficientCredits as u32),
+    );
+
+    // The payment moves from the credits into the task's escrow
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task_with_credits_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            0,
+            requester.pubkey(),
+            single_step_requirements(),
+            3000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let requester_account = banks_client.get_account(requester_address).await.unwrap().unwrap();
+    assert_eq!(Requester::unpack(&requester_account.data).unwrap().credits, 1000);
+    assert_eq!(banks_client.get_balance(credit_vault).await.unwrap(), reserve + 1000);
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    assert_eq!(banks_client.get_balance(vault_address).await.unwrap(), reserve + 3000);
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.requester, requester.pubkey());
+    assert_eq!(task.payment_amount, 3000);
+
+    // and settles like any other task
+    let owner_balance = banks_client.get_balance(owner_keypair.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::start_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+            ),
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner_keypair.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(owner_keypair.pubkey()).await.unwrap(),
+        owner_balance + 3000
+    );
+}
+
+#[tokio::test]
+async fn test_disputes() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let owner_keypair = Keypair::new();
+    let requester = Keypair::new();
+    let arbitrator = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(treasury, wallet_account());
+
+    // Payments wait out a 30 second window, and a lost dispute slashes a
+    // fifth of the stake
+    let config = Config {
+        treasury,
+        reputation_penalty: REPUTATION_PENALTY,
+        slash_bps: 2000,
+        dispute_window_seconds: 30,
+        arbitrator: arbitrator.pubkey(),
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    let task_count = 3;
+    let payment_amount = 1000;
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: task_count,
+        open_tasks: task_count as u32,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 10_000,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    let (stake_vault, _) = find_stake_vault_address(&agent_address, &program_id);
+    program_test.add_account(stake_vault, vault_account_with(10_000));
+
+    let mut tasks = vec![];
+    for index in 0..task_count {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: single_step_requirements(),
+            status: TaskStatus::InProgress,
+            payment_amount,
+            index,
+            bump: task_bump,
+            requester: requester.pubkey(),
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            ..ComputeTask::default()
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        let (vault_address, _) = find_vault_address(&task_address, &program_id);
+        program_test.add_account(vault_address, vault_account_with(payment_amount));
+        tasks.push(task_address);
+    }
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let owner = &owner_keypair;
+    let reserve = Rent::default().minimum_balance(0);
+
+    let task_state = |task_account: Account| ComputeTask::unpack(&task_account.data).unwrap();
+
+    // Completing a task inside the window holds its payment in the vault
+    let instructions: Vec<Instruction> = tasks
+        .iter()
+        .map(|&task_address| {
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            )
+        })
+        .collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    for &task_address in &tasks {
+        let task_account = context.banks_client.get_account(task_address).await.unwrap().unwrap();
+        let task = task_state(task_account);
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert!(task.payment_pending);
+        let (vault_address, _) = find_vault_address(&task_address, &program_id);
+        assert_eq!(
+            context.banks_client.get_balance(vault_address).await.unwrap(),
+            reserve + payment_amount
+        );
+    }
+
+    // The payment can't be claimed, nor the task closed, while the window is open
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_payment_instruction(
+            program_id,
+            tasks[0],
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DisputeWindowOpen as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_task_instruction(
+            program_id,
+            tasks[2],
+            agent_address,
+            owner.pubkey(),
+            owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskStillOpen as u32),
+    );
+
+    // Only the task's requester can dispute it
+    let impostor = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[dispute_task_instruction(program_id, tasks[0], impostor.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &impostor], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            dispute_task_instruction(program_id, tasks[0], requester.pubkey()),
+            dispute_task_instruction(program_id, tasks[1], requester.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    assert_eq!(task_state(task_account).status, TaskStatus::Disputed);
+
+    // Only the configured arbitrator can rule
+    let mut transaction = Transaction::new_with_payer(
+        &[resolve_dispute_instruction(
+            program_id,
+            tasks[0],
+            agent_address,
+            impostor.pubkey(),
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            true,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &impostor], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // Upholding the result pays the agent owner
+    let owner_balance = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[resolve_dispute_instruction(
+            program_id,
+            tasks[0],
+            agent_address,
+            arbitrator.pubkey(),
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            true,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &arbitrator], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    let task = task_state(task_account);
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert!(!task.payment_pending);
+
+    // Ruling for the requester refunds the escrow, penalizes the agent and
+    // slashes its stake to the treasury
+    let requester_balance = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let treasury_balance = context.banks_client.get_balance(treasury).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[resolve_dispute_instruction(
+            program_id,
+            tasks[1],
+            agent_address,
+            arbitrator.pubkey(),
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            false,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &arbitrator], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + payment_amount + reserve
+    );
+    assert_eq!(
+        context.banks_client.get_balance(treasury).await.unwrap(),
+        treasury_balance + 2000
+    );
+    let task_account = context.banks_client.get_account(tasks[1]).await.unwrap().unwrap();
+    let task = task_state(task_account);
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert_eq!(task.failure_reason, Some(FailureReason::DisputeLost));
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.reputation_score, 100 - REPUTATION_PENALTY);
+    assert_eq!(agent.staked_lamports, 8000);
+    assert_eq!(agent.tasks_completed, 1);
+    assert_eq!(agent.open_tasks, 1);
+
+    context.warp_to_slot(100_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    // Once the window has passed the result stands and the agent can collect
+    let mut transaction = Transaction::new_with_payer(
+        &[dispute_task_instruction(program_id, tasks[2], requester.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DisputeWindowClosed as u32),
+    );
+
+    let owner_balance = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_payment_instruction(
+            program_id,
+            tasks[2],
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().open_tasks, 0);
+}
+
+#[tokio::test]
+async fn test_commit_reveal() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let owner = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let payment_amount = 1000;
+    program_test.add_account(owner.pubkey(), wallet_account());
+    let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        registrar: owner.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    // The task doesn't opt out, so it has to go through commit-reveal
+    let (task_address, task_bump) = find_task_address(&agent_address, 0, &program_id);
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_address,
+        requirements: ComputeRequirements::default(),
+        status: TaskStatus::InProgress,
+        payment_amount,
+        bump: task_bump,
+        requester,
+        vault_bump: find_vault_address(&task_address, &program_id).1,
+        ..ComputeTask::default()
+    };
+    program_test.add_account(task_address, task_account_with(&task, program_id));
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    program_test.add_account(vault_address, vault_account_with(payment_amount));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let result_hash = [7; 32];
+    let salt = [9; 32];
+    let reveal = |result_hash, salt| {
+        reveal_result_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            requester,
+            treasury,
+            result_hash,
+            salt,
+        )
+    };
+
+    // A single-step completion is refused
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner.pubkey(),
+            &requester,
+            &treasury,
+            result_hash,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidInstruction as u32),
+    );
+
+    // Nothing can be revealed before a commit
+    let mut transaction =
+        Transaction::new_with_payer(&[reveal(result_hash, salt)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    let commitment = hashv(&[&result_hash, &salt]).to_bytes();
+    let mut transaction = Transaction::new_with_payer(
+        &[commit_result_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            commitment,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.commitment, Some(commitment));
+    assert_eq!(task.status, TaskStatus::InProgress);
+
+    // A commitment can't be replaced once made
+    let mut transaction = Transaction::new_with_payer(
+        &[commit_result_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            owner.pubkey(),
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // Revealing a different result or salt doesn't match the commitment
+    for (result_hash, salt) in [([8; 32], salt), (result_hash, [8; 32])] {
+        let mut transaction =
+            Transaction::new_with_payer(&[reveal(result_hash, salt)], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::CommitmentMismatch as u32),
+        );
+    }
+
+    // The matching reveal repeats the rejected one, so it needs a new blockhash
+    let blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[reveal(result_hash, salt)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.result_hash, result_hash);
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+}
+
+#[tokio::test]
+async fn test_result_verification() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let admin = Keypair::new();
+    let verifier = Keypair::new();
+    let impostor = Keypair::new();
+    let owner_keypair = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(admin.pubkey(), wallet_account());
+    program_test.add_account(impostor.pubkey(), wallet_account());
+    program_test.add_account(owner_keypair.pubkey(), wallet_account());
+    program_test.add_account(requester, wallet_account());
+
+    // Payments wait for a verifier, who gets 30 seconds before the
+    // requester can take the escrow back
+    let config = Config {
+        admin: admin.pubkey(),
+        treasury,
+        reputation_penalty: REPUTATION_PENALTY,
+        require_verification: true,
+        verification_timeout_seconds: 30,
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    let payment_amount = 1000;
+    let (agent_address, bump) = find_agent_address(&owner_keypair.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner_keypair.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 2,
+        open_tasks: 2,
+        registrar: owner_keypair.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let mut tasks = vec![];
+    for index in 0..2 {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: single_step_requirements(),
+            status: TaskStatus::InProgress,
+            payment_amount,
+            index,
+            bump: task_bump,
+            requester,
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            ..ComputeTask::default()
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        let (vault_address, _) = find_vault_address(&task_address, &program_id);
+        program_test.add_account(vault_address, vault_account_with(payment_amount));
+        tasks.push(task_address);
+    }
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let owner = &owner_keypair;
+    let reserve = Rent::default().minimum_balance(0);
+
+    let task_state = |task_account: Account| ComputeTask::unpack(&task_account.data).unwrap();
+
+    // Only the admin can register verifiers
+    let mut transaction = Transaction::new_with_payer(
+        &[register_verifier_instruction(program_id, impostor.pubkey(), impostor.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &impostor], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[register_verifier_instruction(program_id, admin.pubkey(), verifier.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let (verifier_address, verifier_bump) =
+        find_verifier_address(&verifier.pubkey(), &program_id);
+    let verifier_account =
+        context.banks_client.get_account(verifier_address).await.unwrap().unwrap();
+    let stored = Verifier::try_from_slice(&verifier_account.data).unwrap();
+    assert_eq!(stored.account_type, AccountType::Verifier);
+    assert_eq!(stored.authority, verifier.pubkey());
+    assert_eq!(stored.bump, verifier_bump);
+
+    // Completing holds the payment in escrow until a verifier signs off
+    let instructions: Vec<Instruction> = tasks
+        .iter()
+        .map(|&task_address| {
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner.pubkey(),
+                &requester,
+                &treasury,
+                [1; 32],
+            )
+        })
+        .collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    let task = task_state(task_account);
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert!(task.payment_pending);
+    assert!(!task.verified);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_payment_instruction(
+            program_id,
+            tasks[0],
+            agent_address,
+            owner.pubkey(),
+            requester,
+            treasury,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, owner], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::ResultNotVerified as u32),
+    );
+
+    // An impostor has no verifier account of its own, and can't sign for the
+    // registered one
+    let verify_as = |signer: Pubkey| {
+        verify_result_instruction(
+            program_id,
+            tasks[0],
+            agent_address,
+            signer,
+            owner.pubkey(),
+            requester,
+            treasury,
+        )
+    };
+    let mut transaction =
+        Transaction::new_with_payer(&[verify_as(impostor.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &impostor], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+    );
+
+    let mut instruction = verify_as(impostor.pubkey());
+    instruction.accounts[3].pubkey = verifier_address;
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &impostor], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // The registered verifier's sign-off releases the payment
+    let owner_balance = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[verify_as(verifier.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &verifier], context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+    let task_account = context.banks_client.get_account(tasks[0]).await.unwrap().unwrap();
+    let task = task_state(task_account);
+    assert!(task.verified);
+    assert!(!task.payment_pending);
+
+    // The other task can't be refunded until the timeout passes
+    let mut transaction = Transaction::new_with_payer(
+        &[refund_unverified_task_instruction(program_id, tasks[1], agent_address, requester)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], context.last_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::VerificationPending as u32),
+    );
+
+    context.warp_to_slot(100_000).unwrap();
+    let blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    let requester_balance = context.banks_client.get_balance(requester).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[refund_unverified_task_instruction(program_id, tasks[1], agent_address, requester)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_balance(requester).await.unwrap(),
+        requester_balance + payment_amount + reserve
+    );
+    let task_account = context.banks_client.get_account(tasks[1]).await.unwrap().unwrap();
+    let task = task_state(task_account);
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert_eq!(task.failure_reason, Some(FailureReason::Unverified));
+
+    // The agent isn't penalized for a missing sign-off
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.reputation_score, 100);
+    assert_eq!(agent.tasks_completed, 1);
+    assert_eq!(agent.open_tasks, 0);
+}
+
+#[tokio::test]
+async fn test_batch_complete_tasks() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    // A full batch of running tasks, then three more with a pending one in
+    // the middle
+    let owner = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let payment_amount = 1000;
+    let task_count = MAX_BATCH_COMPLETIONS as u64 + 3;
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester, wallet_account());
+    let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: task_count,
+        open_tasks: task_count as u32,
+        registrar: owner.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let mut tasks = vec![];
+    for index in 0..task_count {
+        let (task_address, task_bump) = find_task_address(&agent_address, index, &program_id);
+        let status = if index == task_count - 2 {
+            TaskStatus::Pending
+        } else {
+            TaskStatus::InProgress
+        };
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: single_step_requirements(),
+            status,
+            payment_amount,
+            index,
+            bump: task_bump,
+            requester,
+            vault_bump: find_vault_address(&task_address, &program_id).1,
+            ..ComputeTask::default()
+        };
+        program_test.add_account(task_address, task_account_with(&task, program_id));
+        let (vault_address, _) = find_vault_address(&task_address, &program_id);
+        program_test.add_account(vault_address, vault_account_with(payment_amount));
+        tasks.push(task_address);
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let batch = |tasks: &[Pubkey]| {
+        let results = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, &task_address)| (task_address, [i as u8 + 1; 32]))
+            .collect();
+        batch_complete_tasks_instruction(
+            program_id,
+            agent_address,
+            owner.pubkey(),
+            requester,
+            treasury,
+            results,
+        )
+    };
+    let (full_batch, rest) = tasks.split_at(MAX_BATCH_COMPLETIONS);
+
+    // One entry over the limit is rejected
+    let mut transaction = Transaction::new_with_payer(
+        &[batch(&tasks[..MAX_BATCH_COMPLETIONS + 1])],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[batch(full_batch)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    for (i, &task_address) in full_batch.iter().enumerate() {
+        let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&task_account.data).unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(task.result_hash, [i as u8 + 1; 32]);
+    }
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount * MAX_BATCH_COMPLETIONS as u64
+    );
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.tasks_completed, MAX_BATCH_COMPLETIONS as u32);
+    assert_eq!(agent.open_tasks, 3);
+
+    // A task that isn't running fails the whole batch, including the
+    // entries before it
+    let mut transaction = Transaction::new_with_payer(&[batch(rest)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    let task_account = banks_client.get_account(rest[0]).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().status, TaskStatus::InProgress);
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().open_tasks, 3);
+}
+
+#[tokio::test]
+async fn test_operator_delegation() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let owner = Keypair::new();
+    let operator = Keypair::new();
+    let requester = Pubkey::new_unique();
+    let payment_amount = 1000;
+    program_test.add_account(owner.pubkey(), wallet_account());
+    let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        compute_credits: 5000,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        registrar: owner.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+    let (credit_vault, _) = find_credit_vault_address(&agent_address, &program_id);
+    program_test.add_account(credit_vault, credit_vault_account_with(5000));
+
+    let (task_address, task_bump) = find_task_address(&agent_address, 0, &program_id);
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_address,
+        requirements: single_step_requirements(),
+        status: TaskStatus::Pending,
+        payment_amount,
+        bump: task_bump,
+        requester,
+        vault_bump: find_vault_address(&task_address, &program_id).1,
+        ..ComputeTask::default()
+    };
+    program_test.add_account(task_address, task_account_with(&task, program_id));
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    program_test.add_account(vault_address, vault_account_with(payment_amount));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let start =
+        instruction::start_task(&program_id, &task_address, &agent_address, &operator.pubkey());
+    let heartbeat = heartbeat_instruction(program_id, agent_address, operator.pubkey());
+
+    // Before delegation the operator key has no say over the agent
+    let mut transaction =
+        Transaction::new_with_payer(std::slice::from_ref(&start), Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &operator], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    // and can't appoint itself
+    let mut transaction = Transaction::new_with_payer(
+        &[set_operator_instruction(
+            program_id,
+            agent_address,
+            operator.pubkey(),
+            operator.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &operator], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_operator_instruction(
+            program_id,
+            agent_address,
+            owner.pubkey(),
+            operator.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().operator, Some(operator.pubkey()));
+
+    // The operator runs the task from start to completion
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            start,
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &operator.pubkey(),
+                &requester,
+                &treasury,
+                [1; 32],
+            ),
+            heartbeat.clone(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &operator], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // but the payment stays in escrow for the owner
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert!(task.payment_pending);
+    assert_eq!(banks_client.get_balance(operator.pubkey()).await.unwrap(), 0);
+
+    // The operator can neither drain credits nor collect the payment
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &operator.pubkey(),
+            5000,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &operator], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let claim = |signer: Pubkey| {
+        claim_payment_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            signer,
+            requester,
+            treasury,
+        )
+    };
+    let mut transaction =
+        Transaction::new_with_payer(&[claim(operator.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &operator], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[claim(owner.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + payment_amount
+    );
+    assert_eq!(
+        banks_client.get_balance(credit_vault).await.unwrap(),
+        5000 + Rent::default().minimum_balance(0)
+    );
+
+    // Revoking the delegation shuts the operator out again
+    let mut transaction = Transaction::new_with_payer(
+        &[revoke_operator_instruction(program_id, agent_address, owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().operator, None);
+
+    let blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[heartbeat], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &operator], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::MissingRequiredSignature,
+    );
+}
+
+#[tokio::test]
+async fn test_requester_allowlist() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let owner = Keypair::new();
+    let customer = Keypair::new();
+    let stranger = Keypair::new();
+    for wallet in [&owner, &customer, &stranger] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        compute_credits: 0,
+        reputation_score: 100,
+        tasks_completed: 0,
+        is_active: true,
+        bump,
+        tasks_created: 0,
+        open_tasks: 0,
+        registrar: owner.pubkey(),
+        last_heartbeat: 0,
+        staked_lamports: 0,
+        unstake_amount: 0,
+        unstake_requested_at: 0,
+        operator: None,
+        allowed_requesters: 0,
+        active_tasks: 0,
+        max_concurrent_tasks: 0,
+        capabilities: AgentCapabilities::default(),
+        tasks_failed: 0,
+        tasks_cancelled: 0,
+        total_completion_seconds: 0,
+        attestation_key: None,
+        batches_created: 0,
+        tips_received: 0,
+        pending_payments: 0,
+        pending_releases: Default::default(),
+        pending_head: 0,
+        pending_count: 0,
+        operation_nonce: 0,
+        accepted_tags: [0; TAG_SET_LEN],
+        last_task_slot: 0,
+        tasks_in_window: 0,
+        withdrawal_policy: WithdrawalPolicy::default(),
+        pending_withdrawal_policy: WithdrawalPolicy::default(),
+        pending_policy_at: 0,
+        withdrawn_today: 0,
+        withdrawal_day: 0,
+        last_withdrawal_at: 0,
+        registered_at_epoch: 0,
+        grant_claimed: false,
+        multisig_owner: false,
+        owner_nft_mint: None,
+        prefer_native: false,
+    };
+    program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (allowlist_address, _) = find_allowlist_address(&agent_address, &program_id);
+
+    // CreateTask for the agent's task `index`, optionally passing the allowlist
+    let create_task = |requester: Pubkey, index: u64, with_allowlist: bool| {
+        let (task_address, _) = find_task_address(&agent_address, index, &program_id);
+        let mut create = instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            index,
+            &requester,
+            single_step_requirements(),
+            100,
+        );
+        if with_allowlist {
+            create.accounts.push(AccountMeta::new_readonly(allowlist_address, false));
+        }
+        create
+    };
+    let add = |signer: Pubkey, requester: Pubkey| {
+        add_allowed_requester_instruction(program_id, agent_address, signer, requester)
+    };
+    let remove = |requester: Pubkey| {
+        remove_allowed_requester_instruction(program_id, agent_address, owner.pubkey(), requester)
+    };
+
+    // Without an allowlist anyone can create tasks
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(stranger.pubkey(), 0, false)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Only the owner manages the list
+    let mut transaction = Transaction::new_with_payer(
+        &[add(stranger.pubkey(), stranger.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[add(owner.pubkey(), customer.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let allowlist_account = banks_client.get_account(allowlist_address).await.unwrap().unwrap();
+    let allowlist = AgentAllowlist::unpack(&allowlist_account.data).unwrap();
+    assert_eq!(allowlist.agent, agent_address);
+    assert_eq!(allowlist.requesters, vec![customer.pubkey()]);
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().allowed_requesters, 1);
+
+    // Listing a requester twice is refused
+    let blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[add(owner.pubkey(), customer.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Requesters off the list are turned away, as are tasks that leave the
+    // allowlist out
+    for (requester, with_allowlist) in [(&stranger, true), (&stranger, false), (&customer, false)] {
+        let mut transaction = Transaction::new_with_payer(
+            &[create_task(requester.pubkey(), 1, with_allowlist)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, requester], blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::RequesterNotAllowed as u32),
+        );
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(customer.pubkey(), 1, true)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &customer], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The list holds a bounded number of requesters
+    let others: Vec<Pubkey> =
+        (1..MAX_ALLOWED_REQUESTERS).map(|_| Pubkey::new_unique()).collect();
+    let instructions: Vec<Instruction> =
+        others.iter().map(|&requester| add(owner.pubkey(), requester)).collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[add(owner.pubkey(), stranger.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AllowlistFull as u32),
+    );
+
+    // A removed requester is turned away while others remain on the list
+    let mut transaction =
+        Transaction::new_with_payer(&[remove(customer.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let blockhash = banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(customer.pubkey(), 2, true)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &customer], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::RequesterNotAllowed as u32),
+    );
+
+    // Removing someone who isn't listed is refused
+    let mut transaction =
+        Transaction::new_with_payer(&[remove(customer.pubkey())], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Emptying the list opens the agent to everyone again
+    let instructions: Vec<Instruction> =
+        others.iter().map(|&requester| remove(requester)).collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().allowed_requesters, 0);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(stranger.pubkey(), 2, false)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_transfer_credits() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    // An agent holding credits and a partner's agent without any
+    let owner = Keypair::new();
+    let partner = Keypair::new();
+    let mut agents = vec![];
+    for (wallet, compute_credits) in [(&owner, 5000), (&partner, 0)] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+        let (agent_address, bump) = find_agent_address(&wallet.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: wallet.pubkey(),
+            compute_credits,
+            reputation_score: 100,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: wallet.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        agents.push(agent_address);
+    }
+    let (source, destination) = (agents[0], agents[1]);
+    let (source_vault, _) = find_credit_vault_address(&source, &program_id);
+    let (destination_vault, _) = find_credit_vault_address(&destination, &program_id);
+    program_test.add_account(source_vault, credit_vault_account_with(5000));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let reserve = Rent::default().minimum_balance(0);
+
+    let credits_of =
+        |agent_account: Account| AIAgent::unpack(&agent_account.data).unwrap().compute_credits;
+
+    // An agent can't transfer to itself
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_credits_instruction(program_id, source, source, owner.pubkey(), 1000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::DuplicateAccount as u32),
+    );
+
+    // nor send more than it holds
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_credits_instruction(program_id, source, destination, owner.pubkey(), 5001)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InsufficientFunds,
+    );
+
+    // Only the source's owner can move its credits
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_credits_instruction(program_id, source, destination, partner.pubkey(), 1000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &partner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    // Gifting credits to another owner's agent moves the backing lamports,
+    // with the sender funding the new vault's rent reserve
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_credits_instruction(program_id, source, destination, owner.pubkey(), 2000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let source_account = banks_client.get_account(source).await.unwrap().unwrap();
+    assert_eq!(credits_of(source_account), 3000);
+    let destination_account = banks_client.get_account(destination).await.unwrap().unwrap();
+    assert_eq!(credits_of(destination_account), 2000);
+    assert_eq!(banks_client.get_balance(source_vault).await.unwrap(), reserve + 3000);
+    assert_eq!(banks_client.get_balance(destination_vault).await.unwrap(), reserve + 2000);
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance - reserve
+    );
+
+    // The partner can withdraw what was gifted
+    let partner_balance = banks_client.get_balance(partner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw_credits(&program_id, &destination, &partner.pubkey(), 2000, None)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &partner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_balance(partner.pubkey()).await.unwrap(),
+        partner_balance + 2000
+    );
+}
+
+#[tokio::test]
+async fn test_credit_allowances() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    // The funding agent holds the credits; the worker runs the tasks
+    let owner = Keypair::new();
+    let worker_owner = Keypair::new();
+    let spender = Keypair::new();
+    program_test.add_account(spender.pubkey(), wallet_account());
+    let mut agents = vec![];
+    for (wallet, compute_credits) in [(&owner, 5000), (&worker_owner, 0)] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+        let (agent_address, bump) = find_agent_address(&wallet.pubkey(), &program_id);
+        let agent = AIAgent {
+            account_type: AccountType::Agent,
+            version: CURRENT_VERSION,
+            owner: wallet.pubkey(),
+            compute_credits,
+            reputation_score: 100,
+            tasks_completed: 0,
+            is_active: true,
+            bump,
+            tasks_created: 0,
+            open_tasks: 0,
+            registrar: wallet.pubkey(),
+            last_heartbeat: 0,
+            staked_lamports: 0,
+            unstake_amount: 0,
+            unstake_requested_at: 0,
+            operator: None,
+            allowed_requesters: 0,
+            active_tasks: 0,
+            max_concurrent_tasks: 0,
+            capabilities: AgentCapabilities::default(),
+            tasks_failed: 0,
+            tasks_cancelled: 0,
+            total_completion_seconds: 0,
+            attestation_key: None,
+            batches_created: 0,
+            tips_received: 0,
+            pending_payments: 0,
+            pending_releases: Default::default(),
+            pending_head: 0,
+            pending_count: 0,
+            operation_nonce: 0,
+            accepted_tags: [0; TAG_SET_LEN],
+            last_task_slot: 0,
+            tasks_in_window: 0,
+            withdrawal_policy: WithdrawalPolicy::default(),
+            pending_withdrawal_policy: WithdrawalPolicy::default(),
+            pending_policy_at: 0,
+            withdrawn_today: 0,
+            withdrawal_day: 0,
+            last_withdrawal_at: 0,
+            registered_at_epoch: 0,
+            grant_claimed: false,
+            multisig_owner: false,
+            owner_nft_mint: None,
+            prefer_native: false,
+        };
+        program_test.add_account(agent_address, agent_account_with(&agent, program_id));
+        agents.push(agent_address);
+    }
+    let (funding, worker) = (agents[0], agents[1]);
+    let (credit_vault, _) = find_credit_vault_address(&funding, &program_id);
+    program_test.add_account(credit_vault, credit_vault_account_with(5000));
+    let (allowance_address, _) = find_allowance_address(&funding, &spender.pubkey(), &program_id);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let reserve = Rent::default().minimum_balance(0);
+
+    let create_task = |index: u64, payment_amount: u64| {
+        let (task, _) = find_task_address(&worker, index, &program_id);
+        let instruction = create_task_with_allowance_instruction(
+            program_id,
+            task,
+            worker,
+            index,
+            spender.pubkey(),
+            funding,
+            single_step_requirements(),
+            payment_amount,
+        );
+        (task, instruction)
+    };
+
+    // Without an allowance the spender can't draw on the agent's credits
+    let (_, instruction) = create_task(0, 500);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &spender], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+    );
+
+    // Only the agent's owner can approve a spender
+    let mut transaction = Transaction::new_with_payer(
+        &[approve_credits_instruction(
+            program_id,
+            funding,
+            worker_owner.pubkey(),
+            spender.pubkey(),
+            1000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &worker_owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[approve_credits_instruction(program_id, funding, owner.pubkey(), spender.pubkey(), 1000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let allowance_account = banks_client.get_account(allowance_address).await.unwrap().unwrap();
+    let allowance = Allowance::unpack(&allowance_account.data).unwrap();
+    assert_eq!(allowance.agent, funding);
+    assert_eq!(allowance.spender, spender.pubkey());
+    assert_eq!(allowance.amount, 1000);
+
+    // Spending 600 escrows it from the agent's credits, with the spender as
+    // the task's requester
+    let (task, instruction) = create_task(0, 600);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &spender], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task).await.unwrap().unwrap();
+    let task_data = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task_data.requester, spender.pubkey());
+    assert_eq!(task_data.payment_amount, 600);
+    assert_eq!(
+        banks_client.get_balance(find_vault_address(&task, &program_id).0).await.unwrap(),
+        reserve + 600
+    );
+    let funding_account = banks_client.get_account(funding).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&funding_account.data).unwrap().compute_credits, 4400);
+    assert_eq!(banks_client.get_balance(credit_vault).await.unwrap(), reserve + 4400);
+    let allowance_account = banks_client.get_account(allowance_address).await.unwrap().unwrap();
+    assert_eq!(Allowance::unpack(&allowance_account.data).unwrap().amount, 400);
+
+    // A second 600 is more than the 400 left
+    let (_, instruction) = create_task(1, 600);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &spender], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AllowanceExceeded as u32),
+    );
+
+    // Revoking closes the allowance and refunds its rent to the owner
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[revoke_allowance_instruction(program_id, funding, owner.pubkey(), spender.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert!(banks_client.get_account(allowance_address).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + Rent::default().minimum_balance(Allowance::LEN)
+    );
+
+    // after which even a spend within the old remainder fails
+    let (_, instruction) = create_task(1, 100);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &spender], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_agent_concurrency_cap() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create_task = |index| {
+        instruction::create_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            100,
+        )
+    };
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    // Only the owner may set the cap
+    let intruder = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_agent_limits(&program_id, &agent_address, &intruder.pubkey(), 2)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &intruder], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::set_agent_limits(&program_id, &agent_address, &owner.pubkey(), 2),
+            create_task(0),
+            create_task(1),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.max_concurrent_tasks, 2);
+    assert_eq!(agent.active_tasks, 2);
+
+    // A third task is turned away while both are unfinished
+    let mut transaction = Transaction::new_with_payer(&[create_task(2)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AgentAtCapacity as u32),
+    );
+
+    // Completing one frees its slot
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::start_task(&program_id, &task_address(0), &agent_address, &owner.pubkey()),
+            instruction::complete_task(
+                &program_id,
+                &task_address(0),
+                &agent_address,
+                &owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [7; 32],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().active_tasks, 1);
+
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(&[create_task(2)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Cancelling gives a slot back too
+    let mut transaction = Transaction::new_with_payer(
+        &[cancel_task_instruction(
+            program_id,
+            task_address(1),
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.active_tasks, 1);
+    assert_eq!(agent.open_tasks, 1);
+}
+
+#[tokio::test]
+async fn test_agent_capabilities() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let cpu_owner = Keypair::new();
+    let gpu_owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&cpu_owner, &gpu_owner, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (cpu_agent, _) = find_agent_address(&cpu_owner.pubkey(), &program_id);
+    let (gpu_agent, _) = find_agent_address(&gpu_owner.pubkey(), &program_id);
+    let cpu_only = AgentCapabilities {
+        cpu_units: 64,
+        memory_mb: 65_536,
+        storage_mb: 1_000_000,
+        gpu_units: 0,
+        network_mbps: 10_000,
+    };
+    let requirements = || ComputeRequirements {
+        cpu_units: 8,
+        memory_mb: 16_384,
+        gpu_units: 1,
+        network_mbps: 1_000,
+        single_step: true,
+        ..ComputeRequirements::default()
+    };
+
+    let (open_task, _) = find_task_address(&Pubkey::default(), 0, &program_id);
+    let (assigned_task, _) = find_task_address(&cpu_agent, 0, &program_id);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            register_agent_instruction(program_id, cpu_owner.pubkey()),
+            register_agent_instruction(program_id, gpu_owner.pubkey()),
+            instruction::set_capabilities(&program_id, &cpu_agent, &cpu_owner.pubkey(), cpu_only),
+            instruction::set_capabilities(
+                &program_id,
+                &gpu_agent,
+                &gpu_owner.pubkey(),
+                AgentCapabilities { gpu_units: 8, ..cpu_only },
+            ),
+            instruction::create_task(
+                &program_id,
+                &open_task,
+                &Pubkey::default(),
+                0,
+                &requester.pubkey(),
+                requirements(),
+                300,
+            ),
+            instruction::create_task(
+                &program_id,
+                &assigned_task,
+                &cpu_agent,
+                0,
+                &requester.pubkey(),
+                requirements(),
+                300,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &cpu_owner, &gpu_owner, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(cpu_agent).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().capabilities, cpu_only);
+
+    // The CPU-only agent can neither claim the GPU listing nor start the GPU
+    // task it was assigned
+    for instruction in [
+        claim_task_instruction(program_id, open_task, cpu_agent, cpu_owner.pubkey()),
+        instruction::start_task(&program_id, &assigned_task, &cpu_agent, &cpu_owner.pubkey()),
+    ] {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &cpu_owner], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::InsufficientCapabilities as u32),
+        );
+    }
+
+    // The GPU agent meets every requirement and takes the listing
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_task_instruction(program_id, open_task, gpu_agent, gpu_owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &gpu_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(open_task).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.agent, gpu_agent);
+    assert_eq!(task.requirements.gpu_units, 1);
+    assert_eq!(task.status, TaskStatus::InProgress);
+
+    // Clearing the declaration makes the agent undeclared again, which
+    // accepts any task
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::set_capabilities(
+                &program_id,
+                &cpu_agent,
+                &cpu_owner.pubkey(),
+                AgentCapabilities::default(),
+            ),
+            instruction::start_task(&program_id, &assigned_task, &cpu_agent, &cpu_owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &cpu_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_task_pricing_floor() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        treasury: Pubkey::new_unique(),
+        price_per_cpu_unit_second: 2,
+        price_per_memory_mb: 3,
+        price_per_storage_mb: 1,
+        ..Config::default()
+    };
+    let requirements = || ComputeRequirements {
+        cpu_units: 4,
+        memory_mb: 512,
+        storage_mb: 1024,
+        max_time_seconds: 60,
+        single_step: true,
+        ..ComputeRequirements::default()
+    };
+
+    // Clients quote off the same config the program reads
+    let price = compute_task_price(&requirements(), &config);
+    assert_eq!(price, 4 * 60 * 2 + 512 * 3 + 1024);
+    add_config_account(&mut program_test, program_id, config);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let create_task = |payment_amount| {
+        instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &requester.pubkey(),
+            requirements(),
+            payment_amount,
+        )
+    };
+
+    // A lamport short of the quote is turned away
+    let mut transaction =
+        Transaction::new_with_payer(&[create_task(price - 1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::PaymentBelowMinimum as u32),
+    );
+
+    // Paying the quote is enough
+    let mut transaction = Transaction::new_with_payer(&[create_task(price)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().payment_amount, price);
+}
+
+#[tokio::test]
+async fn test_task_priority() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        treasury: Pubkey::new_unique(),
+        price_per_cpu_unit_second: 10,
+        priority_threshold: 100,
+        priority_multiplier_bps: 500,
+        ..Config::default()
+    };
+    let requirements = || ComputeRequirements {
+        cpu_units: 2,
+        max_time_seconds: 50,
+        single_step: true,
+        ..ComputeRequirements::default()
+    };
+
+    // Each level over the threshold adds 5% to the 1_000 lamport base
+    assert_eq!(compute_task_price(&requirements(), &config), 1_000);
+    let price = priority_task_price(&requirements(), &config, 110);
+    assert_eq!(price, 1_500);
+    let surcharge = priority_surcharge(&requirements(), &config, 110, 120);
+    assert_eq!(surcharge, 500);
+    add_config_account(&mut program_test, program_id, config);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let create_task = |payment_amount| {
+        instruction::create_task_with_priority(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &requester.pubkey(),
+            requirements(),
+            payment_amount,
+            110,
+        )
+    };
+
+    // The base price no longer covers a priority over the threshold
+    let mut transaction =
+        Transaction::new_with_payer(&[create_task(price - 1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::PaymentBelowMinimum as u32),
+    );
+    let mut transaction = Transaction::new_with_payer(&[create_task(price)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    assert_eq!(task_account.data[ComputeTask::PRIORITY_OFFSET], 110);
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().priority, 110);
+    let vault_before = banks_client.get_balance(vault_address).await.unwrap();
+
+    // Only the requester can change it
+    let stranger = Keypair::new();
+    let update =
+        instruction::update_task_priority(&program_id, &task_address, &stranger.pubkey(), 120);
+    let mut transaction = Transaction::new_with_payer(&[update], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    // Raising it escrows the increment, which the agent will earn
+    let update = |priority| {
+        instruction::update_task_priority(&program_id, &task_address, &requester.pubkey(), priority)
+    };
+    let mut transaction = Transaction::new_with_payer(&[update(120)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!((task.priority, task.payment_amount), (120, price + surcharge));
+    assert_eq!(
+        banks_client.get_balance(vault_address).await.unwrap(),
+        vault_before + surcharge
+    );
+
+    // Lowering it refunds nothing
+    let mut transaction = Transaction::new_with_payer(&[update(50)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!((task.priority, task.payment_amount), (50, price + surcharge));
+
+    // Once the task starts its priority is settled
+    let start =
+        instruction::start_task(&program_id, &task_address, &agent_address, &owner.pubkey());
+    let mut transaction = Transaction::new_with_payer(&[start], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[update(200)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().priority, 50);
+}
+
+#[tokio::test]
+async fn test_task_requirement_bounds() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let requirement_bounds = RequirementBounds {
+        min: ResourceLimits { cpu_units: 1, ..ResourceLimits::default() },
+        max: ResourceLimits { memory_mb: 8_192, ..ResourceLimits::default() },
+    };
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        treasury: Pubkey::new_unique(),
+        requirement_bounds,
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let create_task = |cpu_units, memory_mb| {
+        instruction::create_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            0,
+            &requester.pubkey(),
+            ComputeRequirements { cpu_units, memory_mb, ..single_step_requirements() },
+            100,
+        )
+    };
+
+    // Below the floor, then above the ceiling
+    for (cpu_units, memory_mb) in [(0, 512), (1, 8_193)] {
+        let mut transaction = Transaction::new_with_payer(
+            &[create_task(cpu_units, memory_mb)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &requester], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::InvalidRequirements as u32),
+        );
+    }
+    assert!(banks_client.get_account(task_address).await.unwrap().is_none());
+
+    // Exactly at both bounds is fine
+    let mut transaction =
+        Transaction::new_with_payer(&[create_task(1, 8_192)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().requirements.memory_mb, 8_192);
+}
+
+#[tokio::test]
+async fn test_migrate_account() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // A version 1 agent, written before accounts carried a version and
+    // before the outcome counters were appended to AIAgent
+    let owner = Keypair::new();
+    let (agent_address, bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        reputation_score: 700,
+        tasks_completed: 12,
+        is_active: true,
+        bump,
+        registrar: owner.pubkey(),
+        max_concurrent_tasks: 3,
+        ..AIAgent::default()
+    };
+    let legacy_len = AIAgent::V1_LEN - 16;
+    let mut legacy = agent_account_with(&agent, program_id);
+    legacy.data.remove(1);
+    legacy.data.truncate(legacy_len);
+    legacy.lamports = Rent::default().minimum_balance(legacy_len);
+    program_test.add_account(agent_address, legacy);
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    // and an agent from a program newer than this one
+    let future_owner = Keypair::new();
+    let (future_address, future_bump) = find_agent_address(&future_owner.pubkey(), &program_id);
+    let future = AIAgent {
+        owner: future_owner.pubkey(),
+        registrar: future_owner.pubkey(),
+        bump: future_bump,
+        ..AIAgent::default()
+    };
+    let mut future_account = agent_account_with(&future, program_id);
+    future_account.data[0] = AccountType::Agent as u8;
+    future_account.data[1] = CURRENT_VERSION + 1;
+    program_test.add_account(future_address, future_account);
+
+    let sponsor = Keypair::new();
+    program_test.add_account(sponsor.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Handlers refuse both until they are in the current version
+    for (agent, signer) in [(agent_address, &owner), (future_address, &future_owner)] {
+        let mut transaction = Transaction::new_with_payer(
+            &[heartbeat_instruction(program_id, agent, signer.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, signer], recent_blockhash);
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::UnsupportedAccountVersion as u32),
+        );
+    }
+
+    // The newer layout can't be migrated back
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::migrate_account(&program_id, &future_address, &sponsor.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &sponsor], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::UnsupportedAccountVersion as u32),
+    );
+
+    // Anyone can pay to bring the old agent up to date
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::migrate_account(&program_id, &agent_address, &sponsor.pubkey()),
+            heartbeat_instruction(program_id, agent_address, owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &sponsor, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The sponsor paid the rent delta, the old fields carried over and the
+    // new ones took their defaults
+    let rent = Rent::default();
+    assert_eq!(
+        banks_client.get_balance(sponsor.pubkey()).await.unwrap(),
+        LAMPORTS_PER_SOL - (rent.minimum_balance(AIAgent::LEN) - rent.minimum_balance(legacy_len))
+    );
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(agent_account.data.len(), AIAgent::LEN);
+    assert_eq!(agent_account.lamports, rent.minimum_balance(AIAgent::LEN));
+    let migrated = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(migrated.version, CURRENT_VERSION);
+    assert_eq!(migrated.owner, owner.pubkey());
+    assert_eq!(migrated.reputation_score, 700);
+    assert_eq!(migrated.tasks_completed, 12);
+    assert_eq!(migrated.max_concurrent_tasks, 3);
+    assert!(migrated.is_active);
+    assert!(migrated.last_heartbeat > 0);
+    assert_eq!(migrated.tasks_failed, 0);
+    assert_eq!(migrated.tasks_cancelled, 0);
+    assert_eq!(migrated.total_completion_seconds, 0);
+
+    // Migrating a current account changes nothing
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::migrate_account(&program_id, &agent_address, &sponsor.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &sponsor], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let unchanged = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(unchanged.data, agent_account.data);
+}
+
+#[tokio::test]
+async fn test_registry_counters() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let owners = [Keypair::new(), Keypair::new()];
+    let requester = Keypair::new();
+    for wallet in owners.iter().chain([&requester]) {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (registry_address, _) = find_registry_address(&program_id);
+
+    // Agents can't register before the registry exists
+    let result = create_test_agent(
+        &mut banks_client, &payer, recent_blockhash, program_id, &owners[0],
+    ).await;
+    assert_instruction_error(
+        result,
+        InstructionError::Custom(AIInfraError::AccountNotOwnedByProgram as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::initialize_registry(&program_id, &payer.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let registry_account = banks_client.get_account(registry_address).await.unwrap().unwrap();
+    assert_eq!(registry_account.owner, program_id);
+    assert_eq!(registry_account.data.len(), Registry::LEN);
+    let registry = Registry::unpack(&registry_account.data).unwrap();
+    assert_eq!(registry.total_agents, 0);
+    assert_eq!(registry.total_tasks, 0);
+    assert_eq!(registry.total_credits_deposited, 0);
+
+    // It can only be initialized once
+    let blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::initialize_registry(&program_id, &payer.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    let agents: Vec<Pubkey> = owners.iter()
+        .map(|owner| find_agent_address(&owner.pubkey(), &program_id).0)
+        .collect();
+    for owner in &owners {
+        create_test_agent(&mut banks_client, &payer, blockhash, program_id, owner)
+            .await
+            .unwrap();
+    }
+    for (owner, agent) in owners.iter().zip(&agents) {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::deposit_credits(&program_id, agent, &owner.pubkey(), 400, None)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // Two tasks for the first agent, one for the second and an open listing
+    let tasks = [
+        (agents[0], 0),
+        (agents[0], 1),
+        (agents[1], 0),
+        (Pubkey::default(), 0),
+    ];
+    for (agent, index) in tasks {
+        let (task, _) = find_task_address(&agent, index, &program_id);
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::create_task(
+                &program_id,
+                &task,
+                &agent,
+                index,
+                &requester.pubkey(),
+                single_step_requirements(),
+                1000,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &requester], blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    let registry_account = banks_client.get_account(registry_address).await.unwrap().unwrap();
+    let registry = Registry::unpack(&registry_account.data).unwrap();
+    assert_eq!(registry.total_agents, 2);
+    assert_eq!(registry.total_tasks, 4);
+    assert_eq!(registry.total_credits_deposited, 800);
+}
+
+#[tokio::test]
+async fn test_task_index_pages() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let page_address = |page| find_task_page_address(&agent_address, page, &program_id).0;
+
+    let mut tasks = vec![];
+    for index in 0..40 {
+        let (task_address, _) = find_task_address(&agent_address, index, &program_id);
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                index,
+                &requester.pubkey(),
+                single_step_requirements(),
+                100,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &requester], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+        tasks.push(task_address);
+
+        // The second page only opens with the task that overflows the first
+        let second_page = banks_client.get_account(page_address(1)).await.unwrap();
+        assert_eq!(second_page.is_some(), index >= TASKS_PER_PAGE);
+    }
+
+    // Walking the pages in order lists every task in creation order
+    let mut listed = vec![];
+    for page in 0.. {
+        let account = match banks_client.get_account(page_address(page)).await.unwrap() {
+            Some(account) => account,
+            None => break,
+        };
+        assert_eq!(account.owner, program_id);
+        assert_eq!(account.data.len(), TaskPage::LEN);
+        let task_page = TaskPage::unpack(&account.data).unwrap();
+        assert_eq!(task_page.agent, agent_address);
+        assert_eq!(task_page.page, page);
+        listed.extend(task_page.tasks);
+    }
+    assert_eq!(listed.len(), 40);
+    assert_eq!(listed, tasks);
+
+    // A task can only be listed on the page its index falls in
+    let (task_address, _) = find_task_address(&agent_address, 40, &program_id);
+    let mut create = instruction::create_task(
+        &program_id,
+        &task_address,
+        &agent_address,
+        40,
+        &requester.pubkey(),
+        single_step_requirements(),
+        100,
+    );
+    create.accounts[7].pubkey = page_address(0);
+    let mut transaction = Transaction::new_with_payer(&[create], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidSeeds,
+    );
+}
+
+#[tokio::test]
+async fn test_expected_result_hash() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create_task = |index| {
+        instruction::create_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            100,
+        )
+    };
+    let start_task = |index| {
+        instruction::start_task(&program_id, &task_address(index), &agent_address, &owner.pubkey())
+    };
+    let complete_task = |index, result_hash| {
+        instruction::complete_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )
+    };
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    // Task 0 expects a result from the moment it is created; task 1 doesn't
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_task(0),
+            instruction::set_expected_result(
+                &program_id,
+                &task_address(0),
+                &requester.pubkey(),
+                [7; 32],
+            ),
+            create_task(1),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address(0)).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.expected_result_hash, Some([7; 32]));
+    let task_account = banks_client.get_account(task_address(1)).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().expected_result_hash, None);
+
+    // Only the requester sets it, and only once
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_expected_result(
+            &program_id,
+            &task_address(1),
+            &owner.pubkey(),
+            [7; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_expected_result(
+            &program_id,
+            &task_address(0),
+            &requester.pubkey(),
+            [8; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    let mut transaction =
+        Transaction::new_with_payer(&[start_task(0), start_task(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Nor can it be set once the agent has started
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_expected_result(
+            &program_id,
+            &task_address(1),
+            &requester.pubkey(),
+            [7; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // Any other result is turned away, leaving the task for a retry
+    let mut transaction =
+        Transaction::new_with_payer(&[complete_task(0, [8; 32])], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::ResultMismatch as u32),
+    );
+    let task_account = banks_client.get_account(task_address(0)).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&task_account.data).unwrap().status, TaskStatus::InProgress);
+
+    // The expected result settles it, and a task without one takes any result
+    let mut transaction = Transaction::new_with_payer(
+        &[complete_task(0, [7; 32]), complete_task(1, [9; 32])],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    for (index, result_hash) in [(0, [7; 32]), (1, [9; 32])] {
+        let task_account = banks_client.get_account(task_address(index)).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&task_account.data).unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(task.result_hash, result_hash);
+    }
+}
+
+#[tokio::test]
+async fn test_result_attestation() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let worker = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let complete_task = |index, result_hash| {
+        instruction::complete_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )
+    };
+    let attest = |signer, index, result_hash| {
+        ed25519_instruction(signer, &attestation_message(&task_address(index), &result_hash))
+    };
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    let mut instructions = vec![instruction::set_attestation_key(
+        &program_id,
+        &agent_address,
+        &owner.pubkey(),
+        Some(worker.pubkey()),
+    )];
+    for index in 0..4 {
+        instructions.push(instruction::create_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            single_step_requirements(),
+            100,
+        ));
+        instructions.push(instruction::start_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &owner.pubkey(),
+        ));
+    }
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.attestation_key, Some(worker.pubkey()));
+
+    // Without the instructions sysvar there's nothing to check against
+    let mut transaction =
+        Transaction::new_with_payer(&[complete_task(0, [7; 32])], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidAttestation as u32),
+    );
+
+    // Nor is there with the sysvar but no attestation before the completion
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::with_attestation(complete_task(0, [7; 32]))],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidAttestation as u32),
+    );
+
+    // A valid signature by any other key, or over any other result, is refused
+    let forgeries = [attest(&owner, 0, [7; 32]), attest(&worker, 0, [8; 32])];
+    for forgery in forgeries {
+        let mut transaction = Transaction::new_with_payer(
+            &[forgery, instruction::with_attestation(complete_task(0, [7; 32]))],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(AIInfraError::InvalidAttestation as u32),
+            )
+        );
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            attest(&worker, 0, [7; 32]),
+            instruction::with_attestation(complete_task(0, [7; 32])),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let task_account = banks_client.get_account(task_address(0)).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.result_hash, [7; 32]);
+
+    // A batch needs an attestation for each of its results
+    let batch = |results| {
+        instruction::with_attestation(batch_complete_tasks_instruction(
+            program_id,
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            results,
+        ))
+    };
+    let results = vec![(task_address(1), [1; 32]), (task_address(2), [2; 32])];
+    let mut transaction = Transaction::new_with_payer(
+        &[attest(&worker, 1, [1; 32]), batch(results.clone())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(AIInfraError::InvalidAttestation as u32),
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[attest(&worker, 1, [1; 32]), attest(&worker, 2, [2; 32]), batch(results)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Clearing the key lets completions through unattested again
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::set_attestation_key(&program_id, &agent_address, &owner.pubkey(), None),
+            complete_task(3, [3; 32]),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    for index in 1..4 {
+        let task_account = banks_client.get_account(task_address(index)).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&task_account.data).unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(task.result_hash, [index as u8; 32]);
+    }
+}
+
+#[tokio::test]
+async fn test_batch_task_merkle_claims() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(treasury, wallet_account());
+    add_config(&mut program_test, program_id, 500, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (batch_address, _) = find_batch_task_address(&agent_address, 0, &program_id);
+    let (vault_address, _) = find_vault_address(&batch_address, &program_id);
+    let task_count = 10u32;
+    let payment_per_task = 1_000u64;
+
+    // Item i's result is [i; 32]
+    let leaves: Vec<[u8; 32]> =
+        (0..task_count).map(|i| merkle::leaf(i, &[i as u8; 32])).collect();
+    let merkle_root = merkle::root(&leaves);
+    let claim = |index: u32, result_hash, proof| {
+        instruction::claim_batch_item(
+            &program_id,
+            &batch_address,
+            &agent_address,
+            &owner.pubkey(),
+            &treasury,
+            &requester.pubkey(),
+            index,
+            result_hash,
+            proof,
+        )
+    };
+    let claim_item = |index: u32| {
+        claim(index, [index as u8; 32], merkle::proof(&leaves, index as usize))
+    };
+    let submit = |root| {
+        instruction::submit_batch_result(
+            &program_id,
+            &batch_address,
+            &agent_address,
+            &owner.pubkey(),
+            root,
+        )
+    };
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    let requester_before = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_batch_task(
+            &program_id,
+            &batch_address,
+            &agent_address,
+            &requester.pubkey(),
+            task_count,
+            payment_per_task,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The requester escrows every item's payment, plus the vault's reserve
+    // and the batch account's rent
+    let rent = banks_client.get_rent().await.unwrap();
+    let escrow = payment_per_task * task_count as u64 + rent.minimum_balance(0);
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before - escrow - rent.minimum_balance(BatchTask::LEN)
+    );
+    assert_eq!(banks_client.get_balance(vault_address).await.unwrap(), escrow);
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&agent_account.data).unwrap().batches_created, 1);
+
+    // Nothing can be claimed until the agent submits the root
+    let mut transaction = Transaction::new_with_payer(&[claim_item(0)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    let mut transaction =
+        Transaction::new_with_payer(&[submit(merkle_root)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The root can't be replaced
+    let mut transaction = Transaction::new_with_payer(&[submit([1; 32])], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // Claims for the first, a middle and the last item, whose sibling is
+    // missing from the unbalanced tree
+    let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_item(0), claim_item(5), claim_item(task_count - 1)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let fee = payment_per_task * 500 / 10_000;
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_before + 3 * (payment_per_task - fee)
+    );
+    assert_eq!(
+        banks_client.get_balance(treasury).await.unwrap(),
+        wallet_account().lamports + 3 * fee
+    );
+    assert_eq!(
+        banks_client.get_balance(vault_address).await.unwrap(),
+        escrow - 3 * payment_per_task
+    );
+    let batch_account = banks_client.get_account(batch_address).await.unwrap().unwrap();
+    let batch = BatchTask::unpack(&batch_account.data).unwrap();
+    assert_eq!(batch.merkle_root, Some(merkle_root));
+    assert_eq!(batch.items_claimed, 3);
+    for item in 0..task_count {
+        assert_eq!(batch.is_claimed(item), [0, 5, task_count - 1].contains(&item));
+    }
+
+    // Each item pays once
+    let mut transaction = Transaction::new_with_payer(&[claim_item(5)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::BatchItemClaimed as u32),
+    );
+
+    // A proof for another item, or another result, doesn't verify
+    let mut transaction = Transaction::new_with_payer(
+        &[claim(1, [1; 32], merkle::proof(&leaves, 2))],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidMerkleProof as u32),
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[claim(1, [2; 32], merkle::proof(&leaves, 1))],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidMerkleProof as u32),
+    );
+
+    // Items past the end of the batch don't exist
+    let mut transaction = Transaction::new_with_payer(
+        &[claim(task_count, [0; 32], Vec::new())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Claiming the last item closes the batch, returning its rent and the
+    // vault's reserve to the requester
+    let requester_before = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    for index in (1..task_count - 1).filter(|&index| index != 5) {
+        let mut transaction =
+            Transaction::new_with_payer(&[claim_item(index)], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+    assert!(banks_client.get_account(batch_address).await.unwrap().is_none());
+    assert!(banks_client.get_account(vault_address).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before + rent.minimum_balance(0) + rent.minimum_balance(BatchTask::LEN)
+    );
+}
+
+#[tokio::test]
+async fn test_batch_task_refund() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    program_test.add_account(treasury, wallet_account());
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (batch_address, _) = find_batch_task_address(&agent_address, 0, &program_id);
+    let (vault_address, _) = find_vault_address(&batch_address, &program_id);
+    let payment_per_task = 1_000u64;
+    let leaves: Vec<[u8; 32]> = (0..4u32).map(|i| merkle::leaf(i, &[i as u8; 32])).collect();
+    let refund = instruction::refund_batch_task(&program_id, &batch_address, &requester.pubkey());
+
+    create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_batch_task(
+            &program_id,
+            &batch_address,
+            &agent_address,
+            &requester.pubkey(),
+            4,
+            payment_per_task,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // The agent submits a root and claims one item, leaving three unclaimed
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::submit_batch_result(
+                &program_id,
+                &batch_address,
+                &agent_address,
+                &owner.pubkey(),
+                merkle::root(&leaves),
+            ),
+            instruction::claim_batch_item(
+                &program_id,
+                &batch_address,
+                &agent_address,
+                &owner.pubkey(),
+                &treasury,
+                &requester.pubkey(),
+                0,
+                [0; 32],
+                merkle::proof(&leaves, 0),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // Only the requester can refund, and only once the delay has passed
+    let mut transaction =
+        Transaction::new_with_payer(std::slice::from_ref(&refund), Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::TaskNotExpired as u32),
+    );
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += BATCH_REFUND_DELAY_SECONDS;
+    context.set_sysvar(&clock);
+    let mut someone_else = refund.clone();
+    someone_else.accounts[1].pubkey = owner.pubkey();
+    let mut transaction = Transaction::new_with_payer(&[someone_else], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    // The requester takes back the unclaimed payments with every rent
+    // reserve, and the batch closes
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let requester_before = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[refund], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before
+            + 3 * payment_per_task
+            + rent.minimum_balance(0)
+            + rent.minimum_balance(BatchTask::LEN)
+    );
+    assert!(context.banks_client.get_account(batch_address).await.unwrap().is_none());
+    assert!(context.banks_client.get_account(vault_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_referral_payments() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let referred = Keypair::new();
+    let unreferred = Keypair::new();
+    let referrer = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    for wallet in [owner.pubkey(), referred.pubkey(), unreferred.pubkey(), referrer, treasury] {
+        program_test.add_account(wallet, wallet_account());
+    }
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config {
+            admin: Pubkey::new_unique(),
+            fee_bps: 250,
+            referral_bps: 130,
+            treasury,
+            ..Config::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create_task = |index, requester: &Keypair, payment_amount| {
+        instruction::create_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            payment_amount,
+        )
+    };
+    let start_task = |index| {
+        instruction::start_task(&program_id, &task_address(index), &agent_address, &owner.pubkey())
+    };
+    let complete_task = |index, requester: &Keypair| {
+        instruction::complete_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [index as u8; 32],
+        )
+    };
+    // Balances of the three ways a payment splits: agent owner, referrer and
+    // treasury
+    let wallets = [owner.pubkey(), referrer, treasury];
+    async fn balances(banks_client: &mut BanksClient, wallets: [Pubkey; 3]) -> [u64; 3] {
+        let mut balances = [0; 3];
+        for (balance, wallet) in balances.iter_mut().zip(wallets) {
+            *balance = banks_client.get_balance(wallet).await.unwrap();
+        }
+        balances
+    }
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    // Requesters can't refer themselves, and set a referrer only once
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_referrer(&program_id, &referred.pubkey(), &referred.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &referred], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_referrer(&program_id, &referred.pubkey(), &referrer)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &referred], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (requester_address, _) = find_requester_address(&referred.pubkey(), &program_id);
+    let requester_account = banks_client.get_account(requester_address).await.unwrap().unwrap();
+    let requester = Requester::unpack(&requester_account.data).unwrap();
+    assert_eq!(requester.referrer, Some(referrer));
+    assert_eq!(requester.credits, 0);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_referrer(&program_id, &referred.pubkey(), &Pubkey::new_unique())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &referred], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::AlreadyInitialized as u32),
+    );
+
+    // An uneven payment, so each share rounds
+    let payment = 10_007;
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(0, &referred, payment), create_task(1, &unreferred, payment)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &referred, &unreferred], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[start_task(0), start_task(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Completions must pass the referral accounts, naming the right referrer
+    let mut transaction =
+        Transaction::new_with_payer(&[complete_task(0, &referred)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::with_referral(
+            complete_task(0, &referred),
+            &program_id,
+            &referred.pubkey(),
+            Some(&treasury),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let before = balances(&mut banks_client, wallets).await;
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::with_referral(
+            complete_task(0, &referred),
+            &program_id,
+            &referred.pubkey(),
+            Some(&referrer),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // 2.5% to the treasury and 1.3% to the referrer, each rounded down, with
+    // the agent keeping the rest
+    let after = balances(&mut banks_client, wallets).await;
+    let [agent_cut, referral, fee] = [0, 1, 2].map(|i| after[i] - before[i]);
+    assert_eq!(fee, 250);
+    assert_eq!(referral, 130);
+    assert_eq!(agent_cut, payment - 380);
+    assert_eq!(agent_cut + referral + fee, payment);
+
+    // A requester without a referrer, or a requester account, pays the agent
+    // and treasury alone
+    let before = balances(&mut banks_client, wallets).await;
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::with_referral(
+            complete_task(1, &unreferred),
+            &program_id,
+            &unreferred.pubkey(),
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let after = balances(&mut banks_client, wallets).await;
+    let [agent_cut, referral, fee] = [0, 1, 2].map(|i| after[i] - before[i]);
+    assert_eq!((agent_cut, referral, fee), (payment - 250, 0, 250));
+}
+
+#[tokio::test]
+async fn test_tip_agent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create_task = |index| {
+        instruction::create_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            100,
+        )
+    };
+    let start_task = |index| {
+        instruction::start_task(&program_id, &task_address(index), &agent_address, &owner.pubkey())
+    };
+    let complete_task = |index| {
+        instruction::complete_task(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [1; 32],
+        )
+    };
+    let tip = |index, amount| {
+        instruction::tip_agent(
+            &program_id,
+            &task_address(index),
+            &agent_address,
+            &requester.pubkey(),
+            &owner.pubkey(),
+            amount,
+        )
+    };
+
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    // Task 0 is completed, task 1 only started
+    let mut transaction = Transaction::new_with_payer(
+        &[create_task(0), create_task(1), start_task(0), start_task(1), complete_task(0)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester, &owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let reputation_before = AIAgent::unpack(&agent_account.data).unwrap().reputation_score;
+    let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+
+    // Only completed tasks take tips, and only non-zero ones
+    for (index, amount, expected) in [
+        (1, 500, InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32)),
+        (0, 0, InstructionError::InvalidArgument),
+    ] {
+        let mut transaction =
+            Transaction::new_with_payer(&[tip(index, amount)], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &requester], recent_blockhash);
+        assert_instruction_error(banks_client.process_transaction(transaction).await, expected);
+    }
+
+    // The tip has to go to the agent's owner
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::tip_agent(
+            &program_id,
+            &task_address(0),
+            &agent_address,
+            &requester.pubkey(),
+            &treasury,
+            500,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Every tip is paid and counted, but the task's reputation effect stops
+    // at the cap. Amounts differ so the transactions do too.
+    let tips = MAX_TIP_REPUTATION_PER_TASK as u64 + 2;
+    let instructions: Vec<Instruction> = (1..=tips).map(|amount| tip(0, amount * 1_000)).collect();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let total_tips = (1..=tips).sum::<u64>() * 1_000;
+    assert_eq!(banks_client.get_balance(owner.pubkey()).await.unwrap(), owner_before + total_tips);
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.tips_received, total_tips);
+    assert_eq!(agent.reputation_score, reputation_before + MAX_TIP_REPUTATION_PER_TASK);
+    let task_account = banks_client.get_account(task_address(0)).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&task_account.data).unwrap();
+    assert_eq!(task.tip_reputation, MAX_TIP_REPUTATION_PER_TASK);
+
+    // Another completed task has a cap of its own
+    let mut transaction = Transaction::new_with_payer(
+        &[complete_task(1), tip(1, 700)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.tips_received, total_tips + 700);
+    assert_eq!(
+        agent.reputation_score,
+        reputation_before + MAX_TIP_REPUTATION_PER_TASK + TIP_REPUTATION_BONUS
+    );
+}
+
+#[tokio::test]
+async fn test_payment_hold() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    program_test.add_account(requester.pubkey(), wallet_account());
+    let hold_seconds = 3600;
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config {
+            admin: Pubkey::new_unique(),
+            treasury,
+            payment_hold_seconds: hold_seconds,
+            ..Config::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let (credit_vault, _) = find_credit_vault_address(&agent_address, &program_id);
+    let complete_task = || {
+        instruction::with_payment_hold(
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            &program_id,
+            &agent_address,
+        )
+    };
+    let withdraw = |amount| {
+        instruction::withdraw_credits(&program_id, &agent_address, &owner.pubkey(), amount, None)
+    };
+    let release = || instruction::release_payments(&program_id, &agent_address);
+
+    let payment = 5_000;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::register_agent(&program_id, &agent_address, &owner.pubkey()),
+            instruction::create_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                0,
+                &requester.pubkey(),
+                ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+                payment,
+            ),
+            instruction::start_task(&program_id, &task_address, &agent_address, &owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // The payment needs somewhere to wait: the credit vault, once funded
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task_address,
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [1; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+
+    let mut transaction = Transaction::new_with_payer(&[complete_task()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::NotRentExempt as u32),
+    );
+
+    let credits = 1_000;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::deposit_credits(
+                &program_id,
+                &agent_address,
+                &owner.pubkey(),
+                credits,
+                None,
+            ),
+            complete_task(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // The payment sits in the vault, pending, while the owner's wallet and
+    // matured credits stay as they were
+    let vault_before = context.banks_client.get_balance(credit_vault).await.unwrap();
+    let owner_before = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!(agent.pending_payments, payment);
+    assert_eq!(agent.compute_credits, credits);
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    assert_eq!(agent.pending_releases[0].release_at, now + hold_seconds);
+
+    // Before the window closes only the matured credits can be withdrawn,
+    // and releasing moves nothing
+    let mut transaction = Transaction::new_with_payer(
+        &[release(), withdraw(credits + 1)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    let result = context.banks_client.process_transaction(transaction).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(1, InstructionError::InsufficientFunds)
+    );
+
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += hold_seconds - 1;
+    context.set_sysvar(&clock);
+    let mut transaction = Transaction::new_with_payer(&[release()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!((agent.pending_payments, agent.compute_credits), (payment, credits));
+
+    // After it, the payment is released into the credits and withdrawable
+    clock.unix_timestamp += 1;
+    context.set_sysvar(&clock);
+    let mut transaction = Transaction::new_with_payer(
+        &[release(), withdraw(credits + payment)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&agent_account.data).unwrap();
+    assert_eq!((agent.pending_payments, agent.compute_credits, agent.pending_count), (0, 0, 0));
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_before + credits + payment
+    );
+    assert_eq!(
+        context.banks_client.get_balance(credit_vault).await.unwrap(),
+        vault_before - credits - payment
+    );
+}
+
+#[tokio::test]
+async fn test_task_templates() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let stranger = Keypair::new();
+    for wallet in [owner.pubkey(), requester.pubkey(), stranger.pubkey()] {
+        program_test.add_account(wallet, wallet_account());
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (template, _) = find_task_template_address(&requester.pubkey(), 0, &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let from_template = |index, payer: &Keypair, payment_amount| {
+        instruction::create_task_from_template(
+            &program_id,
+            &template,
+            &task_address(index),
+            &agent_address,
+            index,
+            &payer.pubkey(),
+            payment_amount,
+        )
+    };
+    let requirements = || ComputeRequirements {
+        cpu_units: 4,
+        memory_mb: 512,
+        max_time_seconds: 60,
+        single_step: true,
+        ..ComputeRequirements::default()
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_template(
+            &program_id,
+            &template,
+            &requester.pubkey(),
+            requirements(),
+            1_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(template).await.unwrap().unwrap();
+    let stored = TaskTemplate::unpack(&account.data).unwrap();
+    assert_eq!(stored.account_type, AccountType::TaskTemplate);
+    assert_eq!(stored.requester, requester.pubkey());
+    assert_eq!(stored.requirements, requirements());
+    assert_eq!(stored.default_payment, 1_000);
+    let (requester_address, _) = find_requester_address(&requester.pubkey(), &program_id);
+    let account = banks_client.get_account(requester_address).await.unwrap().unwrap();
+    assert_eq!(Requester::unpack(&account.data).unwrap().templates_created, 1);
+
+    // Three tasks share the template's requirements; the last overrides its
+    // payment
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            from_template(0, &requester, None),
+            from_template(1, &requester, None),
+            from_template(2, &requester, Some(2_500)),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    for (index, payment_amount) in [(0, 1_000), (1, 1_000), (2, 2_500)] {
+        let account = banks_client.get_account(task_address(index)).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&account.data).unwrap();
+        assert_eq!(task.requirements, requirements());
+        assert_eq!(task.payment_amount, payment_amount);
+        assert_eq!(task.requester, requester.pubkey());
+    }
+
+    // Only the template's requester may use, update or close it
+    let mut transaction = Transaction::new_with_payer(
+        &[from_template(3, &stranger, None)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let updated = || ComputeRequirements { cpu_units: 8, memory_mb: 2048, ..requirements() };
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::update_template(
+            &program_id,
+            &template,
+            &stranger.pubkey(),
+            updated(),
+            3_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::close_template(&program_id, &template, &stranger.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    // Tasks created after an update take the new requirements, while earlier
+    // ones keep theirs
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::update_template(
+                &program_id,
+                &template,
+                &requester.pubkey(),
+                updated(),
+                3_000,
+            ),
+            from_template(3, &requester, None),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(task_address(3)).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!(task.requirements, updated());
+    assert_eq!(task.payment_amount, 3_000);
+    let account = banks_client.get_account(task_address(0)).await.unwrap().unwrap();
+    let task = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!(task.requirements, requirements());
+
+    // Closing returns the template's rent to its requester
+    let template_rent = banks_client.get_balance(template).await.unwrap();
+    let balance_before = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::close_template(&program_id, &template, &requester.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert!(banks_client.get_account(template).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        balance_before + template_rent
+    );
+}
+
+#[tokio::test]
+async fn test_recurring_task_cranks() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let crank_reward = 5_000;
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config { admin: Pubkey::new_unique(), crank_reward, ..Config::default() },
+    );
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let cranker = Keypair::new();
+    for wallet in [owner.pubkey(), requester.pubkey(), cranker.pubkey()] {
+        program_test.add_account(wallet, wallet_account());
+    }
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let mut blockhash = context.last_blockhash;
+    create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, &owner)
+        .await
+        .unwrap();
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (recurring, _) = find_recurring_task_address(&requester.pubkey(), 0, &program_id);
+    let task_address = |index| find_task_address(&agent_address, index, &program_id).0;
+    let crank = |index| {
+        instruction::crank_recurring_task(
+            &program_id,
+            &recurring,
+            &task_address(index),
+            &agent_address,
+            index,
+            &cranker.pubkey(),
+        )
+    };
+    let interval = 3_600;
+    let payment = 1_000;
+    // Each run escrows its payment and the vault's reserve, refunds the
+    // task's rent and pays the reward. The first also opens a task page.
+    let run_cost =
+        payment + rent.minimum_balance(0) + rent.minimum_balance(ComputeTask::LEN) + crank_reward;
+    let page_rent = rent.minimum_balance(TaskPage::LEN);
+    // Enough for three runs, but not a fourth
+    let funding = 3 * run_cost + page_rent + payment;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_recurring_task(
+            &program_id,
+            &recurring,
+            &requester.pubkey(),
+            &agent_address,
+            single_step_requirements(),
+            payment,
+            interval,
+            funding,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let created_at = clock.unix_timestamp;
+    let cranker_balance = context.banks_client.get_balance(cranker.pubkey()).await.unwrap();
+    for run in 0..3u64 {
+        // Each run is due once its interval has passed, and not before
+        if run > 0 {
+            blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+            let mut transaction = Transaction::new_with_payer(&[crank(run)], Some(&payer.pubkey()));
+            transaction.sign(&[&payer, &cranker], blockhash);
+            assert_instruction_error(
+                context.banks_client.process_transaction(transaction).await,
+                InstructionError::Custom(AIInfraError::RunNotDue as u32),
+            );
+
+            clock.unix_timestamp += interval;
+            context.set_sysvar(&clock);
+        }
+
+        blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+        let mut transaction = Transaction::new_with_payer(&[crank(run)], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &cranker], blockhash);
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = context.banks_client.get_account(task_address(run)).await.unwrap().unwrap();
+        let task = ComputeTask::unpack(&account.data).unwrap();
+        assert_eq!(task.requester, requester.pubkey());
+        assert_eq!(task.payment_amount, payment);
+        assert_eq!(task.requirements, single_step_requirements());
+        assert_eq!(
+            context.banks_client.get_balance(find_vault_address(&task_address(run), &program_id).0)
+                .await
+                .unwrap(),
+            payment + rent.minimum_balance(0)
+        );
+
+        let account = context.banks_client.get_account(recurring).await.unwrap().unwrap();
+        let stored = RecurringTask::unpack(&account.data).unwrap();
+        assert_eq!(stored.runs, run + 1);
+        assert_eq!(stored.next_run_at, created_at + (run as i64 + 1) * interval);
+        let spent = (run + 1) * run_cost + page_rent;
+        assert_eq!(stored.funding_balance, funding - spent);
+        assert_eq!(account.lamports, rent.minimum_balance(RecurringTask::LEN) + funding - spent);
+    }
+
+    // The cranker comes out ahead by the reward for each run
+    assert_eq!(
+        context.banks_client.get_balance(cranker.pubkey()).await.unwrap(),
+        cranker_balance + 3 * crank_reward
+    );
+
+    // The fourth run is due but the funding can't cover it
+    clock.unix_timestamp += interval;
+    context.set_sysvar(&clock);
+    blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[crank(3)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &cranker], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::InsufficientFunds,
+    );
+
+    // Only the requester can cancel, reclaiming the rest of the funding
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::cancel_recurring_task(&program_id, &recurring, &cranker.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &cranker], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let recurring_lamports = context.banks_client.get_balance(recurring).await.unwrap();
+    let requester_balance = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::cancel_recurring_task(&program_id, &recurring, &requester.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(recurring_lamports, rent.minimum_balance(RecurringTask::LEN) + payment);
+    assert!(context.banks_client.get_account(recurring).await.unwrap().is_none());
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + recurring_lamports
+    );
+}
+
+#[tokio::test]
+async fn test_split_task_shards() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 0, treasury);
+
+    let requester = Keypair::new();
+    let owners = [Keypair::new(), Keypair::new(), Keypair::new()];
+    program_test.add_account(requester.pubkey(), wallet_account());
+    for owner in &owners {
+        program_test.add_account(owner.pubkey(), wallet_account());
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut agents = Vec::new();
+    for owner in &owners {
+        create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, owner)
+            .await
+            .unwrap();
+        agents.push(find_agent_address(&owner.pubkey(), &program_id).0);
+    }
+
+    // Open tasks are numbered by the config's counter: the task first, then
+    // its shards
+    let open_task = |index| find_task_address(&Pubkey::default(), index, &program_id).0;
+    let vault_of = |task: &Pubkey| find_vault_address(task, &program_id).0;
+    let task = open_task(0);
+    let payment = 9_000;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::create_task(
+                &program_id,
+                &task,
+                &Pubkey::default(),
+                0,
+                &requester.pubkey(),
+                single_step_requirements(),
+                payment,
+            ),
+            instruction::split_task(
+                &program_id,
+                &task,
+                &Pubkey::default(),
+                &requester.pubkey(),
+                1,
+                vec![5_000, 3_000, 2_000],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(task).await.unwrap().unwrap();
+    let parent = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!((parent.status, parent.shard_count), (TaskStatus::Split, 3));
+    // The escrow moved to the shards, leaving the vault its reserve
+    assert_eq!(banks_client.get_balance(vault_of(&task)).await.unwrap(), rent.minimum_balance(0));
+
+    let mut shards = vec![open_task(1), open_task(2), open_task(3)];
+    let shard_payments = [4_500, 2_700, 1_800];
+    for (shard_index, (shard, shard_payment)) in shards.iter().zip(shard_payments).enumerate() {
+        let account = banks_client.get_account(*shard).await.unwrap().unwrap();
+        let stored = ComputeTask::unpack(&account.data).unwrap();
+        assert_eq!(stored.parent, Some(task));
+        assert_eq!(stored.shard_index as usize, shard_index);
+        assert_eq!(stored.payment_amount, shard_payment);
+        assert_eq!(stored.requester, requester.pubkey());
+        assert_eq!(stored.requirements, single_step_requirements());
+        assert_eq!(
+            banks_client.get_balance(vault_of(shard)).await.unwrap(),
+            shard_payment + rent.minimum_balance(0)
+        );
+    }
+
+    // Each agent claims, and so starts, a shard. The second shard's agent
+    // fails it, and the others complete theirs.
+    let claim_shard = |shard: Pubkey, agent: Pubkey, owner: &Keypair| {
+        claim_task_instruction(program_id, shard, agent, owner.pubkey())
+    };
+    let complete_shard = |shard: Pubkey, agent: Pubkey, owner: &Keypair, result_hash| {
+        instruction::complete_task(
+            &program_id,
+            &shard,
+            &agent,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            result_hash,
+        )
+    };
+    let result_hashes = [[1; 32], [2; 32], [3; 32]];
+    for (index, owner) in owners.iter().enumerate() {
+        let finish = if index == 1 {
+            fail_task_instruction(
+                program_id,
+                shards[index],
+                agents[index],
+                owner.pubkey(),
+                requester.pubkey(),
+                treasury,
+                FailureReason::AgentError,
+            )
+        } else {
+            complete_shard(shards[index], agents[index], owner, result_hashes[index])
+        };
+        let mut transaction = Transaction::new_with_payer(
+            &[claim_shard(shards[index], agents[index], owner), finish],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // Results can't be aggregated while a shard hasn't completed
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::aggregate_results(&program_id, &task, &shards)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // The failed shard's refund funds its replacement, which only the
+    // requester can issue, and only once
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::reissue_shard(&program_id, &task, &shards[1], &owners[1].pubkey(), 4)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owners[1]], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::reissue_shard(&program_id, &task, &shards[1], &requester.pubkey(), 4)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let replacement = open_task(4);
+    let account = banks_client.get_account(replacement).await.unwrap().unwrap();
+    let stored = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!((stored.parent, stored.shard_index), (Some(task), 1));
+    assert_eq!(stored.payment_amount, 2_700);
+    let shard_rent = rent.minimum_balance(ComputeTask::LEN) + rent.minimum_balance(0);
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance - 2_700 - shard_rent
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::reissue_shard(&program_id, &task, &shards[1], &requester.pubkey(), 5)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    // The first agent runs the replacement too
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            claim_shard(replacement, agents[0], &owners[0]),
+            complete_shard(replacement, agents[0], &owners[0], result_hashes[1]),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owners[0]], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Shards must be passed in shard order
+    shards[1] = replacement;
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::aggregate_results(&program_id, &task, &[shards[1], shards[0], shards[2]])],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::aggregate_results(&program_id, &task, &shards)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(task).await.unwrap().unwrap();
+    let parent = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!(parent.status, TaskStatus::Completed);
+    let [first, second, third] = &result_hashes;
+    assert_eq!(parent.result_hash, hashv(&[first, second, third]).to_bytes());
+}
+
+#[tokio::test]
+async fn test_redundant_task_quorum() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 250, treasury);
+
+    let requester = Keypair::new();
+    let owners = [Keypair::new(), Keypair::new(), Keypair::new(), Keypair::new()];
+    for wallet in [requester.pubkey(), treasury] {
+        program_test.add_account(wallet, wallet_account());
+    }
+    for owner in &owners {
+        program_test.add_account(owner.pubkey(), wallet_account());
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut agents = Vec::new();
+    for owner in &owners {
+        create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, owner)
+            .await
+            .unwrap();
+        agents.push(find_agent_address(&owner.pubkey(), &program_id).0);
+    }
+
+    let (task, _) = find_redundant_task_address(&requester.pubkey(), 0, &program_id);
+    let vault = find_vault_address(&task, &program_id).0;
+    let payment = 10_000;
+    let create = |quorum| {
+        instruction::create_redundant_task(
+            &program_id,
+            &task,
+            &requester.pubkey(),
+            single_step_requirements(),
+            payment,
+            3,
+            quorum,
+        )
+    };
+    let submit = |index: usize, result_hash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::submit_redundant_result(
+                &program_id,
+                &task,
+                &agents[index],
+                &owners[index].pubkey(),
+                result_hash,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owners[index]], recent_blockhash);
+        transaction
+    };
+    let submitters: Vec<_> = [0, 1, 2].iter().map(|&i| (agents[i], owners[i].pubkey())).collect();
+    let finalize = |submitters: &[(Pubkey, Pubkey)]| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::finalize_redundant_task(
+                &program_id,
+                &task,
+                &requester.pubkey(),
+                &treasury,
+                submitters,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], recent_blockhash);
+        transaction
+    };
+
+    // A quorum must be a majority of the replicas
+    let mut transaction = Transaction::new_with_payer(&[create(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let mut transaction = Transaction::new_with_payer(&[create(2)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(vault).await.unwrap(),
+        3 * payment + rent.minimum_balance(0)
+    );
+
+    // Each agent submits once, and settling waits on a quorum
+    let agreed = [7; 32];
+    banks_client.process_transaction(submit(0, agreed)).await.unwrap();
+    assert_instruction_error(
+        banks_client.process_transaction(submit(0, [8; 32])).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_instruction_error(
+        banks_client.process_transaction(finalize(&submitters[..1])).await,
+        InstructionError::Custom(AIInfraError::QuorumNotReached as u32),
+    );
+
+    // The second agent dissents, and the third agrees with the first
+    banks_client.process_transaction(submit(1, [9; 32])).await.unwrap();
+    banks_client.process_transaction(submit(2, agreed)).await.unwrap();
+
+    // Every replica is taken
+    assert_instruction_error(
+        banks_client.process_transaction(submit(3, agreed)).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+
+    let mut before = Vec::new();
+    for wallet in [owners[0].pubkey(), owners[1].pubkey(), owners[2].pubkey(), treasury] {
+        before.push(banks_client.get_balance(wallet).await.unwrap());
+    }
+    let requester_before = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    banks_client.process_transaction(finalize(&submitters)).await.unwrap();
+
+    // The agreeing agents are paid their replicas, less the fee, and the
+    // dissenter's replica goes back to the requester with the vault reserve
+    let fee = payment * 250 / 10_000;
+    let mut after = Vec::new();
+    for wallet in [owners[0].pubkey(), owners[1].pubkey(), owners[2].pubkey(), treasury] {
+        after.push(banks_client.get_balance(wallet).await.unwrap());
+    }
+    assert_eq!(after[0], before[0] + payment - fee);
+    assert_eq!(after[1], before[1]);
+    assert_eq!(after[2], before[2] + payment - fee);
+    assert_eq!(after[3], before[3] + 2 * fee);
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before + payment + rent.minimum_balance(0)
+    );
+    assert_eq!(banks_client.get_balance(vault).await.unwrap(), 0);
+
+    for (index, (completed, failed, reputation)) in
+        [(1, 0, 100), (0, 1, 100 - REPUTATION_PENALTY), (1, 0, 100)].into_iter().enumerate()
+    {
+        let account = banks_client.get_account(agents[index]).await.unwrap().unwrap();
+        let agent = AIAgent::unpack(&account.data).unwrap();
+        assert_eq!((agent.tasks_completed, agent.tasks_failed), (completed, failed));
+        assert_eq!(agent.reputation_score, reputation);
+    }
+
+    let account = banks_client.get_account(task).await.unwrap().unwrap();
+    let stored = RedundantTask::unpack(&account.data).unwrap();
+    assert!(stored.finalized);
+    assert_eq!(stored.agreed_hash, Some(agreed));
+    assert_instruction_error(
+        banks_client.process_transaction(finalize(&submitters[..2])).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_redundant_task_expires_without_quorum() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 250, treasury);
+
+    let requester = Keypair::new();
+    let owners = [Keypair::new(), Keypair::new()];
+    for wallet in [requester.pubkey(), treasury] {
+        program_test.add_account(wallet, wallet_account());
+    }
+    for owner in &owners {
+        program_test.add_account(owner.pubkey(), wallet_account());
+    }
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let mut agents = Vec::new();
+    for owner in &owners {
+        create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, owner)
+            .await
+            .unwrap();
+        agents.push(find_agent_address(&owner.pubkey(), &program_id).0);
+    }
+
+    let (task, _) = find_redundant_task_address(&requester.pubkey(), 0, &program_id);
+    let vault = find_vault_address(&task, &program_id).0;
+    let payment = 10_000;
+    let submit = |index: usize, blockhash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::submit_redundant_result(
+                &program_id,
+                &task,
+                &agents[index],
+                &owners[index].pubkey(),
+                [7; 32],
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owners[index]], blockhash);
+        transaction
+    };
+    let finalize = |blockhash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::finalize_redundant_task(
+                &program_id,
+                &task,
+                &requester.pubkey(),
+                &treasury,
+                &[(agents[0], owners[0].pubkey())],
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], blockhash);
+        transaction
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_redundant_task(
+            &program_id,
+            &task,
+            &requester.pubkey(),
+            single_step_requirements(),
+            payment,
+            3,
+            2,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+
+    // One of three replicas submits, short of the quorum of two
+    context.banks_client.process_transaction(submit(0, blockhash)).await.unwrap();
+    assert_instruction_error(
+        context.banks_client.process_transaction(finalize(blockhash)).await,
+        InstructionError::Custom(AIInfraError::QuorumNotReached as u32),
+    );
+
+    // Once the task expires it takes no more results
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += REDUNDANT_TASK_EXPIRY_SECONDS;
+    context.set_sysvar(&clock);
+    let blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    assert_instruction_error(
+        context.banks_client.process_transaction(submit(1, blockhash)).await,
+        InstructionError::Custom(AIInfraError::TaskExpired as u32),
+    );
+
+    // and settles without a quorum, refunding the whole escrow and leaving
+    // the lone submitter's record alone
+    let requester_before = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    context.banks_client.process_transaction(finalize(blockhash)).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before + 3 * payment + rent.minimum_balance(0)
+    );
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), 0);
+    let account = context.banks_client.get_account(agents[0]).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!((agent.tasks_completed, agent.tasks_failed, agent.reputation_score), (0, 0, 100));
+    let account = context.banks_client.get_account(task).await.unwrap().unwrap();
+    let stored = RedundantTask::unpack(&account.data).unwrap();
+    assert!(stored.finalized);
+    assert_eq!(stored.agreed_hash, None);
+}
+
+#[tokio::test]
+async fn test_agent_metadata() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_test_agent(&mut banks_client, &payer, recent_blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (metadata_address, _) = find_metadata_address(&agent, &program_id);
+    let set_metadata = |name: &str, uri: &str, endpoint: &str| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::set_agent_metadata(
+                &program_id,
+                &agent,
+                &owner.pubkey(),
+                name,
+                uri,
+                endpoint,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &owner], recent_blockhash);
+        transaction
+    };
+
+    // The first call creates the account, at the owner's expense
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let transaction = set_metadata("worker", "https://example.com/worker.json", "grpc://w:443");
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client.get_account(metadata_address).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), AgentMetadata::LEN);
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance - account.lamports
+    );
+    let metadata = AgentMetadata::unpack(&account.data).unwrap();
+    assert_eq!(metadata.agent, agent);
+    assert_eq!(metadata.name, "worker");
+    assert_eq!(metadata.uri, "https://example.com/worker.json");
+    assert_eq!(metadata.endpoint, "grpc://w:443");
+
+    // Later calls replace every field, longest allowed included
+    let name = "n".repeat(MAX_METADATA_NAME_LEN);
+    let transaction = set_metadata(&name, "", "grpc://w2:443");
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client.get_account(metadata_address).await.unwrap().unwrap();
+    let metadata = AgentMetadata::unpack(&account.data).unwrap();
+    assert_eq!((metadata.name.as_str(), metadata.uri.as_str()), (name.as_str(), ""));
+    assert_eq!(metadata.endpoint, "grpc://w2:443");
+
+    // A byte over a limit is rejected
+    for transaction in [
+        set_metadata(&(name.clone() + "n"), "", ""),
+        set_metadata("worker", &"u".repeat(MAX_METADATA_URI_LEN + 1), ""),
+    ] {
+        assert_instruction_error(
+            banks_client.process_transaction(transaction).await,
+            InstructionError::Custom(AIInfraError::InvalidMetadata as u32),
+        );
+    }
+
+    // as are bytes that aren't UTF-8
+    let mut bad_name =
+        instruction::set_agent_metadata(&program_id, &agent, &owner.pubkey(), "ok", "", "");
+    bad_name.data[3] = 0xff;
+    let mut transaction = Transaction::new_with_payer(&[bad_name], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::InvalidInstructionData,
+    );
+
+    // Only the owner may set it
+    let stranger = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_agent_metadata(
+            &program_id,
+            &agent,
+            &stranger.pubkey(),
+            "impostor",
+            "",
+            "",
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stranger], recent_blockhash);
+    assert_instruction_error(
+        banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+    let account = banks_client.get_account(metadata_address).await.unwrap().unwrap();
+    assert_eq!(AgentMetadata::unpack(&account.data).unwrap().name, name);
+}
+
+#[tokio::test]
+async fn test_task_rate_limit() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let exempt = Keypair::new();
+    for wallet in [&owner, &requester, &exempt] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        treasury: Pubkey::new_unique(),
+        max_tasks_per_window: 2,
+        window_slots: 1_000,
+        rate_limit_exempt: vec![exempt.pubkey()],
+        ..Config::default()
+    };
+    add_config_account(&mut program_test, program_id, config);
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (requester_address, _) = find_requester_address(&requester.pubkey(), &program_id);
+    let create_task = |index, wallet: &Keypair, blockhash| {
+        let (task, _) = find_task_address(&agent_address, index, &program_id);
+        let instruction = instruction::create_task(
+            &program_id,
+            &task,
+            &agent_address,
+            index,
+            &wallet.pubkey(),
+            single_step_requirements(),
+            1000,
+        );
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, wallet], blockhash);
+        transaction
+    };
+
+    // Start on a window boundary, so the window can't roll over mid-test
+    context.warp_to_slot(1_000).unwrap();
+    let blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+
+    // The requester's first tasks create their requester account to count in
+    for index in 0..2 {
+        let transaction = create_task(index, &requester, blockhash);
+        context.banks_client.process_transaction(transaction).await.unwrap();
+    }
+    let account = context.banks_client.get_account(requester_address).await.unwrap().unwrap();
+    let stored = Requester::unpack(&account.data).unwrap();
+    assert_eq!((stored.last_task_slot, stored.tasks_in_window), (1_000, 2));
+
+    // and the window's limit stops the next
+    assert_instruction_error(
+        context.banks_client.process_transaction(create_task(2, &requester, blockhash)).await,
+        InstructionError::Custom(AIInfraError::RateLimited as u32),
+    );
+
+    // Leaving the requester account off doesn't get around it
+    let (task, _) = find_task_address(&agent_address, 2, &program_id);
+    let mut instruction = instruction::create_task(
+        &program_id,
+        &task,
+        &agent_address,
+        2,
+        &requester.pubkey(),
+        single_step_requirements(),
+        1000,
+    );
+    instruction.accounts.pop();
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+
+    // An exempt requester isn't counted, and needs no requester account
+    for index in 2..5 {
+        let transaction = create_task(index, &exempt, blockhash);
+        context.banks_client.process_transaction(transaction).await.unwrap();
+    }
+    let (exempt_address, _) = find_requester_address(&exempt.pubkey(), &program_id);
+    assert!(context.banks_client.get_account(exempt_address).await.unwrap().is_none());
+
+    // Once the window elapses the count starts over
+    context.warp_to_slot(2_500).unwrap();
+    let blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    for index in 5..7 {
+        let transaction = create_task(index, &requester, blockhash);
+        context.banks_client.process_transaction(transaction).await.unwrap();
+    }
+    let account = context.banks_client.get_account(requester_address).await.unwrap().unwrap();
+    let stored = Requester::unpack(&account.data).unwrap();
+    assert_eq!((stored.last_task_slot, stored.tasks_in_window), (2_500, 2));
+    assert_instruction_error(
+        context.banks_client.process_transaction(create_task(7, &requester, blockhash)).await,
+        InstructionError::Custom(AIInfraError::RateLimited as u32),
+    );
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().tasks_created, 7);
+}
+
+#[tokio::test]
+async fn test_withdrawal_policy() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    program_test.add_account(owner.pubkey(), wallet_account());
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config { admin: Pubkey::new_unique(), ..Config::default() },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, &owner)
+        .await
+        .unwrap();
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let sign = |instructions: &[Instruction]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &owner], blockhash);
+        transaction
+    };
+    let withdraw = |amount| {
+        sign(&[instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &owner.pubkey(),
+            amount,
+            None,
+        )])
+    };
+    let set_policy = |max_per_day, cooldown_seconds| {
+        sign(&[instruction::set_withdrawal_policy(
+            &program_id,
+            &agent_address,
+            &owner.pubkey(),
+            WithdrawalPolicy { max_per_day, cooldown_seconds },
+        )])
+    };
+
+    // Start just into a day, so none of it rolls over unexpectedly
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let day = 86_400;
+    let today = (clock.unix_timestamp / day + 1) * day;
+    clock.unix_timestamp = today + 100;
+    context.set_sysvar(&clock);
+
+    let deposit = instruction::deposit_credits(
+        &program_id,
+        &agent_address,
+        &owner.pubkey(),
+        20_000,
+        None,
+    );
+    context.banks_client.process_transaction(sign(&[deposit])).await.unwrap();
+    context.banks_client.process_transaction(set_policy(1_000, 60)).await.unwrap();
+
+    // Withdrawals wait out the cooldown and stop at the daily limit
+    context.banks_client.process_transaction(withdraw(600)).await.unwrap();
+    assert_instruction_error(
+        context.banks_client.process_transaction(withdraw(300)).await,
+        InstructionError::Custom(AIInfraError::WithdrawalCooldownActive as u32),
+    );
+    clock.unix_timestamp += 60;
+    context.set_sysvar(&clock);
+    assert_instruction_error(
+        context.banks_client.process_transaction(withdraw(401)).await,
+        InstructionError::Custom(AIInfraError::WithdrawalLimitExceeded as u32),
+    );
+    context.banks_client.process_transaction(withdraw(400)).await.unwrap();
+
+    // Turning the policy off is a loosening, so it waits out the delay
+    let loosened_at = clock.unix_timestamp;
+    context.banks_client.process_transaction(set_policy(0, 0)).await.unwrap();
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    let policy = WithdrawalPolicy { max_per_day: 1_000, cooldown_seconds: 60 };
+    assert_eq!(agent.withdrawal_policy, policy);
+    assert_eq!(agent.pending_policy_at, loosened_at + WITHDRAWAL_POLICY_DELAY_SECONDS);
+    clock.unix_timestamp += 60;
+    context.set_sysvar(&clock);
+    assert_instruction_error(
+        context.banks_client.process_transaction(withdraw(1)).await,
+        InstructionError::Custom(AIInfraError::WithdrawalLimitExceeded as u32),
+    );
+
+    // The limit starts over at midnight
+    clock.unix_timestamp = today + day;
+    context.set_sysvar(&clock);
+    context.banks_client.process_transaction(withdraw(1_000)).await.unwrap();
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!((agent.withdrawn_today, agent.withdrawal_day), (1_000, today + day));
+    assert_eq!(agent.compute_credits, 20_000 - 2_000);
+
+    // Once the delay is over the looser policy applies
+    clock.unix_timestamp = loosened_at + WITHDRAWAL_POLICY_DELAY_SECONDS;
+    context.set_sysvar(&clock);
+    context.banks_client.process_transaction(withdraw(5_000)).await.unwrap();
+    context.banks_client.process_transaction(withdraw(5_001)).await.unwrap();
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!(agent.withdrawal_policy, WithdrawalPolicy::default());
+    assert_eq!(agent.pending_policy_at, 0);
+
+    // while a stricter one applies at once
+    context.banks_client.process_transaction(set_policy(10, 0)).await.unwrap();
+    assert_instruction_error(
+        context.banks_client.process_transaction(withdraw(11)).await,
+        InstructionError::Custom(AIInfraError::WithdrawalLimitExceeded as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_agent_grants() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let admin = Keypair::new();
+    let owner = Keypair::new();
+    let late_owner = Keypair::new();
+    for wallet in [&admin, &owner, &late_owner] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config { admin: admin.pubkey(), ..Config::default() },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    let sign = |instructions: &[Instruction], signer: &Keypair| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&payer, signer], blockhash);
+        transaction
+    };
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (late_agent, _) = find_agent_address(&late_owner.pubkey(), &program_id);
+    let (faucet, _) = find_faucet_address(&program_id);
+    let grant = 5_000;
+
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.epoch = 10;
+    context.set_sysvar(&clock);
+    for wallet in [&owner, &late_owner] {
+        create_test_agent(&mut context.banks_client, &payer, blockhash, program_id, wallet)
+            .await
+            .unwrap();
+    }
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().registered_at_epoch, 10);
+
+    // Nothing can be claimed until the admin sets an amount and funds it
+    let claim = || instruction::claim_grant(&program_id, &agent_address, &owner.pubkey());
+    assert_instruction_error(
+        context.banks_client.process_transaction(sign(&[claim()], &owner)).await,
+        InstructionError::Custom(AIInfraError::GrantsDisabled as u32),
+    );
+    let not_admin = instruction::set_grant_amount(&program_id, &owner.pubkey(), grant);
+    assert_instruction_error(
+        context.banks_client.process_transaction(sign(&[not_admin], &owner)).await,
+        InstructionError::MissingRequiredSignature,
+    );
+    let set_amount = instruction::set_grant_amount(&program_id, &admin.pubkey(), grant);
+    let fund = instruction::fund_faucet(&program_id, &admin.pubkey(), grant);
+    context.banks_client.process_transaction(sign(&[set_amount], &admin)).await.unwrap();
+    // A retry of the claim above, so it needs a fresh blockhash to run again
+    let retry_blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], retry_blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::FaucetEmpty as u32),
+    );
+    context.banks_client.process_transaction(sign(&[fund.clone(), fund], &admin)).await.unwrap();
+    let faucet_before = context.banks_client.get_balance(faucet).await.unwrap();
+
+    // An agent claims once, in the epoch after it registered at the latest
+    clock.epoch = 11;
+    context.set_sysvar(&clock);
+    let blockhash =
+        context.banks_client.get_new_latest_blockhash(&retry_blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let account = context.banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!((agent.compute_credits, agent.grant_claimed), (grant, true));
+    assert_eq!(
+        context.banks_client.get_balance(faucet).await.unwrap(),
+        faucet_before - grant
+    );
+
+    let blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::GrantAlreadyClaimed as u32),
+    );
+
+    // Past that, the window has closed though the faucet could still pay
+    clock.epoch = 12;
+    context.set_sysvar(&clock);
+    let claim = instruction::claim_grant(&program_id, &late_agent, &late_owner.pubkey());
+    let mut transaction = Transaction::new_with_payer(&[claim], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &late_owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::GrantWindowClosed as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_insurance_pool() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let admin = Keypair::new();
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    for wallet in [admin.pubkey(), owner.pubkey(), requester.pubkey(), treasury] {
+        program_test.add_account(wallet, wallet_account());
+    }
+    let window = 3600;
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config {
+            admin: admin.pubkey(),
+            treasury,
+            insurance_bps: 1_000,
+            max_insurance_claim: 400,
+            insurance_claim_window_seconds: window,
+            ..Config::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let mut blockhash = context.last_blockhash;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (pool_address, _) = find_insurance_pool_address(&program_id);
+    let task = |index| find_task_address(&agent_address, index, &program_id).0;
+    let payment = 5_000;
+    let create_and_start = |index| {
+        [
+            instruction::create_task(
+                &program_id,
+                &task(index),
+                &agent_address,
+                index,
+                &requester.pubkey(),
+                ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+                payment,
+            ),
+            instruction::start_task(&program_id, &task(index), &agent_address, &owner.pubkey()),
+        ]
+    };
+    let fail = |index| {
+        fail_task_instruction(
+            program_id,
+            task(index),
+            agent_address,
+            owner.pubkey(),
+            requester.pubkey(),
+            treasury,
+            FailureReason::AgentError,
+        )
+    };
+    let complete = |index| {
+        instruction::complete_task(
+            &program_id,
+            &task(index),
+            &agent_address,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [1; 32],
+        )
+    };
+    let claim = |index| {
+        instruction::file_insurance_claim(&program_id, &task(index), &requester.pubkey())
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::initialize_insurance_pool(&program_id, &admin.pubkey()),
+            instruction::register_agent(&program_id, &agent_address, &owner.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &admin, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let pool_reserve = context.banks_client.get_balance(pool_address).await.unwrap();
+    assert_eq!(pool_reserve, Rent::default().minimum_balance(InsurancePool::LEN));
+
+    // Task 0 fails while the pool holds nothing past its rent reserve. The
+    // vault holds the refund back for the claim, which can't pay yet.
+    let mut instructions = create_and_start(0).to_vec();
+    instructions.push(fail(0));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let account = context.banks_client.get_account(task(0)).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&account.data).unwrap().held_refund, payment);
+    let (vault_address, _) = find_vault_address(&task(0), &program_id);
+    let vault_reserve = Rent::default().minimum_balance(0);
+    assert_eq!(
+        context.banks_client.get_balance(vault_address).await.unwrap(),
+        vault_reserve + payment
+    );
+    let task_rent = context.banks_client.get_balance(task(0)).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim(0)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InsurancePoolEmpty as u32),
+    );
+
+    // Completing task 1 needs the pool, which takes a tenth of the payment
+    let mut transaction = Transaction::new_with_payer(&create_and_start(1), Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[complete(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    let owner_before = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let complete_insured = instruction::with_insurance_pool(complete(1), &program_id);
+    let mut transaction = Transaction::new_with_payer(&[complete_insured], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let haircut = payment / 10;
+    assert_eq!(
+        context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_before + payment - haircut
+    );
+    assert_eq!(
+        context.banks_client.get_balance(pool_address).await.unwrap(),
+        pool_reserve + haircut
+    );
+
+    // The claim on task 0 now pays out of the pool, capped at the config's
+    // maximum. The held escrow pays the pool back as much and the requester
+    // the rest, so they get back the payment once, not twice.
+    blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let requester_before = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim(0)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before + payment + vault_reserve
+    );
+    assert_eq!(context.banks_client.get_balance(vault_address).await.unwrap(), 0);
+    assert_eq!(
+        context.banks_client.get_balance(pool_address).await.unwrap(),
+        pool_reserve + haircut
+    );
+    let account = context.banks_client.get_account(task(0)).await.unwrap().unwrap();
+    let claimed = ComputeTask::unpack(&account.data).unwrap();
+    assert!(claimed.insurance_claimed);
+    assert_eq!(claimed.held_refund, 0);
+
+    // but only once
+    blockhash = context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[claim(0)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InsuranceAlreadyClaimed as u32),
+    );
+
+    // Completed tasks aren't insured, and a failed one only within the window
+    let mut transaction = Transaction::new_with_payer(&[claim(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InvalidTaskStatus as u32),
+    );
+    let mut instructions = create_and_start(2).to_vec();
+    instructions.push(fail(2));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += window + 1;
+    context.set_sysvar(&clock);
+    let mut transaction = Transaction::new_with_payer(&[claim(2)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::Custom(AIInfraError::InsuranceClaimWindowClosed as u32),
+    );
+
+    // Closing the task returns the held refund to the requester instead
+    let close = close_task_instruction(
+        program_id,
+        task(2),
+        agent_address,
+        requester.pubkey(),
+        requester.pubkey(),
+    );
+    let mut transaction =
+        Transaction::new_with_payer(std::slice::from_ref(&close), Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    let requester_before = context.banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let close = instruction::with_held_refund(close, &program_id, &task(2));
+    let mut transaction = Transaction::new_with_payer(&[close], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &requester], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_before + task_rent + vault_reserve + payment
+    );
+    assert!(context.banks_client.get_account(task(2)).await.unwrap().is_none());
+
+    // The admin sweeps what is left past the reserve to the treasury
+    let left = haircut;
+    let sweep = |amount| {
+        instruction::sweep_insurance_pool(&program_id, &admin.pubkey(), &treasury, amount)
+    };
+    let mut transaction = Transaction::new_with_payer(&[sweep(left + 1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &admin], blockhash);
+    assert_instruction_error(
+        context.banks_client.process_transaction(transaction).await,
+        InstructionError::InsufficientFunds,
+    );
+    let treasury_before = context.banks_client.get_balance(treasury).await.unwrap();
+    let mut transaction = Transaction::new_with_payer(&[sweep(left)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &admin], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(treasury).await.unwrap(),
+        treasury_before + left
+    );
+    assert_eq!(context.banks_client.get_balance(pool_address).await.unwrap(), pool_reserve);
+    let account = context.banks_client.get_account(pool_address).await.unwrap().unwrap();
+    let pool = InsurancePool::unpack(&account.data).unwrap();
+    assert_eq!(
+        (pool.total_collected, pool.total_paid, pool.total_swept),
+        (haircut + 400, 400, left)
     );
-    
-    transaction.sign(&[payer, owner_keypair], recent_blockhash);
-    banks_client.process_transaction(transaction).await
 }
 
 #[tokio::test]
-async fn test_agent_registration() {
+async fn test_multisig_owner() {
     let program_id = Pubkey::new_unique();
     let mut program_test = ProgramTest::new(
         "sonic_ai_infra",
         program_id,
         processor!(process_instruction),
     );
+    add_registry(&mut program_test, program_id);
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config { admin: Pubkey::new_unique(), ..Config::default() },
+    );
 
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let owner = Keypair::new();
+    let new_owner = Keypair::new();
+    let members = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let outsider = Keypair::new();
+    for wallet in [&owner, &new_owner] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
 
-    // Create a new account
-    let agent_account = Keypair::new();
-    
-    // Calculate exact size needed
-    let space = 32 + // Pubkey (owner)
-                8 +  // u64 (compute_credits)
-                4 +  // u32 (reputation_score)
-                4 +  // u32 (tasks_completed)
-                1;   // bool (is_active)
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (multisig_address, _) = find_multisig_address(&agent_address, &program_id);
+    let (other_agent, _) = find_agent_address(&new_owner.pubkey(), &program_id);
+    let member_keys = members.each_ref().map(|member| member.pubkey());
+    let send = |instruction: Instruction, signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), recent_blockhash);
+        transaction
+    };
+    let withdraw = |amount| {
+        instruction::withdraw_credits(&program_id, &agent_address, &owner.pubkey(), amount, None)
+    };
+    let with_multisig = |instruction, signers: &[&Keypair]| {
+        let keys: Vec<Pubkey> = signers.iter().map(|signer| signer.pubkey()).collect();
+        instruction::with_multisig(instruction, &program_id, &agent_address, &keys)
+    };
 
-    let rent = banks_client.get_rent().await.unwrap();
-    let lamports = rent.minimum_balance(space);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::register_agent(&program_id, &agent_address, &owner.pubkey()),
+            instruction::register_agent(&program_id, &other_agent, &new_owner.pubkey()),
+            instruction::deposit_credits(&program_id, &agent_address, &owner.pubkey(), 5_000, None),
+            instruction::set_multisig_owner(
+                &program_id,
+                &agent_address,
+                &owner.pubkey(),
+                &member_keys,
+                2,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner, &new_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert!(AIAgent::unpack(&account.data).unwrap().multisig_owner);
+    let account = banks_client.get_account(multisig_address).await.unwrap().unwrap();
+    let multisig = MultisigOwner::unpack(&account.data).unwrap();
+    assert_eq!((multisig.threshold, multisig.signers), (2, member_keys.to_vec()));
 
-    let create_account_ix = system_instruction::create_account(
-        &payer.pubkey(),
-        &agent_account.pubkey(),
-        lamports,
-        space as u64,
-        &program_id,
+    // The owner's key alone no longer withdraws
+    assert_instruction_error(
+        banks_client.process_transaction(send(withdraw(100), &[&owner])).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    // nor moves credits, changes the agent's status or deregisters it
+    let transfer =
+        transfer_credits_instruction(program_id, agent_address, other_agent, owner.pubkey(), 100);
+    assert_instruction_error(
+        banks_client.process_transaction(send(transfer, &[&owner])).await,
+        InstructionError::NotEnoughAccountKeys,
     );
+    let update_status =
+        instruction::update_agent_status(&program_id, &agent_address, &owner.pubkey(), false);
+    let deregister = deregister_agent_instruction(program_id, owner.pubkey());
+    for instruction in [update_status, deregister] {
+        assert_instruction_error(
+            banks_client.process_transaction(send(instruction, &[&owner])).await,
+            InstructionError::Custom(AIInfraError::AgentOwnedByMultisig as u32),
+        );
+    }
+
+    // One member is short of the threshold, and an outsider doesn't count
+    let [first, second, third] = members.each_ref();
+    for signers in [&[first][..], &[first, &outsider]] {
+        assert_instruction_error(
+            banks_client.process_transaction(send(with_multisig(withdraw(100), signers), signers))
+                .await,
+            InstructionError::Custom(AIInfraError::MultisigThresholdNotMet as u32),
+        );
+    }
+
+    // Two of the three withdraw to the owner, who doesn't sign
+    let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    banks_client
+        .process_transaction(send(with_multisig(withdraw(100), &[first, third]), &[first, third]))
+        .await
+        .unwrap();
+    assert_eq!(banks_client.get_balance(owner.pubkey()).await.unwrap(), owner_before + 100);
+
+    let operator = Pubkey::new_unique();
+    let set_operator =
+        set_operator_instruction(program_id, agent_address, owner.pubkey(), operator);
+    banks_client
+        .process_transaction(send(with_multisig(set_operator, &[second, third]), &[second, third]))
+        .await
+        .unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().operator, Some(operator));
+
+    let set_limits = instruction::set_agent_limits(&program_id, &agent_address, &owner.pubkey(), 4);
+    banks_client
+        .process_transaction(send(with_multisig(set_limits, &[first, second]), &[first, second]))
+        .await
+        .unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().max_concurrent_tasks, 4);
 
-    let register_ix = Instruction::new_with_borsh(
+    // Handing the agent on returns it to a single key, the new owner's
+    let transfer = Instruction::new_with_borsh(
         program_id,
-        &AIInfraInstruction::RegisterAgent,
+        &AIInfraInstruction::TransferAgentOwnership { new_owner: new_owner.pubkey() },
         vec![
-            AccountMeta::new(agent_account.pubkey(), false),
-            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(agent_address, false),
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new_readonly(new_owner.pubkey(), true),
         ],
     );
-
-    let transaction = Transaction::new_signed_with_payer(
-        &[create_account_ix, register_ix],
-        Some(&payer.pubkey()),
-        &[&payer, &agent_account],
-        recent_blockhash,
-    );
-
-    // Just verify the transaction succeeds
-    banks_client.process_transaction(transaction).await.unwrap();
-
-    // Only verify the account exists
-    let account = banks_client.get_account(agent_account.pubkey()).await.unwrap().unwrap();
-    assert_eq!(account.owner, program_id);
+    banks_client
+        .process_transaction(send(
+            with_multisig(transfer, &[first, second]),
+            &[&new_owner, first, second],
+        ))
+        .await
+        .unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!((agent.owner, agent.multisig_owner), (new_owner.pubkey(), false));
 }
 
 #[tokio::test]
-async fn test_task_creation_and_execution() {
+async fn test_nft_owned_agent() {
     let program_id = Pubkey::new_unique();
     let mut program_test = ProgramTest::new(
         "sonic_ai_infra",
         program_id,
         processor!(process_instruction),
     );
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
-    
-    let agent_keypair = Keypair::new();
-    let task_keypair = Keypair::new();
-    let owner_keypair = Keypair::new();
+    add_registry(&mut program_test, program_id);
+    let treasury = Pubkey::new_unique();
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config { admin: Pubkey::new_unique(), treasury, ..Config::default() },
+    );
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
 
-    // Setup initial agent account with credits
-    let initial_credits = 1000;
-    let agent = AIAgent {
-        owner: owner_keypair.pubkey(),
-        compute_credits: initial_credits,
-        reputation_score: 100,
-        tasks_completed: 0,
-        is_active: true,
-    };
+    let owner = Keypair::new();
+    let buyer = Keypair::new();
+    let operator = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &buyer, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    // A one-of-one mint, and a fungible one an agent can't be bound to
+    let nft_mint = Pubkey::new_unique();
+    let mut nft = mint_account();
+    let mut mint = spl_token::state::Mint::unpack(&nft.data).unwrap();
+    (mint.supply, mint.decimals) = (1, 0);
+    mint.pack_into_slice(&mut nft.data);
+    program_test.add_account(nft_mint, nft);
+    let fungible_mint = Pubkey::new_unique();
+    program_test.add_account(fungible_mint, mint_account());
+    let owner_token = Pubkey::new_unique();
+    let buyer_token = Pubkey::new_unique();
+    program_test.add_account(owner_token, token_account_with(nft_mint, owner.pubkey(), 1));
+    program_test.add_account(buyer_token, token_account_with(nft_mint, buyer.pubkey(), 0));
 
-    let agent_account = Account {
-        lamports: Rent::default().minimum_balance(std::mem::size_of::<AIAgent>()),
-        data: agent.try_to_vec().unwrap(),
-        owner: program_id,
-        executable: false,
-        rent_epoch: Epoch::default(),
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let send = |instructions: &[Instruction], signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), recent_blockhash);
+        transaction
+    };
+    let withdraw = |holder: &Keypair, holder_token: &Pubkey, amount| {
+        let instruction = instruction::withdraw_credits(
+            &program_id,
+            &agent_address,
+            &holder.pubkey(),
+            amount,
+            None,
+        );
+        instruction::with_nft_holder(instruction, holder_token)
     };
 
-    program_test.add_account(agent_keypair.pubkey(), agent_account);
-
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    banks_client
+        .process_transaction(send(
+            &[
+                instruction::register_agent(&program_id, &agent_address, &owner.pubkey()),
+                instruction::deposit_credits(
+                    &program_id,
+                    &agent_address,
+                    &owner.pubkey(),
+                    5_000,
+                    None,
+                ),
+            ],
+            &[&owner],
+        ))
+        .await
+        .unwrap();
 
-    // Create task
-    let requirements = ComputeRequirements {
-        cpu_units: 100,
-        memory_mb: 512,
-        storage_mb: 1024,
-        max_time_seconds: 3600,
-    };
+    let bind =
+        |mint| instruction::bind_agent_to_nft(&program_id, &agent_address, &owner.pubkey(), mint);
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[bind(&fungible_mint)], &[&owner])).await,
+        InstructionError::Custom(AIInfraError::InvalidNftMint as u32),
+    );
+    banks_client.process_transaction(send(&[bind(&nft_mint)], &[&owner])).await.unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().owner_nft_mint, Some(nft_mint));
 
-    let payment_amount = 500;
-    let instruction_data = AIInfraInstruction::CreateTask {
-        requirements,
-        payment_amount,
-    }
-    .try_to_vec()
-    .unwrap();
+    // An operator completes a task, leaving its payment for whoever holds the NFT
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let set_operator =
+        set_operator_instruction(program_id, agent_address, owner.pubkey(), operator.pubkey());
+    banks_client
+        .process_transaction(send(
+            &[instruction::with_nft_holder(set_operator, &owner_token)],
+            &[&owner],
+        ))
+        .await
+        .unwrap();
+    banks_client
+        .process_transaction(send(
+            &[
+                instruction::create_task(
+                    &program_id,
+                    &task_address,
+                    &agent_address,
+                    0,
+                    &requester.pubkey(),
+                    single_step_requirements(),
+                    1_000,
+                ),
+                instruction::start_task(
+                    &program_id,
+                    &task_address,
+                    &agent_address,
+                    &operator.pubkey(),
+                ),
+                instruction::complete_task(
+                    &program_id,
+                    &task_address,
+                    &agent_address,
+                    &operator.pubkey(),
+                    &requester.pubkey(),
+                    &treasury,
+                    [1; 32],
+                ),
+            ],
+            &[&requester, &operator],
+        ))
+        .await
+        .unwrap();
 
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(
-            program_id,
-            &instruction_data,
-            vec![
-                AccountMeta::new(task_keypair.pubkey(), false),
-                AccountMeta::new(agent_keypair.pubkey(), false),
-                AccountMeta::new(owner_keypair.pubkey(), true),
+    // and proves a batch's results, whose items pay the holder too
+    let (batch_address, _) = find_batch_task_address(&agent_address, 0, &program_id);
+    let leaves: Vec<[u8; 32]> = (0..2u32).map(|i| merkle::leaf(i, &[i as u8; 32])).collect();
+    banks_client
+        .process_transaction(send(
+            &[
+                instruction::create_batch_task(
+                    &program_id,
+                    &batch_address,
+                    &agent_address,
+                    &requester.pubkey(),
+                    2,
+                    500,
+                ),
+                instruction::submit_batch_result(
+                    &program_id,
+                    &batch_address,
+                    &agent_address,
+                    &operator.pubkey(),
+                    merkle::root(&leaves),
+                ),
             ],
-        )],
-        Some(&payer.pubkey()),
-    );
+            &[&requester, &operator],
+        ))
+        .await
+        .unwrap();
+    let claim_item = |holder: &Keypair, holder_token: &Pubkey| {
+        let instruction = instruction::claim_batch_item(
+            &program_id,
+            &batch_address,
+            &agent_address,
+            &holder.pubkey(),
+            &treasury,
+            &requester.pubkey(),
+            0,
+            [0; 32],
+            merkle::proof(&leaves, 0),
+        );
+        instruction::with_nft_holder(instruction, holder_token)
+    };
+    let set_limits = |holder: &Keypair, holder_token: &Pubkey, max_concurrent_tasks| {
+        let instruction = instruction::set_agent_limits(
+            &program_id,
+            &agent_address,
+            &holder.pubkey(),
+            max_concurrent_tasks,
+        );
+        instruction::with_nft_holder(instruction, holder_token)
+    };
+    let claim = |holder: &Keypair, holder_token: &Pubkey| {
+        let instruction = claim_payment_instruction(
+            program_id,
+            task_address,
+            agent_address,
+            holder.pubkey(),
+            requester.pubkey(),
+            treasury,
+        );
+        instruction::with_nft_holder(instruction, holder_token)
+    };
+    let update_status = |holder: &Keypair, holder_token: &Pubkey, is_active| {
+        let instruction = instruction::update_agent_status(
+            &program_id,
+            &agent_address,
+            &holder.pubkey(),
+            is_active,
+        );
+        instruction::with_nft_holder(instruction, holder_token)
+    };
 
-    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
+    // The owner's key alone no longer controls the agent, but holding the
+    // NFT, the owner still sets its limits
+    let owner_limits =
+        instruction::set_agent_limits(&program_id, &agent_address, &owner.pubkey(), 3);
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[owner_limits], &[&owner])).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    banks_client
+        .process_transaction(send(&[set_limits(&owner, &owner_token, 3)], &[&owner]))
+        .await
+        .unwrap();
 
-    // Verify task creation
-    let task_account = banks_client
-        .get_account(task_keypair.pubkey())
+    // Holding the NFT, the owner withdraws; the buyer, holding none, can't
+    let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    banks_client
+        .process_transaction(send(&[withdraw(&owner, &owner_token, 100)], &[&owner]))
         .await
-        .unwrap()
         .unwrap();
+    assert_eq!(banks_client.get_balance(owner.pubkey()).await.unwrap(), owner_before + 100);
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[withdraw(&buyer, &buyer_token, 100)], &[&buyer]))
+            .await,
+        InstructionError::Custom(AIInfraError::NotNftHolder as u32),
+    );
 
-    let task = ComputeTask::try_from_slice(&task_account.data).unwrap();
-    assert_eq!(task.agent, agent_keypair.pubkey());
-    assert_eq!(task.payment_amount, payment_amount);
-    assert_eq!(task.status, TaskStatus::Pending);
+    // Selling the NFT hands the agent to the buyer
+    let sale = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &owner_token,
+        &buyer_token,
+        &owner.pubkey(),
+        &[],
+        1,
+    )
+    .unwrap();
+    banks_client.process_transaction(send(&[sale], &[&owner])).await.unwrap();
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[withdraw(&owner, &owner_token, 200)], &[&owner]))
+            .await,
+        InstructionError::Custom(AIInfraError::NotNftHolder as u32),
+    );
+    let buyer_before = banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    banks_client
+        .process_transaction(send(&[withdraw(&buyer, &buyer_token, 200)], &[&buyer]))
+        .await
+        .unwrap();
+    assert_eq!(banks_client.get_balance(buyer.pubkey()).await.unwrap(), buyer_before + 200);
 
-    // Verify agent credits deduction
-    let agent_account = banks_client
-        .get_account(agent_keypair.pubkey())
+    // The seller can neither collect the pending payments nor change the
+    // agent's status or limits
+    assert_instruction_error(
+        banks_client.process_transaction(send(&[claim(&owner, &owner_token)], &[&owner])).await,
+        InstructionError::MissingRequiredSignature,
+    );
+    assert_instruction_error(
+        banks_client
+            .process_transaction(send(&[update_status(&owner, &owner_token, false)], &[&owner]))
+            .await,
+        InstructionError::Custom(AIInfraError::OwnerMismatch as u32),
+    );
+    assert_instruction_error(
+        banks_client
+            .process_transaction(send(&[claim_item(&owner, &owner_token)], &[&owner]))
+            .await,
+        InstructionError::Custom(AIInfraError::NotNftHolder as u32),
+    );
+    assert_instruction_error(
+        banks_client
+            .process_transaction(send(&[set_limits(&owner, &owner_token, 5)], &[&owner]))
+            .await,
+        InstructionError::Custom(AIInfraError::NotNftHolder as u32),
+    );
+    banks_client
+        .process_transaction(send(&[set_limits(&buyer, &buyer_token, 5)], &[&buyer]))
+        .await
+        .unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(AIAgent::unpack(&account.data).unwrap().max_concurrent_tasks, 5);
+    let buyer_before = banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    banks_client
+        .process_transaction(send(&[claim_item(&buyer, &buyer_token)], &[&buyer]))
+        .await
+        .unwrap();
+    assert_eq!(banks_client.get_balance(buyer.pubkey()).await.unwrap(), buyer_before + 500);
+    banks_client
+        .process_transaction(send(&[claim(&buyer, &buyer_token)], &[&buyer]))
         .await
-        .unwrap()
         .unwrap();
+    assert_eq!(
+        banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        buyer_before + 500 + 1_000
+    );
+    banks_client
+        .process_transaction(send(&[update_status(&buyer, &buyer_token, false)], &[&buyer]))
+        .await
+        .unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert!(!AIAgent::unpack(&account.data).unwrap().is_active);
+
+    // Unbinding leaves the buyer owning the agent by key
+    let unbind = instruction::unbind_agent_from_nft(
+        &program_id,
+        &agent_address,
+        &buyer.pubkey(),
+        &buyer_token,
+    );
+    banks_client.process_transaction(send(&[unbind], &[&buyer])).await.unwrap();
+    let account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    let agent = AIAgent::unpack(&account.data).unwrap();
+    assert_eq!((agent.owner, agent.owner_nft_mint), (buyer.pubkey(), None));
+    let buyer_limits =
+        instruction::set_agent_limits(&program_id, &agent_address, &buyer.pubkey(), 3);
+    banks_client.process_transaction(send(&[buyer_limits], &[&buyer])).await.unwrap();
+}
 
-    let updated_agent = AIAgent::try_from_slice(&agent_account.data).unwrap();
+#[tokio::test]
+async fn test_usd_priced_tasks() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_registry(&mut program_test, program_id);
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    // $150.00 per SOL, give or take 0.01%, as Pyth publishes SOL/USD
+    let published_at = 1_700_000_000;
+    let sol_at_150 = OraclePrice {
+        price: 15_000_000_000,
+        conf: 1_500_000,
+        expo: -8,
+        publish_time: published_at,
+    };
+    let oracle_account = |price: OraclePrice| Account {
+        lamports: LAMPORTS_PER_SOL,
+        data: price.pack(),
+        owner: Pubkey::new_unique(),
+        ..Account::default()
+    };
+    let oracle = Pubkey::new_unique();
+    program_test.add_account(oracle, oracle_account(sol_at_150));
+    add_config_account(
+        &mut program_test,
+        program_id,
+        Config {
+            treasury: Pubkey::new_unique(),
+            oracle,
+            max_oracle_age_seconds: 60,
+            max_oracle_confidence_bps: 100,
+            ..Config::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let blockhash = context.last_blockhash;
+    let sign = |instructions: &[Instruction], signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), blockhash);
+        transaction
+    };
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create = |index, usd_cents, oracle: &Pubkey| {
+        sign(
+            &[instruction::create_task_in_usd(
+                &program_id,
+                &task(index),
+                &agent_address,
+                index,
+                &requester.pubkey(),
+                ComputeRequirements::default(),
+                usd_cents,
+                oracle,
+            )],
+            &[&requester],
+        )
+    };
+    let register = register_agent_instruction(program_id, owner.pubkey());
+    context.banks_client.process_transaction(sign(&[register], &[&owner])).await.unwrap();
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = published_at + 30;
+    context.set_sysvar(&clock);
+
+    // Only the config's oracle prices tasks
+    assert_instruction_error(
+        context.banks_client.process_transaction(create(0, 150, &Pubkey::new_unique())).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // $1.50 escrows a hundredth of a SOL
+    context.banks_client.process_transaction(create(0, 150, &oracle)).await.unwrap();
+    let account = context.banks_client.get_account(task(0)).await.unwrap().unwrap();
+    let created = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!(
+        (created.payment_amount, created.payment_usd_cents),
+        (LAMPORTS_PER_SOL / 100, 150)
+    );
+    let (vault, _) = find_vault_address(&task(0), &program_id);
     assert_eq!(
-        updated_agent.compute_credits,
-        initial_credits - payment_amount
+        context.banks_client.get_balance(vault).await.unwrap(),
+        LAMPORTS_PER_SOL / 100 + Rent::default().minimum_balance(0)
+    );
+
+    // A price older than the config allows is refused
+    clock.unix_timestamp = published_at + 61;
+    context.set_sysvar(&clock);
+    assert_instruction_error(
+        context.banks_client.process_transaction(create(1, 150, &oracle)).await,
+        InstructionError::Custom(AIInfraError::StaleOracle as u32),
+    );
+
+    // and so is a fresh price of nothing
+    clock.unix_timestamp = published_at + 30;
+    context.set_sysvar(&clock);
+    context.set_account(&oracle, &oracle_account(OraclePrice { price: 0, ..sol_at_150 }).into());
+    assert_instruction_error(
+        context.banks_client.process_transaction(create(1, 200, &oracle)).await,
+        InstructionError::Custom(AIInfraError::InvalidOraclePrice as u32),
     );
 }
 
 #[tokio::test]
-async fn test_credit_management() {
+async fn test_described_tasks() {
     let program_id = Pubkey::new_unique();
     let mut program_test = ProgramTest::new(
         "sonic_ai_infra",
         program_id,
         processor!(process_instruction),
     );
-    
-    let agent_keypair = Keypair::new();
-    let owner_keypair = Keypair::new();
-    
+    add_registry(&mut program_test, program_id);
+    program_test.add_program(
+        "spl_memo",
+        spl_memo::id(),
+        processor!(spl_memo::processor::process_instruction),
+    );
+    add_config(&mut program_test, program_id, 0, Pubkey::new_unique());
+
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+
     let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let sign = |instructions: &[Instruction], signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), recent_blockhash);
+        transaction
+    };
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let task = |index| find_task_address(&agent_address, index, &program_id).0;
+    let create = |index, payment_amount, description| {
+        instruction::create_tagged_task(
+            &program_id,
+            &task(index),
+            &agent_address,
+            index,
+            &requester.pubkey(),
+            ComputeRequirements::default(),
+            payment_amount,
+            0,
+            [0; TASK_TAGS],
+            description,
+        )
+    };
+    let register = register_agent_instruction(program_id, owner.pubkey());
+    banks_client.process_transaction(sign(&[register], &[&owner])).await.unwrap();
 
-    // Register agent first
-    create_test_agent(
-        &mut banks_client,
-        &payer,
-        recent_blockhash,
-        program_id,
-        &agent_keypair,
-        &owner_keypair,
-    )
-    .await
-    .unwrap();
+    // Only the memo program takes the description, and only so much of it
+    let description = "Summarize the Q3 filings";
+    let mut misdirected = create(0, 1_000, Some(description));
+    misdirected.accounts.last_mut().unwrap().pubkey = Pubkey::new_unique();
+    assert_instruction_error(
+        banks_client.process_transaction(sign(&[misdirected], &[&requester])).await,
+        InstructionError::IncorrectProgramId,
+    );
+    let rambling = "a".repeat(MAX_TASK_DESCRIPTION_LEN + 1);
+    let rambling = create(0, 1_000, Some(&rambling));
+    assert_instruction_error(
+        banks_client.process_transaction(sign(&[rambling], &[&requester])).await,
+        InstructionError::Custom(AIInfraError::DescriptionTooLong as u32),
+    );
+
+    // The description lands in the transaction's logs, and its hash on the task
+    let described = sign(
+        &[create(0, 1_000, Some(description))],
+        &[&requester],
+    );
+    let simulation = banks_client.simulate_transaction(described.clone()).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let logs = simulation.simulation_details.unwrap().logs;
+    let memo = format!("Program log: Memo (len {}): {:?}", description.len(), description);
+    assert!(logs.contains(&memo), "{logs:?}");
+    banks_client.process_transaction(described).await.unwrap();
+    let account = banks_client.get_account(task(0)).await.unwrap().unwrap();
+    let created = ComputeTask::unpack(&account.data).unwrap();
+    assert_eq!(created.description_hash, hash(description.as_bytes()).to_bytes());
+    assert_ne!(created.description_hash, hash(b"Summarize the Q2 filings").to_bytes());
+
+    // A task without one writes no memo
+    let undescribed = sign(&[create(1, 1_000, None)], &[&requester]);
+    let simulation = banks_client.simulate_transaction(undescribed.clone()).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let logs = simulation.simulation_details.unwrap().logs;
+    assert!(!logs.iter().any(|log| log.contains("Memo")), "{logs:?}");
+    banks_client.process_transaction(undescribed).await.unwrap();
+    let account = banks_client.get_account(task(1)).await.unwrap().unwrap();
+    assert_eq!(ComputeTask::unpack(&account.data).unwrap().description_hash, [0; 32]);
+}
 
-    // Test deposit credits
-    let deposit_amount = 1000;
-    let instruction_data = AIInfraInstruction::DepositCredits {
-        amount: deposit_amount,
+// A wrapped SOL token account owned by `owner`, holding `amount` lamports
+// over its rent
+fn native_token_account_with(owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    let rent = Rent::default().minimum_balance(data.len());
+    spl_token::state::Account {
+        mint: spl_token::native_mint::id(),
+        owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: COption::Some(rent),
+        ..spl_token::state::Account::default()
     }
-    .try_to_vec()
-    .unwrap();
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: rent + amount,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
 
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(
-            program_id,
-            &instruction_data,
-            vec![
-                AccountMeta::new(agent_keypair.pubkey(), false),
-                AccountMeta::new(owner_keypair.pubkey(), true),
-            ],
-        )],
-        Some(&payer.pubkey()),
+#[tokio::test]
+async fn test_wsol_payments() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
     );
+    add_registry(&mut program_test, program_id);
+    program_test.add_program(
+        "spl_token",
+        spl_token::id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+    program_test.add_program(
+        "spl_associated_token_account",
+        spl_associated_token_account::id(),
+        processor!(spl_associated_token_account::processor::process_instruction),
+    );
+    let mint = spl_token::native_mint::id();
+    let mut native_mint = mint_account();
+    let mut data = spl_token::state::Mint::unpack(&native_mint.data).unwrap();
+    data.decimals = 9;
+    data.supply = 0;
+    data.pack_into_slice(&mut native_mint.data);
+    program_test.add_account(mint, native_mint);
 
-    transaction.sign(&[&payer, &owner_keypair], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
+    // A 2.5% fee, paid in wrapped SOL to the treasury's token account
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, 250, treasury);
+    let treasury_token = get_associated_token_address(&treasury, &mint);
+    program_test.add_account(treasury_token, native_token_account_with(treasury, 0));
 
-    // Verify credit deposit
-    let agent_account = banks_client
-        .get_account(agent_keypair.pubkey())
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(wallet.pubkey(), wallet_account());
+    }
+    let requester_token = get_associated_token_address(&requester.pubkey(), &mint);
+    let owner_token = get_associated_token_address(&owner.pubkey(), &mint);
+    program_test.add_account(
+        requester_token,
+        native_token_account_with(requester.pubkey(), LAMPORTS_PER_SOL),
+    );
+    program_test.add_account(owner_token, native_token_account_with(owner.pubkey(), 0));
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let sign = |instructions: &[Instruction], signers: &[&Keypair]| {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&[&payer][..], signers].concat(), recent_blockhash);
+        transaction
+    };
+    let (agent_address, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task_address, _) = find_task_address(&agent_address, 0, &program_id);
+    let register = register_agent_instruction(program_id, owner.pubkey());
+    let prefer_native = instruction::set_prefer_native(
+        &program_id,
+        &agent_address,
+        &owner.pubkey(),
+        true,
+    );
+    banks_client.process_transaction(sign(&[register, prefer_native], &[&owner])).await.unwrap();
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert!(AIAgent::unpack(&agent_account.data).unwrap().prefer_native);
+
+    let create = create_task_with_token_instruction(
+        program_id,
+        task_address,
+        agent_address,
+        0,
+        requester.pubkey(),
+        requester_token,
+        mint,
+        spl_token::id(),
+        LAMPORTS_PER_SOL,
+    );
+    let start = instruction::start_task(
+        &program_id,
+        &task_address,
+        &agent_address,
+        &owner.pubkey(),
+    );
+    banks_client
+        .process_transaction(sign(&[create, start], &[&requester, &owner]))
         .await
-        .unwrap()
         .unwrap();
+    let (vault_address, _) = find_vault_address(&task_address, &program_id);
+    let vault_token = get_associated_token_address(&vault_address, &mint);
+    assert_eq!(token_balance(&mut banks_client, vault_token).await, LAMPORTS_PER_SOL);
+
+    let complete = |destination| {
+        let mut complete = with_token_escrow(
+            instruction::complete_task(
+                &program_id,
+                &task_address,
+                &agent_address,
+                &owner.pubkey(),
+                &requester.pubkey(),
+                &treasury,
+                [1; 32],
+            ),
+            task_address,
+            mint,
+            spl_token::id(),
+            destination,
+        );
+        complete.accounts.push(AccountMeta::new(treasury_token, false));
+        sign(&[complete], &[&owner])
+    };
 
-    let agent = AIAgent::try_from_slice(&agent_account.data).unwrap();
-    assert_eq!(agent.compute_credits, deposit_amount);
-}
\ No newline at end of file
+    // An agent preferring native SOL is paid in its owner's wallet, not a
+    // token account
+    assert_instruction_error(
+        banks_client.process_transaction(complete(owner_token)).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let owner_balance = banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let requester_balance = banks_client.get_balance(requester.pubkey()).await.unwrap();
+    let vault_token_rent = banks_client.get_balance(vault_token).await.unwrap() - LAMPORTS_PER_SOL;
+    banks_client.process_transaction(complete(owner.pubkey())).await.unwrap();
+
+    // The owner gets the payment less the fee as lamports, the treasury its
+    // fee in wrapped SOL, and the requester the escrow account's rent
+    let fee = LAMPORTS_PER_SOL / 40;
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        owner_balance + LAMPORTS_PER_SOL - fee
+    );
+    assert_eq!(token_balance(&mut banks_client, treasury_token).await, fee);
+    assert_eq!(token_balance(&mut banks_client, owner_token).await, 0);
+    assert!(banks_client.get_account(vault_token).await.unwrap().is_none());
+    assert_eq!(
+        banks_client.get_balance(requester.pubkey()).await.unwrap(),
+        requester_balance + vault_token_rent
+    );
+    assert_eq!(banks_client.get_balance(vault_address).await.unwrap(), 0);
+}
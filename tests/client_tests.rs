@@ -0,0 +1,121 @@
+// Runs the client helpers against program-test. Built with the client
+// feature: cargo test --features client
+#![cfg(feature = "client")]
+
+use solana_banks_client::BanksClient;
+use solana_client::client_error::ClientError as RpcError;
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, system_program};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+    transport::TransportError,
+};
+use sonic_ai_infra::{
+    client::{self, AccountSource, ClientError},
+    find_agent_address, find_config_address, find_task_address, instruction, process_instruction,
+    AIInfraInstruction, ComputeRequirements, ConfigParams, TaskStatus,
+};
+
+// Reads accounts through program-test's bank
+struct Banks(BanksClient);
+
+impl AccountSource for Banks {
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>, ClientError> {
+        self.0
+            .clone()
+            .get_account(*address)
+            .await
+            .map_err(|error| RpcError::from(TransportError::from(error)).into())
+    }
+}
+
+#[tokio::test]
+async fn test_client_reads_program_accounts() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let tasks: Vec<Pubkey> = (0..2)
+        .map(|index| find_task_address(&agent, index, &program_id).0)
+        .collect();
+
+    let mut instructions = vec![
+        Instruction::new_with_borsh(
+            program_id,
+            &AIInfraInstruction::InitializeConfig {
+                admin: Pubkey::new_unique(),
+                params: ConfigParams::default(),
+            },
+            vec![
+                AccountMeta::new(find_config_address(&program_id).0, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        ),
+        instruction::initialize_registry(&program_id, &payer.pubkey()),
+        instruction::register_agent(&program_id, &agent, &owner.pubkey()),
+    ];
+    for (index, task) in tasks.iter().enumerate() {
+        instructions.push(instruction::create_task(
+            &program_id,
+            task,
+            &agent,
+            index as u64,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            500,
+        ));
+    }
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &owner, &requester], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let banks = Banks(banks_client);
+    let fetched = client::get_agent(&banks, &program_id, &agent).await.unwrap();
+    assert_eq!(fetched.owner, owner.pubkey());
+    assert_eq!(fetched.tasks_created, 2);
+
+    let task = client::get_task(&banks, &program_id, &tasks[1]).await.unwrap();
+    assert_eq!(task.agent, agent);
+    assert_eq!(task.status, TaskStatus::Pending);
+    assert_eq!(task.payment_amount, 500);
+
+    assert_eq!(client::list_agent_tasks(&banks, &program_id, &agent).await.unwrap(), tasks);
+
+    let registry = client::get_registry(&banks, &program_id).await.unwrap();
+    assert_eq!(registry.total_agents, 1);
+    assert_eq!(registry.total_tasks, 2);
+
+    // The requester's wallet exists but isn't a program account
+    assert!(matches!(
+        client::get_agent(&banks, &program_id, &requester.pubkey()).await,
+        Err(ClientError::WrongOwner { .. })
+    ));
+    // Neither is a task that was never created
+    let (missing, _) = find_task_address(&agent, 2, &program_id);
+    assert!(matches!(
+        client::get_task(&banks, &program_id, &missing).await,
+        Err(ClientError::AccountMissing(address)) if address == missing
+    ));
+    // And an agent isn't a task
+    assert!(matches!(
+        client::get_task(&banks, &program_id, &agent).await,
+        Err(ClientError::Decode { .. })
+    ));
+}
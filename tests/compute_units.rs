@@ -0,0 +1,170 @@
+// Compares compute units spent by the SBF build. Program-test only meters
+// the program when it loads its .so instead of the native processor, so this
+// needs the program built first. The tests are ignored otherwise, so runs
+// with --all-features pass without the .so:
+// cargo build-sbf && cargo test --features test-bpf --test compute_units -- --ignored
+#![cfg(feature = "test-bpf")]
+
+use solana_program::{clock::Epoch, pubkey::Pubkey, rent::Rent};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use sonic_ai_infra::{
+    find_agent_address, find_config_address, find_task_address, instruction, AIAgent,
+    AIInfraInstruction, AccountType, ComputeRequirements, ComputeTask, Config, ProgramAccount,
+    TaskStatus, CURRENT_VERSION,
+};
+
+fn program_account<T: ProgramAccount>(value: &T, owner: Pubkey) -> Account {
+    let mut data = value.try_to_vec().unwrap();
+    data.resize(T::LEN, 0);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: Epoch::default(),
+    }
+}
+
+// Simulates `instruction`, signed by `signer`, and returns the units it used
+async fn units_consumed(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    signer: &Keypair,
+    instruction: Instruction,
+) -> u64 {
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer, signer],
+        blockhash,
+    );
+    let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    simulation.simulation_details.unwrap().units_consumed
+}
+
+#[tokio::test]
+#[ignore = "needs the program built with cargo build-sbf"]
+async fn test_zero_copy_start_task_saves_compute() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("sonic_ai_infra", program_id, None);
+
+    let (config_address, config_bump) = find_config_address(&program_id);
+    let config = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        treasury: Pubkey::new_unique(),
+        bump: config_bump,
+        ..Config::default()
+    };
+    program_test.add_account(config_address, program_account(&config, program_id));
+
+    let owner = Keypair::new();
+    let (agent_address, agent_bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        registrar: owner.pubkey(),
+        reputation_score: 100,
+        is_active: true,
+        bump: agent_bump,
+        tasks_created: 2,
+        open_tasks: 2,
+        ..AIAgent::default()
+    };
+    program_test.add_account(agent_address, program_account(&agent, program_id));
+
+    // One pending task to start, and one running task to commit a result for
+    let mut tasks = Vec::new();
+    for (index, status) in [TaskStatus::Pending, TaskStatus::InProgress].into_iter().enumerate() {
+        let (address, bump) = find_task_address(&agent_address, index as u64, &program_id);
+        let task = ComputeTask {
+            account_type: AccountType::Task,
+            version: CURRENT_VERSION,
+            agent: agent_address,
+            requirements: ComputeRequirements { max_time_seconds: 60, ..Default::default() },
+            status,
+            payment_amount: 1_000,
+            index: index as u64,
+            bump,
+            requester: Pubkey::new_unique(),
+            ..ComputeTask::default()
+        };
+        program_test.add_account(address, program_account(&task, program_id));
+        tasks.push(address);
+    }
+
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // CommitResult makes the same loads and checks, and a single write,
+    // through Borsh. StartTask checks more besides, but works in place.
+    let start = instruction::start_task(&program_id, &tasks[0], &agent_address, &owner.pubkey());
+    let commit = Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::CommitResult { task_id: tasks[1], commitment: [1; 32] },
+        vec![
+            AccountMeta::new(tasks[1], false),
+            AccountMeta::new_readonly(agent_address, false),
+            AccountMeta::new_readonly(owner.pubkey(), true),
+            AccountMeta::new_readonly(config_address, false),
+        ],
+    );
+    let start_units = units_consumed(&mut banks_client, &payer, &owner, start).await;
+    let commit_units = units_consumed(&mut banks_client, &payer, &owner, commit).await;
+    println!("StartTask: {start_units} units, CommitResult: {commit_units} units");
+    assert!(start_units < commit_units);
+}
+
+#[tokio::test]
+#[ignore = "needs the program built with cargo build-sbf"]
+async fn test_in_place_agent_updates_save_compute() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("sonic_ai_infra", program_id, None);
+
+    let owner = Keypair::new();
+    let (agent_address, agent_bump) = find_agent_address(&owner.pubkey(), &program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner: owner.pubkey(),
+        registrar: owner.pubkey(),
+        reputation_score: 100,
+        is_active: true,
+        bump: agent_bump,
+        ..AIAgent::default()
+    };
+    program_test.add_account(agent_address, program_account(&agent, program_id));
+
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // SetAgentLimits makes the same checks and writes one field through
+    // Borsh, where the status update and heartbeat write theirs in place
+    let limits = instruction::set_agent_limits(&program_id, &agent_address, &owner.pubkey(), 4);
+    let status =
+        instruction::update_agent_status(&program_id, &agent_address, &owner.pubkey(), false);
+    let heartbeat = Instruction::new_with_borsh(
+        program_id,
+        &AIInfraInstruction::Heartbeat,
+        vec![
+            AccountMeta::new(agent_address, false),
+            AccountMeta::new_readonly(owner.pubkey(), true),
+        ],
+    );
+    let limits_units = units_consumed(&mut banks_client, &payer, &owner, limits).await;
+    let status_units = units_consumed(&mut banks_client, &payer, &owner, status).await;
+    let heartbeat_units = units_consumed(&mut banks_client, &payer, &owner, heartbeat).await;
+    println!(
+        "SetAgentLimits: {limits_units} units, UpdateAgentStatus: {status_units} units, \
+         Heartbeat: {heartbeat_units} units"
+    );
+    assert!(status_units < limits_units);
+    assert!(heartbeat_units < limits_units);
+}
@@ -0,0 +1,94 @@
+// Drives the program from another program through the cpi helpers, the way
+// a dependent program built with the no-entrypoint feature would.
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    signature::Signer,
+    transaction::Transaction,
+};
+use sonic_ai_infra::{
+    cpi, find_agent_address, find_registry_address, instruction, process_instruction, AIAgent,
+    AccountType,
+};
+
+const OWNER_SEED: &[u8] = b"owner";
+
+// A caller program that registers an agent owned by its own PDA
+fn process_caller_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let ai_infra_program = next_account_info(accounts_iter)?;
+    let agent = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let registry = next_account_info(accounts_iter)?;
+
+    let (_, bump) = Pubkey::find_program_address(&[OWNER_SEED], program_id);
+    cpi::register_agent(
+        ai_infra_program,
+        agent,
+        owner,
+        system_program,
+        registry,
+        &[&[OWNER_SEED, &[bump]]],
+    )
+}
+
+#[tokio::test]
+async fn test_register_agent_through_cpi() {
+    let program_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+    program_test.add_program("caller", caller_id, processor!(process_caller_instruction));
+
+    // The caller's PDA owns the agent and pays its rent
+    let (owner, _) = Pubkey::find_program_address(&[OWNER_SEED], &caller_id);
+    program_test.add_account(owner, Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()));
+    let (agent_address, _) = find_agent_address(&owner, &program_id);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &payer.pubkey()),
+            Instruction::new_with_bytes(
+                caller_id,
+                &[],
+                vec![
+                    AccountMeta::new_readonly(program_id, false),
+                    AccountMeta::new(agent_address, false),
+                    AccountMeta::new(owner, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new(find_registry_address(&program_id).0, false),
+                ],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let agent_account = banks_client.get_account(agent_address).await.unwrap().unwrap();
+    assert_eq!(agent_account.owner, program_id);
+    let agent = AIAgent::deserialize(&mut &agent_account.data[..]).unwrap();
+    assert_eq!(agent.account_type, AccountType::Agent);
+    assert_eq!(agent.owner, owner);
+    assert_eq!(agent.registrar, owner);
+    assert!(agent.is_active);
+}
@@ -0,0 +1,239 @@
+// Decodes the events the program logs. Kept in its own test binary because
+// it swaps the process-wide syscall stubs, which would race with other tests
+// running program-test in parallel.
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Epoch},
+    entrypoint::ProgramResult,
+    native_token::LAMPORTS_PER_SOL,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use solana_banks_client::BanksClient;
+use sonic_ai_infra::{
+    events::{parse_event, Event, EventRecord},
+    find_agent_address, find_config_address, find_registry_address, find_task_address,
+    instruction,
+    process_instruction, AccountType, ComputeRequirements, Config, ProgramAccount,
+    CURRENT_VERSION,
+};
+
+// Native processors under program-test 1.14 print sol_log_data to stdout
+// instead of the transaction log. These stubs wrap program-test's own and
+// write each payload to the log in the runtime's format, behind the usual
+// "Program log: " prefix.
+struct LogDataStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for LogDataStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(base64::encode).collect();
+        self.0.sol_log(&format!("Program data: {}", fields.join(" ")))
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+struct NoStubs;
+
+impl SyscallStubs for NoStubs {}
+
+// Wraps the stubs the first ProgramTest::start installed in LogDataStubs
+fn capture_log_data() {
+    let stubs = program_stubs::set_syscall_stubs(Box::new(NoStubs));
+    program_stubs::set_syscall_stubs(Box::new(LogDataStubs(stubs)));
+}
+
+// Simulates `transaction` to capture the events it logs, then processes it
+async fn process_with_events(
+    banks_client: &mut BanksClient,
+    transaction: Transaction,
+) -> Vec<EventRecord> {
+    let simulation = banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    simulation.simulation_details.unwrap().logs.iter()
+        .filter_map(|line| parse_event(line.strip_prefix("Program log: ").unwrap_or(line)))
+        .collect()
+}
+
+fn add_config(program_test: &mut ProgramTest, program_id: Pubkey, treasury: Pubkey) {
+    let (address, bump) = find_config_address(&program_id);
+    let config = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        admin: Pubkey::new_unique(),
+        treasury,
+        bump,
+        ..Config::default()
+    };
+    let mut data = config.try_to_vec().unwrap();
+    data.resize(Config::LEN, 0);
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(Config::LEN),
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: Epoch::default(),
+        },
+    );
+}
+
+#[tokio::test]
+async fn test_event_logs() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "sonic_ai_infra",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let treasury = Pubkey::new_unique();
+    add_config(&mut program_test, program_id, treasury);
+    let owner = Keypair::new();
+    let requester = Keypair::new();
+    for wallet in [&owner, &requester] {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    capture_log_data();
+    let now = banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let stamped = |event| EventRecord { timestamp: now, event };
+
+    let (agent, _) = find_agent_address(&owner.pubkey(), &program_id);
+    let (task, _) = find_task_address(&agent, 0, &program_id);
+
+    // One event per state transition, in instruction order
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &payer.pubkey()),
+            instruction::register_agent(&program_id, &agent, &owner.pubkey()),
+            instruction::deposit_credits(&program_id, &agent, &owner.pubkey(), 5000, None),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_eq!(
+        process_with_events(&mut banks_client, transaction).await,
+        vec![
+            stamped(Event::RegistryInitialized {
+                registry: find_registry_address(&program_id).0,
+            }),
+            stamped(Event::AgentRegistered { agent, owner: owner.pubkey() }),
+            stamped(Event::CreditsDeposited { agent, amount: 5000, nonce: 0 }),
+        ]
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_task(
+            &program_id,
+            &task,
+            &agent,
+            0,
+            &requester.pubkey(),
+            ComputeRequirements { single_step: true, ..ComputeRequirements::default() },
+            1000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &requester], recent_blockhash);
+    assert_eq!(
+        process_with_events(&mut banks_client, transaction).await,
+        vec![stamped(Event::TaskCreated {
+            task,
+            agent,
+            requester: requester.pubkey(),
+            payment_amount: 1000,
+            payment_mint: None,
+        })]
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::start_task(&program_id, &task, &agent, &owner.pubkey())],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_eq!(
+        process_with_events(&mut banks_client, transaction).await,
+        vec![stamped(Event::TaskStarted { task, agent })]
+    );
+
+    // Completing the task logs the completion and then the payout
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete_task(
+            &program_id,
+            &task,
+            &agent,
+            &owner.pubkey(),
+            &requester.pubkey(),
+            &treasury,
+            [7; 32],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &owner], recent_blockhash);
+    assert_eq!(
+        process_with_events(&mut banks_client, transaction).await,
+        vec![
+            stamped(Event::TaskCompleted {
+                task,
+                agent,
+                result_hash: [7; 32],
+                payment_pending: false,
+            }),
+            stamped(Event::TaskPaid { task, agent, amount: 1000, fee: 0, forfeit: 0 }),
+        ]
+    );
+}
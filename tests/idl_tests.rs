@@ -0,0 +1,187 @@
+// Checks the generated IDL against the instruction builders, so the shank
+// annotations keep describing the accounts the program reads. Built with the
+// idl feature: cargo test --features idl
+#![cfg(feature = "idl")]
+
+use std::path::Path;
+
+use serde_json::Value;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use sonic_ai_infra::{
+    idl::generate_json,
+    instruction::{self, COMPACT_FLAG},
+    AgentCapabilities, ComputeRequirements, WithdrawalPolicy, TASK_TAGS,
+};
+
+fn generated_idl() -> Value {
+    let json = generate_json(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+// Every builder, with any variable list of accounts left empty
+fn built_instructions() -> Vec<Instruction> {
+    let program_id = Pubkey::new_unique();
+    let [task, agent, owner, requester, treasury, template, recurring] =
+        [(); 7].map(|_| Pubkey::new_unique());
+    let requirements = ComputeRequirements::default;
+    vec![
+        instruction::register_agent(&program_id, &agent, &owner),
+        instruction::update_agent_status(&program_id, &agent, &owner, false),
+        instruction::set_agent_limits(&program_id, &agent, &owner, 4),
+        instruction::set_capabilities(&program_id, &agent, &owner, AgentCapabilities::default()),
+        instruction::set_accepted_tags(&program_id, &agent, &owner, &[3]),
+        instruction::set_withdrawal_policy(
+            &program_id,
+            &agent,
+            &owner,
+            WithdrawalPolicy::default(),
+        ),
+        instruction::set_attestation_key(&program_id, &agent, &owner, None),
+        instruction::migrate_account(&program_id, &task, &owner),
+        instruction::initialize_registry(&program_id, &owner),
+        instruction::claim_grant(&program_id, &agent, &owner),
+        instruction::fund_faucet(&program_id, &owner, 1),
+        instruction::set_grant_amount(&program_id, &owner, 1),
+        instruction::set_multisig_owner(&program_id, &agent, &owner, &[requester], 1),
+        instruction::bind_agent_to_nft(&program_id, &agent, &owner, &task),
+        instruction::unbind_agent_from_nft(&program_id, &agent, &owner, &requester),
+        instruction::set_prefer_native(&program_id, &agent, &owner, true),
+        instruction::initialize_insurance_pool(&program_id, &owner),
+        instruction::sweep_insurance_pool(&program_id, &owner, &requester, 1),
+        instruction::create_task(&program_id, &task, &agent, 0, &requester, requirements(), 1),
+        instruction::create_tagged_task(
+            &program_id,
+            &task,
+            &agent,
+            0,
+            &requester,
+            requirements(),
+            1,
+            0,
+            [0; TASK_TAGS],
+            Some("task"),
+        ),
+        instruction::create_task_in_usd(
+            &program_id,
+            &task,
+            &agent,
+            0,
+            &requester,
+            requirements(),
+            1,
+            &treasury,
+        ),
+        instruction::set_expected_result(&program_id, &task, &requester, [1; 32]),
+        instruction::start_task(&program_id, &task, &agent, &owner),
+        instruction::file_insurance_claim(&program_id, &task, &requester),
+        instruction::complete_task(
+            &program_id,
+            &task,
+            &agent,
+            &owner,
+            &requester,
+            &treasury,
+            [1; 32],
+        ),
+        instruction::complete_task_with_data(
+            &program_id,
+            &task,
+            &agent,
+            &owner,
+            &requester,
+            &treasury,
+            vec![1],
+        ),
+        instruction::tip_agent(&program_id, &task, &agent, &requester, &owner, 1),
+        instruction::create_batch_task(&program_id, &task, &agent, &requester, 2, 1),
+        instruction::submit_batch_result(&program_id, &task, &agent, &owner, [1; 32]),
+        instruction::claim_batch_item(
+            &program_id,
+            &task,
+            &agent,
+            &owner,
+            &treasury,
+            &requester,
+            0,
+            [1; 32],
+            vec![],
+        ),
+        instruction::refund_batch_task(&program_id, &task, &requester),
+        instruction::deposit_credits(&program_id, &agent, &owner, 1, None),
+        instruction::withdraw_credits(&program_id, &agent, &owner, 1, None),
+        instruction::release_payments(&program_id, &agent),
+        instruction::set_referrer(&program_id, &owner, &requester),
+        instruction::create_template(&program_id, &template, &requester, requirements(), 1),
+        instruction::update_template(&program_id, &template, &requester, requirements(), 1),
+        instruction::close_template(&program_id, &template, &requester),
+        instruction::create_task_from_template(
+            &program_id,
+            &template,
+            &task,
+            &agent,
+            0,
+            &requester,
+            None,
+        ),
+        instruction::create_recurring_task(
+            &program_id,
+            &recurring,
+            &requester,
+            &agent,
+            requirements(),
+            1,
+            60,
+            1,
+        ),
+        instruction::crank_recurring_task(&program_id, &recurring, &task, &agent, 0, &owner),
+        instruction::cancel_recurring_task(&program_id, &recurring, &requester),
+        instruction::split_task(&program_id, &task, &agent, &requester, 0, vec![]),
+        instruction::aggregate_results(&program_id, &task, &[]),
+        instruction::reissue_shard(&program_id, &task, &template, &requester, 0),
+        instruction::create_redundant_task(&program_id, &task, &requester, requirements(), 1, 3, 2),
+        instruction::submit_redundant_result(&program_id, &task, &agent, &owner, [1; 32]),
+        instruction::finalize_redundant_task(&program_id, &task, &requester, &treasury, &[]),
+        instruction::set_agent_metadata(&program_id, &agent, &owner, "agent", "", ""),
+        instruction::update_task_priority(&program_id, &task, &requester, 1),
+    ]
+}
+
+#[test]
+fn idl_accounts_match_the_builders() {
+    let idl = generated_idl();
+    let described = idl["instructions"].as_array().unwrap();
+    for built in built_instructions() {
+        let index = u64::from(built.data[0] & !COMPACT_FLAG);
+        let described = described
+            .iter()
+            .find(|described| described["discriminant"]["value"] == index)
+            .unwrap();
+        let name = described["name"].as_str().unwrap();
+        let accounts = described["accounts"].as_array().unwrap();
+        let required = accounts.iter().filter(|account| account["isOptional"] != true).count();
+        assert!(
+            (required..=accounts.len()).contains(&built.accounts.len()),
+            "{name} builds {} accounts, the IDL lists {required} to {}",
+            built.accounts.len(),
+            accounts.len(),
+        );
+        for (position, (meta, account)) in built.accounts.iter().zip(accounts).enumerate() {
+            assert_eq!(account["isMut"], meta.is_writable, "{name} account {position}");
+            assert_eq!(account["isSigner"], meta.is_signer, "{name} account {position}");
+        }
+    }
+}
+
+#[test]
+fn idl_describes_every_instruction_and_account() {
+    let idl = generated_idl();
+    let instructions = idl["instructions"].as_array().unwrap();
+    for (index, described) in instructions.iter().enumerate() {
+        assert_eq!(described["discriminant"]["value"], index as u64);
+        assert!(!described["accounts"].as_array().unwrap().is_empty(), "{}", described["name"]);
+    }
+    let accounts: Vec<_> =
+        idl["accounts"].as_array().unwrap().iter().map(|account| &account["name"]).collect();
+    assert!(accounts.contains(&&Value::from("ComputeTask")));
+    assert!(accounts.contains(&&Value::from("AIAgent")));
+}
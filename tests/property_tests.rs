@@ -0,0 +1,383 @@
+// Property tests for what the network can send the program: instruction data
+// and accounts it decodes, and the task lifecycle they drive
+use std::fmt::Debug;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use proptest::{collection::vec, prelude::*};
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use sonic_ai_infra::{
+    find_agent_address, find_config_address, find_credit_vault_address, find_registry_address,
+    find_task_address, find_vault_address,
+    instruction::COMPACT_FLAG,
+    process_instruction,
+    test_utils::{account_infos, set_sysvar_stubs, AccountFixture},
+    AIAgent, AIInfraInstruction, AccountType, AgentCapabilities, ComputeRequirements,
+    ComputeTask, Config, FailureReason, PendingPayment, Registry, TaskStatus, CURRENT_VERSION,
+    MAX_PENDING_PAYMENTS, TAG_SET_LEN, TASK_TAGS, TokenProgram, WithdrawalPolicy,
+};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+// Any of an enum's first `count` variants, decoded from its tag
+fn variant<T: BorshDeserialize + Debug>(count: u8) -> impl Strategy<Value = T> {
+    (0..count).prop_map(|tag| T::try_from_slice(&[tag]).unwrap())
+}
+
+fn account_type() -> impl Strategy<Value = AccountType> {
+    variant(16)
+}
+
+fn task_status() -> impl Strategy<Value = TaskStatus> {
+    variant(7)
+}
+
+fn failure_reason() -> impl Strategy<Value = FailureReason> {
+    prop_oneof![variant(7), any::<u16>().prop_map(FailureReason::Other)]
+}
+
+fn requirements() -> impl Strategy<Value = ComputeRequirements> {
+    (any::<[u32; 5]>(), any::<u64>(), any::<bool>(), any::<[u32; 2]>()).prop_map(
+        |([cpu_units, memory_mb, storage_mb, max_time_seconds, min_reputation], min_stake,
+          single_step, [gpu_units, network_mbps])| ComputeRequirements {
+            cpu_units,
+            memory_mb,
+            storage_mb,
+            max_time_seconds,
+            min_reputation,
+            min_stake,
+            single_step,
+            gpu_units,
+            network_mbps,
+        },
+    )
+}
+
+fn capabilities() -> impl Strategy<Value = AgentCapabilities> {
+    any::<[u32; 5]>().prop_map(|[cpu_units, memory_mb, storage_mb, gpu_units, network_mbps]| {
+        AgentCapabilities { cpu_units, memory_mb, storage_mb, gpu_units, network_mbps }
+    })
+}
+
+fn pending_releases() -> impl Strategy<Value = [PendingPayment; MAX_PENDING_PAYMENTS]> {
+    any::<[(u64, i64); MAX_PENDING_PAYMENTS]>().prop_map(|payments| {
+        payments.map(|(amount, release_at)| PendingPayment { amount, release_at })
+    })
+}
+
+fn withdrawal_policy() -> impl Strategy<Value = WithdrawalPolicy> {
+    any::<(u64, i64)>().prop_map(|(max_per_day, cooldown_seconds)| {
+        WithdrawalPolicy { max_per_day, cooldown_seconds }
+    })
+}
+
+fn agent() -> impl Strategy<Value = AIAgent> {
+    (
+        (account_type(), any::<u8>(), pubkey(), any::<u64>(), any::<[u32; 2]>(), any::<bool>()),
+        (any::<u8>(), any::<u64>(), any::<u32>(), pubkey(), any::<[i64; 2]>(), any::<[u64; 2]>()),
+        (any::<Option<[u8; 32]>>(), any::<u16>(), any::<[u32; 2]>(), capabilities()),
+        (any::<[u32; 2]>(), any::<u64>(), any::<Option<[u8; 32]>>(), any::<[u64; 3]>()),
+        (pending_releases(), any::<[u8; 2]>(), any::<u64>(), any::<[u8; TAG_SET_LEN]>()),
+        (any::<u64>(), any::<u32>(), withdrawal_policy(), withdrawal_policy()),
+        (
+            any::<u64>(),
+            any::<[i64; 3]>(),
+            any::<u64>(),
+            any::<[bool; 3]>(),
+            any::<Option<[u8; 32]>>(),
+        ),
+    )
+        .prop_map(|(first, second, third, fourth, fifth, sixth, seventh)| {
+            let (account_type, version, owner, compute_credits, [reputation_score, tasks_completed],
+                is_active) = first;
+            let (bump, tasks_created, open_tasks, registrar, [last_heartbeat, unstake_requested_at],
+                [staked_lamports, unstake_amount]) = second;
+            let (operator, allowed_requesters, [active_tasks, max_concurrent_tasks], capabilities) =
+                third;
+            let ([tasks_failed, tasks_cancelled], total_completion_seconds, attestation_key,
+                [batches_created, tips_received, pending_payments]) = fourth;
+            let (pending_releases, [pending_head, pending_count], operation_nonce, accepted_tags) =
+                fifth;
+            let (last_task_slot, tasks_in_window, withdrawal_policy, pending_withdrawal_policy) =
+                sixth;
+            let (
+                withdrawn_today,
+                [pending_policy_at, withdrawal_day, last_withdrawal_at],
+                registered_at_epoch,
+                [grant_claimed, multisig_owner, prefer_native],
+                owner_nft_mint,
+            ) = seventh;
+            AIAgent {
+                account_type,
+                version,
+                owner,
+                compute_credits,
+                reputation_score,
+                tasks_completed,
+                is_active,
+                bump,
+                tasks_created,
+                open_tasks,
+                registrar,
+                last_heartbeat,
+                staked_lamports,
+                unstake_amount,
+                unstake_requested_at,
+                operator: operator.map(Pubkey::new_from_array),
+                allowed_requesters,
+                active_tasks,
+                max_concurrent_tasks,
+                capabilities,
+                tasks_failed,
+                tasks_cancelled,
+                total_completion_seconds,
+                attestation_key: attestation_key.map(Pubkey::new_from_array),
+                batches_created,
+                tips_received,
+                pending_payments,
+                pending_releases,
+                pending_head,
+                pending_count,
+                operation_nonce,
+                accepted_tags,
+                last_task_slot,
+                tasks_in_window,
+                withdrawal_policy,
+                pending_withdrawal_policy,
+                pending_policy_at,
+                withdrawn_today,
+                withdrawal_day,
+                last_withdrawal_at,
+                registered_at_epoch,
+                grant_claimed,
+                multisig_owner,
+                owner_nft_mint: owner_nft_mint.map(Pubkey::new_from_array),
+                prefer_native,
+            }
+        })
+}
+
+fn task() -> impl Strategy<Value = ComputeTask> {
+    (
+        (account_type(), any::<u8>(), pubkey(), requirements(), task_status(), any::<[u8; 32]>()),
+        (any::<[u64; 2]>(), any::<[u8; 3]>(), pubkey(), any::<Option<[u8; 32]>>()),
+        (any::<[bool; 4]>(), any::<[i64; 4]>(), any::<Option<[u8; 32]>>()),
+        (any::<Option<[u8; 32]>>(), any::<u32>(), any::<Option<[u8; 32]>>(), any::<[u8; 2]>()),
+        proptest::option::of(failure_reason()),
+    )
+        .prop_map(|(first, second, third, fourth, failure_reason)| {
+            let (account_type, version, agent, requirements, status, result_hash) = first;
+            let ([payment_amount, index], [bump, failure_code, vault_bump], requester,
+                payment_mint) = second;
+            let ([open_listing, payment_pending, verified, reissued],
+                [started_at, deadline, completed_at, created_at], commitment) = third;
+            let (expected_result_hash, tip_reputation, parent, [shard_index, shard_count]) =
+                fourth;
+            ComputeTask {
+                account_type,
+                version,
+                agent,
+                requirements,
+                status,
+                result_hash,
+                payment_amount,
+                index,
+                bump,
+                failure_code,
+                requester,
+                vault_bump,
+                payment_mint: payment_mint.map(Pubkey::new_from_array),
+                open_listing,
+                started_at,
+                deadline,
+                completed_at,
+                payment_pending,
+                commitment,
+                verified,
+                created_at,
+                expected_result_hash,
+                tip_reputation,
+                parent: parent.map(Pubkey::new_from_array),
+                shard_index,
+                shard_count,
+                reissued,
+                failure_reason,
+                // Kept in the trailer, outside the Borsh layout
+                priority: 0,
+                tags: [0; TASK_TAGS],
+                failed_at: 0,
+                insurance_claimed: false,
+                token_program: TokenProgram::Spl,
+                payment_usd_cents: 0,
+                description_hash: [0; 32],
+                held_refund: 0,
+                result_data: Vec::new(),
+            }
+        })
+}
+
+// Bytes weighted towards zero and one, so that bools, options, enum tags and
+// vec lengths drawn from them are often valid
+fn payload() -> impl Strategy<Value = Vec<u8>> {
+    vec(prop_oneof![3 => 0u8..2, 1 => any::<u8>()], 0..400)
+}
+
+// Instructions with arbitrary fields, decoded from Borsh data under any tag
+// below the compact ones
+fn instruction() -> impl Strategy<Value = AIInfraInstruction> {
+    (0..COMPACT_FLAG, payload()).prop_filter_map("payload doesn't decode", |(tag, payload)| {
+        let data = [&[tag][..], &payload].concat();
+        AIInfraInstruction::deserialize(&mut data.as_slice()).ok()
+    })
+}
+
+// Instruction data as a client might send it, well formed in either encoding,
+// or as arbitrary bytes
+fn instruction_data() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        instruction().prop_map(|instruction| instruction.try_to_vec().unwrap()),
+        instruction().prop_map(|instruction| instruction.pack()),
+        (any::<u8>(), payload()).prop_map(|(tag, payload)| [&[tag][..], &payload].concat()),
+    ]
+}
+
+// A registered agent with a pending task, and the accounts around them, for
+// instructions to pick from
+fn fixture_accounts(program_id: &Pubkey) -> Vec<AccountFixture> {
+    let owner = Pubkey::new_unique();
+    let requester = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let (agent_key, agent_bump) = find_agent_address(&owner, program_id);
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        version: CURRENT_VERSION,
+        owner,
+        registrar: owner,
+        compute_credits: 1_000_000,
+        reputation_score: 100,
+        is_active: true,
+        bump: agent_bump,
+        tasks_created: 1,
+        open_tasks: 1,
+        ..AIAgent::default()
+    };
+    let (task_key, task_bump) = find_task_address(&agent_key, 0, program_id);
+    let (vault_key, vault_bump) = find_vault_address(&task_key, program_id);
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        version: CURRENT_VERSION,
+        agent: agent_key,
+        requirements: ComputeRequirements { single_step: true, ..Default::default() },
+        payment_amount: 1_000,
+        bump: task_bump,
+        requester,
+        vault_bump,
+        ..ComputeTask::default()
+    };
+    let (config_key, config_bump) = find_config_address(program_id);
+    let config = Config {
+        account_type: AccountType::Config,
+        version: CURRENT_VERSION,
+        admin: owner,
+        treasury,
+        bump: config_bump,
+        ..Config::default()
+    };
+    let (registry_key, registry_bump) = find_registry_address(program_id);
+    let registry = Registry {
+        account_type: AccountType::Registry,
+        version: CURRENT_VERSION,
+        bump: registry_bump,
+        ..Registry::default()
+    };
+    let (credit_vault_key, _) = find_credit_vault_address(&agent_key, program_id);
+    vec![
+        AccountFixture::program(task_key, program_id, &task).writable(true),
+        AccountFixture::program(agent_key, program_id, &agent).writable(true),
+        AccountFixture::new(owner).signer(true).writable(true),
+        AccountFixture::new(vault_key).writable(true),
+        AccountFixture::new(requester).signer(true).writable(true),
+        AccountFixture::system_program(),
+        AccountFixture::program(config_key, program_id, &config).writable(true),
+        AccountFixture::new(treasury).writable(true),
+        AccountFixture::program(registry_key, program_id, &registry).writable(true),
+        AccountFixture::new(credit_vault_key).writable(true),
+        AccountFixture::new(Pubkey::new_unique()),
+    ]
+    .into_iter()
+    .map(|account| account.lamports(LAMPORTS_PER_SOL))
+    .collect()
+}
+
+const FIXTURE_ACCOUNTS: usize = 11;
+
+// Distinct fixture accounts to pass, either a prefix of the fixture's order,
+// which suits the task instructions, or any selection in any order
+fn account_picks() -> impl Strategy<Value = Vec<usize>> {
+    prop_oneof![
+        (0..=FIXTURE_ACCOUNTS).prop_map(|count| (0..count).collect()),
+        Just((0..FIXTURE_ACCOUNTS).collect::<Vec<_>>()).prop_shuffle().prop_flat_map(|order| {
+            (0..=FIXTURE_ACCOUNTS).prop_map(move |count| order[..count].to_vec())
+        }),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig { max_global_rejects: 1 << 16, ..ProptestConfig::default() })]
+
+    #[test]
+    fn agents_round_trip(agent in agent()) {
+        let data = agent.try_to_vec().unwrap();
+        prop_assert_eq!(AIAgent::try_from_slice(&data).unwrap(), agent);
+    }
+
+    #[test]
+    fn tasks_round_trip(task in task()) {
+        let data = task.try_to_vec().unwrap();
+        prop_assert_eq!(ComputeTask::try_from_slice(&data).unwrap(), task);
+    }
+
+    #[test]
+    fn instructions_round_trip(instruction in instruction(), task_id in pubkey()) {
+        let data = instruction.try_to_vec().unwrap();
+        prop_assert_eq!(&AIInfraInstruction::try_from_slice(&data).unwrap(), &instruction);
+
+        // The compact encoding carries everything but the task id, which
+        // comes from the accounts
+        let compact = instruction.pack();
+        let unpacked = AIInfraInstruction::unpack(&compact, |_| Some(task_id)).unwrap();
+        prop_assert_eq!(unpacked.pack(), compact);
+    }
+
+    #[test]
+    fn arbitrary_data_fails_to_decode_cleanly(data in vec(any::<u8>(), 0..400)) {
+        let _ = AIInfraInstruction::unpack(&data, |_| Some(Pubkey::default()));
+        let _ = AIInfraInstruction::unpack(&data, |_| None);
+    }
+
+    // Whatever the data and however the accounts are ordered, the program
+    // returns a result rather than aborting
+    #[test]
+    fn process_instruction_never_panics(data in instruction_data(), picks in account_picks()) {
+        set_sysvar_stubs();
+        let program_id = Pubkey::new_unique();
+        let pool = fixture_accounts(&program_id);
+        let mut accounts: Vec<_> = picks.iter().map(|&pick| pool[pick].clone()).collect();
+        let infos = account_infos(&mut accounts);
+        let _ = process_instruction(&program_id, &infos, &data);
+    }
+
+    #[test]
+    fn final_statuses_are_never_left(statuses in vec(task_status(), 1..24)) {
+        let mut task = ComputeTask { status: statuses[0], ..ComputeTask::default() };
+        for &next in &statuses[1..] {
+            let was_final = task.status.is_final();
+            let before = task.status;
+            if task.transition(next).is_err() || was_final {
+                prop_assert_eq!(task.status, before);
+            }
+            prop_assert!(!was_final || !before.can_transition_to(next));
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// Round-trips the state types through JSON and pins the field names
+// off-chain schemas rely on. Built with the serde feature:
+// cargo test --features serde
+#![cfg(feature = "serde")]
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use sonic_ai_infra::{
+    AIAgent, AccountType, AgentAllowlist, AgentCapabilities, AgentMetadata, Allowance, BatchTask,
+    Bid, ComputeRequirements, ComputeTask, Config, ConfigParams, FailureReason, PendingPayment,
+    RecurringTask, RedundantTask, Registry, ReplicaResult, RequirementBounds, Requester,
+    ResourceLimits, TaskPage, TaskStatus, TaskTemplate, Verifier,
+};
+
+// Fails unless every object key in `value` is snake_case
+fn assert_snake_case(value: &Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                // Enum variants, as externally tagged keys, keep their names
+                let variant = name.starts_with(|c: char| c.is_ascii_uppercase());
+                let snake_case = name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+                assert!(variant || snake_case, "field {name} isn't snake_case");
+                assert_snake_case(field);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(assert_snake_case),
+        _ => {}
+    }
+}
+
+// Writes `value` as JSON, reads it back and checks it writes the same JSON
+// again, returning it
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> Value {
+    let json = serde_json::to_string(value).unwrap();
+    let read: T = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    let value: Value = serde_json::from_str(&json).unwrap();
+    assert_snake_case(&value);
+    value
+}
+
+// Fails unless `value` has exactly the fields `names`, in any order
+fn assert_fields(value: &Value, names: &[&str]) {
+    let mut fields: Vec<_> = value.as_object().unwrap().keys().map(String::as_str).collect();
+    let mut names = names.to_vec();
+    fields.sort_unstable();
+    names.sort_unstable();
+    assert_eq!(fields, names);
+}
+
+fn requirements() -> ComputeRequirements {
+    ComputeRequirements {
+        cpu_units: 4,
+        memory_mb: 512,
+        storage_mb: 10,
+        max_time_seconds: 60,
+        min_reputation: 100,
+        min_stake: 1_000,
+        single_step: true,
+        gpu_units: 1,
+        network_mbps: 100,
+    }
+}
+
+#[test]
+fn agents_and_tasks_round_trip() {
+    let agent = AIAgent {
+        account_type: AccountType::Agent,
+        owner: Pubkey::new_unique(),
+        operator: Some(Pubkey::new_unique()),
+        compute_credits: 5_000,
+        capabilities: AgentCapabilities { cpu_units: 8, ..AgentCapabilities::default() },
+        pending_releases: [PendingPayment { amount: 7, release_at: 9 }; 8],
+        accepted_tags: [0xf0; 32],
+        ..AIAgent::default()
+    };
+    let value = round_trip(&agent);
+    assert_eq!(serde_json::from_value::<AIAgent>(value).unwrap(), agent);
+
+    let task = ComputeTask {
+        account_type: AccountType::Task,
+        agent: Pubkey::new_unique(),
+        requester: Pubkey::new_unique(),
+        requirements: requirements(),
+        status: TaskStatus::Failed,
+        result_hash: [0xab; 32],
+        commitment: Some([1; 32]),
+        parent: Some(Pubkey::new_unique()),
+        failure_reason: Some(FailureReason::Other(300)),
+        priority: 3,
+        tags: [1, 2, 0, 0, 0, 0, 0, 0],
+        result_data: vec![1, 2, 3],
+        ..ComputeTask::default()
+    };
+    let value = round_trip(&task);
+    assert_eq!(serde_json::from_value::<ComputeTask>(value).unwrap(), task);
+
+    for reason in [FailureReason::OutOfTime, FailureReason::Expired, FailureReason::Other(1)] {
+        round_trip(&reason);
+    }
+}
+
+#[test]
+fn every_other_state_type_round_trips() {
+    let key = Pubkey::new_unique;
+    let limits = ResourceLimits { cpu_units: 1, max_time_seconds: 30, ..ResourceLimits::default() };
+    round_trip(&Config {
+        account_type: AccountType::Config,
+        admin: key(),
+        treasury: key(),
+        arbitrator: key(),
+        requirement_bounds: RequirementBounds { min: limits, max: limits },
+        ..Config::default()
+    });
+    round_trip(&ConfigParams { treasury: key(), arbitrator: key(), ..ConfigParams::default() });
+    round_trip(&Bid { task: key(), agent: key(), amount: 10, ..Bid::default() });
+    round_trip(&Requester { owner: key(), referrer: Some(key()), ..Requester::default() });
+    round_trip(&AgentAllowlist {
+        agent: key(),
+        requesters: vec![key(), key()],
+        ..AgentAllowlist::default()
+    });
+    round_trip(&Allowance { agent: key(), spender: key(), amount: 5, ..Allowance::default() });
+    round_trip(&Verifier { authority: key(), ..Verifier::default() });
+    round_trip(&Registry { total_agents: 2, ..Registry::default() });
+    round_trip(&TaskPage { agent: key(), tasks: vec![key()], ..TaskPage::default() });
+    round_trip(&BatchTask {
+        agent: key(),
+        requester: key(),
+        merkle_root: Some([9; 32]),
+        claimed: vec![1, 0],
+        ..BatchTask::default()
+    });
+    round_trip(&TaskTemplate {
+        requester: key(),
+        requirements: requirements(),
+        ..TaskTemplate::default()
+    });
+    round_trip(&RecurringTask { requester: key(), agent: key(), ..RecurringTask::default() });
+    round_trip(&RedundantTask {
+        requester: key(),
+        submissions: [ReplicaResult { agent: key(), result_hash: [2; 32] }; 5],
+        agreed_hash: Some([2; 32]),
+        ..RedundantTask::default()
+    });
+    round_trip(&AgentMetadata {
+        agent: key(),
+        name: "agent".to_string(),
+        ..AgentMetadata::default()
+    });
+    for status in [TaskStatus::Pending, TaskStatus::Split] {
+        round_trip(&status);
+    }
+}
+
+#[test]
+fn keys_are_base58_and_hashes_hex() {
+    let owner = Pubkey::new_unique();
+    let value = round_trip(&AIAgent { owner, accepted_tags: [0xab; 32], ..AIAgent::default() });
+    assert_eq!(value["owner"], json!(owner.to_string()));
+    assert_eq!(value["operator"], Value::Null);
+    assert_eq!(value["accepted_tags"], json!("ab".repeat(32)));
+
+    let mut value = value;
+    value["owner"] = json!("not a pubkey");
+    assert!(serde_json::from_value::<AIAgent>(value.clone()).is_err());
+    value["owner"] = json!(owner.to_string());
+    value["accepted_tags"] = json!("ab");
+    assert!(serde_json::from_value::<AIAgent>(value).is_err());
+}
+
+// Downstream schemas key on these names, so renaming a field breaks them.
+// New fields only ever extend these lists.
+#[test]
+fn field_names_are_stable() {
+    let agent = round_trip(&AIAgent::default());
+    assert_fields(
+        &agent,
+        &[
+            "account_type",
+            "version",
+            "owner",
+            "compute_credits",
+            "reputation_score",
+            "tasks_completed",
+            "is_active",
+            "bump",
+            "tasks_created",
+            "open_tasks",
+            "registrar",
+            "last_heartbeat",
+            "staked_lamports",
+            "unstake_amount",
+            "unstake_requested_at",
+            "operator",
+            "allowed_requesters",
+            "active_tasks",
+            "max_concurrent_tasks",
+            "capabilities",
+            "tasks_failed",
+            "tasks_cancelled",
+            "total_completion_seconds",
+            "attestation_key",
+            "batches_created",
+            "tips_received",
+            "pending_payments",
+            "pending_releases",
+            "pending_head",
+            "pending_count",
+            "operation_nonce",
+            "accepted_tags",
+            "last_task_slot",
+            "tasks_in_window",
+            "withdrawal_policy",
+            "pending_withdrawal_policy",
+            "pending_policy_at",
+            "withdrawn_today",
+            "withdrawal_day",
+            "last_withdrawal_at",
+            "registered_at_epoch",
+            "grant_claimed",
+            "multisig_owner",
+            "owner_nft_mint",
+            "prefer_native",
+        ],
+    );
+    let task = round_trip(&ComputeTask::default());
+    assert_fields(
+        &task,
+        &[
+            "account_type",
+            "version",
+            "agent",
+            "requirements",
+            "status",
+            "result_hash",
+            "payment_amount",
+            "index",
+            "bump",
+            "failure_code",
+            "requester",
+            "vault_bump",
+            "payment_mint",
+            "open_listing",
+            "started_at",
+            "deadline",
+            "completed_at",
+            "payment_pending",
+            "commitment",
+            "verified",
+            "created_at",
+            "expected_result_hash",
+            "tip_reputation",
+            "parent",
+            "shard_index",
+            "shard_count",
+            "reissued",
+            "failure_reason",
+            "priority",
+            "tags",
+            "failed_at",
+            "insurance_claimed",
+            "token_program",
+            "payment_usd_cents",
+            "description_hash",
+            "held_refund",
+            "result_data",
+        ],
+    );
+    assert_fields(
+        &task["requirements"],
+        &[
+            "cpu_units",
+            "memory_mb",
+            "storage_mb",
+            "max_time_seconds",
+            "min_reputation",
+            "min_stake",
+            "single_step",
+            "gpu_units",
+            "network_mbps",
+        ],
+    );
+}
+
+// A REST body naming only some requirements can't be read: every field is
+// required, so a client missing one finds out rather than getting zero
+#[test]
+fn requirements_read_from_rest_bodies() {
+    let body = json!({
+        "cpu_units": 4,
+        "memory_mb": 512,
+        "storage_mb": 10,
+        "max_time_seconds": 60,
+        "min_reputation": 100,
+        "min_stake": 1000,
+        "single_step": true,
+        "gpu_units": 1,
+        "network_mbps": 100,
+    });
+    let read: ComputeRequirements = serde_json::from_value(body.clone()).unwrap();
+    assert_eq!(read, requirements());
+    let mut partial = body;
+    partial.as_object_mut().unwrap().remove("gpu_units");
+    assert!(serde_json::from_value::<ComputeRequirements>(partial).is_err());
+}